@@ -0,0 +1,425 @@
+//! Typed async client for the `wot-oracle` HTTP API.
+//!
+//! ```no_run
+//! use wot_oracle_client::{Client, DistanceRequest};
+//!
+//! # async fn run() -> Result<(), wot_oracle_client::Error> {
+//! let client = Client::new("https://wot.example.com")?;
+//!
+//! let distance = client
+//!     .distance(&DistanceRequest::new("82341f88...", "3bf0c63f...").max_hops(4))
+//!     .await?;
+//! println!("{:?} hops", distance.hops);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requests that fail with a connection error, a timeout, 429 or a 502/503/504 are retried with
+//! exponential backoff (see [`RetryPolicy`]). With the `nip98` feature, requests can be signed
+//! with NIP-98 HTTP auth via [`ClientBuilder::nip98_keys`].
+
+#[cfg(feature = "nip98")]
+mod nip98;
+mod types;
+
+pub use types::*;
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Method, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The oracle answered with an error; `code` is the API error code (e.g. `INVALID_PUBKEY`)
+    #[error("{status} {code}: {message}")]
+    Api {
+        status: u16,
+        code: String,
+        message: String,
+    },
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Invalid base URL: {0}")]
+    InvalidUrl(String),
+    #[cfg(feature = "nip98")]
+    #[error("NIP-98 signing failed: {0}")]
+    Signing(String),
+}
+
+impl Error {
+    /// API error code, if the oracle returned one
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Error::Api { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+}
+
+/// Retry schedule for transient failures. The delay doubles per attempt up to `max_delay`.
+/// A `Retry-After` header from the oracle takes precedence; if it asks for longer than
+/// `max_delay`, the error is returned instead of waiting.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        retry_after.unwrap_or_else(|| {
+            self.base_delay
+                .saturating_mul(2u32.saturating_pow(retry))
+                .min(self.max_delay)
+        })
+    }
+}
+
+pub struct ClientBuilder {
+    base_url: String,
+    timeout: Duration,
+    retry: RetryPolicy,
+    #[cfg(feature = "nip98")]
+    keys: Option<nostr_sdk::prelude::Keys>,
+}
+
+impl ClientBuilder {
+    /// Per-request timeout, covering each attempt separately (default 30s)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sign every request with a NIP-98 `Authorization: Nostr ...` header
+    #[cfg(feature = "nip98")]
+    pub fn nip98_keys(mut self, keys: nostr_sdk::prelude::Keys) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        // Endpoint paths are joined onto the base, which must end in '/' to keep its own path
+        let mut base_url = self.base_url;
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let base_url = Url::parse(&base_url).map_err(|e| Error::InvalidUrl(e.to_string()))?;
+        if base_url.cannot_be_a_base() || !matches!(base_url.scheme(), "http" | "https") {
+            return Err(Error::InvalidUrl(base_url.to_string()));
+        }
+
+        let http = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .user_agent(concat!("wot-oracle-client/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Client {
+            http,
+            base_url,
+            retry: self.retry,
+            #[cfg(feature = "nip98")]
+            keys: self.keys,
+        })
+    }
+}
+
+/// Client for one oracle instance. Cheap to clone; clones share a connection pool.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    retry: RetryPolicy,
+    #[cfg(feature = "nip98")]
+    keys: Option<nostr_sdk::prelude::Keys>,
+}
+
+impl Client {
+    /// Client with the default timeout and retry policy
+    pub fn new(base_url: impl Into<String>) -> Result<Self, Error> {
+        Self::builder(base_url).build()
+    }
+
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            timeout: DEFAULT_TIMEOUT,
+            retry: RetryPolicy::default(),
+            #[cfg(feature = "nip98")]
+            keys: None,
+        }
+    }
+
+    /// `GET /distance`
+    pub async fn distance(&self, request: &DistanceRequest) -> Result<Distance, Error> {
+        self.get("distance", &request.query()).await
+    }
+
+    /// `POST /distance/batch`
+    pub async fn batch_distance(&self, request: &BatchDistanceRequest) -> Result<BatchDistance, Error> {
+        let body = serde_json::to_vec(request).expect("batch request serializes");
+        self.send(Method::POST, "distance/batch", &[], Some(body)).await
+    }
+
+    /// `POST /distance/multi-source`
+    pub async fn multi_source_distance(&self, request: &MultiSourceRequest) -> Result<MultiSourceDistance, Error> {
+        let body = serde_json::to_vec(request).expect("multi-source request serializes");
+        self.send(Method::POST, "distance/multi-source", &[], Some(body)).await
+    }
+
+    /// `POST /verify/batch`: one [`EdgeCheck`] per pair, in order
+    pub async fn verify_batch(&self, pairs: &[VerifyPair]) -> Result<Vec<EdgeCheck>, Error> {
+        #[derive(serde::Serialize)]
+        struct Request<'a> {
+            pairs: &'a [VerifyPair],
+        }
+        #[derive(serde::Deserialize)]
+        struct Response {
+            results: Vec<EdgeCheck>,
+        }
+        let body = serde_json::to_vec(&Request { pairs }).expect("verify request serializes");
+        let response: Response = self.send(Method::POST, "verify/batch", &[], Some(body)).await?;
+        Ok(response.results)
+    }
+
+    /// `GET /path`; `max_hops` falls back to the server default when None
+    pub async fn path(&self, from: &str, to: &str, max_hops: Option<u8>) -> Result<Path, Error> {
+        let mut query = vec![("from", from.to_string()), ("to", to.to_string())];
+        if let Some(max_hops) = max_hops {
+            query.push(("max_hops", max_hops.to_string()));
+        }
+        self.get("path", &query).await
+    }
+
+    /// `GET /follows`
+    pub async fn follows(&self, pubkey: &str) -> Result<Follows, Error> {
+        self.get("follows", &[("pubkey", pubkey.to_string())]).await
+    }
+
+    /// `GET /node/:pubkey`; a pubkey not in the graph is an [`Error::Api`] with code `NODE_NOT_FOUND`
+    pub async fn node(&self, pubkey: &str) -> Result<Node, Error> {
+        self.get(&format!("node/{}", pubkey), &[]).await
+    }
+
+    /// `GET /history/followers/:pubkey`; `granularity` is `hour`, `day` or `week` (server default `day`)
+    pub async fn follower_history(&self, pubkey: &str, granularity: Option<&str>) -> Result<FollowerHistory, Error> {
+        let query: Vec<(&str, String)> = granularity.map(|g| ("granularity", g.to_string())).into_iter().collect();
+        self.get(&format!("history/followers/{}", pubkey), &query).await
+    }
+
+    /// `GET /common-follows`
+    pub async fn common_follows(&self, from: &str, to: &str) -> Result<CommonFollows, Error> {
+        self.get("common-follows", &[("from", from.to_string()), ("to", to.to_string())])
+            .await
+    }
+
+    /// `GET /stats`
+    pub async fn stats(&self) -> Result<Stats, Error> {
+        self.get("stats", &[]).await
+    }
+
+    /// `GET /health`
+    pub async fn health(&self) -> Result<Health, Error> {
+        self.get("health", &[]).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T, Error> {
+        self.send(Method::GET, path, query, None).await
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<Vec<u8>>,
+    ) -> Result<T, Error> {
+        let mut url = self.base_url.join(path).map_err(|e| Error::InvalidUrl(e.to_string()))?;
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+
+        let mut retry = 0;
+        loop {
+            let mut request = self.http.request(method.clone(), url.clone());
+            if let Some(ref body) = body {
+                request = request.header(CONTENT_TYPE, "application/json").body(body.clone());
+            }
+            #[cfg(feature = "nip98")]
+            if let Some(ref keys) = self.keys {
+                let auth = nip98::authorization(keys, &url, &method, body.as_deref())?;
+                request = request.header(reqwest::header::AUTHORIZATION, auth);
+            }
+
+            let retry_after = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response.json().await?),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = retry_after(response.headers());
+                    let error = api_error(response).await;
+                    let too_long = retry_after.is_some_and(|delay| delay > self.retry.max_delay);
+                    if retry >= self.retry.max_retries || !is_transient(status) || too_long {
+                        return Err(error);
+                    }
+                    retry_after
+                }
+                Err(e) => {
+                    if retry >= self.retry.max_retries || !(e.is_connect() || e.is_timeout()) {
+                        return Err(e.into());
+                    }
+                    None
+                }
+            };
+
+            tokio::time::sleep(self.retry.delay(retry, retry_after)).await;
+            retry += 1;
+        }
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// `Retry-After` in delay-seconds form; the HTTP-date form is not used by the oracle
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let secs = headers.get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+async fn api_error(response: reqwest::Response) -> Error {
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    let (code, message) = match serde_json::from_str::<ErrorBody>(&text) {
+        Ok(body) => (body.code, body.error),
+        // Not from the oracle itself, e.g. a proxy error page
+        Err(_) => ("HTTP_ERROR".to_string(), text),
+    };
+    Error::Api {
+        status: status.as_u16(),
+        code,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderValue, StatusCode as AxumStatus};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}/api", addr)
+    }
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0, None), Duration::from_millis(250));
+        assert_eq!(policy.delay(2, None), Duration::from_secs(1));
+        assert_eq!(policy.delay(10, None), policy.max_delay);
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(2))), Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let router = Router::new().route(
+            "/api/distance",
+            get(move || async move {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    let mut response = AxumStatus::SERVICE_UNAVAILABLE.into_response();
+                    response.headers_mut().insert("retry-after", HeaderValue::from_static("0"));
+                    return response;
+                }
+                Json(serde_json::json!({
+                    "from": A, "to": B, "hops": 2, "path_count": 3, "mutual_follow": false
+                }))
+                .into_response()
+            }),
+        );
+        let client = Client::builder(serve(router).await).retry(fast_retries()).build().unwrap();
+
+        let distance = client.distance(&DistanceRequest::new(A, B)).await.unwrap();
+        assert_eq!(distance.hops, Some(2));
+        assert_eq!(distance.bridges, None);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_api_errors_are_not_retried() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let router = Router::new().route(
+            "/api/follows",
+            get(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let body = serde_json::json!({"error": "Invalid pubkey format", "code": "INVALID_PUBKEY"});
+                (AxumStatus::BAD_REQUEST, Json(body))
+            }),
+        );
+        let client = Client::builder(serve(router).await).retry(fast_retries()).build().unwrap();
+
+        let error = client.follows("nope").await.unwrap_err();
+        assert_eq!(error.code(), Some("INVALID_PUBKEY"));
+        assert!(matches!(error, Error::Api { status: 400, .. }));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_invalid_base_url() {
+        assert!(matches!(Client::new("not a url"), Err(Error::InvalidUrl(_))));
+        assert!(matches!(Client::new("ftp://example.com"), Err(Error::InvalidUrl(_))));
+    }
+}
@@ -0,0 +1,30 @@
+//! NIP-98 HTTP auth: each request carries a signed kind 27235 event bound to its URL and method.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use nostr_sdk::prelude::{EventBuilder, Keys, Kind, Tag};
+use reqwest::{Method, Url};
+use sha2::{Digest, Sha256};
+
+use crate::Error;
+
+const HTTP_AUTH_KIND: u16 = 27235;
+
+/// `Authorization` header value for one request. A fresh event is signed per attempt,
+/// since servers reject auth events more than a minute old.
+pub(crate) fn authorization(keys: &Keys, url: &Url, method: &Method, body: Option<&[u8]>) -> Result<String, Error> {
+    let event = EventBuilder::new(Kind::Custom(HTTP_AUTH_KIND), "", tags(url, method, body)?)
+        .to_event(keys)
+        .map_err(|e| Error::Signing(e.to_string()))?;
+    Ok(format!("Nostr {}", STANDARD.encode(event.as_json())))
+}
+
+fn tags(url: &Url, method: &Method, body: Option<&[u8]>) -> Result<Vec<Tag>, Error> {
+    let mut tags = vec![Tag::parse(&["u", url.as_str()]), Tag::parse(&["method", method.as_str()])];
+    if let Some(body) = body {
+        let payload = format!("{:x}", Sha256::digest(body));
+        tags.push(Tag::parse(&["payload", payload.as_str()]));
+    }
+    tags.into_iter()
+        .collect::<Result<_, _>>()
+        .map_err(|e| Error::Signing(e.to_string()))
+}
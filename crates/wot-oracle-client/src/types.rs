@@ -0,0 +1,450 @@
+//! Request and response bodies of the HTTP API. See `docs/API.md` for field semantics.
+
+use serde::{Deserialize, Serialize};
+
+/// `GET /distance` parameters
+#[derive(Debug, Clone)]
+pub struct DistanceRequest {
+    pub from: String,
+    pub to: String,
+    /// Server default (3) when None
+    pub max_hops: Option<u8>,
+    pub include_bridges: bool,
+    pub bypass_cache: bool,
+    /// Measure hops from `to` back to `from`; the result's `from`/`to` are then swapped
+    pub reverse: bool,
+    /// Keep only the bridges on the most shortest paths
+    pub max_bridges: Option<usize>,
+    /// Don't route through pubkeys with fewer followers; None (or 0) disables the filter
+    pub min_followers: Option<usize>,
+    /// `followers` or `rank`: also report `weighted_path_count`
+    pub path_weighting: Option<String>,
+}
+
+impl DistanceRequest {
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            max_hops: None,
+            include_bridges: false,
+            bypass_cache: false,
+            reverse: false,
+            max_bridges: None,
+            min_followers: None,
+            path_weighting: None,
+        }
+    }
+
+    pub fn max_hops(mut self, max_hops: u8) -> Self {
+        self.max_hops = Some(max_hops);
+        self
+    }
+
+    pub fn include_bridges(mut self, include_bridges: bool) -> Self {
+        self.include_bridges = include_bridges;
+        self
+    }
+
+    pub fn bypass_cache(mut self, bypass_cache: bool) -> Self {
+        self.bypass_cache = bypass_cache;
+        self
+    }
+
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    pub fn max_bridges(mut self, max_bridges: usize) -> Self {
+        self.max_bridges = Some(max_bridges);
+        self
+    }
+
+    pub fn min_followers(mut self, min_followers: usize) -> Self {
+        self.min_followers = Some(min_followers);
+        self
+    }
+
+    pub fn path_weighting(mut self, path_weighting: impl Into<String>) -> Self {
+        self.path_weighting = Some(path_weighting.into());
+        self
+    }
+
+    pub(crate) fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = vec![("from", self.from.clone()), ("to", self.to.clone())];
+        if let Some(max_hops) = self.max_hops {
+            query.push(("max_hops", max_hops.to_string()));
+        }
+        if self.include_bridges {
+            query.push(("include_bridges", "true".to_string()));
+        }
+        if self.bypass_cache {
+            query.push(("bypass_cache", "true".to_string()));
+        }
+        if self.reverse {
+            query.push(("reverse", "true".to_string()));
+        }
+        if let Some(max_bridges) = self.max_bridges {
+            query.push(("max_bridges", max_bridges.to_string()));
+        }
+        if let Some(min_followers) = self.min_followers {
+            query.push(("min_followers", min_followers.to_string()));
+        }
+        if let Some(path_weighting) = &self.path_weighting {
+            query.push(("path_weighting", path_weighting.clone()));
+        }
+        query
+    }
+}
+
+/// `POST /distance/batch` body; the server accepts up to 100 targets
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchDistanceRequest {
+    pub from: String,
+    pub targets: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_hops: Option<u8>,
+    pub include_bridges: bool,
+    pub bypass_cache: bool,
+    pub reverse: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bridges: Option<usize>,
+}
+
+impl BatchDistanceRequest {
+    pub fn new(from: impl Into<String>, targets: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            from: from.into(),
+            targets: targets.into_iter().map(Into::into).collect(),
+            max_hops: None,
+            include_bridges: false,
+            bypass_cache: false,
+            reverse: false,
+            max_bridges: None,
+        }
+    }
+
+    pub fn max_hops(mut self, max_hops: u8) -> Self {
+        self.max_hops = Some(max_hops);
+        self
+    }
+
+    pub fn include_bridges(mut self, include_bridges: bool) -> Self {
+        self.include_bridges = include_bridges;
+        self
+    }
+
+    pub fn bypass_cache(mut self, bypass_cache: bool) -> Self {
+        self.bypass_cache = bypass_cache;
+        self
+    }
+
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    pub fn max_bridges(mut self, max_bridges: usize) -> Self {
+        self.max_bridges = Some(max_bridges);
+        self
+    }
+}
+
+/// `POST /distance/multi-source` body; the server accepts up to 1000 sources
+#[derive(Debug, Clone, Serialize)]
+pub struct MultiSourceRequest {
+    pub sources: Vec<String>,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_hops: Option<u8>,
+}
+
+impl MultiSourceRequest {
+    pub fn new(sources: impl IntoIterator<Item = impl Into<String>>, target: impl Into<String>) -> Self {
+        Self {
+            sources: sources.into_iter().map(Into::into).collect(),
+            target: target.into(),
+            max_hops: None,
+        }
+    }
+
+    pub fn max_hops(mut self, max_hops: u8) -> Self {
+        self.max_hops = Some(max_hops);
+        self
+    }
+}
+
+/// Follow distance between two pubkeys
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Distance {
+    pub from: String,
+    pub to: String,
+    /// None when `to` is not reachable within `max_hops`
+    pub hops: Option<u32>,
+    pub path_count: u64,
+    pub mutual_follow: bool,
+    /// Only present when requested with `include_bridges`; most shortest paths first
+    #[serde(default)]
+    pub bridges: Option<Vec<String>>,
+    /// Shortest paths through each of `bridges`, in the same order
+    #[serde(default)]
+    pub bridge_path_counts: Option<Vec<u64>>,
+    /// Set when the oracle sampled a node's neighbors (`BFS_EXPANSION_LIMIT`), so `hops` may be
+    /// longer and `path_count` lower than in the full graph
+    #[serde(default)]
+    pub truncated: bool,
+    /// Unix time the oracle computed the result; cached answers keep their original time
+    #[serde(default)]
+    pub computed_at: u64,
+    /// Graph epoch the search ran at
+    #[serde(default)]
+    pub graph_epoch: u64,
+    /// Served from the oracle's cache or a precomputed anchor table
+    #[serde(default)]
+    pub cached: bool,
+    /// Only present when requested with `path_weighting`
+    #[serde(default)]
+    pub weighted_path_count: Option<f64>,
+    /// Only present when the oracle signs responses
+    #[serde(default)]
+    pub attestation: Option<Attestation>,
+}
+
+/// Oracle signature over a distance result
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Attestation {
+    pub pubkey: String,
+    pub epoch: u64,
+    pub created_at: u64,
+    pub result_hash: String,
+    pub sig: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchDistance {
+    pub from: String,
+    /// Graph epoch every result was computed at (0 from servers predating it)
+    #[serde(default)]
+    pub epoch: u64,
+    /// One result per target, in request order
+    pub results: Vec<Distance>,
+}
+
+/// A pubkey pair for `POST /verify/batch`; the server accepts up to 5000 pairs of hex pubkeys
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyPair {
+    pub from: String,
+    pub to: String,
+}
+
+impl VerifyPair {
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self { from: from.into(), to: to.into() }
+    }
+}
+
+/// Direct follow edges between a pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct EdgeCheck {
+    /// `from` follows `to`
+    pub follows: bool,
+    /// `to` follows `from`
+    pub followed_by: bool,
+    pub mutual: bool,
+}
+
+/// Closest source to a target
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MultiSourceDistance {
+    pub target: String,
+    pub hops: Option<u32>,
+    /// The first listed of the closest sources, or None if none is in range
+    pub source: Option<String>,
+    /// Graph epoch the search ran at (0 from servers predating it)
+    #[serde(default)]
+    pub epoch: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Path {
+    pub from: String,
+    pub to: String,
+    /// Intermediate pubkeys (empty for a direct follow), or None if unreachable
+    pub path: Option<Vec<String>>,
+    /// Set when the oracle sampled a node's neighbors (`BFS_EXPANSION_LIMIT`)
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Follows {
+    pub pubkey: String,
+    pub follows: Vec<String>,
+}
+
+/// What the oracle knows about one pubkey
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Node {
+    pub pubkey: String,
+    pub kind3_event_id: Option<String>,
+    pub kind3_created_at: Option<i64>,
+    /// Relays that delivered the current contact list since the oracle started; None if it
+    /// hasn't received it since (or predates corroboration counts)
+    #[serde(default)]
+    pub confidence: Option<usize>,
+    pub follow_count: usize,
+    pub follower_count: usize,
+    /// Absent from oracles predating follow-back tracking
+    #[serde(default)]
+    pub mutual_count: usize,
+    /// None without follows (or from oracles predating follow-back tracking)
+    #[serde(default)]
+    pub follow_back_ratio: Option<f64>,
+    /// Follows per follower; None without followers
+    #[serde(default)]
+    pub follow_ratio: Option<f64>,
+    /// Unix times; None until the oracle has persisted the pubkey
+    pub first_seen: Option<i64>,
+    pub last_updated: Option<i64>,
+    /// None until the oracle's next background refresh after the pubkey joined the graph
+    pub in_largest_component: Option<bool>,
+    /// Absent from oracles predating report ingestion
+    #[serde(default)]
+    pub report_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FollowerHistory {
+    pub pubkey: String,
+    pub granularity: String,
+    /// Empty if the oracle does not track this pubkey
+    pub points: Vec<FollowerHistoryPoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FollowerHistoryPoint {
+    /// Bucket start, Unix time
+    pub timestamp: i64,
+    pub follower_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CommonFollows {
+    pub from: String,
+    pub to: String,
+    pub common_follows: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Stats {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub nodes_with_follows: usize,
+    /// Absent from oracles predating spam limits
+    #[serde(default)]
+    pub quarantined_nodes: usize,
+    #[serde(default)]
+    pub rejected_lists: u64,
+    pub cache: CacheStats,
+    pub locks: LockStats,
+    /// Per adjacency lock shard, in shard order; absent from oracles predating sharded adjacency
+    #[serde(default)]
+    pub lock_shards: Vec<LockStats>,
+    /// Absent unless the oracle runs its DVM
+    #[serde(default)]
+    pub dvm: Option<DvmStats>,
+    /// Absent from oracles predating ingestion pause
+    #[serde(default)]
+    pub ingestion: IngestionStatus,
+    /// Absent from oracles predating persistence metrics
+    #[serde(default)]
+    pub persistence: PersistenceStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CacheStats {
+    pub size: usize,
+    pub capacity: usize,
+    /// Absent from oracles predating memory-budgeted caches
+    #[serde(default)]
+    pub bytes: u64,
+    #[serde(default)]
+    pub max_bytes: u64,
+    pub ttl_secs: u64,
+    /// Absent from oracles predating hot-pair refresh
+    #[serde(default)]
+    pub hot_pairs: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LockStats {
+    pub write_lock_count: u64,
+    pub write_lock_avg_us: u64,
+    pub write_lock_max_us: u64,
+    pub read_lock_count: u64,
+    pub read_lock_avg_us: u64,
+    pub read_lock_max_us: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct PersistenceStats {
+    pub queue_depth: u64,
+    pub dropped_updates: u64,
+    pub updates_written: u64,
+    pub updates_failed: u64,
+    pub rows_written: u64,
+    pub rows_per_sec: f64,
+    pub flushes: u64,
+    pub last_flush_ms: f64,
+    pub max_flush_ms: f64,
+    pub flush_ms_total: f64,
+    /// Absent before the first successful flush
+    #[serde(default)]
+    pub last_flush_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DvmStats {
+    pub connected_relays: usize,
+    pub relays: Vec<DvmRelayStatus>,
+    pub requests_processed: u64,
+    pub requests_failed: u64,
+    pub requests_rejected: u64,
+    pub error_responses: u64,
+    pub resubscriptions: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DvmRelayStatus {
+    pub url: String,
+    pub status: String,
+    pub connected: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Health {
+    /// "healthy", or "maintenance" while ingestion is paused
+    pub status: String,
+    pub version: String,
+    /// Absent from oracles predating ingestion pause
+    #[serde(default)]
+    pub ingestion: IngestionStatus,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct IngestionStatus {
+    pub paused: bool,
+    /// Unix time ingestion was paused
+    #[serde(default)]
+    pub paused_at: Option<i64>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Error body returned by the API with every non-2xx status
+#[derive(Debug, Deserialize)]
+pub(crate) struct ErrorBody {
+    pub error: String,
+    pub code: String,
+}
@@ -0,0 +1,721 @@
+use moka::sync::Cache;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::graph::bfs::{compute_distance, DistanceQuery, DistanceResult};
+use crate::graph::{NodeId, WotGraph};
+
+// Default values for cache configuration (used by with_defaults())
+const DEFAULT_CACHE_SIZE: usize = 10000;
+const DEFAULT_TTL_SECS: u64 = 300; // 5 minutes
+/// Approximate per-entry bookkeeping inside moka (timer and LRU nodes, hash table slot), counted
+/// toward a [`QueryCache::with_max_bytes`] budget along with the entry itself
+const ENTRY_OVERHEAD_BYTES: usize = 96;
+/// Most pairs kept fresh by [`QueryCache::refresh_hot`]; the least recently queried go first
+const MAX_HOT_PAIRS: u64 = 1000;
+
+/// Compact cache key using node IDs instead of string pubkeys.
+/// 18 bytes vs 178 bytes per key; safe because node IDs are never reused (see [`NodeId`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub from_id: NodeId,
+    pub to_id: NodeId,
+    pub max_hops: u8,
+    pub include_bridges: bool,
+}
+
+impl CacheKey {
+    pub fn new(from_id: NodeId, to_id: NodeId, max_hops: u8, include_bridges: bool) -> Self {
+        Self {
+            from_id,
+            to_id,
+            max_hops,
+            include_bridges,
+        }
+    }
+
+    /// The same query with bridges included or left out
+    fn with_bridges(self, include_bridges: bool) -> Self {
+        Self { include_bridges, ..self }
+    }
+}
+
+/// Compact cached distance using node IDs for bridges.
+/// Resolved to strings only at API boundary.
+#[derive(Debug, Clone)]
+struct CachedDistance {
+    hops: Option<u32>,
+    path_count: u64,
+    mutual_follow: bool,
+    bridge_ids: Option<Vec<NodeId>>, // 8 bytes each vs 88 bytes for strings
+    bridge_path_counts: Option<Vec<u64>>,
+    truncated: bool,
+    computed_at: u64,
+    graph_epoch: u64,
+    // Computed within one graph snapshot, so `graph_epoch` is exact
+    pinned: bool,
+    // For "not found" answers: both endpoints' kind3_created_at when cached, see `is_superseded`
+    endpoints_created_at: Option<(Option<i64>, Option<i64>)>,
+    // Cache hits, shared by the clones `get` hands out
+    hits: Arc<AtomicU32>,
+}
+
+impl CachedDistance {
+    fn from_result(key: &CacheKey, result: &DistanceResult, graph: &WotGraph, pinned: bool) -> Self {
+        let bridge_ids = result.bridges.as_ref().map(|bridges| {
+            bridges
+                .iter()
+                .filter_map(|pubkey| graph.node_id(pubkey))
+                .collect()
+        });
+
+        Self {
+            hops: result.hops,
+            path_count: result.path_count,
+            mutual_follow: result.mutual_follow,
+            bridge_ids,
+            bridge_path_counts: result.bridge_path_counts.clone(),
+            truncated: result.truncated,
+            computed_at: result.computed_at,
+            graph_epoch: result.graph_epoch,
+            pinned,
+            endpoints_created_at: result.hops.is_none().then(|| endpoints_created_at(key, graph)),
+            hits: Arc::default(),
+        }
+    }
+
+    /// A cached "not found" whose endpoints have published (or republished) follow lists
+    /// since: a pubkey unknown at the time may be reachable now, so the answer is recomputed
+    /// instead of served for the rest of its TTL
+    fn is_superseded(&self, key: &CacheKey, graph: &WotGraph) -> bool {
+        self.endpoints_created_at.is_some_and(|cached| cached != endpoints_created_at(key, graph))
+    }
+
+    /// Approximate bytes held by this entry and its key, for [`QueryCache::with_max_bytes`]
+    fn weight(&self) -> u32 {
+        use std::mem::size_of;
+        let bridges = self.bridge_ids.as_ref().map_or(0, |ids| ids.capacity() * size_of::<NodeId>())
+            + self.bridge_path_counts.as_ref().map_or(0, |counts| counts.capacity() * size_of::<u64>());
+        // The hit counter's Arc allocation: two reference counts plus the counter
+        let hits = 2 * size_of::<usize>() + size_of::<AtomicU32>();
+        let bytes = ENTRY_OVERHEAD_BYTES + size_of::<CacheKey>() + size_of::<Self>() + hits + bridges;
+        u32::try_from(bytes).unwrap_or(u32::MAX)
+    }
+
+    fn to_result(&self, graph: &WotGraph, from_id: NodeId, to_id: NodeId) -> Option<DistanceResult> {
+        let from = graph.pubkey(from_id)?;
+        let to = graph.pubkey(to_id)?;
+
+        let bridges = self.bridge_ids.as_ref().map(|ids| graph.resolve_pubkeys(ids));
+
+        Some(DistanceResult {
+            from,
+            to,
+            hops: self.hops,
+            path_count: self.path_count,
+            mutual_follow: self.mutual_follow,
+            bridges,
+            bridge_path_counts: self.bridge_path_counts.clone(),
+            truncated: self.truncated,
+            computed_at: self.computed_at,
+            graph_epoch: self.graph_epoch,
+            cached: true,
+        })
+    }
+}
+
+/// Pairs hit often enough to be recomputed when the graph changes, instead of going stale
+/// until their entry expires
+struct HotPairs {
+    min_hits: u32,
+    // Dropped once not queried for a TTL
+    keys: Cache<CacheKey, ()>,
+    // Graph epoch of the last refresh (u64::MAX = never)
+    epoch: AtomicU64,
+}
+
+/// Lock-free concurrent cache with automatic TTL eviction.
+/// Uses moka for high-performance concurrent access.
+pub struct QueryCache {
+    entries: Cache<CacheKey, CachedDistance>,
+    ttl_secs: u64,
+    // Memory budget when entries are weighed by size, None when bounded by entry count
+    max_bytes: Option<u64>,
+    hot: Option<HotPairs>,
+}
+
+impl QueryCache {
+    /// Cache holding up to `max_capacity` entries, whatever their size
+    pub fn new(max_capacity: usize, ttl_secs: u64) -> Self {
+        let entries = Cache::builder()
+            .max_capacity(max_capacity as u64)
+            .time_to_live(Duration::from_secs(ttl_secs))
+            .build();
+
+        Self { entries, ttl_secs, max_bytes: None, hot: None }
+    }
+
+    /// Cache holding entries up to an approximate total of `max_bytes`, so one entry with
+    /// hundreds of bridges takes the room of many plain ones
+    pub fn with_max_bytes(max_bytes: u64, ttl_secs: u64) -> Self {
+        let entries = Cache::builder()
+            .max_capacity(max_bytes)
+            .weigher(|_, cached: &CachedDistance| cached.weight())
+            .time_to_live(Duration::from_secs(ttl_secs))
+            .build();
+
+        Self { entries, ttl_secs, max_bytes: Some(max_bytes), hot: None }
+    }
+
+    /// Track entries hit at least `min_hits` times as hot pairs, for [`refresh_hot`](Self::refresh_hot)
+    /// to keep fresh. 0 leaves tracking off.
+    pub fn with_hot_pairs(mut self, min_hits: u32) -> Self {
+        self.hot = (min_hits > 0).then(|| HotPairs {
+            min_hits,
+            keys: Cache::builder()
+                .max_capacity(MAX_HOT_PAIRS)
+                .time_to_idle(Duration::from_secs(self.ttl_secs))
+                .build(),
+            epoch: AtomicU64::new(u64::MAX),
+        });
+        self
+    }
+
+    pub fn with_defaults() -> Self {
+        Self::new(DEFAULT_CACHE_SIZE, DEFAULT_TTL_SECS)
+    }
+
+    /// Get cached result, resolving node IDs to pubkey strings.
+    /// Lock-free read - no contention with other readers or writers.
+    pub fn get(&self, key: &CacheKey, graph: &WotGraph) -> Option<DistanceResult> {
+        self.lookup(key, graph, None)
+    }
+
+    /// Like [`get`](Self::get), but only a result stored by [`insert_pinned`](Self::insert_pinned)
+    /// at graph epoch `epoch` hits, for answers that must all reflect one snapshot
+    pub fn get_at(&self, key: &CacheKey, graph: &WotGraph, epoch: u64) -> Option<DistanceResult> {
+        self.lookup(key, graph, Some(epoch))
+    }
+
+    /// A pair is cached with or without bridges, never both (see [`store`](Self::store)); a
+    /// bridge-less query is also answered from the bridges-inclusive entry, minus the bridges
+    fn lookup(&self, key: &CacheKey, graph: &WotGraph, epoch: Option<u64>) -> Option<DistanceResult> {
+        if !key.include_bridges {
+            if let Some(mut result) = self.lookup_entry(&key.with_bridges(true), graph, epoch) {
+                result.bridges = None;
+                result.bridge_path_counts = None;
+                return Some(result);
+            }
+        }
+        self.lookup_entry(key, graph, epoch)
+    }
+
+    fn lookup_entry(&self, key: &CacheKey, graph: &WotGraph, epoch: Option<u64>) -> Option<DistanceResult> {
+        let cached = self.entries.get(key)?;
+        if epoch.is_some_and(|epoch| !cached.pinned || cached.graph_epoch != epoch) {
+            return None;
+        }
+        if cached.is_superseded(key, graph) {
+            return None;
+        }
+        if let Some(hot) = &self.hot {
+            if cached.hits.fetch_add(1, Ordering::Relaxed) + 1 >= hot.min_hits {
+                // Also resets the pair's idle timer
+                hot.keys.insert(*key, ());
+            }
+        }
+        cached.to_result(graph, key.from_id, key.to_id)
+    }
+
+    /// Insert result, converting pubkey strings to node IDs for compact storage.
+    /// Lock-free insert - no contention with readers.
+    pub fn insert(&self, key: CacheKey, result: &DistanceResult, graph: &WotGraph) {
+        let cached = CachedDistance::from_result(&key, result, graph, false);
+        self.store(key, cached);
+    }
+
+    /// Insert a result computed within one [`WotGraph::with_snapshot`], so its `graph_epoch`
+    /// is exact and [`get_at`](Self::get_at) may serve it
+    pub fn insert_pinned(&self, key: CacheKey, result: &DistanceResult, graph: &WotGraph) {
+        let cached = CachedDistance::from_result(&key, result, graph, true);
+        self.store(key, cached);
+    }
+
+    /// Cache an entry in place of the pair's entry with bridges included or left out, whichever
+    /// `key` isn't: the newer answer wins, and a bridges-inclusive one serves both kinds of query
+    fn store(&self, key: CacheKey, cached: CachedDistance) {
+        self.entries.invalidate(&key.with_bridges(!key.include_bridges));
+        self.entries.insert(key, cached);
+    }
+
+    /// Whether a lookup of `key` would find an entry, ignoring expiry and freshness checks
+    fn contains(&self, key: &CacheKey) -> bool {
+        self.entries.contains_key(key) || (!key.include_bridges && self.entries.contains_key(&key.with_bridges(true)))
+    }
+
+    /// Recompute every hot pair and replace its entry, if the graph changed since the last
+    /// refresh. Hot pairs stay hot and their entries get a fresh TTL. Returns the number of
+    /// pairs recomputed. CPU-bound: call from a blocking thread.
+    pub fn refresh_hot(&self, graph: &WotGraph) -> usize {
+        let Some(hot) = &self.hot else {
+            return 0;
+        };
+        let epoch = graph.epoch();
+        if hot.epoch.swap(epoch, Ordering::AcqRel) == epoch {
+            return 0;
+        }
+
+        let mut refreshed = 0;
+        for (key, _) in hot.keys.iter() {
+            let Some(result) = compute(&key, graph) else {
+                continue;
+            };
+            let cached = CachedDistance::from_result(&key, &result, graph, false);
+            cached.hits.store(hot.min_hits, Ordering::Relaxed);
+            self.store(*key, cached);
+            refreshed += 1;
+        }
+        refreshed
+    }
+
+    /// Compute and insert whichever of `keys` are not cached, e.g. the most queried pairs after
+    /// they expired or the cache was cleared. Returns the number inserted. CPU-bound: call from
+    /// a blocking thread.
+    pub fn warm(&self, keys: &[CacheKey], graph: &WotGraph) -> usize {
+        let mut warmed = 0;
+        for key in keys.iter().filter(|&key| !self.contains(key)) {
+            if let Some(result) = compute(key, graph) {
+                self.insert(*key, &result, graph);
+                warmed += 1;
+            }
+        }
+        warmed
+    }
+
+    /// Invalidate all entries. Useful when graph is updated.
+    pub fn invalidate_all(&self) {
+        self.entries.invalidate_all();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let (capacity, bytes) = match self.max_bytes {
+            Some(_) => (0, self.entries.weighted_size()),
+            None => (self.entries.policy().max_capacity().unwrap_or(0) as usize, 0),
+        };
+        CacheStats {
+            size: self.entries.entry_count() as usize,
+            capacity,
+            bytes,
+            max_bytes: self.max_bytes.unwrap_or(0),
+            ttl_secs: self.ttl_secs,
+            hot_pairs: self.hot.as_ref().map_or(0, |hot| hot.keys.entry_count() as usize),
+        }
+    }
+}
+
+/// kind3_created_at of a key's `(from, to)` nodes
+fn endpoints_created_at(key: &CacheKey, graph: &WotGraph) -> (Option<i64>, Option<i64>) {
+    (graph.kind3_created_at(key.from_id), graph.kind3_created_at(key.to_id))
+}
+
+/// The distance a key stands for, or None if either node ID is unknown
+fn compute(key: &CacheKey, graph: &WotGraph) -> Option<DistanceResult> {
+    let query = DistanceQuery {
+        from: graph.pubkey(key.from_id)?,
+        to: graph.pubkey(key.to_id)?,
+        max_hops: key.max_hops,
+        include_bridges: key.include_bridges,
+        min_followers: 0,
+    };
+    Some(compute_distance(graph, &query))
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CacheStats {
+    pub size: usize,
+    /// Entry limit; 0 when bounded by `max_bytes` instead
+    pub capacity: usize,
+    /// Approximate bytes held, when bounded by `max_bytes`
+    pub bytes: u64,
+    /// Memory budget (see `with_max_bytes`); 0 when bounded by entry count
+    pub max_bytes: u64,
+    pub ttl_secs: u64,
+    /// Pairs recomputed as the graph changes (see `with_hot_pairs`)
+    pub hot_pairs: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    fn create_test_graph() -> WotGraph {
+        let graph = WotGraph::new();
+        graph.get_or_create_node(&pk("from_pubkey")).unwrap();
+        graph.get_or_create_node(&pk("to_pubkey")).unwrap();
+        graph.get_or_create_node(&pk("bridge1")).unwrap();
+        graph.get_or_create_node(&pk("bridge2")).unwrap();
+        graph
+    }
+
+    fn make_result(from: &str, to: &str, hops: Option<u32>) -> DistanceResult {
+        DistanceResult {
+            hops,
+            path_count: 1,
+            ..DistanceResult::not_found(from.parse().unwrap(), to.parse().unwrap())
+        }
+    }
+
+    #[test]
+    fn test_cache_insert_and_get() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+
+        let from_id = graph.node_id(&pk("from_pubkey")).unwrap();
+        let to_id = graph.node_id(&pk("to_pubkey")).unwrap();
+        let key = CacheKey::new(from_id, to_id, 5, false);
+        let result = make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(2));
+
+        let result = DistanceResult { computed_at: 1_700_000_000, graph_epoch: 7, ..result };
+        assert!(!result.cached);
+
+        cache.insert(key, &result, &graph);
+
+        // A hit reports when and at which epoch it was originally computed
+        let cached = cache.get(&key, &graph).unwrap();
+        assert_eq!(cached.hops, Some(2));
+        assert_eq!((cached.computed_at, cached.graph_epoch, cached.cached), (1_700_000_000, 7, true));
+    }
+
+    #[test]
+    fn test_cache_get_at_epoch() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+        let key = CacheKey::new(0, 1, 5, false);
+        let result = make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(2));
+
+        // Unpinned entries serve plain lookups only
+        cache.insert(key, &result, &graph);
+        assert!(cache.get_at(&key, &graph, 0).is_none());
+
+        cache.insert_pinned(key, &DistanceResult { graph_epoch: 3, ..result }, &graph);
+        assert_eq!(cache.get_at(&key, &graph, 3).unwrap().hops, Some(2));
+        assert!(cache.get_at(&key, &graph, 4).is_none());
+        assert!(cache.get(&key, &graph).is_some());
+    }
+
+    #[test]
+    fn test_not_found_rechecked_after_ingest() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+        let found = CacheKey::new(0, 1, 5, false);
+        let not_found = CacheKey::new(0, 1, 3, false);
+        cache.insert(found, &make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(2)), &graph);
+        cache.insert(not_found, &make_result(&hex("from_pubkey"), &hex("to_pubkey"), None), &graph);
+        assert!(cache.get(&not_found, &graph).is_some());
+
+        // The unknown endpoint publishes a follow list: the negative answer may be wrong now
+        graph.update_follows(&hex("to_pubkey"), &[hex("bridge1")], None, Some(1));
+        assert!(cache.get(&not_found, &graph).is_none());
+        assert!(cache.get(&found, &graph).is_some());
+
+        // Recomputed against the same lists, it is served again
+        cache.insert(not_found, &make_result(&hex("from_pubkey"), &hex("to_pubkey"), None), &graph);
+        assert!(cache.get(&not_found, &graph).is_some());
+    }
+
+    #[test]
+    fn test_cache_keys_survive_reload() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+        let from_id = graph.node_id(&pk("from_pubkey")).unwrap();
+        let to_id = graph.node_id(&pk("to_pubkey")).unwrap();
+        let key = CacheKey::new(from_id, to_id, 5, false);
+        cache.insert(key, &make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(2)), &graph);
+
+        // A reload sees pubkeys in another order; IDs stay put, so the key still means the same pair
+        let fresh = WotGraph::new();
+        fresh.update_follows(&hex("newcomer"), &[hex("to_pubkey"), hex("from_pubkey")], None, Some(1));
+        graph.replace_with(&fresh).unwrap();
+        assert_eq!(graph.node_id(&pk("from_pubkey")), Some(from_id));
+        let cached = cache.get(&key, &graph).unwrap();
+        assert_eq!((cached.from.to_hex(), cached.to.to_hex()), (hex("from_pubkey"), hex("to_pubkey")));
+    }
+
+    #[test]
+    fn test_cache_miss() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+
+        let from_id = graph.node_id(&pk("from_pubkey")).unwrap();
+        let to_id = graph.node_id(&pk("to_pubkey")).unwrap();
+        let key = CacheKey::new(from_id, to_id, 5, false);
+
+        let cached = cache.get(&key, &graph);
+        assert!(cached.is_none());
+    }
+
+    #[test]
+    fn test_cache_different_params() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+
+        let from_id = graph.node_id(&pk("from_pubkey")).unwrap();
+        let to_id = graph.node_id(&pk("to_pubkey")).unwrap();
+
+        let key1 = CacheKey::new(from_id, to_id, 5, false);
+        let key2 = CacheKey::new(from_id, to_id, 3, false);
+        let key3 = CacheKey::new(from_id, to_id, 5, true);
+
+        let result = make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(2));
+        cache.insert(key1, &result, &graph);
+
+        assert!(cache.get(&key1, &graph).is_some());
+        assert!(cache.get(&key2, &graph).is_none()); // Different max_hops
+        assert!(cache.get(&key3, &graph).is_none()); // Different include_bridges
+    }
+
+    #[test]
+    fn test_cache_expiry() {
+        let graph = create_test_graph();
+        let cache = QueryCache::new(100, 0); // 0 second TTL = immediate expiry
+
+        let from_id = graph.node_id(&pk("from_pubkey")).unwrap();
+        let to_id = graph.node_id(&pk("to_pubkey")).unwrap();
+        let key = CacheKey::new(from_id, to_id, 5, false);
+        let result = make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(2));
+
+        cache.insert(key, &result, &graph);
+
+        // Wait for expiry + sync
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        cache.entries.run_pending_tasks(); // Force moka to process expiry
+
+        let cached = cache.get(&key, &graph);
+        assert!(cached.is_none());
+    }
+
+    #[test]
+    fn test_cache_invalidate_all() {
+        let graph = WotGraph::new();
+        let cache = QueryCache::with_defaults();
+
+        let to_id = graph.get_or_create_node(&pk("to_pubkey")).unwrap();
+        let from_ids: Vec<NodeId> = (0..10)
+            .map(|i| graph.get_or_create_node(&pk(&format!("from{}", i))).unwrap())
+            .collect();
+
+        for (i, &from_id) in from_ids.iter().enumerate() {
+            let key = CacheKey::new(from_id, to_id, 5, false);
+            let result = make_result(&hex(&format!("from{}", i)), &hex("to_pubkey"), Some(2));
+            cache.insert(key, &result, &graph);
+        }
+
+        // Sync to ensure entries are counted
+        cache.entries.run_pending_tasks();
+        assert!(cache.stats().size > 0);
+
+        cache.invalidate_all();
+        cache.entries.run_pending_tasks();
+
+        assert_eq!(cache.stats().size, 0);
+    }
+
+    #[test]
+    fn test_cache_max_capacity() {
+        let graph = WotGraph::new();
+        let node_ids: Vec<NodeId> = (0..10)
+            .map(|i| graph.get_or_create_node(&pk(&format!("node{}", i))).unwrap())
+            .collect();
+
+        let cache = QueryCache::new(5, 300); // Max 5 entries
+
+        let to_id = node_ids[9];
+
+        // Insert 10 entries
+        for (i, &from_id) in node_ids.iter().enumerate() {
+            let key = CacheKey::new(from_id, to_id, 5, false);
+            let result = make_result(&hex(&format!("node{}", i)), &hex("node9"), Some(i as u32));
+            cache.insert(key, &result, &graph);
+        }
+
+        // Force moka to process evictions
+        cache.entries.run_pending_tasks();
+
+        // Cache should respect max capacity (moka uses TinyLFU, not strict LRU)
+        // Allow some slack since eviction is probabilistic
+        assert!(cache.stats().size <= 6, "Cache should be near max capacity");
+
+        // At least some entries should be present
+        let mut found = 0;
+        for &from_id in &node_ids {
+            let key = CacheKey::new(from_id, to_id, 5, false);
+            if cache.get(&key, &graph).is_some() {
+                found += 1;
+            }
+        }
+        assert!(found >= 3, "Should have at least 3 entries remaining");
+    }
+
+    #[test]
+    fn test_cache_max_bytes_weighs_bridges() {
+        let graph = WotGraph::new();
+        let bridges: Vec<Pubkey> = (0..500).map(|i| pk(&format!("bridge{}", i))).collect();
+        for bridge in &bridges {
+            graph.get_or_create_node(bridge).unwrap();
+        }
+        let from_id = graph.get_or_create_node(&pk("from_pubkey")).unwrap();
+        let to_id = graph.get_or_create_node(&pk("to_pubkey")).unwrap();
+        let plain = make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(2));
+        let heavy = DistanceResult {
+            bridges: Some(bridges),
+            bridge_path_counts: Some(vec![1; 500]),
+            ..plain.clone()
+        };
+
+        let cache = QueryCache::with_max_bytes(64 * 1024, 300);
+        let plain_size = CachedDistance::from_result(&CacheKey::new(0, 0, 0, false), &plain, &graph, false).weight();
+        let heavy_size = CachedDistance::from_result(&CacheKey::new(0, 0, 0, true), &heavy, &graph, false).weight();
+        assert!(heavy_size > 20 * plain_size);
+
+        // The same budget holds far fewer bridge-heavy entries than plain ones
+        for max_hops in 0..50 {
+            cache.insert(CacheKey::new(from_id, to_id, max_hops, true), &heavy, &graph);
+        }
+        cache.entries.run_pending_tasks();
+        let stats = cache.stats();
+        assert!(stats.size < 15, "{} bridge-heavy entries kept", stats.size);
+        assert!(stats.bytes <= 64 * 1024);
+        assert_eq!((stats.capacity, stats.max_bytes), (0, 64 * 1024));
+
+        let cache = QueryCache::with_max_bytes(64 * 1024, 300);
+        for max_hops in 0..50 {
+            cache.insert(CacheKey::new(from_id, to_id, max_hops, false), &plain, &graph);
+        }
+        cache.entries.run_pending_tasks();
+        assert_eq!(cache.stats().size, 50);
+    }
+
+    #[test]
+    fn test_hot_pairs_refresh() {
+        let pk = |c: char| c.to_string().repeat(64);
+        let graph = WotGraph::new();
+        graph.update_follows(&pk('a'), &[pk('b')], None, Some(1));
+        graph.update_follows(&pk('b'), &[pk('c')], None, Some(1));
+        let cache = QueryCache::with_defaults().with_hot_pairs(2);
+
+        let from_id = graph.node_id(&pk('a').parse().unwrap()).unwrap();
+        let to_id = graph.node_id(&pk('c').parse().unwrap()).unwrap();
+        let key = CacheKey::new(from_id, to_id, 5, false);
+        cache.insert(key, &make_result(&pk('a'), &pk('c'), Some(2)), &graph);
+
+        // One hit: not hot yet, so the stale entry is left alone
+        cache.get(&key, &graph);
+        graph.update_follows(&pk('a'), &[pk('b'), pk('c')], None, Some(2));
+        assert_eq!(cache.refresh_hot(&graph), 0);
+        assert_eq!(cache.get(&key, &graph).unwrap().hops, Some(2));
+
+        graph.update_follows(&pk('a'), &[pk('c')], None, Some(3));
+        assert_eq!(cache.refresh_hot(&graph), 1);
+        assert_eq!(cache.get(&key, &graph).unwrap().hops, Some(1));
+        // Unchanged graph: nothing to redo
+        assert_eq!(cache.refresh_hot(&graph), 0);
+
+        cache.hot.as_ref().unwrap().keys.run_pending_tasks();
+        assert_eq!(cache.stats().hot_pairs, 1);
+        assert_eq!(QueryCache::with_defaults().refresh_hot(&graph), 0);
+    }
+
+    #[test]
+    fn test_warm() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+        let from_id = graph.node_id(&pk("from_pubkey")).unwrap();
+        let to_id = graph.node_id(&pk("to_pubkey")).unwrap();
+        let cached = CacheKey::new(from_id, to_id, 5, false);
+        cache.insert(cached, &make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(2)), &graph);
+
+        let keys = [cached, CacheKey::new(to_id, from_id, 5, false), CacheKey::new(from_id, 99, 5, false)];
+        assert_eq!(cache.warm(&keys, &graph), 1);
+        assert_eq!(cache.get(&keys[1], &graph).unwrap().hops, None);
+        assert_eq!(cache.get(&cached, &graph).unwrap().hops, Some(2));
+    }
+
+    #[test]
+    fn test_cache_with_bridges() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+
+        let from_id = graph.node_id(&pk("from_pubkey")).unwrap();
+        let to_id = graph.node_id(&pk("to_pubkey")).unwrap();
+        let key = CacheKey::new(from_id, to_id, 5, true);
+
+        let result = DistanceResult {
+            hops: Some(2),
+            path_count: 2,
+            bridges: Some(vec![pk("bridge1"), pk("bridge2")]),
+            bridge_path_counts: Some(vec![1, 1]),
+            ..make_result(&hex("from_pubkey"), &hex("to_pubkey"), None)
+        };
+
+        cache.insert(key, &result, &graph);
+
+        let cached = cache.get(&key, &graph).unwrap();
+        assert_eq!(cached.hops, Some(2));
+        assert_eq!(cached.path_count, 2);
+
+        let bridges = cached.bridges.unwrap();
+        assert_eq!(bridges.len(), 2);
+        assert!(bridges.contains(&pk("bridge1")));
+        assert!(bridges.contains(&pk("bridge2")));
+        assert_eq!(cached.bridge_path_counts, Some(vec![1, 1]));
+    }
+
+    #[test]
+    fn test_bridgeless_view_of_bridges_entry() {
+        let graph = create_test_graph();
+        let cache = QueryCache::with_defaults();
+        let with_bridges = CacheKey::new(0, 1, 5, true);
+        let without = with_bridges.with_bridges(false);
+        let result = DistanceResult {
+            hops: Some(2),
+            path_count: 2,
+            bridges: Some(vec![pk("bridge1")]),
+            bridge_path_counts: Some(vec![2]),
+            ..make_result(&hex("from_pubkey"), &hex("to_pubkey"), None)
+        };
+
+        // A bridges-inclusive entry (as the DVM stores) answers bridge-less queries too
+        cache.insert(with_bridges, &result, &graph);
+        let view = cache.get(&without, &graph).unwrap();
+        assert_eq!((view.hops, view.path_count), (Some(2), 2));
+        assert!(view.bridges.is_none() && view.bridge_path_counts.is_none());
+        assert_eq!(cache.get(&with_bridges, &graph).unwrap().bridges.unwrap().len(), 1);
+        assert_eq!(cache.warm(&[without], &graph), 0);
+
+        // A newer bridge-less answer replaces it, and can't serve bridge queries
+        cache.insert(without, &make_result(&hex("from_pubkey"), &hex("to_pubkey"), Some(3)), &graph);
+        assert!(cache.get(&with_bridges, &graph).is_none());
+        assert_eq!(cache.get(&without, &graph).unwrap().hops, Some(3));
+
+        // And a bridges-inclusive one replaces that in turn
+        cache.insert(with_bridges, &result, &graph);
+        assert_eq!(cache.get(&without, &graph).unwrap().hops, Some(2));
+        cache.entries.run_pending_tasks();
+        assert_eq!(cache.stats().size, 1);
+    }
+}
@@ -0,0 +1,352 @@
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use smallvec::SmallVec;
+use std::cell::OnceCell;
+use std::ops::Index;
+use std::time::Instant;
+
+use super::metrics::{LockMetrics, LockMetricsSnapshot, LockTimer};
+use super::store::NodeId;
+
+/// Neighbors stored inline before spilling to the heap; with the `union` layout a list of up
+/// to this many IDs is the same 24 bytes as an empty `Vec`
+pub const ADJACENCY_INLINE: usize = 2;
+
+/// One node's sorted follow or follower list. Most nodes have only a handful of edges, so
+/// those lists live inline in the outer vector instead of in a heap allocation of their own.
+pub type Adjacency = SmallVec<[NodeId; ADJACENCY_INLINE]>;
+
+/// Adjacency lock shards; node `id`'s lists live in shard `id % ADJACENCY_SHARDS`
+pub const ADJACENCY_SHARDS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Side {
+    Follows,
+    Followers,
+}
+
+/// Shard `id` lives in, and its slot there
+fn locate(id: usize) -> (usize, usize) {
+    (id % ADJACENCY_SHARDS, id / ADJACENCY_SHARDS)
+}
+
+#[derive(Default)]
+struct Shard {
+    follows: Vec<Adjacency>,
+    followers: Vec<Adjacency>,
+}
+
+impl Shard {
+    /// Slots are created on first write, so a slot past the end is an empty list
+    fn list(&self, side: Side, slot: usize) -> &[NodeId] {
+        let lists = match side {
+            Side::Follows => &self.follows,
+            Side::Followers => &self.followers,
+        };
+        lists.get(slot).map_or(&[], |list| list.as_slice())
+    }
+
+    fn list_mut(&mut self, side: Side, slot: usize) -> &mut Adjacency {
+        let lists = match side {
+            Side::Follows => &mut self.follows,
+            Side::Followers => &mut self.followers,
+        };
+        if lists.len() <= slot {
+            lists.resize(slot + 1, Adjacency::new());
+        }
+        &mut lists[slot]
+    }
+}
+
+/// Follow and follower lists split across [`ADJACENCY_SHARDS`] locks by node ID.
+///
+/// Readers take a [`Neighbors`] view, which read-locks each shard the first time it is touched
+/// and holds it until the view is dropped. A writer locks only the shard it changes; if a view
+/// or point read holds it, the writer takes `drain` exclusively to wait out the views in flight
+/// (new ones queue behind it), so a write is never starved and never waits longer than it did
+/// behind the old single lock. Only writers holding `drain` ever wait for a shard lock while
+/// views exist, which rules out lock-order cycles between lazily locking views.
+pub(crate) struct ShardedAdjacency {
+    shards: Box<[RwLock<Shard>]>,
+    metrics: Box<[LockMetrics]>,
+    // Held shared for a view's lifetime; exclusively to wait them all out
+    drain: RwLock<()>,
+}
+
+impl ShardedAdjacency {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..ADJACENCY_SHARDS).map(|_| RwLock::default()).collect(),
+            metrics: (0..ADJACENCY_SHARDS).map(|_| LockMetrics::new()).collect(),
+            drain: RwLock::new(()),
+        }
+    }
+
+    /// Views of both sides over the first `len` node IDs; see [`WotGraph::with_adjacency`](super::WotGraph::with_adjacency)
+    pub fn with_views<R>(&self, len: usize, f: impl for<'v, 'a> FnOnce(&Neighbors<'v, 'a>, &Neighbors<'v, 'a>) -> R) -> R {
+        self.views(len, false, f)
+    }
+
+    /// Like [`with_views`](Self::with_views), but every shard is read-locked before `f` runs,
+    /// so no write lands anywhere until it returns; see [`WotGraph::with_snapshot`](super::WotGraph::with_snapshot)
+    pub fn with_pinned_views<R>(&self, len: usize, f: impl for<'v, 'a> FnOnce(&Neighbors<'v, 'a>, &Neighbors<'v, 'a>) -> R) -> R {
+        self.views(len, true, f)
+    }
+
+    fn views<R>(&self, len: usize, pinned: bool, f: impl for<'v, 'a> FnOnce(&Neighbors<'v, 'a>, &Neighbors<'v, 'a>) -> R) -> R {
+        let _drain = self.drain.read();
+        let view = View {
+            adjacency: self,
+            len,
+            shards: std::array::from_fn(|shard| {
+                if pinned {
+                    OnceCell::from(self.shards[shard].read())
+                } else {
+                    OnceCell::new()
+                }
+            }),
+            start: Instant::now(),
+        };
+        let follows = Neighbors { view: &view, side: Side::Follows };
+        let followers = Neighbors { view: &view, side: Side::Followers };
+        f(&follows, &followers)
+    }
+
+    /// One node's list, under its shard's read lock only
+    pub fn read<R>(&self, side: Side, id: NodeId, f: impl FnOnce(&[NodeId]) -> R) -> R {
+        let (shard, slot) = locate(id as usize);
+        let _timer = LockTimer::read(&self.metrics[shard]);
+        let guard = self.shards[shard].read();
+        f(guard.list(side, slot))
+    }
+
+    /// Change one node's list
+    pub fn update<R>(&self, side: Side, id: NodeId, f: impl FnOnce(&mut Adjacency) -> R) -> R {
+        let (shard, slot) = locate(id as usize);
+        self.write_shard(shard, |lists| f(lists.list_mut(side, slot)))
+    }
+
+    /// Apply `f` to each of `ids`' lists, write-locking every shard involved once
+    pub fn update_each(&self, side: Side, ids: &[NodeId], mut f: impl FnMut(NodeId, &mut Adjacency)) {
+        let mut ids = ids.to_vec();
+        ids.sort_by_key(|&id| locate(id as usize).0);
+        for group in ids.chunk_by(|&a, &b| locate(a as usize).0 == locate(b as usize).0) {
+            self.write_shard(locate(group[0] as usize).0, |lists| {
+                for &id in group {
+                    f(id, lists.list_mut(side, locate(id as usize).1));
+                }
+            });
+        }
+    }
+
+    fn write_shard<R>(&self, shard: usize, f: impl FnOnce(&mut Shard) -> R) -> R {
+        let _timer = LockTimer::write(&self.metrics[shard]);
+        let mut guard = match self.shards[shard].try_write() {
+            Some(guard) => guard,
+            None => {
+                let _drain = self.drain.write();
+                self.shards[shard].write()
+            }
+        };
+        f(&mut guard)
+    }
+
+    /// Exclusive access to every shard, for rewrites spanning the whole graph
+    pub fn write_all(&self) -> AllShards<'_> {
+        let drain = self.drain.write();
+        let timers = self.metrics.iter().map(LockTimer::write).collect();
+        let shards = self.shards.iter().map(|shard| shard.write()).collect();
+        AllShards { shards, _timers: timers, _drain: drain }
+    }
+
+    /// Metrics summed over all shards
+    pub fn metrics(&self) -> LockMetricsSnapshot {
+        LockMetrics::combined(self.metrics.iter())
+    }
+
+    /// Metrics for each shard's lock, in shard order
+    pub fn shard_metrics(&self) -> Vec<LockMetricsSnapshot> {
+        self.metrics.iter().map(LockMetrics::snapshot).collect()
+    }
+
+    pub fn reset_metrics(&self) {
+        self.metrics.iter().for_each(LockMetrics::reset);
+    }
+}
+
+/// Every shard write-locked, from [`ShardedAdjacency::write_all`]
+pub(crate) struct AllShards<'a> {
+    shards: Vec<RwLockWriteGuard<'a, Shard>>,
+    _timers: Vec<LockTimer<'a>>,
+    _drain: RwLockWriteGuard<'a, ()>,
+}
+
+impl AllShards<'_> {
+    pub fn list_mut(&mut self, side: Side, id: NodeId) -> &mut Adjacency {
+        let (shard, slot) = locate(id as usize);
+        self.shards[shard].list_mut(side, slot)
+    }
+
+    /// Replace all lists, indexed by node ID
+    pub fn replace(&mut self, follows: Vec<Adjacency>, followers: Vec<Adjacency>) {
+        for shard in &mut self.shards {
+            **shard = Shard::default();
+        }
+        for (side, lists) in [(Side::Follows, follows), (Side::Followers, followers)] {
+            for (id, list) in lists.into_iter().enumerate() {
+                if !list.is_empty() {
+                    *self.list_mut(side, id as NodeId) = list;
+                }
+            }
+        }
+    }
+}
+
+/// Shards read-locked so far by one [`ShardedAdjacency::with_views`] call, shared by both sides
+struct View<'a> {
+    adjacency: &'a ShardedAdjacency,
+    len: usize,
+    shards: [OnceCell<RwLockReadGuard<'a, Shard>>; ADJACENCY_SHARDS],
+    // Every shard's read is timed from the view's creation, saving a clock read per shard
+    start: Instant,
+}
+
+impl Drop for View<'_> {
+    fn drop(&mut self) {
+        let held = self.start.elapsed();
+        for (shard, guard) in self.shards.iter().enumerate() {
+            if guard.get().is_some() {
+                self.adjacency.metrics[shard].record_read(held);
+            }
+        }
+    }
+}
+
+/// Read access to one side of the adjacency (follows or followers) for node IDs below
+/// [`len`](Self::len), indexed by node ID like a slice of sorted lists. Shards are read-locked
+/// as they are first touched and stay locked until [`WotGraph::with_adjacency`](super::WotGraph::with_adjacency)
+/// returns, so writes to shards a traversal hasn't reached don't wait for it.
+pub struct Neighbors<'v, 'a> {
+    view: &'v View<'a>,
+    side: Side,
+}
+
+impl<'v, 'a> Neighbors<'v, 'a> {
+    /// Number of nodes in the view
+    pub fn len(&self) -> usize {
+        self.view.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.view.len == 0
+    }
+
+    /// Sorted list of node `id`, or `None` past the end
+    pub fn get(&self, id: usize) -> Option<&[NodeId]> {
+        let view = self.view;
+        if id >= view.len {
+            return None;
+        }
+        let (shard, slot) = locate(id);
+        let guard = view.shards[shard].get_or_init(|| view.adjacency.shards[shard].read());
+        let list = guard.list(self.side, slot);
+        // A shard locked after the view was taken may list nodes created since; lists are
+        // sorted, so those are a suffix, cut here to keep every ID in range
+        match list.last() {
+            Some(&last) if last as usize >= view.len => Some(&list[..list.partition_point(|&id| (id as usize) < view.len)]),
+            _ => Some(list),
+        }
+    }
+
+    /// Lists in node ID order
+    pub fn iter(&self) -> impl Iterator<Item = &[NodeId]> + use<'_, 'v, 'a> {
+        (0..self.len()).map(|id| &self[id])
+    }
+}
+
+impl Index<usize> for Neighbors<'_, '_> {
+    type Output = [NodeId];
+
+    fn index(&self, id: usize) -> &[NodeId] {
+        match self.get(id) {
+            Some(list) => list,
+            None => panic!("node {} out of range for {} nodes", id, self.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_spans_shards() {
+        let adjacency = ShardedAdjacency::new();
+        let ids: Vec<NodeId> = (0..200).collect();
+        adjacency.update_each(Side::Followers, &ids, |id, list| list.push(id + 1));
+        adjacency.update(Side::Follows, 130, |list| list.extend([1, 2, 3]));
+
+        adjacency.with_views(150, |follows, followers| {
+            assert_eq!(follows.len(), 150);
+            assert_eq!(&follows[130], &[1, 2, 3]);
+            assert!(follows[129].is_empty());
+            assert_eq!(followers.get(65), Some(&[66][..]));
+            assert_eq!(followers.get(150), None);
+            // Node 149's follower 150 is past the end
+            assert!(followers[149].is_empty());
+            assert_eq!(followers.iter().filter(|list| !list.is_empty()).count(), 149);
+        });
+        assert_eq!(adjacency.read(Side::Followers, 199, |list| list.to_vec()), vec![200]);
+    }
+
+    #[test]
+    fn test_write_waits_out_views() {
+        let adjacency = std::sync::Arc::new(ShardedAdjacency::new());
+        adjacency.update(Side::Follows, 0, |list| list.push(1));
+        adjacency.with_views(2, |follows, _| {
+            assert_eq!(&follows[0], &[1]);
+            // Shard 1 hasn't been touched, so writing it doesn't wait for this view
+            adjacency.update(Side::Follows, 1, |list| list.push(0));
+
+            // Shard 0 is held: the writer drains views, so it lands only after this one ends
+            let writer = {
+                let adjacency = adjacency.clone();
+                std::thread::spawn(move || adjacency.update(Side::Follows, 0, |list| list.push(2)))
+            };
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            assert_eq!(&follows[0], &[1]);
+            assert!(!writer.is_finished());
+            writer
+        })
+        .join()
+        .unwrap();
+        assert_eq!(adjacency.read(Side::Follows, 0, |list| list.to_vec()), vec![1, 2]);
+
+        let metrics = adjacency.shard_metrics();
+        assert_eq!(metrics.len(), ADJACENCY_SHARDS);
+        assert_eq!(metrics[0].write_lock_count, 2);
+        assert_eq!(metrics[1].write_lock_count, 1);
+        assert!(metrics[0].read_lock_count >= 2);
+    }
+
+    #[test]
+    fn test_pinned_view_holds_every_shard() {
+        let adjacency = std::sync::Arc::new(ShardedAdjacency::new());
+        adjacency
+            .with_pinned_views(2, |follows, _| {
+                assert!(follows[0].is_empty());
+                // Shard 1 was locked up front, so even a write to an untouched node waits
+                let writer = {
+                    let adjacency = adjacency.clone();
+                    std::thread::spawn(move || adjacency.update(Side::Follows, 1, |list| list.push(0)))
+                };
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                assert!(follows[1].is_empty());
+                assert!(!writer.is_finished());
+                writer
+            })
+            .join()
+            .unwrap();
+        assert_eq!(adjacency.read(Side::Follows, 1, |list| list.to_vec()), vec![0]);
+        assert!(adjacency.shard_metrics().iter().all(|shard| shard.read_lock_count >= 1));
+    }
+}
@@ -0,0 +1,295 @@
+use parking_lot::RwLock;
+use rustc_hash::FxHashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+
+use super::bfs::{unix_now, DistanceResult};
+use super::{is_quarantined, Neighbors, NodeId, WotGraph};
+use crate::types::Pubkey;
+
+/// Hops value for nodes an anchor can't reach
+const UNREACHABLE: u8 = u8::MAX;
+
+/// Distances from one anchor to every node, indexed by node ID
+pub struct AnchorTable {
+    hops: Vec<u8>,
+    /// Shortest paths to each node (saturating)
+    paths: Vec<u64>,
+    /// Unix time and graph epoch the table was computed at
+    computed_at: u64,
+    graph_epoch: u64,
+}
+
+impl AnchorTable {
+    /// Computed now; [`Anchors::refresh`] records the graph epoch
+    fn new(hops: Vec<u8>, paths: Vec<u64>) -> Self {
+        Self { hops, paths, computed_at: unix_now(), graph_epoch: 0 }
+    }
+
+    /// Hops and shortest-path count to a node, or None if it is unreachable or was added
+    /// after the table was computed
+    pub fn get(&self, node_id: NodeId) -> Option<(u32, u64)> {
+        let hops = *self.hops.get(node_id as usize)?;
+        (hops != UNREACHABLE).then(|| (hops as u32, self.paths[node_id as usize]))
+    }
+}
+
+/// Full distance tables from anchor pubkeys (operator-configured, or picked at runtime), recomputed in the background
+/// with one forward BFS per anchor, so anchor-sourced distance queries are array lookups.
+pub struct Anchors {
+    pubkeys: RwLock<Vec<String>>,
+    tables: RwLock<Arc<FxHashMap<NodeId, Arc<AnchorTable>>>>,
+    // Graph epoch the tables were computed at (u64::MAX = never)
+    epoch: AtomicU64,
+}
+
+impl Anchors {
+    pub fn new(pubkeys: Vec<String>) -> Self {
+        Self {
+            pubkeys: RwLock::new(pubkeys),
+            tables: RwLock::new(Arc::new(FxHashMap::default())),
+            epoch: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// The anchor pubkeys, including any not yet in the graph
+    pub fn pubkeys(&self) -> Vec<String> {
+        self.pubkeys.read().clone()
+    }
+
+    /// Replace the anchor set, e.g. with pubkeys picked at runtime; tables are recomputed on
+    /// the next refresh if it changed
+    pub fn set_pubkeys(&self, pubkeys: Vec<String>) {
+        let mut current = self.pubkeys.write();
+        if *current != pubkeys {
+            *current = pubkeys;
+            self.epoch.store(u64::MAX, Ordering::Release);
+        }
+    }
+
+    /// The table for an anchor, or None if the node is not an anchor or hasn't been computed yet
+    pub fn table(&self, anchor_id: NodeId) -> Option<Arc<AnchorTable>> {
+        self.tables.read().get(&anchor_id).cloned()
+    }
+
+    /// Number of anchors with a computed table
+    pub fn len(&self) -> usize {
+        self.tables.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The distance from an anchor, as [`compute_distance`](super::bfs::compute_distance) would
+    /// find it without bridges (but with every shortest path counted), or None if `from_id` has
+    /// no table or `to_id` is newer than it
+    pub fn distance(&self, graph: &WotGraph, from_id: NodeId, to_id: NodeId, max_hops: u8) -> Option<DistanceResult> {
+        let table = self.table(from_id)?;
+        let from = graph.pubkey(from_id)?;
+        let to = graph.pubkey(to_id)?;
+        let precomputed = |result: DistanceResult| DistanceResult {
+            computed_at: table.computed_at,
+            graph_epoch: table.graph_epoch,
+            cached: true,
+            ..result
+        };
+        if from_id == to_id {
+            return Some(precomputed(DistanceResult::same_node(from)));
+        }
+        if to_id as usize >= table.hops.len() {
+            return None;
+        }
+        match table.get(to_id) {
+            Some((hops, path_count)) if hops <= max_hops as u32 => Some(precomputed(DistanceResult {
+                hops: Some(hops),
+                path_count,
+                mutual_follow: graph.is_following(from_id, to_id) && graph.is_following(to_id, from_id),
+                ..DistanceResult::not_found(from, to)
+            })),
+            _ => Some(precomputed(DistanceResult::not_found(from, to))),
+        }
+    }
+
+    /// Hops from each computed anchor to a node: None where it is unreachable or newer than
+    /// the table
+    pub fn hops_to(&self, node_id: NodeId) -> Vec<Option<u32>> {
+        self.tables.read().values().map(|table| table.get(node_id).map(|(hops, _)| hops)).collect()
+    }
+
+    /// Recompute every anchor's table if the graph changed since the last run.
+    /// CPU-bound: call from a blocking thread.
+    pub fn refresh(&self, graph: &WotGraph) -> bool {
+        let epoch = graph.epoch();
+        let pubkeys = self.pubkeys();
+        if self.epoch.load(Ordering::Acquire) == epoch {
+            return false;
+        }
+        if pubkeys.is_empty() {
+            // An emptied set drops its tables once
+            let dropped = !self.is_empty();
+            *self.tables.write() = Arc::default();
+            self.epoch.store(epoch, Ordering::Release);
+            return dropped;
+        }
+
+        let start = Instant::now();
+        let quarantine_min = graph.limits().quarantine_min_follows;
+        let ids: Vec<NodeId> = pubkeys
+            .iter()
+            .filter_map(|pk| Pubkey::parse(pk).ok())
+            .filter_map(|pk| graph.node_id(&pk))
+            .collect();
+        let tables: FxHashMap<NodeId, Arc<AnchorTable>> = graph.with_adjacency(|follows, followers| {
+            ids.iter()
+                .map(|&id| {
+                    let table = AnchorTable { graph_epoch: epoch, ..compute_table(follows, followers, id, quarantine_min) };
+                    (id, Arc::new(table))
+                })
+                .collect()
+        });
+        info!(
+            "Anchor distances computed for {} of {} anchors in {:?}",
+            tables.len(),
+            pubkeys.len(),
+            start.elapsed()
+        );
+
+        *self.tables.write() = Arc::new(tables);
+        self.epoch.store(epoch, Ordering::Release);
+        true
+    }
+}
+
+impl Default for Anchors {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+/// 0-100 trust score from the hops to each anchor: every anchor contributes 100 when it is
+/// the node or follows it directly, halving per further hop, and 0 when it can't reach it;
+/// the score is the mean over all anchors
+pub fn trust_score(hops: &[Option<u32>]) -> u8 {
+    if hops.is_empty() {
+        return 0;
+    }
+    let total: f64 = hops
+        .iter()
+        .map(|hops| match hops {
+            Some(h) => 100.0 / 2f64.powi(h.saturating_sub(1).min(64) as i32),
+            None => 0.0,
+        })
+        .sum();
+    (total / hops.len() as f64).round() as u8
+}
+
+/// Level-synchronous BFS from `anchor` over follow edges, counting shortest paths.
+/// A quarantined anchor reaches nothing, as in [`compute_distance`](super::bfs::compute_distance).
+pub fn compute_table(follows: &Neighbors, followers: &Neighbors, anchor: NodeId, quarantine_min: usize) -> AnchorTable {
+    let n = follows.len();
+    let mut hops = vec![UNREACHABLE; n];
+    let mut paths = vec![0u64; n];
+    if anchor as usize >= n {
+        return AnchorTable::new(hops, paths);
+    }
+    hops[anchor as usize] = 0;
+    paths[anchor as usize] = 1;
+    if is_quarantined(follows, followers, anchor, quarantine_min) {
+        return AnchorTable::new(hops, paths);
+    }
+
+    let mut current = vec![anchor];
+    let mut next: Vec<NodeId> = Vec::new();
+    let mut dist = 0u8;
+    // Hops stop one short of the unreachable marker
+    while !current.is_empty() && dist < UNREACHABLE - 1 {
+        dist += 1;
+        for &node in &current {
+            let node_paths = paths[node as usize];
+            for &neighbor in &follows[node as usize] {
+                let i = neighbor as usize;
+                if i >= n {
+                    continue;
+                }
+                if hops[i] == UNREACHABLE {
+                    hops[i] = dist;
+                    next.push(neighbor);
+                }
+                if hops[i] == dist {
+                    paths[i] = paths[i].saturating_add(node_paths);
+                }
+            }
+        }
+        current.clear();
+        std::mem::swap(&mut current, &mut next);
+    }
+
+    AnchorTable::new(hops, paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::bfs::{compute_distance, DistanceQuery};
+
+    fn pk(c: char) -> String {
+        c.to_string().repeat(64)
+    }
+
+    #[test]
+    fn test_compute_table() {
+        // 0 -> 1, 0 -> 2, 1 -> 3, 2 -> 3, 3 -> 4; 5 isolated
+        let graph = WotGraph::from_lists(&[&[1, 2], &[3], &[3], &[4], &[], &[]]);
+        let table = graph.with_adjacency(|follows, followers| compute_table(follows, followers, 0, 0));
+        assert_eq!(table.get(0), Some((0, 1)));
+        assert_eq!(table.get(3), Some((2, 2)));
+        assert_eq!(table.get(4), Some((3, 2)));
+        assert_eq!(table.get(5), None);
+
+        // Followed by nobody with 2+ follows: quarantined, reaches nothing
+        let table = graph.with_adjacency(|follows, followers| compute_table(follows, followers, 0, 2));
+        assert_eq!(table.get(1), None);
+    }
+
+    #[test]
+    fn test_trust_score() {
+        assert_eq!(trust_score(&[]), 0);
+        assert_eq!(trust_score(&[Some(0)]), 100);
+        assert_eq!(trust_score(&[Some(1), Some(2)]), 75);
+        assert_eq!(trust_score(&[Some(3), None]), 13);
+        assert_eq!(trust_score(&[None, None]), 0);
+    }
+
+    #[test]
+    fn test_distance_matches_bfs() {
+        let graph = WotGraph::new();
+        graph.update_follows(&pk('a'), &[pk('b'), pk('c')], None, None);
+        graph.update_follows(&pk('b'), &[pk('a'), pk('d')], None, None);
+        graph.update_follows(&pk('d'), &[pk('e')], None, None);
+
+        let anchors = Anchors::new(vec![pk('a'), pk('f')]);
+        assert!(anchors.refresh(&graph));
+        assert!(!anchors.refresh(&graph));
+        assert_eq!(anchors.len(), 1);
+
+        let id = |c| graph.node_id(&pk(c).parse().unwrap()).unwrap();
+        for (to, max_hops) in [('a', 3), ('b', 3), ('d', 3), ('e', 3), ('e', 2), ('c', 1)] {
+            let anchored = anchors.distance(&graph, id('a'), id(to), max_hops).unwrap();
+            let query = DistanceQuery { from: pk('a').parse().unwrap(), to: pk(to).parse().unwrap(), max_hops, include_bridges: false, min_followers: 0 };
+            let computed = compute_distance(&graph, &query);
+            assert_eq!(anchored.hops, computed.hops, "a -> {} within {}", to, max_hops);
+            assert_eq!(anchored.mutual_follow, computed.mutual_follow);
+            assert_eq!((anchored.graph_epoch, anchored.cached), (graph.epoch(), true));
+        }
+        assert_eq!(anchors.distance(&graph, id('b'), id('d'), 3).map(|r| r.hops), None);
+
+        // Nodes added after the refresh fall back to BFS until the next one
+        graph.update_follows(&pk('e'), &[pk('0')], None, None);
+        assert!(anchors.distance(&graph, id('a'), id('0'), 5).is_none());
+        assert!(anchors.refresh(&graph));
+        assert_eq!(anchors.distance(&graph, id('a'), id('0'), 5).unwrap().hops, Some(4));
+    }
+}
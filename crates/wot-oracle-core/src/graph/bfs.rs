@@ -0,0 +1,1257 @@
+use super::{is_quarantined, GraphLimits, Neighbors, NodeId, WotGraph};
+use crate::types::Pubkey;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Initial capacities for preallocated structures
+const VISITED_CAPACITY: usize = 8192;
+const FRONTIER_CAPACITY: usize = 1024;
+const MEETING_NODES_CAPACITY: usize = 64;
+const BRIDGE_CAPACITY: usize = 64;
+
+/// Reusable BFS state to avoid allocations per query
+/// Uses double-buffered Vec (current/next) instead of VecDeque for better cache locality
+struct BfsState {
+    fwd_visited: FxHashMap<NodeId, (u32, u64)>,
+    fwd_current: Vec<NodeId>,
+    fwd_next: Vec<NodeId>,
+    bwd_visited: FxHashMap<NodeId, (u32, u64)>,
+    bwd_current: Vec<NodeId>,
+    bwd_next: Vec<NodeId>,
+    meeting_nodes: Vec<(NodeId, u64, u64)>,
+    // Reusable structures for bridge ranking (avoids per-query allocation)
+    bridge_paths: FxHashMap<NodeId, u64>,
+    bridge_ranked: Vec<(NodeId, u64)>,
+    bridge_ids: Vec<NodeId>,
+}
+
+impl BfsState {
+    fn new() -> Self {
+        Self {
+            fwd_visited: FxHashMap::with_capacity_and_hasher(VISITED_CAPACITY, Default::default()),
+            fwd_current: Vec::with_capacity(FRONTIER_CAPACITY),
+            fwd_next: Vec::with_capacity(FRONTIER_CAPACITY),
+            bwd_visited: FxHashMap::with_capacity_and_hasher(VISITED_CAPACITY, Default::default()),
+            bwd_current: Vec::with_capacity(FRONTIER_CAPACITY),
+            bwd_next: Vec::with_capacity(FRONTIER_CAPACITY),
+            meeting_nodes: Vec::with_capacity(MEETING_NODES_CAPACITY),
+            bridge_paths: FxHashMap::with_capacity_and_hasher(BRIDGE_CAPACITY, Default::default()),
+            bridge_ranked: Vec::with_capacity(BRIDGE_CAPACITY),
+            bridge_ids: Vec::with_capacity(BRIDGE_CAPACITY),
+        }
+    }
+
+    /// Clear all structures while retaining allocated capacity
+    fn clear(&mut self) {
+        self.fwd_visited.clear();
+        self.fwd_current.clear();
+        self.fwd_next.clear();
+        self.bwd_visited.clear();
+        self.bwd_current.clear();
+        self.bwd_next.clear();
+        self.meeting_nodes.clear();
+        self.bridge_paths.clear();
+        self.bridge_ranked.clear();
+        self.bridge_ids.clear();
+    }
+}
+
+thread_local! {
+    static BFS_STATE: RefCell<BfsState> = RefCell::new(BfsState::new());
+}
+
+#[derive(Debug, Clone)]
+pub struct DistanceQuery {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub max_hops: u8,
+    pub include_bridges: bool,
+    /// Intermediate nodes with fewer followers are not walked through (0 = no filter)
+    pub min_followers: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DistanceResult {
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub from: Pubkey,
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub to: Pubkey,
+    pub hops: Option<u32>,
+    pub path_count: u64,
+    pub mutual_follow: bool,
+    /// Nodes where shortest paths meet, most paths first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Vec<String>>))]
+    pub bridges: Option<Vec<Pubkey>>,
+    /// Shortest paths through each of `bridges`, in the same order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bridge_path_counts: Option<Vec<u64>>,
+    /// Set when a node's neighbors were sampled down to the expansion limit, so `hops` may be
+    /// longer and `path_count` lower than in the full graph
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Unix time the result was computed; a cached result keeps its original time
+    pub computed_at: u64,
+    /// Graph epoch the search started at. Updates landing mid-search may still show in the
+    /// result, except for searches run in one [`WotGraph::with_snapshot`].
+    pub graph_epoch: u64,
+    /// Served from the query cache or a precomputed anchor table rather than searched for
+    /// this request
+    pub cached: bool,
+}
+
+impl DistanceResult {
+    /// Unreachable, computed now at epoch 0; searches set the epoch they ran at
+    pub fn not_found(from: Pubkey, to: Pubkey) -> Self {
+        Self {
+            from,
+            to,
+            hops: None,
+            path_count: 0,
+            mutual_follow: false,
+            bridges: None,
+            bridge_path_counts: None,
+            truncated: false,
+            computed_at: unix_now(),
+            graph_epoch: 0,
+            cached: false,
+        }
+    }
+
+    pub fn same_node(pubkey: Pubkey) -> Self {
+        Self {
+            hops: Some(0),
+            path_count: 1,
+            ..Self::not_found(pubkey, pubkey)
+        }
+    }
+
+    /// Keep only the `max` bridges with the most paths
+    pub fn truncate_bridges(&mut self, max: usize) {
+        if let Some(bridges) = self.bridges.as_mut() {
+            bridges.truncate(max);
+        }
+        if let Some(counts) = self.bridge_path_counts.as_mut() {
+            counts.truncate(max);
+        }
+    }
+}
+
+/// Lowers max_hops for searches that start or end at a super-node, whose frontier explodes
+/// within a hop or two. An endpoint counts as one when its follows or followers reach
+/// `degree_threshold`; 0 disables the policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HopPolicy {
+    pub degree_threshold: usize,
+    /// max_hops applied to searches touching a super-node
+    pub max_hops: u8,
+}
+
+/// The policy as applied to one query, reported next to its result
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AdaptiveHops {
+    pub requested_max_hops: u8,
+    pub max_hops: u8,
+    /// The high-degree endpoint: `from`, `to` or `both`
+    pub reason: &'static str,
+}
+
+impl HopPolicy {
+    /// The max_hops to search with, and the applied policy if it lowered `requested`
+    pub fn apply(&self, graph: &WotGraph, from: &Pubkey, to: &Pubkey, requested: u8) -> (u8, Option<AdaptiveHops>) {
+        if self.degree_threshold == 0 || requested <= self.max_hops {
+            return (requested, None);
+        }
+        let is_super_node = |pubkey: &Pubkey| {
+            graph.node_id(pubkey).is_some_and(|id| {
+                let (follows, followers) = graph.degree(id);
+                follows.max(followers) >= self.degree_threshold
+            })
+        };
+        let reason = match (is_super_node(from), is_super_node(to)) {
+            (false, false) => return (requested, None),
+            (true, false) => "from",
+            (false, true) => "to",
+            (true, true) => "both",
+        };
+        let applied = AdaptiveHops { requested_max_hops: requested, max_hops: self.max_hops, reason };
+        (self.max_hops, Some(applied))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PathQuery {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub max_hops: u8,
+    /// Intermediate nodes with fewer followers are not walked through (0 = no filter)
+    pub min_followers: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PathResult {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub path: Option<Vec<Pubkey>>,
+    /// Set when a node's neighbors were sampled down to the expansion limit
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiSourceQuery {
+    pub sources: Vec<Pubkey>,
+    pub target: Pubkey,
+    pub max_hops: u8,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MultiSourceResult {
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub target: Pubkey,
+    pub hops: Option<u32>,
+    /// A source at `hops` from the target (the first listed, on ties); None if none is in range
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub source: Option<Pubkey>,
+    /// Graph epoch the search ran at
+    pub epoch: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetDistanceQuery {
+    pub from: Vec<Pubkey>,
+    pub to: Vec<Pubkey>,
+    pub max_hops: u8,
+}
+
+/// How far apart the members of two pubkey sets are, as counts per distance rather than the
+/// full `from` x `to` matrix
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetDistanceResult {
+    /// Distinct pubkeys in each set
+    pub from_count: usize,
+    pub to_count: usize,
+    /// Pairs `from_count * to_count`
+    pub pairs: u64,
+    /// `histogram[h]` pairs are `h` hops apart (`h = 0` for pubkeys in both sets), up to `max_hops`
+    pub histogram: Vec<u64>,
+    /// Pairs farther apart than `max_hops`, or with a pubkey outside the graph
+    pub unreachable: u64,
+    /// Graph epoch the searches ran at
+    pub epoch: u64,
+}
+
+pub fn compute_distance(graph: &WotGraph, query: &DistanceQuery) -> DistanceResult {
+    // Single read lock for entire BFS traversal
+    graph.with_adjacency(|follows, followers| compute_distance_in(graph, follows, followers, query))
+}
+
+/// [`compute_distance`] over adjacency the caller already holds, e.g. to answer several
+/// queries from one [`WotGraph::with_snapshot`]
+pub fn compute_distance_in(
+    graph: &WotGraph,
+    follows: &Neighbors,
+    followers: &Neighbors,
+    query: &DistanceQuery,
+) -> DistanceResult {
+    let graph_epoch = graph.epoch();
+    DistanceResult { graph_epoch, ..search(graph, follows, followers, query) }
+}
+
+fn search(graph: &WotGraph, follows: &Neighbors, followers: &Neighbors, query: &DistanceQuery) -> DistanceResult {
+    // Handle same node case
+    if query.from == query.to {
+        return DistanceResult::same_node(query.from);
+    }
+
+    // Get node IDs (uses DashMap, separate from adjacency lock)
+    let (from, to) = (query.from, query.to);
+    let (Some(from_id), Some(to_id)) = (graph.node_id(&from), graph.node_id(&to)) else {
+        return DistanceResult::not_found(from, to);
+    };
+
+    // Nodes created after the view was taken have no edges in it yet
+    if from_id as usize >= follows.len() || to_id as usize >= follows.len() {
+        return DistanceResult::not_found(from, to);
+    }
+
+    let limits = graph.limits();
+
+    // Quarantined nodes have no followers, so only a quarantined source can reach them
+    if is_quarantined(follows, followers, from_id, limits.quarantine_min_follows) {
+        return DistanceResult::not_found(from, to);
+    }
+
+    // Direct follow check via binary search on sorted list
+    let is_direct = |from: NodeId, to: NodeId| -> bool {
+        follows
+            .get(from as usize)
+            .map(|list| list.binary_search(&to).is_ok())
+            .unwrap_or(false)
+    };
+
+    // Check for mutual follow
+    let mutual_follow = is_direct(from_id, to_id) && is_direct(to_id, from_id);
+
+    // Check for direct follow (hops = 1)
+    if is_direct(from_id, to_id) {
+        return DistanceResult {
+            hops: Some(1),
+            path_count: 1,
+            mutual_follow,
+            bridges: query.include_bridges.then(Vec::new),
+            bridge_path_counts: query.include_bridges.then(Vec::new),
+            ..DistanceResult::not_found(from, to)
+        };
+    }
+
+    // Bidirectional BFS using thread-local state (zero allocation)
+    BFS_STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.clear();
+        bidirectional_bfs(
+            &mut state,
+            follows,
+            followers,
+            from_id,
+            to_id,
+            query.max_hops,
+            limits,
+            query.min_followers,
+            query.include_bridges,
+            mutual_follow,
+            from,
+            to,
+            graph, // For resolve_pubkeys at end
+        )
+    })
+}
+
+#[allow(clippy::too_many_arguments)] // BFS state is intentionally flat for performance
+fn bidirectional_bfs(
+    state: &mut BfsState,
+    follows: &Neighbors,
+    followers: &Neighbors,
+    from_id: NodeId,
+    to_id: NodeId,
+    max_hops: u8,
+    limits: GraphLimits,
+    min_followers: usize,
+    include_bridges: bool,
+    mutual_follow: bool,
+    from: Pubkey,
+    to: Pubkey,
+    graph: &WotGraph, // Only for resolve_pubkeys at end
+) -> DistanceResult {
+    state.fwd_visited.insert(from_id, (0, 1));
+    state.fwd_current.push(from_id);
+
+    state.bwd_visited.insert(to_id, (0, 1));
+    state.bwd_current.push(to_id);
+
+    let mut fwd_dist = 0u32;
+    let mut bwd_dist = 0u32;
+    let mut best_distance: Option<u32> = None;
+    let mut truncated = false;
+
+    'outer: while !state.fwd_current.is_empty() || !state.bwd_current.is_empty() {
+        // Check if we should stop
+        let current_min_possible = fwd_dist + bwd_dist;
+        if let Some(best) = best_distance {
+            if current_min_possible >= best {
+                break;
+            }
+        }
+
+        if current_min_possible as u8 > max_hops {
+            break;
+        }
+
+        // Expand smaller frontier
+        let expand_forward = if state.fwd_current.is_empty() {
+            false
+        } else if state.bwd_current.is_empty() {
+            true
+        } else {
+            state.fwd_current.len() <= state.bwd_current.len()
+        };
+
+        if expand_forward {
+            fwd_dist += 1;
+
+            // Process all nodes in current level (contiguous memory iteration)
+            for i in 0..state.fwd_current.len() {
+                let node = state.fwd_current[i];
+                let (_, node_paths) = state.fwd_visited[&node];
+
+                // Direct access to neighbors - no lock, no clone
+                let (neighbors, sampled) = expand(&follows[node as usize], limits.expansion_limit);
+                truncated |= sampled;
+                for &neighbor in neighbors {
+                    // Check if we've met the backward search
+                    if let Some(&(bwd_d, bwd_paths)) = state.bwd_visited.get(&neighbor) {
+                        let total_dist = fwd_dist + bwd_d;
+
+                        if best_distance.is_none() || total_dist < best_distance.unwrap() {
+                            best_distance = Some(total_dist);
+                            state.meeting_nodes.clear();
+                        }
+
+                        if best_distance == Some(total_dist) {
+                            state.meeting_nodes.push((neighbor, node_paths, bwd_paths));
+                        }
+
+                        // Early exit: if we don't need bridges, one path is enough
+                        if !include_bridges {
+                            break 'outer;
+                        }
+                    }
+
+                    if is_obscure(followers, neighbor, min_followers) {
+                        continue;
+                    }
+
+                    // Add to next frontier if not visited (single lookup via entry API)
+                    match state.fwd_visited.entry(neighbor) {
+                        std::collections::hash_map::Entry::Vacant(e) => {
+                            e.insert((fwd_dist, node_paths));
+                            state.fwd_next.push(neighbor);
+                        }
+                        std::collections::hash_map::Entry::Occupied(mut e) => {
+                            // Update path count if same distance
+                            let (existing_dist, existing_paths) = e.get_mut();
+                            if *existing_dist == fwd_dist {
+                                *existing_paths += node_paths;
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Swap buffers: next becomes current
+            state.fwd_current.clear();
+            std::mem::swap(&mut state.fwd_current, &mut state.fwd_next);
+        } else {
+            bwd_dist += 1;
+
+            // Process all nodes in current level (contiguous memory iteration)
+            for i in 0..state.bwd_current.len() {
+                let node = state.bwd_current[i];
+                let (_, node_paths) = state.bwd_visited[&node];
+
+                // Direct access to neighbors - no lock, no clone
+                let (neighbors, sampled) = expand(&followers[node as usize], limits.expansion_limit);
+                truncated |= sampled;
+                for &neighbor in neighbors {
+                    if is_quarantined(follows, followers, neighbor, limits.quarantine_min_follows) {
+                        continue;
+                    }
+
+                    // Check if we've met the forward search
+                    if let Some(&(fwd_d, fwd_paths)) = state.fwd_visited.get(&neighbor) {
+                        let total_dist = fwd_d + bwd_dist;
+
+                        if best_distance.is_none() || total_dist < best_distance.unwrap() {
+                            best_distance = Some(total_dist);
+                            state.meeting_nodes.clear();
+                        }
+
+                        if best_distance == Some(total_dist) {
+                            state.meeting_nodes.push((neighbor, fwd_paths, node_paths));
+                        }
+
+                        // Early exit: if we don't need bridges, one path is enough
+                        if !include_bridges {
+                            break 'outer;
+                        }
+                    }
+
+                    if is_obscure(followers, neighbor, min_followers) {
+                        continue;
+                    }
+
+                    // Add to next frontier if not visited (single lookup via entry API)
+                    match state.bwd_visited.entry(neighbor) {
+                        std::collections::hash_map::Entry::Vacant(e) => {
+                            e.insert((bwd_dist, node_paths));
+                            state.bwd_next.push(neighbor);
+                        }
+                        std::collections::hash_map::Entry::Occupied(mut e) => {
+                            // Update path count if same distance
+                            let (existing_dist, existing_paths) = e.get_mut();
+                            if *existing_dist == bwd_dist {
+                                *existing_paths += node_paths;
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Swap buffers: next becomes current
+            state.bwd_current.clear();
+            std::mem::swap(&mut state.bwd_current, &mut state.bwd_next);
+        }
+    }
+
+    match best_distance {
+        Some(hops) if hops as u8 <= max_hops => {
+            // Calculate total path count
+            let path_count: u64 = state.meeting_nodes
+                .iter()
+                .map(|(_, fwd_paths, bwd_paths)| fwd_paths * bwd_paths)
+                .sum();
+
+            // Rank unique bridge nodes using reusable structures
+            let (bridges, bridge_path_counts) = if include_bridges {
+                // A node can meet the other side through several edges; sum its paths
+                for &(id, fwd_paths, bwd_paths) in &state.meeting_nodes {
+                    *state.bridge_paths.entry(id).or_insert(0) += fwd_paths * bwd_paths;
+                }
+                state.bridge_ranked.extend(state.bridge_paths.iter().map(|(&id, &paths)| (id, paths)));
+                // Most paths first; node ID breaks ties so the order is stable
+                state.bridge_ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+                state.bridge_ids.extend(state.bridge_ranked.iter().map(|&(id, _)| id));
+                let counts = state.bridge_ranked.iter().map(|&(_, paths)| paths).collect();
+                (Some(graph.resolve_pubkeys(&state.bridge_ids)), Some(counts))
+            } else {
+                (None, None)
+            };
+
+            DistanceResult {
+                hops: Some(hops),
+                path_count,
+                mutual_follow,
+                bridges,
+                bridge_path_counts,
+                truncated,
+                ..DistanceResult::not_found(from, to)
+            }
+        }
+        Some(_) | None => DistanceResult { truncated, ..DistanceResult::not_found(from, to) },
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Neighbors to walk from a node, and whether they were sampled: lists longer than
+/// `expansion_limit` (0 = no limit) give an evenly spaced sample of that many, so one account
+/// with hundreds of thousands of follows or followers can't dominate a query
+fn expand(
+    neighbors: &[NodeId],
+    expansion_limit: usize,
+) -> (std::iter::Take<std::iter::StepBy<std::slice::Iter<'_, NodeId>>>, bool) {
+    if expansion_limit == 0 || neighbors.len() <= expansion_limit {
+        return (neighbors.iter().step_by(1).take(neighbors.len()), false);
+    }
+    (neighbors.iter().step_by(neighbors.len() / expansion_limit).take(expansion_limit), true)
+}
+
+/// Whether `min_followers` keeps a node off search frontiers. Frontier nodes are the only ones
+/// searches meet through, so the endpoints (seeded into the frontiers) stay reachable.
+fn is_obscure(followers: &Neighbors, node: NodeId, min_followers: usize) -> bool {
+    min_followers > 0 && followers[node as usize].len() < min_followers
+}
+
+/// Minimum distance from any of `sources` to the target, as one BFS seeded with every source
+/// rather than one search per source, over one [`WotGraph::with_snapshot`]
+pub fn compute_multi_source_distance(graph: &WotGraph, query: &MultiSourceQuery) -> MultiSourceResult {
+    let not_found = |epoch: u64| MultiSourceResult {
+        target: query.target,
+        hops: None,
+        source: None,
+        epoch,
+    };
+
+    let Some(to_id) = graph.node_id(&query.target) else {
+        return not_found(graph.epoch());
+    };
+
+    let quarantine_min = graph.limits().quarantine_min_follows;
+
+    // One snapshot for the entire BFS traversal
+    let (epoch, reached) = graph.with_snapshot(|epoch, follows, followers| {
+        // Sources outside the graph or quarantined reach nothing; duplicates keep their first position
+        let mut visited: FxHashMap<NodeId, (u32, usize)> = FxHashMap::default();
+        let mut current: Vec<NodeId> = Vec::with_capacity(query.sources.len());
+        for (idx, source) in query.sources.iter().enumerate() {
+            if let Some(id) = graph.node_id(source) {
+                // Nodes created after the snapshot was taken aren't in it
+                if id as usize >= follows.len() || is_quarantined(follows, followers, id, quarantine_min) {
+                    continue;
+                }
+                if let std::collections::hash_map::Entry::Vacant(e) = visited.entry(id) {
+                    e.insert((0, idx));
+                    current.push(id);
+                }
+            }
+        }
+
+        let mut next: Vec<NodeId> = Vec::new();
+        let mut dist = 0u32;
+
+        let reached = loop {
+            // Checked per completed level, so ties resolve to the first listed source
+            if let Some(&(hops, origin)) = visited.get(&to_id) {
+                break Some((hops, origin));
+            }
+            if current.is_empty() || dist >= query.max_hops as u32 {
+                break None;
+            }
+
+            dist += 1;
+            for &node in &current {
+                let (_, origin) = visited[&node];
+                for &neighbor in &follows[node as usize] {
+                    match visited.entry(neighbor) {
+                        std::collections::hash_map::Entry::Vacant(e) => {
+                            e.insert((dist, origin));
+                            next.push(neighbor);
+                        }
+                        std::collections::hash_map::Entry::Occupied(mut e) => {
+                            let (existing_dist, existing_origin) = e.get_mut();
+                            if *existing_dist == dist && origin < *existing_origin {
+                                *existing_origin = origin;
+                            }
+                        }
+                    }
+                }
+            }
+
+            current.clear();
+            std::mem::swap(&mut current, &mut next);
+        };
+        (epoch, reached)
+    });
+
+    match reached {
+        Some((hops, origin)) => MultiSourceResult {
+            target: query.target,
+            hops: Some(hops),
+            source: Some(query.sources[origin]),
+            epoch,
+        },
+        None => not_found(epoch),
+    }
+}
+
+/// Histogram of the distances from each `from` pubkey to each `to` pubkey, over one
+/// [`WotGraph::with_snapshot`]. One bounded BFS runs per member of the smaller set: forward over
+/// follows from `from`, or backward over followers from `to`, so the cost scales with the
+/// smaller set. Quarantined pubkeys reach nothing, as in [`compute_multi_source_distance`].
+pub fn compute_set_distances(graph: &WotGraph, query: &SetDistanceQuery) -> SetDistanceResult {
+    let distinct = |set: &[Pubkey]| -> Vec<Pubkey> {
+        let mut seen = FxHashSet::default();
+        set.iter().filter(|pk| seen.insert(**pk)).copied().collect()
+    };
+    let (from, to) = (distinct(&query.from), distinct(&query.to));
+    let pairs = from.len() as u64 * to.len() as u64;
+    let quarantine_min = graph.limits().quarantine_min_follows;
+
+    // Search from the smaller side, in the direction that keeps hops meaning from -> to
+    let backward = to.len() < from.len();
+    let (starts, ends) = if backward { (&to, &from) } else { (&from, &to) };
+    let start_ids: Vec<NodeId> = starts.iter().filter_map(|pk| graph.node_id(pk)).collect();
+    let end_ids: FxHashSet<NodeId> = ends.iter().filter_map(|pk| graph.node_id(pk)).collect();
+
+    let (epoch, histogram) = graph.with_snapshot(|epoch, follows, followers| {
+        let walk = if backward { followers } else { follows };
+        let mut histogram = vec![0u64; query.max_hops as usize + 1];
+        let mut visited: FxHashSet<NodeId> = FxHashSet::default();
+        let (mut current, mut next): (Vec<NodeId>, Vec<NodeId>) = (Vec::new(), Vec::new());
+
+        for &start in &start_ids {
+            // Nodes created after the snapshot was taken aren't in it
+            if start as usize >= follows.len() || is_quarantined(follows, followers, start, quarantine_min) {
+                continue;
+            }
+            visited.clear();
+            visited.insert(start);
+            current.clear();
+            current.push(start);
+            let mut found = usize::from(end_ids.contains(&start));
+            histogram[0] += found as u64;
+
+            for count in histogram.iter_mut().skip(1) {
+                if current.is_empty() || found == end_ids.len() {
+                    break;
+                }
+                for &node in &current {
+                    for &neighbor in &walk[node as usize] {
+                        if visited.insert(neighbor) {
+                            next.push(neighbor);
+                            if end_ids.contains(&neighbor) {
+                                *count += 1;
+                                found += 1;
+                            }
+                        }
+                    }
+                }
+                current.clear();
+                std::mem::swap(&mut current, &mut next);
+            }
+        }
+        (epoch, histogram)
+    });
+
+    SetDistanceResult {
+        from_count: from.len(),
+        to_count: to.len(),
+        pairs,
+        unreachable: pairs - histogram.iter().sum::<u64>(),
+        histogram,
+        epoch,
+    }
+}
+
+/// Compute the shortest path between two nodes, returning the actual path
+pub fn compute_path(graph: &WotGraph, query: &PathQuery) -> PathResult {
+    // Handle same node case
+    let (from, to) = (query.from, query.to);
+    if from == to {
+        return PathResult {
+            from,
+            to,
+            path: Some(vec![]),
+            truncated: false,
+        };
+    }
+
+    let (Some(from_id), Some(to_id)) = (graph.node_id(&from), graph.node_id(&to)) else {
+        return PathResult {
+            from,
+            to,
+            path: None,
+            truncated: false,
+        };
+    };
+
+    let limits = graph.limits();
+
+    // Single read lock for entire BFS traversal
+    graph.with_adjacency(|follows, followers| {
+        if is_quarantined(follows, followers, from_id, limits.quarantine_min_follows) {
+            return PathResult {
+                from,
+                to,
+                path: None,
+                truncated: false,
+            };
+        }
+
+        // Direct follow check via binary search on sorted list
+        let is_direct = |from: NodeId, to: NodeId| -> bool {
+            follows
+                .get(from as usize)
+                .map(|list| list.binary_search(&to).is_ok())
+                .unwrap_or(false)
+        };
+
+        // Check for direct follow (hops = 1)
+        if is_direct(from_id, to_id) {
+            return PathResult {
+                from,
+                to,
+                path: Some(vec![]),
+                truncated: false,
+            };
+        }
+
+        // BFS with parent tracking for path reconstruction
+        let mut fwd_parent: FxHashMap<NodeId, NodeId> = FxHashMap::default();
+        let mut bwd_parent: FxHashMap<NodeId, NodeId> = FxHashMap::default();
+        let mut fwd_visited: FxHashSet<NodeId> = FxHashSet::default();
+        let mut bwd_visited: FxHashSet<NodeId> = FxHashSet::default();
+        let mut fwd_current: Vec<NodeId> = vec![from_id];
+        let mut bwd_current: Vec<NodeId> = vec![to_id];
+        let mut fwd_next: Vec<NodeId> = Vec::new();
+        let mut bwd_next: Vec<NodeId> = Vec::new();
+
+        fwd_visited.insert(from_id);
+        bwd_visited.insert(to_id);
+
+        let mut meeting_node: Option<NodeId> = None;
+        let mut fwd_dist = 0u32;
+        let mut bwd_dist = 0u32;
+        let mut truncated = false;
+
+        'outer: while !fwd_current.is_empty() || !bwd_current.is_empty() {
+            let current_min_possible = fwd_dist + bwd_dist;
+            if current_min_possible as u8 > query.max_hops {
+                break;
+            }
+
+            // Expand smaller frontier
+            let expand_forward = if fwd_current.is_empty() {
+                false
+            } else if bwd_current.is_empty() {
+                true
+            } else {
+                fwd_current.len() <= bwd_current.len()
+            };
+
+            if expand_forward {
+                fwd_dist += 1;
+                for &node in &fwd_current {
+                    let (neighbors, sampled) = expand(&follows[node as usize], limits.expansion_limit);
+                    truncated |= sampled;
+                    for &neighbor in neighbors {
+                        if bwd_visited.contains(&neighbor) {
+                            fwd_parent.insert(neighbor, node);
+                            meeting_node = Some(neighbor);
+                            break 'outer;
+                        }
+                        if !fwd_visited.contains(&neighbor) && !is_obscure(followers, neighbor, query.min_followers) {
+                            fwd_visited.insert(neighbor);
+                            fwd_parent.insert(neighbor, node);
+                            fwd_next.push(neighbor);
+                        }
+                    }
+                }
+                fwd_current.clear();
+                std::mem::swap(&mut fwd_current, &mut fwd_next);
+            } else {
+                bwd_dist += 1;
+                for &node in &bwd_current {
+                    let (neighbors, sampled) = expand(&followers[node as usize], limits.expansion_limit);
+                    truncated |= sampled;
+                    for &neighbor in neighbors {
+                        if is_quarantined(follows, followers, neighbor, limits.quarantine_min_follows) {
+                            continue;
+                        }
+                        if fwd_visited.contains(&neighbor) {
+                            bwd_parent.insert(neighbor, node);
+                            meeting_node = Some(neighbor);
+                            break 'outer;
+                        }
+                        if !bwd_visited.contains(&neighbor) && !is_obscure(followers, neighbor, query.min_followers) {
+                            bwd_visited.insert(neighbor);
+                            bwd_parent.insert(neighbor, node);
+                            bwd_next.push(neighbor);
+                        }
+                    }
+                }
+                bwd_current.clear();
+                std::mem::swap(&mut bwd_current, &mut bwd_next);
+            }
+        }
+
+        match meeting_node {
+            Some(meet) => {
+                // Reconstruct path from from_id to meeting point
+                let mut path_ids: Vec<NodeId> = Vec::new();
+                let mut current = meet;
+                while current != from_id {
+                    if let Some(&parent) = fwd_parent.get(&current) {
+                        path_ids.push(current);
+                        current = parent;
+                    } else {
+                        break;
+                    }
+                }
+                path_ids.reverse();
+
+                // Reconstruct path from meeting point to to_id
+                current = meet;
+                while current != to_id {
+                    if let Some(&child) = bwd_parent.get(&current) {
+                        if child != to_id {
+                            path_ids.push(child);
+                        }
+                        current = child;
+                    } else {
+                        break;
+                    }
+                }
+
+                // Convert IDs to pubkeys
+                let path = graph.resolve_pubkeys(&path_ids);
+
+                PathResult {
+                    from,
+                    to,
+                    path: Some(path),
+                    truncated,
+                }
+            }
+            None => PathResult {
+                from,
+                to,
+                path: None,
+                truncated,
+            },
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as the graph stores it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    fn create_test_graph() -> WotGraph {
+        // Create a simple test graph:
+        // alice -> bob -> carol -> dave
+        //       -> eve -> carol
+        let graph = WotGraph::new();
+
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("eve")], None, None);
+        graph.update_follows(&hex("bob"), &[hex("carol")], None, None);
+        graph.update_follows(&hex("eve"), &[hex("carol")], None, None);
+        graph.update_follows(&hex("carol"), &[hex("dave")], None, None);
+
+        graph
+    }
+
+    #[test]
+    fn test_same_node() {
+        let graph = create_test_graph();
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("alice"),
+            max_hops: 5,
+            include_bridges: false,
+            min_followers: 0,
+        };
+
+        let result = compute_distance(&graph, &query);
+        assert_eq!(result.hops, Some(0));
+        assert_eq!(result.path_count, 1);
+    }
+
+    #[test]
+    fn test_direct_follow() {
+        let graph = create_test_graph();
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("bob"),
+            max_hops: 5,
+            include_bridges: false,
+            min_followers: 0,
+        };
+
+        let result = compute_distance(&graph, &query);
+        assert_eq!(result.hops, Some(1));
+        assert_eq!(result.path_count, 1);
+    }
+
+    #[test]
+    fn test_two_hops() {
+        let graph = create_test_graph();
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("carol"),
+            max_hops: 5,
+            include_bridges: true,
+            min_followers: 0,
+        };
+
+        let result = compute_distance(&graph, &query);
+        assert_eq!(result.hops, Some(2));
+        assert_eq!(result.path_count, 2); // Two paths: alice->bob->carol and alice->eve->carol
+
+        let bridges = result.bridges.unwrap();
+        assert_eq!(bridges.len(), 2);
+        assert!(bridges.contains(&pk("bob")) || bridges.contains(&pk("eve")));
+        assert_eq!(result.bridge_path_counts, Some(vec![1, 1]));
+    }
+
+    #[test]
+    fn test_bridges_ranked_by_path_count() {
+        // alice reaches frank through carol on two paths and through gina on one
+        let graph = create_test_graph();
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("eve"), hex("henry")], None, None);
+        graph.update_follows(&hex("carol"), &[hex("dave"), hex("frank")], None, None);
+        graph.update_follows(&hex("henry"), &[hex("gina")], None, None);
+        graph.update_follows(&hex("gina"), &[hex("frank")], None, None);
+
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("frank"),
+            max_hops: 5,
+            include_bridges: true,
+            min_followers: 0,
+        };
+
+        let mut result = compute_distance(&graph, &query);
+        assert_eq!(result.hops, Some(3));
+        assert_eq!(result.path_count, 3);
+        let counts = result.bridge_path_counts.clone().unwrap();
+        assert_eq!(counts.iter().sum::<u64>(), result.path_count);
+        assert!(counts.windows(2).all(|w| w[0] >= w[1]));
+
+        result.truncate_bridges(1);
+        assert_eq!(result.bridges.as_ref().unwrap().len(), 1);
+        assert_eq!(result.bridge_path_counts, Some(vec![counts[0]]));
+    }
+
+    #[test]
+    fn test_three_hops() {
+        let graph = create_test_graph();
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("dave"),
+            max_hops: 5,
+            include_bridges: false,
+            min_followers: 0,
+        };
+
+        let result = compute_distance(&graph, &query);
+        assert_eq!(result.hops, Some(3));
+    }
+
+    #[test]
+    fn test_not_found() {
+        let graph = create_test_graph();
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("unknown"),
+            max_hops: 5,
+            include_bridges: false,
+            min_followers: 0,
+        };
+
+        let result = compute_distance(&graph, &query);
+        assert_eq!(result.hops, None);
+        assert_eq!(result.path_count, 0);
+    }
+
+    #[test]
+    fn test_max_hops_exceeded() {
+        let graph = create_test_graph();
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("dave"),
+            max_hops: 2, // dave is 3 hops away
+            include_bridges: false,
+            min_followers: 0,
+        };
+
+        let result = compute_distance(&graph, &query);
+        assert_eq!(result.hops, None);
+    }
+
+    #[test]
+    fn test_mutual_follow() {
+        let graph = WotGraph::new();
+        graph.update_follows(&hex("alice"), &[hex("bob")], None, None);
+        graph.update_follows(&hex("bob"), &[hex("alice")], None, None);
+
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("bob"),
+            max_hops: 5,
+            include_bridges: false,
+            min_followers: 0,
+        };
+
+        let result = compute_distance(&graph, &query);
+        assert_eq!(result.hops, Some(1));
+        assert!(result.mutual_follow);
+    }
+
+    #[test]
+    fn test_multi_source_distance() {
+        let graph = create_test_graph();
+        let query = |sources: &[&str], max_hops| MultiSourceQuery {
+            sources: sources.iter().copied().map(pk).collect(),
+            target: pk("dave"),
+            max_hops,
+        };
+
+        let result = compute_multi_source_distance(&graph, &query(&["alice", "eve", "unknown"], 5));
+        assert_eq!(result.hops, Some(2));
+        assert_eq!(result.source, Some(pk("eve")));
+
+        // bob and eve are both 2 hops away; the first listed wins
+        let result = compute_multi_source_distance(&graph, &query(&["eve", "bob"], 5));
+        assert_eq!(result.source, Some(pk("eve")));
+        let result = compute_multi_source_distance(&graph, &query(&["bob", "eve"], 5));
+        assert_eq!(result.source, Some(pk("bob")));
+
+        let result = compute_multi_source_distance(&graph, &query(&["alice", "dave"], 5));
+        assert_eq!((result.hops, result.source), (Some(0), Some(pk("dave"))));
+
+        let result = compute_multi_source_distance(&graph, &query(&["alice"], 2));
+        assert_eq!((result.hops, result.source), (None, None));
+        assert_eq!(result.epoch, graph.epoch());
+    }
+
+    #[test]
+    fn test_set_distances() {
+        let graph = create_test_graph();
+        let query = |from: &[&str], to: &[&str], max_hops| SetDistanceQuery {
+            from: from.iter().copied().map(pk).collect(),
+            to: to.iter().copied().map(pk).collect(),
+            max_hops,
+        };
+
+        // alice -> carol 2, alice -> dave 3, bob -> carol 1, bob -> dave 2; unknown reaches nothing
+        let result = compute_set_distances(&graph, &query(&["alice", "bob", "bob", "unknown"], &["carol", "dave"], 5));
+        assert_eq!((result.from_count, result.to_count, result.pairs), (3, 2, 6));
+        assert_eq!(result.histogram, vec![0, 1, 2, 1, 0, 0]);
+        assert_eq!(result.unreachable, 2);
+        assert_eq!(result.epoch, graph.epoch());
+
+        // Searched backward from the smaller set, with the same distances
+        let result = compute_set_distances(&graph, &query(&["alice", "bob", "eve", "carol"], &["dave"], 2));
+        assert_eq!(result.histogram, vec![0, 1, 2]);
+        assert_eq!(result.unreachable, 1);
+
+        let result = compute_set_distances(&graph, &query(&["carol"], &["carol", "alice"], 3));
+        assert_eq!((result.histogram, result.unreachable), (vec![1, 0, 0, 0], 1));
+    }
+
+    #[test]
+    fn test_min_followers() {
+        let graph = create_test_graph();
+        let query = DistanceQuery {
+            from: pk("alice"),
+            to: pk("dave"),
+            max_hops: 5,
+            include_bridges: true,
+            min_followers: 2,
+        };
+        // bob and eve have one follower each, so nothing connects alice to carol
+        assert_eq!(compute_distance(&graph, &query).hops, None);
+        let path = PathQuery { from: pk("alice"), to: pk("carol"), max_hops: 5, min_followers: 2 };
+        assert!(compute_path(&graph, &path).path.is_none());
+
+        // A second follower lets eve carry paths; bob still doesn't
+        graph.update_follows(&hex("frank"), &[hex("eve")], None, None);
+        let result = compute_distance(&graph, &query);
+        assert_eq!((result.hops, result.path_count), (Some(3), 1));
+        assert_eq!(compute_path(&graph, &path).path.unwrap()[0], pk("eve"));
+
+        // Endpoints are exempt: dave has a single follower
+        let query = DistanceQuery { to: pk("carol"), ..query };
+        assert_eq!(compute_distance(&graph, &query).hops, Some(2));
+    }
+
+    #[test]
+    fn test_expansion_limit() {
+        // hub follows ten accounts that each follow target
+        let graph = WotGraph::new();
+        let middle: Vec<String> = (0..10).map(|i| hex(&format!("m{}", i))).collect();
+        graph.update_follows(&hex("hub"), &middle, None, None);
+        for m in &middle {
+            graph.update_follows(m, &[hex("target")], None, None);
+        }
+        let query = DistanceQuery {
+            from: pk("hub"),
+            to: pk("target"),
+            max_hops: 5,
+            include_bridges: true,
+            min_followers: 0,
+        };
+        let path = PathQuery { from: pk("hub"), to: pk("target"), max_hops: 5, min_followers: 0 };
+        let result = compute_distance(&graph, &query);
+        assert_eq!((result.hops, result.path_count, result.truncated), (Some(2), 10, false));
+        assert!(!compute_path(&graph, &path).truncated);
+
+        // Only an evenly spaced two of the hub's follows and target's followers are walked
+        graph.set_limits(crate::graph::GraphLimits { expansion_limit: 2, ..Default::default() });
+        let result = compute_distance(&graph, &query);
+        assert_eq!((result.hops, result.path_count, result.truncated), (Some(2), 2, true));
+        assert_eq!(result.bridges.unwrap().len(), 2);
+        let result = compute_path(&graph, &path);
+        assert!(result.truncated);
+        assert!(result.path.is_some());
+    }
+
+    #[test]
+    fn test_quarantined_source_excluded() {
+        let graph = create_test_graph();
+        let spam_follows: Vec<String> = ["alice", "bob", "eve"].into_iter().map(hex).collect();
+        graph.update_follows(&hex("spam"), &spam_follows, None, None);
+        let query = DistanceQuery {
+            from: pk("spam"),
+            to: pk("carol"),
+            max_hops: 5,
+            include_bridges: false,
+            min_followers: 0,
+        };
+        assert_eq!(compute_distance(&graph, &query).hops, Some(2));
+
+        graph.set_limits(crate::graph::GraphLimits { quarantine_min_follows: 3, ..Default::default() });
+        assert_eq!(compute_distance(&graph, &query).hops, None);
+        let path = PathQuery { from: pk("spam"), to: pk("carol"), max_hops: 5, min_followers: 0 };
+        assert!(compute_path(&graph, &path).path.is_none());
+        let multi = MultiSourceQuery {
+            sources: vec![pk("spam")],
+            target: pk("carol"),
+            max_hops: 5,
+        };
+        assert_eq!(compute_multi_source_distance(&graph, &multi).hops, None);
+
+        // Other queries are unaffected
+        let query = DistanceQuery { from: pk("alice"), ..query };
+        assert_eq!(compute_distance(&graph, &query).hops, Some(2));
+    }
+
+    #[test]
+    fn test_multiple_queries_reuse_state() {
+        // Verify that multiple queries work correctly with state reuse
+        let graph = create_test_graph();
+
+        for _ in 0..10 {
+            let query1 = DistanceQuery {
+                from: pk("alice"),
+                to: pk("carol"),
+                max_hops: 5,
+                include_bridges: false,
+                min_followers: 0,
+            };
+            let result1 = compute_distance(&graph, &query1);
+            assert_eq!(result1.hops, Some(2));
+
+            let query2 = DistanceQuery {
+                from: pk("alice"),
+                to: pk("dave"),
+                max_hops: 5,
+                include_bridges: false,
+                min_followers: 0,
+            };
+            let result2 = compute_distance(&graph, &query2);
+            assert_eq!(result2.hops, Some(3));
+        }
+    }
+
+    #[test]
+    fn test_hop_policy() {
+        let graph = create_test_graph();
+        // carol has two followers; alice follows two
+        let policy = HopPolicy { degree_threshold: 2, max_hops: 2 };
+
+        let (max_hops, applied) = policy.apply(&graph, &pk("bob"), &pk("carol"), 5);
+        assert_eq!(max_hops, 2);
+        assert_eq!(applied, Some(AdaptiveHops { requested_max_hops: 5, max_hops: 2, reason: "to" }));
+        assert_eq!(policy.apply(&graph, &pk("alice"), &pk("carol"), 4).1.unwrap().reason, "both");
+
+        // Already within the cap, or no super-node involved
+        assert_eq!(policy.apply(&graph, &pk("alice"), &pk("carol"), 2), (2, None));
+        assert_eq!(policy.apply(&graph, &pk("bob"), &pk("dave"), 5), (5, None));
+        assert_eq!(policy.apply(&graph, &pk("bob"), &pk("unknown"), 5), (5, None));
+
+        let disabled = HopPolicy { degree_threshold: 0, max_hops: 2 };
+        assert_eq!(disabled.apply(&graph, &pk("alice"), &pk("carol"), 5), (5, None));
+    }
+}
@@ -0,0 +1,173 @@
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+
+use super::{Neighbors, NodeId, WotGraph};
+
+/// Membership of the largest weakly connected component (follow direction ignored),
+/// recomputed in the background. Nodes outside it cannot reach or be reached by most of the graph.
+pub struct Components {
+    largest: RwLock<Arc<Vec<bool>>>,
+    // Graph epoch the membership was computed at (u64::MAX = never)
+    epoch: AtomicU64,
+}
+
+impl Components {
+    pub fn new() -> Self {
+        Self {
+            largest: RwLock::new(Arc::new(Vec::new())),
+            epoch: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Whether a node is in the largest component, or None if it was added after the last computation
+    pub fn in_largest(&self, node_id: NodeId) -> Option<bool> {
+        self.largest.read().get(node_id as usize).copied()
+    }
+
+    /// Recompute membership if the graph changed since the last run.
+    /// CPU-bound: call from a blocking thread.
+    pub fn refresh(&self, graph: &WotGraph) -> bool {
+        let epoch = graph.epoch();
+        if self.epoch.load(Ordering::Acquire) == epoch {
+            return false;
+        }
+
+        let start = Instant::now();
+        let largest = graph.with_adjacency(|follows, _| largest_component(follows));
+        info!(
+            "Largest component computed: {} of {} nodes in {:?}",
+            largest.iter().filter(|&&member| member).count(),
+            largest.len(),
+            start.elapsed()
+        );
+
+        *self.largest.write() = Arc::new(largest);
+        self.epoch.store(epoch, Ordering::Release);
+        true
+    }
+}
+
+impl Default for Components {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Union-find over follow edges; returns per-node membership of the largest component.
+/// Ties go to the component containing the lowest node ID.
+pub fn largest_component(follows: &Neighbors) -> Vec<bool> {
+    let (roots, size) = union_roots(follows);
+    let mut best: Option<(NodeId, u32)> = None;
+    for &root in &roots {
+        let root_size = size[root as usize];
+        if best.is_none_or(|(_, best_size)| root_size > best_size) {
+            best = Some((root, root_size));
+        }
+    }
+
+    match best {
+        Some((largest, _)) => roots.iter().map(|&root| root == largest).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Sizes of all weakly connected components, largest first; isolated nodes count as
+/// components of one
+pub fn component_sizes(follows: &Neighbors) -> Vec<u32> {
+    let (roots, size) = union_roots(follows);
+    let mut sizes: Vec<u32> =
+        roots.iter().enumerate().filter(|&(x, &root)| x as NodeId == root).map(|(_, &root)| size[root as usize]).collect();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes
+}
+
+/// The component root of every node, and component sizes indexed by root
+fn union_roots(follows: &Neighbors) -> (Vec<NodeId>, Vec<u32>) {
+    let n = follows.len();
+    let mut parent: Vec<NodeId> = (0..n as NodeId).collect();
+    let mut size = vec![1u32; n];
+
+    fn find(parent: &mut [NodeId], mut x: NodeId) -> NodeId {
+        while parent[x as usize] != x {
+            // Path halving
+            parent[x as usize] = parent[parent[x as usize] as usize];
+            x = parent[x as usize];
+        }
+        x
+    }
+
+    for (from, list) in follows.iter().enumerate() {
+        for &to in list {
+            if to as usize >= n {
+                continue;
+            }
+            let (a, b) = (find(&mut parent, from as NodeId), find(&mut parent, to));
+            if a == b {
+                continue;
+            }
+            let (big, small) = if size[a as usize] >= size[b as usize] { (a, b) } else { (b, a) };
+            parent[small as usize] = big;
+            size[big as usize] += size[small as usize];
+        }
+    }
+
+    let roots = (0..n as NodeId).map(|x| find(&mut parent, x)).collect();
+    (roots, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    #[test]
+    fn test_largest_component() {
+        // 0 -> 1, 2 -> 1 (one component via shared followee), 3 -> 4, 5 isolated
+        let graph = WotGraph::from_lists(&[&[1], &[], &[1], &[4], &[], &[]]);
+        assert_eq!(
+            graph.with_adjacency(|follows, _| largest_component(follows)),
+            vec![true, true, true, false, false, false]
+        );
+        assert!(WotGraph::new().with_adjacency(|follows, _| largest_component(follows)).is_empty());
+    }
+
+    #[test]
+    fn test_component_sizes() {
+        let graph = WotGraph::from_lists(&[&[1], &[], &[1], &[4], &[], &[]]);
+        assert_eq!(graph.with_adjacency(|follows, _| component_sizes(follows)), vec![3, 2, 1]);
+        assert!(WotGraph::new().with_adjacency(|follows, _| component_sizes(follows)).is_empty());
+    }
+
+    #[test]
+    fn test_refresh_tracks_epoch() {
+        let graph = WotGraph::new();
+        let components = Components::new();
+        assert!(components.refresh(&graph));
+        assert!(!components.refresh(&graph));
+
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("carol")], None, Some(1));
+        graph.update_follows(&hex("dave"), &[hex("erin")], None, Some(1));
+        assert!(components.refresh(&graph));
+
+        let carol = graph.node_id(&pk("carol")).unwrap();
+        let erin = graph.node_id(&pk("erin")).unwrap();
+        assert_eq!(components.in_largest(carol), Some(true));
+        assert_eq!(components.in_largest(erin), Some(false));
+        assert_eq!(components.in_largest(NodeId::MAX), None);
+    }
+}
@@ -0,0 +1,308 @@
+//! Structure of a pubkey's ego network: the pubkeys it reaches along follows within a few hops.
+
+use rustc_hash::FxHashMap;
+
+use super::{Neighbors, NodeId, WotGraph};
+
+/// Owner of a pubkey reached through more than one follow
+const SHARED: NodeId = NodeId::MAX;
+
+/// Most pubkeys [`cut_vertices`] takes into an ego network, the ego included; the BFS stops
+/// adding pubkeys past it
+pub const EGO_MAX_NODES: usize = 50_000;
+
+/// A follow the ego network depends on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeakTie {
+    pub follow: NodeId,
+    /// Pubkeys beyond the follows whose every shortest path from the ego runs through this follow
+    pub exclusive: usize,
+    /// No other follow of the ego follows this one, so dropping the edge takes it past two hops
+    pub local_bridge: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WeakTies {
+    /// Pubkeys within `max_hops`, the ego itself excluded
+    pub reached: usize,
+    /// Follows with an exclusive region or a local bridge, most exclusive pubkeys first
+    pub ties: Vec<WeakTie>,
+}
+
+/// Which of `ego`'s follows are its only way into parts of its `max_hops` network. One BFS from
+/// `ego` labels each pubkey with the follow its shortest paths start with, or as shared once
+/// they start with different follows, so a follow's exclusive region is what a shortest path
+/// can only reach through it.
+pub fn weak_ties(graph: &WotGraph, ego: NodeId, max_hops: u8) -> WeakTies {
+    graph.with_adjacency(|follows, followers| {
+        let Some(own) = follows.get(ego as usize) else {
+            return WeakTies::default();
+        };
+        let owners = label_owners(follows, ego, max_hops);
+
+        let mut exclusive: FxHashMap<NodeId, usize> = FxHashMap::default();
+        for (&node, &(owner, hops)) in &owners {
+            if hops >= 2 && owner != SHARED && node != ego {
+                *exclusive.entry(owner).or_default() += 1;
+            }
+        }
+
+        let mut ties: Vec<WeakTie> = own
+            .iter()
+            .filter(|&&follow| follow != ego)
+            .filter_map(|&follow| {
+                let exclusive = exclusive.get(&follow).copied().unwrap_or(0);
+                // Scan whichever side is shorter: the follow's followers, or the ego's follows
+                let theirs = followers.get(follow as usize).unwrap_or(&[]);
+                let local_bridge = if theirs.len() < own.len() {
+                    !theirs.iter().any(|&g| g != ego && g != follow && own.binary_search(&g).is_ok())
+                } else {
+                    !own.iter().any(|&g| {
+                        g != follow && g != ego && follows.get(g as usize).is_some_and(|list| list.binary_search(&follow).is_ok())
+                    })
+                };
+                (exclusive > 0 || local_bridge).then_some(WeakTie { follow, exclusive, local_bridge })
+            })
+            .collect();
+        ties.sort_unstable_by(|a, b| {
+            b.exclusive.cmp(&a.exclusive).then(b.local_bridge.cmp(&a.local_bridge)).then(a.follow.cmp(&b.follow))
+        });
+
+        WeakTies { reached: owners.keys().filter(|&&node| node != ego).count(), ties }
+    })
+}
+
+/// `(owner, hops)` for every node within `max_hops` of `ego`, where the owner is the follow
+/// all of the node's shortest paths start with, or `SHARED`
+fn label_owners(follows: &Neighbors, ego: NodeId, max_hops: u8) -> FxHashMap<NodeId, (NodeId, u8)> {
+    let mut owners: FxHashMap<NodeId, (NodeId, u8)> = FxHashMap::default();
+    owners.insert(ego, (SHARED, 0));
+    let mut current: Vec<NodeId> = Vec::new();
+    for &follow in &follows[ego as usize] {
+        if owners.insert(follow, (follow, 1)).is_none() {
+            current.push(follow);
+        }
+    }
+
+    let mut next = Vec::new();
+    for hops in 2..=max_hops {
+        for &node in &current {
+            let owner = owners[&node].0;
+            for &neighbor in &follows[node as usize] {
+                match owners.get_mut(&neighbor) {
+                    None => {
+                        owners.insert(neighbor, (owner, hops));
+                        next.push(neighbor);
+                    }
+                    Some((other, at)) if *at == hops && *other != owner => *other = SHARED,
+                    Some(_) => {}
+                }
+            }
+        }
+        current.clear();
+        std::mem::swap(&mut current, &mut next);
+    }
+    owners
+}
+
+/// A pubkey the ego network falls apart without
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutVertex {
+    pub node: NodeId,
+    /// Hops from the ego along follows
+    pub hops: u8,
+    /// Pubkeys cut off from the ego when this one is removed
+    pub separated: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CutVertices {
+    /// Pubkeys in the ego network, the ego itself excluded
+    pub nodes: usize,
+    /// The network hit `EGO_MAX_NODES` before reaching `max_hops`
+    pub truncated: bool,
+    /// Most separated pubkeys first
+    pub cut_vertices: Vec<CutVertex>,
+}
+
+/// Articulation points of `ego`'s network: the pubkeys within `max_hops` along follows, joined
+/// by every follow between them with direction ignored. Removing one disconnects part of the
+/// network from the ego; the ego itself is not reported.
+pub fn cut_vertices(graph: &WotGraph, ego: NodeId, max_hops: u8) -> CutVertices {
+    graph.with_adjacency(|follows, followers| {
+        if follows.get(ego as usize).is_none() {
+            return CutVertices::default();
+        }
+
+        // Members in BFS order, so the ego is local index 0
+        let mut members: Vec<(NodeId, u8)> = vec![(ego, 0)];
+        let mut index: FxHashMap<NodeId, u32> = FxHashMap::default();
+        index.insert(ego, 0);
+        let (mut start, mut truncated) = (0, false);
+        'bfs: for hops in 1..=max_hops {
+            let end = members.len();
+            for i in start..end {
+                for &neighbor in &follows[members[i].0 as usize] {
+                    if index.contains_key(&neighbor) {
+                        continue;
+                    }
+                    if members.len() >= EGO_MAX_NODES {
+                        truncated = true;
+                        break 'bfs;
+                    }
+                    index.insert(neighbor, members.len() as u32);
+                    members.push((neighbor, hops));
+                }
+            }
+            start = end;
+        }
+
+        let adjacency: Vec<Vec<u32>> = members
+            .iter()
+            .map(|&(node, _)| {
+                let mut local: Vec<u32> = follows[node as usize]
+                    .iter()
+                    .chain(followers[node as usize].iter())
+                    .filter_map(|neighbor| index.get(neighbor).copied())
+                    .collect();
+                local.sort_unstable();
+                local.dedup();
+                local
+            })
+            .collect();
+
+        let separated = articulation_points(&adjacency);
+        let mut cut_vertices: Vec<CutVertex> = separated
+            .into_iter()
+            .enumerate()
+            .skip(1)
+            .filter(|&(_, count)| count > 0)
+            .map(|(i, count)| CutVertex { node: members[i].0, hops: members[i].1, separated: count })
+            .collect();
+        cut_vertices.sort_unstable_by(|a, b| b.separated.cmp(&a.separated).then(a.node.cmp(&b.node)));
+
+        CutVertices { nodes: members.len() - 1, truncated, cut_vertices }
+    })
+}
+
+/// For each vertex of a connected undirected graph, how many vertices lose their connection to
+/// vertex 0 when it is removed: nonzero exactly for the articulation points other than 0.
+/// Iterative Hopcroft-Tarjan DFS, so deep networks don't overflow the stack.
+fn articulation_points(adjacency: &[Vec<u32>]) -> Vec<usize> {
+    let n = adjacency.len();
+    let mut separated = vec![0usize; n];
+    if n == 0 {
+        return separated;
+    }
+    let (mut disc, mut low) = (vec![u32::MAX; n], vec![0u32; n]);
+    let (mut parent, mut size) = (vec![u32::MAX; n], vec![1usize; n]);
+    disc[0] = 0;
+    let mut time = 1;
+    let mut stack: Vec<(u32, usize)> = vec![(0, 0)];
+
+    while let Some(&mut (v, ref mut next)) = stack.last_mut() {
+        let v = v as usize;
+        if let Some(&w) = adjacency[v].get(*next) {
+            *next += 1;
+            let w = w as usize;
+            if disc[w] == u32::MAX {
+                parent[w] = v as u32;
+                disc[w] = time;
+                low[w] = time;
+                time += 1;
+                stack.push((w as u32, 0));
+            } else if w as u32 != parent[v] {
+                low[v] = low[v].min(disc[w]);
+            }
+            continue;
+        }
+        stack.pop();
+        if let Some(&(p, _)) = stack.last() {
+            let p = p as usize;
+            low[p] = low[p].min(low[v]);
+            size[p] += size[v];
+            // v's subtree has no edge around p, so it hangs off p alone
+            if p != 0 && low[v] >= disc[p] {
+                separated[p] += size[v];
+            }
+        }
+    }
+    separated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    #[test]
+    fn test_weak_ties() {
+        let graph = WotGraph::new();
+        let follows = |pks: &[&str]| pks.iter().map(|name| hex(name)).collect::<Vec<_>>();
+        // me follows a, b and c; b also follows c. Only a leads to x and z and only c to y,
+        // while both b and c lead to shared
+        graph.update_follows(&hex("me"), &follows(&["a", "b", "c"]), None, Some(1));
+        graph.update_follows(&hex("a"), &follows(&["x", "me"]), None, Some(1));
+        graph.update_follows(&hex("b"), &follows(&["c", "shared"]), None, Some(1));
+        graph.update_follows(&hex("c"), &follows(&["shared", "y"]), None, Some(1));
+        graph.update_follows(&hex("x"), &follows(&["z"]), None, Some(1));
+        let id = |name: &str| graph.node_id(&pk(name)).unwrap();
+
+        let ties = weak_ties(&graph, id("me"), 3);
+        assert_eq!(ties.reached, 7);
+        assert_eq!(
+            ties.ties,
+            vec![
+                WeakTie { follow: id("a"), exclusive: 2, local_bridge: true },
+                WeakTie { follow: id("c"), exclusive: 1, local_bridge: false },
+                WeakTie { follow: id("b"), exclusive: 0, local_bridge: true },
+            ]
+        );
+
+        // Within two hops, z is out of reach
+        assert_eq!(weak_ties(&graph, id("me"), 2).ties[0].exclusive, 1);
+        assert_eq!(weak_ties(&graph, id("z"), 3), WeakTies::default());
+    }
+
+    #[test]
+    fn test_articulation_points() {
+        // 0 - 1 - 2 - 3, with 1 - 4 - 2 closing a cycle and 5 hanging off 3
+        let adjacency = vec![vec![1], vec![0, 2, 4], vec![1, 3, 4], vec![2, 5], vec![1, 2], vec![3]];
+        assert_eq!(articulation_points(&adjacency), vec![0, 4, 2, 1, 0, 0]);
+        assert!(articulation_points(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_cut_vertices() {
+        let graph = WotGraph::new();
+        let follows = |pks: &[&str]| pks.iter().map(|name| hex(name)).collect::<Vec<_>>();
+        // me follows a and b, which follow each other; only a follows x, whose follower y
+        // is also followed by b
+        graph.update_follows(&hex("me"), &follows(&["a", "b"]), None, Some(1));
+        graph.update_follows(&hex("a"), &follows(&["b", "x"]), None, Some(1));
+        graph.update_follows(&hex("b"), &follows(&["a", "y"]), None, Some(1));
+        graph.update_follows(&hex("x"), &follows(&["z"]), None, Some(1));
+        graph.update_follows(&hex("y"), &follows(&["x"]), None, Some(1));
+        let id = |name: &str| graph.node_id(&pk(name)).unwrap();
+
+        let found = cut_vertices(&graph, id("me"), 3);
+        assert_eq!((found.nodes, found.truncated), (5, false));
+        assert_eq!(found.cut_vertices, vec![CutVertex { node: id("x"), hops: 2, separated: 1 }]);
+
+        // Within two hops z is left out, and nothing else hangs off one pubkey
+        assert!(cut_vertices(&graph, id("me"), 2).cut_vertices.is_empty());
+        assert_eq!(cut_vertices(&graph, id("z"), 3), CutVertices::default());
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet};
+
+use super::NodeId;
+
+/// LSH bands per signature, and MinHash rows per band. Two lists with Jaccard similarity `s`
+/// share a band with probability `1 - (1 - s^ROWS)^BANDS`: about 98% at 0.8, 40% at 0.5.
+const BANDS: usize = 8;
+const ROWS: usize = 4;
+const SIGNATURE_LEN: usize = BANDS * ROWS;
+
+/// Shorter follow lists aren't indexed: new accounts following the same onboarding
+/// suggestions would all look alike
+pub const MIN_INDEXED_FOLLOWS: usize = 10;
+
+/// Candidates collected per query, so a large cluster of clones stays cheap to look up
+const MAX_CANDIDATES: usize = 10_000;
+
+type Signature = [u32; SIGNATURE_LEN];
+
+/// MinHash signatures of follow lists, bucketed by band (LSH), to find accounts following
+/// near-identical sets without comparing every pair. Kept up to date by [`super::WotGraph`]
+/// as follow lists change; candidates still need their exact similarity checked.
+#[derive(Default)]
+pub struct LookalikeIndex {
+    signatures: HashMap<NodeId, Signature>,
+    /// Per band: hash of the band's rows -> nodes with those rows
+    buckets: [HashMap<u64, Vec<NodeId>>; BANDS],
+}
+
+impl LookalikeIndex {
+    /// Re-index a node for its new (sorted) follow list
+    pub fn update(&mut self, node: NodeId, follows: &[NodeId]) {
+        self.remove(node);
+        if follows.len() < MIN_INDEXED_FOLLOWS {
+            return;
+        }
+        let signature = signature(follows);
+        for (band, buckets) in self.buckets.iter_mut().enumerate() {
+            buckets.entry(band_key(&signature, band)).or_default().push(node);
+        }
+        self.signatures.insert(node, signature);
+    }
+
+    pub fn remove(&mut self, node: NodeId) {
+        let Some(signature) = self.signatures.remove(&node) else {
+            return;
+        };
+        for (band, buckets) in self.buckets.iter_mut().enumerate() {
+            let key = band_key(&signature, band);
+            if let Some(nodes) = buckets.get_mut(&key) {
+                if let Some(pos) = nodes.iter().position(|&n| n == node) {
+                    nodes.swap_remove(pos);
+                }
+                if nodes.is_empty() {
+                    buckets.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Nodes sharing a band with `node`, i.e. likely to follow a similar set
+    pub fn candidates(&self, node: NodeId) -> Vec<NodeId> {
+        let Some(signature) = self.signatures.get(&node) else {
+            return Vec::new();
+        };
+        let mut candidates = HashSet::new();
+        for (band, buckets) in self.buckets.iter().enumerate() {
+            if let Some(nodes) = buckets.get(&band_key(signature, band)) {
+                candidates.extend(nodes.iter().copied().filter(|&n| n != node).take(MAX_CANDIDATES));
+            }
+            if candidates.len() >= MAX_CANDIDATES {
+                break;
+            }
+        }
+        candidates.into_iter().collect()
+    }
+
+    /// Indexed nodes whose signatures agree with `node`'s in the most rows, best first, with the
+    /// rows they share. Agreeing rows over `SIGNATURE_LEN` estimates Jaccard similarity, so
+    /// unlike [`candidates`](Self::candidates) this also ranks lists far below the LSH
+    /// threshold, at the cost of scanning every signature.
+    pub fn most_similar(&self, node: NodeId, count: usize) -> Vec<(NodeId, usize)> {
+        let Some(own) = self.signatures.get(&node) else {
+            return Vec::new();
+        };
+        let mut matches: Vec<(NodeId, usize)> = self
+            .signatures
+            .iter()
+            .filter(|&(&other, _)| other != node)
+            .map(|(&other, signature)| (other, own.iter().zip(signature).filter(|(a, b)| a == b).count()))
+            .filter(|&(_, rows)| rows > 0)
+            .collect();
+        let by_rows = |a: &(NodeId, usize), b: &(NodeId, usize)| b.1.cmp(&a.1).then(a.0.cmp(&b.0));
+        if matches.len() > count {
+            matches.select_nth_unstable_by(count, by_rows);
+            matches.truncate(count);
+        }
+        matches.sort_unstable_by(by_rows);
+        matches
+    }
+
+    /// Indexed nodes
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+}
+
+/// Jaccard similarity of two sorted, deduplicated lists
+pub fn jaccard(a: &[NodeId], b: &[NodeId]) -> f64 {
+    let shared = shared_count(a, b);
+    let union = a.len() + b.len() - shared;
+    if union == 0 {
+        return 0.0;
+    }
+    shared as f64 / union as f64
+}
+
+/// Entries two sorted, deduplicated lists have in common
+pub fn shared_count(a: &[NodeId], b: &[NodeId]) -> usize {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    shared
+}
+
+fn signature(follows: &[NodeId]) -> Signature {
+    let mut signature = [u32::MAX; SIGNATURE_LEN];
+    for &followed in follows {
+        for (row, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(row_hash(followed, row));
+        }
+    }
+    signature
+}
+
+/// One of the MinHash permutations: splitmix64 of the node ID and row
+fn row_hash(node: NodeId, row: usize) -> u32 {
+    (mix(node.rotate_left(32) ^ row as u64) >> 32) as u32
+}
+
+fn band_key(signature: &Signature, band: usize) -> u64 {
+    signature[band * ROWS..(band + 1) * ROWS]
+        .iter()
+        .fold(band as u64, |acc, &row| mix(acc ^ row as u64))
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jaccard() {
+        assert_eq!(jaccard(&[1, 2, 3, 4], &[2, 3, 4, 5]), 0.6);
+        assert_eq!(jaccard(&[1, 2], &[1, 2]), 1.0);
+        assert_eq!(jaccard(&[], &[]), 0.0);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut index = LookalikeIndex::default();
+        let base: Vec<NodeId> = (100..200).collect();
+        let clone: Vec<NodeId> = (100..199).chain([500]).collect();
+        let unrelated: Vec<NodeId> = (1000..1100).collect();
+        index.update(1, &base);
+        index.update(2, &clone);
+        index.update(3, &unrelated);
+        index.update(4, &[100, 101]);
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.candidates(1), vec![2]);
+        assert!(index.candidates(3).is_empty());
+        assert!(index.candidates(4).is_empty());
+
+        // Re-indexing replaces the old buckets
+        index.update(2, &unrelated);
+        assert_eq!(index.candidates(1), Vec::<NodeId>::new());
+        assert_eq!(index.candidates(3), vec![2]);
+        index.remove(2);
+        assert!(index.candidates(3).is_empty());
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_most_similar() {
+        let mut index = LookalikeIndex::default();
+        let base: Vec<NodeId> = (0..200).collect();
+        index.update(1, &base);
+        index.update(2, &(0..150).collect::<Vec<NodeId>>()); // similarity 0.75
+        index.update(3, &(100..300).collect::<Vec<NodeId>>()); // 0.33, below the LSH threshold
+        index.update(4, &(1000..1200).collect::<Vec<NodeId>>());
+
+        assert!(!index.candidates(1).contains(&3));
+        let ranked: Vec<NodeId> = index.most_similar(1, 10).into_iter().map(|(node, _)| node).collect();
+        assert_eq!(ranked, vec![2, 3]);
+        assert_eq!(index.most_similar(1, 1).len(), 1);
+        assert!(index.most_similar(4, 10).is_empty());
+        assert!(index.most_similar(9, 10).is_empty());
+    }
+}
@@ -41,30 +41,32 @@ impl LockMetrics {
     }
 
     pub fn snapshot(&self) -> LockMetricsSnapshot {
-        let write_count = self.write_lock_count.load(Ordering::Relaxed);
-        let write_total_ns = self.write_lock_total_ns.load(Ordering::Relaxed);
-        let read_count = self.read_lock_count.load(Ordering::Relaxed);
-        let read_total_ns = self.read_lock_total_ns.load(Ordering::Relaxed);
+        Self::combined([self])
+    }
+
+    /// One snapshot over several locks: counts and averages across all, the largest max
+    pub fn combined<'a>(metrics: impl IntoIterator<Item = &'a LockMetrics>) -> LockMetricsSnapshot {
+        let (mut write_count, mut write_total_ns, mut write_max_ns) = (0u64, 0u64, 0u64);
+        let (mut read_count, mut read_total_ns, mut read_max_ns) = (0u64, 0u64, 0u64);
+        for m in metrics {
+            write_count += m.write_lock_count.load(Ordering::Relaxed);
+            write_total_ns += m.write_lock_total_ns.load(Ordering::Relaxed);
+            write_max_ns = write_max_ns.max(m.write_lock_max_ns.load(Ordering::Relaxed));
+            read_count += m.read_lock_count.load(Ordering::Relaxed);
+            read_total_ns += m.read_lock_total_ns.load(Ordering::Relaxed);
+            read_max_ns = read_max_ns.max(m.read_lock_max_ns.load(Ordering::Relaxed));
+        }
 
         LockMetricsSnapshot {
             write_lock_count: write_count,
-            write_lock_avg_us: if write_count > 0 {
-                (write_total_ns / write_count) / 1000
-            } else {
-                0
-            },
-            write_lock_max_us: self.write_lock_max_ns.load(Ordering::Relaxed) / 1000,
+            write_lock_avg_us: write_total_ns.checked_div(write_count).unwrap_or(0) / 1000,
+            write_lock_max_us: write_max_ns / 1000,
             read_lock_count: read_count,
-            read_lock_avg_us: if read_count > 0 {
-                (read_total_ns / read_count) / 1000
-            } else {
-                0
-            },
-            read_lock_max_us: self.read_lock_max_ns.load(Ordering::Relaxed) / 1000,
+            read_lock_avg_us: read_total_ns.checked_div(read_count).unwrap_or(0) / 1000,
+            read_lock_max_us: read_max_ns / 1000,
         }
     }
 
-    #[allow(dead_code)] // Public API for metrics management after warmup
     pub fn reset(&self) {
         self.write_lock_count.store(0, Ordering::Relaxed);
         self.write_lock_total_ns.store(0, Ordering::Relaxed);
@@ -82,6 +84,7 @@ impl Default for LockMetrics {
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct LockMetricsSnapshot {
     pub write_lock_count: u64,
     pub write_lock_avg_us: u64,
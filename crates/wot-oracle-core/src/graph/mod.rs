@@ -0,0 +1,19 @@
+pub mod store;
+pub mod adjacency;
+pub mod bfs;
+pub mod metrics;
+pub mod pagerank;
+pub mod components;
+pub mod anchors;
+pub mod lookalikes;
+pub mod ego;
+#[cfg(feature = "synth")]
+pub mod synth;
+
+pub use store::{is_quarantined, FollowChanges, GraphLimits, NodeId, NodeIdsExhausted, WotGraph};
+pub use adjacency::{Adjacency, Neighbors, ADJACENCY_SHARDS};
+pub use metrics::LockMetricsSnapshot;
+pub use pagerank::PageRank;
+pub use components::Components;
+pub use anchors::{trust_score, Anchors};
+pub use ego::{CutVertex, CutVertices, WeakTie, WeakTies};
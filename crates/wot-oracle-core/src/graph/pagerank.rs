@@ -0,0 +1,420 @@
+use parking_lot::{Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+
+use super::{FollowChanges, Neighbors, NodeId, WotGraph};
+
+const DAMPING: f64 = 0.85;
+const MAX_ITERATIONS: usize = 50;
+// L1 change between iterations at which scores are considered converged
+const TOLERANCE: f64 = 1e-6;
+// Score thresholds kept for percentile lookups (0.1% resolution)
+const QUANTILES: usize = 1000;
+// Residual a node may keep without pushing it on in `update`, as a share of the average score
+const PUSH_TOLERANCE: f64 = 1e-3;
+// Follow edges an update may push along before it gives up for a full computation
+const PUSH_MAX_EDGES: usize = 50_000_000;
+
+/// Global PageRank over the follow graph, recomputed in the background.
+/// Scores sum to 1 across all nodes; a follow passes rank to the followed pubkey.
+pub struct PageRank {
+    scores: RwLock<Arc<Vec<f64>>>,
+    // Evenly spaced thresholds over the sorted scores, so percentiles don't need a full sort
+    quantiles: RwLock<Arc<Vec<f64>>>,
+    // Graph epoch the current scores were computed or updated to (u64::MAX = never)
+    epoch: AtomicU64,
+    // Graph epoch of the last full computation, which updates only approximate
+    full_epoch: AtomicU64,
+    // Held through each computation or update, so one never overwrites another's newer scores
+    computing: Mutex<()>,
+}
+
+impl PageRank {
+    pub fn new() -> Self {
+        Self {
+            scores: RwLock::new(Arc::new(Vec::new())),
+            quantiles: RwLock::new(Arc::new(Vec::new())),
+            epoch: AtomicU64::new(u64::MAX),
+            full_epoch: AtomicU64::new(u64::MAX),
+            computing: Mutex::new(()),
+        }
+    }
+
+    /// Score for a node, or None if it was added after the last computation
+    pub fn score(&self, node_id: NodeId) -> Option<f64> {
+        self.scores.read().get(node_id as usize).copied()
+    }
+
+    /// Share of nodes ranking below this one (0-1, to 0.1%), or None if it was added after
+    /// the last computation
+    pub fn percentile(&self, node_id: NodeId) -> Option<f64> {
+        let score = self.score(node_id)?;
+        let quantiles = self.quantiles.read();
+        Some(quantiles.partition_point(|&q| q < score) as f64 / quantiles.len() as f64)
+    }
+
+    /// All scores from the last computation, indexed by node ID
+    pub fn snapshot(&self) -> Arc<Vec<f64>> {
+        self.scores.read().clone()
+    }
+
+    /// The `n` highest-ranked nodes from the last computation, best first
+    pub fn top(&self, n: usize) -> Vec<(NodeId, f64)> {
+        top_n(&self.snapshot(), n, None)
+    }
+
+    /// Graph epoch the scores were computed or updated to, or None before the first computation
+    pub fn epoch(&self) -> Option<u64> {
+        Some(self.epoch.load(Ordering::Acquire)).filter(|&epoch| epoch != u64::MAX)
+    }
+
+    /// Recompute scores if the graph changed since the last full computation.
+    /// CPU-bound: call from a blocking thread.
+    pub fn refresh(&self, graph: &WotGraph) -> bool {
+        let _computing = self.computing.lock();
+        if self.full_epoch.load(Ordering::Acquire) == graph.epoch() {
+            return false;
+        }
+        // Follow-list changes from here on are for `update`
+        let epoch = graph.with_snapshot(|epoch, _, _| {
+            graph.take_changes();
+            epoch
+        });
+
+        let start = Instant::now();
+        let scores = graph.with_adjacency(|follows, _| compute(follows));
+        info!(
+            "PageRank computed for {} nodes in {:?}",
+            scores.len(),
+            start.elapsed()
+        );
+
+        *self.quantiles.write() = Arc::new(quantiles(&scores));
+        *self.scores.write() = Arc::new(scores);
+        self.epoch.store(epoch, Ordering::Release);
+        self.full_epoch.store(epoch, Ordering::Release);
+        true
+    }
+
+    /// Bring the scores up to date with the follow-list changes since the last computation or
+    /// update, pushing the rank each change moves along follows instead of recomputing. Scores
+    /// stay within about 0.1% of the average score of a full computation, except that rank
+    /// from pubkeys following nobody, spread over everyone, is only rebalanced by the next full
+    /// one; percentiles keep its thresholds too. Falls back to [`refresh`](Self::refresh) when
+    /// there are no scores yet or the changes are too many to track.
+    /// CPU-bound: call from a blocking thread.
+    pub fn update(&self, graph: &WotGraph) -> bool {
+        let computing = self.computing.lock();
+        if self.epoch.load(Ordering::Acquire) == graph.epoch() {
+            return false;
+        }
+        let (epoch, changes) = graph.with_snapshot(|epoch, _, _| (epoch, graph.take_changes()));
+        let scores = self.snapshot();
+
+        let start = Instant::now();
+        let updated = match changes {
+            Some(changes) if !scores.is_empty() => graph.with_adjacency(|follows, _| push_changes(follows, &scores, &changes)),
+            _ => None,
+        };
+        let Some(updated) = updated else {
+            drop(computing);
+            return self.refresh(graph);
+        };
+        info!("PageRank updated for {} nodes in {:?}", updated.len(), start.elapsed());
+
+        *self.scores.write() = Arc::new(updated);
+        self.epoch.store(epoch, Ordering::Release);
+        true
+    }
+}
+
+impl Default for PageRank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Power iteration. Rank from nodes without follows is spread evenly.
+pub fn compute(follows: &Neighbors) -> Vec<f64> {
+    power_iterate(follows, None)
+}
+
+/// Personalized PageRank: random jumps (and dangling rank) return to `seed`,
+/// so scores measure how strongly each node is trusted from the seed's position.
+pub fn personalized(follows: &Neighbors, seed: NodeId) -> Vec<f64> {
+    if seed as usize >= follows.len() {
+        return vec![0.0; follows.len()];
+    }
+    power_iterate(follows, Some(seed as usize))
+}
+
+fn power_iterate(follows: &Neighbors, seed: Option<usize>) -> Vec<f64> {
+    let n = follows.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let uniform = 1.0 / n as f64;
+    let mut scores = match seed {
+        Some(seed) => {
+            let mut scores = vec![0.0; n];
+            scores[seed] = 1.0;
+            scores
+        }
+        None => vec![uniform; n],
+    };
+    let mut next = vec![0.0; n];
+
+    for _ in 0..MAX_ITERATIONS {
+        let dangling: f64 = follows
+            .iter()
+            .zip(&scores)
+            .filter(|(out, _)| out.is_empty())
+            .map(|(_, s)| s)
+            .sum();
+        let teleport = (1.0 - DAMPING) + DAMPING * dangling;
+        match seed {
+            Some(seed) => {
+                next.iter_mut().for_each(|s| *s = 0.0);
+                next[seed] = teleport;
+            }
+            None => next.iter_mut().for_each(|s| *s = teleport * uniform),
+        }
+
+        for (node, out) in follows.iter().enumerate() {
+            if out.is_empty() {
+                continue;
+            }
+            let share = DAMPING * scores[node] / out.len() as f64;
+            for &target in out {
+                next[target as usize] += share;
+            }
+        }
+
+        let delta: f64 = scores.iter().zip(&next).map(|(a, b)| (a - b).abs()).sum();
+        std::mem::swap(&mut scores, &mut next);
+        if delta < TOLERANCE {
+            break;
+        }
+    }
+
+    scores
+}
+
+/// `scores` moved to the graph `follows` describes, given the lists that changed since they
+/// were computed: each change leaves residual rank on the pubkeys a follow list gained or lost,
+/// pushed on along follows until no node holds more than its tolerance. None past
+/// `PUSH_MAX_EDGES`.
+fn push_changes(follows: &Neighbors, scores: &[f64], changes: &FollowChanges) -> Option<Vec<f64>> {
+    let n = follows.len();
+    let mut updated = scores.to_vec();
+    updated.resize(n, 0.0);
+    let mut residual = vec![0.0; n];
+    // Pubkeys new since the scores start with their teleport share
+    for r in residual.iter_mut().skip(scores.len()) {
+        *r = (1.0 - DAMPING) / n as f64;
+    }
+    for (&node, old) in &changes.old_follows {
+        let Some(&score) = scores.get(node as usize) else {
+            continue;
+        };
+        if !old.is_empty() {
+            let share = DAMPING * score / old.len() as f64;
+            old.iter().for_each(|&followed| residual[followed as usize] -= share);
+        }
+        let new = &follows[node as usize];
+        if !new.is_empty() {
+            let share = DAMPING * score / new.len() as f64;
+            new.iter().for_each(|&followed| residual[followed as usize] += share);
+        }
+    }
+
+    let tolerance = PUSH_TOLERANCE / n as f64;
+    let mut queued: Vec<bool> = residual.iter().map(|r| r.abs() > tolerance).collect();
+    let mut queue: Vec<usize> = (0..n).filter(|&node| queued[node]).collect();
+    let mut budget = PUSH_MAX_EDGES;
+    while let Some(node) = queue.pop() {
+        queued[node] = false;
+        let r = std::mem::take(&mut residual[node]);
+        updated[node] += r;
+        let out = &follows[node];
+        if out.is_empty() {
+            continue;
+        }
+        budget = budget.checked_sub(out.len())?;
+        let share = DAMPING * r / out.len() as f64;
+        for &followed in out {
+            let followed = followed as usize;
+            residual[followed] += share;
+            if !queued[followed] && residual[followed].abs() > tolerance {
+                queued[followed] = true;
+                queue.push(followed);
+            }
+        }
+    }
+
+    // Residuals left behind and dangling rank shift the total slightly
+    updated.iter_mut().for_each(|s| *s = s.max(0.0));
+    let total: f64 = updated.iter().sum();
+    if total > 0.0 {
+        updated.iter_mut().for_each(|s| *s /= total);
+    }
+    Some(updated)
+}
+
+/// Up to `QUANTILES` ascending thresholds, evenly spaced over the sorted scores
+fn quantiles(scores: &[f64]) -> Vec<f64> {
+    let mut sorted = scores.to_vec();
+    sorted.sort_unstable_by(f64::total_cmp);
+    let count = QUANTILES.min(sorted.len());
+    (0..count).map(|i| sorted[i * sorted.len() / count]).collect()
+}
+
+/// The `n` highest-scoring nodes, best first, skipping `exclude` and zero scores
+pub fn top_n(scores: &[f64], n: usize, exclude: Option<NodeId>) -> Vec<(NodeId, f64)> {
+    let mut ranked: Vec<(NodeId, f64)> = scores
+        .iter()
+        .enumerate()
+        .map(|(id, &score)| (id as NodeId, score))
+        .filter(|&(id, score)| score > 0.0 && Some(id) != exclude)
+        .collect();
+
+    let by_score_desc = |a: &(NodeId, f64), b: &(NodeId, f64)| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0));
+    if ranked.len() > n {
+        ranked.select_nth_unstable_by(n, by_score_desc);
+        ranked.truncate(n);
+    }
+    ranked.sort_unstable_by(by_score_desc);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    #[test]
+    fn test_scores_sum_to_one() {
+        // 0 -> 1, 2 -> 1, 1 -> 0, 3 follows no one
+        let graph = WotGraph::from_lists(&[&[1], &[0], &[1], &[]]);
+        let scores = graph.with_adjacency(|follows, _| compute(follows));
+
+        let total: f64 = scores.iter().sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        // Most-followed node ranks highest; unfollowed nodes rank lowest
+        assert!(scores[1] > scores[0]);
+        assert!(scores[0] > scores[2]);
+        assert!((scores[2] - scores[3]).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_personalized_favours_seed_neighbourhood() {
+        // 0 -> 1 -> 2, 3 -> 4
+        let graph = WotGraph::from_lists(&[&[1], &[2], &[], &[4], &[]]);
+        let scores = graph.with_adjacency(|follows, _| personalized(follows, 0));
+
+        let total: f64 = scores.iter().sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!(scores[1] > scores[2]);
+        assert!(scores[2] > 0.0);
+        // Unreachable from the seed
+        assert_eq!(scores[3], 0.0);
+        assert_eq!(scores[4], 0.0);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let ranks = PageRank::new();
+        let graph = WotGraph::new();
+        assert_eq!(ranks.percentile(0), None);
+
+        // hub is followed by everyone else, who have no followers
+        for i in 0..9 {
+            graph.update_follows(&hex(&format!("n{}", i)), &[hex("hub")], None, None);
+        }
+        ranks.refresh(&graph);
+        let hub = graph.node_id(&pk("hub")).unwrap();
+        assert_eq!(ranks.percentile(hub), Some(0.9));
+        assert_eq!(ranks.percentile(graph.node_id(&pk("n0")).unwrap()), Some(0.0));
+
+        let scores: Vec<f64> = (0..5000).map(|i| i as f64).collect();
+        let thresholds = quantiles(&scores);
+        assert_eq!(thresholds.len(), QUANTILES);
+        assert_eq!((thresholds[0], thresholds[500]), (0.0, 2500.0));
+    }
+
+    #[test]
+    fn test_top_n() {
+        let scores = [0.1, 0.4, 0.0, 0.3, 0.2];
+        assert_eq!(top_n(&scores, 2, None), vec![(1, 0.4), (3, 0.3)]);
+        assert_eq!(top_n(&scores, 2, Some(1)), vec![(3, 0.3), (4, 0.2)]);
+        // Zero scores are never listed
+        assert_eq!(top_n(&scores, 10, None).len(), 4);
+    }
+
+    #[test]
+    fn test_update_follows_full_computation() {
+        // A ring of 200 with chords, so every pubkey follows and is followed
+        let graph = WotGraph::new();
+        let name = |i: usize| hex(&format!("n{}", i % 200));
+        for i in 0..200 {
+            graph.update_follows(&name(i), &[name(i + 1), name(i * 7 + 3)], None, Some(1));
+        }
+        let ranks = PageRank::new();
+        // No scores yet: a full computation
+        assert!(ranks.update(&graph));
+        assert!(!ranks.update(&graph));
+
+        // Everyone starts following n0, and a newcomer joins following n5
+        for i in (0..200).step_by(3) {
+            graph.update_follows(&name(i), &[name(i + 1), name(i * 7 + 3), name(0)], None, Some(2));
+        }
+        graph.update_follows(&hex("newcomer"), &[name(5)], None, Some(1));
+        assert!(ranks.update(&graph));
+        let updated = ranks.snapshot();
+
+        let exact = graph.with_adjacency(|follows, _| compute(follows));
+        assert_eq!(updated.len(), exact.len());
+        let error: f64 = updated.iter().zip(&exact).map(|(a, b)| (a - b).abs()).sum();
+        assert!(error < 0.01, "L1 error {}", error);
+        let n0 = graph.node_id(&pk("n0")).unwrap() as usize;
+        assert!((updated[n0] - exact[n0]).abs() < exact[n0] * 0.01);
+
+        // Updates don't stand in for the next full computation
+        assert!(ranks.refresh(&graph));
+        assert!(!ranks.refresh(&graph));
+        assert!(!ranks.update(&graph));
+    }
+
+    #[test]
+    fn test_refresh_tracks_epoch() {
+        let graph = WotGraph::new();
+        let ranks = PageRank::new();
+        assert_eq!(ranks.epoch(), None);
+        assert!(ranks.refresh(&graph));
+        assert!(!ranks.refresh(&graph));
+
+        graph.update_follows(&hex("alice"), &[hex("bob")], None, Some(1));
+        assert!(ranks.refresh(&graph));
+        assert_eq!(ranks.epoch(), Some(graph.epoch()));
+
+        let alice = graph.node_id(&pk("alice")).unwrap();
+        let bob = graph.node_id(&pk("bob")).unwrap();
+        assert!(ranks.score(bob).unwrap() > ranks.score(alice).unwrap());
+    }
+}
@@ -0,0 +1,1417 @@
+use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock};
+use rustc_hash::FxHashMap;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use super::adjacency::{Adjacency, Neighbors, ShardedAdjacency, Side};
+use super::lookalikes::{jaccard, shared_count, LookalikeIndex};
+use super::metrics::LockMetricsSnapshot;
+use crate::types::Pubkey;
+
+/// Follower-list entries changed per write-lock acquisition when applying a follow list
+const FOLLOWER_UPDATE_CHUNK: usize = 1024;
+
+/// MinHash matches re-ranked by exact similarity per peer returned by [`WotGraph::peers`]
+const PEER_CANDIDATES_PER_RESULT: usize = 4;
+
+/// Follow hash for adjacency that no longer matches any published list
+const STALE_LIST_HASH: u64 = u64::MAX;
+
+/// Follow IDs the change journal holds (16 MiB) before it gives up on the changes
+const CHANGE_JOURNAL_MAX_IDS: usize = 4_000_000;
+
+/// Node metadata (the pubkey is stored separately, indexed by node ID)
+#[derive(Debug, Clone)]
+pub struct NodeInfo {
+    pub kind3_event_id: Option<String>,
+    pub kind3_created_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GraphStats {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub nodes_with_follows: usize,
+    /// Nodes excluded from BFS by the quarantine rule (see [`GraphLimits`])
+    pub quarantined_nodes: usize,
+    /// Follow lists rejected for exceeding `max_follows`
+    pub rejected_lists: u64,
+    /// Contact list events rejected unparsed for exceeding `max_tags`
+    pub oversized_lists: u64,
+    /// Contact list events cut down to their first `max_tags` tags
+    pub truncated_lists: u64,
+    /// Follows dropped as repeats within the list they appeared in
+    pub duplicate_follows: u64,
+}
+
+/// Follow lists changed since the previous [`WotGraph::take_changes`], as they were before
+#[derive(Debug, Clone, Default)]
+pub struct FollowChanges {
+    /// Nodes when recording started; lists of nodes added since are not recorded
+    pub node_count: usize,
+    /// Each changed node's follow list before its first change
+    pub old_follows: FxHashMap<NodeId, Adjacency>,
+}
+
+#[derive(Default)]
+enum ChangeJournal {
+    /// Nobody has asked for changes yet
+    #[default]
+    Off,
+    Recording { changes: FollowChanges, ids: usize },
+    /// More changed than the journal holds, or the whole graph was replaced
+    Overflowed,
+}
+
+/// Dense index of a pubkey in the adjacency vectors. IDs are assigned in order and never
+/// reused, even by [`WotGraph::replace_with`], so structures keyed by ID (the query cache, anchor
+/// tables, popularity counts) can't alias one pubkey to another. 64 bits wide so long uptimes
+/// can't run out of them; allocation still fails with [`NodeIdsExhausted`] rather than wrap.
+pub type NodeId = u64;
+
+/// No node ID is left for a new pubkey
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeIdsExhausted;
+
+impl fmt::Display for NodeIdsExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("node ID space exhausted")
+    }
+}
+
+impl std::error::Error for NodeIdsExhausted {}
+
+/// Spam and traversal limits; 0 disables each
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphLimits {
+    /// Follow lists longer than this are rejected outright
+    pub max_follows: usize,
+    /// Nodes followed by nobody that follow at least this many are quarantined:
+    /// kept in the graph but excluded from BFS
+    pub quarantine_min_follows: usize,
+    /// Neighbors BFS walks from any one node; longer lists are sampled (see [`bfs`](super::bfs))
+    pub expansion_limit: usize,
+    /// Contact list events with more tags than this are rejected before their tags are parsed
+    pub max_tags: usize,
+    /// Keep the first `max_tags` tags of longer events instead of rejecting them
+    pub truncate_tags: bool,
+}
+
+/// Whether `id` is quarantined, given adjacency from [`WotGraph::with_adjacency`]
+pub fn is_quarantined(follows: &Neighbors, followers: &Neighbors, id: NodeId, min_follows: usize) -> bool {
+    min_follows > 0
+        && followers.get(id as usize).is_some_and(|list| list.is_empty())
+        && follows.get(id as usize).is_some_and(|list| list.len() >= min_follows)
+}
+
+pub struct WotGraph {
+    pubkey_to_id: DashMap<Pubkey, NodeId>,
+    id_to_pubkey: RwLock<Vec<Pubkey>>,
+    // Sorted ID lists for cache-friendly iteration and O(log n) membership checks, sharded
+    // by node ID so writes only contend with readers of the same shard
+    adjacency: ShardedAdjacency,
+    // Held shared by each adjacency change until its epoch bump, and exclusively by
+    // `with_snapshot` while it locks every shard, so a snapshot never sees half an update.
+    // Taken before `follower_writes` and any adjacency lock.
+    updates: RwLock<()>,
+    // Serializes follower-list changes, so two pubkeys following each other at once count
+    // as one mutual pair. Taken before any adjacency lock.
+    follower_writes: Mutex<()>,
+    node_info: RwLock<Vec<Option<NodeInfo>>>,
+    // Order-independent hash of the follow list each node's adjacency was built from
+    // (0 = empty), to skip republished identical lists without touching adjacency
+    follow_hashes: RwLock<Vec<u64>>,
+    // MinHash/LSH index over follow lists, for `lookalikes`
+    lookalikes: RwLock<LookalikeIndex>,
+    // Reciprocated follows per node (follows that follow back), kept in step with followers
+    mutuals: RwLock<Vec<u32>>,
+    // Follow entries added or removed by list updates since load, per node that changed its list
+    churn: RwLock<HashMap<NodeId, u32>>,
+    hasher: RandomState,
+    max_follows: AtomicUsize,
+    quarantine_min_follows: AtomicUsize,
+    expansion_limit: AtomicUsize,
+    max_tags: AtomicUsize,
+    truncate_tags: AtomicBool,
+    rejected_lists: AtomicU64,
+    oversized_lists: AtomicU64,
+    truncated_lists: AtomicU64,
+    duplicate_follows: AtomicU64,
+    // Operator blocklist: never follow targets or list authors; held across update_follows
+    blocked: RwLock<HashSet<String>>,
+    // Incremented on every applied follow-list change
+    epoch: AtomicU64,
+    // Old follow lists for `take_changes`. Taken after any adjacency lock, and holds no other.
+    changes: Mutex<ChangeJournal>,
+}
+
+impl WotGraph {
+    pub fn new() -> Self {
+        Self {
+            pubkey_to_id: DashMap::new(),
+            id_to_pubkey: RwLock::new(Vec::new()),
+            adjacency: ShardedAdjacency::new(),
+            updates: RwLock::new(()),
+            follower_writes: Mutex::new(()),
+            node_info: RwLock::new(Vec::new()),
+            follow_hashes: RwLock::new(Vec::new()),
+            lookalikes: RwLock::new(LookalikeIndex::default()),
+            mutuals: RwLock::new(Vec::new()),
+            churn: RwLock::new(HashMap::new()),
+            hasher: RandomState::new(),
+            max_follows: AtomicUsize::new(0),
+            quarantine_min_follows: AtomicUsize::new(0),
+            expansion_limit: AtomicUsize::new(0),
+            max_tags: AtomicUsize::new(0),
+            truncate_tags: AtomicBool::new(false),
+            rejected_lists: AtomicU64::new(0),
+            oversized_lists: AtomicU64::new(0),
+            truncated_lists: AtomicU64::new(0),
+            duplicate_follows: AtomicU64::new(0),
+            blocked: RwLock::new(HashSet::new()),
+            epoch: AtomicU64::new(0),
+            changes: Mutex::new(ChangeJournal::Off),
+        }
+    }
+
+    /// Apply spam limits; lists already in the graph are not re-checked against `max_follows`
+    pub fn set_limits(&self, limits: GraphLimits) {
+        self.max_follows.store(limits.max_follows, Ordering::Relaxed);
+        self.quarantine_min_follows.store(limits.quarantine_min_follows, Ordering::Relaxed);
+        self.expansion_limit.store(limits.expansion_limit, Ordering::Relaxed);
+        self.max_tags.store(limits.max_tags, Ordering::Relaxed);
+        self.truncate_tags.store(limits.truncate_tags, Ordering::Relaxed);
+    }
+
+    pub fn limits(&self) -> GraphLimits {
+        GraphLimits {
+            max_follows: self.max_follows.load(Ordering::Relaxed),
+            quarantine_min_follows: self.quarantine_min_follows.load(Ordering::Relaxed),
+            expansion_limit: self.expansion_limit.load(Ordering::Relaxed),
+            max_tags: self.max_tags.load(Ordering::Relaxed),
+            truncate_tags: self.truncate_tags.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether a follow list of this length is within `max_follows`; counts rejections.
+    /// `update_follows` checks this itself; callers may check first to skip parsing work.
+    pub fn admit_follow_count(&self, count: usize) -> bool {
+        let max = self.max_follows.load(Ordering::Relaxed);
+        if max > 0 && count > max {
+            self.rejected_lists.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// How many of a contact list event's `count` tags to parse: all of them within
+    /// `max_tags`, else the first `max_tags` when truncating, or `None` to reject the event.
+    /// Counts truncations and rejections.
+    pub fn admit_tag_count(&self, count: usize) -> Option<usize> {
+        let max = self.max_tags.load(Ordering::Relaxed);
+        if max == 0 || count <= max {
+            return Some(count);
+        }
+        if self.truncate_tags.load(Ordering::Relaxed) {
+            self.truncated_lists.fetch_add(1, Ordering::Relaxed);
+            Some(max)
+        } else {
+            self.oversized_lists.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+
+    /// Count follows an event listed more than once, dropped before `update_follows`
+    pub fn count_duplicate_follows(&self, duplicates: usize) {
+        if duplicates > 0 {
+            self.duplicate_follows.fetch_add(duplicates as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_blocked(&self, pubkey: &str) -> bool {
+        self.blocked.read().contains(pubkey)
+    }
+
+    pub fn blocked_count(&self) -> usize {
+        self.blocked.read().len()
+    }
+
+    /// Drop blocked pubkeys from a follow list
+    pub fn strip_blocked(&self, pubkeys: &mut Vec<String>) {
+        let blocked = self.blocked.read();
+        if !blocked.is_empty() {
+            pubkeys.retain(|pk| !blocked.contains(pk));
+        }
+    }
+
+    /// Block pubkeys (hex, any case): their contact lists are ignored and they are stripped
+    /// from everyone else's, so they never become nodes. Nodes that already exist lose all
+    /// their edges. Updates in flight hold the blocklist, so this waits for them to finish
+    /// rather than detach a node between their follower chunks. Returns how many were newly
+    /// blocked.
+    pub fn block(&self, pubkeys: &[String]) -> usize {
+        let mut blocked = self.blocked.write();
+        let added: Vec<String> = pubkeys
+            .iter()
+            .map(|pk| pk.to_ascii_lowercase())
+            .filter(|pk| blocked.insert(pk.clone()))
+            .collect();
+
+        let detached: Vec<NodeId> = added
+            .iter()
+            .filter_map(|pk| Pubkey::parse(pk).ok())
+            .filter_map(|pk| self.node_id(&pk))
+            .collect();
+        if !detached.is_empty() {
+            let _update = self.updates.read();
+            let _follower_writes = self.follower_writes.lock();
+            let mut adjacency = self.adjacency.write_all();
+            let mut node_info = self.node_info.write();
+            let mut follow_hashes = self.follow_hashes.write();
+            let mut mutuals = self.mutuals.write();
+            for &id in &detached {
+                let follows = std::mem::take(adjacency.list_mut(Side::Follows, id));
+                self.record_change(id, &follows);
+                let followers = std::mem::take(adjacency.list_mut(Side::Followers, id));
+                // Counts are kept against follower lists, so partners are read from those rather
+                // than from `follows`: a pair counts once each is in the other's followers
+                for &partner in &followers {
+                    if partner != id && adjacency.list_mut(Side::Followers, partner).binary_search(&id).is_ok() {
+                        mutuals[partner as usize] = mutuals[partner as usize].saturating_sub(1);
+                    }
+                }
+                mutuals[id as usize] = 0;
+                for followed in follows {
+                    let list = adjacency.list_mut(Side::Followers, followed);
+                    if let Ok(pos) = list.binary_search(&id) {
+                        list.remove(pos);
+                    }
+                }
+                for follower in followers {
+                    let list = adjacency.list_mut(Side::Follows, follower);
+                    if let Ok(pos) = list.binary_search(&id) {
+                        self.record_change(follower, list);
+                        list.remove(pos);
+                    }
+                    // Adjacency no longer matches the published list, so its republish must not
+                    // take the unchanged fast path (it would miss the edge after an unblock)
+                    follow_hashes[follower as usize] = STALE_LIST_HASH;
+                }
+                node_info[id as usize] = None;
+                follow_hashes[id as usize] = 0;
+            }
+            drop((adjacency, node_info, follow_hashes, mutuals));
+            let (mut lookalikes, mut churn) = (self.lookalikes.write(), self.churn.write());
+            for &id in &detached {
+                lookalikes.remove(id);
+                churn.remove(&id);
+            }
+            self.epoch.fetch_add(1, Ordering::Release);
+        }
+        added.len()
+    }
+
+    /// Unblock pubkeys. Edges are not restored; they return with newer contact lists.
+    /// Returns how many were blocked.
+    pub fn unblock(&self, pubkeys: &[String]) -> usize {
+        let mut blocked = self.blocked.write();
+        pubkeys
+            .iter()
+            .filter(|pk| blocked.remove(&pk.to_ascii_lowercase()))
+            .count()
+    }
+
+    pub fn get_or_create_node(&self, pubkey: &Pubkey) -> Result<NodeId, NodeIdsExhausted> {
+        // Fast path: check if already exists
+        if let Some(id) = self.pubkey_to_id.get(pubkey) {
+            return Ok(*id);
+        }
+
+        // Adjacency slots are created on first write, so new nodes never lock it
+        let mut id_to_pubkey = self.id_to_pubkey.write();
+        let mut node_info = self.node_info.write();
+        let mut follow_hashes = self.follow_hashes.write();
+        let mut mutuals = self.mutuals.write();
+
+        // Double-check after acquiring write lock
+        if let Some(id) = self.pubkey_to_id.get(pubkey) {
+            return Ok(*id);
+        }
+
+        // Failing beats wrapping around and aliasing an existing node
+        let id = NodeId::try_from(id_to_pubkey.len()).map_err(|_| NodeIdsExhausted)?;
+
+        id_to_pubkey.push(*pubkey);
+        node_info.push(None);
+        follow_hashes.push(0);
+        mutuals.push(0);
+        self.pubkey_to_id.insert(*pubkey, id);
+
+        Ok(id)
+    }
+
+    pub fn node_id(&self, pubkey: &Pubkey) -> Option<NodeId> {
+        self.pubkey_to_id.get(pubkey).map(|r| *r)
+    }
+
+    /// A node's pubkey, or None for unknown IDs
+    pub fn pubkey(&self, id: NodeId) -> Option<Pubkey> {
+        self.id_to_pubkey.read().get(id as usize).copied()
+    }
+
+    /// Replace a node's follow list. Returns false (and changes nothing) if `created_at` is not
+    /// newer than the applied list's, the author is blocked or not a hex pubkey, the list exceeds
+    /// `max_follows`, or node IDs ran out for its new pubkeys.
+    /// Blocked pubkeys and entries that aren't hex pubkeys are dropped from the list. A republished identical follow set only
+    /// updates the event metadata and leaves the epoch unchanged.
+    pub fn update_follows(
+        &self,
+        pubkey: &str,
+        follow_pubkeys: &[String],
+        event_id: Option<String>,
+        created_at: Option<i64>,
+    ) -> bool {
+        let blocked = self.blocked.read();
+        if blocked.contains(pubkey) {
+            return false;
+        }
+        let unblocked: Vec<String>;
+        let follow_pubkeys = if !blocked.is_empty() && follow_pubkeys.iter().any(|pk| blocked.contains(pk)) {
+            unblocked = follow_pubkeys.iter().filter(|pk| !blocked.contains(*pk)).cloned().collect();
+            &unblocked[..]
+        } else {
+            follow_pubkeys
+        };
+
+        if !self.admit_follow_count(follow_pubkeys.len()) {
+            return false;
+        }
+        let Ok(pubkey) = Pubkey::parse(pubkey) else {
+            return false;
+        };
+        let follow_pubkeys: Vec<Pubkey> = follow_pubkeys.iter().filter_map(|pk| Pubkey::parse(pk).ok()).collect();
+        let Ok(node_id) = self.get_or_create_node(&pubkey) else {
+            return false;
+        };
+
+        // Check if we should update (only if newer event)
+        let had_list = {
+            let node_info = self.node_info.read();
+            if let Some(Some(info)) = node_info.get(node_id as usize) {
+                if let (Some(existing_ts), Some(new_ts)) = (info.kind3_created_at, created_at) {
+                    if new_ts <= existing_ts {
+                        return false; // Event is older or same age, skip
+                    }
+                }
+            }
+            matches!(node_info.get(node_id as usize), Some(Some(_)))
+        };
+
+        // Fast path: the same follow set republished only changes event metadata, so
+        // adjacency, the epoch and anything cached against it stay as they are
+        let list_hash = self.list_hash(&follow_pubkeys);
+        let unchanged = self.follow_hashes.read().get(node_id as usize) == Some(&list_hash);
+        let _update = (!unchanged).then(|| self.updates.read());
+
+        if !unchanged {
+            // Get or create IDs for all follows and sort them
+            let Ok(mut new_follow_ids) = follow_pubkeys
+                .iter()
+                .map(|pk| self.get_or_create_node(pk))
+                .collect::<Result<Adjacency, _>>()
+            else {
+                return false;
+            };
+            new_follow_ids.sort_unstable();
+            new_follow_ids.dedup();
+
+            // Diff against the current list under a read lock (no clone, readers unaffected)
+            let (to_remove, to_add) = self
+                .adjacency
+                .read(Side::Follows, node_id, |old_follow_ids| {
+                    self.record_change(node_id, old_follow_ids);
+                    sorted_diff(old_follow_ids, &new_follow_ids)
+                });
+            self.lookalikes.write().update(node_id, &new_follow_ids);
+            // A node's first list (e.g. loaded from the database) is not churn
+            if had_list {
+                let changed = u32::try_from(to_remove.len() + to_add.len()).unwrap_or(u32::MAX);
+                let mut churn = self.churn.write();
+                let count = churn.entry(node_id).or_default();
+                *count = count.saturating_add(changed);
+            }
+
+            // Minimal write lock - swap in the new follow list
+            self.adjacency.update(Side::Follows, node_id, |follow_list| *follow_list = new_follow_ids);
+            if let Some(hash) = self.follow_hashes.write().get_mut(node_id as usize) {
+                *hash = list_hash;
+            }
+
+            // Follower references in chunks, releasing the shard locks in between so a
+            // mega-list update doesn't stall readers for its whole length
+            for chunk in to_remove.chunks(FOLLOWER_UPDATE_CHUNK) {
+                self.update_followers(node_id, chunk, false);
+            }
+            for chunk in to_add.chunks(FOLLOWER_UPDATE_CHUNK) {
+                self.update_followers(node_id, chunk, true);
+            }
+        }
+
+        // Update node info (pubkey stored via interner, not duplicated here)
+        {
+            let mut node_info = self.node_info.write();
+            if let Some(info_slot) = node_info.get_mut(node_id as usize) {
+                *info_slot = Some(NodeInfo {
+                    kind3_event_id: event_id,
+                    kind3_created_at: created_at,
+                });
+            }
+        }
+
+        if !unchanged {
+            self.epoch.fetch_add(1, Ordering::Release);
+        }
+        true
+    }
+
+    /// Add or remove `node_id` from the follower lists of `followed`, keeping mutual counts in
+    /// step. Reciprocation is checked under `follower_writes`, so two pubkeys following each
+    /// other at once count once.
+    fn update_followers(&self, node_id: NodeId, followed: &[NodeId], add: bool) {
+        let _follower_writes = self.follower_writes.lock();
+        // Only lists that actually gained or lost `node_id` count, so a removal that was
+        // already applied (e.g. by a racing update of the same list) leaves mutuals alone
+        let mut changed = Vec::new();
+        self.adjacency.update_each(Side::Followers, followed, |id, follower_list| {
+            match (follower_list.binary_search(&node_id), add) {
+                (Err(pos), true) => follower_list.insert(pos, node_id),
+                (Ok(pos), false) => {
+                    follower_list.remove(pos);
+                }
+                _ => return,
+            }
+            if id != node_id {
+                changed.push(id);
+            }
+        });
+        let reciprocated: Vec<NodeId> = self.adjacency.read(Side::Followers, node_id, |own_followers| {
+            changed.into_iter().filter(|id| own_followers.binary_search(id).is_ok()).collect()
+        });
+        if !reciprocated.is_empty() {
+            let mut mutuals = self.mutuals.write();
+            for id in reciprocated {
+                if add {
+                    mutuals[node_id as usize] += 1;
+                    mutuals[id as usize] += 1;
+                } else {
+                    mutuals[node_id as usize] -= 1;
+                    mutuals[id as usize] -= 1;
+                }
+            }
+        }
+    }
+
+    /// Order-independent hash of a follow list; the empty list hashes to 0
+    fn list_hash(&self, follow_pubkeys: &[Pubkey]) -> u64 {
+        self.hash_pubkeys(follow_pubkeys.iter())
+    }
+
+    fn hash_pubkeys<'a>(&self, pubkeys: impl ExactSizeIterator<Item = &'a Pubkey>) -> u64 {
+        let len = pubkeys.len() as u64;
+        let sum = pubkeys.fold(0u64, |acc, pk| acc.wrapping_add(self.hasher.hash_one(pk)));
+        sum ^ len.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+
+    /// Replace all adjacency and node metadata with `other`'s in one write-lock section, so
+    /// readers see either the old graph or the new one. Node IDs are kept: pubkeys new in
+    /// `other` are added, ones missing from it are left without edges. Bumps the epoch.
+    pub fn replace_with(&self, other: &WotGraph) -> Result<(), NodeIdsExhausted> {
+        let other_pubkeys = other.id_to_pubkey.read().clone();
+        let ids: Vec<NodeId> =
+            other_pubkeys.iter().map(|pk| self.get_or_create_node(pk)).collect::<Result<_, _>>()?;
+        let other_info = other.node_info.read();
+
+        let _update = self.updates.read();
+        let _follower_writes = self.follower_writes.lock();
+        let mut adjacency = self.adjacency.write_all();
+        let mut node_info = self.node_info.write();
+        let mut follow_hashes = self.follow_hashes.write();
+
+        let len = node_info.len();
+        let mut new_follows = vec![Adjacency::new(); len];
+        let mut new_followers = vec![Adjacency::new(); len];
+        let mut new_info = vec![None; len];
+        let mut new_hashes = vec![0; len];
+        other.with_adjacency(|other_follows, _| {
+            for (other_id, list) in other_follows.iter().enumerate() {
+                let id = ids[other_id] as usize;
+                let mut mapped: Adjacency = list.iter().map(|&followed| ids[followed as usize]).collect();
+                mapped.sort_unstable();
+                for &followed in &mapped {
+                    new_followers[followed as usize].push(id as NodeId);
+                }
+                new_hashes[id] = self.hash_pubkeys(list.iter().map(|&followed| &other_pubkeys[followed as usize]));
+                new_follows[id] = mapped;
+                new_info[id] = other_info[other_id].clone();
+            }
+        });
+        for list in &mut new_followers {
+            list.sort_unstable();
+        }
+        let mut new_lookalikes = LookalikeIndex::default();
+        for (id, list) in new_follows.iter().enumerate() {
+            new_lookalikes.update(id as NodeId, list);
+        }
+        let new_mutuals: Vec<u32> = new_follows
+            .iter()
+            .zip(&new_followers)
+            .enumerate()
+            .map(|(id, (follows, followers))| mutual_partners(id as NodeId, follows, followers).count() as u32)
+            .collect();
+
+        adjacency.replace(new_follows, new_followers);
+        *node_info = new_info;
+        *follow_hashes = new_hashes;
+        *self.lookalikes.write() = new_lookalikes;
+        *self.mutuals.write() = new_mutuals;
+        let mut changes = self.changes.lock();
+        if matches!(*changes, ChangeJournal::Recording { .. }) {
+            *changes = ChangeJournal::Overflowed;
+        }
+        drop(changes);
+        self.epoch.fetch_add(1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Follow lists changed since the previous call, as they were before, and start recording
+    /// anew. None on the first call, after [`replace_with`](Self::replace_with), or when more
+    /// changed than the journal holds: whatever was computed from the old lists needs a full
+    /// recomputation then. Call from [`with_snapshot`](Self::with_snapshot) to get exactly the
+    /// changes behind the adjacency it sees.
+    pub fn take_changes(&self) -> Option<FollowChanges> {
+        let node_count = self.id_to_pubkey.read().len();
+        let changes = FollowChanges { node_count, old_follows: FxHashMap::default() };
+        match std::mem::replace(&mut *self.changes.lock(), ChangeJournal::Recording { changes, ids: 0 }) {
+            ChangeJournal::Recording { changes, .. } => Some(changes),
+            ChangeJournal::Off | ChangeJournal::Overflowed => None,
+        }
+    }
+
+    /// Keep a node's follow list from before its first change since `take_changes`
+    fn record_change(&self, id: NodeId, old_follows: &[NodeId]) {
+        let mut journal = self.changes.lock();
+        let ChangeJournal::Recording { changes, ids } = &mut *journal else {
+            return;
+        };
+        if id as usize >= changes.node_count || changes.old_follows.contains_key(&id) {
+            return;
+        }
+        *ids += old_follows.len();
+        if *ids > CHANGE_JOURNAL_MAX_IDS {
+            *journal = ChangeJournal::Overflowed;
+        } else {
+            changes.old_follows.insert(id, old_follows.into());
+        }
+    }
+
+    /// Graph version counter, bumped on every applied follow-list update.
+    /// Equal epochs imply identical adjacency (within one process lifetime).
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    pub fn get_follows(&self, pubkey: &Pubkey) -> Option<Vec<Pubkey>> {
+        let node_id = self.node_id(pubkey)?;
+        Some(self.adjacency.read(Side::Follows, node_id, |follow_list| self.resolve_pubkeys(follow_list)))
+    }
+
+    pub fn get_followers(&self, pubkey: &Pubkey) -> Option<Vec<Pubkey>> {
+        let node_id = self.node_id(pubkey)?;
+        Some(self.adjacency.read(Side::Followers, node_id, |follower_list| self.resolve_pubkeys(follower_list)))
+    }
+
+    /// Batch resolve node IDs to pubkeys, skipping unknown IDs
+    pub fn resolve_pubkeys(&self, ids: &[NodeId]) -> Vec<Pubkey> {
+        let id_to_pubkey = self.id_to_pubkey.read();
+        ids.iter().filter_map(|&id| id_to_pubkey.get(id as usize).copied()).collect()
+    }
+
+    /// `(follows, followers)` counts for a node, without resolving pubkeys
+    pub fn degree(&self, node_id: NodeId) -> (usize, usize) {
+        let follows = self.adjacency.read(Side::Follows, node_id, <[NodeId]>::len);
+        let followers = self.adjacency.read(Side::Followers, node_id, <[NodeId]>::len);
+        (follows, followers)
+    }
+
+    /// Whether `from` directly follows `to` (binary search on the sorted follow list)
+    pub fn is_following(&self, from: NodeId, to: NodeId) -> bool {
+        self.adjacency.read(Side::Follows, from, |list| list.binary_search(&to).is_ok())
+    }
+
+    /// Nodes whose follow lists have a Jaccard similarity of at least `min_similarity` with
+    /// `node_id`'s, most similar first. Candidates come from the MinHash index, so lists under
+    /// [`MIN_INDEXED_FOLLOWS`](super::lookalikes::MIN_INDEXED_FOLLOWS) never match and pairs
+    /// well below 0.8 similarity are often missed.
+    pub fn lookalikes(&self, node_id: NodeId, min_similarity: f64, limit: usize) -> Vec<(NodeId, f64)> {
+        let candidates = self.lookalikes.read().candidates(node_id);
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        let mut matches: Vec<(NodeId, f64)> = self.with_adjacency(|follows, _| {
+            let Some(own) = follows.get(node_id as usize) else {
+                return Vec::new();
+            };
+            candidates
+                .into_iter()
+                .filter_map(|id| {
+                    let similarity = jaccard(own, follows.get(id as usize)?);
+                    (similarity >= min_similarity).then_some((id, similarity))
+                })
+                .collect()
+        });
+        matches.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        matches.truncate(limit);
+        matches
+    }
+
+    /// Nodes whose follow lists overlap most with `node_id`'s, as `(node, jaccard, shared follows)`,
+    /// most similar first. Candidates are the closest MinHash signatures, re-ranked by exact
+    /// Jaccard similarity, so the top few are reliable and the tail approximate. Lists under
+    /// [`MIN_INDEXED_FOLLOWS`](super::lookalikes::MIN_INDEXED_FOLLOWS) have no peers.
+    pub fn peers(&self, node_id: NodeId, limit: usize) -> Vec<(NodeId, f64, usize)> {
+        let candidates = self.lookalikes.read().most_similar(node_id, limit.saturating_mul(PEER_CANDIDATES_PER_RESULT));
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        let mut peers: Vec<(NodeId, f64, usize)> = self.with_adjacency(|follows, _| {
+            let Some(own) = follows.get(node_id as usize) else {
+                return Vec::new();
+            };
+            candidates
+                .into_iter()
+                .filter_map(|(id, _)| {
+                    let list = follows.get(id as usize)?;
+                    let shared = shared_count(own, list);
+                    (shared > 0).then(|| (id, jaccard(own, list), shared))
+                })
+                .collect()
+        });
+        peers.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        peers.truncate(limit);
+        peers
+    }
+
+    /// Followers of `to` that `from` follows directly, then with `two_hop` those followed by one
+    /// of `from`'s follows but not by `from` itself: who vouches for `to` from `from`'s view.
+    /// Neither endpoint counts as its own endorser.
+    pub fn endorsers(&self, from: NodeId, to: NodeId, two_hop: bool) -> (Vec<NodeId>, Vec<NodeId>) {
+        self.with_adjacency(|follows, followers| {
+            let (Some(own), Some(candidates)) = (follows.get(from as usize), followers.get(to as usize)) else {
+                return (Vec::new(), Vec::new());
+            };
+            let candidates = candidates.iter().copied().filter(|&id| id != from && id != to);
+            let (direct, rest): (Vec<NodeId>, Vec<NodeId>) = candidates.partition(|id| own.binary_search(id).is_ok());
+            if !two_hop || rest.is_empty() {
+                return (direct, Vec::new());
+            }
+            // Second-degree contacts are bounded by `from`'s follows, however popular `to` is
+            let reached: HashSet<NodeId> = own
+                .iter()
+                .filter_map(|&id| follows.get(id as usize))
+                .flat_map(|list| list.iter().copied())
+                .collect();
+            let indirect = rest.into_iter().filter(|id| reached.contains(id)).collect();
+            (direct, indirect)
+        })
+    }
+
+    /// Follows of this node that follow it back
+    pub fn mutual_count(&self, node_id: NodeId) -> usize {
+        self.mutuals.read().get(node_id as usize).map_or(0, |&count| count as usize)
+    }
+
+    /// Follow entries added or removed by this node's list updates since the graph was loaded
+    pub fn churn(&self, node_id: NodeId) -> u32 {
+        self.churn.read().get(&node_id).copied().unwrap_or(0)
+    }
+
+    /// `created_at` of the follow list this node last published, None if none was ingested
+    pub fn kind3_created_at(&self, node_id: NodeId) -> Option<i64> {
+        self.node_info.read().get(node_id as usize)?.as_ref()?.kind3_created_at
+    }
+
+    /// Execute a closure with read access to both adjacency lists - use for BFS traversals.
+    /// Each adjacency shard is read-locked when the closure first touches it and stays locked
+    /// until it returns, so what it reads is consistent; writes to other shards go ahead.
+    /// Must not be nested: a writer waiting on the outer call would deadlock the inner one.
+    pub fn with_adjacency<F, R>(&self, f: F) -> R
+    where
+        F: for<'v, 'a> FnOnce(&Neighbors<'v, 'a>, &Neighbors<'v, 'a>) -> R,
+    {
+        let len = self.id_to_pubkey.read().len();
+        self.adjacency.with_views(len, f)
+    }
+
+    /// Like [`with_adjacency`](Self::with_adjacency), but pinned to one graph state: waits for
+    /// follow-list updates in flight to finish, then read-locks every shard up front and passes
+    /// the epoch they reflect. Updates wait until the closure returns, so keep it to one batch
+    /// of work. Must not be nested, nor wrap `with_adjacency`.
+    pub fn with_snapshot<F, R>(&self, f: F) -> R
+    where
+        F: for<'v, 'a> FnOnce(u64, &Neighbors<'v, 'a>, &Neighbors<'v, 'a>) -> R,
+    {
+        let update = self.updates.write();
+        let len = self.id_to_pubkey.read().len();
+        self.adjacency.with_pinned_views(len, |follows, followers| {
+            let epoch = self.epoch();
+            // Every shard is held now, so later updates wait on them instead
+            drop(update);
+            f(epoch, follows, followers)
+        })
+    }
+
+    pub fn get_node_info(&self, pubkey: &Pubkey) -> Option<NodeInfo> {
+        let node_id = self.node_id(pubkey)?;
+        let node_info = self.node_info.read();
+        node_info.get(node_id as usize).and_then(|info| info.clone())
+    }
+
+    /// Nodes created so far; IDs run from 0 to this
+    pub fn node_count(&self) -> usize {
+        self.id_to_pubkey.read().len()
+    }
+
+    /// A node's applied follow list with the metadata of the event it came from, None for nodes
+    /// without one. The metadata is read first: a list replaced meanwhile comes back as the
+    /// newer follows under the older metadata, never the other way round.
+    pub fn follow_list(&self, node_id: NodeId) -> Option<(NodeInfo, Vec<NodeId>)> {
+        let info = self.node_info.read().get(node_id as usize)?.clone()?;
+        Some((info, self.adjacency.read(Side::Follows, node_id, <[NodeId]>::to_vec)))
+    }
+
+    pub fn stats(&self) -> GraphStats {
+        let min_follows = self.quarantine_min_follows.load(Ordering::Relaxed);
+        let (node_count, edge_count, nodes_with_follows, quarantined_nodes) = self.with_adjacency(|follows, followers| {
+            let edge_count: usize = follows.iter().map(|list| list.len()).sum();
+            let nodes_with_follows = follows.iter().filter(|list| !list.is_empty()).count();
+            let quarantined_nodes = (0..follows.len() as NodeId)
+                .filter(|&id| is_quarantined(follows, followers, id, min_follows))
+                .count();
+            (follows.len(), edge_count, nodes_with_follows, quarantined_nodes)
+        });
+
+        GraphStats {
+            node_count,
+            edge_count,
+            nodes_with_follows,
+            quarantined_nodes,
+            rejected_lists: self.rejected_lists.load(Ordering::Relaxed),
+            oversized_lists: self.oversized_lists.load(Ordering::Relaxed),
+            truncated_lists: self.truncated_lists.load(Ordering::Relaxed),
+            duplicate_follows: self.duplicate_follows.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get lock contention metrics, summed over the adjacency shards
+    pub fn lock_metrics(&self) -> LockMetricsSnapshot {
+        self.adjacency.metrics()
+    }
+
+    /// Lock contention metrics for each adjacency shard, in shard order
+    pub fn shard_lock_metrics(&self) -> Vec<LockMetricsSnapshot> {
+        self.adjacency.shard_metrics()
+    }
+
+    /// Reset lock metrics (useful after warmup period)
+    pub fn reset_lock_metrics(&self) {
+        self.adjacency.reset_metrics();
+    }
+}
+
+/// Entries only in `old` and only in `new`; both must be sorted and deduplicated
+fn sorted_diff(old: &[NodeId], new: &[NodeId]) -> (Vec<NodeId>, Vec<NodeId>) {
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        match old[i].cmp(&new[j]) {
+            std::cmp::Ordering::Less => {
+                removed.push(old[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                added.push(new[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    removed.extend_from_slice(&old[i..]);
+    added.extend_from_slice(&new[j..]);
+    (removed, added)
+}
+
+/// Nodes that `id` follows and that follow it back, given its sorted lists; a self-follow
+/// doesn't count
+fn mutual_partners<'a>(
+    id: NodeId,
+    follows: &'a [NodeId],
+    followers: &'a [NodeId],
+) -> impl Iterator<Item = NodeId> + 'a {
+    follows.iter().copied().filter(move |&x| x != id && followers.binary_search(&x).is_ok())
+}
+
+impl Default for WotGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+impl WotGraph {
+    /// Graph in which node `i` is the pubkey numbered `i` (hex `{:064x}`) and follows `lists[i]`
+    pub(crate) fn from_lists(lists: &[&[NodeId]]) -> Self {
+        let hex = |i: u64| format!("{:064x}", i);
+        let graph = Self::new();
+        for i in 0..lists.len() as u64 {
+            graph.get_or_create_node(&hex(i).parse().unwrap()).unwrap();
+        }
+        for (i, list) in lists.iter().enumerate() {
+            let follows: Vec<String> = list.iter().map(|&id| hex(id)).collect();
+            graph.update_follows(&hex(i as u64), &follows, None, None);
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    #[test]
+    fn test_create_nodes() {
+        let graph = WotGraph::new();
+        let id1 = graph.get_or_create_node(&pk("pubkey1")).unwrap();
+        let id2 = graph.get_or_create_node(&pk("pubkey2")).unwrap();
+        let id1_again = graph.get_or_create_node(&pk("pubkey1")).unwrap();
+
+        assert_eq!(id1, 0);
+        assert_eq!(id2, 1);
+        assert_eq!(id1, id1_again);
+    }
+
+    #[test]
+    fn test_update_follows() {
+        let graph = WotGraph::new();
+
+        graph.update_follows(
+            &hex("alice"),
+            &[hex("bob"), hex("carol")],
+            Some(hex("event1")),
+            Some(1000),
+        );
+
+        let follows = graph.get_follows(&pk("alice")).unwrap();
+        assert_eq!(follows.len(), 2);
+        assert!(follows.contains(&pk("bob")));
+        assert!(follows.contains(&pk("carol")));
+
+        let bob_followers = graph.get_followers(&pk("bob")).unwrap();
+        assert!(bob_followers.contains(&pk("alice")));
+    }
+
+    #[test]
+    fn test_replace_follows() {
+        let graph = WotGraph::new();
+
+        graph.update_follows(&hex("alice"), &[hex("bob")], None, Some(1000));
+        graph.update_follows(&hex("alice"), &[hex("carol")], None, Some(2000));
+
+        let follows = graph.get_follows(&pk("alice")).unwrap();
+        assert_eq!(follows.len(), 1);
+        assert!(follows.contains(&pk("carol")));
+
+        // Bob should no longer have alice as follower
+        let bob_followers = graph.get_followers(&pk("bob")).unwrap();
+        assert!(!bob_followers.contains(&pk("alice")));
+    }
+
+    #[test]
+    fn test_skip_old_event() {
+        let graph = WotGraph::new();
+
+        graph.update_follows(&hex("alice"), &[hex("bob")], None, Some(2000));
+        let result = graph.update_follows(&hex("alice"), &[hex("carol")], None, Some(1000));
+
+        assert!(!result); // Should skip old event
+
+        let follows = graph.get_follows(&pk("alice")).unwrap();
+        assert!(follows.contains(&pk("bob")));
+        assert!(!follows.contains(&pk("carol")));
+    }
+
+    #[test]
+    fn test_epoch_advances_on_applied_updates() {
+        let graph = WotGraph::new();
+        assert_eq!(graph.epoch(), 0);
+
+        graph.update_follows(&hex("alice"), &[hex("bob")], None, Some(2000));
+        assert_eq!(graph.epoch(), 1);
+
+        // Skipped (older) events don't change the graph
+        graph.update_follows(&hex("alice"), &[hex("carol")], None, Some(1000));
+        assert_eq!(graph.epoch(), 1);
+    }
+
+    #[test]
+    fn test_identical_list_skips_adjacency() {
+        let graph = WotGraph::new();
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("carol")], None, Some(1));
+        assert_eq!(graph.epoch(), 1);
+
+        // Same set in another order: accepted, metadata updated, epoch unchanged
+        assert!(graph.update_follows(&hex("alice"), &[hex("carol"), hex("bob")], Some(hex("e2")), Some(2)));
+        assert_eq!(graph.epoch(), 1);
+        assert_eq!(graph.get_node_info(&pk("alice")).unwrap().kind3_created_at, Some(2));
+
+        // An empty list for a node that never had follows is also a no-op
+        assert!(graph.update_follows(&hex("dave"), &[], None, Some(1)));
+        assert_eq!(graph.epoch(), 1);
+
+        assert!(graph.update_follows(&hex("alice"), &[hex("bob")], None, Some(3)));
+        assert_eq!(graph.epoch(), 2);
+        assert_eq!(graph.get_followers(&pk("carol")).unwrap(), Vec::<Pubkey>::new());
+        assert!(graph.update_follows(&hex("alice"), &[], None, Some(4)));
+        assert_eq!(graph.epoch(), 3);
+        assert_eq!(graph.get_followers(&pk("bob")).unwrap(), Vec::<Pubkey>::new());
+    }
+
+    #[test]
+    fn test_snapshot_pins_epoch() {
+        let graph = Arc::new(WotGraph::new());
+        graph.update_follows(&hex("alice"), &[hex("bob")], None, Some(1));
+
+        let writer = graph.with_snapshot(|epoch, follows, followers| {
+            assert_eq!(epoch, 1);
+            let writer = {
+                let graph = graph.clone();
+                std::thread::spawn(move || graph.update_follows(&hex("bob"), &[hex("carol")], None, Some(1)))
+            };
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            // The update waits for the snapshot, touching neither adjacency nor the epoch
+            assert!(!writer.is_finished());
+            assert_eq!(graph.epoch(), 1);
+            assert_eq!((follows.len(), followers.len()), (2, 2));
+            assert!(follows[1].is_empty());
+            writer
+        });
+        assert!(writer.join().unwrap());
+        assert_eq!(graph.with_snapshot(|epoch, follows, _| (epoch, follows[1].to_vec())), (2, vec![2]));
+    }
+
+    #[test]
+    fn test_replace_with_keeps_ids() {
+        let graph = WotGraph::new();
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("carol")], None, Some(1));
+        let alice = graph.node_id(&pk("alice")).unwrap();
+        let epoch = graph.epoch();
+
+        let fresh = WotGraph::new();
+        fresh.update_follows(&hex("dave"), &[hex("bob")], None, Some(5));
+        fresh.update_follows(&hex("alice"), &[hex("dave")], Some(hex("e2")), Some(2));
+        graph.replace_with(&fresh).unwrap();
+
+        assert!(graph.epoch() > epoch);
+        assert_eq!(graph.node_id(&pk("alice")), Some(alice));
+        assert_eq!(graph.get_follows(&pk("alice")).unwrap(), vec![pk("dave")]);
+        assert_eq!(graph.get_followers(&pk("bob")).unwrap(), vec![pk("dave")]);
+        assert!(graph.get_followers(&pk("carol")).unwrap().is_empty());
+        assert_eq!(graph.get_node_info(&pk("alice")).unwrap().kind3_created_at, Some(2));
+        assert_eq!(graph.stats().edge_count, 2);
+
+        // Hashes carry over, so republishing the same list leaves the epoch alone
+        let epoch = graph.epoch();
+        assert!(graph.update_follows(&hex("alice"), &[hex("dave")], None, Some(3)));
+        assert_eq!(graph.epoch(), epoch);
+    }
+
+    #[test]
+    fn test_mega_list_chunked_followers() {
+        let graph = WotGraph::new();
+        let big: Vec<String> = (0..FOLLOWER_UPDATE_CHUNK * 3 + 7).map(|i| hex(&format!("pk{}", i))).collect();
+        graph.update_follows(&hex("bot"), &big, None, Some(1));
+        assert_eq!(graph.stats().edge_count, big.len());
+
+        let half: Vec<String> = big.iter().step_by(2).cloned().collect();
+        graph.update_follows(&hex("bot"), &half, None, Some(2));
+        assert_eq!(graph.stats().edge_count, half.len());
+        assert_eq!(graph.get_followers(&pk("pk0")).unwrap(), vec![pk("bot")]);
+        assert!(graph.get_followers(&pk("pk1")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_limits_and_quarantine() {
+        let graph = WotGraph::new();
+        graph.set_limits(GraphLimits { max_follows: 3, quarantine_min_follows: 2, ..Default::default() });
+
+        let four: Vec<String> = ["a", "b", "c", "d"].map(hex).to_vec();
+        assert!(!graph.update_follows(&hex("spam"), &four, None, Some(1)));
+        assert!(graph.node_id(&pk("spam")).is_none());
+
+        graph.update_follows(&hex("bot"), &four[..2], None, Some(1));
+        graph.update_follows(&hex("alice"), &[hex("carol")], None, Some(1));
+        let stats = graph.stats();
+        assert_eq!(stats.rejected_lists, 1);
+        assert_eq!(stats.quarantined_nodes, 1);
+
+        // Gaining a follower lifts the quarantine
+        graph.update_follows(&hex("carol"), &[hex("bot")], None, Some(1));
+        assert_eq!(graph.stats().quarantined_nodes, 0);
+    }
+
+    #[test]
+    fn test_admit_tag_count() {
+        let graph = WotGraph::new();
+        assert_eq!(graph.admit_tag_count(1_000_000), Some(1_000_000));
+
+        graph.set_limits(GraphLimits { max_tags: 10, ..Default::default() });
+        assert_eq!(graph.admit_tag_count(10), Some(10));
+        assert_eq!(graph.admit_tag_count(11), None);
+        graph.set_limits(GraphLimits { max_tags: 10, truncate_tags: true, ..Default::default() });
+        assert_eq!(graph.admit_tag_count(11), Some(10));
+        graph.count_duplicate_follows(3);
+
+        let stats = graph.stats();
+        assert_eq!((stats.oversized_lists, stats.truncated_lists, stats.duplicate_follows), (1, 1, 3));
+    }
+
+    #[test]
+    fn test_block_detaches_and_strips() {
+        let graph = WotGraph::new();
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("mallory")], None, Some(1));
+        graph.update_follows(&hex("mallory"), &[hex("bob")], None, Some(1));
+        let epoch = graph.epoch();
+
+        assert_eq!(graph.block(&[hex("mallory").to_uppercase(), hex("eve")]), 2);
+        assert!(graph.epoch() > epoch);
+        assert!(graph.is_blocked(&hex("mallory")));
+        assert_eq!(graph.get_follows(&pk("alice")).unwrap(), vec![pk("bob")]);
+        assert_eq!(graph.get_followers(&pk("bob")).unwrap(), vec![pk("alice")]);
+        assert!(graph.get_follows(&pk("mallory")).unwrap().is_empty());
+
+        // Blocked authors are ignored and blocked follows never become nodes
+        assert!(!graph.update_follows(&hex("mallory"), &[hex("carol")], None, Some(2)));
+        graph.update_follows(&hex("bob"), &[hex("eve"), hex("carol")], None, Some(1));
+        assert_eq!(graph.get_follows(&pk("bob")).unwrap(), vec![pk("carol")]);
+        assert!(graph.node_id(&pk("eve")).is_none());
+
+        // After an unblock, the follower's republished list restores the edge
+        assert_eq!(graph.unblock(&[hex("mallory")]), 1);
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("mallory")], None, Some(2));
+        assert_eq!(graph.get_followers(&pk("mallory")).unwrap(), vec![pk("alice")]);
+    }
+
+    #[test]
+    fn test_block_during_chunked_updates() {
+        let graph = Arc::new(WotGraph::new());
+        let peers: Vec<String> = (0..3 * FOLLOWER_UPDATE_CHUNK).map(|i| hex(&format!("peer{}", i))).collect();
+        for peer in &peers {
+            graph.update_follows(peer, &[hex("alice")], None, Some(1));
+        }
+
+        // Alice's lists span several follower chunks, so blocks land between them
+        let writer = {
+            let (graph, peers) = (graph.clone(), peers.clone());
+            std::thread::spawn(move || {
+                for round in 0..20 {
+                    let list = if round % 2 == 0 { &peers[..] } else { &peers[FOLLOWER_UPDATE_CHUNK..] };
+                    graph.update_follows(&hex("alice"), list, None, Some(round + 2));
+                }
+            })
+        };
+        for blocked in peers.chunks(64).step_by(4).take(10) {
+            graph.block(blocked);
+        }
+        writer.join().unwrap();
+
+        let (follows, followers): (Vec<Vec<NodeId>>, Vec<Vec<NodeId>>) = graph.with_adjacency(|follows, followers| {
+            (follows.iter().map(<[NodeId]>::to_vec).collect(), followers.iter().map(<[NodeId]>::to_vec).collect())
+        });
+        for id in 0..follows.len() {
+            let expected = mutual_partners(id as NodeId, &follows[id], &followers[id]).count();
+            assert_eq!(graph.mutual_count(id as NodeId), expected, "mutuals of {}", id);
+        }
+        let alice = graph.node_id(&pk("alice")).unwrap();
+        assert_eq!(graph.mutual_count(alice), graph.get_follows(&pk("alice")).unwrap().len());
+        assert!(graph.get_followers(&peers[0].parse().unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_take_changes() {
+        let graph = WotGraph::new();
+        let follows = |pks: &[&str]| pks.iter().map(|name| hex(name)).collect::<Vec<_>>();
+        graph.update_follows(&hex("alice"), &follows(&["bob", "mallory"]), None, Some(1));
+        graph.update_follows(&hex("bob"), &follows(&["alice"]), None, Some(1));
+        // Nothing recorded before the first call
+        assert!(graph.take_changes().is_none());
+        let id = |name: &str| graph.node_id(&pk(name)).unwrap();
+
+        // The first old list of each node is kept; lists of nodes added since are not
+        graph.update_follows(&hex("alice"), &follows(&["bob"]), None, Some(2));
+        graph.update_follows(&hex("alice"), &follows(&["carol"]), None, Some(3));
+        graph.update_follows(&hex("carol"), &follows(&["bob"]), None, Some(1));
+        let changes = graph.take_changes().unwrap();
+        assert_eq!(changes.node_count, 3);
+        assert_eq!(changes.old_follows.len(), 1);
+        assert_eq!(changes.old_follows[&id("alice")].as_slice(), &[id("bob"), id("mallory")]);
+
+        // Blocking changes the lists of the blocked pubkey's followers
+        let (alice, bob, carol) = (id("alice"), id("bob"), id("carol"));
+        graph.block(&[hex("carol")]);
+        let changes = graph.take_changes().unwrap();
+        assert_eq!(changes.old_follows[&alice].as_slice(), &[carol]);
+        assert_eq!(changes.old_follows[&carol].as_slice(), &[bob]);
+
+        // A replaced graph can't be diffed
+        graph.replace_with(&WotGraph::new()).unwrap();
+        assert!(graph.take_changes().is_none());
+        assert!(graph.take_changes().unwrap().old_follows.is_empty());
+    }
+
+    #[test]
+    fn test_lookalikes() {
+        let graph = WotGraph::new();
+        let list = |range: std::ops::Range<u32>| range.map(|i| hex(&format!("pk{}", i))).collect::<Vec<_>>();
+        graph.update_follows(&hex("original"), &list(0..50), None, Some(1));
+        graph.update_follows(&hex("clone"), &list(0..49), None, Some(1));
+        graph.update_follows(&hex("other"), &list(100..150), None, Some(1));
+        let id = |name: &str| graph.node_id(&pk(name)).unwrap();
+
+        let matches = graph.lookalikes(id("original"), 0.8, 10);
+        assert_eq!(matches, vec![(id("clone"), 0.98)]);
+        assert!(graph.lookalikes(id("original"), 0.99, 10).is_empty());
+        assert!(graph.lookalikes(id("other"), 0.8, 10).is_empty());
+
+        // Kept in step with list changes, reloads and blocks
+        graph.update_follows(&hex("other"), &list(0..50), None, Some(2));
+        assert_eq!(graph.lookalikes(id("original"), 0.8, 10).len(), 2);
+        let fresh = WotGraph::new();
+        fresh.update_follows(&hex("original"), &list(0..50), None, Some(1));
+        fresh.update_follows(&hex("clone"), &list(0..50), None, Some(1));
+        graph.replace_with(&fresh).unwrap();
+        assert_eq!(graph.lookalikes(id("original"), 0.8, 10), vec![(id("clone"), 1.0)]);
+        graph.block(&[hex("clone")]);
+        assert!(graph.lookalikes(id("original"), 0.8, 10).is_empty());
+    }
+
+    #[test]
+    fn test_peers() {
+        let graph = WotGraph::new();
+        let list = |range: std::ops::Range<u32>| range.map(|i| hex(&format!("pk{}", i))).collect::<Vec<_>>();
+        graph.update_follows(&hex("me"), &list(0..100), None, Some(1));
+        graph.update_follows(&hex("close"), &list(0..80), None, Some(1));
+        graph.update_follows(&hex("distant"), &list(70..170), None, Some(1));
+        graph.update_follows(&hex("stranger"), &list(500..600), None, Some(1));
+        graph.update_follows(&hex("newcomer"), &list(0..5), None, Some(1));
+        let id = |name: &str| graph.node_id(&pk(name)).unwrap();
+
+        let peers = graph.peers(id("me"), 10);
+        assert_eq!(peers.iter().map(|p| p.0).collect::<Vec<_>>(), vec![id("close"), id("distant")]);
+        assert_eq!((peers[0].1, peers[0].2), (0.8, 80));
+        assert_eq!(graph.peers(id("me"), 1).len(), 1);
+        assert!(graph.peers(id("newcomer"), 10).is_empty());
+    }
+
+    #[test]
+    fn test_endorsers() {
+        let graph = WotGraph::new();
+        let follows = |pks: &[&str]| pks.iter().map(|name| hex(name)).collect::<Vec<_>>();
+        graph.update_follows(&hex("me"), &follows(&["me", "alice", "bob", "target"]), None, Some(1));
+        graph.update_follows(&hex("alice"), &follows(&["target", "carol"]), None, Some(1));
+        graph.update_follows(&hex("bob"), &follows(&["dave"]), None, Some(1));
+        graph.update_follows(&hex("carol"), &follows(&["target"]), None, Some(1));
+        graph.update_follows(&hex("dave"), &follows(&["target"]), None, Some(1));
+        graph.update_follows(&hex("erin"), &follows(&["target"]), None, Some(1));
+        graph.update_follows(&hex("target"), &follows(&["target"]), None, Some(1));
+        let id = |name: &str| graph.node_id(&pk(name)).unwrap();
+
+        assert_eq!(graph.endorsers(id("me"), id("target"), false), (vec![id("alice")], vec![]));
+        let (direct, mut indirect) = graph.endorsers(id("me"), id("target"), true);
+        indirect.sort_unstable();
+        assert_eq!((direct, indirect), (vec![id("alice")], vec![id("carol"), id("dave")]));
+        assert_eq!(graph.endorsers(id("erin"), id("target"), true), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_mutual_count() {
+        let graph = WotGraph::new();
+        let follows = |pks: &[&str]| pks.iter().map(|name| hex(name)).collect::<Vec<_>>();
+        graph.update_follows(&hex("alice"), &follows(&["alice", "bob", "carol"]), None, Some(1));
+        graph.update_follows(&hex("bob"), &follows(&["alice", "carol"]), None, Some(1));
+        graph.update_follows(&hex("carol"), &follows(&["alice"]), None, Some(1));
+        let id = |name: &str| graph.node_id(&pk(name)).unwrap();
+        let counts = |graph: &WotGraph| ["alice", "bob", "carol"].map(|pk| graph.mutual_count(id(pk)));
+        assert_eq!(counts(&graph), [2, 1, 1]);
+
+        // Dropping and re-adding a follow updates both sides
+        graph.update_follows(&hex("bob"), &follows(&["carol"]), None, Some(2));
+        assert_eq!(counts(&graph), [1, 0, 1]);
+        graph.update_follows(&hex("bob"), &follows(&["alice", "carol"]), None, Some(3));
+        assert_eq!(counts(&graph), [2, 1, 1]);
+
+        // Rebuilt by replace_with, and cleared for blocked pubkeys
+        let fresh = WotGraph::new();
+        fresh.update_follows(&hex("alice"), &follows(&["bob"]), None, Some(1));
+        fresh.update_follows(&hex("bob"), &follows(&["alice"]), None, Some(1));
+        graph.replace_with(&fresh).unwrap();
+        assert_eq!(counts(&graph), [1, 1, 0]);
+        graph.block(&[hex("bob")]);
+        assert_eq!(counts(&graph), [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_mutual_count_repeated_removal() {
+        let graph = WotGraph::new();
+        graph.update_follows(&hex("alice"), &[hex("bob")], None, Some(1));
+        graph.update_follows(&hex("bob"), &[hex("alice")], None, Some(1));
+        let (alice, bob) = (graph.node_id(&pk("alice")).unwrap(), graph.node_id(&pk("bob")).unwrap());
+        assert_eq!((graph.mutual_count(alice), graph.mutual_count(bob)), (1, 1));
+
+        // Two updates racing on alice's list can both diff against the old one; the second
+        // removal finds nothing to remove and must not count again
+        graph.update_followers(alice, &[bob], false);
+        graph.update_followers(alice, &[bob], false);
+        assert_eq!((graph.mutual_count(alice), graph.mutual_count(bob)), (0, 0));
+        graph.update_followers(alice, &[bob], true);
+        graph.update_followers(alice, &[bob], true);
+        assert_eq!((graph.mutual_count(alice), graph.mutual_count(bob)), (1, 1));
+    }
+
+    #[test]
+    fn test_churn() {
+        let graph = WotGraph::new();
+        let follows = |pks: &[&str]| pks.iter().map(|name| hex(name)).collect::<Vec<_>>();
+        graph.update_follows(&hex("alice"), &follows(&["bob", "carol"]), None, Some(1));
+        let alice = graph.node_id(&pk("alice")).unwrap();
+        assert_eq!(graph.churn(alice), 0);
+
+        graph.update_follows(&hex("alice"), &follows(&["bob", "dave", "erin"]), None, Some(2));
+        assert_eq!(graph.churn(alice), 3);
+        graph.update_follows(&hex("alice"), &follows(&["erin", "dave", "bob"]), None, Some(3));
+        assert_eq!(graph.churn(alice), 3);
+        graph.block(&[hex("alice")]);
+        assert_eq!(graph.churn(alice), 0);
+    }
+
+    #[test]
+    fn test_sorted_diff() {
+        assert_eq!(sorted_diff(&[1, 3, 5, 7], &[2, 3, 7, 8, 9]), (vec![1, 5], vec![2, 8, 9]));
+        assert_eq!(sorted_diff(&[], &[1]), (vec![], vec![1]));
+        assert_eq!(sorted_diff(&[1, 2], &[1, 2]), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_stats() {
+        let graph = WotGraph::new();
+
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("carol")], None, None);
+        graph.update_follows(&hex("bob"), &[hex("carol")], None, None);
+
+        let stats = graph.stats();
+        assert_eq!(stats.node_count, 3);
+        assert_eq!(stats.edge_count, 3);
+        assert_eq!(stats.nodes_with_follows, 2);
+    }
+
+    #[test]
+    fn test_sorted_follows() {
+        let graph = WotGraph::new();
+
+        // Insert in random order
+        graph.update_follows(
+            &hex("alice"),
+            &[hex("zebra"), hex("apple"), hex("mango")],
+            None,
+            None,
+        );
+
+        // Internal IDs should be sorted
+        let alice_id = graph.node_id(&pk("alice")).unwrap();
+
+        // Verify sorted order using with_adjacency
+        graph.with_adjacency(|follows, _| {
+            let follows_ids = &follows[alice_id as usize];
+            for i in 1..follows_ids.len() {
+                assert!(follows_ids[i - 1] < follows_ids[i], "follows should be sorted");
+            }
+        });
+    }
+
+    #[test]
+    fn test_binary_search_is_direct_follow() {
+        let graph = WotGraph::new();
+
+        graph.update_follows(
+            &hex("alice"),
+            &[hex("bob"), hex("carol"), hex("dave")],
+            None,
+            None,
+        );
+
+        let alice_id = graph.node_id(&pk("alice")).unwrap();
+        let bob_id = graph.node_id(&pk("bob")).unwrap();
+        let carol_id = graph.node_id(&pk("carol")).unwrap();
+        let eve_id = graph.get_or_create_node(&pk("eve")).unwrap();
+
+        // Test using with_adjacency and binary search
+        graph.with_adjacency(|follows, _| {
+            let alice_follows = &follows[alice_id as usize];
+            assert!(alice_follows.binary_search(&bob_id).is_ok());
+            assert!(alice_follows.binary_search(&carol_id).is_ok());
+            assert!(alice_follows.binary_search(&eve_id).is_err());
+
+            let bob_follows = &follows[bob_id as usize];
+            assert!(bob_follows.binary_search(&alice_id).is_err());
+        });
+    }
+
+    #[test]
+    fn test_adjacency_spills_and_shrinks() {
+        assert_eq!(std::mem::size_of::<Adjacency>(), std::mem::size_of::<Vec<NodeId>>());
+
+        let graph = WotGraph::new();
+        let many: Vec<String> = (0..10).map(|i| hex(&format!("f{}", i))).collect();
+        graph.update_follows(&hex("alice"), &many, None, Some(1));
+        let alice = graph.node_id(&pk("alice")).unwrap();
+        let f0 = graph.node_id(&pk("f0")).unwrap();
+        assert!(graph.adjacency.update(Side::Follows, alice, |list| list.spilled()));
+        assert!(!graph.adjacency.update(Side::Followers, f0, |list| list.spilled()));
+
+        // A shorter list goes back inline, and followers stay consistent either way
+        graph.update_follows(&hex("alice"), &many[..2], None, Some(2));
+        assert!(!graph.adjacency.update(Side::Follows, alice, |list| list.spilled()));
+        graph.with_adjacency(|follows, followers| {
+            assert_eq!(follows[alice as usize].len(), 2);
+            assert!(followers[graph.node_id(&pk("f9")).unwrap() as usize].is_empty());
+        });
+    }
+}
@@ -0,0 +1,172 @@
+//! Synthetic follow graphs for benchmarks and tests, so neither depends on a production dump.
+//! Generation is deterministic for a given seed. Node `i` gets the pubkey [`pubkey(i)`](pubkey).
+
+use super::WotGraph;
+
+/// SplitMix64: small, seedable and good enough to shape test graphs and pick query inputs
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`; `n` must be non-zero
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The 64-char hex pubkey of synthetic node `i`
+pub fn pubkey(i: usize) -> String {
+    format!("{:064x}", i)
+}
+
+/// Scale-free graph by preferential attachment: each new node follows `follows` earlier
+/// nodes, picked in proportion to how many followers they already have. Gives the few
+/// heavily followed hubs and short distances of real follow graphs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarabasiAlbert {
+    pub nodes: usize,
+    pub follows: usize,
+    pub seed: u64,
+}
+
+impl BarabasiAlbert {
+    pub fn build(&self) -> WotGraph {
+        let graph = WotGraph::new();
+        self.populate(&graph);
+        graph
+    }
+
+    /// Add the generated contact lists to an existing graph
+    pub fn populate(&self, graph: &WotGraph) {
+        let mut rng = Rng::new(self.seed);
+        // Every node once, plus once per follower, so a uniform pick is degree-proportional
+        let mut endpoints: Vec<usize> = Vec::with_capacity(self.nodes * (self.follows + 1));
+        for node in 0..self.nodes {
+            let want = self.follows.min(node);
+            let mut list: Vec<usize> = Vec::with_capacity(want);
+            // Bounded retries on repeat picks, so a few hubs can't stall generation
+            for _ in 0..want * 20 {
+                if list.len() == want {
+                    break;
+                }
+                let target = endpoints[rng.below(endpoints.len())];
+                if !list.contains(&target) {
+                    list.push(target);
+                }
+            }
+            let follows: Vec<String> = list.iter().map(|&target| pubkey(target)).collect();
+            graph.update_follows(&pubkey(node), &follows, None, Some(1));
+            endpoints.push(node);
+            endpoints.extend(list);
+        }
+    }
+}
+
+/// Small-world graph: nodes on a ring each follow the next `follows` nodes, then every
+/// follow is redirected to a random node with probability `rewire`. Low `rewire` keeps
+/// long distances and tight clusters; 1.0 gives a random graph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WattsStrogatz {
+    pub nodes: usize,
+    pub follows: usize,
+    pub rewire: f64,
+    pub seed: u64,
+}
+
+impl WattsStrogatz {
+    pub fn build(&self) -> WotGraph {
+        let graph = WotGraph::new();
+        self.populate(&graph);
+        graph
+    }
+
+    /// Add the generated contact lists to an existing graph
+    pub fn populate(&self, graph: &WotGraph) {
+        let mut rng = Rng::new(self.seed);
+        let want = self.follows.min(self.nodes.saturating_sub(1));
+        for node in 0..self.nodes {
+            let mut list: Vec<usize> = Vec::with_capacity(want);
+            for offset in 1..=want {
+                let mut target = (node + offset) % self.nodes;
+                if rng.unit() < self.rewire {
+                    // Redirect to a random node other than itself or one already followed
+                    for _ in 0..20 {
+                        let candidate = rng.below(self.nodes);
+                        if candidate != node && !list.contains(&candidate) {
+                            target = candidate;
+                            break;
+                        }
+                    }
+                }
+                if !list.contains(&target) {
+                    list.push(target);
+                }
+            }
+            let follows: Vec<String> = list.iter().map(|&target| pubkey(target)).collect();
+            graph.update_follows(&pubkey(node), &follows, None, Some(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::bfs::{compute_distance, DistanceQuery};
+
+    fn hops(graph: &WotGraph, from: usize, to: usize) -> Option<u32> {
+        let query = DistanceQuery {
+            from: pubkey(from).parse().unwrap(),
+            to: pubkey(to).parse().unwrap(),
+            max_hops: 5,
+            include_bridges: false,
+            min_followers: 0,
+        };
+        compute_distance(graph, &query).hops
+    }
+
+    #[test]
+    fn test_barabasi_albert() {
+        let model = BarabasiAlbert { nodes: 2000, follows: 5, seed: 7 };
+        let graph = model.build();
+        let stats = graph.stats();
+        assert_eq!(stats.node_count, 2000);
+        assert!(stats.edge_count > 1990 * 5);
+        // Preferential attachment gives early nodes far more followers than average
+        let first = graph.get_followers(&pubkey(0).parse().unwrap()).unwrap().len();
+        assert!(first > 10 * 5, "node 0 has {} followers", first);
+
+        // Same seed, same graph
+        assert_eq!(model.build().get_follows(&pubkey(1999).parse().unwrap()), graph.get_follows(&pubkey(1999).parse().unwrap()));
+    }
+
+    #[test]
+    fn test_watts_strogatz() {
+        let ring = WattsStrogatz { nodes: 100, follows: 2, rewire: 0.0, seed: 1 }.build();
+        assert_eq!(ring.stats().edge_count, 200);
+        assert_eq!(ring.get_follows(&pubkey(99).parse().unwrap()).unwrap().len(), 2);
+        // Two steps of two along the ring
+        assert_eq!(hops(&ring, 0, 4), Some(2));
+        assert_eq!(hops(&ring, 0, 50), None);
+
+        let rewired = WattsStrogatz { nodes: 100, follows: 2, rewire: 1.0, seed: 1 }.build();
+        assert!(rewired.get_follows(&pubkey(0).parse().unwrap()).unwrap() != ring.get_follows(&pubkey(0).parse().unwrap()).unwrap());
+        assert!(rewired.stats().edge_count <= 200);
+    }
+}
@@ -0,0 +1,55 @@
+//! Web of Trust engine behind the `wot-oracle` daemon, usable without the daemon.
+//!
+//! - [`WotGraph`]: interned, concurrently readable follow graph built from kind 3 contact lists
+//! - [`bfs`]: bidirectional BFS distance and path queries ([`DistanceQuery`], [`PathQuery`]), and
+//!   closest-of-many-sources distance ([`MultiSourceQuery`]), and distance histograms between
+//!   two pubkey sets ([`SetDistanceQuery`])
+//! - [`PageRank`]: global and personalized PageRank over the follow graph, kept fresh between full
+//!   computations by push updates from the changed follow lists
+//! - [`Components`]: membership of the largest weakly connected component
+//! - [`QueryCache`]: distance result cache, invalidated as the graph changes
+//! - [`Pubkey`]: 32-byte public key, parsed from hex or `npub1…` and written back as lowercase hex
+//!
+//! Queries take [`Pubkey`]s and results serialize them as hex. The graph stores pubkeys as lowercase
+//! hex strings, which callers get by parsing input through [`Pubkey`]. Nothing here talks to relays.
+//!
+//! ```
+//! use wot_oracle_core::{bfs, DistanceQuery, Pubkey, WotGraph};
+//!
+//! let (alice, bob, carol) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+//!
+//! let graph = WotGraph::new();
+//! graph.update_follows(&alice, &[bob.clone()], None, None);
+//! graph.update_follows(&bob, &[carol.clone()], None, None);
+//!
+//! let result = bfs::compute_distance(
+//!     &graph,
+//!     &DistanceQuery {
+//!         from: Pubkey::parse(&alice).unwrap(),
+//!         to: Pubkey::parse(&carol).unwrap(),
+//!         max_hops: 3,
+//!         include_bridges: false,
+//!         min_followers: 0,
+//!     },
+//! );
+//! assert_eq!(result.hops, Some(2));
+//! assert_eq!(result.path_count, 1);
+//! ```
+//!
+//! With the `openapi` feature the result types derive `utoipa::ToSchema`. The `synth` feature adds
+//! `graph::synth`, seeded generators for synthetic follow graphs of any size.
+
+pub mod cache;
+pub mod graph;
+pub mod types;
+
+pub use cache::{CacheKey, CacheStats, QueryCache};
+pub use graph::bfs::{
+    self, DistanceQuery, DistanceResult, MultiSourceQuery, MultiSourceResult, PathQuery, PathResult, SetDistanceQuery,
+    SetDistanceResult,
+};
+pub use graph::{
+    Adjacency, Anchors, Components, GraphLimits, LockMetricsSnapshot, Neighbors, NodeId, NodeIdsExhausted, PageRank,
+    WotGraph, ADJACENCY_SHARDS,
+};
+pub use types::{InvalidPubkey, Pubkey};
@@ -0,0 +1,159 @@
+//! Domain types shared by the graph, queries and their callers.
+
+use bech32::{Bech32, Hrp};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Human-readable part of NIP-19 public keys
+const NPUB_HRP: Hrp = Hrp::parse_unchecked("npub");
+
+/// A 32-byte Nostr public key. Hex parsing accepts either case, so the same key never becomes two
+/// nodes; output is always lowercase hex (`Display`, serde) or `npub1…` ([`Pubkey::to_bech32`]).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pubkey([u8; 32]);
+
+/// Input that is not a pubkey in the expected encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPubkey;
+
+impl fmt::Display for InvalidPubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid pubkey (expected 64 hex characters)")
+    }
+}
+
+impl std::error::Error for InvalidPubkey {}
+
+impl Pubkey {
+    /// Characters in a hex pubkey
+    pub const HEX_LEN: usize = 64;
+
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Parse 64 hex characters, in either case
+    pub fn parse(input: &str) -> Result<Self, InvalidPubkey> {
+        if !Self::is_valid(input) {
+            return Err(InvalidPubkey);
+        }
+        let mut bytes = [0u8; 32];
+        for (byte, pair) in bytes.iter_mut().zip(input.as_bytes().chunks_exact(2)) {
+            *byte = (hex_value(pair[0]) << 4) | hex_value(pair[1]);
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Whether `input` is 64 hex characters, in either case
+    pub fn is_valid(input: &str) -> bool {
+        input.len() == Self::HEX_LEN && input.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    /// Lowercase hex, the form the graph stores
+    pub fn to_hex(&self) -> String {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+        let mut hex = String::with_capacity(Self::HEX_LEN);
+        for byte in self.0 {
+            hex.push(DIGITS[usize::from(byte >> 4)] as char);
+            hex.push(DIGITS[usize::from(byte & 0x0f)] as char);
+        }
+        hex
+    }
+
+    /// Parse a NIP-19 `npub1…` key
+    pub fn from_bech32(input: &str) -> Result<Self, InvalidPubkey> {
+        let (hrp, data) = bech32::decode(input).map_err(|_| InvalidPubkey)?;
+        if hrp != NPUB_HRP {
+            return Err(InvalidPubkey);
+        }
+        data.try_into().map(Self).map_err(|_| InvalidPubkey)
+    }
+
+    /// The NIP-19 `npub1…` encoding
+    pub fn to_bech32(&self) -> String {
+        bech32::encode::<Bech32>(NPUB_HRP, &self.0).expect("32 bytes fit a bech32 string")
+    }
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+impl FromStr for Pubkey {
+    type Err = InvalidPubkey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl From<Pubkey> for String {
+    fn from(pubkey: Pubkey) -> Self {
+        pubkey.to_hex()
+    }
+}
+
+impl fmt::Display for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Debug for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pubkey({})", self)
+    }
+}
+
+impl Serialize for Pubkey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pubkey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Self::parse(&hex).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_normalizes_and_rejects() {
+        let upper = "AB".repeat(32);
+        assert_eq!(Pubkey::parse(&upper).unwrap().to_hex(), "ab".repeat(32));
+        assert_eq!(Pubkey::parse(&upper).unwrap(), Pubkey::from_bytes([0xab; 32]));
+
+        assert_eq!(Pubkey::parse(&"a".repeat(63)), Err(InvalidPubkey));
+        assert_eq!(Pubkey::parse(&"a".repeat(65)), Err(InvalidPubkey));
+        assert_eq!(Pubkey::parse(&"g".repeat(64)), Err(InvalidPubkey));
+        // Multi-byte characters can't pass for hex by byte length
+        assert_eq!(Pubkey::parse(&"é".repeat(32)), Err(InvalidPubkey));
+    }
+
+    #[test]
+    fn test_bech32_round_trip() {
+        // NIP-19 test vector
+        let pubkey = Pubkey::parse("3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d").unwrap();
+        let npub = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+        assert_eq!(pubkey.to_bech32(), npub);
+        assert_eq!(Pubkey::from_bech32(npub), Ok(pubkey));
+
+        assert_eq!(Pubkey::from_bech32(&pubkey.to_hex()), Err(InvalidPubkey));
+        let nsec = bech32::encode::<Bech32>(Hrp::parse_unchecked("nsec"), pubkey.as_bytes()).unwrap();
+        assert_eq!(Pubkey::from_bech32(&nsec), Err(InvalidPubkey));
+    }
+}
@@ -0,0 +1,285 @@
+//! `wot_oracle` Python module: the daemon's graph engine, for analysis from Python.
+//!
+//! ```python
+//! import wot_oracle
+//!
+//! graph = wot_oracle.Graph.load("data/wot.db")
+//! graph.distance(alice, bob, max_hops=3)
+//! graph.rank(anchor=alice, top=20)
+//! ```
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+use wot_oracle_core::graph::pagerank;
+use wot_oracle_core::{bfs, DistanceQuery, PathQuery, Pubkey, WotGraph};
+
+/// In-memory follow graph with distance, path and PageRank queries.
+/// Queries release the GIL, so a Graph can be shared between Python threads.
+#[pyclass(name = "Graph", module = "wot_oracle", frozen)]
+struct Graph {
+    inner: WotGraph,
+}
+
+#[pymethods]
+impl Graph {
+    #[new]
+    fn new() -> Self {
+        Self { inner: WotGraph::new() }
+    }
+
+    /// Load the follow graph from a wot-oracle SQLite database.
+    /// The file is opened read-only, so this is safe while the daemon is running.
+    #[staticmethod]
+    fn load(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+        let inner = py
+            .allow_threads(|| load_sqlite(&path))
+            .map_err(|e| PyIOError::new_err(format!("Failed to load {}: {}", path.display(), e)))?;
+        Ok(Self { inner })
+    }
+
+    /// Replace `pubkey`'s follow list. With `created_at`, older lists than the current one are
+    /// ignored. Hex in either case is accepted and stored lowercase. Returns whether the graph
+    /// changed.
+    #[pyo3(signature = (pubkey, follows, created_at=None))]
+    fn update_follows(&self, pubkey: &str, follows: Vec<String>, created_at: Option<i64>) -> PyResult<bool> {
+        let pubkey = check_pubkey(pubkey)?;
+        let follows = follows
+            .into_iter()
+            .map(|follow| check_pubkey(&follow).map(String::from))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(self.inner.update_follows(&pubkey.to_hex(), &follows, None, created_at))
+    }
+
+    /// Follow distance from `source` to `target`, as a dict shaped like the HTTP `/distance` response
+    #[pyo3(signature = (source, target, max_hops=3, include_bridges=false))]
+    fn distance<'py>(
+        &self,
+        py: Python<'py>,
+        source: &str,
+        target: &str,
+        max_hops: u8,
+        include_bridges: bool,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let source = check_pubkey(source)?;
+        let target = check_pubkey(target)?;
+        check_max_hops(max_hops)?;
+
+        let query = DistanceQuery {
+            from: source,
+            to: target,
+            max_hops,
+            include_bridges,
+            min_followers: 0,
+        };
+        let result = py.allow_threads(|| bfs::compute_distance(&self.inner, &query));
+
+        let dict = PyDict::new(py);
+        dict.set_item("from", result.from.to_hex())?;
+        dict.set_item("to", result.to.to_hex())?;
+        dict.set_item("hops", result.hops)?;
+        dict.set_item("path_count", result.path_count)?;
+        dict.set_item("mutual_follow", result.mutual_follow)?;
+        if let Some(bridges) = result.bridges {
+            dict.set_item("bridges", to_strings(bridges))?;
+        }
+        if let Some(counts) = result.bridge_path_counts {
+            dict.set_item("bridge_path_counts", counts)?;
+        }
+        Ok(dict)
+    }
+
+    /// Intermediate pubkeys on one shortest path from `source` to `target` (empty for a direct
+    /// follow), or None if unreachable
+    #[pyo3(signature = (source, target, max_hops=3))]
+    fn path(&self, py: Python<'_>, source: &str, target: &str, max_hops: u8) -> PyResult<Option<Vec<String>>> {
+        let source = check_pubkey(source)?;
+        let target = check_pubkey(target)?;
+        check_max_hops(max_hops)?;
+
+        let query = PathQuery {
+            from: source,
+            to: target,
+            max_hops,
+            min_followers: 0,
+        };
+        let result = py.allow_threads(|| bfs::compute_path(&self.inner, &query));
+        Ok(result.path.map(to_strings))
+    }
+
+    /// Top `top` pubkeys by global PageRank, or by PageRank personalized to `anchor`
+    /// (excluding the anchor itself). Scores sum to 1 over the whole graph.
+    #[pyo3(signature = (anchor=None, top=100))]
+    fn rank(&self, py: Python<'_>, anchor: Option<&str>, top: usize) -> PyResult<Vec<(String, f64)>> {
+        let seed = match anchor {
+            Some(anchor) => {
+                let anchor = check_pubkey(anchor)?;
+                match self.inner.node_id(&anchor) {
+                    Some(id) => Some(id),
+                    // An anchor outside the graph trusts no one
+                    None => return Ok(Vec::new()),
+                }
+            }
+            None => None,
+        };
+
+        let ranked = py.allow_threads(|| {
+            let scores = self.inner.with_adjacency(|follows, _| match seed {
+                Some(seed) => pagerank::personalized(follows, seed),
+                None => pagerank::compute(follows),
+            });
+            pagerank::top_n(&scores, top, seed)
+        });
+
+        Ok(ranked
+            .into_iter()
+            .filter_map(|(id, score)| self.inner.pubkey(id).map(|pk| (pk.to_hex(), score)))
+            .collect())
+    }
+
+    /// Pubkeys followed by `pubkey`
+    fn follows(&self, pubkey: &str) -> Vec<String> {
+        lookup_key(pubkey).and_then(|pk| self.inner.get_follows(&pk)).map(to_strings).unwrap_or_default()
+    }
+
+    /// Pubkeys following `pubkey`
+    fn followers(&self, pubkey: &str) -> Vec<String> {
+        lookup_key(pubkey).and_then(|pk| self.inner.get_followers(&pk)).map(to_strings).unwrap_or_default()
+    }
+
+    /// Node and edge counts, as a dict shaped like the graph fields of `/stats`
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = self.inner.stats();
+        let dict = PyDict::new(py);
+        dict.set_item("node_count", stats.node_count)?;
+        dict.set_item("edge_count", stats.edge_count)?;
+        dict.set_item("nodes_with_follows", stats.nodes_with_follows)?;
+        dict.set_item("quarantined_nodes", stats.quarantined_nodes)?;
+        dict.set_item("rejected_lists", stats.rejected_lists)?;
+        Ok(dict)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.stats().node_count
+    }
+
+    fn __contains__(&self, pubkey: &str) -> bool {
+        lookup_key(pubkey).and_then(|pk| self.inner.node_id(&pk)).is_some()
+    }
+
+    fn __repr__(&self) -> String {
+        let stats = self.inner.stats();
+        format!("<wot_oracle.Graph nodes={} edges={}>", stats.node_count, stats.edge_count)
+    }
+}
+
+/// `pubkey` lowercased, as the graph stores it
+fn check_pubkey(pubkey: &str) -> PyResult<Pubkey> {
+    Pubkey::parse(pubkey)
+        .map_err(|_| PyValueError::new_err(format!("Invalid pubkey (expected 64 hex chars): {:?}", pubkey)))
+}
+
+/// Lookups match whatever case the caller used; anything that isn't a pubkey simply isn't found
+fn lookup_key(pubkey: &str) -> Option<Pubkey> {
+    Pubkey::parse(pubkey).ok()
+}
+
+fn check_max_hops(max_hops: u8) -> PyResult<()> {
+    if max_hops == 0 {
+        return Err(PyValueError::new_err("max_hops must be at least 1"));
+    }
+    Ok(())
+}
+
+fn to_strings(pubkeys: Vec<Pubkey>) -> Vec<String> {
+    pubkeys.into_iter().map(String::from).collect()
+}
+
+/// Rebuild the graph from the daemon's `nodes` and `edges` tables
+fn load_sqlite(path: &Path) -> Result<WotGraph, Box<dyn std::error::Error + Send + Sync>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let graph = WotGraph::new();
+
+    // Nodes first, in ID order, so nodes without follows or followers are present too;
+    // rows that aren't hex pubkeys are skipped, as follow lists drop them
+    let mut nodes = conn.prepare("SELECT pubkey FROM nodes ORDER BY id")?;
+    for pubkey in nodes.query_map([], |row| row.get::<_, String>(0))? {
+        if let Ok(pubkey) = Pubkey::parse(&pubkey?) {
+            graph.get_or_create_node(&pubkey)?;
+        }
+    }
+
+    let mut edges = conn.prepare(
+        "SELECT n.pubkey, n.kind3_event_id, n.kind3_created_at, f.pubkey
+         FROM edges e
+         JOIN nodes n ON e.follower_id = n.id
+         JOIN nodes f ON e.followed_id = f.id
+         ORDER BY e.follower_id",
+    )?;
+    let mut rows = edges.query([])?;
+
+    // Rows arrive grouped by follower; flush each group as one follow list
+    let mut current: Option<(String, Option<String>, Option<i64>)> = None;
+    let mut follows: Vec<String> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let follower: String = row.get(0)?;
+        if current.as_ref().is_some_and(|(pk, _, _)| *pk != follower) {
+            if let Some((pubkey, event_id, created_at)) = current.take() {
+                graph.update_follows(&pubkey, &std::mem::take(&mut follows), event_id, created_at);
+            }
+        }
+        if current.is_none() {
+            current = Some((follower, row.get(1)?, row.get(2)?));
+        }
+        follows.push(row.get(3)?);
+    }
+    if let Some((pubkey, event_id, created_at)) = current {
+        graph.update_follows(&pubkey, &follows, event_id, created_at);
+    }
+
+    Ok(graph)
+}
+
+#[pymodule]
+fn wot_oracle(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Graph>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+    const C: &str = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+
+    #[test]
+    fn test_load_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wot.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE nodes (id INTEGER PRIMARY KEY, pubkey TEXT NOT NULL UNIQUE,
+                                 kind3_event_id TEXT, kind3_created_at INTEGER, updated_at INTEGER NOT NULL);
+             CREATE TABLE edges (follower_id INTEGER NOT NULL, followed_id INTEGER NOT NULL);
+             INSERT INTO nodes VALUES (1, '{A}', 'e1', 100, 0), (2, '{B}', 'e2', 200, 0), (3, '{C}', NULL, NULL, 0);
+             INSERT INTO edges VALUES (1, 2), (1, 3), (2, 3);"
+        ))
+        .unwrap();
+        drop(conn);
+
+        let graph = load_sqlite(&path).unwrap();
+        assert_eq!(graph.stats().node_count, 3);
+        assert_eq!(graph.stats().edge_count, 3);
+
+        let key = |pk: &str| Pubkey::parse(pk).unwrap();
+        let mut follows = graph.get_follows(&key(A)).unwrap();
+        follows.sort();
+        assert_eq!(follows, vec![key(B), key(C)]);
+        assert_eq!(graph.get_followers(&key(C)).unwrap().len(), 2);
+        assert_eq!(graph.get_node_info(&key(B)).unwrap().kind3_created_at, Some(200));
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const ALERT_QUEUE_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    RelayDown,
+    IngestionLag,
+    PersistenceFailure,
+    Anomaly,
+}
+
+impl AlertKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::RelayDown => "relay_down",
+            Self::IngestionLag => "ingestion_lag",
+            Self::PersistenceFailure => "persistence_failure",
+            Self::Anomaly => "anomaly",
+        }
+    }
+}
+
+/// An operator notification. Alerts with the same kind and subject are deduplicated.
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    /// What the alert is about: a relay URL, a pubkey, or a fixed name for global conditions
+    pub subject: String,
+    pub message: String,
+    pub timestamp: i64,
+    /// Identical alerts dropped during the cooldown before this one
+    pub suppressed: u32,
+}
+
+/// A channel alerts are delivered through
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn deliver(&self, alert: &Alert) -> Result<()>;
+}
+
+/// POSTs each alert as JSON to `ALERT_WEBHOOK_URL`
+pub struct WebhookSink {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl AlertSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn deliver(&self, alert: &Alert) -> Result<()> {
+        self.http
+            .post(&self.url)
+            .json(alert)
+            .send()
+            .await?
+            .error_for_status()
+            .context("Webhook rejected alert")?;
+        Ok(())
+    }
+}
+
+/// Sends each alert as a NIP-04 encrypted DM from the oracle key to `ALERT_NOSTR_PUBKEY`
+pub struct NostrDmSink {
+    client: Client,
+    keys: Keys,
+    recipient: PublicKey,
+}
+
+impl NostrDmSink {
+    pub async fn connect(keys: Keys, recipient: PublicKey, relays: &[String]) -> Self {
+        let client = Client::new(&keys);
+        for relay_url in relays {
+            if let Err(e) = client.add_relay(relay_url).await {
+                warn!("Alert DM client failed to add relay {}: {}", relay_url, e);
+            }
+        }
+        client.connect().await;
+        Self { client, keys, recipient }
+    }
+}
+
+#[async_trait]
+impl AlertSink for NostrDmSink {
+    fn name(&self) -> &'static str {
+        "nostr_dm"
+    }
+
+    async fn deliver(&self, alert: &Alert) -> Result<()> {
+        let mut content = format!("[wot-oracle] {}: {}", alert.kind.as_str(), alert.message);
+        if alert.suppressed > 0 {
+            content.push_str(&format!(" ({} similar alerts suppressed)", alert.suppressed));
+        }
+        let encrypted = nip04::encrypt(self.keys.secret_key(), &self.recipient, content)?;
+        let builder = EventBuilder::new(Kind::EncryptedDirectMessage, encrypted, [Tag::public_key(self.recipient)]);
+        self.client.send_event_builder(builder).await?;
+        Ok(())
+    }
+}
+
+/// Suppressed counts are reported with the next delivery if it comes within this long
+const SUPPRESSED_MEMORY: Duration = Duration::from_secs(86_400);
+
+/// Per kind and subject: alerts within `cooldown` of the last delivered one are dropped and counted
+struct Cooldowns {
+    cooldown: Duration,
+    last: HashMap<(AlertKind, String), (Instant, u32)>,
+}
+
+impl Cooldowns {
+    fn new(cooldown: Duration) -> Self {
+        Self { cooldown, last: HashMap::new() }
+    }
+
+    /// None if the alert should be dropped, else how many were suppressed since the last delivery
+    fn admit(&mut self, kind: AlertKind, subject: &str, now: Instant) -> Option<u32> {
+        let cooldown = self.cooldown;
+        let memory = cooldown.max(SUPPRESSED_MEMORY);
+        self.last.retain(|_, (at, suppressed)| {
+            let age = now.duration_since(*at);
+            age < cooldown || (*suppressed > 0 && age < memory)
+        });
+
+        let key = (kind, subject.to_string());
+        match self.last.get_mut(&key) {
+            Some((at, suppressed)) if now.duration_since(*at) < cooldown => {
+                *suppressed += 1;
+                None
+            }
+            Some(entry) => {
+                let suppressed = entry.1;
+                *entry = (now, 0);
+                Some(suppressed)
+            }
+            None => {
+                self.last.insert(key, (now, 0));
+                Some(0)
+            }
+        }
+    }
+}
+
+/// Cheap handle for raising alerts; a no-op when no alert channel is configured
+#[derive(Clone, Default)]
+pub struct Alerts {
+    tx: Option<mpsc::Sender<Alert>>,
+}
+
+impl Alerts {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn raise(&self, kind: AlertKind, subject: impl Into<String>, message: impl Into<String>) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        let alert = Alert {
+            kind,
+            subject: subject.into(),
+            message: message.into(),
+            timestamp: chrono::Utc::now().timestamp(),
+            suppressed: 0,
+        };
+        if let Err(e) = tx.try_send(alert) {
+            warn!("Alert queue full, dropping alert: {}", e);
+        }
+    }
+}
+
+/// Start the alert dispatcher for the configured channels. DMs are sent from the oracle key,
+/// so `ALERT_NOSTR_PUBKEY` needs `DVM_PRIVATE_KEY`.
+pub fn start(config: Arc<Config>, oracle_keys: Option<Keys>) -> Alerts {
+    let recipient = config.alert_nostr_pubkey.as_deref().and_then(|pk| match PublicKey::parse(pk) {
+        Ok(pk) => Some(pk),
+        Err(e) => {
+            error!("Invalid ALERT_NOSTR_PUBKEY: {}", e);
+            None
+        }
+    });
+    let dm = match (recipient, oracle_keys) {
+        (Some(recipient), Some(keys)) => Some((keys, recipient)),
+        (Some(_), None) => {
+            error!("ALERT_NOSTR_PUBKEY set but no valid DVM_PRIVATE_KEY; alert DMs disabled");
+            None
+        }
+        (None, _) => None,
+    };
+
+    if config.alert_webhook_url.is_none() && dm.is_none() {
+        info!("Alerting disabled (no ALERT_WEBHOOK_URL or ALERT_NOSTR_PUBKEY)");
+        return Alerts::disabled();
+    }
+
+    let (tx, rx) = mpsc::channel(ALERT_QUEUE_SIZE);
+    tokio::spawn(async move {
+        let mut sinks: Vec<Box<dyn AlertSink>> = Vec::new();
+        if let Some(url) = &config.alert_webhook_url {
+            sinks.push(Box::new(WebhookSink::new(url)));
+        }
+        if let Some((keys, recipient)) = dm {
+            sinks.push(Box::new(NostrDmSink::connect(keys, recipient, &config.relays).await));
+        }
+        let cooldown = Duration::from_secs(config.alert_cooldown_secs);
+        info!("Alerting enabled ({} channels, {}s cooldown)", sinks.len(), cooldown.as_secs());
+        dispatch(rx, sinks, cooldown).await;
+    });
+
+    Alerts { tx: Some(tx) }
+}
+
+async fn dispatch(mut rx: mpsc::Receiver<Alert>, sinks: Vec<Box<dyn AlertSink>>, cooldown: Duration) {
+    let mut cooldowns = Cooldowns::new(cooldown);
+    while let Some(mut alert) = rx.recv().await {
+        let Some(suppressed) = cooldowns.admit(alert.kind, &alert.subject, Instant::now()) else {
+            continue;
+        };
+        alert.suppressed = suppressed;
+
+        for sink in &sinks {
+            if let Err(e) = sink.deliver(&alert).await {
+                warn!("Failed to deliver {} alert via {}: {}", alert.kind.as_str(), sink.name(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+
+    #[test]
+    fn test_cooldowns() {
+        let mut cooldowns = Cooldowns::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert_eq!(cooldowns.admit(AlertKind::RelayDown, "wss://a", start), Some(0));
+        assert_eq!(cooldowns.admit(AlertKind::RelayDown, "wss://a", start + Duration::from_secs(10)), None);
+        assert_eq!(cooldowns.admit(AlertKind::RelayDown, "wss://a", start + Duration::from_secs(20)), None);
+        // Other subjects and kinds are independent
+        assert_eq!(cooldowns.admit(AlertKind::RelayDown, "wss://b", start), Some(0));
+        assert_eq!(cooldowns.admit(AlertKind::IngestionLag, "wss://a", start), Some(0));
+
+        // After the cooldown the next alert goes out and reports what was dropped
+        assert_eq!(cooldowns.admit(AlertKind::RelayDown, "wss://a", start + Duration::from_secs(61)), Some(2));
+        assert_eq!(cooldowns.admit(AlertKind::RelayDown, "wss://a", start + Duration::from_secs(62)), None);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_sink() {
+        let (tx, mut rx) = mpsc::channel::<serde_json::Value>(1);
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                tx.send(body).await.unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let sink = WebhookSink::new(&format!("http://{}/hook", addr));
+        let alert = Alert {
+            kind: AlertKind::PersistenceFailure,
+            subject: "database".to_string(),
+            message: "disk full".to_string(),
+            timestamp: 1,
+            suppressed: 3,
+        };
+        sink.deliver(&alert).await.unwrap();
+
+        let body = rx.recv().await.unwrap();
+        assert_eq!(body["kind"], "persistence_failure");
+        assert_eq!(body["subject"], "database");
+        assert_eq!(body["suppressed"], 3);
+    }
+}
@@ -0,0 +1,333 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use super::admin::constant_time_eq;
+use super::http::{AppState, ErrorResponse};
+use super::ratelimit::ClientIp;
+use crate::config::{parse_ip_net, Config};
+use crate::types::Pubkey;
+
+/// Header carrying one of `API_KEYS`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// The operator-managed access lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessList {
+    IpAllow,
+    IpDeny,
+    PubkeyAllow,
+    PubkeyDeny,
+    /// Registered query sources, enforced when `RESTRICT_SOURCES` is set
+    SourceAllow,
+}
+
+impl FromStr for AccessList {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ip-allow" => Ok(Self::IpAllow),
+            "ip-deny" => Ok(Self::IpDeny),
+            "pubkey-allow" => Ok(Self::PubkeyAllow),
+            "pubkey-deny" => Ok(Self::PubkeyDeny),
+            "source-allow" => Ok(Self::SourceAllow),
+            _ => Err(()),
+        }
+    }
+}
+
+/// IP and subject-pubkey allow/deny lists, editable at runtime.
+/// An empty allowlist admits everyone; a denylist entry always wins.
+/// With `RESTRICT_SOURCES`, the source allowlist is enforced even when empty.
+pub struct AccessLists {
+    ip_allow: RwLock<Vec<IpNet>>,
+    ip_deny: RwLock<Vec<IpNet>>,
+    pubkey_allow: RwLock<HashSet<String>>,
+    pubkey_deny: RwLock<HashSet<String>>,
+    restrict_sources: bool,
+    source_allow: RwLock<HashSet<String>>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AccessListsSnapshot {
+    pub ip_allow: Vec<String>,
+    pub ip_deny: Vec<String>,
+    pub pubkey_allow: Vec<String>,
+    pub pubkey_deny: Vec<String>,
+    pub restrict_sources: bool,
+    pub source_allow: Vec<String>,
+}
+
+impl AccessLists {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            ip_allow: RwLock::new(config.ip_allowlist.clone()),
+            ip_deny: RwLock::new(config.ip_denylist.clone()),
+            pubkey_allow: RwLock::new(normalize_pubkeys(&config.pubkey_allowlist)),
+            pubkey_deny: RwLock::new(normalize_pubkeys(&config.pubkey_denylist)),
+            restrict_sources: config.restrict_sources,
+            source_allow: RwLock::new(normalize_pubkeys(&config.source_allowlist)),
+            api_keys: config.api_keys.clone(),
+        }
+    }
+
+    pub fn is_ip_allowed(&self, ip: IpAddr) -> bool {
+        if self.ip_deny.read().iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        let allow = self.ip_allow.read();
+        allow.is_empty() || allow.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Whether a pubkey may be the subject of a query
+    pub fn is_pubkey_allowed(&self, pubkey: &str) -> bool {
+        let pubkey = pubkey.to_ascii_lowercase();
+        if self.pubkey_deny.read().contains(&pubkey) {
+            return false;
+        }
+        let allow = self.pubkey_allow.read();
+        allow.is_empty() || allow.contains(&pubkey)
+    }
+
+    /// Whether a pubkey may be the `from` of a query made without an API key
+    pub fn is_source_allowed(&self, pubkey: &str) -> bool {
+        !self.restrict_sources || self.source_allow.read().contains(&pubkey.to_ascii_lowercase())
+    }
+
+    /// Whether `key` is one of `API_KEYS`
+    pub fn is_api_key(&self, key: &str) -> bool {
+        self.api_keys.iter().any(|k| constant_time_eq(k.as_bytes(), key.as_bytes()))
+    }
+
+    /// Add entries to a list, returning how many were new.
+    /// All entries are validated before any are applied.
+    pub fn add(&self, list: AccessList, entries: &[String]) -> Result<usize, String> {
+        match list {
+            AccessList::IpAllow | AccessList::IpDeny => {
+                let nets = parse_nets(entries)?;
+                let mut target = match list {
+                    AccessList::IpAllow => self.ip_allow.write(),
+                    _ => self.ip_deny.write(),
+                };
+                let mut added = 0;
+                for net in nets {
+                    if !target.contains(&net) {
+                        target.push(net);
+                        added += 1;
+                    }
+                }
+                Ok(added)
+            }
+            AccessList::PubkeyAllow | AccessList::PubkeyDeny | AccessList::SourceAllow => {
+                let pubkeys = entries
+                    .iter()
+                    .map(|pk| Pubkey::parse(pk).map_err(|_| format!("Invalid pubkey: {}", pk)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut target = self.pubkeys(list);
+                Ok(pubkeys.into_iter().filter(|pk| target.insert(pk.to_string())).count())
+            }
+        }
+    }
+
+    /// Remove entries from a list, returning how many were present
+    pub fn remove(&self, list: AccessList, entries: &[String]) -> Result<usize, String> {
+        match list {
+            AccessList::IpAllow | AccessList::IpDeny => {
+                let nets = parse_nets(entries)?;
+                let mut target = match list {
+                    AccessList::IpAllow => self.ip_allow.write(),
+                    _ => self.ip_deny.write(),
+                };
+                let before = target.len();
+                target.retain(|net| !nets.contains(net));
+                Ok(before - target.len())
+            }
+            AccessList::PubkeyAllow | AccessList::PubkeyDeny | AccessList::SourceAllow => {
+                let mut target = self.pubkeys(list);
+                Ok(entries
+                    .iter()
+                    .filter(|pk| target.remove(&pk.to_ascii_lowercase()))
+                    .count())
+            }
+        }
+    }
+
+    fn pubkeys(&self, list: AccessList) -> parking_lot::RwLockWriteGuard<'_, HashSet<String>> {
+        match list {
+            AccessList::PubkeyAllow => self.pubkey_allow.write(),
+            AccessList::SourceAllow => self.source_allow.write(),
+            _ => self.pubkey_deny.write(),
+        }
+    }
+
+    pub fn snapshot(&self) -> AccessListsSnapshot {
+        let sorted = |set: &RwLock<HashSet<String>>| {
+            let mut pubkeys: Vec<String> = set.read().iter().cloned().collect();
+            pubkeys.sort_unstable();
+            pubkeys
+        };
+
+        AccessListsSnapshot {
+            ip_allow: self.ip_allow.read().iter().map(|n| n.to_string()).collect(),
+            ip_deny: self.ip_deny.read().iter().map(|n| n.to_string()).collect(),
+            pubkey_allow: sorted(&self.pubkey_allow),
+            pubkey_deny: sorted(&self.pubkey_deny),
+            restrict_sources: self.restrict_sources,
+            source_allow: sorted(&self.source_allow),
+        }
+    }
+}
+
+fn normalize_pubkeys(pubkeys: &[String]) -> HashSet<String> {
+    pubkeys.iter().map(|pk| pk.to_ascii_lowercase()).collect()
+}
+
+fn parse_nets(entries: &[String]) -> Result<Vec<IpNet>, String> {
+    entries
+        .iter()
+        .map(|e| parse_ip_net(e.trim()).ok_or_else(|| format!("Invalid IP or CIDR: {}", e)))
+        .collect()
+}
+
+/// Whether a request may name any query source despite `RESTRICT_SOURCES`, because it
+/// presents one of `API_KEYS` in `X-API-Key`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceAccess {
+    api_key: bool,
+}
+
+impl SourceAccess {
+    pub fn new(api_key: Option<&str>, access: &AccessLists) -> Self {
+        Self { api_key: api_key.is_some_and(|key| access.is_api_key(key)) }
+    }
+
+    pub fn from_headers(headers: &HeaderMap, access: &AccessLists) -> Self {
+        Self::new(headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()), access)
+    }
+
+    /// Reject a query `from` that isn't a registered source
+    pub fn check(&self, access: &AccessLists, source: &str) -> Result<(), ErrorResponse> {
+        match self.api_key || access.is_source_allowed(source) {
+            true => Ok(()),
+            false => Err(ErrorResponse::source_denied()),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for SourceAccess {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers, &state.access))
+    }
+}
+
+/// Middleware rejecting requests from denied (or non-allowlisted) client IPs.
+/// Runs inside the rate limiter, which resolves the client IP.
+pub async fn ip_filter(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if let Some(ClientIp(ip)) = req.extensions().get::<ClientIp>().copied() {
+        if !state.access.is_ip_allowed(ip) {
+            return ErrorResponse::ip_denied().into_response();
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const BOB: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    fn empty_lists() -> AccessLists {
+        AccessLists {
+            ip_allow: RwLock::new(Vec::new()),
+            ip_deny: RwLock::new(Vec::new()),
+            pubkey_allow: RwLock::new(HashSet::new()),
+            pubkey_deny: RwLock::new(HashSet::new()),
+            restrict_sources: false,
+            source_allow: RwLock::new(HashSet::new()),
+            api_keys: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_ip_deny_overrides_allow() {
+        let lists = empty_lists();
+        let ip: IpAddr = "10.1.2.3".parse().unwrap();
+        assert!(lists.is_ip_allowed(ip));
+
+        lists.add(AccessList::IpAllow, &["10.0.0.0/8".to_string()]).unwrap();
+        assert!(lists.is_ip_allowed(ip));
+        assert!(!lists.is_ip_allowed("192.168.0.1".parse().unwrap()));
+
+        lists.add(AccessList::IpDeny, &["10.1.2.3".to_string()]).unwrap();
+        assert!(!lists.is_ip_allowed(ip));
+
+        assert_eq!(lists.remove(AccessList::IpDeny, &["10.1.2.3".to_string()]).unwrap(), 1);
+        assert!(lists.is_ip_allowed(ip));
+    }
+
+    #[test]
+    fn test_pubkey_lists() {
+        let lists = empty_lists();
+        assert!(lists.is_pubkey_allowed(ALICE));
+
+        lists.add(AccessList::PubkeyDeny, &[ALICE.to_uppercase()]).unwrap();
+        assert!(!lists.is_pubkey_allowed(ALICE));
+        assert!(lists.is_pubkey_allowed(BOB));
+
+        lists.add(AccessList::PubkeyAllow, &[ALICE.to_string()]).unwrap();
+        assert!(!lists.is_pubkey_allowed(BOB)); // Allowlist now non-empty
+        assert!(!lists.is_pubkey_allowed(ALICE)); // Deny still wins
+    }
+
+    #[test]
+    fn test_source_restriction() {
+        let open = empty_lists();
+        assert!(open.is_source_allowed(ALICE));
+
+        let lists = AccessLists { restrict_sources: true, api_keys: vec!["secret".to_string()], ..empty_lists() };
+        assert!(!lists.is_source_allowed(ALICE)); // Enforced even while empty
+        lists.add(AccessList::SourceAllow, &[ALICE.to_uppercase()]).unwrap();
+        assert!(lists.is_source_allowed(ALICE));
+        assert!(!lists.is_source_allowed(BOB));
+        assert_eq!(lists.snapshot().source_allow, vec![ALICE.to_string()]);
+
+        let mut headers = HeaderMap::new();
+        let anonymous = SourceAccess::from_headers(&headers, &lists);
+        assert!(anonymous.check(&lists, ALICE).is_ok());
+        assert_eq!(anonymous.check(&lists, BOB).unwrap_err().code, "SOURCE_DENIED");
+        headers.insert(API_KEY_HEADER, "wrong".parse().unwrap());
+        assert!(SourceAccess::from_headers(&headers, &lists).check(&lists, BOB).is_err());
+        headers.insert(API_KEY_HEADER, "secret".parse().unwrap());
+        assert!(SourceAccess::from_headers(&headers, &lists).check(&lists, BOB).is_ok());
+
+        assert_eq!(lists.remove(AccessList::SourceAllow, &[ALICE.to_string()]).unwrap(), 1);
+        assert!(!lists.is_source_allowed(ALICE));
+    }
+
+    #[test]
+    fn test_invalid_entries_rejected() {
+        let lists = empty_lists();
+        assert!(lists.add(AccessList::IpDeny, &["not-an-ip".to_string()]).is_err());
+        assert!(lists.add(AccessList::PubkeyDeny, &["abc".to_string()]).is_err());
+        assert!(lists.snapshot().ip_deny.is_empty());
+        assert!(lists.snapshot().pubkey_deny.is_empty());
+    }
+}
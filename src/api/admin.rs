@@ -0,0 +1,657 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+
+use super::access::{AccessList, AccessListsSnapshot};
+use super::http::{AppState, ErrorResponse};
+use crate::config::suffixed_path;
+use crate::db::{BlockedPubkey, FollowAnomaly};
+use crate::diagnostics::{self, DiagnosticDump};
+use crate::namespace;
+use crate::report::{self, GraphReport, LiveIngestion};
+use crate::sync::pause::IngestionStatus;
+use crate::sync::watchlist::WatchedPubkey;
+use crate::types::Pubkey;
+
+const ANOMALIES_LIMIT_DEFAULT: usize = 50;
+const ANOMALIES_LIMIT_MAX: usize = 500;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AccessEntriesRequest {
+    pub entries: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AccessUpdateResponse {
+    pub list: String,
+    pub changed: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WatchlistRequest {
+    /// Hex pubkeys
+    pub pubkeys: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WatchlistUpdateResponse {
+    pub changed: usize,
+    pub watched: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BlockRequest {
+    /// Hex pubkeys
+    pub pubkeys: Vec<String>,
+    /// Why they are blocked (e.g. a takedown reference); ignored on removal
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BlocklistUpdateResponse {
+    pub changed: usize,
+    pub blocked: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheInvalidateResponse {
+    /// Cached results dropped across all graphs (approximate)
+    pub invalidated: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GraphSnapshot {
+    pub graph: String,
+    /// Database copy written on the server
+    pub path: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GraphReload {
+    pub graph: String,
+    pub node_count: usize,
+    pub edge_count: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PauseRequest {
+    /// Shown in `/health` and `/stats` while paused (e.g. "compaction")
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AnomaliesQueryParams {
+    /// Most recent anomalies to return (default 50, max 500)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReportQueryParams {
+    /// `json` (default) or `html`
+    pub format: Option<String>,
+    /// Days of growth to cover (1-365, default 28)
+    pub days: Option<u32>,
+    /// Report on this graph only (default: every graph)
+    pub graph: Option<String>,
+}
+
+/// Admin routes, mounted under `/admin` when `ADMIN_TOKEN` is set.
+/// Every route requires `Authorization: Bearer <ADMIN_TOKEN>`.
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/access", get(get_access))
+        .route("/access/:list", post(add_access).delete(remove_access))
+        .route("/anomalies", get(get_anomalies))
+        .route("/watchlist", get(get_watchlist).post(add_watchlist).delete(remove_watchlist))
+        .route("/blocklist", get(get_blocklist).post(add_blocklist).delete(remove_blocklist))
+        .route("/cache/invalidate", post(invalidate_cache))
+        .route("/metrics/reset", post(reset_metrics))
+        .route("/snapshot", post(snapshot))
+        .route("/reload", post(reload))
+        .route("/ingestion/pause", post(pause_ingestion))
+        .route("/ingestion/resume", post(resume_ingestion))
+        .route("/debug", get(debug))
+        .route("/report", get(get_report))
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+async fn require_admin(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(expected) = state.config.admin_token.as_deref() else {
+        return ErrorResponse::unauthorized().into_response();
+    };
+
+    let provided = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => next.run(req).await,
+        _ => ErrorResponse::unauthorized().into_response(),
+    }
+}
+
+/// Compare secrets without short-circuiting on the first differing byte
+pub(super) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn parse_list(list: &str) -> Result<AccessList, ErrorResponse> {
+    list.parse().map_err(|_| ErrorResponse::invalid(
+        "INVALID_LIST",
+        "Unknown list (expected ip-allow, ip-deny, pubkey-allow, pubkey-deny or source-allow)",
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/access",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Current access lists", body = AccessListsSnapshot),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn get_access(State(state): State<AppState>) -> Json<AccessListsSnapshot> {
+    Json(state.access.snapshot())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/access/{list}",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(("list" = String, Path, description = "ip-allow, ip-deny, pubkey-allow, pubkey-deny or source-allow")),
+    request_body = AccessEntriesRequest,
+    responses(
+        (status = 200, description = "Number of entries added", body = AccessUpdateResponse),
+        (status = 400, description = "Unknown list or invalid entry", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn add_access(
+    State(state): State<AppState>,
+    Path(list): Path<String>,
+    Json(request): Json<AccessEntriesRequest>,
+) -> Result<Json<AccessUpdateResponse>, ErrorResponse> {
+    let access_list = parse_list(&list)?;
+    let changed = state
+        .access
+        .add(access_list, &request.entries)
+        .map_err(|e| ErrorResponse::invalid("INVALID_ENTRY", e))?;
+
+    info!("Admin added {} entries to {}", changed, list);
+    Ok(Json(AccessUpdateResponse { list, changed }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/access/{list}",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(("list" = String, Path, description = "ip-allow, ip-deny, pubkey-allow, pubkey-deny or source-allow")),
+    request_body = AccessEntriesRequest,
+    responses(
+        (status = 200, description = "Number of entries removed", body = AccessUpdateResponse),
+        (status = 400, description = "Unknown list or invalid entry", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn remove_access(
+    State(state): State<AppState>,
+    Path(list): Path<String>,
+    Json(request): Json<AccessEntriesRequest>,
+) -> Result<Json<AccessUpdateResponse>, ErrorResponse> {
+    let access_list = parse_list(&list)?;
+    let changed = state
+        .access
+        .remove(access_list, &request.entries)
+        .map_err(|e| ErrorResponse::invalid("INVALID_ENTRY", e))?;
+
+    info!("Admin removed {} entries from {}", changed, list);
+    Ok(Json(AccessUpdateResponse { list, changed }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/anomalies",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(AnomaliesQueryParams),
+    responses(
+        (status = 200, description = "Recently detected mass follow/unfollow updates, newest first", body = [FollowAnomaly]),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn get_anomalies(
+    State(state): State<AppState>,
+    Query(params): Query<AnomaliesQueryParams>,
+) -> Result<Json<Vec<FollowAnomaly>>, ErrorResponse> {
+    let limit = params.limit.unwrap_or(ANOMALIES_LIMIT_DEFAULT).min(ANOMALIES_LIMIT_MAX);
+    let db = state.db.clone();
+    let anomalies = tokio::task::spawn_blocking(move || db.recent_anomalies(limit))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+    Ok(Json(anomalies))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/watchlist",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Watched pubkeys with the newest contact, mute and relay list seen for each", body = [WatchedPubkey]),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn get_watchlist(State(state): State<AppState>) -> Json<Vec<WatchedPubkey>> {
+    Json(state.watchlist.snapshot())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/watchlist",
+    tag = "admin",
+    security(("admin_token" = [])),
+    request_body = WatchlistRequest,
+    responses(
+        (status = 200, description = "Number of pubkeys added", body = WatchlistUpdateResponse),
+        (status = 400, description = "Invalid pubkey", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn add_watchlist(
+    State(state): State<AppState>,
+    Json(request): Json<WatchlistRequest>,
+) -> Result<Json<WatchlistUpdateResponse>, ErrorResponse> {
+    let changed = state
+        .watchlist
+        .add(&request.pubkeys)
+        .map_err(|e| ErrorResponse::invalid("INVALID_PUBKEY", e))?;
+
+    info!("Admin added {} pubkeys to the watchlist", changed);
+    Ok(Json(WatchlistUpdateResponse {
+        changed,
+        watched: state.watchlist.len(),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/watchlist",
+    tag = "admin",
+    security(("admin_token" = [])),
+    request_body = WatchlistRequest,
+    responses(
+        (status = 200, description = "Number of pubkeys removed", body = WatchlistUpdateResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn remove_watchlist(
+    State(state): State<AppState>,
+    Json(request): Json<WatchlistRequest>,
+) -> Json<WatchlistUpdateResponse> {
+    let changed = state.watchlist.remove(&request.pubkeys);
+
+    info!("Admin removed {} pubkeys from the watchlist", changed);
+    Json(WatchlistUpdateResponse {
+        changed,
+        watched: state.watchlist.len(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/blocklist",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Blocked pubkeys, most recently blocked first", body = [BlockedPubkey]),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn get_blocklist(State(state): State<AppState>) -> Result<Json<Vec<BlockedPubkey>>, ErrorResponse> {
+    let db = state.db.clone();
+    let blocked = tokio::task::spawn_blocking(move || db.blocked_pubkeys())
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+    Ok(Json(blocked))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/blocklist",
+    tag = "admin",
+    security(("admin_token" = [])),
+    request_body = BlockRequest,
+    responses(
+        (status = 200, description = "Number of pubkeys newly blocked", body = BlocklistUpdateResponse),
+        (status = 400, description = "Invalid pubkey", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn add_blocklist(
+    State(state): State<AppState>,
+    Json(request): Json<BlockRequest>,
+) -> Result<Json<BlocklistUpdateResponse>, ErrorResponse> {
+    let pubkeys = request
+        .pubkeys
+        .iter()
+        .map(|pk| {
+            Pubkey::parse(pk).map(String::from).map_err(|_| ErrorResponse::invalid(
+                "INVALID_PUBKEY",
+                format!("Invalid pubkey: {}", pk),
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Applies to every named graph. Persist (and purge stored data) first, so a failure
+    // leaves that graph untouched.
+    let mut changed = 0;
+    for (_, selected) in each_graph(&state) {
+        let (db, persisted, reason) = (selected.db.clone(), pubkeys.clone(), request.reason.clone());
+        tokio::task::spawn_blocking(move || db.block_pubkeys(&persisted, reason.as_deref()))
+            .await
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+        changed = changed.max(selected.graph.block(&pubkeys));
+    }
+
+    info!("Admin blocked {} pubkeys", changed);
+    Ok(Json(BlocklistUpdateResponse {
+        changed,
+        blocked: state.graph.blocked_count(),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/blocklist",
+    tag = "admin",
+    security(("admin_token" = [])),
+    request_body = BlockRequest,
+    responses(
+        (status = 200, description = "Number of pubkeys unblocked", body = BlocklistUpdateResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn remove_blocklist(
+    State(state): State<AppState>,
+    Json(request): Json<BlockRequest>,
+) -> Result<Json<BlocklistUpdateResponse>, ErrorResponse> {
+    let pubkeys: Vec<String> = request.pubkeys.iter().map(|pk| pk.to_ascii_lowercase()).collect();
+    let mut changed = 0;
+    for (_, selected) in each_graph(&state) {
+        let (db, persisted) = (selected.db.clone(), pubkeys.clone());
+        tokio::task::spawn_blocking(move || db.unblock_pubkeys(&persisted))
+            .await
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+        changed = changed.max(selected.graph.unblock(&pubkeys));
+    }
+
+    info!("Admin unblocked {} pubkeys", changed);
+    Ok(Json(BlocklistUpdateResponse {
+        changed,
+        blocked: state.graph.blocked_count(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/cache/invalidate",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Query caches of every graph cleared", body = CacheInvalidateResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn invalidate_cache(State(state): State<AppState>) -> Json<CacheInvalidateResponse> {
+    let mut invalidated = 0;
+    for (_, selected) in each_graph(&state) {
+        invalidated += selected.cache.stats().size;
+        selected.cache.invalidate_all();
+    }
+
+    info!("Admin invalidated {} cached results", invalidated);
+    Json(CacheInvalidateResponse { invalidated })
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/metrics/reset",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 204, description = "Lock metrics of every graph reset"),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn reset_metrics(State(state): State<AppState>) -> StatusCode {
+    for (_, selected) in each_graph(&state) {
+        selected.graph.reset_lock_metrics();
+    }
+
+    info!("Admin reset lock metrics");
+    StatusCode::NO_CONTENT
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/snapshot",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Database copies written next to each graph's database", body = [GraphSnapshot]),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn snapshot(State(state): State<AppState>) -> Result<Json<Vec<GraphSnapshot>>, ErrorResponse> {
+    let taken_at = chrono::Utc::now().timestamp();
+    let mut snapshots = Vec::new();
+    for (graph, selected) in each_graph(&state) {
+        let path = suffixed_path(&selected.config.db_path, &format!("snapshot-{}", taken_at));
+        let (db, target) = (selected.db.clone(), path.clone());
+        let bytes = tokio::task::spawn_blocking(move || {
+            db.snapshot_to(&target)?;
+            anyhow::Ok(std::fs::metadata(&target)?.len())
+        })
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+        info!("Admin snapshot of graph {} written to {} ({} bytes)", graph, path, bytes);
+        snapshots.push(GraphSnapshot { graph, path, bytes });
+    }
+    Ok(Json(snapshots))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Graphs rebuilt from their databases", body = [GraphReload]),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn reload(State(state): State<AppState>) -> Result<Json<Vec<GraphReload>>, ErrorResponse> {
+    let mut reloaded = Vec::new();
+    for (graph, selected) in each_graph(&state) {
+        let (live, db) = (selected.graph.clone(), selected.db.clone());
+        tokio::task::spawn_blocking(move || namespace::reload(&live, &db))
+            .await
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+        // Cached results hold node IDs that are still valid, but the lists behind them may not be
+        selected.cache.invalidate_all();
+
+        let stats = selected.graph.stats();
+        info!("Admin reloaded graph {}: {} nodes, {} edges", graph, stats.node_count, stats.edge_count);
+        reloaded.push(GraphReload {
+            graph,
+            node_count: stats.node_count,
+            edge_count: stats.edge_count,
+        });
+    }
+    Ok(Json(reloaded))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/ingestion/pause",
+    tag = "admin",
+    security(("admin_token" = [])),
+    request_body(content = Option<PauseRequest>, description = "Optional reason for the pause"),
+    responses(
+        (status = 200, description = "Relay ingestion paused for every graph (already paused: unchanged)", body = IngestionStatus),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn pause_ingestion(
+    State(state): State<AppState>,
+    body: Option<Json<PauseRequest>>,
+) -> Json<IngestionStatus> {
+    let reason = body.and_then(|Json(body)| body.reason).filter(|r| !r.is_empty());
+    if state.ingestion_pause.pause(reason, chrono::Utc::now().timestamp()) {
+        info!("Admin paused ingestion");
+    }
+    Json(state.ingestion_pause.status())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/ingestion/resume",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Relay ingestion resumed, catching up on events published while paused", body = IngestionStatus),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn resume_ingestion(State(state): State<AppState>) -> Json<IngestionStatus> {
+    if state.ingestion_pause.resume() {
+        info!("Admin resumed ingestion");
+    }
+    Json(state.ingestion_pause.status())
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/debug",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Diagnostic dump (the same one SIGUSR1 writes to the log)", body = DiagnosticDump),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse)
+    )
+)]
+pub async fn debug(State(state): State<AppState>) -> Result<Json<DiagnosticDump>, ErrorResponse> {
+    let dump = tokio::task::spawn_blocking(move || diagnostics::dump(&state))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+    Ok(Json(dump))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/report",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(ReportQueryParams),
+    responses(
+        (status = 200, description = "Summary of each graph for operator review, as JSON or an HTML page", body = [GraphReport]),
+        (status = 400, description = "Invalid format or days", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorResponse),
+        (status = 404, description = "No graph with that name", body = ErrorResponse)
+    )
+)]
+pub async fn get_report(
+    State(state): State<AppState>,
+    Query(params): Query<ReportQueryParams>,
+) -> Result<Response, ErrorResponse> {
+    let html = match params.format.as_deref() {
+        None | Some("json") => false,
+        Some("html") => true,
+        Some(_) => {
+            return Err(ErrorResponse::invalid("INVALID_FORMAT", "format must be json or html"))
+        }
+    };
+    let days = params.days.unwrap_or(report::DAYS_DEFAULT);
+    if !(1..=report::DAYS_MAX).contains(&days) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_DAYS",
+            format!("days must be between 1 and {}", report::DAYS_MAX),
+        ));
+    }
+    let graphs = match params.graph {
+        Some(name) => vec![(name.clone(), state.select_graph(Some(&name))?)],
+        None => each_graph(&state),
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let mut reports = Vec::new();
+    for (graph, selected) in graphs {
+        let persistence = selected.persistence.clone();
+        let mut report = tokio::task::spawn_blocking(move || {
+            report::build(&graph, &selected.graph, &selected.db, &selected.ranks, days, now)
+        })
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+        report.ingestion.live = Some(LiveIngestion {
+            status: state.ingestion_pause.status(),
+            persistence: persistence.snapshot(),
+            relays: state.diagnostics.relays(),
+        });
+        reports.push(report);
+    }
+
+    Ok(match html {
+        true => Html(report::render_html(&reports)).into_response(),
+        false => Json(reports).into_response(),
+    })
+}
+
+/// The state for the primary graph and every named graph, with their names
+fn each_graph(state: &AppState) -> Vec<(String, AppState)> {
+    state
+        .namespaces
+        .names()
+        .into_iter()
+        .filter_map(|name| Some((name.to_string(), state.select_graph(Some(name)).ok()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+}
@@ -0,0 +1,104 @@
+use nostr_sdk::prelude::secp256k1::Message;
+use nostr_sdk::prelude::Keys;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::graph::bfs::{AdaptiveHops, DistanceResult};
+
+/// Domain separator so attestation signatures can't be replayed as anything else
+const ATTESTATION_TAG: &str = "wot-oracle/attestation/v1";
+
+/// Oracle signature over a distance result, verifiable against the oracle's Nostr pubkey
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Attestation {
+    pub pubkey: String,
+    /// Graph epoch the result was computed at
+    pub epoch: u64,
+    /// Unix timestamp of signing
+    pub created_at: u64,
+    /// sha256 of `[from, to, hops, path_count, mutual_follow, bridges]` as compact JSON
+    pub result_hash: String,
+    /// BIP-340 signature over sha256 of `[tag, pubkey, result_hash, epoch, created_at]`
+    pub sig: String,
+}
+
+/// A distance result with an optional attestation, serialized flat
+/// so unsigned responses keep their original shape
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttestedDistance {
+    #[serde(flatten)]
+    pub result: DistanceResult,
+    /// Set when an endpoint is a super-node and max_hops was lowered for it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adaptive_hops: Option<AdaptiveHops>,
+    /// Shortest paths weighted by their bridges' standing, when `path_weighting` is given.
+    /// Not covered by the attestation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weighted_path_count: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+pub fn result_hash(result: &DistanceResult) -> String {
+    let canonical = json!([
+        result.from,
+        result.to,
+        result.hops,
+        result.path_count,
+        result.mutual_follow,
+        result.bridges,
+    ]);
+    sha256_hex(canonical.to_string().as_bytes())
+}
+
+pub fn attest(keys: &Keys, result: &DistanceResult, epoch: u64, created_at: u64) -> Attestation {
+    let pubkey = keys.public_key().to_hex();
+    let result_hash = result_hash(result);
+
+    let commitment = json!([ATTESTATION_TAG, pubkey, result_hash, epoch, created_at]).to_string();
+    let digest: [u8; 32] = Sha256::digest(commitment.as_bytes()).into();
+    let sig = keys.sign_schnorr(&Message::from_digest(digest)).to_string();
+
+    Attestation {
+        pubkey,
+        epoch,
+        created_at,
+        result_hash,
+        sig,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    #[test]
+    fn test_result_hash_is_canonical_json() {
+        let (from, to) = (Pubkey::from_bytes([0xaa; 32]), Pubkey::from_bytes([0xbb; 32]));
+        let mut result = DistanceResult::not_found(from, to);
+        // sha256 of `["aa…","bb…",null,0,false,null]`, pubkeys as hex
+        let expected = sha256_hex(format!(r#"["{}","{}",null,0,false,null]"#, from, to).as_bytes());
+        assert_eq!(result_hash(&result), expected);
+
+        result.hops = Some(2);
+        let bridge = Pubkey::from_bytes([0xcc; 32]);
+        result.bridges = Some(vec![bridge]);
+        let expected = sha256_hex(format!(r#"["{}","{}",2,0,false,["{}"]]"#, from, to, bridge).as_bytes());
+        assert_eq!(result_hash(&result), expected);
+    }
+
+    #[test]
+    fn test_unsigned_result_keeps_shape() {
+        let result = DistanceResult::not_found(Pubkey::from_bytes([0xaa; 32]), Pubkey::from_bytes([0xbb; 32]));
+        let plain = serde_json::to_value(&result).unwrap();
+        let wrapped = serde_json::to_value(AttestedDistance { result, adaptive_hops: None, weighted_path_count: None, attestation: None }).unwrap();
+        assert_eq!(plain, wrapped);
+    }
+}
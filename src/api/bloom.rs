@@ -0,0 +1,191 @@
+//! Bloom filter export of a node's follow set, for clients making many local membership
+//! checks. Parameters are sized server-side from the set size and the requested false
+//! positive rate.
+//!
+//! Hashing, so clients can test membership: `d = sha256(lowercase hex pubkey)`,
+//! `h1` and `h2` are `d[0..8]` and `d[8..16]` as little-endian u64s, and hash `i` sets bit
+//! `(h1 + i * h2) mod bits` (wrapping u64 arithmetic). Bit `j` is `filter[j / 8] & (1 << (j % 8))`.
+
+use axum::{
+    extract::{Path, Query},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use utoipa::{IntoParams, ToSchema};
+
+use super::http::{resolve_subject, ErrorResponse, GraphParam, SelectedGraph};
+use crate::graph::NodeId;
+use crate::types::Pubkey;
+
+const FPR_DEFAULT: f64 = 0.01;
+const FPR_MIN: f64 = 0.0001;
+const FPR_MAX: f64 = 0.5;
+const MAX_HASHES: u32 = 30;
+/// Largest set exported; a 2-hop set from a well-connected pubkey can cover most of the graph
+const MAX_ELEMENTS: usize = 2_000_000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BloomQueryParams {
+    /// Target false positive rate (0.0001-0.5, default 0.01)
+    pub fpr: Option<f64>,
+    /// 1 for the pubkey's follows, 2 to add their follows too (default 1)
+    pub hops: Option<u8>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BloomResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub hops: u8,
+    /// Pubkeys in the filter
+    pub count: usize,
+    /// False positive rate the filter was sized for
+    pub fpr: f64,
+    /// Filter length in bits
+    pub bits: u64,
+    /// Hash functions per element
+    pub hashes: u32,
+    /// The filter bits, base64
+    pub filter: String,
+}
+
+/// A Bloom filter using the double-hashing scheme described in the module docs
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// A filter sized for `count` elements at false positive rate `fpr`
+    pub fn with_rate(count: usize, fpr: f64) -> Self {
+        let n = count.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-n * fpr.ln() / (ln2 * ln2)).ceil().max(8.0) as u64;
+        let hashes = ((bits as f64 / n) * ln2).round().clamp(1.0, MAX_HASHES as f64) as u32;
+        Self {
+            bits: vec![0; bits.div_ceil(8) as usize],
+            hashes,
+        }
+    }
+
+    pub fn len_bits(&self) -> u64 {
+        self.bits.len() as u64 * 8
+    }
+
+    fn indexes(&self, pubkey: &str) -> impl Iterator<Item = u64> {
+        let digest = Sha256::digest(pubkey.to_ascii_lowercase().as_bytes());
+        let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap_or_default());
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap_or_default());
+        let bits = self.len_bits();
+        (0..self.hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bits)
+    }
+
+    pub fn insert(&mut self, pubkey: &str) {
+        for j in self.indexes(pubkey).collect::<Vec<_>>() {
+            self.bits[(j / 8) as usize] |= 1 << (j % 8);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, pubkey: &str) -> bool {
+        self.indexes(pubkey).all(|j| self.bits[(j / 8) as usize] & (1 << (j % 8)) != 0)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/bloom/{pubkey}",
+    tag = "queries",
+    params(("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"), BloomQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Bloom filter of the pubkey's follows, or everything within 2 hops", body = BloomResponse),
+        (status = 400, description = "Invalid parameters, or a set too large to export", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 404, description = "Pubkey not in graph", body = ErrorResponse)
+    )
+)]
+pub async fn get_bloom(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+    Query(params): Query<BloomQueryParams>,
+) -> Result<Json<BloomResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    let fpr = params.fpr.unwrap_or(FPR_DEFAULT);
+    if !(FPR_MIN..=FPR_MAX).contains(&fpr) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_FPR",
+            format!("fpr must be between {} and {}", FPR_MIN, FPR_MAX),
+        ));
+    }
+    let hops = params.hops.unwrap_or(1);
+    if !(1..=2).contains(&hops) {
+        return Err(ErrorResponse::invalid("INVALID_HOPS", "hops must be 1 or 2"));
+    }
+    let node_id = state.graph.node_id(&pubkey).ok_or_else(ErrorResponse::not_found)?;
+
+    let graph = state.graph.clone();
+    let (count, filter) = tokio::task::spawn_blocking(move || {
+        let ids: Vec<NodeId> = graph.with_adjacency(|follows, _| {
+            let direct = follows.get(node_id as usize).unwrap_or_default();
+            if hops == 1 {
+                return direct.to_vec();
+            }
+            let mut within: HashSet<NodeId> = direct.iter().copied().collect();
+            for &id in direct {
+                within.extend(follows.get(id as usize).into_iter().flatten());
+            }
+            within.remove(&node_id);
+            within.into_iter().collect()
+        });
+        if ids.len() > MAX_ELEMENTS {
+            return Err(ErrorResponse::invalid(
+                "SET_TOO_LARGE",
+                format!("{} pubkeys within {} hops; at most {} can be exported", ids.len(), hops, MAX_ELEMENTS),
+            ));
+        }
+        let mut filter = BloomFilter::with_rate(ids.len(), fpr);
+        for member in graph.resolve_pubkeys(&ids) {
+            filter.insert(&member.to_hex());
+        }
+        Ok((ids.len(), filter))
+    })
+    .await
+    .map_err(|e| ErrorResponse::internal(e.to_string()))??;
+
+    Ok(Json(BloomResponse {
+        pubkey,
+        hops,
+        count,
+        fpr,
+        bits: filter.len_bits(),
+        hashes: filter.hashes,
+        filter: STANDARD.encode(&filter.bits),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let members: Vec<String> = (0..1000).map(|i| format!("{:064x}", i)).collect();
+        let mut filter = BloomFilter::with_rate(members.len(), 0.01);
+        assert_eq!((filter.len_bits(), filter.hashes), (9592, 7));
+        for member in &members {
+            filter.insert(member);
+        }
+        assert!(members.iter().all(|member| filter.contains(member)));
+        assert!(filter.contains(&members[0].to_ascii_uppercase()));
+
+        let false_positives = (1000..11_000).filter(|i| filter.contains(&format!("{:064x}", i))).count();
+        assert!(false_positives < 200, "{} false positives", false_positives);
+
+        let empty = BloomFilter::with_rate(0, 0.01);
+        assert!(!empty.contains(&members[0]));
+    }
+}
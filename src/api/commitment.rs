@@ -0,0 +1,409 @@
+//! Merkle commitment over every follow list in the primary graph, recomputed on a schedule
+//! and published as a signed Nostr event, with inclusion proofs served at
+//! `GET /proof/follows/:pubkey`.
+//!
+//! Tree layout, so clients can check proofs: leaves are the pubkeys with a non-empty follow
+//! list, sorted by lowercase hex pubkey. A leaf is `sha256(0x00 || "<pubkey>:<follow>,<follow>,...")`
+//! with the follows sorted the same way; an inner node is `sha256(0x01 || left || right)`. The
+//! last node of an odd-sized level moves up unchanged.
+
+use anyhow::{Context, Result};
+use axum::{extract::{Path, State}, Json};
+use nostr_sdk::prelude::*;
+use parking_lot::RwLock;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+use utoipa::ToSchema;
+
+use super::http::{resolve_subject, AppState, ErrorResponse};
+use crate::error::OracleError;
+use crate::graph::{NodeId, WotGraph};
+use crate::types::Pubkey;
+
+/// Parameterized replaceable: relays keep only the latest root
+pub const COMMITMENT_KIND: u16 = 30951;
+
+const COMMITMENT_D_TAG: &str = "wot-follows-root";
+/// Nodes whose follow lists are copied per adjacency read lock
+const LEAF_CHUNK: usize = 10_000;
+
+type Hash = [u8; 32];
+
+fn to_hex(hash: &Hash) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Leaf hash of a pubkey's follow list over their lowercase hex; `follows` must be sorted
+pub fn leaf_hash(pubkey: &Pubkey, follows: &[Pubkey]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(pubkey.to_hex().as_bytes());
+    hasher.update(b":");
+    for (i, follow) in follows.iter().enumerate() {
+        if i > 0 {
+            hasher.update(b",");
+        }
+        hasher.update(follow.to_hex().as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A sibling hash on the path from a leaf to the root
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProofStep {
+    pub hash: String,
+    /// Which side of the running hash the sibling goes on
+    pub side: Side,
+}
+
+/// One computed tree: every level from the leaves up, kept so proofs are lookups
+pub struct FollowCommitment {
+    pub epoch: u64,
+    pub created_at: u64,
+    /// Published commitment event, if publication succeeded
+    pub event_id: Option<EventId>,
+    /// Leaf pubkeys, sorted
+    pubkeys: Vec<Pubkey>,
+    levels: Vec<Vec<Hash>>,
+}
+
+impl FollowCommitment {
+    /// Commit to the graph's follow lists. Lists are copied a chunk of nodes at a time, so
+    /// writers are not held up for the whole computation; a graph changing meanwhile yields
+    /// a root mixing lists from around `epoch`. CPU-bound: call from a blocking thread.
+    pub fn compute(graph: &WotGraph, created_at: u64) -> Self {
+        let epoch = graph.epoch();
+        let node_count = graph.with_adjacency(|follows, _| follows.len());
+
+        let mut leaves: Vec<(Pubkey, Hash)> = Vec::new();
+        for start in (0..node_count).step_by(LEAF_CHUNK) {
+            let end = (start + LEAF_CHUNK).min(node_count);
+            let lists: Vec<(NodeId, Vec<NodeId>)> = graph.with_adjacency(|follows, _| {
+                (start..end)
+                    .filter_map(|id| follows.get(id).filter(|list| !list.is_empty()).map(|list| (id as NodeId, list.to_vec())))
+                    .collect()
+            });
+            for (id, list) in lists {
+                let Some(pubkey) = graph.pubkey(id) else {
+                    continue;
+                };
+                let mut follows = graph.resolve_pubkeys(&list);
+                follows.sort_unstable();
+                let leaf = leaf_hash(&pubkey, &follows);
+                leaves.push((pubkey, leaf));
+            }
+        }
+        leaves.sort_unstable_by_key(|&(pubkey, _)| pubkey);
+
+        let (pubkeys, hashes): (Vec<_>, Vec<_>) = leaves.into_iter().unzip();
+        let mut levels = vec![hashes];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Self { epoch, created_at, event_id: None, pubkeys, levels }
+    }
+
+    /// Merkle root; all zeros for a graph without follow lists
+    pub fn root(&self) -> Hash {
+        self.levels.last().and_then(|level| level.first()).copied().unwrap_or_default()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.pubkeys.len()
+    }
+
+    /// Leaf index and hash of a pubkey, if it had a follow list when committed
+    pub fn leaf(&self, pubkey: &Pubkey) -> Option<(usize, Hash)> {
+        let index = self.pubkeys.binary_search(pubkey).ok()?;
+        Some((index, self.levels[0][index]))
+    }
+
+    /// Sibling hashes from a leaf up to the root
+    pub fn proof(&self, mut index: usize) -> Vec<ProofStep> {
+        let mut steps = Vec::new();
+        for level in &self.levels[..self.levels.len().saturating_sub(1)] {
+            let sibling = index ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                let side = if sibling < index { Side::Left } else { Side::Right };
+                steps.push(ProofStep { hash: to_hex(hash), side });
+            }
+            index /= 2;
+        }
+        steps
+    }
+}
+
+/// Fold a leaf hash up through a proof, returning the root it implies
+#[cfg(test)]
+pub fn proof_root(leaf: Hash, proof: &[ProofStep]) -> Option<Hash> {
+    proof.iter().try_fold(leaf, |running, step| {
+        let mut sibling = [0u8; 32];
+        for (i, byte) in sibling.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(step.hash.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+        Some(match step.side {
+            Side::Left => node_hash(&sibling, &running),
+            Side::Right => node_hash(&running, &sibling),
+        })
+    })
+}
+
+/// The latest commitment, shared between the publisher and the proof endpoint
+#[derive(Default)]
+pub struct Commitments {
+    latest: RwLock<Option<Arc<FollowCommitment>>>,
+}
+
+impl Commitments {
+    pub fn latest(&self) -> Option<Arc<FollowCommitment>> {
+        self.latest.read().clone()
+    }
+
+    pub fn set(&self, commitment: FollowCommitment) {
+        *self.latest.write() = Some(Arc::new(commitment));
+    }
+}
+
+/// Recomputes the commitment when the graph changes and publishes its root to the
+/// configured relays, when the oracle has keys
+pub struct CommitmentPublisher {
+    graph: Arc<WotGraph>,
+    commitments: Arc<Commitments>,
+    relays: Vec<String>,
+    keys: Option<Keys>,
+}
+
+impl CommitmentPublisher {
+    pub fn new(graph: Arc<WotGraph>, commitments: Arc<Commitments>, relays: Vec<String>, keys: Option<Keys>) -> Self {
+        Self { graph, commitments, relays, keys }
+    }
+
+    pub async fn start(&self, interval_secs: u64) -> Result<()> {
+        info!("Starting follow commitments (every {}s)", interval_secs);
+
+        let client = match &self.keys {
+            Some(keys) => {
+                let client = Client::new(keys);
+                for relay_url in &self.relays {
+                    match client.add_relay(relay_url).await {
+                        Ok(_) => info!("Commitment publisher added relay: {}", relay_url),
+                        Err(e) => warn!("Commitment publisher failed to add relay {}: {}", relay_url, e),
+                    }
+                }
+                client.connect().await;
+                Some(client)
+            }
+            None => None,
+        };
+
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        let mut committed_epoch = None;
+
+        loop {
+            interval.tick().await;
+
+            let epoch = self.graph.epoch();
+            if committed_epoch == Some(epoch) {
+                debug!("Graph unchanged since last commitment, skipping");
+                continue;
+            }
+
+            let graph = self.graph.clone();
+            let created_at = chrono::Utc::now().timestamp().max(0) as u64;
+            let start = Instant::now();
+            let mut commitment = tokio::task::spawn_blocking(move || FollowCommitment::compute(&graph, created_at))
+                .await
+                .context("Commitment task failed")?;
+            info!(
+                "Committed {} follow lists at epoch {} in {:?}: {}",
+                commitment.leaf_count(),
+                commitment.epoch,
+                start.elapsed(),
+                to_hex(&commitment.root())
+            );
+
+            if let Some(client) = &client {
+                match publish(client, &commitment).await {
+                    Ok(event_id) => commitment.event_id = Some(event_id),
+                    Err(e) => error!("Failed to publish commitment: {}", e),
+                }
+            }
+            committed_epoch = Some(commitment.epoch);
+            self.commitments.set(commitment);
+        }
+    }
+}
+
+async fn publish(client: &Client, commitment: &FollowCommitment) -> Result<EventId> {
+    let output = client.send_event_builder(commitment_event(commitment)?).await?;
+    Ok(*output.id())
+}
+
+fn commitment_event(commitment: &FollowCommitment) -> Result<EventBuilder> {
+    let root = to_hex(&commitment.root());
+    let tags = vec![
+        Tag::parse(&["d", COMMITMENT_D_TAG])?,
+        Tag::parse(&["root", &root])?,
+        Tag::parse(&["epoch", &commitment.epoch.to_string()])?,
+        Tag::parse(&["alt", "Web of Trust follow graph commitment"])?,
+    ];
+    let content = json!({ "root": root, "epoch": commitment.epoch, "leaves": commitment.leaf_count() });
+    Ok(EventBuilder::new(Kind::Custom(COMMITMENT_KIND), content.to_string(), tags))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FollowProofResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    /// The committed follow list, sorted
+    #[schema(value_type = Vec<String>)]
+    pub follows: Vec<Pubkey>,
+    /// Merkle root of the latest commitment, hex
+    pub root: String,
+    pub epoch: u64,
+    /// When the commitment was computed (unix seconds)
+    pub created_at: u64,
+    /// The commitment event on `RELAYS`; absent if it was not published
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// Sibling hashes from the leaf up to the root
+    pub proof: Vec<ProofStep>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/proof/follows/{pubkey}",
+    tag = "queries",
+    params(("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier")),
+    responses(
+        (status = 200, description = "The pubkey's follow list with a Merkle inclusion proof against the latest commitment", body = FollowProofResponse),
+        (status = 400, description = "Invalid pubkey", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 404, description = "Pubkey had no follow list at the latest commitment", body = ErrorResponse),
+        (status = 409, description = "Follow list changed since the latest commitment", body = ErrorResponse),
+        (status = 503, description = "No commitment computed yet, or COMMITMENT_INTERVAL_SECS unset", body = ErrorResponse)
+    )
+)]
+pub async fn get_follow_proof(
+    State(state): State<AppState>,
+    Path(pubkey): Path<String>,
+) -> Result<Json<FollowProofResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    let commitment = state.commitments.latest().ok_or_else(|| ErrorResponse::no_commitment(&state.config))?;
+    let (leaf_index, leaf) = commitment.leaf(&pubkey).ok_or_else(|| {
+        ErrorResponse::not_found().with_message("Pubkey had no follow list at the latest commitment")
+    })?;
+
+    // Only leaves are kept, so the list is served from the graph if it still matches
+    let mut follows = state.graph.get_follows(&pubkey).unwrap_or_default();
+    follows.sort_unstable();
+    if leaf_hash(&pubkey, &follows) != leaf {
+        let message = "Follow list changed since the latest commitment".to_string();
+        return Err(OracleError::FollowsChanged(message).into());
+    }
+
+    Ok(Json(FollowProofResponse {
+        pubkey,
+        follows,
+        root: to_hex(&commitment.root()),
+        epoch: commitment.epoch,
+        created_at: commitment.created_at,
+        event_id: commitment.event_id.map(|id| id.to_hex()),
+        leaf_index,
+        leaf_count: commitment.leaf_count(),
+        proof: commitment.proof(leaf_index),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pk(i: usize) -> Pubkey {
+        Pubkey::parse(&format!("{:064x}", i)).unwrap()
+    }
+
+    fn follow(graph: &WotGraph, i: usize, follows: &[usize], created_at: Option<i64>) {
+        let follows: Vec<String> = follows.iter().map(|&j| pk(j).to_hex()).collect();
+        graph.update_follows(&pk(i).to_hex(), &follows, None, created_at);
+    }
+
+    #[test]
+    fn test_proofs_verify_against_root() {
+        let graph = WotGraph::new();
+        for i in 0..7 {
+            follow(&graph, i, &[i + 1, i + 10], None);
+        }
+        let commitment = FollowCommitment::compute(&graph, 0);
+        assert_eq!(commitment.leaf_count(), 7);
+
+        for i in 0..7 {
+            let (index, leaf) = commitment.leaf(&pk(i)).unwrap();
+            assert_eq!(leaf, leaf_hash(&pk(i), &[pk(i + 1), pk(i + 10)]));
+            let proof = commitment.proof(index);
+            assert_eq!(proof_root(leaf, &proof), Some(commitment.root()), "leaf {}", i);
+        }
+        // Followed but following nobody: not a leaf
+        assert!(commitment.leaf(&pk(15)).is_none());
+
+        let (index, _) = commitment.leaf(&pk(0)).unwrap();
+        let forged = leaf_hash(&pk(0), &[pk(1)]);
+        assert_ne!(proof_root(forged, &commitment.proof(index)), Some(commitment.root()));
+    }
+
+    #[test]
+    fn test_root_tracks_follow_lists() {
+        let graph = WotGraph::new();
+        assert_eq!(FollowCommitment::compute(&graph, 0).root(), [0u8; 32]);
+
+        follow(&graph, 1, &[2], Some(1));
+        let single = FollowCommitment::compute(&graph, 0);
+        assert_eq!(single.root(), leaf_hash(&pk(1), &[pk(2)]));
+        assert!(single.proof(0).is_empty());
+
+        follow(&graph, 1, &[3], Some(2));
+        assert_ne!(FollowCommitment::compute(&graph, 0).root(), single.root());
+    }
+
+    #[test]
+    fn test_commitment_event() {
+        let graph = WotGraph::new();
+        follow(&graph, 1, &[2], None);
+        let commitment = FollowCommitment::compute(&graph, 0);
+        let event = commitment_event(&commitment).unwrap().to_event(&Keys::generate()).unwrap();
+        assert_eq!(event.kind, Kind::Custom(COMMITMENT_KIND));
+        let content: serde_json::Value = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(content["root"], to_hex(&commitment.root()));
+        assert_eq!(content["leaves"], 1);
+    }
+}
@@ -0,0 +1,87 @@
+//! CSV output for tabular endpoints, chosen with `Accept: text/csv`, so results load straight
+//! into spreadsheets and notebooks. One header row, then one record per result; list cells are
+//! space-separated and nested objects are written as JSON.
+
+use axum::{
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+pub const CSV: &str = "text/csv";
+pub const CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Whether the client asked for CSV
+pub fn accepts_csv(headers: &HeaderMap) -> bool {
+    super::http::accepts_media(headers, CSV)
+}
+
+/// One CSV line, quoting cells that need it (RFC 4180)
+pub fn record<I, S>(cells: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut line = String::new();
+    for (i, cell) in cells.into_iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        let cell = cell.as_ref();
+        if cell.contains([',', '"', '\n', '\r']) {
+            line.push('"');
+            line.push_str(&cell.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(cell);
+        }
+    }
+    line.push_str("\r\n");
+    line
+}
+
+/// The record of `columns` of a JSON object; missing and null fields are empty
+pub fn object_record(object: &Value, columns: &[&str]) -> String {
+    record(columns.iter().map(|column| cell(object.get(column).unwrap_or(&Value::Null))))
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+        Value::Array(items) if items.iter().all(|item| !item.is_array() && !item.is_object()) => {
+            items.iter().map(cell).collect::<Vec<_>>().join(" ")
+        }
+        Value::Array(_) | Value::Object(_) => value.to_string(),
+    }
+}
+
+/// A buffered CSV response
+pub fn respond(body: String) -> Response {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_quoting() {
+        assert_eq!(record(["a", "b c", ""]), "a,b c,\r\n");
+        assert_eq!(record(["say \"hi\"", "x,y"]), "\"say \"\"hi\"\"\",\"x,y\"\r\n");
+    }
+
+    #[test]
+    fn test_object_record() {
+        let object = serde_json::json!({
+            "from": "a",
+            "hops": 2,
+            "mutual_follow": false,
+            "bridges": ["b", "c"],
+            "attestation": {"sig": "s"},
+        });
+        let columns = ["from", "hops", "path_count", "mutual_follow", "bridges", "attestation"];
+        assert_eq!(object_record(&object, &columns), "a,2,,false,b c,\"{\"\"sig\"\":\"\"s\"\"}\"\r\n");
+    }
+}
@@ -0,0 +1,153 @@
+//! DVM liveness: relay connection status, request counters and retry backoff for the relay loop.
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+const BACKOFF_MIN: Duration = Duration::from_secs(5);
+const BACKOFF_MAX: Duration = Duration::from_secs(300);
+
+/// Counters and relay status published by the DVM, read by `/stats`
+#[derive(Default)]
+pub struct DvmStats {
+    requests_processed: AtomicU64,
+    requests_failed: AtomicU64,
+    requests_rejected: AtomicU64,
+    error_responses: AtomicU64,
+    resubscriptions: AtomicU64,
+    relays: RwLock<Vec<DvmRelayStatus>>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DvmRelayStatus {
+    pub url: String,
+    pub status: String,
+    pub connected: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DvmStatsSnapshot {
+    pub connected_relays: usize,
+    pub relays: Vec<DvmRelayStatus>,
+    /// Requests handled to completion, including those answered with an error
+    pub requests_processed: u64,
+    /// Requests abandoned on an internal error (e.g. a relay rejected the response)
+    pub requests_failed: u64,
+    /// Requests turned away because the job queue was full
+    pub requests_rejected: u64,
+    pub error_responses: u64,
+    pub resubscriptions: u64,
+}
+
+impl DvmStats {
+    pub fn record_processed(&self) {
+        self.requests_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failed(&self) {
+        self.requests_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_rejected(&self) {
+        self.requests_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error_response(&self) {
+        self.error_responses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_resubscription(&self) {
+        self.resubscriptions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_relays(&self, relays: Vec<DvmRelayStatus>) {
+        *self.relays.write() = relays;
+    }
+
+    pub fn snapshot(&self) -> DvmStatsSnapshot {
+        let relays = self.relays.read().clone();
+        DvmStatsSnapshot {
+            connected_relays: relays.iter().filter(|r| r.connected).count(),
+            relays,
+            requests_processed: self.requests_processed.load(Ordering::Relaxed),
+            requests_failed: self.requests_failed.load(Ordering::Relaxed),
+            requests_rejected: self.requests_rejected.load(Ordering::Relaxed),
+            error_responses: self.error_responses.load(Ordering::Relaxed),
+            resubscriptions: self.resubscriptions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Exponential retry delay for relay reconnects and resubscriptions
+pub struct Backoff {
+    delay: Duration,
+    next_attempt: Instant,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self {
+            delay: BACKOFF_MIN,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    pub fn ready(&self) -> bool {
+        Instant::now() >= self.next_attempt
+    }
+
+    /// Record a failed attempt; returns the wait before the next one
+    pub fn failed(&mut self) -> Duration {
+        let delay = self.delay;
+        self.next_attempt = Instant::now() + delay;
+        self.delay = (delay * 2).min(BACKOFF_MAX);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new();
+        assert!(backoff.ready());
+
+        assert_eq!(backoff.failed(), BACKOFF_MIN);
+        assert!(!backoff.ready());
+        tokio::time::advance(BACKOFF_MIN).await;
+        assert!(backoff.ready());
+
+        assert_eq!(backoff.failed(), BACKOFF_MIN * 2);
+        for _ in 0..10 {
+            backoff.failed();
+        }
+        assert_eq!(backoff.failed(), BACKOFF_MAX);
+
+        backoff.reset();
+        assert!(backoff.ready());
+        assert_eq!(backoff.failed(), BACKOFF_MIN);
+    }
+
+    #[test]
+    fn test_snapshot_counts_connected_relays() {
+        let stats = DvmStats::default();
+        stats.record_processed();
+        stats.set_relays(vec![
+            DvmRelayStatus { url: "wss://a".into(), status: "Connected".into(), connected: true },
+            DvmRelayStatus { url: "wss://b".into(), status: "Disconnected".into(), connected: false },
+        ]);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.connected_relays, 1);
+        assert_eq!(snapshot.requests_processed, 1);
+    }
+}
@@ -0,0 +1,473 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use super::vertex::Sort;
+use super::DvmService;
+use crate::cache::CacheKey;
+use crate::graph::{bfs, pagerank};
+use crate::types::Pubkey;
+
+/// Same limit as `POST /distance/batch`
+pub const MAX_BATCH_TARGETS: usize = 100;
+pub const RANK_LIMIT_DEFAULT: usize = 20;
+pub const RANK_LIMIT_MAX: usize = 100;
+
+/// A DVM job, selected by `["param", "job_type", ...]` (default: `distance`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Job {
+    Distance { from: String, to: String, max_hops: u8 },
+    Path { from: String, to: String, max_hops: u8 },
+    /// Personalized PageRank from `anchor`: scores for `targets`, or the top `limit` pubkeys
+    Rank { anchor: String, targets: Vec<String>, limit: usize },
+    Verify { from: String, to: String },
+    Batch { from: String, targets: Vec<String>, max_hops: u8 },
+    /// Vertex-style kind 5312
+    VerifyReputation { target: String, source: String, sort: Sort, limit: usize },
+    /// Vertex-style kind 5313
+    RecommendFollows { source: String, sort: Sort, limit: usize },
+    /// Vertex-style kind 5314
+    SortAuthors { source: String, sort: Sort, targets: Vec<String> },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct JobRequest {
+    pub job: Job,
+    /// Maximum the requester will pay, in msats (NIP-90 `bid` tag)
+    pub bid: Option<u64>,
+}
+
+/// Job result: event content plus an optional `["result", value, unit]` tag
+pub struct JobOutput {
+    pub content: Value,
+    pub result: Option<(String, &'static str)>,
+}
+
+impl Job {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Job::Distance { .. } => "distance",
+            Job::Path { .. } => "path",
+            Job::Rank { .. } => "rank",
+            Job::Verify { .. } => "verify",
+            Job::Batch { .. } => "batch",
+            Job::VerifyReputation { .. } => "verify_reputation",
+            Job::RecommendFollows { .. } => "recommend_follows",
+            Job::SortAuthors { .. } => "sort_authors",
+        }
+    }
+
+    /// Every pubkey the job reads, for validation and access checks
+    pub fn pubkeys(&self) -> Vec<&str> {
+        match self {
+            Job::Distance { from, to, .. } | Job::Path { from, to, .. } | Job::Verify { from, to } => {
+                vec![from, to]
+            }
+            Job::Rank { anchor: from, targets, .. }
+            | Job::Batch { from, targets, .. }
+            | Job::SortAuthors { source: from, targets, .. } => {
+                std::iter::once(from).chain(targets).map(String::as_str).collect()
+            }
+            Job::VerifyReputation { target, source, .. } => vec![target, source],
+            Job::RecommendFollows { source, .. } => vec![source],
+        }
+    }
+
+    /// Mutable view of [`Job::pubkeys`], for replacing inputs with resolved hex pubkeys
+    pub fn pubkeys_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Job::Distance { from, to, .. } | Job::Path { from, to, .. } | Job::Verify { from, to } => {
+                vec![from, to]
+            }
+            Job::Rank { anchor: from, targets, .. }
+            | Job::Batch { from, targets, .. }
+            | Job::SortAuthors { source: from, targets, .. } => std::iter::once(from).chain(targets).collect(),
+            Job::VerifyReputation { target, source, .. } => vec![target, source],
+            Job::RecommendFollows { source, .. } => vec![source],
+        }
+    }
+
+    /// The pubkey whose view of the graph the job queries, subject to `RESTRICT_SOURCES`
+    pub fn source(&self) -> &str {
+        match self {
+            Job::Distance { from, .. }
+            | Job::Path { from, .. }
+            | Job::Verify { from, .. }
+            | Job::Batch { from, .. }
+            | Job::Rank { anchor: from, .. } => from,
+            Job::VerifyReputation { source, .. }
+            | Job::RecommendFollows { source, .. }
+            | Job::SortAuthors { source, .. } => source,
+        }
+    }
+
+    /// Billing units: batches are priced per target, everything else per job
+    pub fn units(&self) -> u64 {
+        match self {
+            Job::Batch { targets, .. } => targets.len() as u64,
+            _ => 1,
+        }
+    }
+}
+
+/// Parse a job from NIP-90 request tags
+pub fn parse_request<'a>(
+    tags: impl IntoIterator<Item = &'a [String]>,
+    default_max_hops: u8,
+    max_hops_limit: u8,
+) -> Result<JobRequest, String> {
+    // Parse request parameters from tags (NIP-90 standard)
+    let mut inputs: Vec<String> = Vec::new();
+    let mut max_hops: u8 = default_max_hops;
+    let mut job_type = "distance".to_string();
+    let mut limit: usize = RANK_LIMIT_DEFAULT;
+    let mut bid: Option<u64> = None;
+
+    for tag_slice in tags {
+        if tag_slice.len() >= 3 && tag_slice[0] == "i" && tag_slice[2] == "text" {
+            let value = &tag_slice[1];
+            // Check for colon-separated format (backwards compatibility)
+            if value.contains(':') {
+                let parts: Vec<&str> = value.split(':').collect();
+                if parts.len() == 2 {
+                    inputs.push(parts[0].to_string());
+                    inputs.push(parts[1].to_string());
+                }
+            } else {
+                // NIP-90 standard: single pubkey per "i" tag
+                inputs.push(value.to_string());
+            }
+        } else if tag_slice.len() >= 3 && tag_slice[0] == "param" {
+            match tag_slice[1].as_str() {
+                "max_hops" => {
+                    // Validate and clamp max_hops to safe range (1-DVM_MAX_HOPS)
+                    max_hops = match tag_slice[2].parse::<u8>() {
+                        Ok(h) if (1..=max_hops_limit).contains(&h) => h,
+                        Ok(h) => {
+                            warn!("DVM request max_hops {} out of range, clamping to {}", h, max_hops_limit);
+                            h.clamp(1, max_hops_limit)
+                        }
+                        Err(_) => {
+                            warn!("DVM request invalid max_hops value, using default {}", default_max_hops);
+                            default_max_hops
+                        }
+                    };
+                }
+                "from" => {
+                    inputs.insert(0, tag_slice[2].to_string());
+                }
+                "to" => {
+                    inputs.push(tag_slice[2].to_string());
+                }
+                "job_type" => {
+                    job_type = tag_slice[2].to_ascii_lowercase();
+                }
+                "limit" => {
+                    limit = tag_slice[2]
+                        .parse::<usize>()
+                        .map(|l| l.clamp(1, RANK_LIMIT_MAX))
+                        .unwrap_or(RANK_LIMIT_DEFAULT);
+                }
+                _ => {}
+            }
+        } else if tag_slice.len() >= 2 && tag_slice[0] == "bid" {
+            bid = tag_slice[1].parse().ok();
+        }
+    }
+
+    let pair = |inputs: Vec<String>| -> Result<(String, String), String> {
+        match <[String; 2]>::try_from(inputs) {
+            Ok([from, to]) => Ok((from, to)),
+            Err(_) => Err("Expected two 'i' tags with pubkeys or 'from'/'to' params".to_string()),
+        }
+    };
+
+    let job = match job_type.as_str() {
+        "distance" => {
+            let (from, to) = pair(inputs)?;
+            Job::Distance { from, to, max_hops }
+        }
+        "path" => {
+            let (from, to) = pair(inputs)?;
+            Job::Path { from, to, max_hops }
+        }
+        "verify" => {
+            let (from, to) = pair(inputs)?;
+            Job::Verify { from, to }
+        }
+        "rank" => {
+            let mut inputs = inputs.into_iter();
+            let anchor = inputs.next().ok_or("Expected an anchor pubkey")?;
+            let targets: Vec<String> = inputs.collect();
+            if targets.len() > RANK_LIMIT_MAX {
+                return Err(format!("Maximum {} targets allowed per rank job", RANK_LIMIT_MAX));
+            }
+            Job::Rank { anchor, targets, limit }
+        }
+        "batch" => {
+            let mut inputs = inputs.into_iter();
+            let from = inputs.next().ok_or("Expected a source pubkey and targets")?;
+            let targets: Vec<String> = inputs.collect();
+            if targets.is_empty() {
+                return Err("Expected a source pubkey and targets".to_string());
+            }
+            if targets.len() > MAX_BATCH_TARGETS {
+                return Err(format!("Maximum {} targets allowed per batch", MAX_BATCH_TARGETS));
+            }
+            Job::Batch { from, targets, max_hops }
+        }
+        _ => return Err("Unsupported job_type".to_string()),
+    };
+
+    Ok(JobRequest { job, bid })
+}
+
+/// A job pubkey as a query key; job pubkeys are resolved to hex before the job runs
+pub(super) fn query_key(pubkey: &str) -> Result<Pubkey> {
+    Pubkey::parse(pubkey).with_context(|| format!("Unresolved pubkey {}", pubkey))
+}
+
+impl DvmService {
+    pub(super) async fn run_job(&self, job: &Job) -> Result<JobOutput> {
+        match job {
+            Job::Distance { from, to, max_hops } => {
+                let (from, to) = (query_key(from)?, query_key(to)?);
+                let (max_hops, adaptive_hops) = self.config.hop_policy().apply(&self.graph, &from, &to, *max_hops);
+                let result = self.distance(from, to, max_hops).await?;
+                let mut content = serde_json::to_value(&result)?;
+                if let Some(adaptive_hops) = adaptive_hops {
+                    content["adaptive_hops"] = json!(adaptive_hops);
+                }
+                Ok(JobOutput {
+                    result: result.hops.map(|hops| (hops.to_string(), "hops")),
+                    content,
+                })
+            }
+            Job::Path { from, to, max_hops } => {
+                let (from, to) = (query_key(from)?, query_key(to)?);
+                let (max_hops, adaptive_hops) = self.config.hop_policy().apply(&self.graph, &from, &to, *max_hops);
+                let graph = Arc::clone(&self.graph);
+                let query = bfs::PathQuery {
+                    from,
+                    to,
+                    max_hops,
+                    min_followers: 0,
+                };
+                let result = tokio::task::spawn_blocking(move || bfs::compute_path(&graph, &query))
+                    .await
+                    .context("Path computation task failed")?;
+                let mut content = json!({"from": from, "to": to, "path": result.path});
+                if let Some(adaptive_hops) = adaptive_hops {
+                    content["adaptive_hops"] = json!(adaptive_hops);
+                }
+                Ok(JobOutput {
+                    result: result.path.as_ref().map(|p| ((p.len() - 1).to_string(), "hops")),
+                    content,
+                })
+            }
+            Job::Rank { anchor, targets, limit } => {
+                let rankings = self.personalized_rank(anchor, targets, *limit).await?;
+                Ok(JobOutput {
+                    result: None,
+                    content: json!({"anchor": anchor, "rankings": rankings}),
+                })
+            }
+            Job::Verify { from, to } => {
+                let graph = &self.graph;
+                let (from, to) = (query_key(from)?, query_key(to)?);
+                let (follows, followed_by) = match (graph.node_id(&from), graph.node_id(&to)) {
+                    (Some(from), Some(to)) => (graph.is_following(from, to), graph.is_following(to, from)),
+                    _ => (false, false),
+                };
+                Ok(JobOutput {
+                    result: Some((follows.to_string(), "follows")),
+                    content: json!({
+                        "from": from,
+                        "to": to,
+                        "follows": follows,
+                        "followed_by": followed_by,
+                        "mutual": follows && followed_by,
+                    }),
+                })
+            }
+            Job::Batch { from, targets, max_hops } => {
+                let mut results = Vec::with_capacity(targets.len());
+                let hop_policy = self.config.hop_policy();
+                let source = query_key(from)?;
+                for to in targets {
+                    let to = query_key(to)?;
+                    let (max_hops, adaptive_hops) = hop_policy.apply(&self.graph, &source, &to, *max_hops);
+                    let mut result = serde_json::to_value(self.distance(source, to, max_hops).await?)?;
+                    if let Some(adaptive_hops) = adaptive_hops {
+                        result["adaptive_hops"] = json!(adaptive_hops);
+                    }
+                    results.push(result);
+                }
+                let found = results.iter().filter(|r| !r["hops"].is_null()).count();
+                Ok(JobOutput {
+                    result: Some((found.to_string(), "found")),
+                    content: json!({"from": from, "results": results}),
+                })
+            }
+            Job::VerifyReputation { target, source, sort, limit } => Ok(JobOutput {
+                content: self.verify_reputation(target, source, *sort, *limit).await?,
+                result: None,
+            }),
+            Job::RecommendFollows { source, sort, limit } => Ok(JobOutput {
+                content: self.recommend_follows(source, *sort, *limit).await?,
+                result: None,
+            }),
+            Job::SortAuthors { source, sort, targets } => Ok(JobOutput {
+                content: self.sort_authors(source, *sort, targets).await?,
+                result: None,
+            }),
+        }
+    }
+
+    /// Distance lookup through the shared query cache (bridges always included)
+    pub(super) async fn distance(&self, from: Pubkey, to: Pubkey, max_hops: u8) -> Result<bfs::DistanceResult> {
+        // Check cache first
+        let from_id = self.graph.node_id(&from);
+        let to_id = self.graph.node_id(&to);
+        let include_bridges = true;
+
+        let result = if let (Some(from_id), Some(to_id)) = (from_id, to_id) {
+            let cache_key = CacheKey::new(from_id, to_id, max_hops, include_bridges);
+            if let Some(cached_result) = self.cache.get(&cache_key, &self.graph) {
+                debug!("DVM cache hit for {} -> {}", &from.to_hex()[..8], &to.to_hex()[..8]);
+                cached_result
+            } else {
+                // Compute on blocking thread pool and cache
+                let query = bfs::DistanceQuery {
+                    from,
+                    to,
+                    max_hops,
+                    include_bridges,
+                    min_followers: 0,
+                };
+                let graph = Arc::clone(&self.graph);
+                let result = tokio::task::spawn_blocking(move || {
+                    bfs::compute_distance(&graph, &query)
+                })
+                .await
+                .context("BFS computation task failed")?;
+                self.cache.insert(cache_key, &result, &self.graph);
+                debug!("DVM cache miss for {} -> {}, computed and cached", &from.to_hex()[..8], &to.to_hex()[..8]);
+                result
+            }
+        } else {
+            // Node not in graph, compute on blocking thread pool without caching
+            let query = bfs::DistanceQuery {
+                from,
+                to,
+                max_hops,
+                include_bridges,
+                min_followers: 0,
+            };
+            let graph = Arc::clone(&self.graph);
+            tokio::task::spawn_blocking(move || {
+                bfs::compute_distance(&graph, &query)
+            })
+            .await
+            .context("BFS computation task failed")?
+        };
+
+        Ok(result)
+    }
+
+    async fn personalized_rank(&self, anchor: &str, targets: &[String], limit: usize) -> Result<Vec<Value>> {
+        let targets = targets.iter().map(|pk| query_key(pk)).collect::<Result<Vec<_>>>()?;
+        let Some(seed) = self.graph.node_id(&query_key(anchor)?) else {
+            return Ok(targets.iter().map(|pk| json!({"pubkey": pk, "score": 0.0})).collect());
+        };
+
+        let graph = Arc::clone(&self.graph);
+        let ranked: Vec<(Pubkey, f64)> = tokio::task::spawn_blocking(move || {
+            let scores = graph.with_adjacency(|follows, _| pagerank::personalized(follows, seed));
+            if targets.is_empty() {
+                pagerank::top_n(&scores, limit, Some(seed))
+                    .into_iter()
+                    .filter_map(|(id, score)| graph.pubkey(id).map(|pk| (pk, score)))
+                    .collect()
+            } else {
+                targets
+                    .into_iter()
+                    .map(|pk| {
+                        let score = graph
+                            .node_id(&pk)
+                            .and_then(|id| scores.get(id as usize).copied())
+                            .unwrap_or(0.0);
+                        (pk, score)
+                    })
+                    .collect()
+            }
+        })
+        .await
+        .context("Personalized PageRank task failed")?;
+
+        Ok(ranked
+            .into_iter()
+            .filter(|(pk, _)| self.access.is_pubkey_allowed(&pk.to_hex()))
+            .map(|(pk, score)| json!({"pubkey": pk, "score": score}))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MAX_HOPS_LIMIT;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    fn parse(tags: &[&[&str]]) -> Result<JobRequest, String> {
+        let tags: Vec<Vec<String>> = tags
+            .iter()
+            .map(|t| t.iter().map(|s| s.to_string()).collect())
+            .collect();
+        parse_request(tags.iter().map(Vec::as_slice), 3, MAX_HOPS_LIMIT)
+    }
+
+    #[test]
+    fn test_parse_distance_formats() {
+        let expected = Job::Distance { from: A.into(), to: B.into(), max_hops: 3 };
+
+        let two_inputs = parse(&[&["i", A, "text"], &["i", B, "text"]]).unwrap();
+        assert_eq!(two_inputs.job, expected);
+
+        let combined = format!("{A}:{B}");
+        let legacy = parse(&[&["i", &combined, "text"]]).unwrap();
+        assert_eq!(legacy.job, expected);
+
+        let params = parse(&[&["param", "to", B], &["param", "from", A], &["bid", "5000"]]).unwrap();
+        assert_eq!(params.job, expected);
+        assert_eq!(params.bid, Some(5000));
+    }
+
+    #[test]
+    fn test_parse_job_types() {
+        let path = parse(&[&["i", A, "text"], &["i", B, "text"], &["param", "job_type", "path"], &["param", "max_hops", "9"]]);
+        assert_eq!(path.unwrap().job, Job::Path { from: A.into(), to: B.into(), max_hops: MAX_HOPS_LIMIT });
+
+        // A lower DVM_MAX_HOPS clamps requests to it
+        let tags: Vec<Vec<String>> = [["i", A, "text"], ["i", B, "text"], ["param", "max_hops", "4"]]
+            .iter()
+            .map(|t| t.iter().map(|s| s.to_string()).collect())
+            .collect();
+        let limited = parse_request(tags.iter().map(Vec::as_slice), 2, 2).unwrap();
+        assert_eq!(limited.job, Job::Distance { from: A.into(), to: B.into(), max_hops: 2 });
+
+        let rank = parse(&[&["i", A, "text"], &["param", "job_type", "rank"], &["param", "limit", "500"]]);
+        assert_eq!(rank.unwrap().job, Job::Rank { anchor: A.into(), targets: vec![], limit: RANK_LIMIT_MAX });
+
+        let batch = parse(&[&["param", "job_type", "batch"], &["i", A, "text"], &["i", B, "text"], &["i", B, "text"]]).unwrap();
+        assert_eq!(batch.job.units(), 2);
+        assert_eq!(batch.job.pubkeys(), vec![A, B, B]);
+
+        assert!(parse(&[&["i", A, "text"], &["param", "job_type", "verify"]]).is_err());
+        assert!(parse(&[&["i", A, "text"], &["param", "job_type", "batch"]]).is_err());
+        assert!(parse(&[&["i", A, "text"], &["i", B, "text"], &["param", "job_type", "nope"]]).is_err());
+    }
+}
@@ -0,0 +1,1016 @@
+use anyhow::{Context, Result};
+use moka::sync::Cache;
+use nostr_sdk::prelude::*;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn, error, debug};
+
+use super::access::AccessLists;
+use super::identity::Resolver;
+use super::lightning::{self, Invoice, LightningBackend};
+use crate::cache::QueryCache;
+use crate::config::Config;
+use crate::db::Database;
+use crate::error::OracleError;
+use crate::graph::{PageRank, WotGraph};
+use crate::namespace::Namespaces;
+
+mod health;
+mod jobs;
+mod queue;
+mod vertex;
+
+pub use health::{DvmRelayStatus, DvmStats, DvmStatsSnapshot};
+pub use vertex::{recommendations, Sort};
+use health::Backoff;
+use jobs::{Job, JobOutput, JobRequest};
+use queue::JobQueue;
+
+const DVM_REQUEST_KIND: u16 = 5950;
+const DVM_RESULT_KIND_OFFSET: u16 = 1000;
+const DVM_FEEDBACK_KIND: u16 = 7000;
+
+// Each unpaid invoice holds a polling task, so a flood of requests is capped here
+const MAX_PENDING_INVOICES: usize = 1000;
+const PAYMENT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+// The same request usually arrives once per relay; remember IDs (and the answers published for
+// them) long enough to drop the copies
+const SEEN_REQUESTS_CAPACITY: u64 = 10_000;
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// Responses go to at most this many of the relays a request names
+const MAX_REPLY_RELAYS: usize = 5;
+// Requester relays joined to the pool; beyond this only relays already connected are used
+const MAX_ADDED_RELAYS: usize = 50;
+
+/// Lightning pricing for DVM jobs (`DVM_PRICE_MSATS`)
+#[derive(Clone)]
+struct Payments {
+    backend: Arc<dyn LightningBackend>,
+    price_msats: u64,
+    free_pubkeys: Arc<HashSet<String>>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl Payments {
+    /// Price of a job of `units`: None if the requester is exempt, Err if their bid is too low
+    fn quote(&self, requester: &str, bid: Option<u64>, units: u64) -> Result<Option<u64>, String> {
+        if self.free_pubkeys.contains(&requester.to_ascii_lowercase()) {
+            return Ok(None);
+        }
+        let price_msats = self.price_msats.saturating_mul(units);
+        match bid {
+            Some(bid) if bid < price_msats => Err(format!("Bid below price of {} msats", price_msats)),
+            _ => Ok(Some(price_msats)),
+        }
+    }
+
+    fn reserve(&self) -> Option<PendingInvoice> {
+        if self.pending.fetch_add(1, Ordering::AcqRel) >= MAX_PENDING_INVOICES {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+        Some(PendingInvoice(self.pending.clone()))
+    }
+}
+
+/// Where a request seen on the relays was answered, and its answer once published. Copies of the
+/// request arriving from other relays are answered there with the same signed event instead of
+/// running the job again.
+#[derive(Default)]
+struct Answer {
+    // Relays the request arrived from after the first, still waiting when `response` is None
+    relays: Vec<String>,
+    response: Option<Event>,
+}
+
+/// Slot in the pending-invoice budget, released on drop
+struct PendingInvoice(Arc<AtomicUsize>);
+
+impl Drop for PendingInvoice {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Clone)]
+pub struct DvmService {
+    graph: Arc<WotGraph>,
+    cache: Arc<QueryCache>,
+    ranks: Arc<PageRank>,
+    config: Arc<Config>,
+    access: Arc<AccessLists>,
+    resolver: Arc<Resolver>,
+    keys: Keys,
+    payments: Option<Payments>,
+    seen_requests: Cache<EventId, Arc<Mutex<Answer>>>,
+    queue: Arc<JobQueue>,
+    stats: Arc<DvmStats>,
+    namespaces: Arc<Namespaces>,
+    /// The primary graph's database, for requesters' stored settings
+    db: Arc<Database>,
+    added_relays: Arc<AtomicUsize>,
+}
+
+impl DvmService {
+    #[allow(clippy::too_many_arguments)] // One shared handle per subsystem, all owned by main
+    pub fn new(
+        graph: Arc<WotGraph>,
+        cache: Arc<QueryCache>,
+        ranks: Arc<PageRank>,
+        config: Arc<Config>,
+        access: Arc<AccessLists>,
+        resolver: Arc<Resolver>,
+        stats: Arc<DvmStats>,
+        namespaces: Arc<Namespaces>,
+        db: Arc<Database>,
+        private_key: &str,
+    ) -> Result<Self> {
+        let keys = Keys::parse(private_key).context("Failed to parse DVM private key")?;
+
+        info!("DVM service pubkey: {}", keys.public_key().to_hex());
+
+        let payments = match (config.dvm_price_msats, lightning::backend_from_config(&config)) {
+            (0, _) => None,
+            (price_msats, Some(backend)) => {
+                info!("DVM jobs cost {} msats", price_msats);
+                Some(Payments {
+                    backend,
+                    price_msats,
+                    free_pubkeys: Arc::new(
+                        config.dvm_free_pubkeys.iter().map(|pk| pk.to_ascii_lowercase()).collect(),
+                    ),
+                    pending: Arc::new(AtomicUsize::new(0)),
+                })
+            }
+            (_, None) => anyhow::bail!(
+                "DVM_PRICE_MSATS is set but no Lightning backend is configured (LNBITS_URL, LNBITS_API_KEY)"
+            ),
+        };
+
+        // Past twice the max age a replayed copy is rejected as too old anyway
+        let seen_requests = Cache::builder()
+            .max_capacity(SEEN_REQUESTS_CAPACITY)
+            .time_to_live(Duration::from_secs(config.dvm_max_request_age_secs * 2))
+            .build();
+
+        let queue = Arc::new(JobQueue::new(config.dvm_workers, config.dvm_queue_size));
+
+        Ok(Self {
+            graph,
+            cache,
+            ranks,
+            config,
+            access,
+            resolver,
+            keys,
+            payments,
+            seen_requests,
+            queue,
+            stats,
+            namespaces,
+            db,
+            added_relays: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        info!("Starting DVM service...");
+
+        let client = Client::new(&self.keys);
+
+        // Add relays
+        for relay_url in &self.config.relays {
+            match client.add_relay(relay_url).await {
+                Ok(_) => info!("DVM added relay: {}", relay_url),
+                Err(e) => warn!("DVM failed to add relay {}: {}", relay_url, e),
+            }
+        }
+
+        client.connect().await;
+
+        // Subscribe to DVM requests (kind 5950 plus Vertex-style kinds)
+        let request_kinds: Vec<u16> = std::iter::once(DVM_REQUEST_KIND).chain(vertex::VERTEX_KINDS).collect();
+        let subscription = SubscriptionId::generate();
+        let mut subscribed = self
+            .subscribe(&client, &subscription, &request_kinds, Timestamp::now())
+            .await;
+
+        info!("DVM listening for requests (kinds {:?})", request_kinds);
+
+        let mut notifications = client.notifications();
+        let mut health = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        let mut backoff = Backoff::new();
+        // Relays connected at the last health check, and relays that have ever been connected
+        let mut connected: HashSet<Url> = HashSet::new();
+        let mut ever_connected: HashSet<Url> = HashSet::new();
+
+        loop {
+            tokio::select! {
+                notification = notifications.recv() => match notification {
+                    Ok(RelayPoolNotification::Event { relay_url, event, .. }) => {
+                        if request_kinds.contains(&event.kind.as_u16()) && self.should_handle(&client, &relay_url, &event).await {
+                            self.dispatch(&client, relay_url, *event).await;
+                        }
+                    }
+                    // Copies of an event already received from another relay only come as messages
+                    Ok(RelayPoolNotification::Message { relay_url, message: RelayMessage::Event { event, .. } }) => {
+                        if let Some(answer) = self.seen_requests.get(&event.id) {
+                            self.answer_duplicate(&client, &relay_url, &event, &answer).await;
+                        }
+                    }
+                    Ok(RelayPoolNotification::Shutdown) => anyhow::bail!("DVM relay pool shut down"),
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Error receiving notification: {}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                },
+                _ = health.tick() => {
+                    let now_connected = self.refresh_relay_status(&client).await;
+                    let reconnected = now_connected
+                        .iter()
+                        .any(|url| ever_connected.contains(url) && !connected.contains(url));
+                    if reconnected {
+                        info!("DVM relay reconnected, refreshing subscription");
+                        subscribed = false;
+                    }
+                    ever_connected.extend(now_connected.iter().cloned());
+                    connected = now_connected;
+
+                    if connected.is_empty() {
+                        if backoff.ready() {
+                            let retry = backoff.failed();
+                            warn!("DVM has no connected relays, reconnecting (next attempt in {:?})", retry);
+                            client.connect().await;
+                        }
+                    } else if !subscribed {
+                        if backoff.ready() {
+                            // Reach back far enough to pick up requests sent while disconnected
+                            let since = Timestamp::now() - Duration::from_secs(self.config.dvm_max_request_age_secs);
+                            subscribed = self.subscribe(&client, &subscription, &request_kinds, since).await;
+                            if subscribed {
+                                self.stats.record_resubscription();
+                                backoff.reset();
+                            } else {
+                                backoff.failed();
+                            }
+                        }
+                    } else {
+                        backoff.reset();
+                    }
+                }
+            }
+        }
+    }
+
+    /// (Re)send the request subscription; the same ID replaces it on relays that still hold it
+    async fn subscribe(&self, client: &Client, id: &SubscriptionId, kinds: &[u16], since: Timestamp) -> bool {
+        let filter = Filter::new()
+            .kinds(kinds.iter().map(|&k| Kind::Custom(k)))
+            .since(since);
+        match client.subscribe_with_id(id.clone(), vec![filter], None).await {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("DVM failed to subscribe to requests: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Publish each relay's status to the stats and return the connected ones
+    async fn refresh_relay_status(&self, client: &Client) -> HashSet<Url> {
+        let mut relays = Vec::new();
+        let mut connected = HashSet::new();
+        for (url, relay) in client.relays().await {
+            let status = relay.status().await;
+            let is_connected = status == RelayStatus::Connected;
+            if is_connected {
+                connected.insert(url.clone());
+            }
+            relays.push(DvmRelayStatus {
+                url: url.to_string(),
+                status: status.to_string(),
+                connected: is_connected,
+            });
+        }
+        relays.sort_by(|a, b| a.url.cmp(&b.url));
+        self.stats.set_relays(relays);
+        connected
+    }
+
+    /// Relays to answer on: those named in the request's NIP-90 `relays` tag, else the relay it arrived from.
+    /// Requested relays missing from the pool are added, up to `MAX_ADDED_RELAYS` over the service's lifetime.
+    async fn reply_to(&self, client: &Client, source: Url, request: &Event) -> ReplyTo {
+        let requested = requested_relays(request.tags.iter().map(|t| t.as_slice()));
+        let mut relays = Vec::new();
+        if !requested.is_empty() {
+            let pool: HashSet<String> = client.relays().await.keys().map(|url| url.to_string()).collect();
+            let mut added = false;
+            for url in requested {
+                if pool.contains(&url) {
+                    relays.push(url);
+                } else if self.added_relays.fetch_add(1, Ordering::AcqRel) < MAX_ADDED_RELAYS {
+                    match client.add_relay(&url).await {
+                        Ok(_) => {
+                            debug!("DVM added requester relay: {}", url);
+                            relays.push(url);
+                            added = true;
+                        }
+                        Err(e) => warn!("DVM failed to add requester relay {}: {}", url, e),
+                    }
+                } else {
+                    self.added_relays.fetch_sub(1, Ordering::AcqRel);
+                }
+            }
+            if added {
+                client.connect().await;
+            }
+        }
+        if relays.is_empty() {
+            relays.push(source.to_string());
+        }
+        ReplyTo { client: client.clone(), relays }
+    }
+
+    /// Hand a request to the worker pool, or turn it away with an error when the queue is full
+    async fn dispatch(&self, client: &Client, source: Url, request: Event) {
+        let reply = &self.reply_to(client, source, &request).await;
+        let Some(ticket) = self.queue.enqueue() else {
+            self.stats.record_rejected();
+            let busy = OracleError::Overloaded("DVM is busy, try again later".to_string());
+            if let Err(e) = self.send_error(reply, &request, &busy).await {
+                error!("Failed to reject DVM request: {}", e);
+            }
+            return;
+        };
+
+        let (dvm, reply) = (self.clone(), reply.clone());
+        tokio::spawn(async move {
+            if ticket.position() > 0 {
+                let info = format!("Queued at position {}", ticket.position());
+                if let Err(e) = dvm.send_feedback(&reply, &request, "processing", Some(&info), None).await {
+                    warn!("Failed to send DVM queue feedback: {}", e);
+                }
+            }
+
+            let _worker = ticket.start().await;
+            match dvm.handle_request(&reply, &request).await {
+                Ok(_) => {
+                    dvm.stats.record_processed();
+                    debug!("Processed DVM request: {}", request.id);
+                }
+                Err(e) => {
+                    dvm.stats.record_failed();
+                    error!("Failed to process DVM request: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Drop duplicates of requests already seen on another relay, expired requests and replayed history
+    async fn should_handle(&self, client: &Client, source: &Url, request: &Event) -> bool {
+        if let Some(answer) = self.seen_requests.get(&request.id) {
+            debug!("Duplicate DVM request: {}", request.id);
+            self.answer_duplicate(client, source, request, &answer).await;
+            return false;
+        }
+        self.seen_requests.insert(request.id, Arc::default());
+
+        let tags = request.tags.iter().map(|t| t.as_slice());
+        let now = Timestamp::now().as_u64();
+        match stale_reason(tags, request.created_at.as_u64(), now, self.config.dvm_max_request_age_secs) {
+            Some(reason) => {
+                debug!("Ignoring {} DVM request: {}", reason, request.id);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Answer a copy of a request from `source` too, unless the requester named its relays: with
+    /// the stored response if already published, else once it is (see [`Self::send_answer`])
+    async fn answer_duplicate(&self, client: &Client, source: &Url, request: &Event, answer: &Mutex<Answer>) {
+        if !requested_relays(request.tags.iter().map(|t| t.as_slice())).is_empty() {
+            return;
+        }
+        let source = source.to_string();
+        let response = {
+            let mut answer = answer.lock();
+            if answer.relays.contains(&source) {
+                return;
+            }
+            answer.relays.push(source.clone());
+            answer.response.clone()
+        };
+        if let Some(response) = response {
+            match client.send_event_to([source.as_str()], response).await {
+                Ok(_) => debug!("Re-sent DVM response for {} to {}", request.id, source),
+                Err(e) => warn!("Failed to re-send DVM response to {}: {}", source, e),
+            }
+        }
+    }
+
+    /// Publish a request's result or error on its reply relays and any relays its copies came
+    /// from meanwhile, and keep it for copies still to come
+    async fn send_answer(&self, reply: &ReplyTo, request: &Event, builder: EventBuilder) -> Result<()> {
+        let response = reply.client.sign_event_builder(builder).await?;
+        let mut relays = reply.relays.clone();
+        if let Some(answer) = self.seen_requests.get(&request.id) {
+            let mut answer = answer.lock();
+            for relay in &answer.relays {
+                if !relays.contains(relay) {
+                    relays.push(relay.clone());
+                }
+            }
+            answer.relays.clone_from(&relays);
+            answer.response = Some(response.clone());
+        }
+        reply.client.send_event_to(&relays, response).await?;
+        Ok(())
+    }
+
+    async fn handle_request(&self, reply: &ReplyTo, request: &Event) -> Result<()> {
+        debug!("Received DVM request: {}", request.id);
+
+        let tags = request.tags.iter().map(|t| t.as_slice());
+        let parsed = match request.kind.as_u16() {
+            vertex::SEARCH_PROFILES_KIND => {
+                let unsupported = OracleError::invalid("UNSUPPORTED_KIND", "SearchProfiles (5315) is not supported");
+                self.send_error(reply, request, &unsupported).await?;
+                return Ok(());
+            }
+            DVM_REQUEST_KIND => jobs::parse_request(tags, self.config.max_hops, self.config.dvm_max_hops),
+            kind => {
+                let requester = request.pubkey.to_hex();
+                vertex::parse_request(kind, tags, &requester, self.default_sort(&requester))
+                    .map(|job| JobRequest { job, bid: bid_amount(request) })
+            }
+        };
+        let JobRequest { mut job, bid } = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                self.send_error(reply, request, &OracleError::invalid("INVALID_REQUEST", e)).await?;
+                return Ok(());
+            }
+        };
+
+        // Jobs run against the graph named by the `graph` param (default: the primary graph)
+        let Some(dvm) = self.for_graph(requested_graph(request.tags.iter().map(|t| t.as_slice()))) else {
+            self.send_error(reply, request, &OracleError::UnknownGraph).await?;
+            return Ok(());
+        };
+
+        // Resolve npub, nprofile and NIP-05 inputs (a capped number of lookups, run concurrently);
+        // this also rejects malformed pubkeys
+        let mut pubkeys = job.pubkeys_mut();
+        let inputs: Vec<&str> = pubkeys.iter().map(|pubkey| pubkey.as_str()).collect();
+        match dvm.resolver.resolve_all(&inputs).await {
+            Ok(resolved) => {
+                for (pubkey, resolved) in pubkeys.iter_mut().zip(resolved) {
+                    **pubkey = resolved.to_hex();
+                }
+            }
+            Err(e) => {
+                dvm.send_error(reply, request, &e.into()).await?;
+                return Ok(());
+            }
+        }
+
+        let pubkeys = job.pubkeys();
+        if !pubkeys
+            .iter()
+            .all(|pk| dvm.access.is_pubkey_allowed(pk) && !dvm.graph.is_blocked(pk))
+        {
+            dvm.send_error(reply, request, &OracleError::PubkeyDenied).await?;
+            return Ok(());
+        }
+        if !dvm.access.is_source_allowed(job.source()) {
+            dvm.send_error(reply, request, &OracleError::SourceDenied).await?;
+            return Ok(());
+        }
+
+        if let Some(ref payments) = dvm.payments {
+            match payments.quote(&request.pubkey.to_hex(), bid, job.units()) {
+                Ok(Some(price_msats)) => {
+                    return dvm.request_payment(reply, request, payments, price_msats, job).await;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    dvm.send_error(reply, request, &OracleError::BudgetExceeded(e)).await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        let output = match dvm.run_job(&job).await {
+            Ok(output) => output,
+            Err(e) => {
+                dvm.send_error(reply, request, &OracleError::internal("Job failed")).await?;
+                return Err(e);
+            }
+        };
+        dvm.send_result(reply, request, &job, output, None).await
+    }
+
+    /// The requester's stored scoring (`/me/settings`), for Vertex-style requests without `sort`
+    fn default_sort(&self, requester: &str) -> Option<vertex::Sort> {
+        match self.db.user_settings(requester) {
+            Ok(settings) => settings?.scoring.and_then(|scoring| vertex::Sort::parse(&scoring).ok()),
+            Err(e) => {
+                warn!("Failed to load settings for {}: {}", requester, e);
+                None
+            }
+        }
+    }
+
+    /// This service pointed at a named graph; None if no graph has that name
+    fn for_graph(&self, name: Option<&str>) -> Option<Self> {
+        match self.namespaces.select(name) {
+            Ok(None) => Some(self.clone()),
+            Ok(Some(namespace)) => Some(Self {
+                graph: namespace.graph.clone(),
+                cache: namespace.cache.clone(),
+                ranks: namespace.ranks.clone(),
+                ..self.clone()
+            }),
+            Err(_) => None,
+        }
+    }
+
+    async fn send_result(
+        &self,
+        reply: &ReplyTo,
+        request: &Event,
+        job: &Job,
+        output: JobOutput,
+        paid: Option<(u64, &Invoice)>,
+    ) -> Result<()> {
+        // Build response (don't echo full request for security)
+        let response_content = output.content.to_string();
+
+        let mut tags = vec![
+            Tag::parse(&["e", &request.id.to_hex()])?,
+            Tag::parse(&["p", &request.pubkey.to_hex()])?,
+        ];
+
+        // Add result tags
+        if let Some((ref value, unit)) = output.result {
+            tags.push(Tag::parse(&["result", value, unit])?);
+        }
+        if let Some((amount_msats, invoice)) = paid {
+            tags.push(Tag::parse(&["amount", &amount_msats.to_string(), &invoice.bolt11])?);
+        }
+
+        let response_event = EventBuilder::new(response_kind(request), response_content, tags);
+
+        self.send_answer(reply, request, response_event).await?;
+
+        info!(
+            "Sent DVM {} response for {}: {:?}",
+            job.name(),
+            request.id,
+            output.result
+        );
+
+        Ok(())
+    }
+
+    /// Send a `payment-required` invoice and run the job in the background once it is paid
+    async fn request_payment(
+        &self,
+        reply: &ReplyTo,
+        request: &Event,
+        payments: &Payments,
+        price_msats: u64,
+        job: Job,
+    ) -> Result<()> {
+        let Some(slot) = payments.reserve() else {
+            let busy = OracleError::Overloaded("Too many pending payments, try again later".to_string());
+            self.send_error(reply, request, &busy).await?;
+            return Ok(());
+        };
+
+        let expiry = Duration::from_secs(self.config.dvm_invoice_expiry_secs);
+        let memo = format!("WoT Oracle DVM {} job {}", job.name(), request.id.to_hex());
+        let invoice = match payments.backend.create_invoice(price_msats, &memo, expiry).await {
+            Ok(invoice) => invoice,
+            Err(e) => {
+                error!("Failed to create DVM invoice: {}", e);
+                let unavailable = OracleError::internal("Payment backend unavailable");
+                self.send_error(reply, request, &unavailable).await?;
+                return Ok(());
+            }
+        };
+
+        self.send_feedback(reply, request, "payment-required", None, Some((price_msats, &invoice)))
+            .await?;
+
+        let (dvm, reply, request) = (self.clone(), reply.clone(), request.clone());
+        let backend = payments.backend.clone();
+        tokio::spawn(async move {
+            let _slot = slot;
+            let outcome = if await_payment(backend.as_ref(), &invoice, expiry).await {
+                // Paid jobs wait for a worker but are never turned away
+                let _worker = dvm.queue.acquire().await;
+                match dvm.run_job(&job).await {
+                    Ok(output) => {
+                        dvm.send_result(&reply, &request, &job, output, Some((price_msats, &invoice)))
+                            .await
+                    }
+                    Err(e) => {
+                        let failed = OracleError::internal("Job failed");
+                        if let Err(send_err) = dvm.send_error(&reply, &request, &failed).await {
+                            warn!("Failed to send DVM error response: {}", send_err);
+                        }
+                        Err(e)
+                    }
+                }
+            } else {
+                dvm.send_error(&reply, &request, &OracleError::PaymentExpired).await
+            };
+            if let Err(e) = outcome {
+                dvm.stats.record_failed();
+                error!("Failed to complete paid DVM request: {}", e);
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn send_feedback(
+        &self,
+        reply: &ReplyTo,
+        request: &Event,
+        status: &str,
+        info: Option<&str>,
+        amount: Option<(u64, &Invoice)>,
+    ) -> Result<()> {
+        let status_tag = match info {
+            Some(info) => Tag::parse(&["status", status, info])?,
+            None => Tag::parse(&["status", status])?,
+        };
+        let mut tags = vec![
+            status_tag,
+            Tag::parse(&["e", &request.id.to_hex()])?,
+            Tag::parse(&["p", &request.pubkey.to_hex()])?,
+        ];
+        if let Some((amount_msats, invoice)) = amount {
+            tags.push(Tag::parse(&["amount", &amount_msats.to_string(), &invoice.bolt11])?);
+        }
+
+        reply
+            .send(EventBuilder::new(Kind::Custom(DVM_FEEDBACK_KIND), "", tags))
+            .await?;
+
+        debug!("Sent DVM {} feedback for {}", status, request.id);
+
+        Ok(())
+    }
+
+    /// Answer with an error: the message and its [`OracleError::code`] go in the `status` tag and,
+    /// for result events, in the content as `{"error", "code"}` like HTTP error bodies
+    async fn send_error(&self, reply: &ReplyTo, request: &Event, error: &OracleError) -> Result<()> {
+        self.stats.record_error_response();
+        let (error_msg, code) = (&error.to_string(), error.code());
+
+        // Vertex-style clients expect errors as job feedback rather than results
+        if request.kind.as_u16() != DVM_REQUEST_KIND {
+            let tags = vec![
+                Tag::parse(&["status", "error", error_msg, code])?,
+                Tag::parse(&["e", &request.id.to_hex()])?,
+                Tag::parse(&["p", &request.pubkey.to_hex()])?,
+            ];
+            self.send_answer(reply, request, EventBuilder::new(Kind::Custom(DVM_FEEDBACK_KIND), "", tags))
+                .await?;
+            warn!("Sent DVM error feedback: {} ({})", error_msg, code);
+            return Ok(());
+        }
+
+        let tags = vec![
+            Tag::parse(&["e", &request.id.to_hex()])?,
+            Tag::parse(&["p", &request.pubkey.to_hex()])?,
+            Tag::parse(&["status", "error", error_msg, code])?,
+        ];
+
+        let error_event = EventBuilder::new(
+            response_kind(request),
+            serde_json::json!({"error": error_msg, "code": code}).to_string(),
+            tags,
+        );
+
+        self.send_answer(reply, request, error_event).await?;
+
+        warn!("Sent DVM error response: {} ({})", error_msg, code);
+
+        Ok(())
+    }
+}
+
+/// NIP-90 result kind: the request kind + 1000
+fn response_kind(request: &Event) -> Kind {
+    Kind::Custom(request.kind.as_u16() + DVM_RESULT_KIND_OFFSET)
+}
+
+/// NIP-90 `bid` tag, in msats
+fn bid_amount(request: &Event) -> Option<u64> {
+    request
+        .tags
+        .iter()
+        .map(|t| t.as_slice())
+        .find(|t| t.len() >= 2 && t[0] == "bid")
+        .and_then(|t| t[1].parse().ok())
+}
+
+/// Poll the backend until the invoice is settled or expires
+async fn await_payment(backend: &dyn LightningBackend, invoice: &Invoice, expiry: Duration) -> bool {
+    let deadline = Instant::now() + expiry;
+    while Instant::now() < deadline {
+        tokio::time::sleep(PAYMENT_POLL_INTERVAL).await;
+        match backend.is_paid(invoice).await {
+            Ok(true) => return true,
+            Ok(false) => {}
+            Err(e) => debug!("Payment status check failed for {}: {}", invoice.payment_hash, e),
+        }
+    }
+    false
+}
+
+/// Client handle bound to the relays one request's responses are published on
+#[derive(Clone)]
+struct ReplyTo {
+    client: Client,
+    relays: Vec<String>,
+}
+
+impl ReplyTo {
+    async fn send(&self, builder: EventBuilder) -> Result<()> {
+        self.client.send_event_builder_to(&self.relays, builder).await?;
+        Ok(())
+    }
+}
+
+/// The `["param", "graph", <name>]` tag, if any
+fn requested_graph<'a>(tags: impl IntoIterator<Item = &'a [String]>) -> Option<&'a str> {
+    tags.into_iter()
+        .find(|tag| tag.len() >= 3 && tag[0] == "param" && tag[1] == "graph")
+        .map(|tag| tag[2].as_str())
+}
+
+/// Normalized `wss://` URLs from the NIP-90 `relays` tag, deduplicated and capped at `MAX_REPLY_RELAYS`
+fn requested_relays<'a>(tags: impl IntoIterator<Item = &'a [String]>) -> Vec<String> {
+    let mut relays: Vec<String> = Vec::new();
+    let urls = tags
+        .into_iter()
+        .filter(|tag| tag.first().is_some_and(|name| name == "relays"))
+        .flat_map(|tag| &tag[1..]);
+    for url in urls {
+        // Plain ws:// is refused so requests can't point the oracle at internal services
+        if !url.starts_with("wss://") {
+            continue;
+        }
+        let Ok(url) = Url::parse(url) else {
+            continue;
+        };
+        let url = url.to_string();
+        if !relays.contains(&url) {
+            relays.push(url);
+        }
+        if relays.len() == MAX_REPLY_RELAYS {
+            break;
+        }
+    }
+    relays
+}
+
+/// Why a request should be ignored: a NIP-40 `expiration` in the past, or `created_at` older than `max_age`
+fn stale_reason<'a>(
+    tags: impl IntoIterator<Item = &'a [String]>,
+    created_at: u64,
+    now: u64,
+    max_age: u64,
+) -> Option<&'static str> {
+    let expired = tags.into_iter().any(|tag| {
+        tag.len() >= 2 && tag[0] == "expiration" && tag[1].parse::<u64>().is_ok_and(|expiration| expiration <= now)
+    });
+    if expired {
+        Some("expired")
+    } else if now.saturating_sub(created_at) > max_age {
+        Some("stale")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    struct MockBackend;
+
+    #[async_trait]
+    impl LightningBackend for MockBackend {
+        async fn create_invoice(&self, _amount_msats: u64, _memo: &str, _expiry: Duration) -> Result<Invoice> {
+            Ok(Invoice { bolt11: "lnbc1".into(), payment_hash: "00".into() })
+        }
+
+        async fn is_paid(&self, _invoice: &Invoice) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn payments(free: &[&str]) -> Payments {
+        Payments {
+            backend: Arc::new(MockBackend),
+            price_msats: 10_000,
+            free_pubkeys: Arc::new(free.iter().map(|pk| pk.to_string()).collect()),
+            pending: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    #[test]
+    fn test_quote() {
+        let payments = payments(&["aa"]);
+        assert_eq!(payments.quote("bb", None, 1), Ok(Some(10_000)));
+        assert_eq!(payments.quote("bb", Some(20_000), 1), Ok(Some(10_000)));
+        assert!(payments.quote("bb", Some(5_000), 1).is_err());
+        // Batches are priced per target
+        assert!(payments.quote("bb", Some(20_000), 3).is_err());
+        assert_eq!(payments.quote("bb", None, 3), Ok(Some(30_000)));
+        // Whitelisted requesters are never charged
+        assert_eq!(payments.quote("AA", Some(1), 3), Ok(None));
+    }
+
+    #[test]
+    fn test_pending_invoices_are_bounded() {
+        let payments = payments(&[]);
+        let slots: Vec<_> = (0..MAX_PENDING_INVOICES).map(|_| payments.reserve().unwrap()).collect();
+        assert!(payments.reserve().is_none());
+
+        drop(slots);
+        assert!(payments.reserve().is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_await_payment() {
+        let invoice = Invoice { bolt11: "lnbc1".into(), payment_hash: "00".into() };
+        assert!(await_payment(&MockBackend, &invoice, Duration::from_secs(60)).await);
+    }
+
+    #[test]
+    fn test_stale_reason() {
+        let expiration = |ts: &str| vec![vec!["expiration".to_string(), ts.to_string()]];
+        let check = |tags: Vec<Vec<String>>, created_at| stale_reason(tags.iter().map(Vec::as_slice), created_at, 1000, 300);
+
+        assert_eq!(check(vec![], 900), None);
+        assert_eq!(check(vec![], 600), Some("stale"));
+        assert_eq!(check(expiration("1001"), 900), None);
+        assert_eq!(check(expiration("1000"), 900), Some("expired"));
+        // Unparseable expirations are ignored
+        assert_eq!(check(expiration("soon"), 900), None);
+    }
+
+    #[test]
+    fn test_requested_graph() {
+        let tags = [
+            vec!["param".to_string(), "max_hops".into(), "3".into()],
+            vec!["param".to_string(), "graph".into(), "test".into()],
+        ];
+        assert_eq!(requested_graph(tags.iter().map(Vec::as_slice)), Some("test"));
+        assert_eq!(requested_graph(tags[..1].iter().map(Vec::as_slice)), None);
+    }
+
+    #[test]
+    fn test_requested_relays() {
+        let tags = [
+            vec!["relays".to_string(), "wss://a.example".into(), "ws://internal:8080".into(), "wss://a.example".into()],
+            vec!["relays".to_string(), "wss://b.example".into(), "not a url".into()],
+            vec!["p".to_string(), "wss://c.example".into()],
+        ];
+        let relays = requested_relays(tags.iter().map(Vec::as_slice));
+        assert_eq!(relays.len(), 2);
+        assert!(relays[0].starts_with("wss://a.example"));
+        assert!(relays[1].starts_with("wss://b.example"));
+
+        let many: [Vec<String>; 1] =
+            [std::iter::once("relays".to_string()).chain((0..10).map(|i| format!("wss://r{}.example", i))).collect()];
+        assert_eq!(requested_relays(many.iter().map(Vec::as_slice)).len(), MAX_REPLY_RELAYS);
+    }
+
+    /// A free DVM on `relays`, answering from a graph where one pubkey follows another: the
+    /// service, its stats and a distance request between the two
+    fn e2e_service(relays: &[&crate::mock_relay::MockRelay]) -> (DvmService, Arc<DvmStats>, Event) {
+        let (alice, bob) = (Keys::generate().public_key().to_hex(), Keys::generate().public_key().to_hex());
+        let graph = Arc::new(WotGraph::new());
+        graph.update_follows(&alice, std::slice::from_ref(&bob), None, None);
+
+        let mut config = Config::from_env();
+        config.relays = relays.iter().map(|relay| relay.url().to_string()).collect();
+        config.dvm_price_msats = 0;
+        let config = Arc::new(config);
+        let stats = Arc::new(DvmStats::default());
+        let dvm = DvmService::new(
+            graph,
+            Arc::new(QueryCache::new(100, 60)),
+            Arc::new(PageRank::new()),
+            config.clone(),
+            Arc::new(AccessLists::from_config(&config)),
+            Arc::new(Resolver::new(false)),
+            stats.clone(),
+            Arc::default(),
+            Arc::new(Database::open(":memory:").unwrap()),
+            &Keys::generate().secret_key().to_secret_hex(),
+        )
+        .unwrap();
+
+        let tags = [Tag::parse(&["i", &alice, "text"]).unwrap(), Tag::parse(&["i", &bob, "text"]).unwrap()];
+        let request = EventBuilder::new(Kind::Custom(DVM_REQUEST_KIND), "", tags)
+            .to_event(&Keys::generate())
+            .unwrap();
+        (dvm, stats, request)
+    }
+
+    #[tokio::test]
+    async fn test_e2e_answers_distance_request_over_relay() {
+        use crate::mock_relay::MockRelay;
+        use serde_json::{json, Value};
+
+        let relay = MockRelay::start().await;
+        let (dvm, _, request) = e2e_service(&[&relay]);
+        let oracle = dvm.keys.public_key();
+        let task = tokio::spawn(async move { dvm.start().await });
+        assert!(relay.wait_for_subscription(Duration::from_secs(10)).await);
+
+        relay.publish_event(&request);
+
+        let filter = json!({
+            "kinds": [DVM_REQUEST_KIND + DVM_RESULT_KIND_OFFSET],
+            "#e": [request.id.to_hex()],
+        });
+        let result = relay
+            .wait_for_event(&filter, Duration::from_secs(10))
+            .await
+            .expect("no result published");
+        assert_eq!(result["pubkey"], oracle.to_hex());
+        let content: Value = serde_json::from_str(result["content"].as_str().unwrap()).unwrap();
+        assert_eq!(content["hops"], 1);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_duplicate_request_reuses_response() {
+        use crate::mock_relay::{eventually, MockRelay};
+        use serde_json::json;
+
+        let (first, second) = (MockRelay::start().await, MockRelay::start().await);
+        let (dvm, stats, request) = e2e_service(&[&first, &second]);
+        let task = tokio::spawn(async move { dvm.start().await });
+        assert!(first.wait_for_subscription(Duration::from_secs(10)).await);
+        assert!(second.wait_for_subscription(Duration::from_secs(10)).await);
+
+        let filter = json!({
+            "kinds": [DVM_REQUEST_KIND + DVM_RESULT_KIND_OFFSET],
+            "#e": [request.id.to_hex()],
+        });
+        first.publish_event(&request);
+        let result = first.wait_for_event(&filter, Duration::from_secs(10)).await.expect("no result published");
+
+        // The copy on the second relay gets the same signed response, without running the job again
+        second.publish_event(&request);
+        let copy = second.wait_for_event(&filter, Duration::from_secs(10)).await.expect("response not re-sent");
+        assert_eq!(copy["id"], result["id"]);
+        assert!(eventually(Duration::from_secs(1), || stats.snapshot().requests_processed == 1).await);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_search_profiles_gets_error_feedback() {
+        use crate::mock_relay::MockRelay;
+        use serde_json::json;
+
+        let relay = MockRelay::start().await;
+        let (dvm, _, _) = e2e_service(&[&relay]);
+        let task = tokio::spawn(async move { dvm.start().await });
+        assert!(relay.wait_for_subscription(Duration::from_secs(10)).await);
+
+        let tags = [Tag::parse(&["param", "search", "alice"]).unwrap()];
+        let request = EventBuilder::new(Kind::Custom(vertex::SEARCH_PROFILES_KIND), "", tags)
+            .to_event(&Keys::generate())
+            .unwrap();
+        relay.publish_event(&request);
+
+        let filter = json!({"kinds": [DVM_FEEDBACK_KIND], "#e": [request.id.to_hex()]});
+        let feedback = relay
+            .wait_for_event(&filter, Duration::from_secs(10))
+            .await
+            .expect("no feedback published");
+        let status = feedback["tags"].as_array().unwrap().iter().find(|tag| tag[0] == "status").unwrap();
+        assert_eq!(status[1], "error");
+        assert_eq!(status[3], "UNSUPPORTED_KIND");
+        task.abort();
+    }
+}
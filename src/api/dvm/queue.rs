@@ -0,0 +1,101 @@
+//! Bounded DVM job execution: a fixed number of jobs run at once and a bounded number wait.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub struct JobQueue {
+    slots: Arc<Semaphore>,
+    waiting: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+impl JobQueue {
+    /// `workers` jobs run in parallel; up to `capacity` more wait for a free worker
+    pub fn new(workers: usize, capacity: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(workers)),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            capacity,
+        }
+    }
+
+    /// Take a place in line, or None when the queue is full
+    pub fn enqueue(&self) -> Option<QueueTicket> {
+        let ahead = self.waiting.fetch_add(1, Ordering::AcqRel);
+        if ahead >= self.capacity {
+            self.waiting.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+        // Approximate: workers may free up between these two reads
+        let position = if ahead == 0 && self.slots.available_permits() > 0 { 0 } else { ahead + 1 };
+        Some(QueueTicket {
+            slots: self.slots.clone(),
+            waiting: self.waiting.clone(),
+            position,
+        })
+    }
+
+    /// Wait for a worker without taking a place in line; for jobs that must not be turned away
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        acquire(&self.slots).await
+    }
+}
+
+/// A place in the queue, released when dropped or once a worker is acquired
+pub struct QueueTicket {
+    slots: Arc<Semaphore>,
+    waiting: Arc<AtomicUsize>,
+    position: usize,
+}
+
+impl QueueTicket {
+    /// Jobs waiting ahead of this one when it was queued, counting itself; 0 if it runs immediately
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Wait for a worker; the job runs while the returned permit is held
+    pub async fn start(self) -> OwnedSemaphorePermit {
+        acquire(&self.slots).await
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        self.waiting.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+async fn acquire(slots: &Arc<Semaphore>) -> OwnedSemaphorePermit {
+    slots
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("job queue semaphore is never closed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_is_bounded() {
+        let queue = JobQueue::new(1, 2);
+
+        let first = queue.enqueue().unwrap();
+        assert_eq!(first.position(), 0);
+        let running = first.start().await;
+
+        let second = queue.enqueue().unwrap();
+        let third = queue.enqueue().unwrap();
+        assert_eq!((second.position(), third.position()), (1, 2));
+        assert!(queue.enqueue().is_none());
+
+        // Starting a waiting job frees its place in line
+        drop(running);
+        let _running = second.start().await;
+        assert_eq!(queue.waiting.load(Ordering::Acquire), 1);
+        assert!(queue.enqueue().is_some());
+    }
+}
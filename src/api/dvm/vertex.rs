@@ -0,0 +1,317 @@
+//! Request kinds used by other Web of Trust DVMs (Vertex-style), answered from the same graph
+//! so clients built against those services work unchanged.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::jobs::{query_key, Job};
+use super::DvmService;
+use crate::graph::{pagerank, Neighbors, NodeId, PageRank, WotGraph};
+use crate::types::Pubkey;
+
+pub const VERIFY_REPUTATION_KIND: u16 = 5312;
+pub const RECOMMEND_FOLLOWS_KIND: u16 = 5313;
+pub const SORT_AUTHORS_KIND: u16 = 5314;
+/// Subscribed to only to answer with an error: the graph holds no profile metadata to search
+pub const SEARCH_PROFILES_KIND: u16 = 5315;
+pub const VERTEX_KINDS: [u16; 4] =
+    [VERIFY_REPUTATION_KIND, RECOMMEND_FOLLOWS_KIND, SORT_AUTHORS_KIND, SEARCH_PROFILES_KIND];
+
+const LIMIT_DEFAULT: usize = 5;
+const LIMIT_MAX: usize = 100;
+const SORT_AUTHORS_MAX: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    GlobalPagerank,
+    PersonalizedPagerank,
+    FollowerCount,
+}
+
+impl Sort {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "globalPagerank" => Ok(Sort::GlobalPagerank),
+            "personalizedPagerank" => Ok(Sort::PersonalizedPagerank),
+            "followerCount" => Ok(Sort::FollowerCount),
+            _ => Err(format!("Unsupported sort: {}", s)),
+        }
+    }
+}
+
+/// Parse a Vertex-style request; `source` defaults to the requester, and `sort` to their
+/// stored scoring, then to the kind's own default
+pub fn parse_request<'a>(
+    kind: u16,
+    tags: impl IntoIterator<Item = &'a [String]>,
+    requester: &str,
+    default_sort: Option<Sort>,
+) -> Result<Job, String> {
+    let mut source = requester.to_string();
+    let mut sort = default_sort;
+    let mut limit = LIMIT_DEFAULT;
+    let mut targets: Vec<String> = Vec::new();
+
+    for tag in tags {
+        if tag.len() < 3 || tag[0] != "param" {
+            continue;
+        }
+        match tag[1].as_str() {
+            "source" => source = tag[2].clone(),
+            "target" => targets.push(tag[2].clone()),
+            "sort" => sort = Some(Sort::parse(&tag[2])?),
+            "limit" => {
+                limit = tag[2]
+                    .parse::<usize>()
+                    .map(|l| l.clamp(1, LIMIT_MAX))
+                    .unwrap_or(LIMIT_DEFAULT);
+            }
+            _ => {}
+        }
+    }
+
+    match kind {
+        VERIFY_REPUTATION_KIND => {
+            let [target] = <[String; 1]>::try_from(targets)
+                .map_err(|_| "Expected exactly one target".to_string())?;
+            Ok(Job::VerifyReputation {
+                target,
+                source,
+                sort: sort.unwrap_or(Sort::GlobalPagerank),
+                limit,
+            })
+        }
+        RECOMMEND_FOLLOWS_KIND => Ok(Job::RecommendFollows {
+            source,
+            sort: sort.unwrap_or(Sort::PersonalizedPagerank),
+            limit,
+        }),
+        SORT_AUTHORS_KIND => {
+            if targets.is_empty() {
+                return Err("Expected at least one target".to_string());
+            }
+            if targets.len() > SORT_AUTHORS_MAX {
+                return Err(format!("Maximum {} targets allowed", SORT_AUTHORS_MAX));
+            }
+            Ok(Job::SortAuthors {
+                source,
+                sort: sort.unwrap_or(Sort::PersonalizedPagerank),
+                targets,
+            })
+        }
+        _ => Err("Unsupported request kind".to_string()),
+    }
+}
+
+/// Score every node by `sort`. CPU-bound: call from a blocking thread.
+fn score_all(graph: &WotGraph, ranks: &PageRank, sort: Sort, source: Option<NodeId>) -> Arc<Vec<f64>> {
+    match (sort, source) {
+        (Sort::GlobalPagerank, _) => ranks.snapshot(),
+        (Sort::PersonalizedPagerank, Some(source)) => {
+            Arc::new(graph.with_adjacency(|follows, _| pagerank::personalized(follows, source)))
+        }
+        // A source outside the graph trusts no one
+        (Sort::PersonalizedPagerank, None) => Arc::new(Vec::new()),
+        (Sort::FollowerCount, _) => Arc::new(
+            graph.with_adjacency(|_, followers| followers.iter().map(|f| f.len() as f64).collect()),
+        ),
+    }
+}
+
+/// The `n` highest-scored pubkeys `source` does not already follow, best first.
+/// CPU-bound: call from a blocking thread.
+pub fn recommendations(graph: &WotGraph, ranks: &PageRank, sort: Sort, source: NodeId, n: usize) -> Vec<(Pubkey, f64)> {
+    let mut scores = Arc::unwrap_or_clone(score_all(graph, ranks, sort, Some(source)));
+    graph.with_adjacency(|follows, _| {
+        for &id in follows.get(source as usize).into_iter().flatten() {
+            if let Some(score) = scores.get_mut(id as usize) {
+                *score = 0.0;
+            }
+        }
+    });
+    pagerank::top_n(&scores, n, Some(source))
+        .into_iter()
+        .filter_map(|(id, score)| graph.pubkey(id).map(|pk| (pk, score)))
+        .collect()
+}
+
+fn score_of(scores: &[f64], id: Option<NodeId>) -> f64 {
+    id.and_then(|id| scores.get(id as usize).copied()).unwrap_or(0.0)
+}
+
+impl DvmService {
+    /// Target first (with follow counts), then its highest-scored followers
+    pub(super) async fn verify_reputation(
+        &self,
+        target: &str,
+        source: &str,
+        sort: Sort,
+        limit: usize,
+    ) -> Result<Value> {
+        let (target, source) = (query_key(target)?, query_key(source)?);
+        let (graph, ranks) = (Arc::clone(&self.graph), Arc::clone(&self.ranks));
+        let (target_id, source_id) = (graph.node_id(&target), graph.node_id(&source));
+
+        let (target_entry, followers) = tokio::task::spawn_blocking(move || {
+            let scores = score_all(&graph, &ranks, sort, source_id);
+            let (follows, mut followers) = match target_id {
+                Some(id) => graph.with_adjacency(|follows, followers| {
+                    let count = |adj: &Neighbors| adj.get(id as usize).map_or(0, |list| list.len());
+                    let top: Vec<(NodeId, f64)> = followers
+                        .get(id as usize)
+                        .map(|f| f.iter().map(|&f| (f, score_of(&scores, Some(f)))).collect())
+                        .unwrap_or_default();
+                    ((count(follows), count(followers)), top)
+                }),
+                None => ((0, 0), Vec::new()),
+            };
+            followers.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            // Extra headroom for entries dropped by the access lists
+            followers.truncate(limit * 2);
+
+            let target_entry = (score_of(&scores, target_id), follows);
+            let followers: Vec<(Pubkey, f64)> = followers
+                .into_iter()
+                .filter_map(|(id, score)| graph.pubkey(id).map(|pk| (pk, score)))
+                .collect();
+            (target_entry, followers)
+        })
+        .await
+        .context("Reputation task failed")?;
+
+        let ((rank, (follows, follower_count)), followers) = (target_entry, followers);
+        let mut entries = vec![json!({
+            "pubkey": target,
+            "rank": rank,
+            "follows": follows,
+            "followers": follower_count,
+        })];
+        entries.extend(
+            followers
+                .into_iter()
+                .filter(|(pk, _)| self.access.is_pubkey_allowed(&pk.to_hex()))
+                .take(limit)
+                .map(|(pk, rank)| json!({"pubkey": pk, "rank": rank})),
+        );
+        Ok(Value::Array(entries))
+    }
+
+    /// Highest-scored pubkeys the source does not already follow
+    pub(super) async fn recommend_follows(&self, source: &str, sort: Sort, limit: usize) -> Result<Value> {
+        let Some(source_id) = self.graph.node_id(&query_key(source)?) else {
+            return Ok(Value::Array(Vec::new()));
+        };
+
+        let (graph, ranks) = (Arc::clone(&self.graph), Arc::clone(&self.ranks));
+        // Extra headroom for entries dropped by the access lists
+        let recommended = tokio::task::spawn_blocking(move || recommendations(&graph, &ranks, sort, source_id, limit * 2))
+            .await
+            .context("Recommendation task failed")?;
+
+        Ok(Value::Array(
+            recommended
+                .into_iter()
+                .filter(|(pk, _)| self.access.is_pubkey_allowed(&pk.to_hex()))
+                .take(limit)
+                .map(|(pk, rank)| json!({"pubkey": pk, "rank": rank}))
+                .collect(),
+        ))
+    }
+
+    /// Targets ordered by score, best first
+    pub(super) async fn sort_authors(&self, source: &str, sort: Sort, targets: &[String]) -> Result<Value> {
+        let (graph, ranks) = (Arc::clone(&self.graph), Arc::clone(&self.ranks));
+        let source_id = graph.node_id(&query_key(source)?);
+        let targets = targets.iter().map(|pk| query_key(pk)).collect::<Result<Vec<_>>>()?;
+
+        let mut sorted = tokio::task::spawn_blocking(move || {
+            let scores = score_all(&graph, &ranks, sort, source_id);
+            targets
+                .into_iter()
+                .map(|pk| {
+                    let score = score_of(&scores, graph.node_id(&pk));
+                    (pk, score)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .context("Sort task failed")?;
+        sorted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        Ok(Value::Array(
+            sorted
+                .into_iter()
+                .map(|(pk, rank)| json!({"pubkey": pk, "rank": rank}))
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    fn parse(kind: u16, tags: &[&[&str]]) -> Result<Job, String> {
+        let tags: Vec<Vec<String>> = tags
+            .iter()
+            .map(|t| t.iter().map(|s| s.to_string()).collect())
+            .collect();
+        parse_request(kind, tags.iter().map(Vec::as_slice), A, None)
+    }
+
+    #[test]
+    fn test_parse_defaults_to_requester_source() {
+        let job = parse(VERIFY_REPUTATION_KIND, &[&["param", "target", B]]).unwrap();
+        assert_eq!(
+            job,
+            Job::VerifyReputation {
+                target: B.into(),
+                source: A.into(),
+                sort: Sort::GlobalPagerank,
+                limit: LIMIT_DEFAULT,
+            }
+        );
+
+        let job = parse(
+            RECOMMEND_FOLLOWS_KIND,
+            &[&["param", "source", B], &["param", "sort", "followerCount"], &["param", "limit", "1000"]],
+        )
+        .unwrap();
+        assert_eq!(
+            job,
+            Job::RecommendFollows { source: B.into(), sort: Sort::FollowerCount, limit: LIMIT_MAX }
+        );
+
+        // The requester's stored scoring replaces the kind's default, not an explicit sort
+        let param = |name: &str, value: &str| vec!["param".to_string(), name.to_string(), value.to_string()];
+        let stored = Some(Sort::FollowerCount);
+        let tags = [param("target", B)];
+        let job = parse_request(SORT_AUTHORS_KIND, tags.iter().map(Vec::as_slice), A, stored).unwrap();
+        assert_eq!(job, Job::SortAuthors { source: A.into(), sort: Sort::FollowerCount, targets: vec![B.into()] });
+        let tags = [param("target", B), param("sort", "globalPagerank")];
+        let job = parse_request(SORT_AUTHORS_KIND, tags.iter().map(Vec::as_slice), A, stored).unwrap();
+        assert_eq!(job, Job::SortAuthors { source: A.into(), sort: Sort::GlobalPagerank, targets: vec![B.into()] });
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(VERIFY_REPUTATION_KIND, &[]).is_err());
+        assert!(parse(SORT_AUTHORS_KIND, &[]).is_err());
+        assert!(parse(SORT_AUTHORS_KIND, &[&["param", "target", B], &["param", "sort", "nope"]]).is_err());
+    }
+
+    #[test]
+    fn test_follower_count_scores() {
+        let graph = WotGraph::new();
+        graph.update_follows(A, &[B.to_string()], None, None);
+        let scores = score_all(&graph, &PageRank::new(), Sort::FollowerCount, None);
+
+        assert_eq!(score_of(&scores, graph.node_id(&B.parse().unwrap())), 1.0);
+        assert_eq!(score_of(&scores, graph.node_id(&A.parse().unwrap())), 0.0);
+        assert_eq!(score_of(&scores, None), 0.0);
+    }
+}
@@ -0,0 +1,145 @@
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{ETAG, IF_NONE_MATCH, VARY},
+        HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::csv;
+use super::http::{AppState, GraphParam};
+use super::me::Caller;
+
+/// Routes whose responses change independently of the graph (metrics, liveness, the edge
+/// change log with its window ending now by default, unseeded samples)
+const UNCACHEABLE_PATHS: &[&str] = &["/health", "/stats", "/stats/popular", "/metrics", "/diff", "/diff/daily", "/outbox", "/sample"];
+
+/// Node metadata, anchor and bot scores, follow proofs, follower history and replication batches
+/// also change through persistence and background tasks; admin state, user settings, explorer
+/// profile names, the relay socket and the Swagger UI files don't come from the graph at all
+const UNCACHEABLE_PREFIXES: &[&str] = &[
+    "/node/",
+    "/score/",
+    "/botscore/",
+    "/proof/",
+    "/history/",
+    "/replication/",
+    "/admin/",
+    "/me/",
+    "/explore/",
+    "/relay",
+    "/docs",
+];
+
+fn is_cacheable(path: &str) -> bool {
+    !UNCACHEABLE_PATHS.contains(&path) && !UNCACHEABLE_PREFIXES.iter().any(|p| path.starts_with(p))
+}
+
+/// Distinguishes process lifetimes, since the graph epoch restarts from zero
+fn boot_id() -> u64 {
+    static BOOT_ID: OnceLock<u64> = OnceLock::new();
+    *BOOT_ID.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    })
+}
+
+/// Weak ETag derived from the graph epoch and the full request URI (path + query)
+pub fn compute_etag(epoch: u64, uri: &str) -> HeaderValue {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    boot_id().hash(&mut hasher);
+    epoch.hash(&mut hasher);
+    uri.hash(&mut hasher);
+    HeaderValue::from_str(&format!("W/\"{:016x}\"", hasher.finish())).unwrap()
+}
+
+/// Weak comparison of an If-None-Match header against our ETag
+fn if_none_match(header: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(header) = header.to_str() else {
+        return false;
+    };
+    let ours = etag.to_str().unwrap_or_default().trim_start_matches("W/");
+
+    header
+        .split(',')
+        .map(|t| t.trim())
+        .any(|t| t.trim_start_matches("W/") == ours)
+}
+
+/// Conditional GET support: attach an ETag to successful graph-derived responses
+/// and answer 304 Not Modified when the client already holds the current version.
+/// The handler always runs first, so validation, access lists and authorization apply
+/// and only a request that would have succeeded is answered with a 304.
+pub async fn conditional_get(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    // Responses to an authenticated caller also depend on their settings
+    if req.method() != Method::GET || !is_cacheable(req.uri().path()) || req.extensions().get::<Caller>().is_some() {
+        return next.run(req).await;
+    }
+
+    // Unknown graphs are rejected by the handler
+    let Ok(selected) = state.select_graph(GraphParam::from_uri(req.uri()).graph.as_deref()) else {
+        return next.run(req).await;
+    };
+
+    // Epoch read before computing: a concurrent update yields a stale tag, never a stale body
+    // CSV is another representation of the same URI
+    let mut key = req.uri().to_string();
+    if csv::accepts_csv(req.headers()) {
+        key.push_str(" csv");
+    }
+    let etag = compute_etag(selected.graph.epoch(), &key);
+    let not_modified = req.headers().get(IF_NONE_MATCH).is_some_and(|header| if_none_match(header, &etag));
+
+    let mut response = next.run(req).await;
+    if response.status() == StatusCode::OK && not_modified {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
+    }
+    if response.status() == StatusCode::OK {
+        response.headers_mut().insert(ETAG, etag);
+        response.headers_mut().append(VARY, HeaderValue::from_static("accept"));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etag_changes_with_epoch_and_query() {
+        let a = compute_etag(1, "/distance?from=a&to=b");
+        assert_eq!(a, compute_etag(1, "/distance?from=a&to=b"));
+        assert_ne!(a, compute_etag(2, "/distance?from=a&to=b"));
+        assert_ne!(a, compute_etag(1, "/distance?from=a&to=c"));
+    }
+
+    #[test]
+    fn test_non_graph_routes_uncacheable() {
+        assert!(is_cacheable("/distance"));
+        for path in ["/admin/access", "/admin/report", "/me/settings", "/botscore/a", "/explore/a", "/relay"] {
+            assert!(!is_cacheable(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = compute_etag(1, "/follows?pubkey=a");
+        let weak = etag.clone();
+        let strong = HeaderValue::from_str(etag.to_str().unwrap().trim_start_matches("W/")).unwrap();
+        let list = HeaderValue::from_str(&format!("\"other\", {}", etag.to_str().unwrap())).unwrap();
+
+        assert!(if_none_match(&weak, &etag));
+        assert!(if_none_match(&strong, &etag));
+        assert!(if_none_match(&list, &etag));
+        // `*` would match routes that fail for this request; only exact tags count
+        assert!(!if_none_match(&HeaderValue::from_static("*"), &etag));
+        assert!(!if_none_match(&HeaderValue::from_static("\"other\""), &etag));
+    }
+}
@@ -0,0 +1,374 @@
+//! Server-rendered HTML views of the graph, so people can look up a pubkey or the distance
+//! between two without a separate frontend. Pubkeys are shown with their profile names and
+//! link to each other; the JSON endpoints stay the API. The `explore` rate limit bucket keeps
+//! the pages, which fetch profiles from relays, from costing more than the API they wrap.
+
+use axum::{
+    extract::{Path, Query},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::access::SourceAccess;
+use super::http::{
+    default_max_hops, resolve_subject, validate_max_hops, AppState, ErrorResponse, GraphParam,
+    SelectedGraph,
+};
+use super::profiles::Profile;
+use crate::graph::{bfs, NodeId};
+use crate::report::escape;
+use crate::types::Pubkey;
+
+/// Follows and followers listed on a pubkey's page, highest PageRank first
+const LIST_LIMIT: usize = 20;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index))
+        .route("/distance", get(distance))
+        .route("/:pubkey", get(pubkey))
+}
+
+/// An error rendered as an HTML page with the status of its code
+pub struct ErrorPage(ErrorResponse);
+
+impl From<ErrorResponse> for ErrorPage {
+    fn from(err: ErrorResponse) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ErrorPage {
+    fn into_response(self) -> Response {
+        let body = format!("<p>{}</p>\n{}", escape(&self.0.error), search_form(""));
+        (self.0.status(), page("Error", &body)).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexParams {
+    /// Pubkey, npub or NIP-05 address to look up
+    pub q: Option<String>,
+}
+
+async fn index(
+    SelectedGraph(state): SelectedGraph,
+    Query(graph): Query<GraphParam>,
+    Query(params): Query<IndexParams>,
+) -> Result<Response, ErrorPage> {
+    let links = Links::new(&graph, HashMap::new());
+    if let Some(q) = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let pubkey = resolve_subject(&state, q).await?;
+        return Ok(Redirect::to(&links.pubkey_url(&pubkey.to_hex())).into_response());
+    }
+
+    let stats = state.graph.stats();
+    let body = format!(
+        "<p>{} pubkeys, {} follows</p>\n<h2>Look up a pubkey</h2>\n{}\n<h2>Distance</h2>\n{}",
+        stats.node_count,
+        stats.edge_count,
+        search_form(&links.graph_field()),
+        distance_form(&state, &links, "", "", default_max_hops()),
+    );
+    Ok(page("Explorer", &body).into_response())
+}
+
+async fn pubkey(
+    SelectedGraph(state): SelectedGraph,
+    Query(graph): Query<GraphParam>,
+    Path(input): Path<String>,
+) -> Result<Html<String>, ErrorPage> {
+    let key = resolve_subject(&state, &input).await?;
+    let Some(node_id) = state.graph.node_id(&key) else {
+        return Err(ErrorResponse::not_found().into());
+    };
+    let pubkey = key.to_hex();
+
+    let (follows, followers) = state.graph.with_adjacency(|follows, followers| {
+        let ids = |side: &crate::graph::Neighbors| side.get(node_id as usize).unwrap_or_default().to_vec();
+        (ids(follows), ids(followers))
+    });
+    let scores = state.ranks.snapshot();
+    let top = |ids: &[NodeId]| {
+        let mut ranked: Vec<(NodeId, f64)> =
+            ids.iter().map(|&id| (id, scores.get(id as usize).copied().unwrap_or(0.0))).collect();
+        ranked.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        let ranked: Vec<NodeId> = ranked.into_iter().map(|(id, _)| id).collect();
+        state
+            .graph
+            .resolve_pubkeys(&ranked)
+            .into_iter()
+            .map(String::from)
+            .filter(|pk| state.access.is_pubkey_allowed(pk))
+            .take(LIST_LIMIT)
+            .collect::<Vec<String>>()
+    };
+    let (top_follows, top_followers) = (top(&follows), top(&followers));
+
+    let mut shown = vec![pubkey.clone()];
+    shown.extend(top_follows.iter().chain(&top_followers).cloned());
+    let links = Links::new(&graph, state.profiles.get(&shown).await);
+    let profile = links.profiles.get(&pubkey).cloned().unwrap_or_default();
+
+    let mut body = String::new();
+    // Writing to a String cannot fail
+    let _ = write_pubkey(
+        &mut body,
+        &state,
+        &links,
+        &pubkey,
+        node_id,
+        &profile,
+        (&top_follows, &top_followers),
+    );
+    let title = profile.name.unwrap_or_else(|| short(&npub(&pubkey)));
+    Ok(page(&title, &body))
+}
+
+fn write_pubkey(
+    html: &mut String,
+    state: &AppState,
+    links: &Links,
+    pubkey: &str,
+    node_id: NodeId,
+    profile: &Profile,
+    (follows, followers): (&[String], &[String]),
+) -> std::fmt::Result {
+    if let Some(picture) = &profile.picture {
+        writeln!(html, "<img src=\"{}\" alt=\"\" width=\"96\" height=\"96\">", escape(picture))?;
+    }
+    writeln!(html, "<p><code>{}</code><br><code>{}</code></p>", npub(pubkey), pubkey)?;
+    if let Some(nip05) = &profile.nip05 {
+        writeln!(html, "<p>NIP-05: {}</p>", escape(nip05))?;
+    }
+
+    let (follow_count, follower_count) = state.graph.degree(node_id);
+    writeln!(html, "<table>")?;
+    for (label, value) in [
+        ("Follows", follow_count.to_string()),
+        ("Followers", follower_count.to_string()),
+        ("Mutual follows", state.graph.mutual_count(node_id).to_string()),
+        (
+            "PageRank percentile",
+            state.ranks.percentile(node_id).map_or_else(|| "-".to_string(), |p| format!("{:.1}", p)),
+        ),
+        (
+            "In the largest component",
+            match state.components.in_largest(node_id) {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "-",
+            }
+            .to_string(),
+        ),
+    ] {
+        writeln!(html, "<tr><th class=\"l\">{}</th><td>{}</td></tr>", label, value)?;
+    }
+    writeln!(html, "</table>")?;
+
+    for (heading, list, total) in [("Follows", follows, follow_count), ("Followers", followers, follower_count)] {
+        writeln!(html, "<h2>{} <small>(top {} of {} by PageRank)</small></h2>", heading, list.len(), total)?;
+        writeln!(html, "<ol>")?;
+        for pk in list {
+            writeln!(html, "<li>{}</li>", links.person(pk))?;
+        }
+        writeln!(html, "</ol>")?;
+    }
+
+    writeln!(html, "<h2>Distance from another pubkey</h2>")?;
+    writeln!(html, "{}", distance_form(state, links, "", &npub(pubkey), default_max_hops()))?;
+    writeln!(html, "<p><a href=\"/node/{}{}\">JSON</a></p>", pubkey, links.query('?'))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DistanceParams {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(default = "default_max_hops")]
+    pub max_hops: u8,
+}
+
+async fn distance(
+    SelectedGraph(state): SelectedGraph,
+    Query(graph): Query<GraphParam>,
+    sources: SourceAccess,
+    Query(params): Query<DistanceParams>,
+) -> Result<Html<String>, ErrorPage> {
+    let non_empty = |s: &Option<String>| s.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+    let (Some(from), Some(to)) = (non_empty(&params.from), non_empty(&params.to)) else {
+        let links = Links::new(&graph, HashMap::new());
+        let form = distance_form(
+            &state,
+            &links,
+            params.from.as_deref().unwrap_or_default(),
+            params.to.as_deref().unwrap_or_default(),
+            params.max_hops,
+        );
+        return Ok(page("Distance", &form));
+    };
+
+    let from_key = resolve_subject(&state, &from).await?;
+    let from = from_key.to_hex();
+    sources.check(&state.access, &from)?;
+    let to_key = resolve_subject(&state, &to).await?;
+    let to = to_key.to_hex();
+    validate_max_hops(&state.config, params.max_hops)?;
+    let (max_hops, _) = state.config.hop_policy().apply(&state.graph, &from_key, &to_key, params.max_hops);
+
+    let query = bfs::PathQuery {
+        from: from_key,
+        to: to_key,
+        max_hops,
+        min_followers: 0,
+    };
+    let path_graph = state.graph.clone();
+    let result = tokio::task::spawn_blocking(move || bfs::compute_path(&path_graph, &query))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    // The path starts after `from`
+    let path: Option<Vec<String>> = result
+        .path
+        .map(|hops| std::iter::once(from.clone()).chain(hops.into_iter().map(String::from)).collect());
+    let mut shown = vec![from.clone(), to.clone()];
+    shown.extend(path.iter().flatten().cloned());
+    shown.sort();
+    shown.dedup();
+    let links = Links::new(&graph, state.profiles.get(&shown).await);
+
+    let mut body = String::new();
+    let _ = write_distance(&mut body, &links, &from, &to, path.as_deref(), max_hops, result.truncated);
+    body.push_str(&distance_form(&state, &links, &npub(&from), &npub(&to), params.max_hops));
+    Ok(page("Distance", &body))
+}
+
+fn write_distance(
+    html: &mut String,
+    links: &Links,
+    from: &str,
+    to: &str,
+    path: Option<&[String]>,
+    max_hops: u8,
+    truncated: bool,
+) -> std::fmt::Result {
+    writeln!(html, "<p>From {} to {}</p>", links.person(from), links.person(to))?;
+    match path {
+        Some(path) => {
+            let hops = path.len() - 1;
+            writeln!(html, "<p><strong>{} hop{}</strong></p>\n<ol start=\"0\">", hops, if hops == 1 { "" } else { "s" })?;
+            for pk in path {
+                writeln!(html, "<li>{}</li>", links.person(pk))?;
+            }
+            writeln!(html, "</ol>")?;
+        }
+        None if truncated => writeln!(html, "<p>The search stopped before finding a path; try fewer hops.</p>")?,
+        None => writeln!(html, "<p>Not connected within {} hops.</p>", max_hops)?,
+    }
+    writeln!(
+        html,
+        "<p><a href=\"/distance?from={}&amp;to={}{}\">JSON</a></p>",
+        from,
+        to,
+        links.query('&')
+    )
+}
+
+/// Links between explorer pages, keeping the selected graph, with the names of the pubkeys
+/// on the page
+struct Links {
+    graph: Option<String>,
+    profiles: HashMap<String, Profile>,
+}
+
+impl Links {
+    fn new(graph: &GraphParam, profiles: HashMap<String, Profile>) -> Self {
+        Self { graph: graph.graph.clone(), profiles }
+    }
+
+    /// `graph` query parameter starting with `separator`, or nothing for the primary graph.
+    /// Graph names are `[a-z0-9_-]`, so they need no encoding
+    fn query(&self, separator: char) -> String {
+        match &self.graph {
+            Some(graph) => format!("{}graph={}", separator, escape(graph)),
+            None => String::new(),
+        }
+    }
+
+    /// Hidden form field carrying the selected graph
+    fn graph_field(&self) -> String {
+        match &self.graph {
+            Some(graph) => format!("<input type=\"hidden\" name=\"graph\" value=\"{}\">", escape(graph)),
+            None => String::new(),
+        }
+    }
+
+    fn pubkey_url(&self, pubkey: &str) -> String {
+        format!("/explore/{}{}", npub(pubkey), self.query('?'))
+    }
+
+    /// Link to a pubkey's page, labelled with its profile name when known
+    fn person(&self, pubkey: &str) -> String {
+        let npub = npub(pubkey);
+        let label = match self.profiles.get(pubkey).and_then(|profile| profile.name.as_deref()) {
+            Some(name) => format!("{} <code>{}</code>", escape(name), short(&npub)),
+            None => format!("<code>{}</code>", short(&npub)),
+        };
+        format!("<a href=\"{}\">{}</a>", self.pubkey_url(pubkey), label)
+    }
+}
+
+/// npub of a hex pubkey; anything else is shown as is
+fn npub(pubkey: &str) -> String {
+    Pubkey::parse(pubkey).map_or_else(|_| pubkey.to_string(), |pk| pk.to_bech32())
+}
+
+/// First and last characters of a long key
+fn short(key: &str) -> String {
+    match (key.get(..12), key.get(key.len().saturating_sub(6)..)) {
+        (Some(head), Some(tail)) if key.len() > 20 => format!("{}…{}", head, tail),
+        _ => key.to_string(),
+    }
+}
+
+fn search_form(graph_field: &str) -> String {
+    format!(
+        "<form action=\"/explore\">{}<input name=\"q\" size=\"64\" placeholder=\"npub, hex pubkey or NIP-05 address\"> \
+         <button>Look up</button></form>",
+        graph_field
+    )
+}
+
+fn distance_form(state: &AppState, links: &Links, from: &str, to: &str, max_hops: u8) -> String {
+    let mut options = String::new();
+    for hops in 1..=state.config.http_max_hops {
+        let selected = if hops == max_hops { " selected" } else { "" };
+        let _ = write!(options, "<option{}>{}</option>", selected, hops);
+    }
+    format!(
+        "<form action=\"/explore/distance\">{}<input name=\"from\" size=\"64\" placeholder=\"From\" value=\"{}\"><br>\
+         <input name=\"to\" size=\"64\" placeholder=\"To\" value=\"{}\"><br>\
+         <label>Max hops <select name=\"max_hops\">{}</select></label> <button>Find path</button></form>",
+        links.graph_field(),
+        escape(from),
+        escape(to),
+        options
+    )
+}
+
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{} - WoT Oracle</title>\n\
+         <meta name=\"viewport\" content=\"width=device-width\">\n\
+         <style>body{{font-family:sans-serif;margin:2em;max-width:60em}}table{{border-collapse:collapse;margin:0.5em 0 1.5em}}\
+         td,th{{border:1px solid #ccc;padding:2px 8px;text-align:right}}th.l{{text-align:left}}\
+         code{{font-size:0.85em}}img{{border-radius:50%}}li{{margin:2px 0}}</style></head><body>\n\
+         <p><a href=\"/explore\">WoT Oracle explorer</a></p>\n<h1>{}</h1>\n{}\n</body></html>\n",
+        escape(title),
+        escape(title),
+        body
+    ))
+}
@@ -0,0 +1,132 @@
+//! Sparse fieldsets: `fields=hops,mutual_follow` keeps only the listed top-level fields of
+//! each distance or path result, so high-volume callers don't pay for echoes they ignore.
+
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::IntoParams;
+
+use super::http::ErrorResponse;
+
+/// Top-level fields of a distance result, including the ones only some responses carry
+pub const DISTANCE_FIELDS: &[&str] = &[
+    "from",
+    "to",
+    "hops",
+    "path_count",
+    "mutual_follow",
+    "bridges",
+    "bridge_path_counts",
+    "truncated",
+    "computed_at",
+    "graph_epoch",
+    "cached",
+    "adaptive_hops",
+    "weighted_path_count",
+    "attestation",
+];
+
+pub const PATH_FIELDS: &[&str] = &["from", "to", "path", "truncated", "adaptive_hops"];
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FieldsParam {
+    /// Comma-separated fields to return per result, e.g. `hops,mutual_follow` (default: all)
+    pub fields: Option<String>,
+}
+
+impl FieldsParam {
+    /// The selection, with every name checked against `allowed`
+    pub fn parse(&self, allowed: &[&str]) -> Result<Fields, ErrorResponse> {
+        let names = self.fields.as_deref().unwrap_or_default();
+        let mut selected: Vec<String> = Vec::new();
+        for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            if !allowed.contains(&name) {
+                return Err(ErrorResponse::invalid(
+                    "INVALID_FIELDS",
+                    format!("Unknown field '{}', expected any of: {}", name, allowed.join(", ")),
+                ));
+            }
+            if !selected.iter().any(|s| s == name) {
+                selected.push(name.to_string());
+            }
+        }
+        Ok(Fields(selected))
+    }
+}
+
+/// A validated field selection; empty keeps every field
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields(Vec<String>);
+
+impl Fields {
+    pub fn is_all(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `value` as JSON with only the selected fields
+    pub fn shape<T: Serialize>(&self, value: &T) -> serde_json::Result<Value> {
+        let mut value = serde_json::to_value(value)?;
+        if let (false, Value::Object(object)) = (self.is_all(), &mut value) {
+            object.retain(|key, _| self.0.contains(key));
+        }
+        Ok(value)
+    }
+
+    /// A JSON response with only the selected fields of `value`
+    pub fn respond<T: Serialize>(&self, value: T) -> Result<Response, ErrorResponse> {
+        if self.is_all() {
+            return Ok(Json(value).into_response());
+        }
+        let shaped = self.shape(&value).map_err(|e| ErrorResponse::internal(e.to_string()))?;
+        Ok(Json(shaped).into_response())
+    }
+
+    /// `value` serialized with only the selected fields
+    /// The selected field names, or all of `allowed`, as CSV columns
+    pub fn columns<'a>(&'a self, allowed: &[&'a str]) -> Vec<&'a str> {
+        match self.is_all() {
+            true => allowed.to_vec(),
+            false => self.0.iter().map(String::as_str).collect(),
+        }
+    }
+
+    pub fn to_json<T: Serialize>(&self, value: &T) -> serde_json::Result<String> {
+        match self.is_all() {
+            true => serde_json::to_string(value),
+            false => serde_json::to_string(&self.shape(value)?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn param(fields: &str) -> FieldsParam {
+        FieldsParam { fields: Some(fields.to_string()) }
+    }
+
+    #[test]
+    fn test_parse_and_shape() {
+        let result = json!({"from": "a", "to": "b", "hops": 2, "path_count": 3, "mutual_follow": false});
+
+        let fields = param("hops, mutual_follow,hops").parse(DISTANCE_FIELDS).unwrap();
+        assert_eq!(fields.shape(&result).unwrap(), json!({"hops": 2, "mutual_follow": false}));
+        assert_eq!(fields.to_json(&result).unwrap(), r#"{"hops":2,"mutual_follow":false}"#);
+
+        // Absent or empty keeps everything; fields a result lacks are simply not there
+        assert!(FieldsParam::default().parse(DISTANCE_FIELDS).unwrap().is_all());
+        assert_eq!(param("").parse(DISTANCE_FIELDS).unwrap().shape(&result).unwrap(), result);
+        let bridges = param("bridges").parse(DISTANCE_FIELDS).unwrap();
+        assert_eq!(bridges.shape(&result).unwrap(), json!({}));
+
+        let err = param("hops,path").parse(DISTANCE_FIELDS).unwrap_err();
+        assert_eq!(err.code, "INVALID_FIELDS");
+        assert!(param("path").parse(PATH_FIELDS).is_ok());
+    }
+}
@@ -0,0 +1,265 @@
+//! Follow recommendations as a NIP-51 follow set (kind 30000), so clients can show or apply
+//! them without knowing the oracle's API. The oracle signs the set and publishes it to relays
+//! the user picks, or hands it back unsigned for the user to sign and publish themselves.
+
+use axum::{extract::State, Extension, Json};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use super::access::SourceAccess;
+use super::dvm::{self, Sort};
+use super::http::{AppState, ErrorResponse};
+use super::me::{require_caller, Caller};
+use crate::error::OracleError;
+use crate::types::Pubkey;
+
+/// Parameterized replaceable: one set per `d` tag, replaced on each publication
+pub const FOLLOW_SET_KIND: u16 = 30000;
+
+const D_TAG: &str = "wot-oracle-recommendations";
+const TITLE_DEFAULT: &str = "Recommended follows";
+const LIMIT_DEFAULT: usize = 20;
+const LIMIT_MAX: usize = 100;
+const RELAYS_MAX: usize = 10;
+/// How long publishing waits for relays to connect and acknowledge the event
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PublishRecommendationsRequest {
+    /// Recommendations in the set (1-100, default 20)
+    pub limit: Option<usize>,
+    /// `globalPagerank`, `personalizedPagerank` or `followerCount`; defaults to the stored
+    /// `scoring` setting, then `personalizedPagerank`
+    pub sort: Option<String>,
+    /// `oracle` (default): the oracle signs and publishes to `relays`. `user`: the event is
+    /// returned unsigned, authored by the caller
+    pub signer: Option<String>,
+    /// Relays to publish to when the oracle signs (1-10 `ws://` or `wss://` URLs)
+    #[serde(default)]
+    pub relays: Vec<String>,
+    /// `title` tag of the set
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PublishRecommendationsResponse {
+    /// The follow set: signed by the oracle, or unsigned (no `sig`) for the caller to sign
+    #[schema(value_type = Object)]
+    pub event: serde_json::Value,
+    /// Recommended pubkeys in the order of the event's `p` tags, best first
+    pub recommendations: Vec<Recommendation>,
+    /// Relays that accepted the event; absent for `signer=user`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<Vec<String>>,
+    /// Relays that rejected the event or could not be reached, with the reason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed: Option<Vec<FailedRelay>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Recommendation {
+    pub pubkey: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FailedRelay {
+    pub url: String,
+    pub error: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/recommendations/publish",
+    tag = "settings",
+    security(("nip98" = [])),
+    request_body = PublishRecommendationsRequest,
+    responses(
+        (status = 200, description = "Follow set of the caller's top recommendations, published or unsigned", body = PublishRecommendationsResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid NIP-98 authorization", body = ErrorResponse),
+        (status = 403, description = "Caller excluded by access lists, or unregistered with RESTRICT_SOURCES", body = ErrorResponse),
+        (status = 404, description = "Caller not in the graph", body = ErrorResponse),
+        (status = 503, description = "signer=oracle without an oracle key (DVM_PRIVATE_KEY)", body = ErrorResponse)
+    )
+)]
+pub async fn publish_recommendations(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    sources: SourceAccess,
+    body: Option<Json<PublishRecommendationsRequest>>,
+) -> Result<Json<PublishRecommendationsResponse>, ErrorResponse> {
+    let caller = require_caller(caller)?;
+    let request = body.map(|Json(body)| body).unwrap_or_default();
+    if !state.access.is_pubkey_allowed(&caller.pubkey) {
+        return Err(ErrorResponse::pubkey_denied());
+    }
+    sources.check(&state.access, &caller.pubkey)?;
+
+    let limit = request.limit.unwrap_or(LIMIT_DEFAULT);
+    if !(1..=LIMIT_MAX).contains(&limit) {
+        return Err(ErrorResponse::invalid("INVALID_LIMIT", format!("limit must be between 1 and {}", LIMIT_MAX)));
+    }
+    let sort = match request.sort.as_deref().or(caller.settings.scoring.as_deref()) {
+        Some(sort) => Sort::parse(sort).map_err(|error| ErrorResponse::invalid("INVALID_SORT", error))?,
+        None => Sort::PersonalizedPagerank,
+    };
+    let oracle_keys = match request.signer.as_deref() {
+        None | Some("oracle") => {
+            let keys = state.oracle_keys.clone().ok_or_else(|| OracleError::Disabled {
+                code: "NO_ORACLE_KEY",
+                message: "The oracle has no signing key; use signer=user".to_string(),
+            })?;
+            validate_relays(&request.relays)?;
+            Some(keys)
+        }
+        Some("user") => None,
+        Some(_) => {
+            return Err(ErrorResponse::invalid("INVALID_SIGNER", "signer must be oracle or user"))
+        }
+    };
+    let Some(source_id) = Pubkey::parse(&caller.pubkey).ok().and_then(|pubkey| state.graph.node_id(&pubkey)) else {
+        return Err(ErrorResponse::not_found());
+    };
+
+    let (graph, ranks) = (state.graph.clone(), state.ranks.clone());
+    // Extra headroom for entries dropped by the access lists and mute list
+    let recommended = tokio::task::spawn_blocking(move || dvm::recommendations(&graph, &ranks, sort, source_id, limit * 2))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+    let recommendations: Vec<Recommendation> = recommended
+        .into_iter()
+        .map(|(pk, score)| (pk.to_hex(), score))
+        .filter(|(pk, _)| state.access.is_pubkey_allowed(pk) && !caller.muted.contains(pk))
+        .take(limit)
+        .map(|(pubkey, score)| Recommendation { pubkey, score })
+        .collect();
+
+    let title = request.title.as_deref().unwrap_or(TITLE_DEFAULT);
+    let Some(keys) = oracle_keys else {
+        let user = PublicKey::from_hex(&caller.pubkey).map_err(|e| ErrorResponse::internal(e.to_string()))?;
+        let mut unsigned = follow_set(D_TAG, title, &recommendations)?.to_unsigned_event(user);
+        unsigned.ensure_id();
+        return Ok(Json(PublishRecommendationsResponse {
+            event: serde_json::to_value(&unsigned).map_err(|e| ErrorResponse::internal(e.to_string()))?,
+            recommendations,
+            published: None,
+            failed: None,
+        }));
+    };
+
+    // The oracle keeps one set per user, so the d tag names who it is for; a p tag would
+    // make the user a member of their own set
+    let event = follow_set(&format!("{}:{}", D_TAG, caller.pubkey), title, &recommendations)?
+        .to_event(&keys)
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+    let (published, failed) = publish(&keys, &request.relays, event.clone()).await;
+    info!(
+        "Published {} recommendations for {} to {} of {} relays",
+        recommendations.len(),
+        caller.pubkey,
+        published.len(),
+        request.relays.len()
+    );
+
+    Ok(Json(PublishRecommendationsResponse {
+        event: serde_json::to_value(&event).map_err(|e| ErrorResponse::internal(e.to_string()))?,
+        recommendations,
+        published: Some(published),
+        failed: Some(failed),
+    }))
+}
+
+/// 1 to `RELAYS_MAX` websocket URLs
+fn validate_relays(relays: &[String]) -> Result<(), ErrorResponse> {
+    let invalid = |error: String| ErrorResponse::invalid("INVALID_RELAYS", error);
+    if relays.is_empty() || relays.len() > RELAYS_MAX {
+        return Err(invalid(format!("relays must list between 1 and {} relay URLs", RELAYS_MAX)));
+    }
+    for relay in relays {
+        match Url::parse(relay) {
+            Ok(url) if matches!(url.scheme(), "ws" | "wss") && url.host().is_some() => {}
+            _ => return Err(invalid(format!("Invalid relay URL: {}", relay))),
+        }
+    }
+    Ok(())
+}
+
+/// Kind 30000 set with a `p` tag per recommendation, best first
+fn follow_set(d_tag: &str, title: &str, recommendations: &[Recommendation]) -> Result<EventBuilder, ErrorResponse> {
+    let parse = |tag: &[&str]| Tag::parse(tag).map_err(|e| ErrorResponse::internal(e.to_string()));
+    let mut tags = vec![
+        parse(&["d", d_tag])?,
+        parse(&["title", title])?,
+        parse(&["alt", "Follow set: Web of Trust recommendations"])?,
+    ];
+    for recommendation in recommendations {
+        tags.push(parse(&["p", &recommendation.pubkey])?);
+    }
+    Ok(EventBuilder::new(Kind::Custom(FOLLOW_SET_KIND), "", tags))
+}
+
+/// Send `event` to each relay from a short-lived client; returns the relays that accepted it
+/// and those that did not, with the reason
+async fn publish(keys: &Keys, relays: &[String], event: Event) -> (Vec<String>, Vec<FailedRelay>) {
+    let client = Client::new(keys);
+    let mut failed = Vec::new();
+    let mut added = Vec::new();
+    for relay in relays {
+        match client.add_relay(relay.as_str()).await {
+            Ok(_) => added.push(relay.clone()),
+            Err(e) => failed.push(FailedRelay { url: relay.clone(), error: e.to_string() }),
+        }
+    }
+    client.connect_with_timeout(PUBLISH_TIMEOUT).await;
+
+    let mut published = Vec::new();
+    match tokio::time::timeout(PUBLISH_TIMEOUT, client.send_event_to(&added, event)).await {
+        Ok(Ok(output)) => {
+            published.extend(output.success.iter().map(|url| url.to_string()));
+            failed.extend(output.failed.into_iter().map(|(url, error)| FailedRelay {
+                url: url.to_string(),
+                error: error.unwrap_or_else(|| "rejected".to_string()),
+            }));
+        }
+        Ok(Err(e)) => failed.extend(added.into_iter().map(|url| FailedRelay { url, error: e.to_string() })),
+        Err(_) => failed.extend(added.into_iter().map(|url| FailedRelay { url, error: "timed out".to_string() })),
+    }
+    if let Err(e) = client.shutdown().await {
+        warn!("Failed to shut down follow set client: {}", e);
+    }
+    published.sort();
+    failed.sort_by(|a, b| a.url.cmp(&b.url));
+    (published, failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_relays() {
+        assert!(validate_relays(&["wss://relay.example.com".to_string()]).is_ok());
+        assert!(validate_relays(&[]).is_err());
+        assert!(validate_relays(&["https://relay.example.com".to_string()]).is_err());
+        assert!(validate_relays(&["not a url".to_string()]).is_err());
+        assert!(validate_relays(&vec!["wss://relay.example.com".to_string(); RELAYS_MAX + 1]).is_err());
+    }
+
+    #[test]
+    fn test_follow_set() {
+        let recommendations = vec![
+            Recommendation { pubkey: "b".repeat(64), score: 0.6 },
+            Recommendation { pubkey: "c".repeat(64), score: 0.4 },
+        ];
+        let event = follow_set(D_TAG, TITLE_DEFAULT, &recommendations).unwrap().to_event(&Keys::generate()).unwrap();
+        assert_eq!(event.kind, Kind::Custom(FOLLOW_SET_KIND));
+        let tags: Vec<Vec<String>> = event.tags.iter().map(|tag| tag.as_slice().to_vec()).collect();
+        assert_eq!(tags[0], vec!["d".to_string(), D_TAG.to_string()]);
+        assert_eq!(tags[3], vec!["p".to_string(), "b".repeat(64)]);
+        assert_eq!(tags[4], vec!["p".to_string(), "c".repeat(64)]);
+    }
+}
@@ -0,0 +1,286 @@
+use async_graphql::{
+    http::GraphiQLSource, Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object,
+    Schema, SimpleObject,
+};
+use axum::{response::Html, Extension, Json};
+
+use super::access::SourceAccess;
+use super::http::{adapt_max_hops, cached_distance, validate_max_hops, validate_subject, AppState, DistanceQueryParams, ErrorResponse};
+use crate::config::MAX_HOPS_DEFAULT;
+use crate::graph::{bfs, NodeId};
+use crate::types::Pubkey;
+
+pub type OracleSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+const LIST_LIMIT_DEFAULT: usize = 100;
+const LIST_LIMIT_MAX: usize = 1000;
+
+// Follow lists multiply cost by their limit, so nested lists are bounded too
+const MAX_DEPTH: usize = 8;
+const MAX_COMPLEXITY: usize = 20_000;
+
+pub fn build_schema(state: AppState) -> OracleSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(state)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+fn gql_error(e: ErrorResponse) -> async_graphql::Error {
+    async_graphql::Error::new(e.error).extend_with(|_, ext| ext.set("code", e.code))
+}
+
+fn list_limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(LIST_LIMIT_DEFAULT).min(LIST_LIMIT_MAX)
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A pubkey in the follow graph, or null if it has never been seen
+    async fn node(&self, ctx: &Context<'_>, pubkey: String) -> async_graphql::Result<Option<Node>> {
+        let state = ctx.data_unchecked::<AppState>();
+        let pubkey = validate_subject(state, &pubkey).map_err(gql_error)?;
+        Ok(state.graph.node_id(&pubkey).map(|id| Node { id, pubkey }))
+    }
+
+    /// Shortest follow distance between two pubkeys
+    async fn distance(
+        &self,
+        ctx: &Context<'_>,
+        from: String,
+        to: String,
+        max_hops: Option<u8>,
+        #[graphql(default)] include_bridges: bool,
+    ) -> async_graphql::Result<Distance> {
+        let state = ctx.data_unchecked::<AppState>();
+        let mut params = DistanceQueryParams {
+            from,
+            to,
+            max_hops: max_hops.unwrap_or(MAX_HOPS_DEFAULT),
+            include_bridges,
+            bypass_cache: false,
+            reverse: false,
+            max_bridges: None,
+            min_followers: 0,
+            path_weighting: None,
+        };
+        validate_subject(state, &params.from).map_err(gql_error)?;
+        validate_subject(state, &params.to).map_err(gql_error)?;
+        let sources = ctx.data_opt::<SourceAccess>().copied().unwrap_or_default();
+        sources.check(&state.access, &params.from).map_err(gql_error)?;
+        validate_max_hops(&state.config, params.max_hops).map_err(gql_error)?;
+        adapt_max_hops(state, &mut params);
+
+        let result = cached_distance(state, &params).await.map_err(gql_error)?;
+        Ok(result.into())
+    }
+
+    /// Pubkeys followed by both `from` and `to`
+    async fn common_follows(
+        &self,
+        ctx: &Context<'_>,
+        from: String,
+        to: String,
+    ) -> async_graphql::Result<Vec<String>> {
+        let state = ctx.data_unchecked::<AppState>();
+        let from = validate_subject(state, &from).map_err(gql_error)?;
+        let to = validate_subject(state, &to).map_err(gql_error)?;
+
+        let from_follows: std::collections::HashSet<Pubkey> = state
+            .graph
+            .get_follows(&from)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        Ok(state
+            .graph
+            .get_follows(&to)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|f| from_follows.contains(f))
+            .map(String::from)
+            .collect())
+    }
+}
+
+pub struct Node {
+    id: NodeId,
+    pubkey: Pubkey,
+}
+
+impl Node {
+    fn resolve(state: &AppState, pubkeys: Vec<Pubkey>, limit: Option<usize>) -> Vec<Node> {
+        pubkeys
+            .into_iter()
+            .take(list_limit(limit))
+            .filter_map(|pubkey| state.graph.node_id(&pubkey).map(|id| Node { id, pubkey }))
+            .collect()
+    }
+}
+
+#[Object]
+impl Node {
+    async fn pubkey(&self) -> String {
+        self.pubkey.to_hex()
+    }
+
+    #[graphql(complexity = "list_limit(limit) * child_complexity")]
+    async fn follows(&self, ctx: &Context<'_>, limit: Option<usize>) -> Vec<Node> {
+        let state = ctx.data_unchecked::<AppState>();
+        Node::resolve(state, state.graph.get_follows(&self.pubkey).unwrap_or_default(), limit)
+    }
+
+    #[graphql(complexity = "list_limit(limit) * child_complexity")]
+    async fn followers(&self, ctx: &Context<'_>, limit: Option<usize>) -> Vec<Node> {
+        let state = ctx.data_unchecked::<AppState>();
+        Node::resolve(state, state.graph.get_followers(&self.pubkey).unwrap_or_default(), limit)
+    }
+
+    async fn follow_count(&self, ctx: &Context<'_>) -> usize {
+        let state = ctx.data_unchecked::<AppState>();
+        state.graph.with_adjacency(|follows, _| follows.get(self.id as usize).map_or(0, |list| list.len()))
+    }
+
+    async fn follower_count(&self, ctx: &Context<'_>) -> usize {
+        let state = ctx.data_unchecked::<AppState>();
+        state.graph.with_adjacency(|_, followers| followers.get(self.id as usize).map_or(0, |list| list.len()))
+    }
+
+    /// Global PageRank (scores sum to 1); null until the next background refresh
+    async fn rank(&self, ctx: &Context<'_>) -> Option<f64> {
+        ctx.data_unchecked::<AppState>().ranks.score(self.id)
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Distance {
+    from: String,
+    to: String,
+    hops: Option<u32>,
+    path_count: u64,
+    mutual_follow: bool,
+    bridges: Option<Vec<String>>,
+}
+
+impl From<bfs::DistanceResult> for Distance {
+    fn from(r: bfs::DistanceResult) -> Self {
+        Self {
+            from: r.from.to_string(),
+            to: r.to.to_string(),
+            hops: r.hops,
+            path_count: r.path_count,
+            mutual_follow: r.mutual_follow,
+            bridges: r.bridges.map(|b| b.iter().map(|pk| pk.to_string()).collect()),
+        }
+    }
+}
+
+pub async fn graphql_handler(
+    Extension(schema): Extension<OracleSchema>,
+    sources: SourceAccess,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request.data(sources)).await)
+}
+
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::access::AccessLists;
+    use crate::api::identity::Resolver;
+    use crate::api::ratelimit::RateLimits;
+    use crate::cache::QueryCache;
+    use crate::config::Config;
+    use crate::db::Database;
+    use crate::graph::{PageRank, WotGraph};
+    use std::sync::Arc;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+    const C: &str = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+
+    fn create_test_schema() -> OracleSchema {
+        let graph = Arc::new(WotGraph::new());
+        graph.update_follows(A, &[B.to_string(), C.to_string()], None, None);
+        graph.update_follows(B, &[C.to_string()], None, None);
+
+        let config = Arc::new(Config::from_env());
+        let ranks = Arc::new(PageRank::new());
+        ranks.refresh(&graph);
+        build_schema(AppState {
+            graph,
+            cache: Arc::new(QueryCache::new(config.cache_size, config.cache_ttl_secs)),
+            rate_limits: Arc::new(RateLimits::new(60, 60)),
+            access: Arc::new(AccessLists::from_config(&config)),
+            config,
+            ranks,
+            components: Arc::default(),
+            anchors: Arc::default(),
+            popular_anchors: Arc::default(),
+            popularity: Arc::default(),
+            sightings: Arc::default(),
+            botscores: Arc::default(),
+            persistence: Arc::default(),
+            jobs: Arc::default(),
+            commitments: Arc::default(),
+            db: Arc::new(Database::open(":memory:").unwrap()),
+            oracle_keys: None,
+            resolver: Arc::new(Resolver::new(false)),
+            profiles: Arc::default(),
+            dvm_stats: Arc::default(),
+            watchlist: Arc::default(),
+            ingestion_pause: Arc::default(),
+            namespaces: Arc::default(),
+            diagnostics: Arc::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_composite_query() {
+        let schema = create_test_schema();
+        let query = format!(
+            r#"{{
+                distance(from: "{A}", to: "{C}") {{ hops mutualFollow }}
+                commonFollows(from: "{A}", to: "{B}")
+                node(pubkey: "{C}") {{ followerCount rank followers(limit: 1) {{ pubkey }} }}
+            }}"#
+        );
+
+        let response = schema.execute(query).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["distance"]["hops"], 1);
+        assert_eq!(data["commonFollows"], serde_json::json!([C]));
+        assert_eq!(data["node"]["followerCount"], 2);
+        assert!(data["node"]["rank"].as_f64().unwrap() > 0.0);
+        assert_eq!(data["node"]["followers"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_pubkey_error_code() {
+        let schema = create_test_schema();
+        let response = schema.execute(r#"{ node(pubkey: "nope") { pubkey } }"#).await;
+
+        assert_eq!(response.errors.len(), 1);
+        let code = response.errors[0].extensions.as_ref().unwrap().get("code");
+        assert_eq!(code, Some(&async_graphql::Value::from("INVALID_PUBKEY")));
+    }
+
+    #[tokio::test]
+    async fn test_nested_lists_bounded_by_complexity() {
+        let schema = create_test_schema();
+        let query = format!(
+            r#"{{ node(pubkey: "{A}") {{ follows(limit: 1000) {{ followers(limit: 1000) {{ pubkey }} }} }} }}"#
+        );
+        let response = schema.execute(query).await;
+        assert!(!response.errors.is_empty());
+    }
+}
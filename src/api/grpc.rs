@@ -0,0 +1,366 @@
+use axum::http::StatusCode;
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Code, Request, Response, Status};
+use tracing::info;
+
+use super::access::{SourceAccess, API_KEY_HEADER};
+use super::http::{adapt_max_hops, cached_distance, validate_max_hops, validate_subject, AppState, DistanceQueryParams, ErrorResponse};
+use super::ratelimit::{self, Bucket, COST_LIGHT, COST_PATH, COST_QUERY};
+use crate::config::{Config, MAX_HOPS_DEFAULT};
+use crate::graph::bfs;
+
+pub mod proto {
+    tonic::include_proto!("wot_oracle.v1");
+}
+
+use proto::wot_oracle_server::{WotOracle, WotOracleServer};
+
+/// Batches stream their results, so they can be much larger than over HTTP
+pub const GRPC_BATCH_TARGETS_MAX: usize = 10_000;
+
+// Results buffered per batch stream before backpressure kicks in
+const BATCH_STREAM_BUFFER: usize = 64;
+
+impl From<ErrorResponse> for Status {
+    fn from(e: ErrorResponse) -> Self {
+        let code = match e.status() {
+            StatusCode::INTERNAL_SERVER_ERROR => Code::Internal,
+            StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+            StatusCode::FORBIDDEN => Code::PermissionDenied,
+            StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            StatusCode::NOT_FOUND => Code::NotFound,
+            StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+            StatusCode::CONFLICT => Code::FailedPrecondition,
+            _ => Code::InvalidArgument,
+        };
+        Status::new(code, format!("{}: {}", e.code, e.error))
+    }
+}
+
+impl From<bfs::DistanceResult> for proto::DistanceResult {
+    fn from(r: bfs::DistanceResult) -> Self {
+        Self {
+            from: r.from.to_string(),
+            to: r.to.to_string(),
+            hops: r.hops,
+            path_count: r.path_count,
+            mutual_follow: r.mutual_follow,
+            bridges: r
+                .bridges
+                .map(|b| b.iter().map(|pk| pk.to_string()).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn max_hops(config: &Config, requested: Option<u32>) -> Result<u8, ErrorResponse> {
+    // Out-of-range values saturate so validation reports them
+    let max_hops = requested.map_or(MAX_HOPS_DEFAULT, |h| u8::try_from(h).unwrap_or(u8::MAX));
+    validate_max_hops(config, max_hops)?;
+    Ok(max_hops)
+}
+
+pub struct GrpcService {
+    state: AppState,
+}
+
+impl GrpcService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Apply the HTTP API's IP access lists and read bucket to a call
+    #[allow(clippy::result_large_err)] // Status is what tonic handlers return anyway
+    fn admit<T>(&self, request: &Request<T>, cost: u32) -> Result<(), Status> {
+        let Some(ip) = request.remote_addr().map(|addr| addr.ip()) else {
+            return Ok(());
+        };
+        if !self.state.access.is_ip_allowed(ip) {
+            return Err(ErrorResponse::ip_denied().into());
+        }
+
+        let status = self.state.rate_limits.check(Bucket::Read, ip, cost);
+        match status.retry_after {
+            Some(wait) => {
+                let mut err: Status = ErrorResponse::rate_limited().into();
+                err.metadata_mut()
+                    .insert("retry-after", ratelimit::retry_after_secs(wait).into());
+                Err(err)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Sources the call may query from, given its `x-api-key` metadata
+    fn sources<T>(&self, request: &Request<T>) -> SourceAccess {
+        let api_key = request.metadata().get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+        SourceAccess::new(api_key, &self.state.access)
+    }
+}
+
+#[tonic::async_trait]
+impl WotOracle for GrpcService {
+    async fn get_distance(
+        &self,
+        request: Request<proto::DistanceRequest>,
+    ) -> Result<Response<proto::DistanceResult>, Status> {
+        self.admit(&request, COST_QUERY)?;
+        let sources = self.sources(&request);
+        let req = request.into_inner();
+
+        validate_subject(&self.state, &req.from)?;
+        validate_subject(&self.state, &req.to)?;
+        sources.check(&self.state.access, &req.from)?;
+        let mut params = DistanceQueryParams {
+            max_hops: max_hops(&self.state.config, req.max_hops)?,
+            from: req.from,
+            to: req.to,
+            include_bridges: req.include_bridges,
+            bypass_cache: req.bypass_cache,
+            reverse: false,
+            max_bridges: None,
+            min_followers: 0,
+            path_weighting: None,
+        };
+
+        adapt_max_hops(&self.state, &mut params);
+        let result = cached_distance(&self.state, &params).await?;
+        Ok(Response::new(result.into()))
+    }
+
+    type BatchDistanceStream = ReceiverStream<Result<proto::DistanceResult, Status>>;
+
+    async fn batch_distance(
+        &self,
+        request: Request<proto::BatchDistanceRequest>,
+    ) -> Result<Response<Self::BatchDistanceStream>, Status> {
+        let target_count = request.get_ref().targets.len();
+        self.admit(&request, COST_QUERY + ratelimit::batch_surcharge(target_count))?;
+        let sources = self.sources(&request);
+        let req = request.into_inner();
+
+        if target_count > GRPC_BATCH_TARGETS_MAX {
+            return Err(Status::invalid_argument(format!(
+                "TOO_MANY_TARGETS: Maximum {} targets allowed per batch",
+                GRPC_BATCH_TARGETS_MAX
+            )));
+        }
+        validate_subject(&self.state, &req.from)?;
+        sources.check(&self.state.access, &req.from)?;
+        let max_hops = max_hops(&self.state.config, req.max_hops)?;
+        for target in &req.targets {
+            validate_subject(&self.state, target)?;
+        }
+
+        let (tx, rx) = mpsc::channel(BATCH_STREAM_BUFFER);
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            for target in req.targets {
+                let mut params = DistanceQueryParams {
+                    from: req.from.clone(),
+                    to: target,
+                    max_hops,
+                    include_bridges: req.include_bridges,
+                    bypass_cache: req.bypass_cache,
+                    reverse: false,
+                    max_bridges: None,
+                    min_followers: 0,
+                    path_weighting: None,
+                };
+                adapt_max_hops(&state, &mut params);
+                let item = cached_distance(&state, &params)
+                    .await
+                    .map(Into::into)
+                    .map_err(Status::from);
+                if tx.send(item).await.is_err() {
+                    break; // Client went away
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_path(
+        &self,
+        request: Request<proto::PathRequest>,
+    ) -> Result<Response<proto::PathResponse>, Status> {
+        self.admit(&request, COST_PATH)?;
+        let sources = self.sources(&request);
+        let req = request.into_inner();
+
+        let from = validate_subject(&self.state, &req.from)?;
+        let to = validate_subject(&self.state, &req.to)?;
+        sources.check(&self.state.access, &req.from)?;
+        let (max_hops, _) = self.state.config.hop_policy().apply(
+            &self.state.graph,
+            &from,
+            &to,
+            max_hops(&self.state.config, req.max_hops)?,
+        );
+        let query = bfs::PathQuery {
+            from,
+            to,
+            max_hops,
+            min_followers: 0,
+        };
+
+        let graph = self.state.graph.clone();
+        let result = tokio::task::spawn_blocking(move || bfs::compute_path(&graph, &query))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(proto::PathResponse {
+            from: req.from,
+            to: req.to,
+            found: result.path.is_some(),
+            path: result
+                .path
+                .map(|p| p.iter().map(|pk| pk.to_string()).collect())
+                .unwrap_or_default(),
+        }))
+    }
+
+    async fn verify(
+        &self,
+        request: Request<proto::VerifyRequest>,
+    ) -> Result<Response<proto::VerifyResponse>, Status> {
+        self.admit(&request, COST_LIGHT)?;
+        let req = request.into_inner();
+
+        let from = validate_subject(&self.state, &req.from)?;
+        let to = validate_subject(&self.state, &req.to)?;
+
+        let graph = &self.state.graph;
+        let (follows, followed_by) = match (graph.node_id(&from), graph.node_id(&to)) {
+            (Some(from), Some(to)) => (graph.is_following(from, to), graph.is_following(to, from)),
+            _ => (false, false),
+        };
+
+        Ok(Response::new(proto::VerifyResponse {
+            follows,
+            followed_by,
+            mutual: follows && followed_by,
+        }))
+    }
+}
+
+pub async fn start_server(state: AppState, port: u16) -> anyhow::Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("gRPC server listening on {}", addr);
+
+    Server::builder()
+        .add_service(WotOracleServer::new(GrpcService::new(state)))
+        .serve(addr)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::access::AccessLists;
+    use crate::api::identity::Resolver;
+    use crate::api::ratelimit::RateLimits;
+    use crate::cache::QueryCache;
+    use crate::config::Config;
+    use crate::db::Database;
+    use crate::graph::{PageRank, WotGraph};
+    use std::sync::Arc;
+    use tokio_stream::StreamExt;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+    const C: &str = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+
+    fn create_test_service() -> GrpcService {
+        let graph = Arc::new(WotGraph::new());
+        graph.update_follows(A, &[B.to_string()], None, None);
+        graph.update_follows(B, &[A.to_string(), C.to_string()], None, None);
+
+        let config = Arc::new(Config::from_env());
+        GrpcService::new(AppState {
+            graph,
+            cache: Arc::new(QueryCache::new(config.cache_size, config.cache_ttl_secs)),
+            rate_limits: Arc::new(RateLimits::new(60, 60)),
+            access: Arc::new(AccessLists::from_config(&config)),
+            config,
+            ranks: Arc::new(PageRank::new()),
+            components: Arc::default(),
+            anchors: Arc::default(),
+            popular_anchors: Arc::default(),
+            popularity: Arc::default(),
+            sightings: Arc::default(),
+            botscores: Arc::default(),
+            persistence: Arc::default(),
+            jobs: Arc::default(),
+            commitments: Arc::default(),
+            db: Arc::new(Database::open(":memory:").unwrap()),
+            oracle_keys: None,
+            resolver: Arc::new(Resolver::new(false)),
+            profiles: Arc::default(),
+            dvm_stats: Arc::default(),
+            watchlist: Arc::default(),
+            ingestion_pause: Arc::default(),
+            namespaces: Arc::default(),
+            diagnostics: Arc::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_batch_distance_streams_in_order() {
+        let service = create_test_service();
+        let request = proto::BatchDistanceRequest {
+            from: A.to_string(),
+            targets: vec![C.to_string(), B.to_string()],
+            ..Default::default()
+        };
+
+        let stream = service.batch_distance(Request::new(request)).await.unwrap().into_inner();
+        let results: Vec<_> = stream.map(|r| r.unwrap()).collect().await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].to.as_str(), results[0].hops), (C, Some(2)));
+        assert_eq!((results[1].to.as_str(), results[1].hops), (B, Some(1)));
+        assert!(results[1].mutual_follow);
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let service = create_test_service();
+        let verify = |from: &str, to: &str| proto::VerifyRequest {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+
+        let ab = service.verify(Request::new(verify(A, B))).await.unwrap().into_inner();
+        assert!(ab.follows && ab.followed_by && ab.mutual);
+
+        let ca = service.verify(Request::new(verify(C, B))).await.unwrap().into_inner();
+        assert!(!ca.follows && ca.followed_by && !ca.mutual);
+    }
+
+    #[tokio::test]
+    async fn test_error_codes() {
+        let service = create_test_service();
+
+        let bad_pubkey = proto::DistanceRequest {
+            from: "nope".to_string(),
+            to: B.to_string(),
+            ..Default::default()
+        };
+        let err = service.get_distance(Request::new(bad_pubkey)).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        let bad_hops = proto::PathRequest {
+            from: A.to_string(),
+            to: C.to_string(),
+            max_hops: Some(300),
+        };
+        let err = service.get_path(Request::new(bad_hops)).await.unwrap_err();
+        assert!(err.message().starts_with("INVALID_MAX_HOPS"));
+    }
+}
@@ -1,31 +1,203 @@
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, HeaderMap, StatusCode, Uri},
+    middleware,
+    response::{IntoResponse, Response},
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
+use nostr_sdk::prelude::Keys;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::limit::RequestBodyLimitLayer;
-use tower_governor::{GovernorLayer, governor::GovernorConfigBuilder, key_extractor::SmartIpKeyExtractor};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+use utoipa::{IntoParams, ToSchema};
 
+use super::access::{self, AccessLists, SourceAccess};
+use super::admin;
+use super::attest::{self, AttestedDistance};
+use super::bloom;
+use super::commitment::{self, Commitments};
+use super::csv;
+use super::dvm::{DvmStats, DvmStatsSnapshot};
+use super::etag;
+use super::explore;
+use super::fields::{Fields, FieldsParam, DISTANCE_FIELDS, PATH_FIELDS};
+use super::graphql;
+use super::identity::{ResolveError, Resolver};
+use super::me::{self, Caller};
+use super::metrics;
+use super::openapi;
+use super::outbox;
+use super::profiles::Profiles;
+use super::ratelimit::{self, RateLimits};
+use super::relay;
+use super::replication;
+use super::simulate;
+
+use crate::botscore::{BotFeatures, BotScores};
 use crate::cache::{CacheKey, CacheStats, QueryCache};
-use crate::config::{Config, MAX_HOPS_DEFAULT, MAX_HOPS_LIMIT, REQUEST_BODY_LIMIT};
-use crate::graph::{bfs, LockMetricsSnapshot, WotGraph};
+use crate::config::{
+    Config, MAX_HOPS_DEFAULT, MULTI_SOURCE_MAX_SOURCES, REQUEST_BODY_LIMIT, SET_DISTANCE_MAX_SEARCHES, SET_DISTANCE_MAX_SIZE,
+    VERIFY_BATCH_MAX_PAIRS,
+};
+use crate::db::Database;
+use crate::diagnostics::{self, Diagnostics};
+use crate::error::OracleError;
+use crate::graph::{bfs, ego, trust_score, Anchors, Components, LockMetricsSnapshot, NodeId, PageRank, WotGraph};
+use crate::history::{self, Granularity};
+use crate::namespace::{Namespaces, UnknownGraph};
+use crate::popularity::Popularity;
+use crate::sample::{self, Weighting};
+use crate::scheduler::{JobStats, Schedule, Scheduler};
+use crate::sync::{pause::IngestionStatus, persistence::PersistenceStatsSnapshot, IngestionPause, PersistenceStats, RelaySightings, Watchlist};
+use crate::types::Pubkey;
+
+/// Targets computed per blocking task; larger batches are split and streamed
+const BATCH_CHUNK_SIZE: usize = 100;
+/// Serialized chunks buffered ahead of a streamed batch's client
+const BATCH_CHUNKS_BUFFERED: usize = 4;
+/// Graph epoch a batch was computed at, for NDJSON clients that get no wrapper object
+const X_GRAPH_EPOCH: header::HeaderName = header::HeaderName::from_static("x-graph-epoch");
+const NDJSON: &str = "application/x-ndjson";
+const POPULAR_LIMIT_DEFAULT: usize = 20;
+const POPULAR_LIMIT_MAX: usize = 100;
+const LOOKALIKES_LIMIT_DEFAULT: usize = 20;
+const SAMPLE_SIZE_DEFAULT: usize = 100;
+const SAMPLE_SIZE_MAX: usize = 1000;
+const LOOKALIKES_LIMIT_MAX: usize = 100;
+const LOOKALIKES_MIN_SIMILARITY_DEFAULT: f64 = 0.8;
+const PEERS_LIMIT_DEFAULT: usize = 20;
+const PEERS_LIMIT_MAX: usize = 100;
+const EGO_LIMIT_DEFAULT: usize = 20;
+const EGO_LIMIT_MAX: usize = 1000;
+/// Beyond three hops the ego network of a well-connected pubkey is most of the graph
+const EGO_MAX_HOPS: u8 = 3;
+const ENDORSERS_LIMIT_DEFAULT: usize = 20;
+const ENDORSERS_LIMIT_MAX: usize = 1000;
+/// Followers at which a bridge carries full weight under `path_weighting=followers`
+const PATH_WEIGHT_FULL_FOLLOWERS: usize = 1000;
 
 #[derive(Clone)]
 pub struct AppState {
     pub graph: Arc<WotGraph>,
-    #[allow(dead_code)] // Reserved for future config-based features (e.g., dynamic max_hops)
     pub config: Arc<Config>,
     pub cache: Arc<QueryCache>,
+    pub rate_limits: Arc<RateLimits>,
+    pub access: Arc<AccessLists>,
+    pub ranks: Arc<PageRank>,
+    pub components: Arc<Components>,
+    /// Precomputed distance tables from `ANCHOR_PUBKEYS`
+    pub anchors: Arc<Anchors>,
+    /// Distance tables for the most popular query sources (`ANCHOR_AUTO_COUNT`)
+    pub popular_anchors: Arc<Anchors>,
+    /// Decaying query counts per pair and pubkey, for `/stats/popular`
+    pub popularity: Arc<Popularity>,
+    /// Relays that delivered each pubkey's current contact list since startup
+    pub sightings: Arc<RelaySightings>,
+    /// Bot-likelihood scores, recomputed every `BOTSCORE_INTERVAL_SECS`
+    pub botscores: Arc<BotScores>,
+    /// Health of ingestion's database writes, for `/stats` and `/metrics`
+    pub persistence: Arc<PersistenceStats>,
+    /// Background recomputation jobs, for their run statistics in `/stats`
+    pub jobs: Arc<Scheduler>,
+    /// Latest Merkle commitment over the primary graph's follow lists (`COMMITMENT_INTERVAL_SECS`)
+    pub commitments: Arc<Commitments>,
+    pub db: Arc<Database>,
+    /// The oracle's Nostr identity (`DVM_PRIVATE_KEY`), used to sign results
+    pub oracle_keys: Option<Keys>,
+    pub resolver: Arc<Resolver>,
+    /// Profile names for the HTML explorer, fetched from `RELAYS`
+    pub profiles: Arc<Profiles>,
+    pub dvm_stats: Arc<DvmStats>,
+    /// Priority authors for ingestion, managed under `/admin/watchlist`
+    pub watchlist: Arc<Watchlist>,
+    /// Relay ingestion pause switch for every graph (`/admin/ingestion/pause`)
+    pub ingestion_pause: Arc<IngestionPause>,
+    /// Graphs besides the primary one (the graph, cache, database and rankings above)
+    pub namespaces: Arc<Namespaces>,
+    /// Request timings and relay states for the diagnostic dump (`/admin/debug`, `SIGUSR1`)
+    pub diagnostics: Arc<Diagnostics>,
+}
+
+impl AppState {
+    /// Wrap a result with the hop policy applied to it, and an oracle attestation when
+    /// `SIGN_RESPONSES` is enabled
+    pub(super) fn attested(&self, result: bfs::DistanceResult, adaptive_hops: Option<bfs::AdaptiveHops>) -> AttestedDistance {
+        let attestation = match (&self.oracle_keys, self.config.sign_responses) {
+            (Some(keys), true) => {
+                let created_at = chrono::Utc::now().timestamp().max(0) as u64;
+                Some(attest::attest(keys, &result, result.graph_epoch, created_at))
+            }
+            _ => None,
+        };
+        AttestedDistance { result, adaptive_hops, weighted_path_count: None, attestation }
+    }
+
+    /// This state pointed at a named graph: its graph, cache, database, rankings and settings
+    /// replace the primary graph's. `None` or the primary graph's name returns the state unchanged.
+    pub(super) fn select_graph(&self, name: Option<&str>) -> Result<AppState, ErrorResponse> {
+        match self.namespaces.select(name) {
+            Ok(None) => Ok(self.clone()),
+            Ok(Some(namespace)) => Ok(AppState {
+                graph: namespace.graph.clone(),
+                cache: namespace.cache.clone(),
+                db: namespace.db.clone(),
+                ranks: namespace.ranks.clone(),
+                components: namespace.components.clone(),
+                anchors: namespace.anchors.clone(),
+                popular_anchors: namespace.popular_anchors.clone(),
+                popularity: namespace.popularity.clone(),
+                sightings: namespace.sightings.clone(),
+                botscores: namespace.botscores.clone(),
+                persistence: namespace.persistence.clone(),
+                jobs: namespace.jobs.clone(),
+                config: namespace.config.clone(),
+                ..self.clone()
+            }),
+            Err(UnknownGraph) => Err(ErrorResponse::unknown_graph()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GraphParam {
+    /// Named graph to query (default: the primary graph)
+    pub graph: Option<String>,
+}
+
+impl GraphParam {
+    /// The `graph` query parameter of a request URI
+    pub(super) fn from_uri(uri: &Uri) -> Self {
+        Query::try_from_uri(uri).map(|Query(param)| param).unwrap_or_default()
+    }
+}
+
+/// Extracts the state for the graph selected by the `graph` query parameter
+pub struct SelectedGraph(pub AppState);
+
+#[async_trait]
+impl FromRequestParts<AppState> for SelectedGraph {
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let param = GraphParam::from_uri(&parts.uri);
+        state.select_graph(param.graph.as_deref()).map(SelectedGraph)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct DistanceQueryParams {
     pub from: String,
     pub to: String,
@@ -35,52 +207,447 @@ pub struct DistanceQueryParams {
     pub include_bridges: bool,
     #[serde(default)]
     pub bypass_cache: bool,
+    /// Measure how `from` is reached from `to` (follow edges walked from `to`)
+    #[serde(default)]
+    pub reverse: bool,
+    /// Return only this many bridges, those on the most shortest paths
+    pub max_bridges: Option<usize>,
+    /// Don't route through pubkeys with fewer followers than this (the endpoints are exempt).
+    /// Filtered queries bypass the cache.
+    #[serde(default)]
+    pub min_followers: usize,
+    /// Also report `weighted_path_count`, each bridge's paths scaled by its `followers` or `rank`
+    pub path_weighting: Option<String>,
+}
+
+impl DistanceQueryParams {
+    /// Source and target of the search
+    fn endpoints(&self) -> (&str, &str) {
+        if self.reverse {
+            (&self.to, &self.from)
+        } else {
+            (&self.from, &self.to)
+        }
+    }
 }
 
-fn default_max_hops() -> u8 {
+pub(super) fn default_max_hops() -> u8 {
     MAX_HOPS_DEFAULT
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct FollowsQueryParams {
     pub pubkey: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CommonFollowsQueryParams {
     pub from: String,
     pub to: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EndorsersQueryParams {
+    pub from: String,
+    pub to: String,
+    /// Also list `to`'s followers that `from` reaches in two hops
+    #[serde(default)]
+    pub two_hop: bool,
+    /// Most pubkeys listed per tier (1-1000, default 20); counts cover all
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PathQueryParams {
     pub from: String,
     pub to: String,
     #[serde(default = "default_max_hops")]
     pub max_hops: u8,
+    /// Don't route through pubkeys with fewer followers than this (the endpoints are exempt)
+    #[serde(default)]
+    pub min_followers: usize,
 }
 
-#[derive(Debug, Serialize)]
-pub struct FollowsResponse {
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FollowerHistoryQueryParams {
+    /// Bucket width: `hour`, `day` (default) or `week`
+    pub granularity: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DiffQueryParams {
+    /// Hex pubkey, npub, nprofile or NIP-05 identifier of the follower
     pub pubkey: String,
-    pub follows: Vec<String>,
+    /// Window start, exclusive (Unix time; default 30 days before `to_ts`)
+    pub from_ts: Option<i64>,
+    /// Window end, inclusive (Unix time; default now)
+    pub to_ts: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DailyDiffQueryParams {
+    /// Window start, exclusive (Unix time; default 30 days before `to_ts`)
+    pub from_ts: Option<i64>,
+    /// Window end, inclusive (Unix time; default now)
+    pub to_ts: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SampleQueryParams {
+    /// Pubkeys to draw (1-1000, default 100)
+    pub n: Option<usize>,
+    /// Only draw pubkeys with at least this many followers
+    #[serde(default)]
+    pub min_followers: usize,
+    /// `uniform` (default) or `degree`, proportional to follows plus followers
+    pub weighting: Option<String>,
+    /// Seed for a reproducible sample (random if omitted; the response reports it)
+    pub seed: Option<u64>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PopularQueryParams {
+    /// Entries per list (1-100, default 20)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LookalikesQueryParams {
+    /// Least Jaccard similarity of the follow lists (0-1, default 0.8)
+    pub min_similarity: Option<f64>,
+    /// Most accounts returned (1-100, default 20)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FollowsResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    #[schema(value_type = Vec<String>)]
+    pub follows: Vec<Pubkey>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CommonFollowsResponse {
-    pub from: String,
-    pub to: String,
-    pub common_follows: Vec<String>,
+    #[schema(value_type = String)]
+    pub from: Pubkey,
+    #[schema(value_type = String)]
+    pub to: Pubkey,
+    #[schema(value_type = Vec<String>)]
+    pub common_follows: Vec<Pubkey>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EndorsersResponse {
+    #[schema(value_type = String)]
+    pub from: Pubkey,
+    #[schema(value_type = String)]
+    pub to: Pubkey,
+    /// Follows of `from` that follow `to`
+    pub count: usize,
+    /// Up to `limit` of them, most followed first
+    #[schema(value_type = Vec<String>)]
+    pub endorsers: Vec<Pubkey>,
+    /// With `two_hop`: followers of `to` followed by one of `from`'s follows, but not by `from`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub two_hop_count: Option<usize>,
+    /// Up to `limit` of them, most followed first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub two_hop: Option<Vec<Pubkey>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PathResponse {
     pub from: String,
     pub to: String,
     pub path: Option<Vec<String>>,
+    /// Set when a node's neighbors were sampled down to `BFS_EXPANSION_LIMIT`, so a path may
+    /// have been missed
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Set when an endpoint is a super-node and max_hops was lowered for it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adaptive_hops: Option<bfs::AdaptiveHops>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NodeResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    /// Latest contact list applied for this pubkey; null if it has never published one
+    pub kind3_event_id: Option<String>,
+    pub kind3_created_at: Option<i64>,
+    /// Distinct relays that delivered that contact list since startup: one means a
+    /// single-relay claim. Null if it hasn't been received since startup (or on a replica).
+    pub confidence: Option<usize>,
+    pub follow_count: usize,
+    pub follower_count: usize,
+    /// Follows that follow back
+    pub mutual_count: usize,
+    /// Share of follows that follow back; null without follows
+    pub follow_back_ratio: Option<f64>,
+    /// Follows per follower; null without followers. Far above 1 suggests follow spam.
+    pub follow_ratio: Option<f64>,
+    /// Unix time the oracle first stored this pubkey; null if not yet persisted or stored
+    /// before first-seen times were tracked
+    pub first_seen: Option<i64>,
+    /// Unix time this pubkey's row was last written; null if not yet persisted
+    pub last_updated: Option<i64>,
+    /// Whether the pubkey is in the largest weakly connected component; null until the next
+    /// background refresh after it joined the graph
+    pub in_largest_component: Option<bool>,
+    /// Pubkeys that have reported this one (NIP-56), as seen on relays subscribed to kind 1984
+    /// (`RELAY_SETTINGS`)
+    pub report_count: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BotScoreResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    /// 0-100, higher is more bot-like: the weighted mean of the features (`BOTSCORE_WEIGHTS`);
+    /// null if the pubkey had no follow list at the last computation
+    pub score: Option<u8>,
+    pub features: Option<BotFeatures>,
+    /// Unix time scores were last computed
+    pub computed_at: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LookalikesResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub follow_count: usize,
+    /// Accounts following a near-identical set, most similar first
+    pub lookalikes: Vec<Lookalike>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Lookalike {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    /// Jaccard similarity of the two follow lists: shared follows over distinct follows
+    pub similarity: f64,
+    pub follow_count: usize,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PeersQueryParams {
+    /// Most accounts returned (1-100, default 20)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PeersResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub follow_count: usize,
+    /// Accounts following the most similar sets, most similar first
+    pub peers: Vec<Peer>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Peer {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    /// Jaccard similarity of the two follow lists: shared follows over distinct follows
+    pub similarity: f64,
+    pub shared_follows: usize,
+    pub follow_count: usize,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WeakTiesQueryParams {
+    /// Radius of the ego network examined (2-3, default 2)
+    pub max_hops: Option<u8>,
+    /// Most follows returned (1-1000, default 20)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WeakTiesResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub follow_count: usize,
+    pub max_hops: u8,
+    /// Pubkeys within `max_hops`
+    pub reached: usize,
+    /// Follows that are the only way into part of the network, most exclusive pubkeys first
+    pub ties: Vec<WeakTie>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WeakTie {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    /// Pubkeys within `max_hops` whose every shortest path runs through this follow
+    pub exclusive_reach: usize,
+    /// No other follow follows this one: without the edge it is more than two hops away
+    pub local_bridge: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CutVerticesQueryParams {
+    /// Radius of the ego network examined (1-3, default 2)
+    pub max_hops: Option<u8>,
+    /// Most pubkeys returned (1-1000, default 20)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CutVerticesResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub max_hops: u8,
+    /// Pubkeys in the ego network
+    pub nodes: usize,
+    /// The network was cut short at 50000 pubkeys
+    pub truncated: bool,
+    /// Pubkeys whose removal disconnects part of the network, most separated first
+    pub cut_vertices: Vec<CutVertex>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CutVertex {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub hops: u8,
+    /// Pubkeys left without a connection to the ego if this one is removed
+    pub separated: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScoreResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    /// Anchors with a computed distance table
+    pub anchors: usize,
+    /// Anchors that reach the pubkey
+    pub reached: usize,
+    /// Fewest hops from any anchor; null if none reaches it
+    pub min_hops: Option<u32>,
+    /// Mean hops over the anchors that reach it; null if none does
+    pub avg_hops: Option<f64>,
+    /// 0-100: each anchor contributes 100 at up to one hop, halving per further hop, 0 if unreachable
+    pub score: u8,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PopularResponse {
+    /// Counts halve over this many seconds (`POPULARITY_HALF_LIFE_SECS`)
+    pub half_life_secs: u64,
+    /// Most queried distance queries, most popular first
+    pub pairs: Vec<PopularPair>,
+    /// Most queried pubkeys, as either end of a query
+    pub pubkeys: Vec<PopularPubkey>,
+    /// Sources given distance tables for their popularity (`ANCHOR_AUTO_COUNT`)
+    pub popular_anchors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PopularPair {
+    #[schema(value_type = String)]
+    pub from: Pubkey,
+    #[schema(value_type = String)]
+    pub to: Pubkey,
+    pub max_hops: u8,
+    pub include_bridges: bool,
+    /// Decayed query count
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PopularPubkey {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    /// Decayed query count
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FollowerHistoryResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub granularity: String,
+    /// Last recorded count in each bucket that has one, oldest first
+    pub points: Vec<FollowerHistoryPoint>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FollowerHistoryPoint {
+    /// Bucket start (Unix time, UTC; weeks start on Monday)
+    pub timestamp: i64,
+    pub follower_count: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DiffResponse {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub from_ts: i64,
+    pub to_ts: i64,
+    /// Pubkeys followed at `to_ts` but not at `from_ts`, sorted
+    pub added: Vec<String>,
+    /// Pubkeys followed at `from_ts` but not at `to_ts`, sorted
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DailyDiffResponse {
+    pub from_ts: i64,
+    pub to_ts: i64,
+    /// Days with changes, oldest first
+    pub days: Vec<DailyEdgeChanges>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DailyEdgeChanges {
+    /// Day start (Unix time, UTC)
+    pub day: i64,
+    /// Follows added that day
+    pub added: u64,
+    /// Follows removed that day
+    pub removed: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SampleResponse {
+    pub seed: u64,
+    pub weighting: String,
+    pub min_followers: usize,
+    /// Pubkeys that could have been drawn
+    pub population: usize,
+    /// Mean follow count of the sample; null if it is empty
+    pub mean_follow_count: Option<f64>,
+    /// Mean follower count of the sample; null if it is empty
+    pub mean_follower_count: Option<f64>,
+    /// Drawn without replacement, in draw order
+    pub pubkeys: Vec<SampledPubkey>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SampledPubkey {
+    #[schema(value_type = String)]
+    pub pubkey: Pubkey,
+    pub follow_count: usize,
+    pub follower_count: usize,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct BatchDistanceRequest {
     pub from: String,
     pub targets: Vec<String>,
@@ -90,98 +657,428 @@ pub struct BatchDistanceRequest {
     pub include_bridges: bool,
     #[serde(default)]
     pub bypass_cache: bool,
+    /// Measure how `from` is reached from each target
+    #[serde(default)]
+    pub reverse: bool,
+    /// Return only this many bridges per result, those on the most shortest paths
+    pub max_bridges: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MultiSourceDistanceRequest {
+    pub sources: Vec<String>,
+    pub target: String,
+    #[serde(default = "default_max_hops")]
+    pub max_hops: u8,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetDistanceRequest {
+    /// Up to 5000 hex pubkeys
+    pub from: Vec<String>,
+    /// Up to 5000 hex pubkeys; the smaller of the two sets may hold at most 100
+    pub to: Vec<String>,
+    #[serde(default = "default_max_hops")]
+    pub max_hops: u8,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyBatchRequest {
+    /// Up to 5000 pairs of hex pubkeys
+    pub pairs: Vec<VerifyPair>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyPair {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VerifyBatchResponse {
+    /// Graph epoch every pair was checked at
+    pub epoch: u64,
+    /// One per pair, in request order
+    pub results: Vec<EdgeCheck>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct EdgeCheck {
+    /// `from` follows `to`
+    pub follows: bool,
+    /// `to` follows `from`
+    pub followed_by: bool,
+    pub mutual: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct BatchDistanceResponse {
     pub from: String,
-    pub results: Vec<bfs::DistanceResult>,
+    /// Graph epoch every result was computed at
+    pub epoch: u64,
+    pub results: Vec<AttestedDistance>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct StatsResponse {
     pub node_count: usize,
     pub edge_count: usize,
     pub nodes_with_follows: usize,
+    /// Nodes excluded from path queries as likely spam (followed by nobody, following at least `QUARANTINE_MIN_FOLLOWS`)
+    pub quarantined_nodes: usize,
+    /// Contact lists ignored for exceeding `MAX_FOLLOWS_PER_LIST` since startup
+    pub rejected_lists: u64,
+    /// Contact list events ignored unparsed for exceeding `MAX_TAGS_PER_LIST` since startup
+    pub oversized_lists: u64,
+    /// Contact list events cut down to `MAX_TAGS_PER_LIST` tags (`TRUNCATE_OVERSIZED_LISTS`)
+    pub truncated_lists: u64,
+    /// Follows dropped as repeats within their contact list since startup
+    pub duplicate_follows: u64,
     pub cache: CacheStats,
+    /// Adjacency lock metrics summed over all shards
     pub locks: LockMetricsSnapshot,
+    /// Adjacency lock metrics per shard, in shard order (node `id` is in shard `id % 16`)
+    pub lock_shards: Vec<LockMetricsSnapshot>,
+    /// DVM relay status and request counters; absent unless `DVM_ENABLED`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dvm: Option<DvmStatsSnapshot>,
+    pub ingestion: IngestionStatus,
+    /// Queue depth, flush latency and throughput of the database writes behind ingestion
+    pub persistence: PersistenceStatsSnapshot,
+    /// Background recomputation jobs: schedule, last run, duration and failures
+    pub jobs: Vec<JobStats>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct HealthResponse {
+    /// "healthy", or "maintenance" while ingestion is paused
     pub status: String,
     pub version: String,
+    pub ingestion: IngestionStatus,
 }
 
-#[derive(Debug, Serialize)]
+/// The JSON body of every API error; `code` is the stable [`OracleError::code`]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ErrorResponse {
     pub error: String,
     pub code: String,
+    #[serde(skip)]
+    status: StatusCode,
 }
 
 impl ErrorResponse {
+    pub fn invalid(code: &'static str, message: impl Into<String>) -> Self {
+        OracleError::invalid(code, message).into()
+    }
+
     pub fn internal(msg: impl Into<String>) -> Self {
+        OracleError::internal(msg).into()
+    }
+
+    pub fn rate_limited() -> Self {
+        OracleError::RateLimited.into()
+    }
+
+    pub fn ip_denied() -> Self {
+        OracleError::IpDenied.into()
+    }
+
+    pub fn unknown_graph() -> Self {
+        OracleError::UnknownGraph.into()
+    }
+
+    pub fn pubkey_denied() -> Self {
+        OracleError::PubkeyDenied.into()
+    }
+
+    pub fn source_denied() -> Self {
+        OracleError::SourceDenied.into()
+    }
+
+    pub fn unauthorized() -> Self {
+        OracleError::Unauthorized.into()
+    }
+
+    pub fn not_found() -> Self {
+        OracleError::NodeNotFound.into()
+    }
+
+    /// No anchors configured, or their distance tables not computed yet
+    pub fn no_anchors(anchors: &Anchors) -> Self {
+        match anchors.pubkeys().is_empty() {
+            true => OracleError::Disabled {
+                code: "NO_ANCHORS",
+                message: "No anchor distances computed; set ANCHOR_PUBKEYS".to_string(),
+            },
+            false => OracleError::GraphWarming("Anchor distances not computed yet".to_string()),
+        }
+        .into()
+    }
+
+    /// Commitments disabled, or none computed yet
+    pub fn no_commitment(config: &Config) -> Self {
+        match config.commitment_interval_secs {
+            None => OracleError::Disabled {
+                code: "NO_COMMITMENT",
+                message: "No follow commitment computed; set COMMITMENT_INTERVAL_SECS".to_string(),
+            },
+            Some(_) => OracleError::GraphWarming("No follow commitment computed yet".to_string()),
+        }
+        .into()
+    }
+
+    /// Bot scores disabled, or none computed yet
+    pub fn no_botscore(config: &Config) -> Self {
+        match config.botscore_interval_secs {
+            Some(_) if config.job_schedules.get("botscores") != Some(&Schedule::Off) => {
+                OracleError::GraphWarming("No bot scores computed yet".to_string())
+            }
+            _ => OracleError::Disabled {
+                code: "NO_BOTSCORE",
+                message: "No bot scores computed; BOTSCORE_INTERVAL_SECS may be 0".to_string(),
+            },
+        }
+        .into()
+    }
+
+    /// Replace the error's generic message, keeping its code and status
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.error = message.into();
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
+impl From<OracleError> for ErrorResponse {
+    fn from(e: OracleError) -> Self {
         Self {
-            error: msg.into(),
-            code: "INTERNAL_ERROR".to_string(),
+            error: e.to_string(),
+            code: e.code().to_string(),
+            status: e.status(),
         }
     }
 }
 
+impl From<ResolveError> for ErrorResponse {
+    fn from(e: ResolveError) -> Self {
+        OracleError::from(e).into()
+    }
+}
+
 impl IntoResponse for ErrorResponse {
-    fn into_response(self) -> axum::response::Response {
-        (StatusCode::BAD_REQUEST, Json(self)).into_response()
+    fn into_response(self) -> Response {
+        (self.status, Json(self)).into_response()
     }
 }
 
-fn validate_pubkey(pubkey: &str) -> Result<(), ErrorResponse> {
+/// Parse a hex pubkey, as query inputs are once resolved
+pub(super) fn validate_pubkey(pubkey: &str) -> Result<Pubkey, ErrorResponse> {
     // Less verbose error messages to avoid leaking validation details
-    if pubkey.len() != 64 {
-        return Err(ErrorResponse {
-            error: "Invalid pubkey format".to_string(),
-            code: "INVALID_PUBKEY".to_string(),
-        });
-    }
+    Pubkey::parse(pubkey).map_err(|_| OracleError::InvalidPubkey.into())
+}
 
-    if !pubkey.chars().all(|c| c.is_ascii_hexdigit()) {
-        return Err(ErrorResponse {
-            error: "Invalid pubkey format".to_string(),
-            code: "INVALID_PUBKEY".to_string(),
-        });
+/// Validate a query subject: well-formed, not excluded by the pubkey access lists and not blocked
+pub(super) fn validate_subject(state: &AppState, pubkey: &str) -> Result<Pubkey, ErrorResponse> {
+    let parsed = validate_pubkey(pubkey)?;
+    if !state.access.is_pubkey_allowed(pubkey) || state.graph.is_blocked(&parsed.to_hex()) {
+        return Err(ErrorResponse::pubkey_denied());
     }
+    Ok(parsed)
+}
+
+/// Resolve an npub, nprofile, NIP-05 or hex input to a hex pubkey, then apply the subject checks
+pub(super) async fn resolve_subject(state: &AppState, input: &str) -> Result<Pubkey, ErrorResponse> {
+    let pubkey = state.resolver.resolve(input).await?;
+    validate_subject(state, &pubkey.to_hex())
+}
 
+/// Resolve several inputs in place, each as [`resolve_subject`] would; NIP-05 lookups are capped
+/// per request and run concurrently
+pub(super) async fn resolve_subjects(state: &AppState, inputs: Vec<&mut String>) -> Result<(), ErrorResponse> {
+    let resolved = state.resolver.resolve_all(&inputs.iter().map(|input| input.as_str()).collect::<Vec<_>>()).await?;
+    for (input, pubkey) in inputs.into_iter().zip(resolved) {
+        *input = pubkey.to_hex();
+        validate_subject(state, input)?;
+    }
     Ok(())
 }
 
-fn validate_max_hops(max_hops: u8) -> Result<(), ErrorResponse> {
-    if !(1..=MAX_HOPS_LIMIT).contains(&max_hops) {
-        return Err(ErrorResponse {
-            error: format!("max_hops must be between 1 and {}", MAX_HOPS_LIMIT),
-            code: "INVALID_MAX_HOPS".to_string(),
-        });
+/// Check max_hops against the query APIs' limit (`HTTP_MAX_HOPS`)
+pub(super) fn validate_max_hops(config: &Config, max_hops: u8) -> Result<(), ErrorResponse> {
+    if !(1..=config.http_max_hops).contains(&max_hops) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_MAX_HOPS",
+            format!("max_hops must be between 1 and {}", config.http_max_hops),
+        ));
     }
     Ok(())
 }
 
+#[utoipa::path(
+    get,
+    path = "/distance",
+    tag = "queries",
+    params(DistanceQueryParams, FieldsParam, GraphParam),
+    responses(
+        (status = 200, description = "Shortest follow distance between two pubkeys, limited to `fields` if given", body = AttestedDistance),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists, or unregistered source with RESTRICT_SOURCES", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse)
+    )
+)]
 pub async fn get_distance(
-    State(state): State<AppState>,
-    Query(params): Query<DistanceQueryParams>,
-) -> Result<Json<bfs::DistanceResult>, ErrorResponse> {
-    validate_pubkey(&params.from)?;
-    validate_pubkey(&params.to)?;
-    validate_max_hops(params.max_hops)?;
+    SelectedGraph(state): SelectedGraph,
+    sources: SourceAccess,
+    caller: Option<Extension<Caller>>,
+    Query(mut params): Query<DistanceQueryParams>,
+    Query(fields): Query<FieldsParam>,
+) -> Result<Response, ErrorResponse> {
+    let fields = fields.parse(DISTANCE_FIELDS)?;
+    params.from = resolve_subject(&state, &params.from).await?.to_hex();
+    sources.check(&state.access, &params.from)?;
+    params.to = resolve_subject(&state, &params.to).await?.to_hex();
+    validate_max_hops(&state.config, params.max_hops)?;
+    let adaptive_hops = adapt_max_hops(&state, &mut params);
+    let weighting = params.path_weighting.as_deref().map(PathWeighting::parse).transpose()?;
+    // Weighting needs every bridge and its path count, whether or not the caller asked for them
+    let include_bridges = params.include_bridges;
+    params.include_bridges |= weighting.is_some();
+
+    let mut result = if muted_by(caller).contains(&params.to) {
+        let (from, to) = params.endpoints();
+        let graph_epoch = state.graph.epoch();
+        bfs::DistanceResult { graph_epoch, ..bfs::DistanceResult::not_found(validate_pubkey(from)?, validate_pubkey(to)?) }
+    } else {
+        cached_distance(&state, &params).await?
+    };
+    let weighted_path_count = weighting.map(|weighting| weighting.path_count(&state, &result));
+    if !include_bridges {
+        result.bridges = None;
+        result.bridge_path_counts = None;
+    }
+    if let Some(max_bridges) = params.max_bridges {
+        result.truncate_bridges(max_bridges);
+    }
+    let mut attested = state.attested(result, adaptive_hops);
+    attested.weighted_path_count = weighted_path_count;
+    fields.respond(attested)
+}
+
+/// How `path_weighting` values a bridge (the node where a shortest path's halves meet)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathWeighting {
+    /// Log-scaled follower count, full weight at `PATH_WEIGHT_FULL_FOLLOWERS`
+    Followers,
+    /// PageRank percentile
+    Rank,
+}
+
+impl PathWeighting {
+    fn parse(value: &str) -> Result<Self, ErrorResponse> {
+        match value {
+            "followers" => Ok(Self::Followers),
+            "rank" => Ok(Self::Rank),
+            _ => Err(ErrorResponse::invalid(
+                "INVALID_PATH_WEIGHTING",
+                "path_weighting must be followers or rank",
+            )),
+        }
+    }
+
+    /// 0-1 weight of a bridge
+    fn weight(self, state: &AppState, node_id: NodeId) -> f64 {
+        match self {
+            Self::Followers => {
+                let (_, followers) = state.graph.degree(node_id);
+                ((followers as f64).ln_1p() / (PATH_WEIGHT_FULL_FOLLOWERS as f64).ln_1p()).min(1.0)
+            }
+            // Nodes newer than the last PageRank run haven't earned any standing yet
+            Self::Rank => state.ranks.percentile(node_id).unwrap_or(0.0),
+        }
+    }
+
+    /// Sum of each bridge's path count times its weight. A search can meet at an endpoint,
+    /// which says nothing about the path, so those paths (and direct follows) count fully.
+    fn path_count(self, state: &AppState, result: &bfs::DistanceResult) -> f64 {
+        let (Some(bridges), Some(counts)) = (&result.bridges, &result.bridge_path_counts) else {
+            return result.path_count as f64;
+        };
+        if bridges.is_empty() {
+            return result.path_count as f64;
+        }
+        bridges
+            .iter()
+            .zip(counts)
+            .map(|(bridge, &paths)| {
+                if *bridge == result.from || *bridge == result.to {
+                    return paths as f64;
+                }
+                let weight = state.graph.node_id(bridge).map_or(0.0, |id| self.weight(state, id));
+                paths as f64 * weight
+            })
+            .sum()
+    }
+}
+
+/// Pubkeys an authenticated caller has reported unreachable (`exclude_muted` in `/me/settings`)
+fn muted_by(caller: Option<Extension<Caller>>) -> Arc<HashSet<String>> {
+    caller.map(|Extension(caller)| caller.muted).unwrap_or_default()
+}
+
+/// Lower `params.max_hops` when an endpoint is a super-node (`ADAPTIVE_HOPS_DEGREE`),
+/// returning the applied policy to report with the result
+pub(super) fn adapt_max_hops(state: &AppState, params: &mut DistanceQueryParams) -> Option<bfs::AdaptiveHops> {
+    let (Ok(from), Ok(to)) = (Pubkey::parse(&params.from), Pubkey::parse(&params.to)) else {
+        return None;
+    };
+    let (max_hops, applied) = state.config.hop_policy().apply(&state.graph, &from, &to, params.max_hops);
+    params.max_hops = max_hops;
+    applied
+}
+
+/// Distance lookup through the query cache; parameters must already be validated.
+/// The result's `from` and `to` are the search direction, so they are swapped for `reverse`.
+pub(super) async fn cached_distance(
+    state: &AppState,
+    params: &DistanceQueryParams,
+) -> Result<bfs::DistanceResult, ErrorResponse> {
+    // A reverse query is the forward query between the swapped pair, and shares its cache entry
+    let (from, to) = params.endpoints();
+    let (from_key, to_key) = (validate_pubkey(from)?, validate_pubkey(to)?);
 
-    // Convert pubkeys to node IDs immediately for compact cache lookup
-    let from_id = state.graph.get_node_id(&params.from);
-    let to_id = state.graph.get_node_id(&params.to);
+    // Convert pubkeys to node IDs immediately for compact cache lookup. Neither cache keys
+    // nor anchor tables know about follower filters, so filtered queries always search.
+    let (from_id, to_id) = match params.min_followers {
+        0 => (state.graph.node_id(&from_key), state.graph.node_id(&to_key)),
+        _ => (None, None),
+    };
+
+    if let (Some(from_id), Some(to_id)) = (from_id, to_id) {
+        state.popularity.record(CacheKey::new(from_id, to_id, params.max_hops, params.include_bridges));
+    }
+
+    // Anchor-sourced queries are a lookup in the anchor's precomputed table
+    if let (false, false, Some(from_id), Some(to_id)) = (params.bypass_cache, params.include_bridges, from_id, to_id) {
+        let anchored = state
+            .anchors
+            .distance(&state.graph, from_id, to_id, params.max_hops)
+            .or_else(|| state.popular_anchors.distance(&state.graph, from_id, to_id, params.max_hops));
+        if let Some(result) = anchored {
+            debug!("Anchor hit for {} -> {}", &from[..8], &to[..8]);
+            return Ok(result);
+        }
+    }
 
     // Check cache first (lock-free, stays on async thread)
     if !params.bypass_cache {
         if let (Some(from_id), Some(to_id)) = (from_id, to_id) {
             let cache_key = CacheKey::new(from_id, to_id, params.max_hops, params.include_bridges);
             if let Some(cached_result) = state.cache.get(&cache_key, &state.graph) {
-                debug!("Cache hit for {} -> {}", &params.from[..8], &params.to[..8]);
-                return Ok(Json(cached_result));
+                debug!("Cache hit for {} -> {}", &from[..8], &to[..8]);
+                return Ok(cached_result);
             }
         }
     }
@@ -189,10 +1086,11 @@ pub async fn get_distance(
     // CPU-bound BFS → blocking thread pool (keeps async workers free)
     let graph = state.graph.clone();
     let query = bfs::DistanceQuery {
-        from: Arc::from(params.from.as_str()),
-        to: Arc::from(params.to.as_str()),
+        from: from_key,
+        to: to_key,
         max_hops: params.max_hops,
         include_bridges: params.include_bridges,
+        min_followers: params.min_followers,
     };
 
     let result = tokio::task::spawn_blocking(move || {
@@ -202,181 +1100,1262 @@ pub async fn get_distance(
     .map_err(|e| ErrorResponse::internal(e.to_string()))?;
 
     // Cache insert (lock-free, back on async thread)
-    if let (Some(from_id), Some(to_id)) = (
-        state.graph.get_node_id(&params.from),
-        state.graph.get_node_id(&params.to),
-    ) {
+    if let (0, Some(from_id), Some(to_id)) = (params.min_followers, state.graph.node_id(&from_key), state.graph.node_id(&to_key)) {
         let cache_key = CacheKey::new(from_id, to_id, params.max_hops, params.include_bridges);
         state.cache.insert(cache_key, &result, &state.graph);
     }
-    debug!("Cache miss for {} -> {}, computed and cached", &params.from[..8], &params.to[..8]);
+    debug!("Cache miss for {} -> {}, computed and cached", &from[..8], &to[..8]);
 
-    Ok(Json(result))
+    Ok(result)
 }
 
+#[utoipa::path(
+    post,
+    path = "/distance/batch",
+    tag = "queries",
+    params(FieldsParam, GraphParam),
+    request_body = BatchDistanceRequest,
+    responses(
+        (status = 200, description = "Distances from one pubkey to up to `BATCH_MAX_TARGETS` targets (default 100); batches over 100 targets are streamed. With `Accept: application/x-ndjson`, one result per line as computed", content(
+            (BatchDistanceResponse = "application/json"),
+            (AttestedDistance = "application/x-ndjson")
+        )),
+        (status = 400, description = "Invalid parameters or too many targets", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists, or unregistered source with RESTRICT_SOURCES", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse)
+    )
+)]
 pub async fn batch_distance(
-    State(state): State<AppState>,
-    Json(request): Json<BatchDistanceRequest>,
-) -> Result<Json<BatchDistanceResponse>, ErrorResponse> {
-    validate_pubkey(&request.from)?;
-    validate_max_hops(request.max_hops)?;
-
-    if request.targets.len() > 100 {
-        return Err(ErrorResponse {
-            error: "Maximum 100 targets allowed per batch".to_string(),
-            code: "TOO_MANY_TARGETS".to_string(),
-        });
+    SelectedGraph(state): SelectedGraph,
+    sources: SourceAccess,
+    caller: Option<Extension<Caller>>,
+    headers: HeaderMap,
+    Query(fields): Query<FieldsParam>,
+    Json(mut request): Json<BatchDistanceRequest>,
+) -> Result<Response, ErrorResponse> {
+    let fields = fields.parse(DISTANCE_FIELDS)?;
+    request.from = resolve_subject(&state, &request.from).await?.to_hex();
+    sources.check(&state.access, &request.from)?;
+    validate_max_hops(&state.config, request.max_hops)?;
+
+    let max_targets = state.config.batch_max_targets;
+    if request.targets.len() > max_targets {
+        return Err(ErrorResponse::invalid(
+            "TOO_MANY_TARGETS",
+            format!("Maximum {} targets allowed per batch", max_targets),
+        ));
     }
 
-    for target in &request.targets {
-        validate_pubkey(target)?;
+    resolve_subjects(&state, request.targets.iter_mut().collect()).await?;
+
+    let targets = request.targets.iter().map(|t| validate_pubkey(t)).collect::<Result<Vec<_>, _>>()?;
+    let batch = Arc::new(BatchOptions {
+        from: validate_pubkey(&request.from)?,
+        max_hops: request.max_hops,
+        include_bridges: request.include_bridges,
+        bypass_cache: request.bypass_cache,
+        reverse: request.reverse,
+        max_bridges: request.max_bridges,
+        fields,
+        muted: muted_by(caller),
+    });
+
+    if accepts_ndjson(&headers) {
+        return stream_batch(state, batch, targets, StreamFormat::Ndjson).await;
+    }
+    if csv::accepts_csv(&headers) {
+        return stream_batch(state, batch, targets, StreamFormat::Csv).await;
     }
+    if targets.len() <= BATCH_CHUNK_SIZE {
+        let (epoch, results) = batch_chunk(&state, &batch, targets).await?;
+        let epoch_header = [(X_GRAPH_EPOCH, epoch.to_string())];
+        if batch.fields.is_all() {
+            return Ok((epoch_header, Json(BatchDistanceResponse { from: request.from, epoch, results })).into_response());
+        }
+        let results = results
+            .iter()
+            .map(|result| batch.fields.shape(result))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+        let body = serde_json::json!({ "from": request.from, "epoch": epoch, "results": results });
+        return Ok((epoch_header, Json(body)).into_response());
+    }
+    stream_batch(state, batch, targets, StreamFormat::Json).await
+}
 
-    // Check cache for all targets first (lock-free, stays on async thread)
-    let from_id = state.graph.get_node_id(&request.from);
-    let bypass_cache = request.bypass_cache;
+/// Whether the client asked for newline-delimited JSON
+pub(super) fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    accepts_media(headers, NDJSON)
+}
 
-    let mut results: Vec<bfs::DistanceResult> = Vec::with_capacity(request.targets.len());
-    // Use Arc<str> to avoid String clones in the blocking closure
-    let mut uncached_targets: Vec<(usize, Arc<str>)> = Vec::new();
+/// Whether `Accept` lists the media type `media`
+pub(super) fn accepts_media(headers: &HeaderMap, media: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|listed| listed.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case(media))
+}
 
-    for (idx, target) in request.targets.iter().enumerate() {
-        let mut found_in_cache = false;
+/// How a streamed batch is framed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamFormat {
+    /// The buffered response's JSON document, written piecewise
+    Json,
+    /// One result object per line
+    Ndjson,
+    /// A header row of the selected fields, then one record per result
+    Csv,
+}
 
-        if !bypass_cache {
-            if let Some(from_id) = from_id {
-                if let Some(to_id) = state.graph.get_node_id(target) {
-                    let cache_key = CacheKey::new(from_id, to_id, request.max_hops, request.include_bridges);
-                    if let Some(cached_result) = state.cache.get(&cache_key, &state.graph) {
-                        results.push(cached_result);
-                        found_in_cache = true;
-                    }
-                }
-            }
+impl StreamFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            StreamFormat::Json => "application/json",
+            StreamFormat::Ndjson => NDJSON,
+            StreamFormat::Csv => csv::CONTENT_TYPE,
         }
+    }
 
-        if !found_in_cache {
-            // Placeholder - will be filled by spawn_blocking
-            results.push(bfs::DistanceResult::not_found(
-                Arc::from(""),
-                Arc::from(""),
-            ));
-            uncached_targets.push((idx, Arc::from(target.as_str())));
+    fn head(self, batch: &BatchOptions, epoch: u64) -> String {
+        match self {
+            StreamFormat::Json => format!(r#"{{"from":{},"epoch":{},"results":["#, serde_json::json!(batch.from), epoch),
+            StreamFormat::Ndjson => String::new(),
+            StreamFormat::Csv => csv::record(batch.fields.columns(DISTANCE_FIELDS)),
         }
     }
 
-    // CPU-bound BFS for uncached targets → blocking thread pool
-    if !uncached_targets.is_empty() {
-        let graph = state.graph.clone();
-        // Convert to Arc<str> once - clones in loop are just ref count bumps
-        let from: Arc<str> = Arc::from(request.from.as_str());
-        let max_hops = request.max_hops;
-        let include_bridges = request.include_bridges;
-
-        let computed: Vec<(usize, bfs::DistanceResult)> = tokio::task::spawn_blocking(move || {
-            uncached_targets
-                .into_iter()
-                .map(|(idx, target)| {
-                    let query = bfs::DistanceQuery {
-                        from: Arc::clone(&from), // Cheap ref count bump
-                        to: target,              // Already Arc<str>, moved
-                        max_hops,
-                        include_bridges,
-                    };
-                    (idx, bfs::compute_distance(&graph, &query))
-                })
-                .collect()
-        })
-        .await
-        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
-
-        // Fill in computed results and cache them
-        for (idx, result) in computed {
-            // Cache insert first (by reference), then move into results
-            if let (Some(from_id), Some(to_id)) = (
-                state.graph.get_node_id(&request.from),
-                state.graph.get_node_id(&result.to),
-            ) {
-                let cache_key = CacheKey::new(from_id, to_id, max_hops, include_bridges);
-                state.cache.insert(cache_key, &result, &state.graph);
+    /// Append one result to `part`
+    fn push(self, part: &mut String, fields: &Fields, result: &AttestedDistance, first: bool) -> serde_json::Result<()> {
+        match self {
+            StreamFormat::Json => {
+                if !first {
+                    part.push(',');
+                }
+                part.push_str(&fields.to_json(result)?);
+            }
+            StreamFormat::Ndjson => {
+                part.push_str(&fields.to_json(result)?);
+                part.push('\n');
+            }
+            StreamFormat::Csv => {
+                part.push_str(&csv::object_record(&fields.shape(result)?, &fields.columns(DISTANCE_FIELDS)));
             }
-            results[idx] = result; // Move, no clone
         }
+        Ok(())
     }
 
-    Ok(Json(BatchDistanceResponse {
-        from: request.from,
-        results,
-    }))
+    fn tail(self) -> &'static str {
+        match self {
+            StreamFormat::Json => "]}",
+            StreamFormat::Ndjson | StreamFormat::Csv => "",
+        }
+    }
 }
 
-pub async fn get_follows(
-    State(state): State<AppState>,
-    Query(params): Query<FollowsQueryParams>,
-) -> Result<Json<FollowsResponse>, ErrorResponse> {
-    validate_pubkey(&params.pubkey)?;
-
-    let follows = state.graph.get_follows(&params.pubkey).unwrap_or_default();
-
-    Ok(Json(FollowsResponse {
-        pubkey: params.pubkey,
-        follows,
-    }))
+/// A batch request's settings, shared by its chunks
+struct BatchOptions {
+    from: Pubkey,
+    max_hops: u8,
+    include_bridges: bool,
+    bypass_cache: bool,
+    reverse: bool,
+    max_bridges: Option<usize>,
+    /// Fields kept in each result
+    fields: Fields,
+    /// Targets reported unreachable, muted by the caller
+    muted: Arc<HashSet<String>>,
 }
 
-pub async fn get_common_follows(
-    State(state): State<AppState>,
-    Query(params): Query<CommonFollowsQueryParams>,
-) -> Result<Json<CommonFollowsResponse>, ErrorResponse> {
-    validate_pubkey(&params.from)?;
-    validate_pubkey(&params.to)?;
-
-    let from_follows = state.graph.get_follows(&params.from).unwrap_or_default();
-    let to_follows = state.graph.get_follows(&params.to).unwrap_or_default();
+/// One batch target with the max_hops the hop policy gave it
+type BatchTarget = (Pubkey, u8, Option<bfs::AdaptiveHops>);
 
-    // Find intersection - both lists are from sorted internal storage
-    let from_set: std::collections::HashSet<_> = from_follows.into_iter().collect();
-    let common_follows: Vec<String> = to_follows
+/// Split `targets` into chunks of `BATCH_CHUNK_SIZE`, each target getting its own max_hops,
+/// lowered when it or `from` is a super-node
+fn batch_chunks(state: &AppState, batch: &BatchOptions, targets: Vec<Pubkey>) -> Vec<Vec<BatchTarget>> {
+    let hop_policy = state.config.hop_policy();
+    let targets: Vec<BatchTarget> = targets
         .into_iter()
-        .filter(|f| from_set.contains(f))
+        .map(|target| {
+            let (max_hops, adaptive_hops) = hop_policy.apply(&state.graph, &batch.from, &target, batch.max_hops);
+            (target, max_hops, adaptive_hops)
+        })
         .collect();
-
-    Ok(Json(CommonFollowsResponse {
-        from: params.from,
-        to: params.to,
-        common_follows,
-    }))
+    targets.chunks(BATCH_CHUNK_SIZE).map(<[BatchTarget]>::to_vec).collect()
 }
 
-pub async fn get_path(
-    State(state): State<AppState>,
-    Query(params): Query<PathQueryParams>,
-) -> Result<Json<PathResponse>, ErrorResponse> {
-    validate_pubkey(&params.from)?;
-    validate_pubkey(&params.to)?;
-    validate_max_hops(params.max_hops)?;
-
-    let graph = state.graph.clone();
-    let query = bfs::PathQuery {
-        from: std::sync::Arc::from(params.from.as_str()),
-        to: std::sync::Arc::from(params.to.as_str()),
-        max_hops: params.max_hops,
-    };
+/// Compute a batch's chunks in order inside one graph snapshot, so every result reflects the
+/// same epoch whatever ingestion does meanwhile. `pinned` gets that epoch before the first
+/// chunk, `emit` each chunk's results until it returns false. Cache hits count only if stored
+/// at the snapshot's epoch. CPU-bound: call from a blocking thread.
+fn batch_in_snapshot(
+    state: &AppState,
+    batch: &BatchOptions,
+    chunks: Vec<Vec<BatchTarget>>,
+    pinned: impl FnOnce(u64),
+    mut emit: impl FnMut(Vec<AttestedDistance>) -> bool,
+) {
+    let (reverse, include_bridges) = (batch.reverse, batch.include_bridges);
+    let graph = &state.graph;
+    graph.with_snapshot(|epoch, follows, followers| {
+        pinned(epoch);
+        let from_id = graph.node_id(&batch.from);
+        for chunk in chunks {
+            let results = chunk
+                .into_iter()
+                .map(|(target, max_hops, adaptive_hops)| {
+                    let (from, to) = if reverse { (target, batch.from) } else { (batch.from, target) };
+                    if !batch.muted.is_empty() && batch.muted.contains(&target.to_hex()) {
+                        let muted = bfs::DistanceResult { graph_epoch: epoch, ..bfs::DistanceResult::not_found(from, to) };
+                        return state.attested(muted, adaptive_hops);
+                    }
 
-    let result = tokio::task::spawn_blocking(move || {
-        bfs::compute_path(&graph, &query)
-    })
+                    // Reverse queries search from each target to `from`
+                    let key = from_id.zip(graph.node_id(&target)).map(|(from_id, target_id)| {
+                        let (source, target) = if reverse { (target_id, from_id) } else { (from_id, target_id) };
+                        CacheKey::new(source, target, max_hops, include_bridges)
+                    });
+                    let cached = key
+                        .filter(|_| !batch.bypass_cache)
+                        .and_then(|key| state.cache.get_at(&key, graph, epoch));
+                    let mut result = cached.unwrap_or_else(|| {
+                        let query = bfs::DistanceQuery { from, to, max_hops, include_bridges, min_followers: 0 };
+                        let result = bfs::compute_distance_in(graph, follows, followers, &query);
+                        if let Some(key) = key {
+                            state.cache.insert_pinned(key, &result, graph);
+                        }
+                        result
+                    });
+                    if let Some(max_bridges) = batch.max_bridges {
+                        result.truncate_bridges(max_bridges);
+                    }
+                    state.attested(result, adaptive_hops)
+                })
+                .collect();
+            if !emit(results) {
+                return;
+            }
+        }
+    })
+}
+
+/// Distances from the batch's `from` to `targets` (at most `BATCH_CHUNK_SIZE`), in order, and
+/// the graph epoch they were computed at
+async fn batch_chunk(
+    state: &AppState,
+    batch: &Arc<BatchOptions>,
+    targets: Vec<Pubkey>,
+) -> Result<(u64, Vec<AttestedDistance>), ErrorResponse> {
+    let chunks = batch_chunks(state, batch, targets);
+    let (state, batch) = (state.clone(), batch.clone());
+    tokio::task::spawn_blocking(move || {
+        let (mut epoch, mut results) = (0, Vec::new());
+        batch_in_snapshot(&state, &batch, chunks, |pinned| epoch = pinned, |chunk| {
+            results = chunk;
+            true
+        });
+        (epoch, results)
+    })
+    .await
+    .map_err(|e| ErrorResponse::internal(e.to_string()))
+}
+
+/// Stream a batch as NDJSON or CSV, or as one JSON document of the same shape as a buffered one.
+/// Chunks of `BATCH_CHUNK_SIZE` targets are computed in order inside one graph snapshot and
+/// written as each completes; the snapshot's epoch goes in the `X-Graph-Epoch` header. Computed
+/// chunks queue without bound, so a slow client never holds the snapshot (and ingestion) up. A
+/// failure after the first bytes can no longer change the status, so it aborts the body instead.
+async fn stream_batch(
+    state: AppState,
+    batch: Arc<BatchOptions>,
+    targets: Vec<Pubkey>,
+    format: StreamFormat,
+) -> Result<Response, ErrorResponse> {
+    let chunks = batch_chunks(&state, &batch, targets);
+    let (epoch_tx, epoch_rx) = oneshot::channel();
+    let (chunk_tx, mut chunk_rx) = mpsc::unbounded_channel();
+    let compute = {
+        let batch = batch.clone();
+        tokio::task::spawn_blocking(move || {
+            batch_in_snapshot(&state, &batch, chunks, |epoch| {
+                let _ = epoch_tx.send(epoch);
+            }, |results| chunk_tx.send(results).is_ok());
+        })
+    };
+    let Ok(epoch) = epoch_rx.await else {
+        // The snapshot is taken before anything else, so only a panic gets here
+        let error = compute.await.err().map_or_else(String::new, |e| e.to_string());
+        return Err(ErrorResponse::internal(error));
+    };
+
+    let (tx, rx) = mpsc::channel::<Result<String, std::io::Error>>(BATCH_CHUNKS_BUFFERED);
+    tokio::spawn(async move {
+        if tx.send(Ok(format.head(&batch, epoch))).await.is_err() {
+            return;
+        }
+
+        let mut first = true;
+        while let Some(results) = chunk_rx.recv().await {
+            let part = results.iter().try_fold(String::new(), |mut part, result| {
+                format.push(&mut part, &batch.fields, result, std::mem::take(&mut first))?;
+                Ok::<_, serde_json::Error>(part)
+            });
+            let sent = match part {
+                Ok(part) => tx.send(Ok(part)).await.is_ok(),
+                Err(e) => {
+                    warn!("Streamed batch failed: {}", e);
+                    let _ = tx.send(Err(std::io::Error::other(e))).await;
+                    false
+                }
+            };
+            if !sent {
+                // Dropping the receiver stops the computation at its next chunk
+                return;
+            }
+        }
+        if let Err(e) = compute.await {
+            warn!("Streamed batch failed: {}", e);
+            let _ = tx.send(Err(std::io::Error::other(e))).await;
+            return;
+        }
+        let _ = tx.send(Ok(format.tail().to_string())).await;
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, format.content_type().to_string()), (X_GRAPH_EPOCH, epoch.to_string())],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/distance/multi-source",
+    tag = "queries",
+    params(GraphParam),
+    request_body = MultiSourceDistanceRequest,
+    responses(
+        (status = 200, description = "Minimum distance from any of up to 1000 sources to the target, and the source achieving it", body = bfs::MultiSourceResult),
+        (status = 400, description = "Invalid parameters or too many sources", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists, or unregistered source with RESTRICT_SOURCES", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse)
+    )
+)]
+pub async fn multi_source_distance(
+    SelectedGraph(state): SelectedGraph,
+    sources: SourceAccess,
+    Json(mut request): Json<MultiSourceDistanceRequest>,
+) -> Result<Json<bfs::MultiSourceResult>, ErrorResponse> {
+    request.target = resolve_subject(&state, &request.target).await?.to_hex();
+    validate_max_hops(&state.config, request.max_hops)?;
+
+    if request.sources.len() > MULTI_SOURCE_MAX_SOURCES {
+        return Err(ErrorResponse::invalid(
+            "TOO_MANY_SOURCES",
+            format!("Maximum {} sources allowed", MULTI_SOURCE_MAX_SOURCES),
+        ));
+    }
+
+    resolve_subjects(&state, request.sources.iter_mut().collect()).await?;
+    for source in &request.sources {
+        sources.check(&state.access, source)?;
+    }
+
+    // CPU-bound BFS → blocking thread pool (keeps async workers free)
+    let graph = state.graph.clone();
+    let query = bfs::MultiSourceQuery {
+        sources: request.sources.iter().map(|s| validate_pubkey(s)).collect::<Result<_, _>>()?,
+        target: validate_pubkey(&request.target)?,
+        max_hops: request.max_hops,
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        bfs::compute_multi_source_distance(&graph, &query)
+    })
+    .await
+    .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    Ok(Json(result))
+}
+
+#[utoipa::path(
+    post,
+    path = "/distance/sets",
+    tag = "queries",
+    params(GraphParam),
+    request_body = SetDistanceRequest,
+    responses(
+        (status = 200, description = "How many from x to pairs are each number of hops apart. With `Accept: text/csv`, a `hops,pairs` row per distance and an `unreachable` row", content(
+            (bfs::SetDistanceResult = "application/json"),
+            (String = "text/csv")
+        )),
+        (status = 400, description = "Invalid parameters or sets too large", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists, or unregistered source with RESTRICT_SOURCES", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse)
+    )
+)]
+pub async fn set_distance(
+    SelectedGraph(state): SelectedGraph,
+    sources: SourceAccess,
+    headers: HeaderMap,
+    Json(mut request): Json<SetDistanceRequest>,
+) -> Result<Response, ErrorResponse> {
+    validate_max_hops(&state.config, request.max_hops)?;
+
+    let largest = request.from.len().max(request.to.len());
+    let smallest = request.from.len().min(request.to.len());
+    if largest > SET_DISTANCE_MAX_SIZE || smallest > SET_DISTANCE_MAX_SEARCHES {
+        return Err(ErrorResponse::invalid(
+            "TOO_MANY_PUBKEYS",
+            format!(
+                "Maximum {} pubkeys per set, and {} in the smaller set",
+                SET_DISTANCE_MAX_SIZE, SET_DISTANCE_MAX_SEARCHES
+            ),
+        ));
+    }
+
+    for source in &mut request.from {
+        validate_subject(&state, source)?;
+        source.make_ascii_lowercase();
+        sources.check(&state.access, source)?;
+    }
+    for target in &mut request.to {
+        validate_subject(&state, target)?;
+        target.make_ascii_lowercase();
+    }
+
+    // CPU-bound BFS → blocking thread pool (keeps async workers free)
+    let graph = state.graph.clone();
+    let query = bfs::SetDistanceQuery {
+        from: request.from.iter().map(|s| validate_pubkey(s)).collect::<Result<_, _>>()?,
+        to: request.to.iter().map(|s| validate_pubkey(s)).collect::<Result<_, _>>()?,
+        max_hops: request.max_hops,
+    };
+
+    let result = tokio::task::spawn_blocking(move || bfs::compute_set_distances(&graph, &query))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    if csv::accepts_csv(&headers) {
+        let mut body = csv::record(["hops", "pairs"]);
+        for (hops, pairs) in result.histogram.iter().enumerate() {
+            body.push_str(&csv::record([hops.to_string(), pairs.to_string()]));
+        }
+        body.push_str(&csv::record(["unreachable".to_string(), result.unreachable.to_string()]));
+        return Ok(([(X_GRAPH_EPOCH, result.epoch.to_string())], csv::respond(body)).into_response());
+    }
+    Ok(Json(result).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/verify/batch",
+    tag = "queries",
+    params(GraphParam),
+    request_body = VerifyBatchRequest,
+    responses(
+        (status = 200, description = "Direct follow edges between each of up to 5000 pubkey pairs. With `Accept: text/csv`, a `from,to,follows,followed_by,mutual` row per pair", content(
+            (VerifyBatchResponse = "application/json"),
+            (String = "text/csv")
+        )),
+        (status = 400, description = "Invalid pubkey or too many pairs", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse)
+    )
+)]
+pub async fn verify_batch(
+    SelectedGraph(state): SelectedGraph,
+    headers: HeaderMap,
+    Json(request): Json<VerifyBatchRequest>,
+) -> Result<Response, ErrorResponse> {
+    if request.pairs.len() > VERIFY_BATCH_MAX_PAIRS {
+        return Err(ErrorResponse::invalid(
+            "TOO_MANY_PAIRS",
+            format!("Maximum {} pairs allowed per batch", VERIFY_BATCH_MAX_PAIRS),
+        ));
+    }
+    // Hex only: resolving thousands of NIP-05 names would dwarf the checks themselves
+    let pairs = request
+        .pairs
+        .iter()
+        .map(|pair| Ok((validate_subject(&state, &pair.from)?, validate_subject(&state, &pair.to)?)))
+        .collect::<Result<Vec<(Pubkey, Pubkey)>, ErrorResponse>>()?;
+
+    let graph = state.graph.clone();
+    let (pairs, response) = tokio::task::spawn_blocking(move || {
+        let ids: Vec<(Option<NodeId>, Option<NodeId>)> =
+            pairs.iter().map(|(from, to)| (graph.node_id(from), graph.node_id(to))).collect();
+        // One snapshot for the whole batch
+        let response = graph.with_snapshot(|epoch, follows, _| {
+            let is_direct = |from: NodeId, to: NodeId| follows[from as usize].binary_search(&to).is_ok();
+            let results = ids
+                .into_iter()
+                .map(|ids| match ids {
+                    (Some(from), Some(to)) => {
+                        let (follows, followed_by) = (is_direct(from, to), is_direct(to, from));
+                        EdgeCheck { follows, followed_by, mutual: follows && followed_by }
+                    }
+                    _ => EdgeCheck { follows: false, followed_by: false, mutual: false },
+                })
+                .collect();
+            VerifyBatchResponse { epoch, results }
+        });
+        (pairs, response)
+    })
+    .await
+    .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    if csv::accepts_csv(&headers) {
+        let mut body = csv::record(["from", "to", "follows", "followed_by", "mutual"]);
+        for ((from, to), check) in pairs.iter().zip(&response.results) {
+            body.push_str(&csv::record([
+                from.to_hex(),
+                to.to_hex(),
+                check.follows.to_string(),
+                check.followed_by.to_string(),
+                check.mutual.to_string(),
+            ]));
+        }
+        return Ok(([(X_GRAPH_EPOCH, response.epoch.to_string())], csv::respond(body)).into_response());
+    }
+    Ok(Json(response).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/follows",
+    tag = "queries",
+    params(FollowsQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Pubkeys followed by the given pubkey. With `Accept: text/csv`, one `pubkey` row per follow", content(
+            (FollowsResponse = "application/json"),
+            (String = "text/csv")
+        )),
+        (status = 400, description = "Invalid pubkey", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse)
+    )
+)]
+pub async fn get_follows(
+    SelectedGraph(state): SelectedGraph,
+    headers: HeaderMap,
+    Query(params): Query<FollowsQueryParams>,
+) -> Result<Response, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &params.pubkey).await?;
+
+    let follows = state.graph.get_follows(&pubkey).unwrap_or_default();
+
+    if csv::accepts_csv(&headers) {
+        let mut body = csv::record(["pubkey"]);
+        for pubkey in &follows {
+            body.push_str(&csv::record([pubkey.to_hex()]));
+        }
+        return Ok(csv::respond(body));
+    }
+    Ok(Json(FollowsResponse { pubkey, follows })
+    .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/node/{pubkey}",
+    tag = "queries",
+    params(("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"), GraphParam),
+    responses(
+        (status = 200, description = "Contact list, degree and persistence metadata for a pubkey", body = NodeResponse),
+        (status = 400, description = "Invalid pubkey", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 404, description = "Pubkey not in graph", body = ErrorResponse)
+    )
+)]
+pub async fn get_node(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+) -> Result<Json<NodeResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+
+    let node_id = state.graph.node_id(&pubkey).ok_or_else(ErrorResponse::not_found)?;
+    let info = state.graph.get_node_info(&pubkey);
+    let (follow_count, follower_count) = state.graph.degree(node_id);
+    let mutual_count = state.graph.mutual_count(node_id);
+
+    let hex = pubkey.to_hex();
+    let db = state.db.clone();
+    let key = hex.clone();
+    let (timestamps, report_count) =
+        tokio::task::spawn_blocking(move || anyhow::Ok((db.node_timestamps(&key)?, db.report_count(&key)?)))
+            .await
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    let kind3_event_id = info.as_ref().and_then(|i| i.kind3_event_id.clone());
+    let confidence = kind3_event_id.as_deref().and_then(|id| state.sightings.confidence(&hex, id));
+    Ok(Json(NodeResponse {
+        pubkey,
+        kind3_event_id,
+        kind3_created_at: info.as_ref().and_then(|i| i.kind3_created_at),
+        confidence,
+        follow_count,
+        follower_count,
+        mutual_count,
+        follow_back_ratio: (follow_count > 0).then(|| mutual_count as f64 / follow_count as f64),
+        follow_ratio: (follower_count > 0).then(|| follow_count as f64 / follower_count as f64),
+        first_seen: timestamps.and_then(|(first_seen, _)| first_seen),
+        last_updated: timestamps.map(|(_, updated_at)| updated_at),
+        in_largest_component: state.components.in_largest(node_id),
+        report_count,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/score/{pubkey}",
+    tag = "queries",
+    params(("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"), GraphParam),
+    responses(
+        (status = 200, description = "Hop distances from the configured anchors and a derived 0-100 trust score; pubkeys not in the graph score 0", body = ScoreResponse),
+        (status = 400, description = "Invalid pubkey", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 503, description = "No anchor distances computed yet, or ANCHOR_PUBKEYS unset", body = ErrorResponse)
+    )
+)]
+pub async fn get_score(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+) -> Result<Json<ScoreResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    if state.anchors.is_empty() {
+        return Err(ErrorResponse::no_anchors(&state.anchors));
+    }
+
+    let hops = match state.graph.node_id(&pubkey) {
+        Some(node_id) => state.anchors.hops_to(node_id),
+        None => vec![None; state.anchors.len()],
+    };
+    let reached: Vec<u32> = hops.iter().flatten().copied().collect();
+    Ok(Json(ScoreResponse {
+        pubkey,
+        anchors: hops.len(),
+        reached: reached.len(),
+        min_hops: reached.iter().min().copied(),
+        avg_hops: (!reached.is_empty())
+            .then(|| reached.iter().sum::<u32>() as f64 / reached.len() as f64),
+        score: trust_score(&hops),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/peers/{pubkey}",
+    tag = "queries",
+    params(
+        ("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"),
+        PeersQueryParams,
+        GraphParam
+    ),
+    responses(
+        (status = 200, description = "Accounts whose follow lists overlap most with the pubkey's; empty for lists under 10 follows", body = PeersResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 404, description = "Pubkey not in graph", body = ErrorResponse)
+    )
+)]
+pub async fn get_peers(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+    Query(params): Query<PeersQueryParams>,
+) -> Result<Json<PeersResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    let limit = params.limit.unwrap_or(PEERS_LIMIT_DEFAULT);
+    if !(1..=PEERS_LIMIT_MAX).contains(&limit) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_LIMIT",
+            format!("limit must be between 1 and {}", PEERS_LIMIT_MAX),
+        ));
+    }
+
+    let node_id = state.graph.node_id(&pubkey).ok_or_else(ErrorResponse::not_found)?;
+    let graph = state.graph.clone();
+    // Extra peers make up for ones the access lists hide
+    let matches = tokio::task::spawn_blocking(move || graph.peers(node_id, PEERS_LIMIT_MAX * 2))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    let peers = matches
+        .into_iter()
+        .filter_map(|(id, similarity, shared_follows)| {
+            let pubkey = state.graph.pubkey(id).filter(|pk| state.access.is_pubkey_allowed(&pk.to_hex()))?;
+            Some(Peer {
+                pubkey,
+                similarity,
+                shared_follows,
+                follow_count: state.graph.degree(id).0,
+            })
+        })
+        .take(limit)
+        .collect();
+
+    Ok(Json(PeersResponse {
+        follow_count: state.graph.degree(node_id).0,
+        pubkey,
+        peers,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/weak-ties/{pubkey}",
+    tag = "queries",
+    params(
+        ("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"),
+        WeakTiesQueryParams,
+        GraphParam
+    ),
+    responses(
+        (status = 200, description = "Follows that are the pubkey's only connection to parts of its network", body = WeakTiesResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 404, description = "Pubkey not in graph", body = ErrorResponse)
+    )
+)]
+pub async fn get_weak_ties(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+    Query(params): Query<WeakTiesQueryParams>,
+) -> Result<Json<WeakTiesResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    let max_hops = params.max_hops.unwrap_or(2);
+    if !(2..=EGO_MAX_HOPS).contains(&max_hops) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_MAX_HOPS",
+            format!("max_hops must be between 2 and {}", EGO_MAX_HOPS),
+        ));
+    }
+    let limit = params.limit.unwrap_or(EGO_LIMIT_DEFAULT);
+    if !(1..=EGO_LIMIT_MAX).contains(&limit) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_LIMIT",
+            format!("limit must be between 1 and {}", EGO_LIMIT_MAX),
+        ));
+    }
+
+    let node_id = state.graph.node_id(&pubkey).ok_or_else(ErrorResponse::not_found)?;
+    let graph = state.graph.clone();
+    let found = tokio::task::spawn_blocking(move || ego::weak_ties(&graph, node_id, max_hops))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    let ties = found
+        .ties
+        .into_iter()
+        .filter_map(|tie| {
+            let pubkey = state.graph.pubkey(tie.follow).filter(|pk| state.access.is_pubkey_allowed(&pk.to_hex()))?;
+            Some(WeakTie { pubkey, exclusive_reach: tie.exclusive, local_bridge: tie.local_bridge })
+        })
+        .take(limit)
+        .collect();
+
+    Ok(Json(WeakTiesResponse {
+        follow_count: state.graph.degree(node_id).0,
+        pubkey,
+        max_hops,
+        reached: found.reached,
+        ties,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/ego/cut-vertices/{pubkey}",
+    tag = "queries",
+    params(
+        ("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"),
+        CutVerticesQueryParams,
+        GraphParam
+    ),
+    responses(
+        (status = 200, description = "Articulation points of the pubkey's ego network", body = CutVerticesResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 404, description = "Pubkey not in graph", body = ErrorResponse)
+    )
+)]
+pub async fn get_cut_vertices(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+    Query(params): Query<CutVerticesQueryParams>,
+) -> Result<Json<CutVerticesResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    let max_hops = params.max_hops.unwrap_or(2);
+    if !(1..=EGO_MAX_HOPS).contains(&max_hops) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_MAX_HOPS",
+            format!("max_hops must be between 1 and {}", EGO_MAX_HOPS),
+        ));
+    }
+    let limit = params.limit.unwrap_or(EGO_LIMIT_DEFAULT);
+    if !(1..=EGO_LIMIT_MAX).contains(&limit) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_LIMIT",
+            format!("limit must be between 1 and {}", EGO_LIMIT_MAX),
+        ));
+    }
+
+    let node_id = state.graph.node_id(&pubkey).ok_or_else(ErrorResponse::not_found)?;
+    let graph = state.graph.clone();
+    let found = tokio::task::spawn_blocking(move || ego::cut_vertices(&graph, node_id, max_hops))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    let cut_vertices = found
+        .cut_vertices
+        .into_iter()
+        .filter_map(|cut| {
+            let pubkey = state.graph.pubkey(cut.node).filter(|pk| state.access.is_pubkey_allowed(&pk.to_hex()))?;
+            Some(CutVertex { pubkey, hops: cut.hops, separated: cut.separated })
+        })
+        .take(limit)
+        .collect();
+
+    Ok(Json(CutVerticesResponse {
+        pubkey,
+        max_hops,
+        nodes: found.nodes,
+        truncated: found.truncated,
+        cut_vertices,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/botscore/{pubkey}",
+    tag = "queries",
+    params(("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"), GraphParam),
+    responses(
+        (status = 200, description = "Bot-likelihood score and the features behind it, as of the last background computation", body = BotScoreResponse),
+        (status = 400, description = "Invalid pubkey", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 404, description = "Pubkey not in graph", body = ErrorResponse),
+        (status = 503, description = "No scores computed yet, or BOTSCORE_INTERVAL_SECS is 0", body = ErrorResponse)
+    )
+)]
+pub async fn get_botscore(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+) -> Result<Json<BotScoreResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    let computed_at = state.botscores.computed_at().ok_or_else(|| ErrorResponse::no_botscore(&state.config))?;
+    let node_id = state.graph.node_id(&pubkey).ok_or_else(ErrorResponse::not_found)?;
+
+    let scored = state.botscores.get(node_id);
+    Ok(Json(BotScoreResponse {
+        pubkey,
+        score: scored.map(|s| s.score),
+        features: scored.map(|s| s.features),
+        computed_at,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/lookalikes/{pubkey}",
+    tag = "queries",
+    params(
+        ("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"),
+        LookalikesQueryParams,
+        GraphParam
+    ),
+    responses(
+        (status = 200, description = "Accounts with near-identical follow lists, e.g. impersonators or spam farms; empty for lists under 10 follows", body = LookalikesResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse),
+        (status = 404, description = "Pubkey not in graph", body = ErrorResponse)
+    )
+)]
+pub async fn get_lookalikes(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+    Query(params): Query<LookalikesQueryParams>,
+) -> Result<Json<LookalikesResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    let limit = params.limit.unwrap_or(LOOKALIKES_LIMIT_DEFAULT);
+    if !(1..=LOOKALIKES_LIMIT_MAX).contains(&limit) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_LIMIT",
+            format!("limit must be between 1 and {}", LOOKALIKES_LIMIT_MAX),
+        ));
+    }
+    let min_similarity = params.min_similarity.unwrap_or(LOOKALIKES_MIN_SIMILARITY_DEFAULT);
+    if !(0.0..=1.0).contains(&min_similarity) {
+        return Err(ErrorResponse::invalid("INVALID_SIMILARITY", "min_similarity must be between 0 and 1"));
+    }
+
+    let node_id = state.graph.node_id(&pubkey).ok_or_else(ErrorResponse::not_found)?;
+    let graph = state.graph.clone();
+    // Extra matches make up for ones the access lists hide
+    let matches = tokio::task::spawn_blocking(move || graph.lookalikes(node_id, min_similarity, LOOKALIKES_LIMIT_MAX * 2))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    let lookalikes = matches
+        .into_iter()
+        .filter_map(|(id, similarity)| {
+            let pubkey = state.graph.pubkey(id).filter(|pk| state.access.is_pubkey_allowed(&pk.to_hex()))?;
+            Some(Lookalike {
+                pubkey,
+                similarity,
+                follow_count: state.graph.degree(id).0,
+            })
+        })
+        .take(limit)
+        .collect();
+
+    Ok(Json(LookalikesResponse {
+        follow_count: state.graph.degree(node_id).0,
+        pubkey,
+        lookalikes,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/history/followers/{pubkey}",
+    tag = "queries",
+    params(
+        ("pubkey" = String, Path, description = "Hex pubkey, npub, nprofile or NIP-05 identifier"),
+        FollowerHistoryQueryParams,
+        GraphParam
+    ),
+    responses(
+        (status = 200, description = "Recorded follower counts over time; empty if the pubkey is not tracked", body = FollowerHistoryResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse)
+    )
+)]
+pub async fn get_follower_history(
+    SelectedGraph(state): SelectedGraph,
+    Path(pubkey): Path<String>,
+    Query(params): Query<FollowerHistoryQueryParams>,
+) -> Result<Json<FollowerHistoryResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &pubkey).await?;
+    let granularity = match params.granularity.as_deref() {
+        None => Granularity::Day,
+        Some(g) => Granularity::parse(g).ok_or_else(|| ErrorResponse::invalid(
+            "INVALID_GRANULARITY",
+            "granularity must be hour, day or week",
+        ))?,
+    };
+
+    let db = state.db.clone();
+    let key = pubkey.to_hex();
+    let snapshots = tokio::task::spawn_blocking(move || db.follower_history(&key))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    let points = history::bucket(&snapshots, granularity)
+        .into_iter()
+        .map(|(timestamp, follower_count)| FollowerHistoryPoint { timestamp, follower_count })
+        .collect();
+
+    Ok(Json(FollowerHistoryResponse {
+        pubkey,
+        granularity: granularity.as_str().to_string(),
+        points,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/sample",
+    tag = "queries",
+    params(SampleQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Randomly sampled pubkeys with their follow and follower counts", body = SampleResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse)
+    )
+)]
+pub async fn get_sample(
+    SelectedGraph(state): SelectedGraph,
+    Query(params): Query<SampleQueryParams>,
+) -> Result<Json<SampleResponse>, ErrorResponse> {
+    let n = params.n.unwrap_or(SAMPLE_SIZE_DEFAULT);
+    if !(1..=SAMPLE_SIZE_MAX).contains(&n) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_SAMPLE_SIZE",
+            format!("n must be between 1 and {}", SAMPLE_SIZE_MAX),
+        ));
+    }
+    let weighting = match params.weighting.as_deref() {
+        None => Weighting::Uniform,
+        Some(w) => Weighting::parse(w).ok_or_else(|| ErrorResponse::invalid(
+            "INVALID_WEIGHTING",
+            "weighting must be uniform or degree",
+        ))?,
+    };
+    // Random seeds stay below 2^53 so JSON clients can pass them back unchanged
+    let seed = params.seed.unwrap_or_else(|| {
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64 & ((1 << 53) - 1)
+    });
+
+    let min_followers = params.min_followers;
+    let (graph, access) = (state.graph.clone(), state.access.clone());
+    let (population, sampled) = tokio::task::spawn_blocking(move || {
+        sample::sample(&graph, n, min_followers, weighting, seed, |pk| access.is_pubkey_allowed(&pk.to_hex()))
+    })
     .await
     .map_err(|e| ErrorResponse::internal(e.to_string()))?;
 
-    Ok(Json(PathResponse {
+    let mean = |count: fn(&sample::Sampled) -> usize| {
+        (!sampled.is_empty()).then(|| sampled.iter().map(count).sum::<usize>() as f64 / sampled.len() as f64)
+    };
+    Ok(Json(SampleResponse {
+        seed,
+        weighting: weighting.as_str().to_string(),
+        min_followers,
+        population,
+        mean_follow_count: mean(|s| s.follow_count),
+        mean_follower_count: mean(|s| s.follower_count),
+        pubkeys: sampled
+            .into_iter()
+            .map(|s| SampledPubkey { pubkey: s.pubkey, follow_count: s.follow_count, follower_count: s.follower_count })
+            .collect(),
+    }))
+}
+
+/// Default `/diff` window length
+const DIFF_DEFAULT_WINDOW_SECS: i64 = 30 * 86_400;
+
+/// `(from_ts, to_ts)` for a diff window, defaulting to the 30 days up to now
+fn diff_window(from_ts: Option<i64>, to_ts: Option<i64>) -> Result<(i64, i64), ErrorResponse> {
+    let to_ts = to_ts.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let from_ts = from_ts.unwrap_or(to_ts - DIFF_DEFAULT_WINDOW_SECS);
+    if from_ts >= to_ts {
+        return Err(ErrorResponse::invalid("INVALID_RANGE", "from_ts must be before to_ts"));
+    }
+    Ok((from_ts, to_ts))
+}
+
+#[utoipa::path(
+    get,
+    path = "/diff",
+    tag = "queries",
+    params(DiffQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Follows the pubkey added and removed within the window", body = DiffResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse)
+    )
+)]
+pub async fn get_diff(
+    SelectedGraph(state): SelectedGraph,
+    Query(params): Query<DiffQueryParams>,
+) -> Result<Json<DiffResponse>, ErrorResponse> {
+    let pubkey = resolve_subject(&state, &params.pubkey).await?;
+    let (from_ts, to_ts) = diff_window(params.from_ts, params.to_ts)?;
+
+    let db = state.db.clone();
+    let key = pubkey.to_hex();
+    let (added, removed) = tokio::task::spawn_blocking(move || db.edge_changes(&key, from_ts, to_ts))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    Ok(Json(DiffResponse { pubkey, from_ts, to_ts, added, removed }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/diff/daily",
+    tag = "queries",
+    params(DailyDiffQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Follows added and removed across the graph per UTC day", body = DailyDiffResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse)
+    )
+)]
+pub async fn get_daily_diff(
+    SelectedGraph(state): SelectedGraph,
+    Query(params): Query<DailyDiffQueryParams>,
+) -> Result<Json<DailyDiffResponse>, ErrorResponse> {
+    let (from_ts, to_ts) = diff_window(params.from_ts, params.to_ts)?;
+
+    let db = state.db.clone();
+    let days = tokio::task::spawn_blocking(move || db.daily_edge_changes(from_ts, to_ts))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .into_iter()
+        .map(|(day, added, removed)| DailyEdgeChanges { day, added, removed })
+        .collect();
+
+    Ok(Json(DailyDiffResponse { from_ts, to_ts, days }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/common-follows",
+    tag = "queries",
+    params(CommonFollowsQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Pubkeys followed by both pubkeys", body = CommonFollowsResponse),
+        (status = 400, description = "Invalid pubkey", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists", body = ErrorResponse)
+    )
+)]
+pub async fn get_common_follows(
+    SelectedGraph(state): SelectedGraph,
+    Query(params): Query<CommonFollowsQueryParams>,
+) -> Result<Json<CommonFollowsResponse>, ErrorResponse> {
+    let from = resolve_subject(&state, &params.from).await?;
+    let to = resolve_subject(&state, &params.to).await?;
+
+    let from_follows = state.graph.get_follows(&from).unwrap_or_default();
+    let to_follows = state.graph.get_follows(&to).unwrap_or_default();
+
+    // Find intersection - both lists are from sorted internal storage
+    let from_set: std::collections::HashSet<_> = from_follows.into_iter().collect();
+    let common_follows: Vec<Pubkey> = to_follows
+        .into_iter()
+        .filter(|f| from_set.contains(f))
+        .collect();
+
+    Ok(Json(CommonFollowsResponse {
+        from,
+        to,
+        common_follows,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/endorsers",
+    tag = "queries",
+    params(EndorsersQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Pubkeys `from` follows (or reaches in two hops) that follow `to`", body = EndorsersResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists, or unregistered source with RESTRICT_SOURCES", body = ErrorResponse)
+    )
+)]
+pub async fn get_endorsers(
+    SelectedGraph(state): SelectedGraph,
+    sources: SourceAccess,
+    Query(params): Query<EndorsersQueryParams>,
+) -> Result<Json<EndorsersResponse>, ErrorResponse> {
+    let from = resolve_subject(&state, &params.from).await?;
+    let to = resolve_subject(&state, &params.to).await?;
+    sources.check(&state.access, &from.to_hex())?;
+    let limit = params.limit.unwrap_or(ENDORSERS_LIMIT_DEFAULT);
+    if !(1..=ENDORSERS_LIMIT_MAX).contains(&limit) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_LIMIT",
+            format!("limit must be between 1 and {}", ENDORSERS_LIMIT_MAX),
+        ));
+    }
+
+    let (direct, indirect) = match (state.graph.node_id(&from), state.graph.node_id(&to)) {
+        (Some(from), Some(to)) => {
+            let graph = state.graph.clone();
+            tokio::task::spawn_blocking(move || graph.endorsers(from, to, params.two_hop))
+                .await
+                .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        }
+        _ => (Vec::new(), Vec::new()),
+    };
+
+    // Hidden pubkeys are left out of the counts too; the rest are ranked by follower count
+    let rank = |ids: Vec<NodeId>| -> (usize, Vec<Pubkey>) {
+        let mut ranked: Vec<(usize, Pubkey)> = ids
+            .into_iter()
+            .filter_map(|id| {
+                let pubkey = state.graph.pubkey(id).filter(|pk| state.access.is_pubkey_allowed(&pk.to_hex()))?;
+                Some((state.graph.degree(id).1, pubkey))
+            })
+            .collect();
+        ranked.sort_unstable_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        let count = ranked.len();
+        (count, ranked.into_iter().take(limit).map(|(_, pk)| pk).collect())
+    };
+    let (count, endorsers) = rank(direct);
+    let (two_hop_count, two_hop) = match params.two_hop {
+        true => {
+            let (count, pubkeys) = rank(indirect);
+            (Some(count), Some(pubkeys))
+        }
+        false => (None, None),
+    };
+
+    Ok(Json(EndorsersResponse {
+        from,
+        to,
+        count,
+        endorsers,
+        two_hop_count,
+        two_hop,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/path",
+    tag = "queries",
+    params(PathQueryParams, FieldsParam, GraphParam),
+    responses(
+        (status = 200, description = "One shortest follow path, or null if unreachable; limited to `fields` if given", body = PathResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists, or unregistered source with RESTRICT_SOURCES", body = ErrorResponse)
+    )
+)]
+pub async fn get_path(
+    SelectedGraph(state): SelectedGraph,
+    sources: SourceAccess,
+    caller: Option<Extension<Caller>>,
+    Query(mut params): Query<PathQueryParams>,
+    Query(fields): Query<FieldsParam>,
+) -> Result<Response, ErrorResponse> {
+    let fields = fields.parse(PATH_FIELDS)?;
+    params.from = resolve_subject(&state, &params.from).await?.to_hex();
+    sources.check(&state.access, &params.from)?;
+    params.to = resolve_subject(&state, &params.to).await?.to_hex();
+    validate_max_hops(&state.config, params.max_hops)?;
+    let (from, to) = (validate_pubkey(&params.from)?, validate_pubkey(&params.to)?);
+    let (max_hops, adaptive_hops) = state.config.hop_policy().apply(&state.graph, &from, &to, params.max_hops);
+
+    let graph = state.graph.clone();
+    let query = bfs::PathQuery {
+        from,
+        to,
+        max_hops,
+        min_followers: params.min_followers,
+    };
+
+    let (path, truncated) = if muted_by(caller).contains(&params.to) {
+        (None, false)
+    } else {
+        let result = tokio::task::spawn_blocking(move || bfs::compute_path(&graph, &query))
+            .await
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+        (result.path, result.truncated)
+    };
+
+    fields.respond(PathResponse {
         from: params.from,
         to: params.to,
-        path: result.path.map(|p| p.into_iter().map(|s| s.to_string()).collect()),
-    }))
+        path: path.map(|p| p.into_iter().map(|s| s.to_string()).collect()),
+        truncated,
+        adaptive_hops,
+    })
 }
 
-pub async fn get_stats(State(state): State<AppState>) -> Json<StatsResponse> {
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "service",
+    params(GraphParam),
+    responses((status = 200, description = "Graph, cache and lock statistics", body = StatsResponse))
+)]
+pub async fn get_stats(SelectedGraph(state): SelectedGraph) -> Json<StatsResponse> {
     let stats = state.graph.stats();
     let cache_stats = state.cache.stats();
     let lock_metrics = state.graph.lock_metrics();
@@ -384,64 +2363,205 @@ pub async fn get_stats(State(state): State<AppState>) -> Json<StatsResponse> {
         node_count: stats.node_count,
         edge_count: stats.edge_count,
         nodes_with_follows: stats.nodes_with_follows,
+        quarantined_nodes: stats.quarantined_nodes,
+        rejected_lists: stats.rejected_lists,
+        oversized_lists: stats.oversized_lists,
+        truncated_lists: stats.truncated_lists,
+        duplicate_follows: stats.duplicate_follows,
         cache: cache_stats,
         locks: lock_metrics,
+        lock_shards: state.graph.shard_lock_metrics(),
+        dvm: state.config.dvm_enabled.then(|| state.dvm_stats.snapshot()),
+        ingestion: state.ingestion_pause.status(),
+        persistence: state.persistence.snapshot(),
+        jobs: state.jobs.stats(),
     })
 }
 
-pub async fn health() -> Json<HealthResponse> {
+#[utoipa::path(
+    get,
+    path = "/stats/popular",
+    tag = "service",
+    params(PopularQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Most queried distance pairs and pubkeys, by decaying query count", body = PopularResponse),
+        (status = 400, description = "Invalid limit", body = ErrorResponse)
+    )
+)]
+pub async fn get_popular(
+    SelectedGraph(state): SelectedGraph,
+    Query(params): Query<PopularQueryParams>,
+) -> Result<Json<PopularResponse>, ErrorResponse> {
+    let limit = params.limit.unwrap_or(POPULAR_LIMIT_DEFAULT);
+    if !(1..=POPULAR_LIMIT_MAX).contains(&limit) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_LIMIT",
+            format!("limit must be between 1 and {}", POPULAR_LIMIT_MAX),
+        ));
+    }
+
+    // Pubkeys excluded by the access lists are left out, as everywhere else
+    let allowed = |id: NodeId| state.graph.pubkey(id).filter(|pk| state.access.is_pubkey_allowed(&pk.to_hex()));
+    let pairs = state
+        .popularity
+        .top_pairs(POPULAR_LIMIT_MAX)
+        .into_iter()
+        .filter_map(|(key, score)| {
+            Some(PopularPair {
+                from: allowed(key.from_id)?,
+                to: allowed(key.to_id)?,
+                max_hops: key.max_hops,
+                include_bridges: key.include_bridges,
+                score,
+            })
+        })
+        .take(limit)
+        .collect();
+    let pubkeys = state
+        .popularity
+        .top_subjects(POPULAR_LIMIT_MAX)
+        .into_iter()
+        .filter_map(|(id, score)| Some(PopularPubkey { pubkey: allowed(id)?, score }))
+        .take(limit)
+        .collect();
+
+    Ok(Json(PopularResponse {
+        half_life_secs: state.popularity.half_life_secs(),
+        pairs,
+        pubkeys,
+        popular_anchors: state
+            .popular_anchors
+            .pubkeys()
+            .into_iter()
+            .filter(|pk| state.access.is_pubkey_allowed(pk))
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "service",
+    responses((status = 200, description = "Service is up", body = HealthResponse))
+)]
+pub async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
+    let ingestion = state.ingestion_pause.status();
     Json(HealthResponse {
-        status: "healthy".to_string(),
+        status: if ingestion.paused { "maintenance" } else { "healthy" }.to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
+        ingestion,
     })
 }
 
-pub fn create_router(state: AppState, rate_limit_per_minute: u32) -> Router {
+pub fn create_router(state: AppState) -> Router {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
 
-    // Per-IP rate limiting with token bucket algorithm
-    let per_second = std::cmp::max(1, rate_limit_per_minute / 60);
-    let burst_size = std::cmp::max(5, rate_limit_per_minute / 6); // 10 sec burst
-
-    let governor_conf = GovernorConfigBuilder::default()
-        .per_second(per_second as u64)
-        .burst_size(burst_size)
-        .key_extractor(SmartIpKeyExtractor)
-        .finish()
-        .unwrap();
-
+    // Per-IP weighted rate limiting with token bucket algorithm
     info!(
-        "Rate limiter: {} req/sec, burst size {}, body limit {}KB",
-        per_second, burst_size, REQUEST_BODY_LIMIT / 1024
+        "Rate limiter: {} tokens/min (admin {}, explorer {} tokens/min), burst size {}, body limit {}KB",
+        state.config.rate_limit_per_minute,
+        state.config.admin_rate_limit_per_minute,
+        state.config.explore_rate_limit_per_minute,
+        state.rate_limits.read_burst(),
+        REQUEST_BODY_LIMIT / 1024
     );
 
-    Router::new()
+    let router = Router::new()
         .route("/health", get(health))
         .route("/stats", get(get_stats))
+        .route("/stats/popular", get(get_popular))
+        .route("/metrics", get(metrics::get_metrics))
         .route("/distance", get(get_distance))
         .route("/distance/batch", post(batch_distance))
+        .route("/distance/multi-source", post(multi_source_distance))
+        .route("/distance/sets", post(set_distance))
+        .route("/simulate/reach", post(simulate::simulate_reach))
+        .route("/verify/batch", post(verify_batch))
         .route("/follows", get(get_follows))
+        .route("/node/:pubkey", get(get_node))
+        .route("/score/:pubkey", get(get_score))
+        .route("/lookalikes/:pubkey", get(get_lookalikes))
+        .route("/peers/:pubkey", get(get_peers))
+        .route("/weak-ties/:pubkey", get(get_weak_ties))
+        .route("/ego/cut-vertices/:pubkey", get(get_cut_vertices))
+        .route("/botscore/:pubkey", get(get_botscore))
+        .route("/bloom/:pubkey", get(bloom::get_bloom))
+        .route("/proof/follows/:pubkey", get(commitment::get_follow_proof))
+        .route("/history/followers/:pubkey", get(get_follower_history))
+        .route("/diff", get(get_diff))
+        .route("/diff/daily", get(get_daily_diff))
+        .route("/outbox", get(outbox::get_outbox))
+        .route("/sample", get(get_sample))
         .route("/common-follows", get(get_common_follows))
+        .route("/endorsers", get(get_endorsers))
         .route("/path", get(get_path))
+        .route("/openapi.json", get(openapi::openapi_json))
+        .route("/docs", get(openapi::swagger_ui))
+        .route("/docs/:asset", get(openapi::swagger_ui_asset))
+        .route(
+            "/graphql",
+            get(graphql::graphiql)
+                .post(graphql::graphql_handler)
+                .layer(Extension(graphql::build_schema(state.clone()))),
+        );
+
+    let router = router.nest("/me", me::router()).nest("/explore", explore::router());
+
+    let router = match (state.config.relay_enabled, &state.oracle_keys) {
+        (true, Some(_)) => router.route("/relay", get(relay::relay_handler)),
+        (true, None) => {
+            warn!("RELAY_ENABLED set but no valid DVM_PRIVATE_KEY; relay endpoint disabled");
+            router
+        }
+        (false, _) => router,
+    };
+
+    let router = if state.config.admin_token.is_some() {
+        router.nest("/admin", admin::router(state.clone()))
+    } else {
+        info!("Admin API disabled (ADMIN_TOKEN not set)");
+        router
+    };
+
+    let router = if state.config.replication_token.is_some() {
+        router.nest("/replication", replication::router(state.clone()))
+    } else {
+        router
+    };
+
+    router
+        .layer(middleware::from_fn_with_state(state.clone(), diagnostics::track_requests))
+        .layer(middleware::from_fn_with_state(state.clone(), etag::conditional_get))
+        .layer(middleware::from_fn_with_state(state.clone(), me::apply_settings))
+        .layer(CompressionLayer::new())
         .layer(cors)
         .layer(RequestBodyLimitLayer::new(REQUEST_BODY_LIMIT))
-        .layer(GovernorLayer {
-            config: Arc::new(governor_conf),
-        })
+        .layer(middleware::from_fn_with_state(state.clone(), access::ip_filter))
+        .layer(middleware::from_fn_with_state(state.clone(), ratelimit::rate_limit))
         .with_state(state)
 }
 
-pub async fn start_server(state: AppState, port: u16, rate_limit_per_minute: u32) -> anyhow::Result<()> {
-    let router = create_router(state, rate_limit_per_minute);
+pub async fn start_server(state: AppState, port: u16) -> anyhow::Result<()> {
+    // Periodically drop rate limiter state for idle clients
+    let rate_limits = state.rate_limits.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            rate_limits.retain_recent();
+        }
+    });
+
+    let router = create_router(state);
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
 
     info!("HTTP server listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, router).await?;
+    axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
@@ -453,7 +2573,7 @@ mod tests {
     use axum::http::Request;
     use tower::ServiceExt;
 
-    /// Test router without rate limiting (SmartIpKeyExtractor fails in tests)
+    /// Test router without rate limiting (no ConnectInfo in oneshot tests)
     fn create_test_router(state: AppState) -> Router {
         let cors = CorsLayer::new()
             .allow_origin(Any)
@@ -463,11 +2583,36 @@ mod tests {
         Router::new()
             .route("/health", get(health))
             .route("/stats", get(get_stats))
+        .route("/stats/popular", get(get_popular))
+            .route("/metrics", get(metrics::get_metrics))
             .route("/distance", get(get_distance))
             .route("/distance/batch", post(batch_distance))
+            .route("/distance/multi-source", post(multi_source_distance))
+            .route("/distance/sets", post(set_distance))
+            .route("/simulate/reach", post(simulate::simulate_reach))
+            .route("/verify/batch", post(verify_batch))
             .route("/follows", get(get_follows))
+        .route("/node/:pubkey", get(get_node))
+        .route("/score/:pubkey", get(get_score))
+        .route("/lookalikes/:pubkey", get(get_lookalikes))
+        .route("/peers/:pubkey", get(get_peers))
+        .route("/weak-ties/:pubkey", get(get_weak_ties))
+        .route("/ego/cut-vertices/:pubkey", get(get_cut_vertices))
+        .route("/botscore/:pubkey", get(get_botscore))
+        .route("/bloom/:pubkey", get(bloom::get_bloom))
+        .route("/proof/follows/:pubkey", get(commitment::get_follow_proof))
+        .route("/history/followers/:pubkey", get(get_follower_history))
+        .route("/diff", get(get_diff))
+        .route("/diff/daily", get(get_daily_diff))
+        .route("/outbox", get(outbox::get_outbox))
+        .route("/sample", get(get_sample))
             .route("/common-follows", get(get_common_follows))
+            .route("/endorsers", get(get_endorsers))
             .route("/path", get(get_path))
+            .route("/docs", get(openapi::swagger_ui))
+            .route("/docs/:asset", get(openapi::swagger_ui_asset))
+            .nest("/me", me::router())
+            .nest("/explore", explore::router())
             .layer(cors)
             .with_state(state)
     }
@@ -485,11 +2630,41 @@ mod tests {
 
         let config = Arc::new(Config::from_env());
         let cache = Arc::new(QueryCache::new(config.cache_size, config.cache_ttl_secs));
+        let rate_limits = Arc::new(RateLimits::new(
+            config.rate_limit_per_minute,
+            config.admin_rate_limit_per_minute,
+        ));
+        let access = Arc::new(AccessLists::from_config(&config));
+        let ranks = Arc::new(PageRank::new());
+        ranks.refresh(&graph);
+        let components = Arc::new(Components::new());
+        components.refresh(&graph);
 
         AppState {
             graph,
             config,
             cache,
+            rate_limits,
+            access,
+            ranks,
+            components,
+            anchors: Arc::default(),
+            popular_anchors: Arc::default(),
+            popularity: Arc::default(),
+            sightings: Arc::default(),
+            botscores: Arc::default(),
+            persistence: Arc::default(),
+            jobs: Arc::default(),
+            commitments: Arc::default(),
+            db: Arc::new(Database::open(":memory:").unwrap()),
+            oracle_keys: None,
+            resolver: Arc::new(Resolver::new(false)),
+            profiles: Arc::default(),
+            dvm_stats: Arc::default(),
+            watchlist: Arc::default(),
+            ingestion_pause: Arc::default(),
+            namespaces: Arc::default(),
+            diagnostics: Arc::default(),
         }
     }
 
@@ -503,99 +2678,1098 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_paused_ingestion() {
+        let state = create_test_state();
+        state.ingestion_pause.pause(Some("compaction".to_string()), 1_700_000_000);
+        let get = |uri: &str| {
+            create_test_router(state.clone()).oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/health").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "maintenance");
+        assert_eq!(json["ingestion"]["paused_at"], 1_700_000_000);
+        assert_eq!(json["ingestion"]["reason"], "compaction");
+
+        let body = axum::body::to_bytes(get("/stats").await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["ingestion"]["paused"], true);
+
+        state.ingestion_pause.resume();
+        let body = axum::body::to_bytes(get("/health").await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "healthy");
+        assert_eq!(json["ingestion"], serde_json::json!({ "paused": false }));
+    }
+
+    #[tokio::test]
+    async fn test_stats_endpoint() {
+        let state = create_test_state();
+        let router = create_test_router(state);
+
+        let response = router
+            .oneshot(Request::builder().uri("/stats").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let shards = json["lock_shards"].as_array().unwrap();
+        assert_eq!(shards.len(), crate::graph::ADJACENCY_SHARDS);
+        let shard_writes: u64 = shards.iter().map(|s| s["write_lock_count"].as_u64().unwrap()).sum();
+        assert_eq!(json["locks"]["write_lock_count"].as_u64(), Some(shard_writes));
+        assert_eq!(json["persistence"]["dropped_updates"], 0);
+        assert!(json["persistence"].get("last_flush_at").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_outbox_endpoint() {
+        let state = create_test_state();
+        let (alice, bob) = ("a".repeat(64), "b".repeat(64));
+        state.db.update_follows(&alice, std::slice::from_ref(&bob), Some("e1"), Some(100)).unwrap();
+        state.db.update_follows(&alice, &[], Some("e2"), Some(200)).unwrap();
+        let get = |uri: &str| {
+            create_test_router(state.clone()).oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let body = axum::body::to_bytes(get("/outbox?limit=1").await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["entries"][0]["added"], serde_json::json!([bob]));
+        assert_eq!((json["next_seq"].as_i64(), json["oldest_seq"].as_i64()), (Some(1), Some(1)));
+        assert_eq!(json["gap"], false);
+
+        let body = axum::body::to_bytes(get("/outbox?since_seq=1").await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["entries"][0]["event_id"], "e2");
+        assert_eq!(json["entries"][0]["removed"], serde_json::json!([bob]));
+        assert_eq!(json["next_seq"], 2);
+
+        // Entries pruned before they were read
+        state.db.prune_outbox(i64::MAX).unwrap();
+        let body = axum::body::to_bytes(get("/outbox?since_seq=1").await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!((json["next_seq"].as_i64(), json["gap"].as_bool()), (Some(1), Some(true)));
+
+        assert_eq!(get("/outbox?limit=0").await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_swagger_ui_served_locally() {
+        let body = |response: axum::response::Response| async move {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+        let get = |router: Router, uri: &str| router.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        // Without assets the page only links the spec
+        let mut state = create_test_state();
+        state.config = Arc::new(Config { swagger_ui_dir: None, ..Config::from_env() });
+        let page = body(get(create_test_router(state), "/docs").await.unwrap()).await;
+        assert!(page.contains("/openapi.json") && !page.contains("<script"));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("swagger-ui.css"), "body {}").unwrap();
+        let mut state = create_test_state();
+        state.config = Arc::new(Config {
+            swagger_ui_dir: Some(dir.path().to_string_lossy().into_owned()),
+            ..Config::from_env()
+        });
+        let router = create_test_router(state);
+
+        // Every asset comes from this origin
+        let page = body(get(router.clone(), "/docs").await.unwrap()).await;
+        assert!(page.contains("src=\"/docs/swagger-ui-bundle.js\""));
+        assert!(!page.contains("https://"));
+
+        let response = get(router.clone(), "/docs/swagger-ui.css").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/css");
+        assert_eq!(body(response).await, "body {}");
+        // Missing files and anything besides the known assets are not found
+        for uri in ["/docs/swagger-ui-bundle.js", "/docs/index.html", "/docs/..%2Fwot.db"] {
+            assert_eq!(get(router.clone(), uri).await.unwrap().status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let state = create_test_state();
+        state.persistence.record_queued();
+        state.persistence.record_queued();
+        state.persistence.record_dropped();
+        state.persistence.record_flush(1, Some(2), Duration::from_millis(250));
+        let router = create_test_router(state);
+
+        let response = router
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/plain"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("# TYPE wot_edges gauge\nwot_edges 1\n"));
+        assert!(text.contains("wot_persistence_queue_depth 1\n"));
+        assert!(text.contains("wot_persistence_dropped_updates_total 1\n"));
+        assert!(text.contains("wot_persistence_rows_written_total 2\n"));
+        assert!(text.contains("wot_persistence_last_flush_seconds 0.25\n"));
+        assert!(!text.contains("wot_persistence_last_flush_timestamp_seconds 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_distance_endpoint() {
+        let state = create_test_state();
+        let router = create_test_router(state);
+
+        let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let to = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/distance?from={}&to={}", from, to))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_min_followers() {
+        let state = create_test_state();
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let c = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+        // b, followed only by a, is the one link from a to c
+        state.graph.update_follows(
+            "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+            &[c.to_string()],
+            None,
+            None,
+        );
+        let router = create_test_router(state);
+
+        let get = |uri: String| {
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+            }
+        };
+
+        // The unfiltered result is cached, but not served to the filtered query
+        assert_eq!(get(format!("/distance?from={}&to={}", a, c)).await["hops"], 2);
+        assert!(get(format!("/distance?from={}&to={}&min_followers=2", a, c)).await["hops"].is_null());
+        assert_eq!(get(format!("/distance?from={}&to={}&min_followers=1", a, c)).await["hops"], 2);
+
+        assert!(get(format!("/path?from={}&to={}&min_followers=2", a, c)).await["path"].is_null());
+        assert_eq!(get(format!("/path?from={}&to={}&min_followers=1", a, c)).await["path"][0], "b".repeat(64));
+    }
+
+    #[tokio::test]
+    async fn test_path_weighting() {
+        let state = create_test_state();
+        let pk = |c: &str| c.repeat(64);
+        // a reaches c through b (one follower) and through d (a thousand)
+        state.graph.update_follows(&pk("a"), &[pk("b"), pk("d")], None, None);
+        state.graph.update_follows(&pk("b"), &[pk("c")], None, None);
+        state.graph.update_follows(&pk("d"), &[pk("c")], None, None);
+        for i in 0..999 {
+            state.graph.update_follows(&format!("{:064x}", i), &[pk("d")], None, None);
+        }
+        state.ranks.refresh(&state.graph);
+        let router = create_test_router(state);
+
+        let get = |query: &str| {
+            let request = Request::builder()
+                .uri(format!("/distance?from={}&to={}&{}", pk("a"), pk("c"), query))
+                .body(Body::empty())
+                .unwrap();
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        let (status, body) = get("path_weighting=followers").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["path_count"], 2);
+        // Bridges were only collected for the weighting
+        assert!(body.get("bridges").is_none());
+        let expected = 1.0 + 2f64.ln() / 1001f64.ln();
+        assert!((body["weighted_path_count"].as_f64().unwrap() - expected).abs() < 1e-9);
+
+        let (_, body) = get("path_weighting=rank&include_bridges=true").await;
+        assert_eq!(body["bridges"].as_array().unwrap().len(), 2);
+        let weighted = body["weighted_path_count"].as_f64().unwrap();
+        assert!(weighted > 0.0 && weighted < 2.0);
+
+        let (_, body) = get("").await;
+        assert!(body.get("weighted_path_count").is_none());
+
+        let (status, body) = get("path_weighting=reputation").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "INVALID_PATH_WEIGHTING");
+    }
+
+    #[tokio::test]
+    async fn test_signed_distance_response() {
+        let keys = Keys::generate();
+        let mut state = create_test_state();
+        state.config = Arc::new(Config { sign_responses: true, ..Config::from_env() });
+        state.oracle_keys = Some(keys.clone());
+        let epoch = state.graph.epoch();
+        let router = create_test_router(state);
+
+        let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let to = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/distance?from={}&to={}", from, to))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["hops"], 1);
+
+        let attestation = &body["attestation"];
+        assert_eq!(attestation["pubkey"], keys.public_key().to_hex());
+        assert_eq!(attestation["epoch"], epoch);
+        assert_eq!(attestation["result_hash"].as_str().unwrap().len(), 64);
+        assert!(attestation["sig"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_pubkey() {
+        let state = create_test_state();
+        let router = create_test_router(state);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/distance?from=invalid&to=alsoinvalid")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_uppercase_pubkey_normalized() {
+        let state = create_test_state();
+        let router = create_test_router(state);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/distance?from={}&to={}", "A".repeat(64), "B".repeat(64)))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["from"], "a".repeat(64));
+        assert_eq!(json["hops"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_denied_pubkey() {
+        let state = create_test_state();
+        let to = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        state
+            .access
+            .add(access::AccessList::PubkeyDeny, &[to.to_string()])
+            .unwrap();
+        let router = create_test_router(state);
+
+        let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/distance?from={}&to={}", from, to))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_get_not_modified() {
+        let state = create_test_state();
+        let router = create_test_router(state.clone())
+            .layer(middleware::from_fn_with_state(state.clone(), etag::conditional_get));
+
+        let uri = "/follows?pubkey=aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
+        let response = router
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers().get(axum::http::header::ETAG).unwrap().clone();
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header(axum::http::header::IF_NONE_MATCH, etag.clone())
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // A graph update invalidates the tag
+        state.graph.update_follows(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            &["cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc".to_string()],
+            None,
+            None,
+        );
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header(axum::http::header::IF_NONE_MATCH, etag)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A matching tag never turns a failing request into a 304
+        let invalid = "/follows?pubkey=zz";
+        let any = axum::http::HeaderValue::from_static("*");
+        for tag in [etag::compute_etag(state.graph.epoch(), invalid), any] {
+            let response = create_test_router(state.clone())
+                .layer(middleware::from_fn_with_state(state.clone(), etag::conditional_get))
+                .oneshot(
+                    Request::builder()
+                        .uri(invalid)
+                        .header(axum::http::header::IF_NONE_MATCH, tag)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bypass_cache() {
+        let state = create_test_state();
+        let router = create_test_router(state.clone());
+
+        let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let to = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+        // First request without bypass_cache (populates cache)
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/distance?from={}&to={}", from, to))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Verify cache was populated
+        let from_id = state.graph.node_id(&from.parse().unwrap()).unwrap();
+        let to_id = state.graph.node_id(&to.parse().unwrap()).unwrap();
+        let cache_key = CacheKey::new(from_id, to_id, MAX_HOPS_DEFAULT, false);
+        assert!(state.cache.get(&cache_key, &state.graph).is_some());
+
+        // Second request with bypass_cache=true should still succeed
+        let router2 = create_test_router(state);
+        let response = router2
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/distance?from={}&to={}&bypass_cache=true", from, to))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_distance_freshness() {
+        let state = create_test_state();
+        let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let to = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let get = |query: &str| {
+            let uri = format!("/distance?from={}&to={}{}", from, to, query);
+            let router = create_test_router(state.clone());
+            async move {
+                let response = router.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+            }
+        };
+
+        let fresh = get("").await;
+        assert_eq!((fresh["cached"].as_bool(), fresh["graph_epoch"].as_u64()), (Some(false), Some(1)));
+        assert!(fresh["computed_at"].as_u64().unwrap() > 0);
+
+        // The cached answer keeps its original computation time and epoch, even once the
+        // graph has moved on
+        state.graph.update_follows(to, &[from.to_string()], None, None);
+        let cached = get("").await;
+        assert_eq!(cached["cached"], true);
+        assert_eq!((&cached["computed_at"], &cached["graph_epoch"]), (&fresh["computed_at"], &fresh["graph_epoch"]));
+        assert_eq!(state.graph.epoch(), 2);
+
+        let bypassed = get("&bypass_cache=true").await;
+        assert_eq!((bypassed["cached"].as_bool(), bypassed["graph_epoch"].as_u64()), (Some(false), Some(2)));
+        assert_eq!(bypassed["mutual_follow"], true);
+    }
+
+    #[tokio::test]
+    async fn test_anchor_distance() {
+        let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let to = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let mut state = create_test_state();
+        state.anchors = Arc::new(Anchors::new(vec![from.to_string()]));
+        state.anchors.refresh(&state.graph);
+
+        // Answered from the table, so nothing is cached
+        let response = create_test_router(state.clone())
+            .oneshot(Request::builder().uri(format!("/distance?from={}&to={}", from, to)).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["hops"], 1);
+        assert_eq!(json["path_count"], 1);
+        let key = CacheKey::new(state.graph.node_id(&from.parse().unwrap()).unwrap(), state.graph.node_id(&to.parse().unwrap()).unwrap(), MAX_HOPS_DEFAULT, false);
+        assert!(state.cache.get(&key, &state.graph).is_none());
+
+        // A reverse query walks from the anchor too; bridges still need a search
+        for (query, bridges) in [("reverse=true", None), ("include_bridges=true", Some(0))] {
+            let (source, target) = if bridges.is_some() { (from, to) } else { (to, from) };
+            let response = create_test_router(state.clone())
+                .oneshot(Request::builder().uri(format!("/distance?from={}&to={}&{}", source, target, query)).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["hops"], 1);
+            assert_eq!(json["bridges"].as_array().map(Vec::len), bridges);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_score() {
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let c = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+        let mut state = create_test_state();
+        let get = |state: &AppState, pubkey: &str| {
+            create_test_router(state.clone())
+                .oneshot(Request::builder().uri(format!("/score/{}", pubkey)).body(Body::empty()).unwrap())
+        };
+
+        let code = |response: Response| async {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()["code"].clone()
+        };
+        let response = get(&state, b).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(code(response).await, "NO_ANCHORS");
+
+        // a follows b; c is an anchor outside the graph, so only a has a table once computed
+        state.anchors = Arc::new(Anchors::new(vec![a.to_string(), c.to_string()]));
+        let response = get(&state, b).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(code(response).await, "GRAPH_WARMING");
+        state.anchors.refresh(&state.graph);
+        let response = get(&state, b).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({"pubkey": b, "anchors": 1, "reached": 1, "min_hops": 1, "avg_hops": 1.0, "score": 100}));
+
+        let body = axum::body::to_bytes(get(&state, c).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!((json["reached"].clone(), json["min_hops"].clone(), json["score"].clone()), (0.into(), serde_json::Value::Null, 0.into()));
+    }
+
+    #[tokio::test]
+    async fn test_bloom() {
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let c = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+        let state = create_test_state();
+        state.graph.update_follows(
+            "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+            &[c.to_string(), a.to_string()],
+            None,
+            None,
+        );
+        let get = |uri: String| {
+            create_test_router(state.clone()).oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        for (query, count) in [("", 1), ("?hops=2&fpr=0.001", 2)] {
+            let response = get(format!("/bloom/{}{}", a, query)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["count"], count);
+            assert_eq!(json["bits"].as_u64().unwrap() % 8, 0);
+        }
+
+        for (uri, status) in [
+            (format!("/bloom/{}?fpr=0.9", a), StatusCode::BAD_REQUEST),
+            (format!("/bloom/{}?hops=3", a), StatusCode::BAD_REQUEST),
+            (format!("/bloom/{}", "d".repeat(64)), StatusCode::NOT_FOUND),
+        ] {
+            assert_eq!(get(uri).await.unwrap().status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_popular() {
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let state = create_test_state();
+        let get = |uri: String| {
+            create_test_router(state.clone()).oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+        for (from, to) in [(a, b), (a, b), (b, a)] {
+            let response = get(format!("/distance?from={}&to={}", from, to)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = get("/stats/popular?limit=1".to_string()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["pairs"].as_array().unwrap().len(), 1);
+        assert_eq!((json["pairs"][0]["from"].as_str(), json["pairs"][0]["to"].as_str()), (Some(a), Some(b)));
+        assert_eq!(json["pubkeys"].as_array().unwrap().len(), 1);
+
+        assert_eq!(get("/stats/popular?limit=0".to_string()).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_follow_proof() {
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let state = create_test_state();
+        let get = |uri: String| {
+            create_test_router(state.clone()).oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+        assert_eq!(get(format!("/proof/follows/{}", a)).await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        state.commitments.set(commitment::FollowCommitment::compute(&state.graph, 0));
+        let response = get(format!("/proof/follows/{}", a)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["follows"], serde_json::json!([b]));
+        assert_eq!(json["leaf_count"], 1);
+
+        // b follows nobody, so has no leaf
+        assert_eq!(get(format!("/proof/follows/{}", b)).await.unwrap().status(), StatusCode::NOT_FOUND);
+        state.graph.update_follows(a, &[], None, Some(i64::MAX));
+        assert_eq!(get(format!("/proof/follows/{}", a)).await.unwrap().status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_distance() {
+        let state = create_test_state();
+
+        // aaaa follows bbbb, so bbbb is reached from aaaa but does not reach it
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+        let get = |uri: String| {
+            create_test_router(state.clone()).oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+        let forward = get(format!("/distance?from={}&to={}", b, a)).await.unwrap();
+        let bytes = axum::body::to_bytes(forward.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["hops"], serde_json::Value::Null);
+
+        let reverse = get(format!("/distance?from={}&to={}&reverse=true", b, a)).await.unwrap();
+        let bytes = axum::body::to_bytes(reverse.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["hops"], 1);
+        // The result describes the direction searched
+        assert_eq!((body["from"].as_str(), body["to"].as_str()), (Some(a), Some(b)));
+
+        let batch = serde_json::json!({ "from": b, "targets": [a], "reverse": true });
+        let response = create_test_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/distance/batch")
+                    .header("content-type", "application/json")
+                    .body(Body::from(batch.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["results"][0]["hops"], 1);
+        assert_eq!(body["results"][0]["from"], a);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_batch() {
+        let mut state = create_test_state();
+        state.config = Arc::new(Config { batch_max_targets: 250, ..Config::from_env() });
+
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let post = |targets: Vec<String>| {
+            let batch = serde_json::json!({ "from": a, "targets": targets });
+            create_test_router(state.clone()).oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/distance/batch")
+                    .header("content-type", "application/json")
+                    .body(Body::from(batch.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        // Three chunks, written in request order
+        let mut targets: Vec<String> = (1..250).map(|i| format!("{:064x}", i)).collect();
+        targets.insert(120, b.to_string());
+        let response = post(targets.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let epoch = state.graph.epoch();
+        assert_eq!(response.headers()["x-graph-epoch"], epoch.to_string().as_str());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!((body["from"].as_str(), body["epoch"].as_u64()), (Some(a), Some(epoch)));
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(results.len(), 250);
+        assert_eq!(results[120]["hops"], 1);
+        assert_eq!(results[249]["to"], targets[249]);
+        assert_eq!(results.iter().filter(|r| r["hops"].is_null()).count(), 249);
+
+        targets.push(format!("{:064x}", 250));
+        let response = post(targets).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_batch_pinned_to_epoch() {
+        let state = create_test_state();
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let post = || {
+            let batch = serde_json::json!({ "from": a, "targets": [b] });
+            let request = Request::builder()
+                .method("POST")
+                .uri("/distance/batch")
+                .header("content-type", "application/json")
+                .body(Body::from(batch.to_string()))
+                .unwrap();
+            let router = create_test_router(state.clone());
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+            }
+        };
+
+        // An entry not known to come from this epoch is recomputed
+        let key = CacheKey::new(state.graph.node_id(&a.parse().unwrap()).unwrap(), state.graph.node_id(&b.parse().unwrap()).unwrap(), 3, false);
+        let mut stale = bfs::DistanceResult::not_found(a.parse().unwrap(), b.parse().unwrap());
+        stale.hops = Some(3);
+        state.cache.insert(key, &stale, &state.graph);
+        let body = post().await;
+        assert_eq!((body["epoch"].as_u64(), body["results"][0]["hops"].as_u64()), (Some(1), Some(1)));
+
+        // The batch's own entry serves its epoch only
+        assert!(state.cache.get_at(&key, &state.graph, 1).is_some());
+        state.graph.update_follows(a, &[], None, Some(1));
+        let body = post().await;
+        assert_eq!(body["epoch"], 2);
+        assert_eq!(body["results"][0]["hops"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_batch() {
+        let state = create_test_state();
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let c = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+
+        let batch = serde_json::json!({ "from": a, "targets": [b, c] });
+        let response = create_test_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/distance/batch")
+                    .header("content-type", "application/json")
+                    .header("accept", "application/json;q=0.5, application/x-ndjson")
+                    .body(Body::from(batch.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        assert_eq!(response.headers()["x-graph-epoch"], state.graph.epoch().to_string().as_str());
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<serde_json::Value> = std::str::from_utf8(&bytes)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0]["to"].as_str(), lines[0]["hops"].as_u64()), (Some(b), Some(1)));
+        assert_eq!(lines[1]["to"], c);
+    }
+
+    #[tokio::test]
+    async fn test_csv_output() {
+        let state = create_test_state();
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let send = |request: Request<Body>| create_test_router(state.clone()).oneshot(request);
+        let csv_body = |response: Response| async move {
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        let batch = serde_json::json!({ "from": a, "targets": [b] });
+        let request = Request::builder()
+            .method("POST")
+            .uri("/distance/batch?fields=to,hops")
+            .header("content-type", "application/json")
+            .header("accept", "text/csv")
+            .body(Body::from(batch.to_string()))
+            .unwrap();
+        let body = csv_body(send(request).await.unwrap()).await;
+        assert_eq!(body, format!("to,hops\r\n{},1\r\n", b));
+
+        let request = Request::builder()
+            .uri(format!("/follows?pubkey={}", a))
+            .header("accept", "text/csv")
+            .body(Body::empty())
+            .unwrap();
+        let body = csv_body(send(request).await.unwrap()).await;
+        assert_eq!(body, format!("pubkey\r\n{}\r\n", b));
+
+        // Pairs come back as parsed: lowercase hex
+        let pairs = serde_json::json!({ "pairs": [{"from": a, "to": b.to_uppercase()}] });
+        let request = Request::builder()
+            .method("POST")
+            .uri("/verify/batch")
+            .header("content-type", "application/json")
+            .header("accept", "text/csv")
+            .body(Body::from(pairs.to_string()))
+            .unwrap();
+        let body = csv_body(send(request).await.unwrap()).await;
+        assert_eq!(body, format!("from,to,follows,followed_by,mutual\r\n{},{},true,false,false\r\n", a, b));
+    }
+
+    #[tokio::test]
+    async fn test_sparse_fields() {
+        let state = create_test_state();
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let send = |request: Request<Body>| create_test_router(state.clone()).oneshot(request);
+        let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let response = send(get(format!("/distance?from={}&to={}&fields=hops,mutual_follow", a, b))).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body, serde_json::json!({"hops": 1, "mutual_follow": false}));
+
+        let response = send(get(format!("/path?from={}&to={}&fields=path", a, b))).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        // Direct follow: no intermediate pubkeys
+        assert_eq!(body, serde_json::json!({"path": []}));
+
+        let batch = serde_json::json!({ "from": a, "targets": [b] });
+        let response = send(
+            Request::builder()
+                .method("POST")
+                .uri("/distance/batch?fields=to,hops")
+                .header("content-type", "application/json")
+                .body(Body::from(batch.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let epoch = state.graph.epoch();
+        assert_eq!(body, serde_json::json!({"from": a, "epoch": epoch, "results": [{"to": b, "hops": 1}]}));
+
+        let response = send(get(format!("/distance?from={}&to={}&fields=path", a, b))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
     #[tokio::test]
-    async fn test_stats_endpoint() {
+    async fn test_caller_settings() {
         let state = create_test_state();
-        let router = create_test_router(state);
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let caller = Caller {
+            pubkey: "c".repeat(64),
+            settings: Default::default(),
+            muted: Arc::new(HashSet::from([b.to_string()])),
+        };
+        let send = |request: Request<Body>| {
+            create_test_router(state.clone()).layer(Extension(caller.clone())).oneshot(request)
+        };
+        let json = |method: &str, uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        async fn body(response: Response) -> serde_json::Value {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice(&bytes).unwrap()
+        }
 
-        let response = router
-            .oneshot(Request::builder().uri("/stats").body(Body::empty()).unwrap())
-            .await
-            .unwrap();
+        // A pubkey the caller muted is unreachable for them
+        let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = send(get(format!("/distance?from={}&to={}", a, b))).await.unwrap();
+        assert_eq!(body(response).await["hops"], serde_json::Value::Null);
+        let response = send(get(format!("/path?from={}&to={}", a, b))).await.unwrap();
+        assert_eq!(body(response).await["path"], serde_json::Value::Null);
+        let batch = serde_json::json!({ "from": a, "targets": [b, a] });
+        let results = body(send(json("POST", "/distance/batch", batch)).await.unwrap()).await["results"].take();
+        assert_eq!(results[0]["hops"], serde_json::Value::Null);
+        assert_eq!(results[1]["hops"], 0);
 
+        let settings = serde_json::json!({
+            "root_pubkey": a,
+            "max_hops": 2,
+            "exclude_muted": true,
+            "scoring": "followerCount",
+        });
+        let response = send(json("PUT", "/me/settings", settings.clone())).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
-    }
+        assert_eq!(body(response).await, settings);
+        let stored = state.db.user_settings(&caller.pubkey).unwrap().unwrap();
+        assert_eq!(stored.max_hops, Some(2));
+        // Watched so their mute list is ingested
+        assert_eq!(state.watchlist.pubkeys(), vec![caller.pubkey.clone()]);
 
-    #[tokio::test]
-    async fn test_distance_endpoint() {
-        let state = create_test_state();
-        let router = create_test_router(state);
+        for invalid in [serde_json::json!({"max_hops": 9}), serde_json::json!({"scoring": "random"})] {
+            let response = send(json("PUT", "/me/settings", invalid)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
 
-        let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
-        let to = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let response = create_test_router(state.clone()).oneshot(get("/me/settings".to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
 
-        let response = router
-            .oneshot(
+    #[tokio::test]
+    async fn test_publish_recommendations() {
+        let mut state = create_test_state();
+        let a = "a".repeat(64);
+        let c = "c".repeat(64);
+        state.graph.update_follows(&"b".repeat(64), std::slice::from_ref(&c), None, None);
+        let caller = Caller { pubkey: a.clone(), settings: Default::default(), muted: Arc::default() };
+        let publish = |state: &AppState, body: serde_json::Value| {
+            create_test_router(state.clone()).layer(Extension(caller.clone())).oneshot(
                 Request::builder()
-                    .uri(format!("/distance?from={}&to={}", from, to))
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/me/recommendations/publish")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+        };
 
+        // Unsigned, authored by the caller, listing who they don't follow yet
+        let response = publish(&state, serde_json::json!({"signer": "user", "sort": "followerCount"})).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let event = &body["event"];
+        assert_eq!((event["kind"].as_u64(), event["pubkey"].as_str()), (Some(30000), Some(a.as_str())));
+        assert!(event.get("sig").is_none() && event["id"].is_string());
+        assert_eq!(event["tags"][3], serde_json::json!(["p", c]));
+        assert_eq!(body["recommendations"][0]["pubkey"], c);
+        assert!(body.get("published").is_none());
+
+        // The oracle signs only with a key, and publishes only to valid relays
+        let response = publish(&state, serde_json::json!({"relays": ["wss://relay.test"]})).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        state.oracle_keys = Some(Keys::generate());
+        for invalid in [
+            serde_json::json!({}),
+            serde_json::json!({"relays": ["https://relay.test"]}),
+            serde_json::json!({"signer": "both", "relays": ["wss://relay.test"]}),
+            serde_json::json!({"limit": 0, "relays": ["wss://relay.test"]}),
+        ] {
+            let response = publish(&state, invalid.clone()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", invalid);
+        }
+
+        let response = create_test_router(state.clone())
+            .oneshot(Request::builder().method("POST").uri("/me/recommendations/publish").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
     #[tokio::test]
-    async fn test_invalid_pubkey() {
-        let state = create_test_state();
-        let router = create_test_router(state);
+    async fn test_restricted_sources() {
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let mut state = create_test_state();
+        state.access = Arc::new(AccessLists::from_config(&Config {
+            restrict_sources: true,
+            source_allowlist: vec![a.to_string()],
+            api_keys: vec!["key".to_string()],
+            ..Config::from_env()
+        }));
 
-        let response = router
+        let cases = [
+            (a, b, None, StatusCode::OK),
+            (b, a, None, StatusCode::FORBIDDEN),
+            (b, a, Some("wrong"), StatusCode::FORBIDDEN),
+            (b, a, Some("key"), StatusCode::OK),
+        ];
+        for (from, to, api_key, status) in cases {
+            let mut request = Request::builder().uri(format!("/distance?from={}&to={}", from, to));
+            if let Some(key) = api_key {
+                request = request.header("x-api-key", key);
+            }
+            let response = create_test_router(state.clone())
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "from={} key={:?}", &from[..1], api_key);
+        }
+
+        // Every multi-source source must be registered; targets needn't be
+        let multi = serde_json::json!({ "sources": [a, b], "target": b });
+        let response = create_test_router(state.clone())
             .oneshot(
                 Request::builder()
-                    .uri("/distance?from=invalid&to=alsoinvalid")
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/distance/multi-source")
+                    .header("content-type", "application/json")
+                    .body(Body::from(multi.to_string()))
                     .unwrap(),
             )
             .await
             .unwrap();
-
-        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
     }
 
     #[tokio::test]
-    async fn test_bypass_cache() {
-        let state = create_test_state();
-        let router = create_test_router(state.clone());
+    async fn test_http_max_hops_limit() {
+        let mut state = create_test_state();
+        state.config = Arc::new(Config { http_max_hops: 2, ..Config::from_env() });
 
-        let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
-        let to = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        for (max_hops, status) in [(2, StatusCode::OK), (3, StatusCode::BAD_REQUEST)] {
+            let response = create_test_router(state.clone())
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/distance?from={}&to={}&max_hops={}", a, b, max_hops))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "max_hops={}", max_hops);
+        }
+    }
 
-        // First request without bypass_cache (populates cache)
-        let response = router
+    #[tokio::test]
+    async fn test_adaptive_max_hops() {
+        let mut state = create_test_state();
+        // bbbb has one follower, which makes it a super-node here
+        state.config = Arc::new(Config { adaptive_hops_degree: 1, adaptive_max_hops: 1, ..Config::from_env() });
+
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let response = create_test_router(state.clone())
             .oneshot(
                 Request::builder()
-                    .uri(format!("/distance?from={}&to={}", from, to))
+                    .uri(format!("/distance?from={}&to={}&max_hops=3", a, b))
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
-
-        // Verify cache was populated
-        let from_id = state.graph.get_node_id(from).unwrap();
-        let to_id = state.graph.get_node_id(to).unwrap();
-        let cache_key = CacheKey::new(from_id, to_id, MAX_HOPS_DEFAULT, false);
-        assert!(state.cache.get(&cache_key, &state.graph).is_some());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["hops"], 1);
+        assert_eq!(
+            body["adaptive_hops"],
+            serde_json::json!({"requested_max_hops": 3, "max_hops": 1, "reason": "both"})
+        );
 
-        // Second request with bypass_cache=true should still succeed
-        let router2 = create_test_router(state);
-        let response = router2
+        // Within the cap: nothing to report
+        let response = create_test_router(state)
             .oneshot(
                 Request::builder()
-                    .uri(format!("/distance?from={}&to={}&bypass_cache=true", from, to))
+                    .uri(format!("/distance?from={}&to={}&max_hops=1", a, b))
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(body.get("adaptive_hops").is_none());
     }
 
     #[tokio::test]
@@ -644,7 +3818,14 @@ mod tests {
 
         let config = Arc::new(Config::from_env());
         let cache = Arc::new(QueryCache::new(config.cache_size, config.cache_ttl_secs));
-        let state = AppState { graph, config, cache };
+        let rate_limits = Arc::new(RateLimits::new(
+            config.rate_limit_per_minute,
+            config.admin_rate_limit_per_minute,
+        ));
+        let access = Arc::new(AccessLists::from_config(&config));
+        let ranks = Arc::new(PageRank::new());
+        let db = Arc::new(Database::open(":memory:").unwrap());
+        let state = AppState { graph, config, cache, rate_limits, access, ranks, components: Arc::default(), anchors: Arc::default(), popular_anchors: Arc::default(), popularity: Arc::default(), sightings: Arc::default(), botscores: Arc::default(), persistence: Arc::default(), jobs: Arc::default(), commitments: Arc::default(), db, oracle_keys: None, resolver: Arc::new(Resolver::new(false)), profiles: Arc::default(), dvm_stats: Arc::default(), watchlist: Arc::default(), ingestion_pause: Arc::default(), namespaces: Arc::default(), diagnostics: Arc::default() };
         let router = create_test_router(state);
 
         let from = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
@@ -663,6 +3844,38 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_endorsers_endpoint() {
+        let state = create_test_state();
+        let (a, b, c, d) = ("a".repeat(64), "b".repeat(64), "c".repeat(64), "d".repeat(64));
+        // a follows b; b follows c and d, which a reaches in two hops; all three follow the target
+        let target = "e".repeat(64);
+        state.graph.update_follows(&a, &[b.clone(), target.clone()], None, Some(2));
+        state.graph.update_follows(&b, &[c.clone(), d.clone(), target.clone()], None, Some(2));
+        state.graph.update_follows(&c, std::slice::from_ref(&target), None, Some(2));
+        state.graph.update_follows(&d, &[target.clone(), c.clone()], None, Some(2));
+        let router = create_test_router(state);
+
+        let get = |uri: String| async {
+            let response = router.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+        };
+
+        let (status, body) = get(format!("/endorsers?from={}&to={}", a, target)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["count"].clone(), body["endorsers"].clone()), (serde_json::json!(1), serde_json::json!([b])));
+        assert!(body.get("two_hop").is_none());
+
+        // c has more followers than d, so it is listed first
+        let (_, body) = get(format!("/endorsers?from={}&to={}&two_hop=true&limit=1", a, target)).await;
+        assert_eq!((body["two_hop_count"].clone(), body["two_hop"].clone()), (serde_json::json!(2), serde_json::json!([c])));
+
+        let (status, _) = get(format!("/endorsers?from={}&to={}&limit=0", a, target)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_path_endpoint() {
         let state = create_test_state();
@@ -683,4 +3896,495 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_explore_pages() {
+        let state = create_test_state();
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+        state.graph.update_follows(&a, std::slice::from_ref(&b), None, None);
+        state.graph.update_follows(&b, std::slice::from_ref(&c), None, None);
+        state.ranks.refresh(&state.graph);
+        let router = create_test_router(state);
+
+        let get = |uri: String| {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, content_type, String::from_utf8(bytes.to_vec()).unwrap())
+            }
+        };
+
+        let (status, content_type, body) = get(format!("/explore/{}?graph=default", b)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/html"));
+        assert!(body.contains("<td>1</td>"));
+        // Neighbours link to their own pages by npub, keeping the graph
+        let npub = Pubkey::parse(&a).unwrap().to_bech32();
+        assert!(body.contains(&format!("href=\"/explore/{}?graph=default\"", npub)));
+
+        let (status, _, body) = get(format!("/explore/distance?from={}&to={}", a, c)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("2 hops"));
+        let (_, _, body) = get(format!("/explore/distance?from={}&to={}", c, a)).await;
+        assert!(body.contains("Not connected"));
+
+        let (status, content_type, _) = get(format!("/explore/{}", "d".repeat(64))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(content_type.starts_with("text/html"));
+        let (status, _, body) = get("/explore".to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("action=\"/explore/distance\""));
+    }
+
+    #[tokio::test]
+    async fn test_verify_batch() {
+        let state = create_test_state();
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+        state.graph.update_follows(&b, std::slice::from_ref(&a), None, None);
+        let router = create_test_router(state);
+
+        let post = |pairs: serde_json::Value| {
+            let request = Request::builder()
+                .method("POST")
+                .uri("/verify/batch")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::json!({ "pairs": pairs }).to_string()))
+                .unwrap();
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        let (status, body) = post(serde_json::json!([
+            {"from": a, "to": b},
+            {"from": b.to_uppercase(), "to": a},
+            {"from": a, "to": c},
+        ]))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["epoch"], 2);
+        assert_eq!(
+            body["results"],
+            serde_json::json!([
+                {"follows": true, "followed_by": true, "mutual": true},
+                {"follows": true, "followed_by": true, "mutual": true},
+                {"follows": false, "followed_by": false, "mutual": false},
+            ])
+        );
+
+        let (status, body) = post(serde_json::json!([{"from": a, "to": "alice@example.com"}])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "INVALID_PUBKEY");
+
+        let pairs: Vec<_> = (0..=VERIFY_BATCH_MAX_PAIRS).map(|_| serde_json::json!({"from": a, "to": b})).collect();
+        let (status, body) = post(serde_json::json!(pairs)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "TOO_MANY_PAIRS");
+    }
+
+    #[tokio::test]
+    async fn test_multi_source_endpoint() {
+        let state = create_test_state();
+        let router = create_test_router(state);
+
+        let body = serde_json::json!({
+            "sources": [
+                "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc",
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+            ],
+            "target": "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"
+        });
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/distance/multi-source")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["hops"], 1);
+        assert_eq!(body["source"], "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    }
+
+    #[tokio::test]
+    async fn test_set_distance_endpoint() {
+        let state = create_test_state();
+        let router = create_test_router(state);
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+
+        let post = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/distance/sets")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = router
+            .clone()
+            .oneshot(post(serde_json::json!({ "from": [&a, &c], "to": [&b, a.to_uppercase()], "max_hops": 2 })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["pairs"], 4);
+        assert_eq!(body["histogram"], serde_json::json!([1, 1, 0]));
+        assert_eq!(body["unreachable"], 2);
+
+        let too_many: Vec<_> = (0..=SET_DISTANCE_MAX_SEARCHES).map(|_| &a).collect();
+        let response = router
+            .oneshot(post(serde_json::json!({ "from": &too_many, "to": &too_many })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_reach_endpoint() {
+        let state = create_test_state();
+        let router = create_test_router(state);
+        let b = "b".repeat(64);
+
+        let post = |body: serde_json::Value| {
+            router.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/simulate/reach")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        // b's one follower passes the post on
+        let response = post(serde_json::json!({ "source": &b, "probability": 1.0, "max_hops": 2, "seed": 3 })).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["model"], "cascade");
+        assert_eq!(body["reach_per_hop"], serde_json::json!([1.0, 0.0]));
+        assert_eq!((body["runs"].clone(), body["seed"].clone()), (serde_json::json!(100), serde_json::json!(3)));
+
+        let response = post(serde_json::json!({ "source": &b, "model": "decay", "probability": 0.5 })).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["expected_reach"], 0.5);
+        assert!(body.get("runs").is_none());
+
+        for invalid in [
+            serde_json::json!({ "source": &b, "probability": 0.0 }),
+            serde_json::json!({ "source": &b, "probability": 0.5, "model": "viral" }),
+            serde_json::json!({ "source": &b, "probability": 0.5, "runs": 0 }),
+        ] {
+            assert_eq!(post(invalid).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_node_endpoint() {
+        let state = create_test_state();
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        state.db.update_follows(a, &[b.to_string()], Some("e1"), Some(100)).unwrap();
+        state.db.record_report(a, "e2", 100, &[(b.to_string(), Some("spam".to_string()))]).unwrap();
+        let router = create_test_router(state);
+
+        let response = router
+            .clone()
+            .oneshot(Request::builder().uri(format!("/node/{}", b)).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["follow_count"], 0);
+        assert_eq!(body["follower_count"], 1);
+        assert_eq!(body["mutual_count"], 0);
+        assert!(body["follow_back_ratio"].is_null());
+        assert_eq!(body["follow_ratio"], 0.0);
+        assert!(body["kind3_event_id"].is_null());
+        assert!(body["first_seen"].is_i64());
+        assert_eq!(body["first_seen"], body["last_updated"]);
+        assert_eq!(body["in_largest_component"], true);
+        assert_eq!(body["report_count"], 1);
+        // Stored lists not received since startup have no corroboration count
+        assert!(body["confidence"].is_null());
+
+        let c = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+        let response = router
+            .oneshot(Request::builder().uri(format!("/node/{}", c)).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "NODE_NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_botscore_endpoint() {
+        let state = create_test_state();
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+        state.graph.update_follows(&a, std::slice::from_ref(&b), None, Some(1));
+        state.graph.update_follows(&b, std::slice::from_ref(&a), None, Some(1));
+        state.graph.update_follows(&c, &[a.clone(), b.clone()], None, Some(1));
+        let (graph, db, botscores) = (state.graph.clone(), state.db.clone(), state.botscores.clone());
+        let router = create_test_router(state);
+        let get = |uri: String| router.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        // Nothing computed yet
+        assert_eq!(get(format!("/botscore/{}", a)).await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        botscores.refresh(&graph, &db, Default::default(), chrono::Utc::now().timestamp()).unwrap();
+        let response = get(format!("/botscore/{}", c)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["features"]["follow_back_ratio"], 0.0);
+        assert_eq!(body["features"]["clustering"], 1.0);
+        assert_eq!(body["score"], 20);
+        assert!(body["computed_at"].is_i64());
+
+        assert_eq!(get(format!("/botscore/{}", "d".repeat(64))).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_lookalikes_endpoint() {
+        let state = create_test_state();
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+        let list = |n: usize| (0..n).map(|i| format!("{:064x}", i)).collect::<Vec<_>>();
+        state.graph.update_follows(&a, &list(40), None, Some(1));
+        state.graph.update_follows(&b, &list(39), None, Some(1));
+        state.graph.update_follows(&c, &list(5), None, Some(1));
+        let router = create_test_router(state);
+        let get = |uri: String| router.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        let response = get(format!("/lookalikes/{}", a)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["follow_count"], 40);
+        assert_eq!(body["lookalikes"], serde_json::json!([{"pubkey": b, "similarity": 0.975, "follow_count": 39}]));
+
+        // Short lists aren't indexed
+        let response = get(format!("/lookalikes/{}", c)).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["lookalikes"], serde_json::json!([]));
+
+        assert_eq!(get(format!("/lookalikes/{}?min_similarity=1.5", a)).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(format!("/lookalikes/{}?limit=0", a)).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(format!("/lookalikes/{}", "d".repeat(64))).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_peers_endpoint() {
+        let state = create_test_state();
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+        let list = |range: std::ops::Range<usize>| range.map(|i| format!("{:064x}", i)).collect::<Vec<_>>();
+        state.graph.update_follows(&a, &list(0..40), None, Some(1));
+        state.graph.update_follows(&b, &list(20..60), None, Some(1));
+        state.graph.update_follows(&c, &list(0..5), None, Some(1));
+        let router = create_test_router(state);
+        let get = |uri: String| router.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        // Far below the lookalike threshold, but still a peer
+        let response = get(format!("/peers/{}", a)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body["peers"],
+            serde_json::json!([{"pubkey": b, "similarity": 20.0 / 60.0, "shared_follows": 20, "follow_count": 40}])
+        );
+
+        let response = get(format!("/peers/{}", c)).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["peers"], serde_json::json!([]));
+
+        assert_eq!(get(format!("/peers/{}?limit=101", a)).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(format!("/peers/{}", "d".repeat(64))).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_weak_ties_endpoint() {
+        let state = create_test_state();
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+        // a's only follow b is its only way to c
+        state.graph.update_follows(&b, std::slice::from_ref(&c), None, Some(2));
+        let router = create_test_router(state);
+        let get = |uri: String| router.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        let response = get(format!("/weak-ties/{}", a)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["reached"], 2);
+        assert_eq!(body["ties"], serde_json::json!([{"pubkey": b, "exclusive_reach": 1, "local_bridge": true}]));
+
+        assert_eq!(get(format!("/weak-ties/{}?max_hops=1", a)).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(format!("/weak-ties/{}?limit=0", a)).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(format!("/weak-ties/{}", "e".repeat(64))).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_cut_vertices_endpoint() {
+        let state = create_test_state();
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+        // a reaches c only through b
+        state.graph.update_follows(&b, std::slice::from_ref(&c), None, Some(2));
+        let router = create_test_router(state);
+        let get = |uri: String| router.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        let response = get(format!("/ego/cut-vertices/{}", a)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!((body["nodes"].clone(), body["truncated"].clone()), (serde_json::json!(2), serde_json::json!(false)));
+        assert_eq!(body["cut_vertices"], serde_json::json!([{"pubkey": b, "hops": 1, "separated": 1}]));
+
+        assert_eq!(get(format!("/ego/cut-vertices/{}?max_hops=4", a)).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(format!("/ego/cut-vertices/{}", "e".repeat(64))).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_follower_history_endpoint() {
+        let state = create_test_state();
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let key: Pubkey = b.parse().unwrap();
+        state.db.record_follower_counts(86_400 + 10, &[(key, 3)]).unwrap();
+        state.db.record_follower_counts(86_400 + 3_700, &[(key, 4)]).unwrap();
+        let router = create_test_router(state);
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/history/followers/{}?granularity=hour", b))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["points"].as_array().unwrap().len(), 2);
+
+        let response = router
+            .clone()
+            .oneshot(Request::builder().uri(format!("/history/followers/{}", b)).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["granularity"], "day");
+        assert_eq!(body["points"], serde_json::json!([{ "timestamp": 86_400, "follower_count": 4 }]));
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/history/followers/{}?granularity=month", b))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_diff_endpoints() {
+        let state = create_test_state();
+        let (a, b, c) = ("a".repeat(64), "b".repeat(64), "c".repeat(64));
+        state.db.update_follows(&a, std::slice::from_ref(&b), Some("e1"), Some(86_400)).unwrap();
+        state.db.update_follows(&a, std::slice::from_ref(&c), Some("e2"), Some(2 * 86_400 + 5)).unwrap();
+        let router = create_test_router(state);
+
+        let get = |uri: String| {
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        let (status, body) = get(format!("/diff?pubkey={}&from_ts=0&to_ts={}", a, 3 * 86_400)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["added"], serde_json::json!([c]));
+        assert_eq!(body["removed"], serde_json::json!([b]));
+
+        // Before the change the window is empty
+        let (_, body) = get(format!("/diff?pubkey={}&from_ts=0&to_ts={}", a, 2 * 86_400)).await;
+        assert_eq!(body["added"], serde_json::json!([]));
+
+        let (status, body) = get(format!("/diff/daily?from_ts=0&to_ts={}", 3 * 86_400)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["days"], serde_json::json!([{ "day": 2 * 86_400, "added": 1, "removed": 1 }]));
+
+        let (status, body) = get("/diff/daily?from_ts=10&to_ts=10".to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "INVALID_RANGE");
+    }
+
+    #[tokio::test]
+    async fn test_sample_endpoint() {
+        let state = create_test_state();
+        let hub = "a".repeat(64);
+        for i in 0..20 {
+            state.graph.update_follows(&format!("{:064x}", i + 1), std::slice::from_ref(&hub), None, Some(1));
+        }
+        let router = create_test_router(state);
+
+        let get = |uri: &str| {
+            let router = router.clone();
+            let uri = uri.to_string();
+            async move {
+                let response = router.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        let (status, body) = get("/sample?n=5&seed=3").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["seed"], 3);
+        assert_eq!(body["weighting"], "uniform");
+        assert_eq!(body["pubkeys"].as_array().unwrap().len(), 5);
+        assert_eq!(get("/sample?n=5&seed=3").await.1, body);
+
+        let (_, body) = get("/sample?min_followers=20&weighting=degree").await;
+        assert_eq!(body["population"], 1);
+        assert_eq!(body["mean_follower_count"], 20.0);
+        assert_eq!(body["pubkeys"][0]["pubkey"], hub);
+
+        let (status, body) = get("/sample?n=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "INVALID_SAMPLE_SIZE");
+        let (_, body) = get("/sample?weighting=pagerank").await;
+        assert_eq!(body["code"], "INVALID_WEIGHTING");
+    }
 }
@@ -0,0 +1,265 @@
+use futures_util::{stream, StreamExt, TryStreamExt};
+use moka::sync::Cache;
+use nostr_sdk::prelude::{nip05, FromBech32, Nip19Profile};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::types::Pubkey;
+
+const NIP05_CACHE_SIZE: u64 = 10_000;
+// Failed lookups are cached too, so a bad domain isn't fetched on every request
+const NIP05_CACHE_TTL: Duration = Duration::from_secs(3600);
+const NIP05_TIMEOUT: Duration = Duration::from_secs(5);
+const NIP05_MAX_LEN: usize = 320;
+/// NIP-05 identifiers one request may name; each can hold it for up to `NIP05_TIMEOUT`
+pub const NIP05_MAX_PER_REQUEST: usize = 10;
+// Lookups of one request in flight at once
+const NIP05_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ResolveError {
+    #[error("Invalid pubkey format")]
+    Invalid,
+    #[error("NIP-05 identifier could not be resolved")]
+    Unresolved,
+    #[error("NIP-05 resolution is disabled")]
+    Nip05Disabled,
+    #[error("At most {} NIP-05 identifiers per request", NIP05_MAX_PER_REQUEST)]
+    TooManyLookups,
+}
+
+/// Turns user-supplied identities (hex, `npub1…`, `nprofile1…`, NIP-05) into [`Pubkey`]s.
+/// Shared by the HTTP API and the DVM so both accept the same inputs.
+pub struct Resolver {
+    // None when NIP-05 lookups are disabled
+    nip05_cache: Option<Cache<String, Option<Pubkey>>>,
+}
+
+impl Resolver {
+    pub fn new(nip05_enabled: bool) -> Self {
+        let nip05_cache = nip05_enabled.then(|| {
+            Cache::builder()
+                .max_capacity(NIP05_CACHE_SIZE)
+                .time_to_live(NIP05_CACHE_TTL)
+                .build()
+        });
+        Self { nip05_cache }
+    }
+
+    /// Resolve an identity to a pubkey; hex is accepted in either case
+    pub async fn resolve(&self, input: &str) -> Result<Pubkey, ResolveError> {
+        let input = input.trim();
+
+        if let Ok(pubkey) = Pubkey::parse(input) {
+            return Ok(pubkey);
+        }
+        if input.starts_with("npub1") {
+            return Pubkey::from_bech32(input).map_err(|_| ResolveError::Invalid);
+        }
+        if input.starts_with("nprofile1") {
+            return Nip19Profile::from_bech32(input)
+                .map(|profile| Pubkey::from_bytes(profile.public_key.to_bytes()))
+                .map_err(|_| ResolveError::Invalid);
+        }
+
+        match normalize_nip05(input) {
+            Some(identifier) => self.resolve_nip05(identifier).await,
+            None => Err(ResolveError::Invalid),
+        }
+    }
+
+    /// Resolve several identities in order, failing on the first that doesn't resolve. At most
+    /// `NIP05_MAX_PER_REQUEST` may need a NIP-05 lookup, and those run `NIP05_CONCURRENCY` at a time.
+    pub async fn resolve_all(&self, inputs: &[&str]) -> Result<Vec<Pubkey>, ResolveError> {
+        if inputs.iter().filter(|input| is_nip05(input)).count() > NIP05_MAX_PER_REQUEST {
+            return Err(ResolveError::TooManyLookups);
+        }
+        // Collected first: a lazily mapped stream isn't provably Send in axum handlers
+        let lookups: Vec<_> = inputs.iter().map(|input| self.resolve(input)).collect();
+        stream::iter(lookups)
+            .buffered(NIP05_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    async fn resolve_nip05(&self, identifier: String) -> Result<Pubkey, ResolveError> {
+        let Some(ref cache) = self.nip05_cache else {
+            return Err(ResolveError::Nip05Disabled);
+        };
+
+        let resolved = match cache.get(&identifier) {
+            Some(resolved) => resolved,
+            None => {
+                let lookup = async {
+                    // Only hosts on the public internet are fetched, so inputs can't reach internal services
+                    let domain = identifier.rsplit_once('@').map_or(identifier.as_str(), |(_, domain)| domain);
+                    if !resolves_publicly(domain).await {
+                        return Err(format!("{} resolves to a non-public address", domain));
+                    }
+                    nip05::profile(&identifier, None).await.map_err(|e| e.to_string())
+                };
+                let resolved = match tokio::time::timeout(NIP05_TIMEOUT, lookup).await {
+                    Ok(Ok(profile)) => Some(Pubkey::from_bytes(profile.public_key.to_bytes())),
+                    Ok(Err(e)) => {
+                        debug!("NIP-05 lookup failed for {}: {}", identifier, e);
+                        None
+                    }
+                    Err(_) => {
+                        debug!("NIP-05 lookup timed out for {}", identifier);
+                        None
+                    }
+                };
+                cache.insert(identifier, resolved);
+                resolved
+            }
+        };
+
+        resolved.ok_or(ResolveError::Unresolved)
+    }
+}
+
+/// Whether resolving `input` takes a NIP-05 lookup (cached or not) rather than decoding
+pub fn is_nip05(input: &str) -> bool {
+    let input = input.trim();
+    !Pubkey::is_valid(input)
+        && !input.starts_with("npub1")
+        && !input.starts_with("nprofile1")
+        && normalize_nip05(input).is_some()
+}
+
+/// Whether every address `domain` resolves to is publicly routable
+async fn resolves_publicly(domain: &str) -> bool {
+    match tokio::net::lookup_host((domain, 443)).await {
+        Ok(addrs) => {
+            let addrs: Vec<_> = addrs.collect();
+            !addrs.is_empty() && addrs.iter().all(|addr| is_public_ip(addr.ip()))
+        }
+        Err(_) => false,
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Carrier-grade NAT (100.64.0.0/10) and benchmarking (198.18.0.0/15)
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (18..20).contains(&b)))
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        // Documentation (2001:db8::/32)
+        || (first == 0x2001 && ip.segments()[1] == 0xdb8))
+}
+
+/// `name@domain` (or a bare `domain`, meaning `_@domain`), lowercased; None if malformed.
+/// IP literals and local-only names are rejected, since the domain is fetched
+fn normalize_nip05(input: &str) -> Option<String> {
+    if input.len() > NIP05_MAX_LEN {
+        return None;
+    }
+    let input = input.to_ascii_lowercase();
+    let (local, domain) = input.split_once('@').unwrap_or(("_", &input));
+
+    let local_ok = !local.is_empty()
+        && local.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    // A numeric last label would be read as an IPv4 address (`10.0.0.1`, `0x7f.1`)
+    let tld = domain.rsplit('.').next().unwrap_or_default();
+    let domain_ok = domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'))
+        && tld.starts_with(|c: char| c.is_ascii_alphabetic())
+        && !matches!(tld, "localhost" | "local" | "internal" | "localdomain" | "home" | "lan");
+
+    (local_ok && domain_ok).then(|| format!("{}@{}", local, domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_nip05() {
+        assert_eq!(normalize_nip05("Bob@Example.com"), Some("bob@example.com".into()));
+        assert_eq!(normalize_nip05("example.com"), Some("_@example.com".into()));
+        assert_eq!(normalize_nip05("bob@localhost"), None);
+        assert_eq!(normalize_nip05("bob@example.com/path"), None);
+        assert_eq!(normalize_nip05("@example.com"), None);
+        assert_eq!(normalize_nip05("nope"), None);
+
+        // Addresses the server must not be made to fetch
+        assert_eq!(normalize_nip05("x@10.0.0.1"), None);
+        assert_eq!(normalize_nip05("x@169.254.169.254"), None);
+        assert_eq!(normalize_nip05("x@0x7f.1"), None);
+        assert_eq!(normalize_nip05("x@printer.local"), None);
+        assert_eq!(normalize_nip05("x@db.localhost"), None);
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in ["10.0.0.1", "172.16.0.1", "192.168.1.1", "127.0.0.1", "169.254.169.254", "100.64.0.1", "0.0.0.0"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["::1", "fe80::1", "fd00::1", "::ffff:10.0.0.1", "::ffff:127.0.0.1"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:2800:220:1:248:1893:25c8:1946".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_local_names_not_fetched() {
+        assert!(!resolves_publicly("localhost").await);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_without_lookups() {
+        let resolver = Resolver::new(false);
+        let hex = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
+        assert_eq!(resolver.resolve(hex).await.map(String::from), Ok(hex.to_string()));
+        assert_eq!(resolver.resolve(&hex.to_uppercase()).await, resolver.resolve(hex).await);
+        assert_eq!(resolver.resolve("nope").await, Err(ResolveError::Invalid));
+        assert_eq!(resolver.resolve("npub1nope").await, Err(ResolveError::Invalid));
+        assert_eq!(resolver.resolve("bob@example.com").await, Err(ResolveError::Nip05Disabled));
+
+        let upper = hex.to_uppercase();
+        assert_eq!(resolver.resolve_all(&[hex, &upper]).await.map(|keys| keys.len()), Ok(2));
+        let names: Vec<String> = (0..=NIP05_MAX_PER_REQUEST).map(|i| format!("u{}@example.com", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        assert_eq!(resolver.resolve_all(&names).await, Err(ResolveError::TooManyLookups));
+        assert_eq!(resolver.resolve_all(&names[1..]).await, Err(ResolveError::Nip05Disabled));
+    }
+
+    #[test]
+    fn test_is_nip05() {
+        assert!(is_nip05("bob@example.com"));
+        assert!(!is_nip05(&"a".repeat(64)));
+        assert!(!is_nip05("npub1nope"));
+        assert!(!is_nip05("nope"));
+    }
+}
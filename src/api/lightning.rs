@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    pub bolt11: String,
+    pub payment_hash: String,
+}
+
+/// A Lightning node or wallet able to issue invoices and report their settlement
+#[async_trait]
+pub trait LightningBackend: Send + Sync {
+    async fn create_invoice(&self, amount_msats: u64, memo: &str, expiry: Duration) -> Result<Invoice>;
+
+    async fn is_paid(&self, invoice: &Invoice) -> Result<bool>;
+}
+
+/// Backend selected by configuration, or None if no Lightning settings are present
+pub fn backend_from_config(config: &Config) -> Option<Arc<dyn LightningBackend>> {
+    match (&config.lnbits_url, &config.lnbits_api_key) {
+        (Some(url), Some(api_key)) => Some(Arc::new(LnbitsBackend::new(url, api_key))),
+        _ => None,
+    }
+}
+
+/// LNbits wallet API, authenticated with the wallet's invoice key
+pub struct LnbitsBackend {
+    http: reqwest::Client,
+    url: String,
+    api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LnbitsInvoice {
+    payment_hash: String,
+    payment_request: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LnbitsPaymentStatus {
+    paid: bool,
+}
+
+impl LnbitsBackend {
+    pub fn new(url: &str, api_key: &str) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl LightningBackend for LnbitsBackend {
+    async fn create_invoice(&self, amount_msats: u64, memo: &str, expiry: Duration) -> Result<Invoice> {
+        let invoice: LnbitsInvoice = self
+            .http
+            .post(format!("{}/api/v1/payments", self.url))
+            .header("X-Api-Key", &self.api_key)
+            .json(&json!({
+                "out": false,
+                "amount": msats_to_sats(amount_msats),
+                "memo": memo,
+                "expiry": expiry.as_secs(),
+            }))
+            .send()
+            .await?
+            .error_for_status()
+            .context("LNbits rejected invoice request")?
+            .json()
+            .await?;
+
+        Ok(Invoice {
+            bolt11: invoice.payment_request,
+            payment_hash: invoice.payment_hash,
+        })
+    }
+
+    async fn is_paid(&self, invoice: &Invoice) -> Result<bool> {
+        let status: LnbitsPaymentStatus = self
+            .http
+            .get(format!("{}/api/v1/payments/{}", self.url, invoice.payment_hash))
+            .header("X-Api-Key", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()
+            .context("LNbits rejected payment status request")?
+            .json()
+            .await?;
+
+        Ok(status.paid)
+    }
+}
+
+/// LNbits invoices are denominated in sats; round up so the price is never undercut
+fn msats_to_sats(msats: u64) -> u64 {
+    msats.div_ceil(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msats_round_up() {
+        assert_eq!(msats_to_sats(1), 1);
+        assert_eq!(msats_to_sats(1000), 1);
+        assert_eq!(msats_to_sats(1001), 2);
+    }
+}
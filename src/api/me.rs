@@ -0,0 +1,239 @@
+//! Per-user defaults. A user who authenticates with NIP-98 stores a root pubkey, max_hops,
+//! mute exclusion and scoring under `/me/settings`; their later authenticated queries use
+//! them wherever the request leaves the parameter out.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{Method, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::dvm::Sort;
+use super::followset;
+use super::http::{resolve_subject, validate_max_hops, AppState, ErrorResponse};
+use super::nip98::{self, SignedRequest};
+use crate::config::REQUEST_BODY_LIMIT;
+use crate::db::UserSettings;
+use crate::types::Pubkey;
+
+const MUTE_LIST_KIND: u16 = 10000;
+
+/// The NIP-98 authenticated identity behind a request, with their stored settings
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub pubkey: String,
+    pub settings: UserSettings,
+    /// Pubkeys on the caller's mute list, when `exclude_muted` is set
+    pub muted: Arc<HashSet<String>>,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/settings", get(get_settings).put(put_settings))
+        .route("/recommendations/publish", post(followset::publish_recommendations))
+}
+
+/// Authenticate requests carrying NIP-98 auth and fill in the caller's defaults: `from` and
+/// `max_hops` for `GET /distance` and `GET /path` and the batch request body. The caller is
+/// passed on to handlers as an [`Extension<Caller>`]. Requests without auth pass untouched.
+pub async fn apply_settings(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !nip98::has_auth(request.headers()) {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, REQUEST_BODY_LIMIT).await else {
+        return ErrorResponse::invalid("INVALID_BODY", "Request body too large").into_response();
+    };
+    let signed = SignedRequest {
+        path_and_query: parts.uri.path_and_query().map_or("/", |pq| pq.as_str()),
+        method: parts.method.as_str(),
+        body: &body,
+    };
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let pubkey = match nip98::authenticate(&parts.headers, &signed, now) {
+        Ok(Some(pubkey)) => pubkey,
+        Ok(None) => return next.run(Request::from_parts(parts, Body::from(body))).await,
+        Err(e) => return e.into_response(),
+    };
+    let caller = match load_caller(&state, pubkey) {
+        Ok(caller) => caller,
+        Err(e) => return e.into_response(),
+    };
+
+    let mut body = body.to_vec();
+    match (&parts.method, parts.uri.path()) {
+        (&Method::GET, "/distance" | "/path") => {
+            let query = with_query_defaults(parts.uri.query().unwrap_or_default(), &caller.settings);
+            if let Ok(uri) = format!("{}?{}", parts.uri.path(), query).parse::<Uri>() {
+                parts.uri = uri;
+            }
+        }
+        (&Method::POST, "/distance/batch") => {
+            if let Some(defaulted) = with_body_defaults(&body, &caller.settings) {
+                body = defaulted;
+                parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+            }
+        }
+        _ => {}
+    }
+    parts.extensions.insert(caller);
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+/// The caller's settings and, if they asked for it, their mute list
+fn load_caller(state: &AppState, pubkey: String) -> Result<Caller, ErrorResponse> {
+    let settings = state
+        .db
+        .user_settings(&pubkey)
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .unwrap_or_default();
+    let muted = match settings.exclude_muted {
+        true => state
+            .db
+            .author_list(&pubkey, MUTE_LIST_KIND)
+            .map_err(|e| ErrorResponse::internal(e.to_string()))?
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|tag| tag.len() >= 2 && tag[0] == "p")
+            .filter_map(|tag| Pubkey::parse(&tag[1]).ok())
+            .map(String::from)
+            .collect(),
+        false => HashSet::new(),
+    };
+    Ok(Caller { pubkey, settings, muted: Arc::new(muted) })
+}
+
+/// `query` with the stored `from` and `max_hops` added where it leaves them out
+fn with_query_defaults(query: &str, settings: &UserSettings) -> String {
+    let has = |name: &str| {
+        query
+            .split('&')
+            .any(|pair| pair.split('=').next() == Some(name))
+    };
+    let mut query = query.to_string();
+    let mut add = |name: &str, value: String| {
+        if !query.is_empty() {
+            query.push('&');
+        }
+        query.push_str(&format!("{}={}", name, value));
+    };
+    if let (false, Some(root)) = (has("from"), &settings.root_pubkey) {
+        add("from", root.clone());
+    }
+    if let (false, Some(max_hops)) = (has("max_hops"), settings.max_hops) {
+        add("max_hops", max_hops.to_string());
+    }
+    query
+}
+
+/// A JSON request body with the stored `from` and `max_hops` added where it leaves them out;
+/// `None` when nothing changes or the body isn't a JSON object
+fn with_body_defaults(body: &[u8], settings: &UserSettings) -> Option<Vec<u8>> {
+    let Ok(Value::Object(mut object)) = serde_json::from_slice::<Value>(body) else {
+        return None;
+    };
+    let mut changed = false;
+    if let (false, Some(root)) = (object.contains_key("from"), &settings.root_pubkey) {
+        object.insert("from".to_string(), Value::from(root.as_str()));
+        changed = true;
+    }
+    if let (false, Some(max_hops)) = (object.contains_key("max_hops"), settings.max_hops) {
+        object.insert("max_hops".to_string(), Value::from(max_hops));
+        changed = true;
+    }
+    changed.then(|| serde_json::to_vec(&object).unwrap_or_default())
+}
+
+pub(super) fn require_caller(caller: Option<Extension<Caller>>) -> Result<Caller, ErrorResponse> {
+    caller
+        .map(|Extension(caller)| caller)
+        .ok_or_else(|| ErrorResponse::unauthorized().with_message("NIP-98 authorization required"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/settings",
+    tag = "settings",
+    security(("nip98" = [])),
+    responses(
+        (status = 200, description = "The caller's stored settings; all unset if they never stored any", body = UserSettings),
+        (status = 401, description = "Missing or invalid NIP-98 authorization", body = ErrorResponse)
+    )
+)]
+pub async fn get_settings(caller: Option<Extension<Caller>>) -> Result<Json<UserSettings>, ErrorResponse> {
+    Ok(Json(require_caller(caller)?.settings))
+}
+
+#[utoipa::path(
+    put,
+    path = "/me/settings",
+    tag = "settings",
+    security(("nip98" = [])),
+    request_body = UserSettings,
+    responses(
+        (status = 200, description = "Settings stored, replacing any previous ones", body = UserSettings),
+        (status = 400, description = "Invalid settings", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid NIP-98 authorization", body = ErrorResponse),
+        (status = 403, description = "Root pubkey excluded by access lists", body = ErrorResponse)
+    )
+)]
+pub async fn put_settings(
+    State(state): State<AppState>,
+    caller: Option<Extension<Caller>>,
+    Json(mut settings): Json<UserSettings>,
+) -> Result<Json<UserSettings>, ErrorResponse> {
+    let caller = require_caller(caller)?;
+    if let Some(root) = &settings.root_pubkey {
+        settings.root_pubkey = Some(resolve_subject(&state, root).await?.to_hex());
+    }
+    if let Some(max_hops) = settings.max_hops {
+        validate_max_hops(&state.config, max_hops)?;
+    }
+    if let Some(scoring) = &settings.scoring {
+        Sort::parse(scoring).map_err(|error| ErrorResponse::invalid("INVALID_SCORING", error))?;
+    }
+
+    state.db.set_user_settings(&caller.pubkey, &settings).map_err(|e| {
+        error!("Failed to store settings for {}: {}", caller.pubkey, e);
+        ErrorResponse::internal("Failed to store settings")
+    })?;
+    // Mute lists are only ingested for watched authors
+    if settings.exclude_muted && state.watchlist.add(std::slice::from_ref(&caller.pubkey)).unwrap_or(0) > 0 {
+        info!("Watching {} for their mute list", caller.pubkey);
+    }
+    Ok(Json(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let settings = UserSettings {
+            root_pubkey: Some("a".repeat(64)),
+            max_hops: Some(2),
+            ..Default::default()
+        };
+        let root = "a".repeat(64);
+
+        assert_eq!(with_query_defaults("to=b", &settings), format!("to=b&from={}&max_hops=2", root));
+        assert_eq!(with_query_defaults("from=c&to=b&max_hops=4", &settings), "from=c&to=b&max_hops=4");
+        assert_eq!(with_query_defaults("", &UserSettings::default()), "");
+
+        let body = with_body_defaults(br#"{"targets":["b"],"max_hops":3}"#, &settings).unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({"targets": ["b"], "max_hops": 3, "from": root}));
+        assert_eq!(with_body_defaults(br#"{"from":"c","max_hops":3}"#, &settings), None);
+        assert_eq!(with_body_defaults(b"not json", &settings), None);
+    }
+}
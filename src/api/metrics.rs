@@ -0,0 +1,105 @@
+//! Prometheus text exposition of the selected graph's size and persistence health, for
+//! scrapers that don't read `/stats`. Counters are totals since startup.
+
+use axum::http::header;
+use axum::response::IntoResponse;
+use std::fmt::Write;
+
+use super::http::{AppState, GraphParam, SelectedGraph};
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "service",
+    params(GraphParam),
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain")
+    )
+)]
+pub async fn get_metrics(SelectedGraph(state): SelectedGraph) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], render(&state))
+}
+
+fn render(state: &AppState) -> String {
+    let graph = state.graph.stats();
+    let cache = state.cache.stats();
+    let persistence = state.persistence.snapshot();
+
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+    };
+    metric("wot_nodes", "gauge", "Pubkeys in the graph", graph.node_count as f64);
+    metric("wot_edges", "gauge", "Follow edges in the graph", graph.edge_count as f64);
+    metric(
+        "wot_oversized_lists_total",
+        "counter",
+        "Contact list events rejected for exceeding MAX_TAGS_PER_LIST",
+        graph.oversized_lists as f64,
+    );
+    metric(
+        "wot_truncated_lists_total",
+        "counter",
+        "Contact list events cut down to MAX_TAGS_PER_LIST tags",
+        graph.truncated_lists as f64,
+    );
+    metric(
+        "wot_duplicate_follows_total",
+        "counter",
+        "Follows dropped as repeats within their contact list",
+        graph.duplicate_follows as f64,
+    );
+    metric("wot_cache_entries", "gauge", "Query cache entries", cache.size as f64);
+    metric(
+        "wot_persistence_queue_depth",
+        "gauge",
+        "Applied follow list updates not yet written to the database",
+        persistence.queue_depth as f64,
+    );
+    metric(
+        "wot_persistence_dropped_updates_total",
+        "counter",
+        "Follow list updates dropped because the persistence queue was full",
+        persistence.dropped_updates as f64,
+    );
+    metric(
+        "wot_persistence_updates_written_total",
+        "counter",
+        "Follow lists written to the database",
+        persistence.updates_written as f64,
+    );
+    metric(
+        "wot_persistence_updates_failed_total",
+        "counter",
+        "Follow lists in batches the database rejected",
+        persistence.updates_failed as f64,
+    );
+    metric(
+        "wot_persistence_rows_written_total",
+        "counter",
+        "Node and edge rows written to the database",
+        persistence.rows_written as f64,
+    );
+    metric("wot_persistence_flushes_total", "counter", "Persistence batches flushed", persistence.flushes as f64);
+    metric(
+        "wot_persistence_flush_seconds_total",
+        "counter",
+        "Time spent flushing persistence batches",
+        persistence.flush_ms_total / 1000.0,
+    );
+    metric(
+        "wot_persistence_last_flush_seconds",
+        "gauge",
+        "Duration of the last persistence flush",
+        persistence.last_flush_ms / 1000.0,
+    );
+    metric(
+        "wot_persistence_last_flush_timestamp_seconds",
+        "gauge",
+        "Unix time of the last successful persistence flush, 0 before the first",
+        persistence.last_flush_at.unwrap_or(0) as f64,
+    );
+    out
+}
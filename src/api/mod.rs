@@ -1,4 +1,31 @@
+pub mod access;
+pub mod admin;
+pub mod attest;
+pub mod bloom;
+pub mod commitment;
+pub mod csv;
 pub mod http;
 pub mod dvm;
+pub mod etag;
+pub mod explore;
+pub mod fields;
+pub mod followset;
+pub mod graphql;
+pub mod grpc;
+pub mod identity;
+pub mod lightning;
+pub mod me;
+pub mod metrics;
+pub mod nip98;
+pub mod openapi;
+pub mod outbox;
+pub mod profiles;
+pub mod publish;
+pub mod ratelimit;
+pub mod relay;
+pub mod replication;
+pub mod simulate;
 
+pub use commitment::CommitmentPublisher;
 pub use dvm::DvmService;
+pub use publish::RankingPublisher;
@@ -0,0 +1,143 @@
+//! NIP-98 HTTP auth: a request made on behalf of a Nostr identity carries
+//! `Authorization: Nostr <base64 event>`, a signed kind 27235 event bound to its URL, method
+//! and body.
+
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use nostr_sdk::prelude::{Event, JsonUtil};
+use sha2::{Digest, Sha256};
+
+use super::http::ErrorResponse;
+
+pub const HTTP_AUTH_KIND: u16 = 27235;
+/// Auth events further than this from the server clock are rejected, which bounds replays
+const MAX_SKEW_SECS: u64 = 60;
+
+/// The parts of a request its auth event must be bound to
+pub struct SignedRequest<'a> {
+    pub path_and_query: &'a str,
+    pub method: &'a str,
+    pub body: &'a [u8],
+}
+
+/// Whether a request carries `Nostr` authorization, as opposed to none or another scheme
+pub fn has_auth(headers: &HeaderMap) -> bool {
+    token(headers).is_some()
+}
+
+fn token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Nostr "))
+}
+
+/// The hex pubkey that signed a request, or `None` if it carries no `Nostr` authorization
+pub fn authenticate(headers: &HeaderMap, request: &SignedRequest, now: u64) -> Result<Option<String>, ErrorResponse> {
+    let Some(encoded) = token(headers) else {
+        return Ok(None);
+    };
+    let json = STANDARD
+        .decode(encoded.trim())
+        .map_err(|_| unauthorized("Authorization is not base64"))?;
+    let event = Event::from_json(json).map_err(|_| unauthorized("Authorization is not a Nostr event"))?;
+    event.verify().map_err(|_| unauthorized("Invalid auth event signature"))?;
+
+    let tags: Vec<&[String]> = event.tags.iter().map(|tag| tag.as_slice()).collect();
+    check(event.kind.as_u16(), event.created_at.as_u64(), &tags, request, now).map_err(unauthorized)?;
+    Ok(Some(event.pubkey.to_hex()))
+}
+
+/// Check a verified auth event's kind, age and tags against the request
+fn check(kind: u16, created_at: u64, tags: &[&[String]], request: &SignedRequest, now: u64) -> Result<(), &'static str> {
+    if kind != HTTP_AUTH_KIND {
+        return Err("Auth event must be kind 27235");
+    }
+    if created_at.abs_diff(now) > MAX_SKEW_SECS {
+        return Err("Auth event is expired");
+    }
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|tag| tag.len() >= 2 && tag[0] == name)
+            .map(|tag| tag[1].as_str())
+    };
+    let url = tag("u").ok_or("Auth event has no u tag")?;
+    if path_and_query(url) != request.path_and_query {
+        return Err("Auth event is for a different URL");
+    }
+    if !tag("method").is_some_and(|method| method.eq_ignore_ascii_case(request.method)) {
+        return Err("Auth event is for a different method");
+    }
+    // A body must be signed too, or a captured header could be replayed with another one
+    if !request.body.is_empty() || tag("payload").is_some() {
+        let digest = format!("{:x}", Sha256::digest(request.body));
+        if !tag("payload").is_some_and(|payload| payload.eq_ignore_ascii_case(&digest)) {
+            return Err("Auth event payload does not match the body");
+        }
+    }
+    Ok(())
+}
+
+/// Path and query of an absolute URL. The scheme and host are not compared, since behind a
+/// proxy the oracle can't tell which public URL it was reached at.
+fn path_and_query(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest = rest.split('#').next().unwrap_or_default();
+    rest.find('/').map_or("/", |start| &rest[start..])
+}
+
+fn unauthorized(reason: &str) -> ErrorResponse {
+    ErrorResponse::unauthorized().with_message(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&[&str]]) -> Vec<Vec<String>> {
+        tags.iter().map(|tag| tag.iter().map(|s| s.to_string()).collect()).collect()
+    }
+
+    fn checked(kind: u16, created_at: u64, tags: &[Vec<String>], request: &SignedRequest) -> Result<(), &'static str> {
+        let tags: Vec<&[String]> = tags.iter().map(Vec::as_slice).collect();
+        check(kind, created_at, &tags, request, 1000)
+    }
+
+    #[test]
+    fn test_check() {
+        let get = SignedRequest { path_and_query: "/distance?to=b", method: "GET", body: b"" };
+        let signed = tags(&[&["u", "https://oracle.example/distance?to=b"], &["method", "GET"]]);
+        assert_eq!(checked(27235, 1000, &signed, &get), Ok(()));
+        assert_eq!(checked(27235, 1050, &signed, &get), Ok(()));
+        assert!(checked(27235, 900, &signed, &get).is_err());
+        assert!(checked(1, 1000, &signed, &get).is_err());
+
+        let other_url = SignedRequest { path_and_query: "/distance?to=c", ..get };
+        assert_eq!(checked(27235, 1000, &signed, &other_url), Err("Auth event is for a different URL"));
+        let post = SignedRequest { path_and_query: "/distance?to=b", method: "POST", body: b"" };
+        assert_eq!(checked(27235, 1000, &signed, &post), Err("Auth event is for a different method"));
+        assert!(checked(27235, 1000, &tags(&[&["method", "GET"]]), &get).is_err());
+
+        // Bodies must match the signed payload hash
+        let body = br#"{"max_hops":2}"#;
+        let put = SignedRequest { path_and_query: "/me/settings", method: "PUT", body };
+        let digest = format!("{:x}", Sha256::digest(body));
+        let signed = tags(&[&["u", "http://localhost:8080/me/settings"], &["method", "PUT"], &["payload", digest.as_str()]]);
+        assert_eq!(checked(27235, 1000, &signed, &put), Ok(()));
+        let swapped = SignedRequest { body: br#"{"max_hops":5}"#, ..put };
+        assert!(checked(27235, 1000, &signed, &swapped).is_err());
+        let unsigned_body = tags(&[&["u", "http://localhost:8080/me/settings"], &["method", "PUT"]]);
+        assert!(checked(27235, 1000, &unsigned_body, &put).is_err());
+    }
+
+    #[test]
+    fn test_authenticate_without_nostr_header() {
+        let request = SignedRequest { path_and_query: "/distance", method: "GET", body: b"" };
+        let mut headers = HeaderMap::new();
+        assert_eq!(authenticate(&headers, &request, 0).unwrap(), None);
+        headers.insert(AUTHORIZATION, "Bearer token".parse().unwrap());
+        assert_eq!(authenticate(&headers, &request, 0).unwrap(), None);
+        headers.insert(AUTHORIZATION, "Nostr !!!".parse().unwrap());
+        assert_eq!(authenticate(&headers, &request, 0).unwrap_err().code, "UNAUTHORIZED");
+    }
+}
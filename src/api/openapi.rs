@@ -0,0 +1,243 @@
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
+use std::sync::OnceLock;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use super::http::AppState;
+use super::{admin, bloom, commitment, followset, http, me, metrics, outbox, replication, simulate};
+
+/// OpenAPI description of the HTTP API, generated from handler annotations
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "WoT Oracle",
+        description = "Nostr Web of Trust oracle: follow-graph distance and path queries"
+    ),
+    paths(
+        http::health,
+        http::get_stats,
+        http::get_popular,
+        metrics::get_metrics,
+        http::get_distance,
+        http::batch_distance,
+        http::multi_source_distance,
+        http::set_distance,
+        simulate::simulate_reach,
+        http::verify_batch,
+        http::get_follows,
+        http::get_node,
+        http::get_score,
+        http::get_lookalikes,
+        http::get_peers,
+        http::get_weak_ties,
+        http::get_cut_vertices,
+        http::get_botscore,
+        bloom::get_bloom,
+        commitment::get_follow_proof,
+        http::get_follower_history,
+        http::get_diff,
+        http::get_daily_diff,
+        outbox::get_outbox,
+        http::get_sample,
+        http::get_common_follows,
+        http::get_endorsers,
+        http::get_path,
+        me::get_settings,
+        me::put_settings,
+        followset::publish_recommendations,
+        admin::get_access,
+        admin::add_access,
+        admin::remove_access,
+        admin::get_anomalies,
+        admin::get_watchlist,
+        admin::add_watchlist,
+        admin::remove_watchlist,
+        admin::get_blocklist,
+        admin::add_blocklist,
+        admin::remove_blocklist,
+        admin::invalidate_cache,
+        admin::reset_metrics,
+        admin::snapshot,
+        admin::reload,
+        admin::pause_ingestion,
+        admin::resume_ingestion,
+        admin::debug,
+        admin::get_report,
+        replication::get_changes,
+    ),
+    modifiers(&TokenAuth),
+    tags(
+        (name = "queries", description = "Follow-graph queries"),
+        (name = "service", description = "Health and statistics"),
+        (name = "settings", description = "Per-user query defaults, authenticated with NIP-98"),
+        (name = "admin", description = "Operator endpoints, enabled when ADMIN_TOKEN is set"),
+        (name = "replication", description = "Graph deltas for replicas, enabled when REPLICATION_TOKEN is set")
+    )
+)]
+pub struct ApiDoc;
+
+struct TokenAuth;
+
+impl Modify for TokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "replication_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "nip98",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "Authorization",
+                "`Nostr <base64 kind 27235 event>` (NIP-98)",
+            ))),
+        );
+    }
+}
+
+/// The spec is fixed at compile time, so build it once
+fn spec() -> &'static utoipa::openapi::OpenApi {
+    static SPEC: OnceLock<utoipa::openapi::OpenApi> = OnceLock::new();
+    SPEC.get_or_init(|| {
+        let mut doc = ApiDoc::openapi();
+        doc.info.version = env!("CARGO_PKG_VERSION").to_string();
+        doc
+    })
+}
+
+pub async fn openapi_json() -> Json<&'static utoipa::openapi::OpenApi> {
+    Json(spec())
+}
+
+/// swagger-ui-dist release the `/docs` page is written against; `SWAGGER_UI_DIR` holds its files
+const SWAGGER_UI_VERSION: &str = "5.17.14";
+
+/// Files served from `SWAGGER_UI_DIR`, with their content types
+const SWAGGER_UI_ASSETS: [(&str, &str); 2] =
+    [("swagger-ui.css", "text/css"), ("swagger-ui-bundle.js", "application/javascript")];
+
+// Swagger UI assets are served from our own origin (`SWAGGER_UI_DIR`), so the page runs no
+// third-party script; without them it only points at the spec
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>WoT Oracle API</title>
+  <link rel="stylesheet" href="/docs/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="/docs/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+const NO_SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>WoT Oracle API</title>
+</head>
+<body>
+  <p>The OpenAPI spec is at <a href="/openapi.json">/openapi.json</a>. Swagger UI is not installed
+  on this server (<code>SWAGGER_UI_DIR</code>).</p>
+</body>
+</html>
+"##;
+
+pub async fn swagger_ui(State(state): State<AppState>) -> Html<&'static str> {
+    match state.config.swagger_ui_dir {
+        Some(_) => Html(SWAGGER_UI_HTML),
+        None => Html(NO_SWAGGER_UI_HTML),
+    }
+}
+
+/// One of the Swagger UI files from `SWAGGER_UI_DIR`; anything else is 404
+pub async fn swagger_ui_asset(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    let asset = SWAGGER_UI_ASSETS.iter().find(|(file, _)| *file == name);
+    let (Some((file, content_type)), Some(dir)) = (asset, &state.config.swagger_ui_dir) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match tokio::fs::read(std::path::Path::new(dir).join(file)).await {
+        Ok(body) => ([(header::CONTENT_TYPE, *content_type), (header::CACHE_CONTROL, "public, max-age=86400")], body)
+            .into_response(),
+        Err(e) => {
+            tracing::warn!("Failed to read {} (swagger-ui-dist {}) from {}: {}", file, SWAGGER_UI_VERSION, dir, e);
+            StatusCode::NOT_FOUND.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_covers_routes() {
+        let doc = spec();
+        for path in [
+            "/health",
+            "/stats",
+            "/stats/popular",
+            "/metrics",
+            "/distance",
+            "/distance/batch",
+            "/distance/multi-source",
+            "/distance/sets",
+            "/simulate/reach",
+            "/verify/batch",
+            "/follows",
+            "/node/{pubkey}",
+            "/score/{pubkey}",
+            "/lookalikes/{pubkey}",
+            "/peers/{pubkey}",
+            "/weak-ties/{pubkey}",
+            "/ego/cut-vertices/{pubkey}",
+            "/botscore/{pubkey}",
+            "/bloom/{pubkey}",
+            "/proof/follows/{pubkey}",
+            "/history/followers/{pubkey}",
+            "/diff",
+            "/diff/daily",
+            "/outbox",
+            "/sample",
+            "/common-follows",
+            "/endorsers",
+            "/path",
+            "/me/settings",
+            "/me/recommendations/publish",
+            "/admin/access",
+            "/admin/access/{list}",
+            "/admin/anomalies",
+            "/admin/watchlist",
+            "/admin/blocklist",
+            "/admin/cache/invalidate",
+            "/admin/metrics/reset",
+            "/admin/snapshot",
+            "/admin/reload",
+            "/admin/ingestion/pause",
+            "/admin/ingestion/resume",
+            "/admin/debug",
+            "/admin/report",
+            "/replication/changes",
+        ] {
+            assert!(doc.paths.paths.contains_key(path), "missing {}", path);
+        }
+
+        let schemas = &doc.components.as_ref().unwrap().schemas;
+        assert!(schemas.contains_key("DistanceResult"));
+        assert!(schemas.contains_key("ErrorResponse"));
+    }
+}
@@ -0,0 +1,84 @@
+//! Tail of applied contact list changes, for downstream consumers (search indexes, analytics)
+//! that mirror the oracle incrementally. Entries carry a sequence that only ever increases;
+//! a consumer stores the last `seq` it processed and asks for what came after.
+
+use axum::{extract::Query, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::http::{ErrorResponse, GraphParam, SelectedGraph};
+use crate::db::OutboxEntry;
+
+const LIMIT_DEFAULT: usize = 100;
+const LIMIT_MAX: usize = 1000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OutboxQueryParams {
+    /// Return changes after this sequence (default 0: from the oldest kept)
+    pub since_seq: Option<i64>,
+    /// Entries per page (1-1000, default 100)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OutboxResponse {
+    /// Changes after `since_seq`, oldest first
+    pub entries: Vec<OutboxEntry>,
+    /// `since_seq` for the next page: the last entry's `seq`, or `since_seq` when there is nothing new
+    pub next_seq: i64,
+    /// Oldest change still kept (`OUTBOX_RETENTION_DAYS`), or the next to be recorded once all
+    /// have been pruned; absent before the first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest_seq: Option<i64>,
+    /// Changes after `since_seq` were pruned before they were read; the consumer should resync
+    pub gap: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/outbox",
+    tag = "queries",
+    params(OutboxQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Applied contact list changes after since_seq", body = OutboxResponse),
+        (status = 400, description = "Invalid limit", body = ErrorResponse)
+    )
+)]
+pub async fn get_outbox(
+    SelectedGraph(state): SelectedGraph,
+    Query(params): Query<OutboxQueryParams>,
+) -> Result<Json<OutboxResponse>, ErrorResponse> {
+    let limit = params.limit.unwrap_or(LIMIT_DEFAULT);
+    if !(1..=LIMIT_MAX).contains(&limit) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_LIMIT",
+            format!("limit must be between 1 and {}", LIMIT_MAX),
+        ));
+    }
+    let since_seq = params.since_seq.unwrap_or(0).max(0);
+
+    let db = state.db.clone();
+    let (mut entries, oldest_seq) = tokio::task::spawn_blocking(move || {
+        // Oldest after the page, so entries pruned in between show up as a gap
+        Ok::<_, anyhow::Error>((db.outbox_since(since_seq, limit)?, db.outbox_oldest_seq()?))
+    })
+    .await
+    .map_err(|e| ErrorResponse::internal(e.to_string()))?
+    .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+
+    let next_seq = entries.last().map_or(since_seq, |entry| entry.seq);
+    // Pubkeys excluded by the access lists are left out, as everywhere else
+    entries.retain(|entry| state.access.is_pubkey_allowed(&entry.pubkey));
+    for entry in &mut entries {
+        entry.added.retain(|pk| state.access.is_pubkey_allowed(pk));
+        entry.removed.retain(|pk| state.access.is_pubkey_allowed(pk));
+    }
+
+    Ok(Json(OutboxResponse {
+        entries,
+        next_seq,
+        oldest_seq,
+        gap: oldest_seq.is_some_and(|oldest| oldest > since_seq + 1),
+    }))
+}
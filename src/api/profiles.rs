@@ -0,0 +1,150 @@
+//! Display names and pictures for the HTML explorer. The graph only keeps contact lists, so
+//! kind 0 metadata is fetched from the ingestion relays on demand and cached.
+
+use moka::sync::Cache;
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+const PROFILE_CACHE_SIZE: u64 = 50_000;
+// Pubkeys without metadata are cached too, so they aren't asked for on every page
+const PROFILE_CACHE_TTL: Duration = Duration::from_secs(6 * 3600);
+const PROFILE_FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The parts of a kind 0 profile the explorer shows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// `display_name`, else `name`
+    pub name: Option<String>,
+    /// https URL only
+    pub picture: Option<String>,
+    pub nip05: Option<String>,
+}
+
+impl Profile {
+    fn from_metadata(metadata: Metadata) -> Self {
+        let non_empty = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        Self {
+            name: non_empty(metadata.display_name).or_else(|| non_empty(metadata.name)),
+            picture: non_empty(metadata.picture).filter(|url| url.starts_with("https://")),
+            nip05: non_empty(metadata.nip05),
+        }
+    }
+}
+
+pub struct Profiles {
+    relays: Vec<String>,
+    // Connected on first use, so deployments that never serve the explorer open no sockets
+    client: OnceCell<Client>,
+    cache: Cache<String, Profile>,
+}
+
+impl Profiles {
+    /// Profiles fetched from `relays`; none are fetched without relays
+    pub fn new(relays: Vec<String>) -> Self {
+        Self {
+            relays,
+            client: OnceCell::new(),
+            cache: Cache::builder()
+                .max_capacity(PROFILE_CACHE_SIZE)
+                .time_to_live(PROFILE_CACHE_TTL)
+                .build(),
+        }
+    }
+
+    /// Profiles of hex `pubkeys`, from the cache or one request to the relays. Pubkeys without
+    /// metadata get an empty profile; after a failed request they are left out and not cached.
+    pub async fn get(&self, pubkeys: &[String]) -> HashMap<String, Profile> {
+        let mut profiles = HashMap::new();
+        let mut missing = Vec::new();
+        for pubkey in pubkeys {
+            match self.cache.get(pubkey) {
+                Some(profile) => {
+                    profiles.insert(pubkey.clone(), profile);
+                }
+                None => missing.extend(PublicKey::from_hex(pubkey).ok()),
+            }
+        }
+        if missing.is_empty() || self.relays.is_empty() {
+            return profiles;
+        }
+
+        let client = self.client().await;
+        let filter = Filter::new().kind(Kind::Metadata).authors(missing.clone());
+        let events = match client.get_events_of(vec![filter], EventSource::relays(Some(PROFILE_FETCH_TIMEOUT))).await {
+            Ok(events) => events,
+            Err(e) => {
+                debug!("Profile fetch failed: {}", e);
+                return profiles;
+            }
+        };
+
+        // Newest metadata per author
+        let mut newest: HashMap<PublicKey, &Event> = HashMap::new();
+        for event in &events {
+            let entry = newest.entry(event.pubkey).or_insert(event);
+            if event.created_at > entry.created_at {
+                *entry = event;
+            }
+        }
+        for pubkey in missing {
+            let profile = newest
+                .get(&pubkey)
+                .and_then(|event| Metadata::from_json(&event.content).ok())
+                .map(Profile::from_metadata)
+                .unwrap_or_default();
+            let hex = pubkey.to_hex();
+            self.cache.insert(hex.clone(), profile.clone());
+            profiles.insert(hex, profile);
+        }
+        profiles
+    }
+
+    async fn client(&self) -> &Client {
+        self.client
+            .get_or_init(|| async {
+                let client = Client::default();
+                for relay in &self.relays {
+                    if let Err(e) = client.add_relay(relay.as_str()).await {
+                        debug!("Skipping profile relay {}: {}", relay, e);
+                    }
+                }
+                client.connect().await;
+                client
+            })
+            .await
+    }
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_from_metadata() {
+        let metadata = Metadata::from_json(
+            r#"{"name":"alice","display_name":" Alice ","picture":"http://example.com/a.png","nip05":"alice@example.com"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Profile::from_metadata(metadata),
+            Profile {
+                name: Some("Alice".to_string()),
+                picture: None,
+                nip05: Some("alice@example.com".to_string()),
+            }
+        );
+        let metadata = Metadata::from_json(r#"{"name":"bob","display_name":"","picture":"https://example.com/b.png"}"#).unwrap();
+        let profile = Profile::from_metadata(metadata);
+        assert_eq!(profile.name.as_deref(), Some("bob"));
+        assert_eq!(profile.picture.as_deref(), Some("https://example.com/b.png"));
+    }
+}
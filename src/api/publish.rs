@@ -0,0 +1,220 @@
+use anyhow::{Context, Result};
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+use super::access::AccessLists;
+use crate::config::Config;
+use crate::graph::{pagerank, NodeId, PageRank, WotGraph};
+use crate::types::Pubkey;
+
+/// Parameterized replaceable: one event per `d` tag, replaced on each publication
+pub const RANKING_KIND: u16 = 30950;
+
+const GLOBAL_D_TAG: &str = "wot-rank:global";
+
+#[derive(Debug, Serialize)]
+struct RankedPubkey {
+    pubkey: Pubkey,
+    score: f64,
+}
+
+/// Event content: scores sum to 1 over the whole graph, so only the top entries are listed
+#[derive(Debug, Serialize)]
+struct Ranking {
+    /// Pubkey whose perspective the scores are from; absent for the global ranking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    anchor: Option<String>,
+    epoch: u64,
+    rankings: Vec<RankedPubkey>,
+}
+
+/// Signs and publishes top-N ranking events to the configured relays on a schedule
+pub struct RankingPublisher {
+    graph: Arc<WotGraph>,
+    ranks: Arc<PageRank>,
+    config: Arc<Config>,
+    access: Arc<AccessLists>,
+    keys: Keys,
+}
+
+impl RankingPublisher {
+    pub fn new(
+        graph: Arc<WotGraph>,
+        ranks: Arc<PageRank>,
+        config: Arc<Config>,
+        access: Arc<AccessLists>,
+        keys: Keys,
+    ) -> Self {
+        Self { graph, ranks, config, access, keys }
+    }
+
+    pub async fn start(&self, interval_secs: u64) -> Result<()> {
+        info!(
+            "Starting ranking publisher (every {}s, top {}, {} anchors)",
+            interval_secs,
+            self.config.publish_top_n,
+            self.config.publish_anchors.len()
+        );
+
+        let client = Client::new(&self.keys);
+        for relay_url in &self.config.relays {
+            match client.add_relay(relay_url).await {
+                Ok(_) => info!("Publisher added relay: {}", relay_url),
+                Err(e) => warn!("Publisher failed to add relay {}: {}", relay_url, e),
+            }
+        }
+        client.connect().await;
+
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        // Replaceable events stay current until the graph changes
+        let mut published_epoch = None;
+
+        loop {
+            interval.tick().await;
+
+            // Global scores come from the scheduler's PageRank jobs; recomputing them here
+            // would race those jobs and consume the change journal they update from
+            if self.ranks.epoch().is_none() {
+                debug!("PageRank not computed yet, skipping publication");
+                continue;
+            }
+
+            let epoch = self.graph.epoch();
+            if published_epoch == Some(epoch) {
+                debug!("Graph unchanged since last publication, skipping");
+                continue;
+            }
+
+            match self.publish(&client, epoch).await {
+                Ok(count) => {
+                    info!("Published {} ranking events at epoch {}", count, epoch);
+                    published_epoch = Some(epoch);
+                }
+                Err(e) => error!("Failed to publish rankings: {}", e),
+            }
+        }
+    }
+
+    async fn publish(&self, client: &Client, epoch: u64) -> Result<usize> {
+        let mut events = vec![self.global_ranking().await?];
+        for anchor in &self.config.publish_anchors {
+            match self.anchor_ranking(anchor, epoch).await? {
+                Some(builder) => events.push(builder),
+                None => warn!("Publish anchor {} is not in the graph, skipping", anchor),
+            }
+        }
+
+        let count = events.len();
+        for builder in events {
+            client.send_event_builder(builder).await?;
+        }
+        Ok(count)
+    }
+
+    /// Top of the scheduler-maintained global PageRank, stamped with the epoch it reflects
+    async fn global_ranking(&self) -> Result<EventBuilder> {
+        let ranks = self.ranks.clone();
+        let top_n = self.config.publish_top_n;
+        let (epoch, top) = tokio::task::spawn_blocking(move || (ranks.epoch(), ranks.top(top_n)))
+            .await
+            .context("PageRank task failed")?;
+
+        let ranking = Ranking {
+            anchor: None,
+            epoch: epoch.unwrap_or_default(),
+            rankings: self.resolve(&top),
+        };
+        ranking_event(GLOBAL_D_TAG, &ranking)
+    }
+
+    async fn anchor_ranking(&self, anchor: &str, epoch: u64) -> Result<Option<EventBuilder>> {
+        let Some(seed) = Pubkey::parse(anchor).ok().and_then(|pubkey| self.graph.node_id(&pubkey)) else {
+            return Ok(None);
+        };
+
+        let graph = self.graph.clone();
+        let top_n = self.config.publish_top_n;
+        let top = tokio::task::spawn_blocking(move || {
+            let scores = graph.with_adjacency(|follows, _| pagerank::personalized(follows, seed));
+            pagerank::top_n(&scores, top_n, Some(seed))
+        })
+        .await
+        .context("Personalized PageRank task failed")?;
+
+        let ranking = Ranking {
+            anchor: Some(anchor.to_string()),
+            epoch,
+            rankings: self.resolve(&top),
+        };
+        ranking_event(&format!("wot-rank:{}", anchor), &ranking).map(Some)
+    }
+
+    /// Node IDs to pubkeys, dropping anything excluded by the pubkey access lists
+    fn resolve(&self, top: &[(NodeId, f64)]) -> Vec<RankedPubkey> {
+        top.iter()
+            .filter_map(|&(id, score)| self.graph.pubkey(id).map(|pubkey| RankedPubkey { pubkey, score }))
+            .filter(|entry| self.access.is_pubkey_allowed(&entry.pubkey.to_hex()))
+            .collect()
+    }
+}
+
+fn ranking_event(d_tag: &str, ranking: &Ranking) -> Result<EventBuilder> {
+    let mut tags = vec![
+        Tag::parse(&["d", d_tag])?,
+        Tag::parse(&["epoch", &ranking.epoch.to_string()])?,
+        Tag::parse(&["alt", "Web of Trust ranking"])?,
+    ];
+    if let Some(ref anchor) = ranking.anchor {
+        tags.push(Tag::parse(&["p", anchor])?);
+    }
+
+    let content = serde_json::to_string(ranking)?;
+    Ok(EventBuilder::new(Kind::Custom(RANKING_KIND), content, tags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranking_content() {
+        let ranking = Ranking {
+            anchor: None,
+            epoch: 7,
+            rankings: vec![RankedPubkey { pubkey: Pubkey::from_bytes([0xaa; 32]), score: 0.5 }],
+        };
+        assert_eq!(
+            serde_json::to_string(&ranking).unwrap(),
+            format!(r#"{{"epoch":7,"rankings":[{{"pubkey":"{}","score":0.5}}]}}"#, "aa".repeat(32))
+        );
+    }
+
+    #[test]
+    fn test_resolve_respects_access_lists() {
+        const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+        let graph = Arc::new(WotGraph::new());
+        graph.update_follows(A, &[B.to_string()], None, None);
+        let config = Arc::new(Config {
+            pubkey_denylist: vec![B.to_string()],
+            ..Config::from_env()
+        });
+        let publisher = RankingPublisher::new(
+            graph.clone(),
+            Arc::new(PageRank::new()),
+            config.clone(),
+            Arc::new(AccessLists::from_config(&config)),
+            Keys::generate(),
+        );
+
+        let a = graph.node_id(&A.parse().unwrap()).unwrap();
+        let b = graph.node_id(&B.parse().unwrap()).unwrap();
+        let resolved = publisher.resolve(&[(b, 0.6), (a, 0.4)]);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].pubkey.to_hex(), A);
+    }
+}
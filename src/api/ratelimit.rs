@@ -0,0 +1,456 @@
+use axum::{
+    body::{self, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use governor::{
+    clock::{Clock, DefaultClock},
+    middleware::StateInformationMiddleware,
+    state::keyed::DefaultKeyedStateStore,
+    Quota, RateLimiter,
+};
+use ipnet::IpNet;
+use serde::{de::IgnoredAny, Deserialize};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+use super::http::{AppState, ErrorResponse};
+use super::identity;
+use crate::config::{EXPLORE_RATE_LIMIT_DEFAULT, REQUEST_BODY_LIMIT};
+
+type KeyedLimiter =
+    RateLimiter<IpAddr, DefaultKeyedStateStore<IpAddr>, DefaultClock, StateInformationMiddleware>;
+
+const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+
+// Token cost per request, by route weight
+pub const COST_LIGHT: u32 = 1; // health, stats, follows, node, score, follow proofs, follower history, edge-check base cost
+pub const COST_QUERY: u32 = 2; // distance, common-follows, bloom, batch and multi-source base cost
+pub const COST_PATH: u32 = 3; // path reconstruction keeps parent maps per query; GraphQL
+pub const COST_NIP05: u32 = 2; // each NIP-05 identifier in a batch body, which may be fetched
+
+/// Batch and multi-source requests are charged one extra token per this many targets or sources
+pub const BATCH_TARGETS_PER_TOKEN: usize = 10;
+/// Edge checks in `/verify/batch` are binary searches, so this many count as one batch target
+pub const EDGE_CHECKS_PER_TARGET: usize = 10;
+
+/// Which token bucket a route draws from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Read,
+    Admin,
+    /// The HTML explorer, for humans, on a smaller budget than the API
+    Explore,
+}
+
+/// Client IP resolved by the rate limit middleware, available to handlers
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// Outcome of a rate limit check, reported to clients as response headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// Bucket capacity in tokens
+    pub limit: u32,
+    /// Tokens left after this request
+    pub remaining: u32,
+    /// Set when the request was rejected
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitStatus {
+    pub fn is_limited(&self) -> bool {
+        self.retry_after.is_some()
+    }
+
+    /// Write `X-RateLimit-Limit`, `X-RateLimit-Remaining` and, when limited, `Retry-After`
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(X_RATELIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(self.remaining));
+        if let Some(wait) = self.retry_after {
+            headers.insert(RETRY_AFTER, HeaderValue::from(retry_after_secs(wait)));
+        }
+    }
+}
+
+/// Retry-After takes whole seconds; round up so clients never retry too early
+pub(super) fn retry_after_secs(wait: Duration) -> u64 {
+    let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    secs.max(1)
+}
+
+/// Per-IP weighted rate limiting with separate read, admin and explorer buckets.
+/// Each bucket refills at its per-minute budget with a ~10 second burst.
+pub struct RateLimits {
+    clock: DefaultClock,
+    read: KeyedLimiter,
+    read_burst: u32,
+    admin: KeyedLimiter,
+    admin_burst: u32,
+    explore: KeyedLimiter,
+    explore_burst: u32,
+}
+
+impl RateLimits {
+    pub fn new(read_per_minute: u32, admin_per_minute: u32) -> Self {
+        let clock = DefaultClock::default();
+        let (read, read_burst) = keyed_limiter(read_per_minute, &clock);
+        let (admin, admin_burst) = keyed_limiter(admin_per_minute, &clock);
+        let (explore, explore_burst) = keyed_limiter(EXPLORE_RATE_LIMIT_DEFAULT, &clock);
+        Self {
+            clock,
+            read,
+            read_burst,
+            admin,
+            admin_burst,
+            explore,
+            explore_burst,
+        }
+    }
+
+    /// Budget of the explorer bucket, `EXPLORE_RATE_LIMIT_DEFAULT` otherwise
+    pub fn with_explore_limit(mut self, per_minute: u32) -> Self {
+        (self.explore, self.explore_burst) = keyed_limiter(per_minute, &self.clock);
+        self
+    }
+
+    pub fn read_burst(&self) -> u32 {
+        self.read_burst
+    }
+
+    /// Charge `cost` tokens to `ip` in the given bucket.
+    /// Costs above the burst size are capped so heavy requests drain the bucket
+    /// instead of being permanently unservable.
+    pub fn check(&self, bucket: Bucket, ip: IpAddr, cost: u32) -> RateLimitStatus {
+        let (limiter, burst) = match bucket {
+            Bucket::Read => (&self.read, self.read_burst),
+            Bucket::Admin => (&self.admin, self.admin_burst),
+            Bucket::Explore => (&self.explore, self.explore_burst),
+        };
+
+        let cost = NonZeroU32::new(cost.clamp(1, burst)).unwrap();
+        match limiter.check_key_n(&ip, cost) {
+            Ok(Ok(snapshot)) => RateLimitStatus {
+                limit: burst,
+                remaining: snapshot.remaining_burst_capacity(),
+                retry_after: None,
+            },
+            Ok(Err(not_until)) => RateLimitStatus {
+                limit: burst,
+                remaining: 0,
+                retry_after: Some(not_until.wait_time_from(self.clock.now())),
+            },
+            // Unreachable since cost is capped at the burst size
+            Err(_) => RateLimitStatus {
+                limit: burst,
+                remaining: 0,
+                retry_after: Some(Duration::from_secs(60)),
+            },
+        }
+    }
+
+    /// Drop state for keys whose buckets have fully refilled
+    pub fn retain_recent(&self) {
+        self.read.retain_recent();
+        self.admin.retain_recent();
+        self.explore.retain_recent();
+    }
+}
+
+fn keyed_limiter(per_minute: u32, clock: &DefaultClock) -> (KeyedLimiter, u32) {
+    let per_minute = per_minute.max(1);
+    let burst = std::cmp::max(5, per_minute / 6); // 10 sec burst
+    let quota = Quota::per_minute(NonZeroU32::new(per_minute).unwrap())
+        .allow_burst(NonZeroU32::new(burst).unwrap());
+    let limiter: KeyedLimiter = RateLimiter::new(quota, DefaultKeyedStateStore::default(), clock);
+    (limiter, burst)
+}
+
+/// Extra tokens charged for a batch or multi-source request on top of its base cost
+pub fn batch_surcharge(target_count: usize) -> u32 {
+    target_count.div_ceil(BATCH_TARGETS_PER_TOKEN) as u32
+}
+
+#[derive(Deserialize)]
+struct BatchTargets {
+    #[serde(default)]
+    targets: Vec<IgnoredAny>,
+    #[serde(default)]
+    sources: Vec<IgnoredAny>,
+    #[serde(default)]
+    pairs: Vec<IgnoredAny>,
+}
+
+/// `/distance/sets` bodies; `from` is a single pubkey in other batch bodies, which fail to parse here
+#[derive(Deserialize)]
+struct SetMembers {
+    from: Vec<IgnoredAny>,
+    to: Vec<IgnoredAny>,
+}
+
+/// Count batch targets, multi-source sources, set members or edge-check pairs (in targets) without
+/// validating them; malformed bodies are rejected by the handler and only pay the base cost here.
+fn count_batch_targets(body: &[u8]) -> usize {
+    let sets = serde_json::from_slice::<SetMembers>(body).map_or(0, |s| s.from.len() + s.to.len());
+    serde_json::from_slice::<BatchTargets>(body)
+        .map(|b| b.targets.len() + b.sources.len() + b.pairs.len().div_ceil(EDGE_CHECKS_PER_TARGET))
+        .unwrap_or(0)
+        + sets
+}
+
+/// Count pubkey inputs of a batch body that resolve by NIP-05 lookup
+fn count_nip05_inputs(body: &[u8]) -> usize {
+    let Ok(serde_json::Value::Object(fields)) = serde_json::from_slice(body) else {
+        return 0;
+    };
+    ["from", "to", "target", "targets", "sources"]
+        .iter()
+        .filter_map(|field| fields.get(*field))
+        .flat_map(|value| match value {
+            serde_json::Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        })
+        .filter(|value| value.as_str().is_some_and(identity::is_nip05))
+        .count()
+}
+
+fn is_batch_route(method: &Method, path: &str) -> bool {
+    method == Method::POST && matches!(path, "/distance/batch" | "/distance/multi-source" | "/distance/sets" | "/verify/batch")
+}
+
+/// Bucket and base token cost for a request
+pub fn route_cost(method: &Method, path: &str) -> (Bucket, u32) {
+    if path == "/admin" || path.starts_with("/admin/") {
+        return (Bucket::Admin, COST_LIGHT);
+    }
+    if path == "/explore" || path.starts_with("/explore/") {
+        let cost = if path == "/explore/distance" { COST_QUERY } else { COST_LIGHT };
+        return (Bucket::Explore, cost);
+    }
+
+    let cost = match (method, path) {
+        (&Method::GET, "/distance") => COST_QUERY,
+        (&Method::POST, "/distance/batch") => COST_QUERY,
+        (&Method::POST, "/distance/multi-source") => COST_QUERY,
+        (&Method::POST, "/distance/sets") => COST_PATH,
+        (&Method::POST, "/simulate/reach") => COST_PATH,
+        (&Method::POST, "/me/recommendations/publish") => COST_PATH,
+        (&Method::GET, "/common-follows") => COST_QUERY,
+        (&Method::GET, "/endorsers") => COST_QUERY,
+        (&Method::GET, "/diff/daily") => COST_QUERY,
+        (&Method::GET, "/sample") => COST_QUERY,
+        (&Method::GET, "/outbox") => COST_QUERY,
+        (&Method::GET, "/path") => COST_PATH,
+        (&Method::GET, path) if path.starts_with("/bloom/") => COST_QUERY,
+        (&Method::GET, path) if path.starts_with("/peers/") => COST_QUERY,
+        (&Method::GET, path) if path.starts_with("/weak-ties/") => COST_PATH,
+        (&Method::GET, path) if path.starts_with("/ego/") => COST_PATH,
+        (&Method::POST, "/graphql") => COST_PATH,
+        _ => COST_LIGHT,
+    };
+    (Bucket::Read, cost)
+}
+
+fn is_trusted(ip: IpAddr, trusted_proxies: &[IpNet]) -> bool {
+    trusted_proxies.iter().any(|net| net.contains(&ip))
+}
+
+/// Resolve the client IP.
+/// Proxy headers are only honored when the socket peer is a trusted proxy;
+/// otherwise they are spoofable and the peer address itself is the client.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let peer = peer?;
+    if !is_trusted(peer, trusted_proxies) {
+        return Some(peer);
+    }
+
+    maybe_x_forwarded_for(headers, trusted_proxies)
+        .or_else(|| maybe_x_real_ip(headers))
+        .or(Some(peer))
+}
+
+/// Walk X-Forwarded-For right to left, skipping our own proxy hops.
+/// The first untrusted address is the client; entries left of it may be forged.
+fn maybe_x_forwarded_for(headers: &HeaderMap, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let hops: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|hv| hv.to_str().ok())
+        .flat_map(|s| s.split(','))
+        .filter_map(|ip| ip.trim().parse().ok())
+        .collect();
+
+    hops.iter()
+        .rev()
+        .find(|ip| !is_trusted(**ip, trusted_proxies))
+        .or(hops.first()) // Every hop is a trusted proxy
+        .copied()
+}
+
+fn maybe_x_real_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get("x-real-ip")
+        .and_then(|hv| hv.to_str().ok())
+        .and_then(|s| s.trim().parse().ok())
+}
+
+/// Middleware charging each request's route cost against the caller's bucket.
+/// Every response carries the bucket state in `X-RateLimit-*` headers.
+pub async fn rate_limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    // ConnectInfo is always present when served via start_server
+    let ip = client_ip(req.headers(), peer, &state.config.trusted_proxies)
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+    let (bucket, mut cost) = route_cost(req.method(), req.uri().path());
+
+    // Batch cost depends on the body, so buffer it (bounded by the body limit) up front
+    let mut req = if is_batch_route(req.method(), req.uri().path()) {
+        let (parts, body) = req.into_parts();
+        let Ok(bytes) = body::to_bytes(body, REQUEST_BODY_LIMIT).await else {
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        };
+        cost = cost.saturating_add(batch_surcharge(count_batch_targets(&bytes)));
+        cost = cost.saturating_add((count_nip05_inputs(&bytes) as u32).saturating_mul(COST_NIP05));
+        Request::from_parts(parts, Body::from(bytes))
+    } else {
+        req
+    };
+
+    let status = state.rate_limits.check(bucket, ip, cost);
+    let mut response = if status.is_limited() {
+        ErrorResponse::rate_limited().into_response()
+    } else {
+        req.extensions_mut().insert(ClientIp(ip));
+        next.run(req).await
+    };
+    status.apply_headers(response.headers_mut());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn test_route_costs() {
+        assert_eq!(route_cost(&Method::GET, "/health"), (Bucket::Read, COST_LIGHT));
+        assert_eq!(route_cost(&Method::GET, "/distance"), (Bucket::Read, COST_QUERY));
+        assert_eq!(route_cost(&Method::GET, "/path"), (Bucket::Read, COST_PATH));
+        assert_eq!(route_cost(&Method::GET, "/bloom/abc"), (Bucket::Read, COST_QUERY));
+        assert_eq!(route_cost(&Method::POST, "/admin/cache/invalidate").0, Bucket::Admin);
+        assert_eq!(route_cost(&Method::GET, "/explore/abc"), (Bucket::Explore, COST_LIGHT));
+        assert_eq!(route_cost(&Method::GET, "/explore/distance"), (Bucket::Explore, COST_QUERY));
+    }
+
+    #[test]
+    fn test_weighted_cost_drains_bucket() {
+        // 60/min → burst of 10 tokens
+        let limits = RateLimits::new(60, 60);
+
+        for i in 1..=5 {
+            let status = limits.check(Bucket::Read, ip(1), COST_QUERY);
+            assert!(!status.is_limited());
+            assert_eq!(status.limit, 10);
+            assert_eq!(status.remaining, 10 - 2 * i);
+        }
+        assert!(limits.check(Bucket::Read, ip(1), COST_QUERY).is_limited());
+
+        // Other IPs have their own bucket
+        assert!(!limits.check(Bucket::Read, ip(2), COST_QUERY).is_limited());
+    }
+
+    #[test]
+    fn test_admin_bucket_separate_from_read() {
+        let limits = RateLimits::new(60, 60);
+
+        assert!(!limits.check(Bucket::Read, ip(1), 10).is_limited());
+        assert!(limits.check(Bucket::Read, ip(1), 1).is_limited());
+        assert!(!limits.check(Bucket::Admin, ip(1), 1).is_limited());
+    }
+
+    #[test]
+    fn test_nip05_inputs_counted() {
+        let hex = "a".repeat(64);
+        let body = serde_json::json!({"from": "bob@example.com", "targets": [hex, "carol@example.com", "example.org"]});
+        assert_eq!(count_nip05_inputs(body.to_string().as_bytes()), 3);
+        assert_eq!(count_nip05_inputs(br#"{"sources":["npub1x"],"target":"nope"}"#), 0);
+        assert_eq!(count_nip05_inputs(b"not json"), 0);
+    }
+
+    #[test]
+    fn test_batch_surcharge_scales_with_targets() {
+        assert_eq!(batch_surcharge(0), 0);
+        assert_eq!(batch_surcharge(1), 1);
+        assert_eq!(batch_surcharge(100), 10);
+        assert_eq!(count_batch_targets(br#"{"from":"a","targets":["b","c","d"]}"#), 3);
+        assert_eq!(count_batch_targets(br#"{"sources":["a","b"],"target":"c"}"#), 2);
+        let pairs: Vec<_> = (0..25).map(|_| serde_json::json!({"from": "a", "to": "b"})).collect();
+        assert_eq!(count_batch_targets(serde_json::json!({ "pairs": pairs }).to_string().as_bytes()), 3);
+        assert_eq!(count_batch_targets(br#"{"from":["a","b"],"to":["c"],"max_hops":3}"#), 3);
+        assert_eq!(count_batch_targets(b"not json"), 0);
+
+        // Oversized costs are capped at the burst rather than rejected forever
+        let limits = RateLimits::new(60, 60);
+        assert!(!limits.check(Bucket::Read, ip(2), batch_surcharge(10_000)).is_limited());
+    }
+
+    #[test]
+    fn test_limited_status_headers() {
+        let limits = RateLimits::new(60, 60);
+        limits.check(Bucket::Read, ip(1), 10);
+        let status = limits.check(Bucket::Read, ip(1), 1);
+        assert!(status.is_limited());
+
+        let mut headers = HeaderMap::new();
+        status.apply_headers(&mut headers);
+        assert_eq!(headers[X_RATELIMIT_LIMIT], "10");
+        assert_eq!(headers[X_RATELIMIT_REMAINING], "0");
+        // One token refills every second at 60/min
+        assert_eq!(headers[RETRY_AFTER], "1");
+    }
+
+    #[test]
+    fn test_retry_after_rounds_up() {
+        assert_eq!(retry_after_secs(Duration::ZERO), 1);
+        assert_eq!(retry_after_secs(Duration::from_millis(1500)), 2);
+        assert_eq!(retry_after_secs(Duration::from_secs(3)), 3);
+    }
+
+    #[test]
+    fn test_client_ip_ignores_headers_from_untrusted_peer() {
+        let trusted: Vec<IpNet> = vec!["192.168.0.0/16".parse().unwrap()];
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.1".parse().unwrap());
+        headers.insert("x-real-ip", "10.0.0.2".parse().unwrap());
+
+        assert_eq!(client_ip(&headers, Some(ip(9)), &trusted), Some(ip(9)));
+        assert_eq!(client_ip(&headers, Some(ip(9)), &[]), Some(ip(9)));
+    }
+
+    #[test]
+    fn test_client_ip_from_trusted_proxy() {
+        let trusted: Vec<IpNet> = vec!["192.168.0.0/16".parse().unwrap()];
+        let proxy = Some("192.168.1.1".parse().unwrap());
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_ip(&headers, proxy, &trusted), proxy);
+
+        headers.insert("x-real-ip", "10.0.0.2".parse().unwrap());
+        assert_eq!(client_ip(&headers, proxy, &trusted), Some(ip(2)));
+
+        // Rightmost untrusted hop wins; the spoofed leftmost entry is ignored
+        headers.insert("x-forwarded-for", "10.0.0.7, 10.0.0.1, 192.168.5.5".parse().unwrap());
+        assert_eq!(client_ip(&headers, proxy, &trusted), Some(ip(1)));
+    }
+}
@@ -0,0 +1,347 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use nostr_sdk::prelude::*;
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use tracing::{debug, warn};
+
+use super::access::SourceAccess;
+use super::http::{cached_distance, validate_subject, AppState, DistanceQueryParams};
+use super::ratelimit::{self, Bucket, ClientIp, COST_QUERY};
+
+/// Result events use the DVM response kind and content shape
+pub const RELAY_RESULT_KIND: u16 = 6950;
+
+const MAX_FILTERS: usize = 10;
+const MAX_TARGETS_PER_REQ: usize = 100;
+const MAX_SUBSCRIPTION_ID_LEN: usize = 64;
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// A `{"kinds":[6950], "#f":[from], "#p":[to, ...]}` filter
+#[derive(Debug, PartialEq, Eq)]
+struct RelayQuery {
+    from: String,
+    targets: Vec<String>,
+}
+
+/// Extract a distance query from a NIP-01 filter.
+/// Filters that don't ask for result events are ignored (`Ok(None)`).
+fn parse_query(filter: &Value) -> Result<Option<RelayQuery>, String> {
+    let wants_results = filter
+        .get("kinds")
+        .and_then(Value::as_array)
+        .is_some_and(|kinds| kinds.iter().any(|k| k.as_u64() == Some(RELAY_RESULT_KIND as u64)));
+    if !wants_results {
+        return Ok(None);
+    }
+
+    let strings = |key: &str| -> Vec<String> {
+        filter
+            .get(key)
+            .and_then(Value::as_array)
+            .map(|values| values.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    };
+
+    let from = match strings("#f").as_slice() {
+        [from] => from.clone(),
+        _ => return Err("expected exactly one #f (source pubkey)".to_string()),
+    };
+    let targets = strings("#p");
+    if targets.is_empty() {
+        return Err("expected at least one #p (target pubkey)".to_string());
+    }
+
+    Ok(Some(RelayQuery { from, targets }))
+}
+
+fn closed(subscription_id: &str, message: &str) -> String {
+    json!(["CLOSED", subscription_id, message]).to_string()
+}
+
+fn notice(message: &str) -> String {
+    json!(["NOTICE", message]).to_string()
+}
+
+/// Handle one client message, returning the relay messages to send back
+async fn handle_message(state: &AppState, keys: &Keys, ip: Option<IpAddr>, text: &str) -> Vec<String> {
+    let Ok(Value::Array(message)) = serde_json::from_str::<Value>(text) else {
+        return vec![notice("invalid: message is not a JSON array")];
+    };
+
+    match message.first().and_then(Value::as_str) {
+        Some("REQ") => handle_req(state, keys, ip, &message[1..]).await,
+        // Subscriptions end at EOSE, so there is nothing to close
+        Some("CLOSE") => Vec::new(),
+        Some("EVENT") => {
+            let id = message
+                .get(1)
+                .and_then(|event| event.get("id"))
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            vec![json!(["OK", id, false, "blocked: this relay is read-only"]).to_string()]
+        }
+        _ => vec![notice("invalid: unsupported message type")],
+    }
+}
+
+async fn handle_req(state: &AppState, keys: &Keys, ip: Option<IpAddr>, args: &[Value]) -> Vec<String> {
+    let Some(subscription_id) = args.first().and_then(Value::as_str) else {
+        return vec![notice("invalid: REQ requires a subscription id")];
+    };
+    if subscription_id.is_empty() || subscription_id.len() > MAX_SUBSCRIPTION_ID_LEN {
+        return vec![closed(subscription_id, "invalid: subscription id must be 1-64 characters")];
+    }
+
+    let filters = &args[1..];
+    if filters.len() > MAX_FILTERS {
+        return vec![closed(subscription_id, "invalid: too many filters")];
+    }
+
+    let mut queries = Vec::new();
+    for filter in filters {
+        match parse_query(filter) {
+            Ok(Some(query)) => queries.push(query),
+            Ok(None) => {}
+            Err(e) => return vec![closed(subscription_id, &format!("invalid: {}", e))],
+        }
+    }
+
+    let pair_count: usize = queries.iter().map(|q| q.targets.len()).sum();
+    if pair_count > MAX_TARGETS_PER_REQ {
+        return vec![closed(subscription_id, "invalid: too many target pubkeys")];
+    }
+    for pubkey in queries.iter().flat_map(|q| std::iter::once(&q.from).chain(&q.targets)) {
+        if let Err(e) = validate_subject(state, pubkey) {
+            return vec![closed(subscription_id, &format!("invalid: {}", e.error))];
+        }
+    }
+    // Relay clients can't present API keys, so only registered sources are served
+    for query in &queries {
+        if let Err(e) = SourceAccess::default().check(&state.access, &query.from) {
+            return vec![closed(subscription_id, &format!("restricted: {}", e.error))];
+        }
+    }
+
+    // Each REQ is charged like a batch request of the same size
+    if let (Some(ip), true) = (ip, pair_count > 0) {
+        let cost = COST_QUERY + ratelimit::batch_surcharge(pair_count);
+        if state.rate_limits.check(Bucket::Read, ip, cost).is_limited() {
+            return vec![closed(subscription_id, "rate-limited: slow down")];
+        }
+    }
+
+    let mut replies = Vec::with_capacity(pair_count + 1);
+    for query in queries {
+        for target in query.targets {
+            let params = DistanceQueryParams {
+                from: query.from.clone(),
+                to: target,
+                max_hops: state.config.max_hops,
+                include_bridges: false,
+                bypass_cache: false,
+                reverse: false,
+                max_bridges: None,
+                min_followers: 0,
+                path_weighting: None,
+            };
+            let event = match cached_distance(state, &params).await {
+                Ok(result) => result_event(keys, &result),
+                Err(e) => return vec![closed(subscription_id, &format!("error: {}", e.error))],
+            };
+            match event {
+                Ok(event) => replies.push(format!(
+                    r#"["EVENT",{},{}]"#,
+                    Value::from(subscription_id),
+                    event.as_json()
+                )),
+                Err(e) => {
+                    warn!("Failed to sign relay result event: {}", e);
+                    return vec![closed(subscription_id, "error: could not sign result")];
+                }
+            }
+        }
+    }
+
+    replies.push(json!(["EOSE", subscription_id]).to_string());
+    replies
+}
+
+/// Signed result event; tags mirror the filter so the event matches the REQ
+fn result_event(keys: &Keys, result: &crate::graph::bfs::DistanceResult) -> anyhow::Result<Event> {
+    let mut tags = vec![
+        Tag::parse(&["f", &result.from.to_hex()])?,
+        Tag::parse(&["p", &result.to.to_hex()])?,
+    ];
+    if let Some(hops) = result.hops {
+        tags.push(Tag::parse(&["result", &hops.to_string(), "hops"])?);
+    }
+
+    let content = serde_json::to_string(result)?;
+    Ok(EventBuilder::new(Kind::Custom(RELAY_RESULT_KIND), content, tags).to_event(keys)?)
+}
+
+/// NIP-11 relay information document
+fn relay_info(keys: &Keys) -> Response {
+    let info = json!({
+        "name": "WoT Oracle",
+        "description": format!(
+            "Read-only relay answering REQs for kind {} with signed Web of Trust distance results",
+            RELAY_RESULT_KIND
+        ),
+        "pubkey": keys.public_key().to_hex(),
+        "supported_nips": [1, 11],
+        "software": "wot-oracle",
+        "version": env!("CARGO_PKG_VERSION"),
+        "limitation": {
+            "max_message_length": MAX_MESSAGE_SIZE,
+            "max_filters": MAX_FILTERS,
+            "max_subid_length": MAX_SUBSCRIPTION_ID_LEN,
+            "restricted_writes": true,
+        },
+    });
+    ([(CONTENT_TYPE, "application/nostr+json")], info.to_string()).into_response()
+}
+
+/// WebSocket relay endpoint; plain GETs receive the NIP-11 document
+pub async fn relay_handler(
+    State(state): State<AppState>,
+    client_ip: Option<Extension<ClientIp>>,
+    ws: Option<WebSocketUpgrade>,
+) -> Response {
+    // Only mounted when the oracle has a signing key
+    let Some(keys) = state.oracle_keys.clone() else {
+        return super::http::ErrorResponse::internal("Relay signing key not configured").into_response();
+    };
+
+    let Some(ws) = ws else {
+        return relay_info(&keys);
+    };
+
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+    ws.max_message_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| serve_socket(socket, state, keys, ip))
+}
+
+async fn serve_socket(mut socket: WebSocket, state: AppState, keys: Keys, ip: Option<IpAddr>) {
+    debug!("Relay client connected: {:?}", ip);
+
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue, // Pings are answered by axum
+        };
+
+        for reply in handle_message(&state, &keys, ip, &text).await {
+            if socket.send(Message::Text(reply)).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    debug!("Relay client disconnected: {:?}", ip);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::access::AccessLists;
+    use crate::api::identity::Resolver;
+    use crate::api::ratelimit::RateLimits;
+    use crate::cache::QueryCache;
+    use crate::config::Config;
+    use crate::db::Database;
+    use crate::graph::{PageRank, WotGraph};
+    use std::sync::Arc;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    fn create_test_state() -> AppState {
+        let config = Arc::new(Config::from_env());
+        AppState {
+            graph: Arc::new(WotGraph::new()),
+            cache: Arc::new(QueryCache::new(config.cache_size, config.cache_ttl_secs)),
+            rate_limits: Arc::new(RateLimits::new(60, 60)),
+            access: Arc::new(AccessLists::from_config(&config)),
+            config,
+            ranks: Arc::new(PageRank::new()),
+            components: Arc::default(),
+            anchors: Arc::default(),
+            popular_anchors: Arc::default(),
+            popularity: Arc::default(),
+            sightings: Arc::default(),
+            botscores: Arc::default(),
+            persistence: Arc::default(),
+            jobs: Arc::default(),
+            commitments: Arc::default(),
+            db: Arc::new(Database::open(":memory:").unwrap()),
+            oracle_keys: None,
+            resolver: Arc::new(Resolver::new(false)),
+            profiles: Arc::default(),
+            dvm_stats: Arc::default(),
+            watchlist: Arc::default(),
+            ingestion_pause: Arc::default(),
+            namespaces: Arc::default(),
+            diagnostics: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let filter = json!({"kinds": [6950], "#f": [A], "#p": [B, A]});
+        assert_eq!(
+            parse_query(&filter).unwrap(),
+            Some(RelayQuery {
+                from: A.to_string(),
+                targets: vec![B.to_string(), A.to_string()],
+            })
+        );
+
+        // Other kinds are not ours to answer
+        assert_eq!(parse_query(&json!({"kinds": [1], "#p": [B]})).unwrap(), None);
+
+        assert!(parse_query(&json!({"kinds": [6950], "#p": [B]})).is_err());
+        assert!(parse_query(&json!({"kinds": [6950], "#f": [A, B], "#p": [B]})).is_err());
+        assert!(parse_query(&json!({"kinds": [6950], "#f": [A]})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unrelated_req_gets_eose() {
+        let state = create_test_state();
+        let replies = handle_message(&state, &Keys::generate(), None, r#"["REQ","sub1",{"kinds":[1]}]"#).await;
+        assert_eq!(replies, vec![r#"["EOSE","sub1"]"#.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_req_is_closed() {
+        let state = create_test_state();
+        let keys = Keys::generate();
+
+        let req = json!(["REQ", "sub1", {"kinds": [6950], "#f": ["nope"], "#p": [B]}]).to_string();
+        let replies = handle_message(&state, &keys, None, &req).await;
+        assert_eq!(replies.len(), 1);
+        assert!(replies[0].starts_with(r#"["CLOSED","sub1","invalid:"#));
+
+        let replies = handle_message(&state, &keys, None, r#"["REQ"]"#).await;
+        assert!(replies[0].starts_with(r#"["NOTICE""#));
+    }
+
+    #[tokio::test]
+    async fn test_events_are_rejected() {
+        let state = create_test_state();
+        let replies = handle_message(&state, &Keys::generate(), None, r#"["EVENT",{"id":"abc"}]"#).await;
+        assert_eq!(
+            replies,
+            vec![r#"["OK","abc",false,"blocked: this relay is read-only"]"#.to_string()]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use axum::{
+    extract::{Query, Request, State},
+    http::header::AUTHORIZATION,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::admin::constant_time_eq;
+use super::http::{AppState, ErrorResponse, GraphParam, SelectedGraph};
+use crate::sync::replication::{self, Cursor, ReplicationBatch, PAGE_LIMIT_DEFAULT, PAGE_LIMIT_MAX};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChangesQueryParams {
+    /// `cursor` from the previous batch; omit to start a full snapshot
+    pub cursor: Option<String>,
+    /// Lists per batch (default 1000, max 5000)
+    pub limit: Option<usize>,
+}
+
+/// Replication routes, mounted under `/replication` when `REPLICATION_TOKEN` is set.
+/// Every route requires `Authorization: Bearer <REPLICATION_TOKEN>`.
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/changes", get(get_changes))
+        .route_layer(middleware::from_fn_with_state(state, require_replication_token))
+}
+
+async fn require_replication_token(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(expected) = state.config.replication_token.as_deref() else {
+        return ErrorResponse::unauthorized().into_response();
+    };
+
+    let provided = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => next.run(req).await,
+        _ => ErrorResponse::unauthorized().into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/replication/changes",
+    tag = "replication",
+    security(("replication_token" = [])),
+    params(ChangesQueryParams, GraphParam),
+    responses(
+        (status = 200, description = "Contact lists and blocks written since the cursor", body = ReplicationBatch),
+        (status = 400, description = "Invalid cursor", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid replication token", body = ErrorResponse),
+        (status = 404, description = "Unknown graph", body = ErrorResponse)
+    )
+)]
+pub async fn get_changes(
+    SelectedGraph(state): SelectedGraph,
+    Query(params): Query<ChangesQueryParams>,
+) -> Result<Json<ReplicationBatch>, ErrorResponse> {
+    let cursor = match params.cursor.as_deref() {
+        None | Some("") => Cursor::default(),
+        Some(cursor) => cursor.parse().map_err(|_| ErrorResponse::invalid(
+            "INVALID_CURSOR",
+            "Invalid cursor (expected the cursor from a previous batch)",
+        ))?,
+    };
+    let limit = params.limit.unwrap_or(PAGE_LIMIT_DEFAULT).clamp(1, PAGE_LIMIT_MAX);
+
+    let db = state.db.clone();
+    let now = chrono::Utc::now().timestamp();
+    let batch = tokio::task::spawn_blocking(move || replication::changes(&db, cursor, limit, now))
+        .await
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        .map_err(|e| ErrorResponse::internal(e.to_string()))?;
+    Ok(Json(batch))
+}
@@ -0,0 +1,116 @@
+//! Reach simulation for campaign and broadcast planning: how many pubkeys a post by the source
+//! can be expected to reach at each hop, if followers pass it on with some probability.
+
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::access::SourceAccess;
+use super::http::{default_max_hops, resolve_subject, validate_max_hops, ErrorResponse, GraphParam, SelectedGraph};
+use crate::reach::{self, Model};
+use crate::types::Pubkey;
+
+const RUNS_DEFAULT: usize = 100;
+const RUNS_MAX: usize = 1000;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SimulateReachRequest {
+    /// Author of the post: hex pubkey, npub, nprofile or NIP-05 identifier
+    pub source: String,
+    /// `cascade` (default) or `decay`
+    pub model: Option<String>,
+    /// Chance a follower passes the post on (`cascade`), or is reached per hop (`decay`), in (0, 1]
+    pub probability: f64,
+    #[serde(default = "default_max_hops")]
+    pub max_hops: u8,
+    /// Cascade runs averaged (1-1000, default 100)
+    pub runs: Option<usize>,
+    /// Cascade random seed; random if absent, and returned either way
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SimulateReachResponse {
+    #[schema(value_type = String)]
+    pub source: Pubkey,
+    pub model: String,
+    pub probability: f64,
+    pub max_hops: u8,
+    /// Expected pubkeys first reached at 1, 2, ... `max_hops` hops
+    pub reach_per_hop: Vec<f64>,
+    /// Sum of `reach_per_hop`
+    pub expected_reach: f64,
+    /// Cascade runs averaged, fewer than asked for very large audiences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/simulate/reach",
+    tag = "queries",
+    params(GraphParam),
+    request_body = SimulateReachRequest,
+    responses(
+        (status = 200, description = "Expected reach per hop of a post by the source", body = SimulateReachResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 403, description = "Pubkey excluded by access lists, or unregistered source with RESTRICT_SOURCES", body = ErrorResponse)
+    )
+)]
+pub async fn simulate_reach(
+    SelectedGraph(state): SelectedGraph,
+    sources: SourceAccess,
+    Json(request): Json<SimulateReachRequest>,
+) -> Result<Json<SimulateReachResponse>, ErrorResponse> {
+    let source = resolve_subject(&state, &request.source).await?;
+    sources.check(&state.access, &source.to_hex())?;
+    validate_max_hops(&state.config, request.max_hops)?;
+    let model = match request.model.as_deref() {
+        None => Model::Cascade,
+        Some(m) => Model::parse(m).ok_or_else(|| ErrorResponse::invalid(
+            "INVALID_MODEL",
+            "model must be cascade or decay",
+        ))?,
+    };
+    if !(request.probability > 0.0 && request.probability <= 1.0) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_PROBABILITY",
+            "probability must be greater than 0 and at most 1",
+        ));
+    }
+    let runs = request.runs.unwrap_or(RUNS_DEFAULT);
+    if !(1..=RUNS_MAX).contains(&runs) {
+        return Err(ErrorResponse::invalid(
+            "INVALID_RUNS",
+            format!("runs must be between 1 and {}", RUNS_MAX),
+        ));
+    }
+    // Random seeds stay below 2^53 so JSON clients can pass them back unchanged
+    let seed = request.seed.unwrap_or_else(|| {
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64 & ((1 << 53) - 1)
+    });
+
+    let simulated = match state.graph.node_id(&source) {
+        Some(node_id) => {
+            let (graph, probability, max_hops) = (state.graph.clone(), request.probability, request.max_hops);
+            tokio::task::spawn_blocking(move || reach::simulate(&graph, node_id, model, probability, max_hops, runs, seed))
+                .await
+                .map_err(|e| ErrorResponse::internal(e.to_string()))?
+        }
+        None => reach::Reach { per_hop: vec![0.0; request.max_hops as usize], runs },
+    };
+
+    let cascade = model == Model::Cascade;
+    Ok(Json(SimulateReachResponse {
+        source,
+        model: model.as_str().to_string(),
+        probability: request.probability,
+        max_hops: request.max_hops,
+        expected_reach: simulated.per_hop.iter().sum(),
+        reach_per_hop: simulated.per_hop,
+        runs: cascade.then_some(simulated.runs),
+        seed: cascade.then_some(seed),
+    }))
+}
@@ -0,0 +1,405 @@
+use anyhow::{bail, Context, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config::MAX_HOPS_DEFAULT;
+use crate::db::Database;
+use crate::graph::synth::{BarabasiAlbert, Rng, WattsStrogatz};
+use crate::graph::{bfs, NodeId, WotGraph};
+use crate::types::Pubkey;
+
+const USAGE: &str = "Usage: wot-oracle bench [--model ba|ws] [--nodes N] [--follows N] [--rewire P] \
+[--snapshot PATH] [--threads N] [--duration SECS] [--batch N] [--max-hops N] [--seed N]";
+
+/// Shape of the synthetic graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// Barabási–Albert: scale-free, with heavily followed hubs
+    BarabasiAlbert,
+    /// Watts–Strogatz: small-world ring with clustered neighbourhoods
+    WattsStrogatz,
+}
+
+/// Options for `wot-oracle bench`
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub model: Model,
+    /// Nodes in the synthetic graph
+    pub nodes: usize,
+    /// Follows per synthetic node
+    pub follows: usize,
+    /// Watts–Strogatz rewiring probability
+    pub rewire: f64,
+    /// Benchmark a database instead of a synthetic graph
+    pub snapshot: Option<String>,
+    pub threads: usize,
+    /// Length of each phase
+    pub duration: Duration,
+    /// Targets per batch query
+    pub batch: usize,
+    pub max_hops: u8,
+    pub seed: u64,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            model: Model::BarabasiAlbert,
+            nodes: 100_000,
+            follows: 20,
+            rewire: 0.1,
+            snapshot: None,
+            threads: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            duration: Duration::from_secs(10),
+            batch: 100,
+            max_hops: MAX_HOPS_DEFAULT,
+            seed: 42,
+        }
+    }
+}
+
+impl BenchOptions {
+    /// Parse the arguments following `bench`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            if flag == "--help" || flag == "-h" {
+                bail!(USAGE);
+            }
+            let value = args.next().with_context(|| format!("{} needs a value\n{}", flag, USAGE))?;
+            let number = || value.parse::<u64>().with_context(|| format!("Invalid {}: {}", flag, value));
+            match flag.as_str() {
+                "--model" => {
+                    options.model = match value.as_str() {
+                        "ba" => Model::BarabasiAlbert,
+                        "ws" => Model::WattsStrogatz,
+                        _ => bail!("Unknown model {} (expected ba or ws)", value),
+                    }
+                }
+                "--rewire" => {
+                    options.rewire = value
+                        .parse::<f64>()
+                        .with_context(|| format!("Invalid {}: {}", flag, value))?
+                        .clamp(0.0, 1.0)
+                }
+                "--nodes" => options.nodes = number()?.max(2) as usize,
+                "--follows" => options.follows = number()?.max(1) as usize,
+                "--snapshot" => options.snapshot = Some(value),
+                "--threads" => options.threads = number()?.max(1) as usize,
+                "--duration" => options.duration = Duration::from_secs(number()?.max(1)),
+                "--batch" => options.batch = number()?.max(1) as usize,
+                "--max-hops" => options.max_hops = number()?.clamp(1, u8::MAX as u64) as u8,
+                "--seed" => options.seed = number()?,
+                _ => bail!("Unknown option {}\n{}", flag, USAGE),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Latency percentiles over a set of samples
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Percentiles {
+    fn of(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+        let at = |q: f64| samples[((samples.len() - 1) as f64 * q).round() as usize];
+        Self {
+            p50: at(0.50),
+            p90: at(0.90),
+            p99: at(0.99),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BenchReport {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub load_time: Duration,
+    pub threads: usize,
+    /// Single distance queries per second across all threads
+    pub bfs_qps: f64,
+    pub bfs_latency: Percentiles,
+    pub batches: usize,
+    pub batch_latency: Percentiles,
+    /// Query threads in the contention phase, alongside the one writer
+    pub readers: usize,
+    /// Distance queries per second while one thread rewrites contact lists
+    pub contended_qps: f64,
+    pub contended_latency: Percentiles,
+    pub updates_per_sec: f64,
+    pub update_latency: Percentiles,
+    pub write_lock_max_us: u64,
+    pub read_lock_max_us: u64,
+}
+
+/// Run `worker` on `threads` threads until `duration` passes, collecting each thread's samples
+fn run_for<F>(threads: usize, duration: Duration, worker: F) -> Vec<Duration>
+where
+    F: Fn(usize, &AtomicBool) -> Vec<Duration> + Sync,
+{
+    let (worker, stop) = (&worker, &AtomicBool::new(false));
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads).map(|t| scope.spawn(move || worker(t, stop))).collect();
+        std::thread::sleep(duration);
+        stop.store(true, Ordering::Relaxed);
+        handles.into_iter().flat_map(|h| h.join().unwrap_or_default()).collect()
+    })
+}
+
+/// Build or load the graph, then measure single-query throughput, batch latency and
+/// query throughput under concurrent updates
+pub fn run(options: &BenchOptions) -> Result<BenchReport> {
+    let graph = WotGraph::new();
+
+    let started = Instant::now();
+    match &options.snapshot {
+        Some(path) => {
+            if !std::path::Path::new(path).exists() {
+                bail!("Snapshot {} not found", path);
+            }
+            Database::open(path)?.load_graph(&graph)?;
+        }
+        None => match options.model {
+            Model::BarabasiAlbert => BarabasiAlbert {
+                nodes: options.nodes,
+                follows: options.follows,
+                seed: options.seed,
+            }
+            .populate(&graph),
+            Model::WattsStrogatz => WattsStrogatz {
+                nodes: options.nodes,
+                follows: options.follows,
+                rewire: options.rewire,
+                seed: options.seed,
+            }
+            .populate(&graph),
+        },
+    }
+    let load_time = started.elapsed();
+
+    let stats = graph.stats();
+    if stats.node_count < 2 {
+        bail!("Graph has {} nodes; nothing to benchmark", stats.node_count);
+    }
+    let ids: Vec<NodeId> = (0..stats.node_count as NodeId).collect();
+    let pubkeys: Vec<Pubkey> = ids.iter().filter_map(|&id| graph.pubkey(id)).collect();
+    let query = |rng: &mut Rng| bfs::DistanceQuery {
+        from: pubkeys[rng.below(pubkeys.len())],
+        to: pubkeys[rng.below(pubkeys.len())],
+        max_hops: options.max_hops,
+        include_bridges: false,
+        min_followers: 0,
+    };
+    let seed = |t: usize| Rng::new(options.seed ^ ((t as u64 + 1) << 32));
+
+    // Single queries, every thread reading
+    let bfs_samples = run_for(options.threads, options.duration, |t, stop| {
+        let mut rng = seed(t);
+        let mut samples = Vec::new();
+        while !stop.load(Ordering::Relaxed) {
+            let query = query(&mut rng);
+            let start = Instant::now();
+            bfs::compute_distance(&graph, &query);
+            samples.push(start.elapsed());
+        }
+        samples
+    });
+    let bfs_qps = bfs_samples.len() as f64 / options.duration.as_secs_f64();
+
+    // Batches from one source, answered one target at a time like POST /distance/batch
+    let batch_samples = run_for(options.threads, options.duration, |t, stop| {
+        let mut rng = seed(t);
+        let mut samples = Vec::new();
+        while !stop.load(Ordering::Relaxed) {
+            let from = pubkeys[rng.below(pubkeys.len())];
+            let targets: Vec<Pubkey> = (0..options.batch).map(|_| pubkeys[rng.below(pubkeys.len())]).collect();
+            let start = Instant::now();
+            for to in targets {
+                let query = bfs::DistanceQuery {
+                    from,
+                    to,
+                    max_hops: options.max_hops,
+                    include_bridges: false,
+                    min_followers: 0,
+                };
+                bfs::compute_distance(&graph, &query);
+            }
+            samples.push(start.elapsed());
+        }
+        samples
+    });
+
+    // One writer replacing contact lists while the other threads query
+    graph.reset_lock_metrics();
+    let readers = options.threads.saturating_sub(1).max(1);
+    let follows = (stats.edge_count / stats.node_count).max(1);
+    let mut update_samples = Vec::new();
+    let writer_stop = AtomicBool::new(false);
+    let contended_samples = std::thread::scope(|scope| {
+        let writer = scope.spawn(|| {
+            let mut rng = seed(usize::MAX - 1);
+            let mut samples = Vec::new();
+            while !writer_stop.load(Ordering::Relaxed) {
+                let author = pubkeys[rng.below(pubkeys.len())].to_hex();
+                let list: Vec<String> = (0..follows)
+                    .map(|_| pubkeys[rng.below(pubkeys.len())].to_hex())
+                    .collect();
+                let start = Instant::now();
+                graph.update_follows(&author, &list, None, None);
+                samples.push(start.elapsed());
+            }
+            samples
+        });
+        let samples = run_for(readers, options.duration, |t, stop| {
+            let mut rng = seed(t);
+            let mut samples = Vec::new();
+            while !stop.load(Ordering::Relaxed) {
+                let query = query(&mut rng);
+                let start = Instant::now();
+                bfs::compute_distance(&graph, &query);
+                samples.push(start.elapsed());
+            }
+            samples
+        });
+        writer_stop.store(true, Ordering::Relaxed);
+        update_samples = writer.join().unwrap_or_default();
+        samples
+    });
+    let locks = graph.lock_metrics();
+
+    Ok(BenchReport {
+        node_count: stats.node_count,
+        edge_count: stats.edge_count,
+        load_time,
+        threads: options.threads,
+        bfs_qps,
+        bfs_latency: Percentiles::of(bfs_samples),
+        batches: batch_samples.len(),
+        batch_latency: Percentiles::of(batch_samples),
+        readers,
+        contended_qps: contended_samples.len() as f64 / options.duration.as_secs_f64(),
+        contended_latency: Percentiles::of(contended_samples),
+        updates_per_sec: update_samples.len() as f64 / options.duration.as_secs_f64(),
+        update_latency: Percentiles::of(update_samples),
+        write_lock_max_us: locks.write_lock_max_us,
+        read_lock_max_us: locks.read_lock_max_us,
+    })
+}
+
+fn format_percentiles(p: &Percentiles) -> String {
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    format!(
+        "p50 {:.3} ms, p90 {:.3} ms, p99 {:.3} ms, max {:.3} ms",
+        ms(p.p50),
+        ms(p.p90),
+        ms(p.p99),
+        ms(p.max)
+    )
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Graph: {} nodes, {} edges (built in {:.2}s)",
+            self.node_count,
+            self.edge_count,
+            self.load_time.as_secs_f64()
+        )?;
+        writeln!(f, "Threads: {}", self.threads)?;
+        writeln!(f)?;
+        writeln!(f, "BFS:       {:.0} queries/s", self.bfs_qps)?;
+        writeln!(f, "           {}", format_percentiles(&self.bfs_latency))?;
+        writeln!(f, "Batch:     {} batches", self.batches)?;
+        writeln!(f, "           {}", format_percentiles(&self.batch_latency))?;
+        // Per query thread, since the writer takes one of them
+        let per_thread = |qps: f64, threads: usize| qps / threads as f64;
+        let change = per_thread(self.contended_qps, self.readers)
+            / per_thread(self.bfs_qps, self.threads).max(f64::MIN_POSITIVE)
+            - 1.0;
+        writeln!(
+            f,
+            "Contended: {:.0} queries/s on {} threads ({:+.1}% per thread vs BFS), {:.0} updates/s",
+            self.contended_qps,
+            self.readers,
+            change * 100.0,
+            self.updates_per_sec
+        )?;
+        writeln!(f, "  queries  {}", format_percentiles(&self.contended_latency))?;
+        writeln!(f, "  updates  {}", format_percentiles(&self.update_latency))?;
+        write!(
+            f,
+            "  locks    write max {} us, read max {} us",
+            self.write_lock_max_us, self.read_lock_max_us
+        )
+    }
+}
+
+/// Entry point for `wot-oracle bench <options>`
+pub fn main(args: impl IntoIterator<Item = String>) -> Result<()> {
+    let options = BenchOptions::parse(args)?;
+    match &options.snapshot {
+        Some(path) => println!("Loading {} ...", path),
+        None => println!(
+            "Generating a {:?} graph of {} nodes with {} follows each (seed {}) ...",
+            options.model, options.nodes, options.follows, options.seed
+        ),
+    }
+    println!(
+        "Running each phase for {}s on {} threads, max_hops {}, batch {}",
+        options.duration.as_secs(),
+        options.threads,
+        options.max_hops,
+        options.batch
+    );
+    let report = run(&options)?;
+    println!("\n{}", report);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        let args = ["--nodes", "500", "--threads", "2", "--duration", "3"].map(String::from);
+        let options = BenchOptions::parse(args).unwrap();
+        assert_eq!((options.nodes, options.threads), (500, 2));
+        assert_eq!(options.duration, Duration::from_secs(3));
+        assert!(BenchOptions::parse(["--nodes".to_string()]).is_err());
+        assert!(BenchOptions::parse(["--bogus", "1"].map(String::from)).is_err());
+        let options = BenchOptions::parse(["--model", "ws", "--rewire", "2"].map(String::from)).unwrap();
+        assert_eq!((options.model, options.rewire), (Model::WattsStrogatz, 1.0));
+    }
+
+    #[test]
+    fn test_run_small() {
+        let options = BenchOptions {
+            model: Model::WattsStrogatz,
+            nodes: 300,
+            follows: 4,
+            threads: 2,
+            duration: Duration::from_millis(50),
+            batch: 10,
+            ..BenchOptions::default()
+        };
+        let report = run(&options).unwrap();
+        assert_eq!(report.node_count, 300);
+        assert!(report.bfs_qps > 0.0 && report.batches > 0 && report.updates_per_sec > 0.0);
+        assert!(report.to_string().contains("queries/s"));
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::Result;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::config::BotWeights;
+use crate::db::Database;
+use crate::graph::{Neighbors, NodeId, WotGraph};
+
+/// Follows sampled per account for the clustering coefficient (pairs grow quadratically)
+const CLUSTERING_SAMPLE: usize = 30;
+/// Width of the first-seen windows compared for creation bursts
+const BURST_WINDOW_SECS: i64 = 600;
+/// A first-seen window this many times the median window's size counts as a full burst
+const BURST_SATURATION: f64 = 10.0;
+/// Clustering at or above this looks fully human
+const CLUSTERING_HUMAN: f64 = 0.2;
+/// Reports at which the report feature saturates
+const REPORTS_SATURATION: f64 = 5.0;
+/// Follow-list size below which churn is measured against this many follows instead
+const CHURN_MIN_LIST: f64 = 10.0;
+
+/// Raw bot-likelihood signals for an account
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct BotFeatures {
+    /// Share of follows that follow back
+    pub follow_back_ratio: f32,
+    /// Follow entries added or removed per day since the oracle started (at least a day)
+    pub churn_per_day: f32,
+    /// Accounts first seen in the same 10-minute window as this one; 0 if its first-seen time
+    /// isn't recorded
+    pub burst_size: u32,
+    /// Share of ordered pairs among sampled follows where one follows the other; null with
+    /// fewer than two follows
+    pub clustering: Option<f32>,
+    pub report_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BotScore {
+    /// 0-100, higher is more bot-like
+    pub score: u8,
+    pub features: BotFeatures,
+}
+
+/// Bot-likelihood scores for every account with a follow list, recomputed in the background
+/// every `BOTSCORE_INTERVAL_SECS`. Each feature is mapped to 0-1 (1 = bot-like) and the score
+/// is their weighted mean (`BOTSCORE_WEIGHTS`), scaled to 0-100.
+pub struct BotScores {
+    scores: RwLock<Arc<HashMap<NodeId, BotScore>>>,
+    // Unix time of the last computation (0 = never)
+    computed_at: AtomicI64,
+    // Churn is counted from here, so it is averaged over the process's lifetime
+    started_at: i64,
+}
+
+impl BotScores {
+    pub fn new(started_at: i64) -> Self {
+        Self { scores: RwLock::new(Arc::default()), computed_at: AtomicI64::new(0), started_at }
+    }
+
+    /// A node's score, or None if it had no follow list at the last computation
+    pub fn get(&self, node_id: NodeId) -> Option<BotScore> {
+        self.scores.read().get(&node_id).copied()
+    }
+
+    /// When scores were last computed, or None before the first run
+    pub fn computed_at(&self) -> Option<i64> {
+        Some(self.computed_at.load(Ordering::Acquire)).filter(|&t| t > 0)
+    }
+
+    /// Recompute every score; returns how many accounts were scored.
+    /// CPU-bound and reads the database: call from a blocking thread.
+    pub fn refresh(&self, graph: &WotGraph, db: &Database, weights: BotWeights, now: i64) -> Result<usize> {
+        let start = Instant::now();
+        let reports: HashMap<NodeId, u32> = db
+            .report_counts()?
+            .into_iter()
+            .filter_map(|(pubkey, count)| Some((graph.node_id(&pubkey.parse().ok()?)?, count as u32)))
+            .collect();
+
+        // Size of each account's first-seen window, against the median window
+        let first_seen: Vec<(NodeId, i64)> = db
+            .author_first_seen()?
+            .into_iter()
+            .filter_map(|(pubkey, first_seen)| {
+                Some((graph.node_id(&pubkey.parse().ok()?)?, first_seen.div_euclid(BURST_WINDOW_SECS)))
+            })
+            .collect();
+        let mut windows: HashMap<i64, u32> = HashMap::new();
+        for &(_, window) in &first_seen {
+            *windows.entry(window).or_default() += 1;
+        }
+        let mut sizes: Vec<u32> = windows.values().copied().collect();
+        sizes.sort_unstable();
+        let median = sizes.get(sizes.len() / 2).copied().unwrap_or(0);
+        let bursts: HashMap<NodeId, u32> = first_seen.iter().map(|&(id, window)| (id, windows[&window])).collect();
+
+        let days = ((now - self.started_at) as f64 / 86_400.0).max(1.0);
+        let scores: HashMap<NodeId, BotScore> = graph.with_adjacency(|follows, _| {
+            follows
+                .iter()
+                .enumerate()
+                .filter(|(_, list)| !list.is_empty())
+                .map(|(id, list)| {
+                    let id = id as NodeId;
+                    let features = BotFeatures {
+                        follow_back_ratio: (graph.mutual_count(id) as f64 / list.len() as f64) as f32,
+                        churn_per_day: (graph.churn(id) as f64 / days) as f32,
+                        burst_size: bursts.get(&id).copied().unwrap_or(0),
+                        clustering: clustering(follows, list).map(|c| c as f32),
+                        report_count: reports.get(&id).copied().unwrap_or(0),
+                    };
+                    let score = score(&features, list.len(), median, weights);
+                    (id, BotScore { score, features })
+                })
+                .collect()
+        });
+
+        let count = scores.len();
+        info!("Bot scores computed for {} accounts in {:?}", count, start.elapsed());
+        *self.scores.write() = Arc::new(scores);
+        self.computed_at.store(now, Ordering::Release);
+        Ok(count)
+    }
+}
+
+impl Default for BotScores {
+    fn default() -> Self {
+        Self::new(chrono::Utc::now().timestamp())
+    }
+}
+
+/// Weighted mean of the features mapped to 0-1, scaled to 0-100
+fn score(features: &BotFeatures, follow_count: usize, median_burst: u32, weights: BotWeights) -> u8 {
+    let follow_back = 1.0 - features.follow_back_ratio as f64;
+    let churn = (features.churn_per_day as f64 / (follow_count as f64).max(CHURN_MIN_LIST)).min(1.0);
+    let burst = match (features.burst_size, median_burst) {
+        (0, _) | (_, 0) => 0.0,
+        (size, median) => ((size as f64 / median as f64 - 1.0) / (BURST_SATURATION - 1.0)).clamp(0.0, 1.0),
+    };
+    let clustering = features.clustering.map_or(0.0, |c| (1.0 - c as f64 / CLUSTERING_HUMAN).clamp(0.0, 1.0));
+    let reports = (features.report_count as f64 / REPORTS_SATURATION).min(1.0);
+
+    let weighted = [
+        (follow_back, weights.follow_back),
+        (churn, weights.churn),
+        (burst, weights.burst),
+        (clustering, weights.clustering),
+        (reports, weights.reports),
+    ];
+    let total: f64 = weighted.iter().map(|(_, w)| w).sum();
+    let sum: f64 = weighted.iter().map(|(value, w)| value * w).sum();
+    (100.0 * sum / total).round() as u8
+}
+
+/// Up to `n` entries spread evenly over a sorted list
+fn sample(list: &[NodeId], n: usize) -> impl Iterator<Item = NodeId> + '_ {
+    list.iter().step_by((list.len() / n).max(1)).take(n).copied()
+}
+
+fn clustering(follows: &Neighbors, list: &[NodeId]) -> Option<f64> {
+    let sampled: Vec<NodeId> = sample(list, CLUSTERING_SAMPLE).collect();
+    let k = sampled.len();
+    if k < 2 {
+        return None;
+    }
+    let links = sampled
+        .iter()
+        .flat_map(|&u| sampled.iter().filter(move |&&v| v != u).map(move |&v| (u, v)))
+        .filter(|&(u, v)| follows.get(u as usize).is_some_and(|theirs| theirs.binary_search(&v).is_ok()))
+        .count();
+    Some(links as f64 / (k * (k - 1)) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    #[test]
+    fn test_refresh() {
+        let graph = WotGraph::new();
+        let db = Database::open(":memory:").unwrap();
+        let follows = |names: &[&str]| names.iter().map(|name| hex(name)).collect::<Vec<_>>();
+        // A close-knit trio, and a bot following all of them without being followed back
+        for (name, list) in [("alice", ["bob", "carol"]), ("bob", ["alice", "carol"]), ("carol", ["alice", "bob"])] {
+            graph.update_follows(&hex(name), &follows(&list), None, Some(1));
+        }
+        graph.update_follows(&hex("bot"), &follows(&["alice", "bob", "carol"]), None, Some(1));
+        graph.update_follows(&hex("bot"), &follows(&["alice", "bob", "dave"]), None, Some(2));
+        db.record_report(&hex("alice"), "e1", 1, &[(hex("bot"), None)]).unwrap();
+
+        let scores = BotScores::new(0);
+        assert!(scores.computed_at().is_none());
+        assert_eq!(scores.refresh(&graph, &db, BotWeights::default(), 86_400).unwrap(), 4);
+        assert_eq!(scores.computed_at(), Some(86_400));
+
+        let alice = scores.get(graph.node_id(&pk("alice")).unwrap()).unwrap();
+        assert_eq!(alice.features.follow_back_ratio, 1.0);
+        assert_eq!(alice.features.clustering, Some(1.0));
+        assert_eq!(alice.score, 0);
+
+        let bot = scores.get(graph.node_id(&pk("bot")).unwrap()).unwrap();
+        assert_eq!(
+            bot.features,
+            BotFeatures { follow_back_ratio: 0.0, churn_per_day: 2.0, burst_size: 0, clustering: Some(1.0 / 3.0), report_count: 1 }
+        );
+        // follow-back 1, churn 0.2, burst 0, clustering 0, reports 0.2
+        assert_eq!(bot.score, 28);
+
+        // Weights pick the features
+        scores.refresh(&graph, &db, BotWeights { follow_back: 1.0, churn: 0.0, burst: 0.0, clustering: 0.0, reports: 0.0 }, 86_400).unwrap();
+        assert_eq!(scores.get(graph.node_id(&pk("bot")).unwrap()).unwrap().score, 100);
+        assert!(scores.get(graph.node_id(&pk("dave")).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_burst() {
+        let features = BotFeatures { follow_back_ratio: 1.0, churn_per_day: 0.0, burst_size: 55, clustering: None, report_count: 0 };
+        let only_burst = BotWeights { follow_back: 0.0, churn: 0.0, burst: 1.0, clustering: 0.0, reports: 0.0 };
+        assert_eq!(score(&features, 100, 10, only_burst), 50);
+        assert_eq!(score(&features, 100, 100, only_burst), 0);
+        assert_eq!(score(&BotFeatures { burst_size: 500, ..features }, 100, 10, only_burst), 100);
+    }
+}
@@ -1,27 +1,266 @@
+use ipnet::IpNet;
+use std::collections::HashMap;
 use std::env;
+use std::net::IpAddr;
+use tracing::warn;
+
+use crate::graph::bfs::HopPolicy;
+use crate::scheduler::Schedule;
 
 // Security limits
 pub const MAX_HOPS_LIMIT: u8 = 5;
 pub const MAX_HOPS_DEFAULT: u8 = 3;
+pub const ADAPTIVE_HOPS_DEGREE_DEFAULT: usize = 100_000;
+pub const ADAPTIVE_MAX_HOPS_DEFAULT: u8 = 2;
 pub const CACHE_SIZE_MAX: usize = 100_000;
 pub const CACHE_SIZE_DEFAULT: usize = 10_000;
+pub const CACHE_MAX_MB_MAX: u64 = 16_384;
 pub const RATE_LIMIT_MAX: u32 = 1000;
 pub const RATE_LIMIT_DEFAULT: u32 = 100;
+pub const ADMIN_RATE_LIMIT_DEFAULT: u32 = 30;
+pub const EXPLORE_RATE_LIMIT_DEFAULT: u32 = 30;
 #[allow(dead_code)] // Reserved for future timeout configuration
 pub const REQUEST_TIMEOUT_SECS: u64 = 30;
 pub const REQUEST_BODY_LIMIT: usize = 1024 * 1024; // 1MB
+pub const MULTI_SOURCE_MAX_SOURCES: usize = 1000; // Room for a full follow list
+pub const VERIFY_BATCH_MAX_PAIRS: usize = 5000; // ~750KB of hex pairs, within the body limit
+pub const SET_DISTANCE_MAX_SIZE: usize = 5000; // Per set, ~700KB for both within the body limit
+pub const SET_DISTANCE_MAX_SEARCHES: usize = 100; // One BFS per member of the smaller set
+pub const BATCH_MAX_TARGETS_DEFAULT: usize = 100;
+pub const BATCH_MAX_TARGETS_MAX: usize = 10_000;
+pub const HOT_PAIR_REFRESH_DEFAULT_SECS: u64 = 30;
+pub const POPULARITY_HALF_LIFE_DEFAULT_SECS: u64 = 3600;
+pub const CACHE_WARM_COUNT_MAX: usize = 1000;
+pub const ANCHOR_AUTO_COUNT_MAX: usize = 20;
+pub const PAGERANK_INTERVAL_DEFAULT_SECS: u64 = 900;
+pub const PAGERANK_UPDATE_INTERVAL_DEFAULT_SECS: u64 = 60;
+pub const ANCHOR_INTERVAL_DEFAULT_SECS: u64 = 300;
+pub const PUBLISH_TOP_N_DEFAULT: usize = 100;
+pub const PUBLISH_TOP_N_MAX: usize = 1000;
+pub const DVM_INVOICE_EXPIRY_DEFAULT_SECS: u64 = 600;
+pub const DVM_MAX_REQUEST_AGE_DEFAULT_SECS: u64 = 300;
+pub const DVM_WORKERS_DEFAULT: usize = 4;
+pub const DVM_QUEUE_SIZE_DEFAULT: usize = 100;
+pub const FOLLOWER_HISTORY_INTERVAL_DEFAULT_SECS: u64 = 3600;
+pub const FOLLOWER_HISTORY_MIN_FOLLOWERS_DEFAULT: usize = 1000;
+pub const FOLLOWER_HISTORY_RETENTION_DEFAULT_DAYS: u64 = 365;
+pub const BOTSCORE_INTERVAL_DEFAULT_SECS: u64 = 3600;
+pub const EDGE_HISTORY_RETENTION_DEFAULT_DAYS: u64 = 90;
+pub const OUTBOX_RETENTION_DEFAULT_DAYS: u64 = 7;
+pub const ANOMALY_CHANGE_RATIO_DEFAULT: f64 = 0.5;
+pub const ANOMALY_MIN_CHANGES_DEFAULT: usize = 100;
+pub const ANOMALY_MAX_CHANGES_DEFAULT: usize = 2000;
+pub const SEEN_CACHE_CAPACITY_DEFAULT: usize = 100_000;
+pub const SEEN_CACHE_PERSIST_DEFAULT_SECS: u64 = 300;
+pub const INGESTION_DEBOUNCE_DEFAULT_MS: u64 = 1000;
+pub const PERSIST_BATCH_SIZE_DEFAULT: usize = 100;
+pub const PERSIST_FLUSH_INTERVAL_DEFAULT_MS: u64 = 5000;
+pub const PERSIST_QUEUE_SIZE_DEFAULT: usize = 10_000;
+pub const MAX_FOLLOWS_PER_LIST_DEFAULT: usize = 50_000;
+pub const MAX_TAGS_PER_LIST_DEFAULT: usize = 100_000;
+pub const QUARANTINE_MIN_FOLLOWS_DEFAULT: usize = 10_000;
+pub const ALERT_COOLDOWN_DEFAULT_SECS: u64 = 3600;
+pub const ALERT_RELAY_DOWN_DEFAULT_MINS: u64 = 10;
+pub const ALERT_INGESTION_LAG_DEFAULT_SECS: u64 = 1800;
+/// Background jobs whose schedule `SCHEDULE_<JOB>` overrides
+pub const SCHEDULED_JOBS: [&str; 9] = [
+    "pagerank",
+    "pagerank_updates",
+    "components",
+    "anchors",
+    "hot_pairs",
+    "cache_warming",
+    "botscores",
+    "pruning",
+    "graph_snapshot",
+];
+pub const GRAPH_NAME_DEFAULT: &str = "default";
+pub const REPLICATION_INTERVAL_DEFAULT_SECS: u64 = 10;
+pub const GOSSIP_RELAYS_PER_AUTHOR_DEFAULT: usize = 2;
+pub const GOSSIP_MAX_RELAYS_DEFAULT: usize = 50;
+/// Kinds ingestion can subscribe to per relay: contact lists, mute lists, relay lists, reports
+pub const RELAY_KINDS: [u16; 4] = [3, 10000, 10002, 1984];
+
+/// An additional named graph with its own relays and database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphConfig {
+    pub name: String,
+    pub relays: Vec<String>,
+    pub db_path: String,
+}
+
+/// How ingestion uses a relay
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RelayMode {
+    /// Firehose subscription, plus watched authors' subscriptions
+    #[default]
+    Read,
+    /// Watched authors' subscriptions only, no firehose
+    Discovery,
+}
+
+/// Per-relay ingestion settings (`RELAY_SETTINGS`); relays without an entry use the defaults
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelaySettings {
+    /// Kinds of the firehose subscription, from [`RELAY_KINDS`] (default: contact lists only)
+    pub kinds: Vec<u16>,
+    /// Firehose events are kept only from authors whose hex pubkey starts with one of these
+    /// (empty: every author)
+    pub author_prefixes: Vec<String>,
+    /// Most firehose events accepted from the relay per second (0: unlimited)
+    pub max_events_per_sec: u32,
+    pub mode: RelayMode,
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        Self { kinds: vec![3], author_prefixes: Vec::new(), max_events_per_sec: 0, mode: RelayMode::Read }
+    }
+}
+
+/// Weight of each bot-likelihood feature in `/botscore` (`BOTSCORE_WEIGHTS`); 0 leaves one out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BotWeights {
+    pub follow_back: f64,
+    pub churn: f64,
+    pub burst: f64,
+    pub clustering: f64,
+    pub reports: f64,
+}
+
+impl Default for BotWeights {
+    fn default() -> Self {
+        Self { follow_back: 1.0, churn: 1.0, burst: 1.0, clustering: 1.0, reports: 1.0 }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub relays: Vec<String>,
     pub http_port: u16,
+    pub grpc_port: Option<u16>,
     pub db_path: String,
+    /// Name of the graph built from `relays` and `db_path`, selectable as `graph=<name>`
+    pub graph_name: String,
+    pub graphs: Vec<GraphConfig>,
     pub dvm_enabled: bool,
     pub dvm_private_key: Option<String>,
+    pub dvm_price_msats: u64,
+    pub dvm_free_pubkeys: Vec<String>,
+    pub dvm_invoice_expiry_secs: u64,
+    pub dvm_max_request_age_secs: u64,
+    pub dvm_workers: usize,
+    pub dvm_queue_size: usize,
+    pub lnbits_url: Option<String>,
+    pub lnbits_api_key: Option<String>,
+    pub relay_enabled: bool,
+    pub sign_responses: bool,
+    pub nip05_enabled: bool,
     pub rate_limit_per_minute: u32,
+    pub admin_rate_limit_per_minute: u32,
+    /// Budget of the HTML explorer (`/explore`), separate from the API's
+    pub explore_rate_limit_per_minute: u32,
+    /// Directory holding swagger-ui-dist's `swagger-ui.css` and `swagger-ui-bundle.js`, served
+    /// for the Swagger UI at `/docs`; None serves a page linking the spec instead
+    pub swagger_ui_dir: Option<String>,
+    pub trusted_proxies: Vec<IpNet>,
+    pub ip_allowlist: Vec<IpNet>,
+    pub ip_denylist: Vec<IpNet>,
+    pub pubkey_allowlist: Vec<String>,
+    pub pubkey_denylist: Vec<String>,
+    /// Only `source_allowlist` pubkeys, or requests with one of `api_keys`, may be a query's `from`
+    pub restrict_sources: bool,
+    pub source_allowlist: Vec<String>,
+    pub api_keys: Vec<String>,
+    pub blocked_pubkeys: Vec<String>,
+    pub admin_token: Option<String>,
+    pub replication_token: Option<String>,
+    pub replicate_from: Option<String>,
+    pub replication_interval_secs: u64,
     pub max_hops: u8,
+    /// Largest max_hops accepted by the HTTP, GraphQL and gRPC APIs
+    pub http_max_hops: u8,
+    /// Largest max_hops a DVM job may request; larger values are clamped
+    pub dvm_max_hops: u8,
+    /// Most targets per `/distance/batch` request
+    pub batch_max_targets: usize,
+    /// Follows or followers at which an endpoint lowers max_hops to `adaptive_max_hops` (0 disables)
+    pub adaptive_hops_degree: usize,
+    pub adaptive_max_hops: u8,
     pub cache_size: usize,
+    /// Query cache memory budget in MiB, replacing the `cache_size` entry count (0 disables)
+    pub cache_max_mb: u64,
     pub cache_ttl_secs: u64,
+    /// Cache hits after which a pair is recomputed as the graph changes (0 disables)
+    pub hot_pair_min_hits: u32,
+    pub hot_pair_refresh_secs: u64,
+    /// Half-life of the query popularity counts behind `/stats/popular`
+    pub popularity_half_life_secs: u64,
+    /// Most popular pairs recomputed whenever they drop out of the cache (0 disables)
+    pub cache_warm_count: usize,
+    pub pagerank_interval_secs: u64,
+    /// Incremental PageRank updates between full computations; None disables
+    pub pagerank_update_interval_secs: Option<u64>,
+    pub publish_interval_secs: Option<u64>,
+    pub publish_top_n: usize,
+    pub publish_anchors: Vec<String>,
+    /// Pubkeys whose distances to every node are precomputed, answering their `/distance`
+    /// queries by lookup
+    pub anchor_pubkeys: Vec<String>,
+    pub anchor_interval_secs: u64,
+    /// Most popular query sources given distance tables on top of `anchor_pubkeys` (0 disables)
+    pub anchor_auto_count: usize,
+    /// Interval for the Merkle commitment over follow lists (`/proof/follows`); None disables
+    pub commitment_interval_secs: Option<u64>,
+    pub follower_history_interval_secs: Option<u64>,
+    pub follower_history_min_followers: usize,
+    pub follower_history_pubkeys: Vec<String>,
+    pub follower_history_retention_days: u64,
+    /// Days of follow additions and removals to keep (`/diff`)
+    pub edge_history_retention_days: u64,
+    /// Days of applied contact list changes kept for `/outbox`
+    pub outbox_retention_days: u64,
+    /// Bot-likelihood score refresh interval (`/botscore`); None disables
+    pub botscore_interval_secs: Option<u64>,
+    pub botscore_weights: BotWeights,
+    /// How often the graph is dumped to a binary snapshot loaded at startup; None disables
+    pub graph_snapshot_interval_secs: Option<u64>,
+    /// Background job schedules set with `SCHEDULE_<JOB>`, replacing the job's interval setting
+    pub job_schedules: HashMap<String, Schedule>,
+    pub anomaly_change_ratio: f64,
+    pub anomaly_min_changes: usize,
+    pub anomaly_max_changes: usize,
+    pub anomaly_hold_secs: u64,
+    pub watchlist_pubkeys: Vec<String>,
+    /// Settings for relays that differ from the defaults, by URL without a trailing slash
+    pub relay_settings: HashMap<String, RelaySettings>,
+    /// Follow watched and anchor pubkeys on their own NIP-65 write relays
+    pub gossip_enabled: bool,
+    pub gossip_relays_per_author: usize,
+    pub gossip_max_relays: usize,
+    pub ingestion_pools: usize,
+    pub seen_cache_capacity: usize,
+    pub seen_cache_persist_secs: u64,
+    pub ingestion_debounce_ms: u64,
+    /// Contact lists written per database transaction
+    pub persist_batch_size: usize,
+    /// Longest a partial batch waits before it is written
+    pub persist_flush_interval_ms: u64,
+    /// Applied updates queued for writing before further ones are dropped
+    pub persist_queue_size: usize,
+    pub max_follows_per_list: usize,
+    /// Contact list events with more tags are rejected unparsed (0 = no limit)
+    pub max_tags_per_list: usize,
+    /// Keep the first `max_tags_per_list` tags of longer events instead of rejecting them
+    pub truncate_oversized_lists: bool,
+    pub quarantine_min_follows: usize,
+    /// Neighbors BFS walks from any one node before sampling them (0 = all)
+    pub bfs_expansion_limit: usize,
+    pub alert_webhook_url: Option<String>,
+    pub alert_nostr_pubkey: Option<String>,
+    pub alert_cooldown_secs: u64,
+    pub alert_relay_down_mins: u64,
+    pub alert_ingestion_lag_secs: u64,
 }
 
 impl Config {
@@ -38,14 +277,91 @@ impl Config {
             .and_then(|p| p.parse().ok())
             .unwrap_or(8080);
 
+        // gRPC server is disabled unless a port is configured
+        let grpc_port = env::var("GRPC_PORT").ok().and_then(|p| p.parse().ok());
+
         let db_path = env::var("DB_PATH").unwrap_or_else(|_| "wot.db".into());
 
+        // Named graphs: the primary one above, plus each name in GRAPHS with its own
+        // GRAPH_<NAME>_RELAYS (required) and GRAPH_<NAME>_DB_PATH (default: DB_PATH suffixed with -<name>)
+        let graph_name = env::var("GRAPH_NAME")
+            .ok()
+            .filter(|name| is_graph_name(name))
+            .unwrap_or_else(|| GRAPH_NAME_DEFAULT.into());
+        let mut graphs: Vec<GraphConfig> = Vec::new();
+        for name in env_list("GRAPHS") {
+            if !is_graph_name(&name) || name == graph_name || graphs.iter().any(|g| g.name == name) {
+                warn!("Ignoring invalid or duplicate GRAPHS entry: {}", name);
+                continue;
+            }
+            let prefix = format!("GRAPH_{}", name.to_ascii_uppercase().replace('-', "_"));
+            let relays = env_list(&format!("{}_RELAYS", prefix));
+            if relays.is_empty() {
+                warn!("Ignoring graph {}: {}_RELAYS is not set", name, prefix);
+                continue;
+            }
+            let graph_db_path = env::var(format!("{}_DB_PATH", prefix))
+                .unwrap_or_else(|_| suffixed_path(&db_path, &name));
+            graphs.push(GraphConfig { name, relays, db_path: graph_db_path });
+        }
+
         let dvm_enabled = env::var("DVM_ENABLED")
             .map(|v| v == "true" || v == "1")
             .unwrap_or(false);
 
         let dvm_private_key = env::var("DVM_PRIVATE_KEY").ok();
 
+        // DVM jobs are free unless a price is set; paid jobs need a Lightning backend
+        let dvm_price_msats = env::var("DVM_PRICE_MSATS")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(0);
+        let dvm_free_pubkeys = env_list("DVM_FREE_PUBKEYS");
+
+        // Bounded invoice lifetime (60-3600 seconds)
+        let dvm_invoice_expiry_secs = env::var("DVM_INVOICE_EXPIRY_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(60, 3600))
+            .unwrap_or(DVM_INVOICE_EXPIRY_DEFAULT_SECS);
+
+        // Requests older than this are ignored, so relays replaying history don't trigger work (10-86400 seconds)
+        let dvm_max_request_age_secs = env::var("DVM_MAX_REQUEST_AGE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(10, 86400))
+            .unwrap_or(DVM_MAX_REQUEST_AGE_DEFAULT_SECS);
+
+        // Bounded DVM job parallelism (1-64) and backlog (1-10000); requests beyond the backlog are rejected
+        let dvm_workers = env::var("DVM_WORKERS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|w: usize| w.clamp(1, 64))
+            .unwrap_or(DVM_WORKERS_DEFAULT);
+        let dvm_queue_size = env::var("DVM_QUEUE_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|q: usize| q.clamp(1, 10_000))
+            .unwrap_or(DVM_QUEUE_SIZE_DEFAULT);
+
+        let lnbits_url = env::var("LNBITS_URL").ok().filter(|u| !u.is_empty());
+        let lnbits_api_key = env::var("LNBITS_API_KEY").ok().filter(|k| !k.is_empty());
+
+        // Read-only NIP-01 relay facade (signs results with DVM_PRIVATE_KEY)
+        let relay_enabled = env::var("RELAY_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
+        // Attach oracle signatures to HTTP distance results (requires DVM_PRIVATE_KEY)
+        let sign_responses = env::var("SIGN_RESPONSES")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
+        // Resolve NIP-05 identifiers given in place of pubkeys (outbound HTTPS lookups)
+        let nip05_enabled = env::var("NIP05_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(true);
+
         // Bounded rate limit (1-1000 req/min)
         let rate_limit_per_minute = env::var("RATE_LIMIT_PER_MINUTE")
             .ok()
@@ -53,12 +369,102 @@ impl Config {
             .map(|r: u32| r.clamp(1, RATE_LIMIT_MAX))
             .unwrap_or(RATE_LIMIT_DEFAULT);
 
-        // Bounded max_hops (1-5)
+        // Separate bucket for admin routes (1-1000 req/min)
+        let admin_rate_limit_per_minute = env::var("ADMIN_RATE_LIMIT_PER_MINUTE")
+            .ok()
+            .and_then(|r| r.parse().ok())
+            .map(|r: u32| r.clamp(1, RATE_LIMIT_MAX))
+            .unwrap_or(ADMIN_RATE_LIMIT_DEFAULT);
+
+        // And for the HTML explorer (1-1000 req/min)
+        let explore_rate_limit_per_minute = env::var("EXPLORE_RATE_LIMIT_PER_MINUTE")
+            .ok()
+            .and_then(|r| r.parse().ok())
+            .map(|r: u32| r.clamp(1, RATE_LIMIT_MAX))
+            .unwrap_or(EXPLORE_RATE_LIMIT_DEFAULT);
+        let swagger_ui_dir = env::var("SWAGGER_UI_DIR").ok().filter(|dir| !dir.is_empty());
+
+        // Proxies allowed to set X-Forwarded-For / X-Real-IP (CIDRs or bare IPs)
+        let trusted_proxies = env_ip_nets("TRUSTED_PROXIES");
+
+        // Access lists (also editable at runtime via the admin API)
+        let ip_allowlist = env_ip_nets("IP_ALLOWLIST");
+        let ip_denylist = env_ip_nets("IP_DENYLIST");
+        let pubkey_allowlist = env_list("PUBKEY_ALLOWLIST");
+        let pubkey_denylist = env_list("PUBKEY_DENYLIST");
+
+        // Private serving: restrict query sources to registered pubkeys and API key holders
+        let restrict_sources = env::var("RESTRICT_SOURCES")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        let source_allowlist = env_list("SOURCE_ALLOWLIST");
+        let api_keys = env_list("API_KEYS");
+        if restrict_sources && source_allowlist.is_empty() && api_keys.is_empty() {
+            warn!("RESTRICT_SOURCES set without SOURCE_ALLOWLIST or API_KEYS; queries are refused until sources are added under /admin/access/source-allow");
+        }
+
+        // Pubkeys kept out of the graph entirely; added to the persisted blocklist at startup
+        let blocked_pubkeys = env_list("BLOCKED_PUBKEYS");
+
+        // Admin API is disabled unless a token is configured
+        let admin_token = env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+
+        // Replication: serve /replication to replicas holding this token, and/or pull from a
+        // primary instead of ingesting from relays (poll interval 1-3600 seconds)
+        let replication_token = env::var("REPLICATION_TOKEN").ok().filter(|t| !t.is_empty());
+        let replicate_from = env::var("REPLICATE_FROM")
+            .ok()
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+        let replication_interval_secs = env::var("REPLICATION_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(1, 3600))
+            .unwrap_or(REPLICATION_INTERVAL_DEFAULT_SECS);
+
+        // Bounded max_hops limits (1-5), separately for the query APIs and for DVM jobs
+        let http_max_hops = env::var("HTTP_MAX_HOPS")
+            .ok()
+            .and_then(|h| h.parse().ok())
+            .map(|h: u8| h.clamp(1, MAX_HOPS_LIMIT))
+            .unwrap_or(MAX_HOPS_LIMIT);
+        if http_max_hops < MAX_HOPS_DEFAULT {
+            warn!(
+                "HTTP_MAX_HOPS={} is below the default max_hops of {}; queries must pass max_hops",
+                http_max_hops, MAX_HOPS_DEFAULT
+            );
+        }
+        let dvm_max_hops = env::var("DVM_MAX_HOPS")
+            .ok()
+            .and_then(|h| h.parse().ok())
+            .map(|h: u8| h.clamp(1, MAX_HOPS_LIMIT))
+            .unwrap_or(MAX_HOPS_LIMIT);
+
+        // Bounded batch size (1-10,000 targets); batches over 100 are streamed in chunks
+        let batch_max_targets = env::var("BATCH_MAX_TARGETS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: usize| s.clamp(1, BATCH_MAX_TARGETS_MAX))
+            .unwrap_or(BATCH_MAX_TARGETS_DEFAULT);
+
+        // Default max_hops for DVM jobs, within DVM_MAX_HOPS
         let max_hops = env::var("MAX_HOPS")
             .ok()
             .and_then(|h| h.parse().ok())
             .map(|h: u8| h.clamp(1, MAX_HOPS_LIMIT))
-            .unwrap_or(MAX_HOPS_DEFAULT);
+            .unwrap_or(MAX_HOPS_DEFAULT)
+            .min(dvm_max_hops);
+
+        // Searches touching a super-node are capped at ADAPTIVE_MAX_HOPS (1-5); 0 disables
+        let adaptive_hops_degree = env::var("ADAPTIVE_HOPS_DEGREE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(ADAPTIVE_HOPS_DEGREE_DEFAULT);
+        let adaptive_max_hops = env::var("ADAPTIVE_MAX_HOPS")
+            .ok()
+            .and_then(|h| h.parse().ok())
+            .map(|h: u8| h.clamp(1, MAX_HOPS_LIMIT))
+            .unwrap_or(ADAPTIVE_MAX_HOPS_DEFAULT);
 
         // Bounded cache size (100-100,000)
         let cache_size = env::var("CACHE_SIZE")
@@ -67,6 +473,14 @@ impl Config {
             .map(|s: usize| s.clamp(100, CACHE_SIZE_MAX))
             .unwrap_or(CACHE_SIZE_DEFAULT);
 
+        // Cache memory budget in MiB (0-16384); when set, entries are weighed by size and
+        // CACHE_SIZE no longer applies
+        let cache_max_mb = env::var("CACHE_MAX_MB")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|mb: u64| mb.min(CACHE_MAX_MB_MAX))
+            .unwrap_or(0);
+
         // Bounded cache TTL (10-3600 seconds)
         let cache_ttl_secs = env::var("CACHE_TTL_SECS")
             .ok()
@@ -74,16 +488,585 @@ impl Config {
             .map(|s: u64| s.clamp(10, 3600))
             .unwrap_or(300);
 
+        // Hot pairs are kept fresh only when a hit threshold is set; refreshed every 5-3600 seconds
+        let hot_pair_min_hits = env::var("HOT_PAIR_MIN_HITS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let hot_pair_refresh_secs = env::var("HOT_PAIR_REFRESH_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(5, 3600))
+            .unwrap_or(HOT_PAIR_REFRESH_DEFAULT_SECS);
+
+        // Query popularity decays with a 60-604800 second half-life; the most popular pairs
+        // can be kept cached (up to 1000)
+        let popularity_half_life_secs = env::var("POPULARITY_HALF_LIFE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(60, 604_800))
+            .unwrap_or(POPULARITY_HALF_LIFE_DEFAULT_SECS);
+        let cache_warm_count = env::var("CACHE_WARM_COUNT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.min(CACHE_WARM_COUNT_MAX))
+            .unwrap_or(0);
+
+        // Bounded PageRank refresh interval (60-86400 seconds)
+        let pagerank_interval_secs = env::var("PAGERANK_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(60, 86_400))
+            .unwrap_or(PAGERANK_INTERVAL_DEFAULT_SECS);
+        // Incremental updates in between (10-3600 seconds; 0 disables)
+        let pagerank_update_interval_secs = match env::var("PAGERANK_UPDATE_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(0) => None,
+            Some(s) => Some(u64::clamp(s, 10, 3600)),
+            None => Some(PAGERANK_UPDATE_INTERVAL_DEFAULT_SECS),
+        };
+
+        // Ranking publication is disabled unless an interval is configured (300-86400 seconds)
+        let publish_interval_secs = env::var("PUBLISH_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(300, 86_400));
+
+        // Bounded entries per ranking event (1-1000)
+        let publish_top_n = env::var("PUBLISH_TOP_N")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: usize| s.clamp(1, PUBLISH_TOP_N_MAX))
+            .unwrap_or(PUBLISH_TOP_N_DEFAULT);
+
+        // Pubkeys to publish personalized trust scores for, alongside the global ranking
+        let publish_anchors = env_list("PUBLISH_ANCHORS");
+
+        // Trust anchors with precomputed distance tables, refreshed every 30-86400 seconds
+        let anchor_pubkeys: Vec<String> = env_list("ANCHOR_PUBKEYS")
+            .into_iter()
+            .map(|pk| pk.to_ascii_lowercase())
+            .collect();
+        let anchor_interval_secs = env::var("ANCHOR_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(30, 86_400))
+            .unwrap_or(ANCHOR_INTERVAL_DEFAULT_SECS);
+        // The most popular query sources become anchors too (up to 20)
+        let anchor_auto_count = env::var("ANCHOR_AUTO_COUNT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.min(ANCHOR_AUTO_COUNT_MAX))
+            .unwrap_or(0);
+
+        // Follow-list commitments are disabled unless an interval is configured (300-86400 seconds)
+        let commitment_interval_secs = env::var("COMMITMENT_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(300, 86_400));
+
+        // Follower-count snapshots (300-86400 seconds; 0 disables)
+        let follower_history_interval_secs = match env::var("FOLLOWER_HISTORY_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(0) => None,
+            Some(s) => Some(u64::clamp(s, 300, 86_400)),
+            None => Some(FOLLOWER_HISTORY_INTERVAL_DEFAULT_SECS),
+        };
+
+        // Pubkeys with at least this many followers are snapshotted, plus the listed ones
+        let follower_history_min_followers = env::var("FOLLOWER_HISTORY_MIN_FOLLOWERS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(FOLLOWER_HISTORY_MIN_FOLLOWERS_DEFAULT);
+        let follower_history_pubkeys = env_list("FOLLOWER_HISTORY_PUBKEYS");
+
+        // Bounded snapshot retention (1-3650 days)
+        let follower_history_retention_days = env::var("FOLLOWER_HISTORY_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|d: u64| d.clamp(1, 3650))
+            .unwrap_or(FOLLOWER_HISTORY_RETENTION_DEFAULT_DAYS);
+
+        // Bounded edge change log retention (1-3650 days)
+        let edge_history_retention_days = env::var("EDGE_HISTORY_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|d: u64| d.clamp(1, 3650))
+            .unwrap_or(EDGE_HISTORY_RETENTION_DEFAULT_DAYS);
+
+        // Applied contact list changes kept for /outbox consumers (1-3650 days)
+        let outbox_retention_days = env::var("OUTBOX_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|d: u64| d.clamp(1, 3650))
+            .unwrap_or(OUTBOX_RETENTION_DEFAULT_DAYS);
+
+        // Bot-likelihood scores (300-86400 seconds; 0 disables), weighted per feature:
+        // `follow_back=1,churn=1,burst=1,clustering=1,reports=2`
+        let botscore_interval_secs = match env::var("BOTSCORE_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(0) => None,
+            Some(s) => Some(u64::clamp(s, 300, 86_400)),
+            None => Some(BOTSCORE_INTERVAL_DEFAULT_SECS),
+        };
+        let botscore_weights = parse_bot_weights(&env::var("BOTSCORE_WEIGHTS").unwrap_or_default());
+
+        // Graph snapshots for fast restarts (300-86400 seconds; unset or 0 disables)
+        let graph_snapshot_interval_secs = env::var("GRAPH_SNAPSHOT_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&s: &u64| s > 0)
+            .map(|s| s.clamp(300, 86_400));
+
+        // Seconds, a cron expression or `off` per background job: `SCHEDULE_PAGERANK=0 */2 * * *`
+        let mut job_schedules = HashMap::new();
+        for job in SCHEDULED_JOBS {
+            let name = format!("SCHEDULE_{}", job.to_ascii_uppercase());
+            if let Ok(value) = env::var(&name) {
+                match Schedule::parse(&value) {
+                    Ok(schedule) => {
+                        job_schedules.insert(job.to_string(), schedule);
+                    }
+                    Err(e) => warn!("Ignoring invalid {}: {}", name, e),
+                }
+            }
+        }
+
+        // A contact list replacing more than this fraction of the previous one (0.05-1.0) is an
+        // anomaly once at least ANOMALY_MIN_CHANGES entries changed; ANOMALY_MAX_CHANGES always is
+        let anomaly_change_ratio = env::var("ANOMALY_CHANGE_RATIO")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|r: f64| r.clamp(0.05, 1.0))
+            .unwrap_or(ANOMALY_CHANGE_RATIO_DEFAULT);
+        let anomaly_min_changes = env::var("ANOMALY_MIN_CHANGES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.max(1))
+            .unwrap_or(ANOMALY_MIN_CHANGES_DEFAULT);
+        let anomaly_max_changes = env::var("ANOMALY_MAX_CHANGES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.max(1))
+            .unwrap_or(ANOMALY_MAX_CHANGES_DEFAULT);
+
+        // Anomalous updates wait this long before being applied (0-86400 seconds; 0 applies them at once)
+        let anomaly_hold_secs = env::var("ANOMALY_HOLD_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.min(86_400))
+            .unwrap_or(0);
+
+        // Priority authors with dedicated ingestion subscriptions (hex pubkeys)
+        let watchlist_pubkeys = env_list("WATCHLIST_PUBKEYS");
+
+        // Per-relay kinds, author prefixes, rate caps and modes, for relays in any graph:
+        // `wss://a|kinds=3,10000|rate=200;wss://b|authors=00,01|mode=discovery`
+        let relay_settings = parse_relay_settings(&env::var("RELAY_SETTINGS").unwrap_or_default());
+
+        // Outbox-model subscriptions for watched and anchor pubkeys: write relays used per
+        // author (1-10) and in total (1-500)
+        let gossip_enabled = env::var("GOSSIP_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        let gossip_relays_per_author = env::var("GOSSIP_RELAYS_PER_AUTHOR")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.clamp(1, 10))
+            .unwrap_or(GOSSIP_RELAYS_PER_AUTHOR_DEFAULT);
+        let gossip_max_relays = env::var("GOSSIP_MAX_RELAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.clamp(1, 500))
+            .unwrap_or(GOSSIP_MAX_RELAYS_DEFAULT);
+
+        // Firehose clients, each subscribed on its own share of RELAYS (1-16)
+        let ingestion_pools = env::var("INGESTION_POOLS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.clamp(1, 16))
+            .unwrap_or(1);
+
+        // Ingestion dedup cache size (1000-10000000 pubkeys) and save interval (30-86400 seconds)
+        let seen_cache_capacity = env::var("SEEN_CACHE_CAPACITY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.clamp(1_000, 10_000_000))
+            .unwrap_or(SEEN_CACHE_CAPACITY_DEFAULT);
+        let seen_cache_persist_secs = env::var("SEEN_CACHE_PERSIST_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(30, 86_400))
+            .unwrap_or(SEEN_CACHE_PERSIST_DEFAULT_SECS);
+
+        // Coalesce a pubkey's contact lists arriving within this window (0-60000 ms; 0 applies each at once)
+        let ingestion_debounce_ms = env::var("INGESTION_DEBOUNCE_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|ms: u64| ms.min(60_000))
+            .unwrap_or(INGESTION_DEBOUNCE_DEFAULT_MS);
+
+        // Database writes behind ingestion: lists per transaction (1-10000), how long a partial
+        // batch may wait (100-600000 ms), and updates queued before they are dropped (100-1000000)
+        let persist_batch_size = env::var("PERSIST_BATCH_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.clamp(1, 10_000))
+            .unwrap_or(PERSIST_BATCH_SIZE_DEFAULT);
+        let persist_flush_interval_ms = env::var("PERSIST_FLUSH_INTERVAL_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|ms: u64| ms.clamp(100, 600_000))
+            .unwrap_or(PERSIST_FLUSH_INTERVAL_DEFAULT_MS);
+        let persist_queue_size = env::var("PERSIST_QUEUE_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|n: usize| n.clamp(100, 1_000_000))
+            .unwrap_or(PERSIST_QUEUE_SIZE_DEFAULT);
+
+        // Spam limits (0 disables either): contact lists with more follows are ignored, and
+        // pubkeys followed by nobody that follow at least QUARANTINE_MIN_FOLLOWS are left out of path queries
+        let max_follows_per_list = env::var("MAX_FOLLOWS_PER_LIST")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(MAX_FOLLOWS_PER_LIST_DEFAULT);
+        let quarantine_min_follows = env::var("QUARANTINE_MIN_FOLLOWS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(QUARANTINE_MIN_FOLLOWS_DEFAULT);
+
+        // Contact list events with more tags than this are dropped before their tags are
+        // parsed, or cut down to the first MAX_TAGS_PER_LIST with TRUNCATE_OVERSIZED_LISTS
+        let max_tags_per_list = env::var("MAX_TAGS_PER_LIST")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(MAX_TAGS_PER_LIST_DEFAULT);
+        let truncate_oversized_lists = env::var("TRUNCATE_OVERSIZED_LISTS")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
+        // BFS walks an evenly spaced sample of at most this many follows or followers from any
+        // one node (0 = all), flagging results `truncated`, so super-nodes can't dominate a query
+        let bfs_expansion_limit = env::var("BFS_EXPANSION_LIMIT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+
+        // Alert channels; alerting is disabled unless one is set (DMs need DVM_PRIVATE_KEY)
+        let alert_webhook_url = env::var("ALERT_WEBHOOK_URL").ok().filter(|u| !u.is_empty());
+        let alert_nostr_pubkey = env::var("ALERT_NOSTR_PUBKEY").ok().filter(|p| !p.is_empty());
+
+        // Repeats of the same alert are dropped for this long (60-86400 seconds)
+        let alert_cooldown_secs = env::var("ALERT_COOLDOWN_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(60, 86_400))
+            .unwrap_or(ALERT_COOLDOWN_DEFAULT_SECS);
+
+        // Alert thresholds: relay disconnected (1-1440 minutes), no new contact lists (60-86400 seconds)
+        let alert_relay_down_mins = env::var("ALERT_RELAY_DOWN_MINS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|m: u64| m.clamp(1, 1440))
+            .unwrap_or(ALERT_RELAY_DOWN_DEFAULT_MINS);
+        let alert_ingestion_lag_secs = env::var("ALERT_INGESTION_LAG_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(|s: u64| s.clamp(60, 86_400))
+            .unwrap_or(ALERT_INGESTION_LAG_DEFAULT_SECS);
+
         Self {
             relays,
             http_port,
+            grpc_port,
             db_path,
+            graph_name,
+            graphs,
             dvm_enabled,
             dvm_private_key,
+            dvm_price_msats,
+            dvm_free_pubkeys,
+            dvm_invoice_expiry_secs,
+            dvm_max_request_age_secs,
+            dvm_workers,
+            dvm_queue_size,
+            lnbits_url,
+            lnbits_api_key,
+            relay_enabled,
+            sign_responses,
+            nip05_enabled,
             rate_limit_per_minute,
+            admin_rate_limit_per_minute,
+            explore_rate_limit_per_minute,
+            swagger_ui_dir,
+            trusted_proxies,
+            ip_allowlist,
+            ip_denylist,
+            pubkey_allowlist,
+            pubkey_denylist,
+            restrict_sources,
+            source_allowlist,
+            api_keys,
+            blocked_pubkeys,
+            admin_token,
+            replication_token,
+            replicate_from,
+            replication_interval_secs,
             max_hops,
+            http_max_hops,
+            dvm_max_hops,
+            batch_max_targets,
+            adaptive_hops_degree,
+            adaptive_max_hops,
             cache_size,
+            cache_max_mb,
             cache_ttl_secs,
+            hot_pair_min_hits,
+            hot_pair_refresh_secs,
+            popularity_half_life_secs,
+            cache_warm_count,
+            pagerank_interval_secs,
+            pagerank_update_interval_secs,
+            publish_interval_secs,
+            publish_top_n,
+            publish_anchors,
+            anchor_pubkeys,
+            anchor_interval_secs,
+            anchor_auto_count,
+            commitment_interval_secs,
+            follower_history_interval_secs,
+            follower_history_min_followers,
+            follower_history_pubkeys,
+            follower_history_retention_days,
+            edge_history_retention_days,
+            outbox_retention_days,
+            botscore_interval_secs,
+            botscore_weights,
+            graph_snapshot_interval_secs,
+            job_schedules,
+            anomaly_change_ratio,
+            anomaly_min_changes,
+            anomaly_max_changes,
+            anomaly_hold_secs,
+            watchlist_pubkeys,
+            relay_settings,
+            gossip_enabled,
+            gossip_relays_per_author,
+            gossip_max_relays,
+            ingestion_pools,
+            seen_cache_capacity,
+            seen_cache_persist_secs,
+            ingestion_debounce_ms,
+            persist_batch_size,
+            persist_flush_interval_ms,
+            persist_queue_size,
+            max_follows_per_list,
+            max_tags_per_list,
+            truncate_oversized_lists,
+            quarantine_min_follows,
+            bfs_expansion_limit,
+            alert_webhook_url,
+            alert_nostr_pubkey,
+            alert_cooldown_secs,
+            alert_relay_down_mins,
+            alert_ingestion_lag_secs,
         }
     }
 }
+
+impl Config {
+    /// `SCHEDULE_<JOB>` when set, else `default` (the job's interval setting)
+    pub fn job_schedule(&self, job: &str, default: Schedule) -> Schedule {
+        self.job_schedules.get(job).cloned().unwrap_or(default)
+    }
+
+    /// Where this graph's snapshot is kept when snapshots are enabled: next to its database
+    pub fn graph_snapshot_path(&self) -> Option<String> {
+        match self.graph_snapshot_interval_secs {
+            Some(_) if self.db_path != ":memory:" => Some(format!("{}.snapshot", self.db_path)),
+            _ => None,
+        }
+    }
+
+    /// The adaptive max_hops policy for searches touching super-nodes
+    pub fn hop_policy(&self) -> HopPolicy {
+        HopPolicy {
+            degree_threshold: self.adaptive_hops_degree,
+            max_hops: self.adaptive_max_hops,
+        }
+    }
+}
+
+impl Config {
+    /// Ingestion settings for a relay, the defaults unless `RELAY_SETTINGS` lists it
+    pub fn relay_settings(&self, url: &str) -> RelaySettings {
+        self.relay_settings.get(url.trim_end_matches('/')).cloned().unwrap_or_default()
+    }
+}
+
+/// Parse `RELAY_SETTINGS`: `;`-separated relays, each a URL followed by `|key=value` options
+/// (`kinds`, `authors`, `rate`, `mode`). Invalid options are skipped with a warning.
+fn parse_relay_settings(spec: &str) -> HashMap<String, RelaySettings> {
+    let mut relays = HashMap::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let mut parts = entry.split('|').map(str::trim);
+        let url = parts.next().unwrap_or_default().trim_end_matches('/').to_string();
+        let mut settings = RelaySettings::default();
+        for option in parts {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            let values = || value.split(',').map(str::trim).filter(|v| !v.is_empty());
+            match key.trim() {
+                "kinds" => {
+                    let kinds: Vec<u16> = values().filter_map(|k| k.parse().ok()).filter(|k| RELAY_KINDS.contains(k)).collect();
+                    if kinds.len() == values().count() && !kinds.is_empty() {
+                        settings.kinds = kinds;
+                    } else {
+                        warn!("Ignoring RELAY_SETTINGS kinds for {}: supported kinds are {:?}", url, RELAY_KINDS);
+                    }
+                }
+                "authors" => {
+                    let prefixes: Vec<String> = values().map(str::to_ascii_lowercase).collect();
+                    if prefixes.iter().all(|p| p.len() <= 64 && p.bytes().all(|b| b.is_ascii_hexdigit())) {
+                        settings.author_prefixes = prefixes;
+                    } else {
+                        warn!("Ignoring RELAY_SETTINGS authors for {}: expected hex prefixes", url);
+                    }
+                }
+                "rate" => match value.trim().parse() {
+                    Ok(rate) => settings.max_events_per_sec = rate,
+                    Err(_) => warn!("Ignoring RELAY_SETTINGS rate for {}: {}", url, value),
+                },
+                "mode" => match value.trim() {
+                    "read" => settings.mode = RelayMode::Read,
+                    "discovery" => settings.mode = RelayMode::Discovery,
+                    _ => warn!("Ignoring RELAY_SETTINGS mode for {}: expected read or discovery", url),
+                },
+                _ => warn!("Ignoring unknown RELAY_SETTINGS option for {}: {}", url, option),
+            }
+        }
+        if url.is_empty() {
+            warn!("Ignoring RELAY_SETTINGS entry without a relay URL: {}", entry);
+            continue;
+        }
+        relays.insert(url, settings);
+    }
+    relays
+}
+
+/// Parse `BOTSCORE_WEIGHTS`: `,`-separated `feature=weight` pairs overriding the default
+/// weight of 1. Invalid pairs are skipped with a warning; if every weight ends up 0, the
+/// defaults are used.
+fn parse_bot_weights(spec: &str) -> BotWeights {
+    let mut weights = BotWeights::default();
+    for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let Some(weight) = value.trim().parse::<f64>().ok().filter(|w| w.is_finite() && *w >= 0.0) else {
+            warn!("Ignoring BOTSCORE_WEIGHTS entry {}: expected a non-negative weight", pair);
+            continue;
+        };
+        match key.trim() {
+            "follow_back" => weights.follow_back = weight,
+            "churn" => weights.churn = weight,
+            "burst" => weights.burst = weight,
+            "clustering" => weights.clustering = weight,
+            "reports" => weights.reports = weight,
+            _ => warn!("Ignoring unknown BOTSCORE_WEIGHTS feature: {}", key),
+        }
+    }
+    let BotWeights { follow_back, churn, burst, clustering, reports } = weights;
+    if follow_back + churn + burst + clustering + reports == 0.0 {
+        warn!("BOTSCORE_WEIGHTS are all 0; using the defaults");
+        return BotWeights::default();
+    }
+    weights
+}
+
+/// Graph names are lowercase letters, digits, '-' and '_' (they appear in env var names and URLs)
+fn is_graph_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// `wot.db` -> `wot-<name>.db`
+pub fn suffixed_path(path: &str, name: &str) -> String {
+    let file_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[file_start..].rfind('.') {
+        Some(dot) if dot > 0 => format!("{}-{}{}", &path[..file_start + dot], name, &path[file_start + dot..]),
+        _ => format!("{}-{}", path, name),
+    }
+}
+
+/// Comma-separated env var as a list of trimmed, non-empty entries
+fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn env_ip_nets(name: &str) -> Vec<IpNet> {
+    env_list(name)
+        .into_iter()
+        .filter_map(|s| match parse_ip_net(&s) {
+            Some(net) => Some(net),
+            None => {
+                warn!("Ignoring invalid {} entry: {}", name, s);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parse a CIDR ("10.0.0.0/8") or a bare address (treated as a single host)
+pub fn parse_ip_net(s: &str) -> Option<IpNet> {
+    s.parse::<IpNet>()
+        .ok()
+        .or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_relay_settings() {
+        let relays = parse_relay_settings(
+            "wss://a/|kinds=3, 10000,1984|rate=200 ; wss://b|authors=00,AB|mode=discovery|kinds=7|bogus",
+        );
+        assert_eq!(relays.len(), 2);
+        assert_eq!(
+            relays["wss://a"],
+            RelaySettings { kinds: vec![3, 10000, 1984], max_events_per_sec: 200, ..Default::default() }
+        );
+        // Unsupported kinds and unknown options are ignored
+        assert_eq!(
+            relays["wss://b"],
+            RelaySettings { author_prefixes: vec!["00".into(), "ab".into()], mode: RelayMode::Discovery, ..Default::default() }
+        );
+
+        let config = Config { relay_settings: relays, ..Config::from_env() };
+        assert_eq!(config.relay_settings("wss://a/").max_events_per_sec, 200);
+        assert_eq!(config.relay_settings("wss://c"), RelaySettings::default());
+        assert!(parse_relay_settings("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_bot_weights() {
+        assert_eq!(parse_bot_weights(""), BotWeights::default());
+        assert_eq!(
+            parse_bot_weights("reports=2.5, burst=0,churn=-1,likes=3,clustering"),
+            BotWeights { reports: 2.5, burst: 0.0, ..Default::default() }
+        );
+        let none = "follow_back=0,churn=0,burst=0,clustering=0,reports=0";
+        assert_eq!(parse_bot_weights(none), BotWeights::default());
+    }
+}
@@ -1,3 +1,4 @@
+pub mod snapshot;
 pub mod sqlite;
 
-pub use sqlite::{Database, FollowUpdateBatch};
+pub use sqlite::{BlockedPubkey, Database, FollowAnomaly, FollowUpdateBatch, IntegrityReport, OutboxEntry, ReplicatedList, SyncState, UserSettings};
@@ -0,0 +1,220 @@
+//! Binary dump of a graph's follow lists, loaded at startup in place of reading every edge from
+//! SQLite. A snapshot records when it was taken; lists the database stored since then are
+//! replayed on top of it (see `namespace::load_snapshot`).
+//!
+//! Layout, little-endian: magic, `taken_at` (i64), `max_follows` (u64), the blocked pubkeys,
+//! every node's pubkey in ID order, then each follow list as its node's index, event metadata
+//! and followed indices. Indices are `NodeId`s, lengths u32, and strings are u16-length-prefixed
+//! UTF-8.
+
+use anyhow::{bail, ensure, Context, Result};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::sync::Arc;
+
+use super::sqlite::LOAD_WORKERS_MAX;
+use crate::graph::{NodeId, WotGraph};
+use crate::types::Pubkey;
+
+const MAGIC: &[u8; 8] = b"WOTSNAP2";
+
+/// Follow lists read before they are applied, split across the load workers
+const APPLY_BATCH: usize = 16_384;
+
+/// What a snapshot was taken under, read before any list is applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    /// Unix time the snapshot was started; lists applied after it may be missing
+    pub taken_at: i64,
+    /// `max_follows` of the graph it was taken from; longer lists are not in it
+    pub max_follows: usize,
+    /// Pubkeys blocked when it was taken; their lists and edges are not in it
+    pub blocked: Vec<String>,
+}
+
+/// Write `graph`'s follow lists to `path`, replacing any snapshot there once complete.
+/// `blocked` must be read before `taken_at`, and `taken_at` before the graph. Returns the number
+/// of lists written.
+pub fn write(graph: &WotGraph, blocked: &[String], taken_at: i64, path: &str) -> Result<usize> {
+    let partial = format!("{}.partial", path);
+    let mut out = BufWriter::new(File::create(&partial).with_context(|| format!("Failed to create {}", partial))?);
+    out.write_all(MAGIC)?;
+    out.write_all(&taken_at.to_le_bytes())?;
+    out.write_all(&(graph.limits().max_follows as u64).to_le_bytes())?;
+    write_len(&mut out, blocked.len())?;
+    for pubkey in blocked {
+        write_str(&mut out, pubkey)?;
+    }
+
+    // Nodes created while writing have no list in the snapshot, so the count can be fixed first
+    let node_count = graph.node_count();
+    write_len(&mut out, node_count)?;
+    for id in 0..node_count as NodeId {
+        write_str(&mut out, &graph.pubkey(id).map(String::from).unwrap_or_default())?;
+    }
+
+    let mut lists = 0;
+    for id in 0..node_count as NodeId {
+        let Some((info, follows)) = graph.follow_list(id) else {
+            continue;
+        };
+        // Nodes created after the count was taken are followed by lists written after it;
+        // those lists are newer than the snapshot and replayed on load
+        let follows: Vec<NodeId> = follows.into_iter().filter(|&f| (f as usize) < node_count).collect();
+        out.write_all(&[1])?;
+        out.write_all(&id.to_le_bytes())?;
+        write_opt_str(&mut out, info.kind3_event_id.as_deref())?;
+        match info.kind3_created_at {
+            Some(created_at) => {
+                out.write_all(&[1])?;
+                out.write_all(&created_at.to_le_bytes())?;
+            }
+            None => out.write_all(&[0])?,
+        }
+        write_len(&mut out, follows.len())?;
+        for followed in follows {
+            out.write_all(&followed.to_le_bytes())?;
+        }
+        lists += 1;
+    }
+    out.write_all(&[0])?;
+    out.into_inner()?.sync_all()?;
+    std::fs::rename(&partial, path).with_context(|| format!("Failed to move snapshot to {}", path))?;
+    Ok(lists)
+}
+
+/// An open snapshot whose header has been read
+pub struct SnapshotReader {
+    header: SnapshotHeader,
+    input: BufReader<File>,
+}
+
+impl SnapshotReader {
+    pub fn open(path: &str) -> Result<Self> {
+        let mut input = BufReader::new(File::open(path).with_context(|| format!("Failed to open {}", path))?);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        ensure!(&magic == MAGIC, "{} is not a graph snapshot", path);
+        let taken_at = i64::from_le_bytes(read_array(&mut input)?);
+        let max_follows = u64::from_le_bytes(read_array(&mut input)?) as usize;
+        let blocked = (0..read_len(&mut input)?)
+            .map(|_| read_str(&mut input))
+            .collect::<Result<_>>()?;
+        Ok(Self { header: SnapshotHeader { taken_at, max_follows, blocked }, input })
+    }
+
+    pub fn header(&self) -> &SnapshotHeader {
+        &self.header
+    }
+
+    /// Apply the snapshot's lists to `graph`, creating its nodes in snapshot order first.
+    /// Blocked pubkeys are skipped as in a database load. Returns the number of lists applied.
+    pub fn load_into(mut self, graph: &WotGraph) -> Result<usize> {
+        let input = &mut self.input;
+        let pubkeys: Vec<Arc<str>> = (0..read_len(input)?)
+            .map(|_| read_str(input).map(Arc::from))
+            .collect::<Result<_>>()?;
+        for pubkey in &pubkeys {
+            let Ok(parsed) = Pubkey::parse(pubkey) else {
+                continue;
+            };
+            if !graph.is_blocked(pubkey) {
+                graph.get_or_create_node(&parsed)?;
+            }
+        }
+
+        let pubkey = |index: NodeId| pubkeys.get(index as usize).context("Snapshot refers to an unknown node");
+        let mut applied = 0;
+        loop {
+            let mut batch = Vec::with_capacity(APPLY_BATCH);
+            while batch.len() < APPLY_BATCH && read_u8(input)? == 1 {
+                let author = pubkey(NodeId::from_le_bytes(read_array(input)?))?.clone();
+                let event_id = read_opt_str(input)?;
+                let created_at = match read_u8(input)? {
+                    0 => None,
+                    _ => Some(i64::from_le_bytes(read_array(input)?)),
+                };
+                let follows = (0..read_len(input)?)
+                    .map(|_| pubkey(NodeId::from_le_bytes(read_array(input)?)).map(|pk| pk.to_string()))
+                    .collect::<Result<Vec<String>>>()?;
+                batch.push((author, follows, event_id, created_at));
+            }
+            if batch.is_empty() {
+                break;
+            }
+            let (len, full) = (batch.len(), batch.len() == APPLY_BATCH);
+            apply(graph, &batch);
+            applied += len;
+            if !full {
+                break;
+            }
+        }
+        Ok(applied)
+    }
+}
+
+/// A list as read from a snapshot: author, follows, event ID and `created_at`
+type SnapshotList = (Arc<str>, Vec<String>, Option<String>, Option<i64>);
+
+/// Apply a batch of lists across the load workers
+fn apply(graph: &WotGraph, batch: &[SnapshotList]) {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get()).min(LOAD_WORKERS_MAX);
+    std::thread::scope(|scope| {
+        for chunk in batch.chunks(batch.len().div_ceil(workers)) {
+            scope.spawn(move || {
+                for (author, follows, event_id, created_at) in chunk {
+                    graph.update_follows(author, follows, event_id.clone(), *created_at);
+                }
+            });
+        }
+    });
+}
+
+fn write_len(out: &mut impl Write, len: usize) -> Result<()> {
+    out.write_all(&u32::try_from(len).context("Snapshot section too long")?.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_str(out: &mut impl Write, s: &str) -> Result<()> {
+    out.write_all(&u16::try_from(s.len()).context("Snapshot string too long")?.to_le_bytes())?;
+    out.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+fn write_opt_str(out: &mut impl Write, s: Option<&str>) -> Result<()> {
+    match s {
+        Some(s) => {
+            out.write_all(&[1])?;
+            write_str(out, s)
+        }
+        None => Ok(out.write_all(&[0])?),
+    }
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes).context("Snapshot ends early")?;
+    Ok(bytes)
+}
+
+fn read_u8(input: &mut impl Read) -> Result<u8> {
+    Ok(read_array::<1>(input)?[0])
+}
+
+fn read_len(input: &mut impl Read) -> Result<usize> {
+    Ok(u32::from_le_bytes(read_array(input)?) as usize)
+}
+
+fn read_str(input: &mut impl Read) -> Result<String> {
+    let mut bytes = vec![0; u16::from_le_bytes(read_array(input)?) as usize];
+    input.read_exact(&mut bytes).context("Snapshot ends early")?;
+    String::from_utf8(bytes).context("Snapshot string is not UTF-8")
+}
+
+fn read_opt_str(input: &mut impl Read) -> Result<Option<String>> {
+    match read_u8(input)? {
+        0 => Ok(None),
+        1 => read_str(input).map(Some),
+        flag => bail!("Invalid snapshot flag {}", flag),
+    }
+}
@@ -1,15 +1,30 @@
 use anyhow::Result;
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::sync::Mutex;
 use tracing::{info, debug};
+use utoipa::ToSchema;
 
 use crate::graph::WotGraph;
+use crate::types::Pubkey;
+
+// Threads loading edges in `load_graph` (and snapshot lists), at most one per core
+pub(super) const LOAD_WORKERS_MAX: usize = 8;
 
 pub struct Database {
     conn: Mutex<Connection>,
 }
 
+/// A `nodes` row, as read by `load_graph`
+struct StoredNode {
+    id: i64,
+    pubkey: String,
+    event_id: Option<String>,
+    created_at: Option<i64>,
+}
+
 /// Batch update item for efficient multi-event persistence
 pub struct FollowUpdateBatch<'a> {
     pub pubkey: &'a str,
@@ -18,8 +33,92 @@ pub struct FollowUpdateBatch<'a> {
     pub created_at: Option<i64>,
 }
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)] // Public API for sync state inspection
+/// A contact list update that changed too much at once (see `sync::anomaly`)
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FollowAnomaly {
+    pub id: i64,
+    pub pubkey: String,
+    pub event_id: String,
+    pub event_created_at: i64,
+    pub detected_at: i64,
+    pub previous_count: usize,
+    pub new_count: usize,
+    pub added: usize,
+    pub removed: usize,
+    /// `applied`, `held`, then for held updates `released` or `superseded`
+    pub action: String,
+}
+
+/// An operator-blocked pubkey
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct BlockedPubkey {
+    pub pubkey: String,
+    pub reason: Option<String>,
+    pub blocked_at: i64,
+}
+
+/// A stored contact list, as served to replicas
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReplicatedList {
+    pub pubkey: String,
+    pub follows: Vec<String>,
+    pub event_id: Option<String>,
+    pub created_at: Option<i64>,
+}
+
+/// An applied contact list change, as served by `/outbox`
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct OutboxEntry {
+    /// Position in the outbox: increases with every change and is never reused
+    pub seq: i64,
+    pub pubkey: String,
+    pub event_id: Option<String>,
+    pub created_at: Option<i64>,
+    /// Unix time the change was written
+    pub recorded_at: i64,
+    /// Follows added; every follow for a pubkey's first list
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Invariant violations in a database (`wot-oracle fsck`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// First line of SQLite's own `PRAGMA quick_check`: "ok" when the file itself is sound
+    pub quick_check: String,
+    pub nodes: usize,
+    pub edges: usize,
+    /// Edges whose follower or followed node is missing from `nodes`
+    pub orphan_edges: usize,
+    /// Pubkeys stored as more than one node, ignoring case
+    pub duplicate_pubkeys: usize,
+    pub null_updated_at: usize,
+}
+
+impl IntegrityReport {
+    /// Violations found; the quick check failing counts as one
+    pub fn problems(&self) -> usize {
+        usize::from(self.quick_check != "ok") + self.orphan_edges + self.duplicate_pubkeys + self.null_updated_at
+    }
+}
+
+/// Query defaults a user registered under `/me/settings`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UserSettings {
+    /// `from` for distance, batch and path queries that leave it out
+    pub root_pubkey: Option<String>,
+    /// max_hops for queries that leave it out
+    pub max_hops: Option<u8>,
+    /// Treat pubkeys on the user's mute list (kind 10000) as unreachable
+    #[serde(default)]
+    pub exclude_muted: bool,
+    /// Ranking for DVM reputation requests that don't pass `sort`:
+    /// `globalPagerank`, `personalizedPagerank` or `followerCount`
+    pub scoring: Option<String>,
+}
+
+/// Where ingestion from a relay left off
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
 pub struct SyncState {
     pub relay_url: String,
     pub last_event_time: Option<i64>,
@@ -51,10 +150,12 @@ impl Database {
                 pubkey TEXT NOT NULL UNIQUE,
                 kind3_event_id TEXT,
                 kind3_created_at INTEGER,
-                updated_at INTEGER NOT NULL
+                updated_at INTEGER NOT NULL,
+                first_seen INTEGER
             );
 
             CREATE INDEX IF NOT EXISTS idx_nodes_pubkey ON nodes(pubkey);
+            CREATE INDEX IF NOT EXISTS idx_nodes_updated ON nodes(updated_at, id);
 
             CREATE TABLE IF NOT EXISTS edges (
                 follower_id INTEGER NOT NULL,
@@ -72,73 +173,155 @@ impl Database {
                 last_event_time INTEGER,
                 last_sync_at INTEGER
             );
+
+            CREATE TABLE IF NOT EXISTS follower_history (
+                pubkey TEXT NOT NULL,
+                taken_at INTEGER NOT NULL,
+                follower_count INTEGER NOT NULL,
+                PRIMARY KEY (pubkey, taken_at)
+            ) WITHOUT ROWID;
+
+            CREATE TABLE IF NOT EXISTS follow_anomalies (
+                id INTEGER PRIMARY KEY,
+                pubkey TEXT NOT NULL,
+                event_id TEXT NOT NULL,
+                event_created_at INTEGER NOT NULL,
+                detected_at INTEGER NOT NULL,
+                previous_count INTEGER NOT NULL,
+                new_count INTEGER NOT NULL,
+                added INTEGER NOT NULL,
+                removed INTEGER NOT NULL,
+                action TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_follow_anomalies_detected ON follow_anomalies(detected_at);
+
+            CREATE TABLE IF NOT EXISTS edge_changes (
+                follower_id INTEGER NOT NULL,
+                followed_id INTEGER NOT NULL,
+                changed_at INTEGER NOT NULL,
+                added INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_edge_changes_follower ON edge_changes(follower_id, changed_at);
+            CREATE INDEX IF NOT EXISTS idx_edge_changes_changed ON edge_changes(changed_at);
+
+            CREATE TABLE IF NOT EXISTS outbox (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                pubkey TEXT NOT NULL,
+                event_id TEXT,
+                created_at INTEGER,
+                recorded_at INTEGER NOT NULL,
+                added TEXT NOT NULL,
+                removed TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_outbox_recorded ON outbox(recorded_at);
+
+            CREATE TABLE IF NOT EXISTS seen_events (
+                pubkey BLOB PRIMARY KEY,
+                created_at INTEGER NOT NULL
+            ) WITHOUT ROWID;
+
+            CREATE TABLE IF NOT EXISTS author_lists (
+                pubkey TEXT NOT NULL,
+                kind INTEGER NOT NULL,
+                event_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                tags TEXT NOT NULL,
+                PRIMARY KEY (pubkey, kind)
+            ) WITHOUT ROWID;
+
+            CREATE TABLE IF NOT EXISTS reports (
+                reported TEXT NOT NULL,
+                reporter TEXT NOT NULL,
+                report_type TEXT,
+                event_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (reported, reporter)
+            ) WITHOUT ROWID;
+
+            CREATE TABLE IF NOT EXISTS blocked_pubkeys (
+                pubkey TEXT PRIMARY KEY,
+                reason TEXT,
+                blocked_at INTEGER NOT NULL
+            ) WITHOUT ROWID;
+
+            CREATE TABLE IF NOT EXISTS user_settings (
+                pubkey TEXT PRIMARY KEY,
+                root_pubkey TEXT,
+                max_hops INTEGER,
+                exclude_muted INTEGER NOT NULL,
+                scoring TEXT,
+                updated_at INTEGER NOT NULL
+            ) WITHOUT ROWID;
         "#)?;
 
+        // Databases created before first_seen was tracked keep NULL for existing nodes
+        let has_first_seen = conn
+            .prepare("SELECT 1 FROM pragma_table_info('nodes') WHERE name = 'first_seen'")?
+            .exists([])?;
+        if !has_first_seen {
+            conn.execute_batch("ALTER TABLE nodes ADD COLUMN first_seen INTEGER")?;
+        }
+
         info!("Database schema initialized");
         Ok(())
     }
 
+    /// Load every stored follow list into `graph`. Nodes are created first, in database ID order;
+    /// then up to `LOAD_WORKERS_MAX` threads each read a range of followers' edges over their own
+    /// read-only connection and apply the lists as they go. In-memory databases, which other
+    /// connections can't open, load on one thread.
     pub fn load_graph(&self, graph: &WotGraph) -> Result<()> {
         let conn = self.conn.lock().unwrap();
 
-        // Load all nodes
         let mut node_stmt = conn.prepare(
             "SELECT id, pubkey, kind3_event_id, kind3_created_at FROM nodes ORDER BY id"
         )?;
-
-        let nodes: Vec<(i64, String, Option<String>, Option<i64>)> = node_stmt
+        let nodes = node_stmt
             .query_map([], |row| {
-                Ok((
-                    row.get(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                ))
+                Ok(StoredNode { id: row.get(0)?, pubkey: row.get(1)?, event_id: row.get(2)?, created_at: row.get(3)? })
             })?
-            .filter_map(|r| r.ok())
-            .collect();
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(node_stmt);
 
         info!("Loading {} nodes from database", nodes.len());
 
-        // Create nodes in graph (they will get sequential IDs)
-        for (_, pubkey, _, _) in &nodes {
-            graph.get_or_create_node(pubkey);
+        // Create nodes in graph (they will get sequential IDs); blocked pubkeys and rows that
+        // aren't hex pubkeys never become nodes
+        for node in &nodes {
+            let Ok(pubkey) = Pubkey::parse(&node.pubkey) else {
+                continue;
+            };
+            if !graph.is_blocked(&node.pubkey) {
+                graph.get_or_create_node(&pubkey)?;
+            }
         }
 
-        // Load edges grouped by follower
-        let mut edge_stmt = conn.prepare(
-            "SELECT e.follower_id, n.pubkey, GROUP_CONCAT(n2.pubkey) as follows
-             FROM edges e
-             JOIN nodes n ON e.follower_id = n.id
-             JOIN nodes n2 ON e.followed_id = n2.id
-             GROUP BY e.follower_id"
-        )?;
-
-        let mut edge_count = 0;
-        let edges: Vec<(String, String)> = edge_stmt
-            .query_map([], |row| {
-                Ok((
-                    row.get::<_, String>(1)?,
-                    row.get::<_, String>(2)?,
-                ))
-            })?
-            .filter_map(|r| r.ok())
-            .collect();
-
-        for (follower_pubkey, follows_csv) in edges {
-            let follows: Vec<String> = follows_csv.split(',').map(|s| s.to_string()).collect();
-            edge_count += follows.len();
-
-            // Find the node's event info
-            let node_info = nodes.iter()
-                .find(|(_, pk, _, _)| pk == &follower_pubkey);
-
-            let (event_id, created_at) = node_info
-                .map(|(_, _, eid, cat)| (eid.clone(), *cat))
-                .unwrap_or((None, None));
-
-            graph.update_follows(&follower_pubkey, &follows, event_id, created_at);
-        }
+        let path = conn.path().filter(|path| !path.is_empty()).map(str::to_owned);
+        let edge_count = match path {
+            Some(path) if nodes.len() > 1 => {
+                let workers = std::thread::available_parallelism().map_or(1, |n| n.get()).min(LOAD_WORKERS_MAX);
+                let ranges = nodes.chunks(nodes.len().div_ceil(workers));
+                std::thread::scope(|scope| {
+                    let handles: Vec<_> = ranges
+                        .map(|range| {
+                            let (path, nodes) = (&path, &nodes);
+                            scope.spawn(move || {
+                                let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+                                load_edges(&conn, graph, nodes, range)
+                            })
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                        .sum::<Result<usize>>()
+                })?
+            }
+            _ => load_edges(&conn, graph, &nodes, &nodes)?,
+        };
 
         info!("Loaded {} edges from database", edge_count);
         Ok(())
@@ -156,8 +339,8 @@ impl Database {
 
         conn.execute(
             r#"
-            INSERT INTO nodes (pubkey, kind3_event_id, kind3_created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4)
+            INSERT INTO nodes (pubkey, kind3_event_id, kind3_created_at, updated_at, first_seen)
+            VALUES (?1, ?2, ?3, ?4, ?4)
             ON CONFLICT(pubkey) DO UPDATE SET
                 kind3_event_id = COALESCE(?2, kind3_event_id),
                 kind3_created_at = COALESCE(?3, kind3_created_at),
@@ -177,110 +360,16 @@ impl Database {
 
     #[allow(dead_code)] // Public API for direct follow list updates
     pub fn update_follows(&self, follower_pubkey: &str, follows: &[String], event_id: Option<&str>, created_at: Option<i64>) -> Result<()> {
-        if follows.is_empty() {
-            // Just update the node, clear edges
-            let mut conn = self.conn.lock().unwrap();
-            let tx = conn.transaction()?;
-            let now = chrono::Utc::now().timestamp();
-
-            tx.execute(
-                r#"
-                INSERT INTO nodes (pubkey, kind3_event_id, kind3_created_at, updated_at)
-                VALUES (?1, ?2, ?3, ?4)
-                ON CONFLICT(pubkey) DO UPDATE SET
-                    kind3_event_id = COALESCE(?2, kind3_event_id),
-                    kind3_created_at = COALESCE(?3, kind3_created_at),
-                    updated_at = ?4
-                "#,
-                params![follower_pubkey, event_id, created_at, now],
-            )?;
-
-            let follower_id: i64 = tx.query_row(
-                "SELECT id FROM nodes WHERE pubkey = ?1",
-                params![follower_pubkey],
-                |row| row.get(0),
-            )?;
-
-            tx.execute("DELETE FROM edges WHERE follower_id = ?1", params![follower_id])?;
-            tx.commit()?;
-            return Ok(());
-        }
-
-        let mut conn = self.conn.lock().unwrap();
-        let tx = conn.transaction()?;
-        let now = chrono::Utc::now().timestamp();
-
-        // Upsert follower node
-        tx.execute(
-            r#"
-            INSERT INTO nodes (pubkey, kind3_event_id, kind3_created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4)
-            ON CONFLICT(pubkey) DO UPDATE SET
-                kind3_event_id = COALESCE(?2, kind3_event_id),
-                kind3_created_at = COALESCE(?3, kind3_created_at),
-                updated_at = ?4
-            "#,
-            params![follower_pubkey, event_id, created_at, now],
-        )?;
-
-        let follower_id: i64 = tx.query_row(
-            "SELECT id FROM nodes WHERE pubkey = ?1",
-            params![follower_pubkey],
-            |row| row.get(0),
-        )?;
-
-        // Delete existing edges (single statement)
-        tx.execute("DELETE FROM edges WHERE follower_id = ?1", params![follower_id])?;
-
-        // Batch insert followed nodes using prepared statement
-        {
-            let mut insert_node_stmt = tx.prepare_cached(
-                "INSERT INTO nodes (pubkey, updated_at) VALUES (?1, ?2) ON CONFLICT(pubkey) DO NOTHING"
-            )?;
-
-            for follow_pubkey in follows {
-                insert_node_stmt.execute(params![follow_pubkey, now])?;
-            }
-        }
-
-        // Batch fetch all followed node IDs - chunk to avoid SQLite parameter limit (~999)
-        const CHUNK_SIZE: usize = 500;
-        let mut followed_ids: Vec<i64> = Vec::with_capacity(follows.len());
-
-        for chunk in follows.chunks(CHUNK_SIZE) {
-            let placeholders: Vec<&str> = chunk.iter().map(|_| "?").collect();
-            let in_clause = placeholders.join(",");
-            let select_sql = format!("SELECT id FROM nodes WHERE pubkey IN ({})", in_clause);
-
-            let mut select_stmt = tx.prepare(&select_sql)?;
-            let params: Vec<&dyn rusqlite::ToSql> = chunk
-                .iter()
-                .map(|s| s as &dyn rusqlite::ToSql)
-                .collect();
-
-            let rows = select_stmt.query_map(params.as_slice(), |row| row.get::<_, i64>(0))?;
-            followed_ids.extend(rows.filter_map(|r| r.ok()));
-        }
-
-        // Batch insert edges using prepared statement
-        {
-            let mut insert_edge_stmt = tx.prepare_cached(
-                "INSERT OR IGNORE INTO edges (follower_id, followed_id) VALUES (?1, ?2)"
-            )?;
-
-            for followed_id in &followed_ids {
-                insert_edge_stmt.execute(params![follower_id, followed_id])?;
-            }
-        }
-
-        tx.commit()?;
+        self.update_follows_batch(&[FollowUpdateBatch { pubkey: follower_pubkey, follows, event_id, created_at }])?;
         debug!("Updated follows for {} with {} follows", follower_pubkey, follows.len());
-
         Ok(())
     }
 
     /// Batch update multiple follow lists in a single transaction.
     /// Much faster than calling update_follows() in a loop (1 commit vs N commits).
+    /// Follows added or removed against a node's previous list are logged to `edge_changes`
+    /// at the list's `created_at` (capped at now); a node's first list is backfill and isn't.
+    /// Every first list and changed list is also appended to the `outbox`.
     pub fn update_follows_batch(&self, updates: &[FollowUpdateBatch<'_>]) -> Result<usize> {
         if updates.is_empty() {
             return Ok(0);
@@ -295,8 +384,8 @@ impl Database {
             // Prepare statements once, reuse for all updates
             let mut upsert_node_stmt = tx.prepare_cached(
                 r#"
-                INSERT INTO nodes (pubkey, kind3_event_id, kind3_created_at, updated_at)
-                VALUES (?1, ?2, ?3, ?4)
+                INSERT INTO nodes (pubkey, kind3_event_id, kind3_created_at, updated_at, first_seen)
+                VALUES (?1, ?2, ?3, ?4, ?4)
                 ON CONFLICT(pubkey) DO UPDATE SET
                     kind3_event_id = COALESCE(?2, kind3_event_id),
                     kind3_created_at = COALESCE(?3, kind3_created_at),
@@ -308,12 +397,32 @@ impl Database {
                 "SELECT id FROM nodes WHERE pubkey = ?1"
             )?;
 
+            let mut had_list_stmt = tx.prepare_cached(
+                "SELECT kind3_event_id IS NOT NULL OR kind3_created_at IS NOT NULL FROM nodes WHERE pubkey = ?1"
+            )?;
+
+            let mut old_edges_stmt = tx.prepare_cached(
+                "SELECT followed_id FROM edges WHERE follower_id = ?1"
+            )?;
+
+            let mut insert_change_stmt = tx.prepare_cached(
+                "INSERT INTO edge_changes (follower_id, followed_id, changed_at, added) VALUES (?1, ?2, ?3, ?4)"
+            )?;
+
+            let mut get_pubkey_stmt = tx.prepare_cached(
+                "SELECT pubkey FROM nodes WHERE id = ?1"
+            )?;
+
+            let mut insert_outbox_stmt = tx.prepare_cached(
+                "INSERT INTO outbox (pubkey, event_id, created_at, recorded_at, added, removed) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+            )?;
+
             let mut delete_edges_stmt = tx.prepare_cached(
                 "DELETE FROM edges WHERE follower_id = ?1"
             )?;
 
             let mut insert_follow_node_stmt = tx.prepare_cached(
-                "INSERT INTO nodes (pubkey, updated_at) VALUES (?1, ?2) ON CONFLICT(pubkey) DO NOTHING"
+                "INSERT INTO nodes (pubkey, updated_at, first_seen) VALUES (?1, ?2, ?2) ON CONFLICT(pubkey) DO NOTHING"
             )?;
 
             let mut insert_edge_stmt = tx.prepare_cached(
@@ -323,6 +432,11 @@ impl Database {
             let mut success_count = 0;
 
             for update in updates {
+                let had_list: bool = had_list_stmt
+                    .query_row(params![update.pubkey], |row| row.get(0))
+                    .optional()?
+                    .unwrap_or(false);
+
                 // Upsert follower node
                 upsert_node_stmt.execute(params![
                     update.pubkey,
@@ -336,14 +450,13 @@ impl Database {
                     |row| row.get(0),
                 )?;
 
+                let mut old_ids: Vec<i64> = old_edges_stmt
+                    .query_map(params![follower_id], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+
                 // Delete existing edges
                 delete_edges_stmt.execute(params![follower_id])?;
 
-                if update.follows.is_empty() {
-                    success_count += 1;
-                    continue;
-                }
-
                 // Insert followed nodes
                 for follow_pubkey in update.follows {
                     insert_follow_node_stmt.execute(params![follow_pubkey, now])?;
@@ -373,6 +486,37 @@ impl Database {
                     insert_edge_stmt.execute(params![follower_id, followed_id])?;
                 }
 
+                old_ids.sort_unstable();
+                followed_ids.sort_unstable();
+                followed_ids.dedup();
+                let changes = edge_diff(&old_ids, &followed_ids);
+                if had_list || !old_ids.is_empty() {
+                    let changed_at = update.created_at.map_or(now, |t| t.min(now));
+                    for &(followed_id, added) in &changes {
+                        insert_change_stmt.execute(params![follower_id, followed_id, changed_at, added])?;
+                    }
+                }
+
+                if !had_list || !changes.is_empty() {
+                    let (mut added, mut removed) = (Vec::new(), Vec::new());
+                    for &(followed_id, is_added) in &changes {
+                        let pubkey: String = get_pubkey_stmt.query_row(params![followed_id], |row| row.get(0))?;
+                        if is_added {
+                            added.push(pubkey);
+                        } else {
+                            removed.push(pubkey);
+                        }
+                    }
+                    insert_outbox_stmt.execute(params![
+                        update.pubkey,
+                        update.event_id,
+                        update.created_at,
+                        now,
+                        serde_json::to_string(&added)?,
+                        serde_json::to_string(&removed)?
+                    ])?;
+                }
+
                 success_count += 1;
             }
 
@@ -385,7 +529,6 @@ impl Database {
         Ok(success_count)
     }
 
-    #[allow(dead_code)] // Public API for sync state inspection
     pub fn get_sync_state(&self, relay_url: &str) -> Result<Option<SyncState>> {
         let conn = self.conn.lock().unwrap();
 
@@ -408,7 +551,21 @@ impl Database {
         }
     }
 
-    #[allow(dead_code)] // Public API for sync state management
+    /// Sync state of every relay ingestion has recorded, by URL
+    pub fn sync_states(&self) -> Result<Vec<SyncState>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare_cached("SELECT relay_url, last_event_time, last_sync_at FROM sync_state ORDER BY relay_url")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SyncState {
+                relay_url: row.get(0)?,
+                last_event_time: row.get(1)?,
+                last_sync_at: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
     pub fn set_sync_state(&self, relay_url: &str, last_event_time: Option<i64>) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         let now = chrono::Utc::now().timestamp();
@@ -427,6 +584,569 @@ impl Database {
         Ok(())
     }
 
+    /// `(first_seen, updated_at)` for a node, or None if it was never persisted.
+    /// first_seen is NULL for nodes stored before it was tracked.
+    pub fn node_timestamps(&self, pubkey: &str) -> Result<Option<(Option<i64>, i64)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let result = conn.query_row(
+            "SELECT first_seen, updated_at FROM nodes WHERE pubkey = ?1",
+            params![pubkey],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        );
+
+        match result {
+            Ok(timestamps) => Ok(Some(timestamps)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// `(pubkey, first_seen)` for every node that has published a contact list and has a
+    /// recorded first-seen time
+    pub fn author_first_seen(&self) -> Result<Vec<(String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT pubkey, first_seen FROM nodes WHERE kind3_created_at IS NOT NULL AND first_seen IS NOT NULL"
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// `(day_start, count)` of nodes first seen on each UTC day after `from_ts`, up to and
+    /// including `to_ts`, oldest first
+    pub fn daily_new_nodes(&self, from_ts: i64, to_ts: i64) -> Result<Vec<(i64, u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT (first_seen / 86400) * 86400 AS day, COUNT(*) FROM nodes
+            WHERE first_seen > ?1 AND first_seen <= ?2
+            GROUP BY day ORDER BY day
+            "#,
+        )?;
+        let rows = stmt.query_map(params![from_ts, to_ts], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// `created_at` of the newest stored contact list
+    pub fn latest_list_at(&self) -> Result<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.query_row("SELECT MAX(kind3_created_at) FROM nodes", [], |row| row.get(0))?)
+    }
+
+    /// Store one follower-count snapshot for each pubkey, all at `taken_at`
+    pub fn record_follower_counts(&self, taken_at: i64, counts: &[(Pubkey, usize)]) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO follower_history (pubkey, taken_at, follower_count) VALUES (?1, ?2, ?3)"
+            )?;
+            for (pubkey, count) in counts {
+                stmt.execute(params![pubkey.to_hex(), taken_at, *count as i64])?;
+            }
+        }
+        tx.commit()?;
+        Ok(counts.len())
+    }
+
+    /// `(taken_at, follower_count)` snapshots for a pubkey, oldest first
+    pub fn follower_history(&self, pubkey: &str) -> Result<Vec<(i64, u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT taken_at, follower_count FROM follower_history WHERE pubkey = ?1 ORDER BY taken_at"
+        )?;
+        let rows = stmt.query_map(params![pubkey], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Drop snapshots taken before `before`; returns how many were removed
+    pub fn prune_follower_history(&self, before: i64) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM follower_history WHERE taken_at < ?1", params![before])?)
+    }
+
+    /// Net follows a pubkey added and removed after `from_ts`, up to and including `to_ts`,
+    /// each sorted. A follow added and removed again within the window is in neither.
+    pub fn edge_changes(&self, pubkey: &str, from_ts: i64, to_ts: i64) -> Result<(Vec<String>, Vec<String>)> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT n.pubkey, c.added FROM edge_changes c
+            JOIN nodes f ON f.id = c.follower_id
+            JOIN nodes n ON n.id = c.followed_id
+            WHERE f.pubkey = ?1 AND c.changed_at > ?2 AND c.changed_at <= ?3
+            ORDER BY c.changed_at, c.rowid
+            "#,
+        )?;
+        let rows = stmt.query_map(params![pubkey, from_ts, to_ts], |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?)))?;
+
+        // First and last change per follow: absent before and present after is an addition
+        let mut changes: BTreeMap<String, (bool, bool)> = BTreeMap::new();
+        for row in rows {
+            let (followed, added) = row?;
+            changes.entry(followed).and_modify(|(_, last)| *last = added).or_insert((added, added));
+        }
+        let (mut added, mut removed) = (Vec::new(), Vec::new());
+        for (followed, (first, last)) in changes {
+            match (first, last) {
+                (true, true) => added.push(followed),
+                (false, false) => removed.push(followed),
+                _ => {}
+            }
+        }
+        Ok((added, removed))
+    }
+
+    /// `(day_start, added, removed)` follow counts over the whole graph for each UTC day with
+    /// changes after `from_ts`, up to and including `to_ts`, oldest first
+    pub fn daily_edge_changes(&self, from_ts: i64, to_ts: i64) -> Result<Vec<(i64, u64, u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT (changed_at / 86400) * 86400 AS day, SUM(added), SUM(1 - added) FROM edge_changes
+            WHERE changed_at > ?1 AND changed_at <= ?2
+            GROUP BY day ORDER BY day
+            "#,
+        )?;
+        let rows = stmt.query_map(params![from_ts, to_ts], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Drop edge changes made before `before`; returns how many were removed
+    pub fn prune_edge_changes(&self, before: i64) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM edge_changes WHERE changed_at < ?1", params![before])?)
+    }
+
+    /// Up to `limit` outbox entries after `since_seq`, oldest first
+    pub fn outbox_since(&self, since_seq: i64, limit: usize) -> Result<Vec<OutboxEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT seq, pubkey, event_id, created_at, recorded_at, added, removed FROM outbox
+            WHERE seq > ?1 ORDER BY seq LIMIT ?2
+            "#,
+        )?;
+        let rows = stmt.query_map(params![since_seq, limit as i64], |row| {
+            Ok((
+                OutboxEntry {
+                    seq: row.get(0)?,
+                    pubkey: row.get(1)?,
+                    event_id: row.get(2)?,
+                    created_at: row.get(3)?,
+                    recorded_at: row.get(4)?,
+                    added: Vec::new(),
+                    removed: Vec::new(),
+                },
+                row.get::<_, String>(5)?,
+                row.get::<_, String>(6)?,
+            ))
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (mut entry, added, removed) = row?;
+            entry.added = serde_json::from_str(&added)?;
+            entry.removed = serde_json::from_str(&removed)?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Sequence of the oldest outbox entry still kept, or of the next one to be recorded once
+    /// all have been pruned; None before the first
+    pub fn outbox_oldest_seq(&self) -> Result<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.query_row(
+            "SELECT COALESCE(MIN(seq), (SELECT seq + 1 FROM sqlite_sequence WHERE name = 'outbox')) FROM outbox",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Drop outbox entries written before `before`; returns how many were removed
+    pub fn prune_outbox(&self, before: i64) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM outbox WHERE recorded_at < ?1", params![before])?)
+    }
+
+    /// Store a detected anomaly (its `id` is ignored); returns the assigned ID
+    pub fn record_anomaly(&self, anomaly: &FollowAnomaly) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            r#"
+            INSERT INTO follow_anomalies
+                (pubkey, event_id, event_created_at, detected_at, previous_count, new_count, added, removed, action)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+            params![
+                anomaly.pubkey,
+                anomaly.event_id,
+                anomaly.event_created_at,
+                anomaly.detected_at,
+                anomaly.previous_count as i64,
+                anomaly.new_count as i64,
+                anomaly.added as i64,
+                anomaly.removed as i64,
+                anomaly.action,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn set_anomaly_action(&self, id: i64, action: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE follow_anomalies SET action = ?2 WHERE id = ?1", params![id, action])?;
+        Ok(())
+    }
+
+    /// Most recently detected anomalies first
+    pub fn recent_anomalies(&self, limit: usize) -> Result<Vec<FollowAnomaly>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT id, pubkey, event_id, event_created_at, detected_at, previous_count, new_count, added, removed, action
+            FROM follow_anomalies ORDER BY detected_at DESC, id DESC LIMIT ?1
+            "#,
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok(FollowAnomaly {
+                id: row.get(0)?,
+                pubkey: row.get(1)?,
+                event_id: row.get(2)?,
+                event_created_at: row.get(3)?,
+                detected_at: row.get(4)?,
+                previous_count: row.get::<_, i64>(5)? as usize,
+                new_count: row.get::<_, i64>(6)? as usize,
+                added: row.get::<_, i64>(7)? as usize,
+                removed: row.get::<_, i64>(8)? as usize,
+                action: row.get(9)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Store a replaceable list event (mute list, relay list) of a watched author, or of any
+    /// author from a relay subscribed to the kind, with its tags as JSON. Returns false if a
+    /// list at least as new is already stored.
+    pub fn record_author_list(
+        &self,
+        pubkey: &str,
+        kind: u16,
+        event_id: &str,
+        created_at: i64,
+        tags: &[Vec<String>],
+    ) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let changed = conn.execute(
+            r#"
+            INSERT INTO author_lists (pubkey, kind, event_id, created_at, tags)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(pubkey, kind) DO UPDATE SET
+                event_id = excluded.event_id,
+                created_at = excluded.created_at,
+                tags = excluded.tags
+            WHERE excluded.created_at > author_lists.created_at
+            "#,
+            params![pubkey, kind, event_id, created_at, serde_json::to_string(tags)?],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Store a report (NIP-56) against each `(pubkey, report type)` it names, replacing older
+    /// reports of the same pubkey by the same reporter. Returns the number of rows written.
+    pub fn record_report(
+        &self,
+        reporter: &str,
+        event_id: &str,
+        created_at: i64,
+        reported: &[(String, Option<String>)],
+    ) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            r#"
+            INSERT INTO reports (reported, reporter, report_type, event_id, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(reported, reporter) DO UPDATE SET
+                report_type = excluded.report_type,
+                event_id = excluded.event_id,
+                created_at = excluded.created_at
+            WHERE excluded.created_at > reports.created_at
+            "#,
+        )?;
+        let mut written = 0;
+        for (pubkey, report_type) in reported {
+            written += stmt.execute(params![pubkey, reporter, report_type, event_id, created_at])?;
+        }
+        Ok(written)
+    }
+
+    /// Distinct pubkeys that have reported `pubkey`
+    pub fn report_count(&self, pubkey: &str) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached("SELECT COUNT(*) FROM reports WHERE reported = ?1")?;
+        Ok(stmt.query_row(params![pubkey], |row| row.get::<_, i64>(0))? as usize)
+    }
+
+    /// `(pubkey, distinct reporters)` for every reported pubkey
+    pub fn report_counts(&self) -> Result<Vec<(String, usize)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached("SELECT reported, COUNT(*) FROM reports GROUP BY reported")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Tags of a watched author's stored list of `kind`, if one was seen
+    pub fn author_list(&self, pubkey: &str, kind: u16) -> Result<Option<Vec<Vec<String>>>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached("SELECT tags FROM author_lists WHERE pubkey = ?1 AND kind = ?2")?;
+        let mut rows = stmt.query(params![pubkey, kind])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(serde_json::from_str(&row.get::<_, String>(0)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn user_settings(&self, pubkey: &str) -> Result<Option<UserSettings>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT root_pubkey, max_hops, exclude_muted, scoring FROM user_settings WHERE pubkey = ?1",
+        )?;
+        let mut rows = stmt.query(params![pubkey])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(UserSettings {
+                root_pubkey: row.get(0)?,
+                max_hops: row.get(1)?,
+                exclude_muted: row.get(2)?,
+                scoring: row.get(3)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Users whose mute lists their settings depend on, so they stay watched across restarts
+    pub fn users_excluding_muted(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT pubkey FROM user_settings WHERE exclude_muted = 1")?;
+        let pubkeys = stmt.query_map([], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        Ok(pubkeys)
+    }
+
+    /// Store a user's settings, replacing any previous ones
+    pub fn set_user_settings(&self, pubkey: &str, settings: &UserSettings) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO user_settings (pubkey, root_pubkey, max_hops, exclude_muted, scoring, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            params![
+                pubkey,
+                settings.root_pubkey,
+                settings.max_hops,
+                settings.exclude_muted,
+                settings.scoring,
+                chrono::Utc::now().timestamp(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Replace the saved ingestion dedup cache (pubkey bytes -> newest contact list created_at)
+    pub fn save_seen_events(&self, entries: &[([u8; 32], u64)]) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM seen_events", [])?;
+        {
+            let mut stmt = tx.prepare_cached("INSERT INTO seen_events (pubkey, created_at) VALUES (?1, ?2)")?;
+            for (pubkey, created_at) in entries {
+                stmt.execute(params![&pubkey[..], *created_at as i64])?;
+            }
+        }
+        tx.commit()?;
+        Ok(entries.len())
+    }
+
+    /// The newest `limit` saved dedup cache entries, oldest first
+    pub fn load_seen_events(&self, limit: usize) -> Result<Vec<([u8; 32], u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT pubkey, created_at FROM seen_events ORDER BY created_at DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, i64>(1)?))
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (pubkey, created_at) = row?;
+            if let Ok(pubkey) = <[u8; 32]>::try_from(pubkey.as_slice()) {
+                entries.push((pubkey, created_at as u64));
+            }
+        }
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// Add pubkeys to the blocklist and delete their stored node, edges in both directions and
+    /// author lists. Returns how many were newly blocked.
+    pub fn block_pubkeys(&self, pubkeys: &[String], reason: Option<&str>) -> Result<usize> {
+        let now = chrono::Utc::now().timestamp();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut added = 0;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR IGNORE INTO blocked_pubkeys (pubkey, reason, blocked_at) VALUES (?1, ?2, ?3)",
+            )?;
+            let mut purge_edges = tx.prepare_cached(
+                r#"
+                DELETE FROM edges WHERE follower_id = (SELECT id FROM nodes WHERE pubkey = ?1)
+                    OR followed_id = (SELECT id FROM nodes WHERE pubkey = ?1)
+                "#,
+            )?;
+            let mut purge_node = tx.prepare_cached("DELETE FROM nodes WHERE pubkey = ?1")?;
+            let mut purge_lists = tx.prepare_cached("DELETE FROM author_lists WHERE pubkey = ?1")?;
+            for pubkey in pubkeys {
+                added += insert.execute(params![pubkey, reason, now])?;
+                purge_edges.execute(params![pubkey])?;
+                purge_node.execute(params![pubkey])?;
+                purge_lists.execute(params![pubkey])?;
+            }
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Returns how many were blocked
+    pub fn unblock_pubkeys(&self, pubkeys: &[String]) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached("DELETE FROM blocked_pubkeys WHERE pubkey = ?1")?;
+        let mut removed = 0;
+        for pubkey in pubkeys {
+            removed += stmt.execute(params![pubkey])?;
+        }
+        Ok(removed)
+    }
+
+    /// The whole blocklist, most recently blocked first
+    pub fn blocked_pubkeys(&self) -> Result<Vec<BlockedPubkey>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT pubkey, reason, blocked_at FROM blocked_pubkeys ORDER BY blocked_at DESC, pubkey",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(BlockedPubkey {
+                pubkey: row.get(0)?,
+                reason: row.get(1)?,
+                blocked_at: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Contact lists written after the `(updated_at, id)` position `after` and before `before`,
+    /// in write order, each with its position. Nodes only ever followed are not included.
+    pub fn lists_changed_since(
+        &self,
+        after: (i64, i64),
+        before: i64,
+        limit: usize,
+    ) -> Result<Vec<((i64, i64), ReplicatedList)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut node_stmt = conn.prepare_cached(
+            r#"
+            SELECT id, updated_at, pubkey, kind3_event_id, kind3_created_at FROM nodes
+            WHERE kind3_event_id IS NOT NULL
+                AND (updated_at > ?1 OR (updated_at = ?1 AND id > ?2))
+                AND updated_at < ?3
+            ORDER BY updated_at, id
+            LIMIT ?4
+            "#,
+        )?;
+        let mut follows_stmt = conn.prepare_cached(
+            "SELECT n.pubkey FROM edges e JOIN nodes n ON e.followed_id = n.id WHERE e.follower_id = ?1",
+        )?;
+
+        let mut lists: Vec<((i64, i64), ReplicatedList)> = node_stmt
+            .query_map(params![after.0, after.1, before, limit as i64], |row| {
+                let list = ReplicatedList {
+                    pubkey: row.get(2)?,
+                    follows: Vec::new(),
+                    event_id: row.get(3)?,
+                    created_at: row.get(4)?,
+                };
+                Ok(((row.get(1)?, row.get(0)?), list))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        for ((_, id), list) in &mut lists {
+            list.follows = follows_stmt
+                .query_map(params![*id], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+        }
+        Ok(lists)
+    }
+
+    /// Pubkeys blocked at or after `since` and before `before`
+    pub fn blocked_since(&self, since: i64, before: i64) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT pubkey FROM blocked_pubkeys WHERE blocked_at >= ?1 AND blocked_at < ?2 ORDER BY pubkey",
+        )?;
+        let rows = stmt.query_map(params![since, before], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Write a consistent copy of the database to `path`, which must not exist yet.
+    /// Readers and writers wait while it is written.
+    pub fn snapshot_to(&self, path: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("VACUUM INTO ?1", params![path])?;
+        Ok(())
+    }
+
+    /// Check the invariants the graph loader relies on, without changing anything
+    pub fn check_integrity(&self) -> Result<IntegrityReport> {
+        let conn = self.conn.lock().unwrap();
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, usize>(0));
+        Ok(IntegrityReport {
+            quick_check: conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?,
+            nodes: count("SELECT COUNT(*) FROM nodes")?,
+            edges: count("SELECT COUNT(*) FROM edges")?,
+            orphan_edges: count(
+                "SELECT COUNT(*) FROM edges
+                 WHERE NOT EXISTS (SELECT 1 FROM nodes WHERE id = edges.follower_id)
+                    OR NOT EXISTS (SELECT 1 FROM nodes WHERE id = edges.followed_id)",
+            )?,
+            duplicate_pubkeys: count(
+                "SELECT COUNT(*) FROM (SELECT 1 FROM nodes GROUP BY lower(pubkey) HAVING COUNT(*) > 1)",
+            )?,
+            null_updated_at: count("SELECT COUNT(*) FROM nodes WHERE updated_at IS NULL")?,
+        })
+    }
+
+    /// Delete orphan edges and stamp nodes missing `updated_at` with their `first_seen` (or now),
+    /// in one transaction. Duplicate pubkeys are left for the operator. Returns the rows changed.
+    pub fn repair_integrity(&self) -> Result<usize> {
+        let now = chrono::Utc::now().timestamp();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut changed = tx.execute(
+            "DELETE FROM edges
+             WHERE NOT EXISTS (SELECT 1 FROM nodes WHERE id = edges.follower_id)
+                OR NOT EXISTS (SELECT 1 FROM nodes WHERE id = edges.followed_id)",
+            [],
+        )?;
+        changed += tx.execute(
+            "UPDATE nodes SET updated_at = COALESCE(first_seen, ?1) WHERE updated_at IS NULL",
+            params![now],
+        )?;
+        tx.commit()?;
+        Ok(changed)
+    }
+
     #[allow(dead_code)] // Public API for database statistics
     pub fn get_stats(&self) -> Result<(usize, usize)> {
         let conn = self.conn.lock().unwrap();
@@ -447,11 +1167,86 @@ impl Database {
     }
 }
 
+/// `(followed_id, added)` for each ID in only one of two sorted, deduplicated lists
+fn edge_diff(old: &[i64], new: &[i64]) -> Vec<(i64, bool)> {
+    let (mut i, mut j) = (0, 0);
+    let mut changes = Vec::new();
+    while i < old.len() || j < new.len() {
+        match (old.get(i), new.get(j)) {
+            (Some(a), Some(b)) if a == b => (i, j) = (i + 1, j + 1),
+            (Some(&a), Some(&b)) if a < b => {
+                changes.push((a, false));
+                i += 1;
+            }
+            (Some(&a), None) => {
+                changes.push((a, false));
+                i += 1;
+            }
+            (_, Some(&b)) => {
+                changes.push((b, true));
+                j += 1;
+            }
+            (None, None) => break,
+        }
+    }
+    changes
+}
+
+/// Apply the stored lists of the followers in `range` (consecutive entries of `nodes`, which is
+/// sorted by ID) to `graph`, returning the number of edges read
+fn load_edges(conn: &Connection, graph: &WotGraph, nodes: &[StoredNode], range: &[StoredNode]) -> Result<usize> {
+    let (Some(first), Some(last)) = (range.first(), range.last()) else {
+        return Ok(0);
+    };
+    let node = |id: i64| nodes.binary_search_by_key(&id, |node| node.id).ok().map(|i| &nodes[i]);
+    let apply = |follower: i64, follows: &[String]| {
+        if let Some(node) = node(follower) {
+            graph.update_follows(&node.pubkey, follows, node.event_id.clone(), node.created_at);
+        }
+    };
+
+    // The primary key index serves the range already ordered by follower
+    let mut stmt = conn.prepare(
+        "SELECT follower_id, followed_id FROM edges WHERE follower_id BETWEEN ?1 AND ?2 ORDER BY follower_id"
+    )?;
+    let mut rows = stmt.query(params![first.id, last.id])?;
+    let (mut current, mut follows, mut edge_count) = (None, Vec::new(), 0);
+    while let Some(row) = rows.next()? {
+        let (follower, followed): (i64, i64) = (row.get(0)?, row.get(1)?);
+        if current != Some(follower) {
+            if let Some(previous) = current.replace(follower) {
+                apply(previous, &follows);
+                follows.clear();
+            }
+        }
+        if let Some(followed) = node(followed) {
+            follows.push(followed.pubkey.clone());
+            edge_count += 1;
+        }
+    }
+    if let Some(previous) = current {
+        apply(previous, &follows);
+    }
+    Ok(edge_count)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
     #[test]
     fn test_database_creation() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -495,8 +1290,10 @@ mod tests {
         let temp_file = NamedTempFile::new().unwrap();
         let db = Database::open(temp_file.path()).unwrap();
 
-        db.update_follows("alice", &["bob".to_string()], None, None).unwrap();
-        db.update_follows("bob", &["carol".to_string()], None, None).unwrap();
+        db.update_follows(&hex("alice"), &[hex("bob")], None, None).unwrap();
+        db.update_follows(&hex("bob"), &[hex("carol")], None, None).unwrap();
+        // Rows that aren't hex pubkeys don't become nodes
+        db.update_follows("dave", &[hex("alice")], None, None).unwrap();
 
         let graph = WotGraph::new();
         db.load_graph(&graph).unwrap();
@@ -506,6 +1303,44 @@ mod tests {
         assert_eq!(stats.edge_count, 2);
     }
 
+    #[test]
+    fn test_load_graph_in_parallel() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        let memory = Database::open(":memory:").unwrap();
+
+        // Enough followers to give every worker a range
+        let pk = |i: usize| format!("{:064x}", i);
+        let lists: Vec<(String, Vec<String>)> = (0..200)
+            .map(|i| (pk(i), (1..=i % 7).map(|step| pk((i + step * 13) % 230)).collect()))
+            .collect();
+        let updates: Vec<FollowUpdateBatch> = lists
+            .iter()
+            .enumerate()
+            .map(|(i, (pubkey, follows))| FollowUpdateBatch { pubkey, follows, event_id: None, created_at: Some(i as i64) })
+            .collect();
+        db.update_follows_batch(&updates).unwrap();
+        memory.update_follows_batch(&updates).unwrap();
+
+        for db in [&db, &memory] {
+            let graph = WotGraph::new();
+            db.load_graph(&graph).unwrap();
+            assert_eq!(graph.stats().edge_count, db.get_stats().unwrap().1);
+            for (i, (pubkey, follows)) in lists.iter().enumerate() {
+                let pubkey: Pubkey = pubkey.parse().unwrap();
+                let mut loaded: Vec<String> = graph.get_follows(&pubkey).unwrap().into_iter().map(String::from).collect();
+                let mut expected = follows.clone();
+                loaded.sort();
+                expected.sort();
+                expected.dedup();
+                assert_eq!(loaded, expected);
+                if !follows.is_empty() {
+                    assert_eq!(graph.get_node_info(&pubkey).unwrap().kind3_created_at, Some(i as i64));
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_sync_state() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -519,6 +1354,11 @@ mod tests {
         let state = db.get_sync_state("wss://relay.test").unwrap().unwrap();
         assert_eq!(state.relay_url, "wss://relay.test");
         assert_eq!(state.last_event_time, Some(1000));
+
+        db.set_sync_state("wss://a.test", None).unwrap();
+        let states = db.sync_states().unwrap();
+        assert_eq!(states.len(), 2);
+        assert_eq!((states[0].relay_url.as_str(), states[0].last_event_time), ("wss://a.test", None));
     }
 
     #[test]
@@ -551,4 +1391,279 @@ mod tests {
         assert_eq!(nodes, 5); // alice, bob, carol, dave, eve
         assert_eq!(edges, 3); // alice->bob, alice->carol, dave->eve
     }
+
+    #[test]
+    fn test_edge_changes() {
+        let db = Database::open(":memory:").unwrap();
+        let follows = |pks: &[&str]| pks.iter().map(|pk| pk.to_string()).collect::<Vec<_>>();
+
+        // The first list is backfill
+        db.update_follows("alice", &follows(&["bob", "carol"]), Some("e1"), Some(100)).unwrap();
+        assert_eq!(db.edge_changes("alice", 0, 1000).unwrap(), (vec![], vec![]));
+
+        db.update_follows("alice", &follows(&["carol", "dave"]), Some("e2"), Some(200)).unwrap();
+        db.update_follows("alice", &follows(&["carol", "dave", "eve"]), Some("e3"), Some(300)).unwrap();
+        db.update_follows("alice", &follows(&["carol", "dave"]), Some("e4"), Some(400)).unwrap();
+        db.update_follows("alice", &[], Some("e5"), Some(90_000)).unwrap();
+
+        // eve was added and removed again within the window
+        assert_eq!(
+            db.edge_changes("alice", 100, 400).unwrap(),
+            (vec!["dave".to_string()], vec!["bob".to_string()])
+        );
+        assert_eq!(db.edge_changes("alice", 250, 350).unwrap(), (vec!["eve".to_string()], vec![]));
+        assert_eq!(
+            db.edge_changes("alice", 400, 100_000).unwrap(),
+            (vec![], vec!["carol".to_string(), "dave".to_string()])
+        );
+
+        assert_eq!(db.daily_edge_changes(0, 100_000).unwrap(), vec![(0, 2, 2), (86_400, 0, 2)]);
+        assert_eq!(db.prune_edge_changes(86_400).unwrap(), 4);
+        assert_eq!(db.daily_edge_changes(0, 100_000).unwrap(), vec![(86_400, 0, 2)]);
+    }
+
+    #[test]
+    fn test_outbox() {
+        let db = Database::open(":memory:").unwrap();
+        let follows = |pks: &[&str]| pks.iter().map(|pk| pk.to_string()).collect::<Vec<_>>();
+        assert_eq!(db.outbox_oldest_seq().unwrap(), None);
+
+        // First lists are recorded in full; a republished identical list isn't recorded again
+        db.update_follows("alice", &follows(&["bob", "carol"]), Some("e1"), Some(100)).unwrap();
+        db.update_follows("bob", &[], Some("e2"), Some(100)).unwrap();
+        db.update_follows("alice", &follows(&["carol", "bob"]), Some("e3"), Some(200)).unwrap();
+        db.update_follows("alice", &follows(&["carol", "dave"]), Some("e4"), Some(300)).unwrap();
+
+        let entries = db.outbox_since(0, 10).unwrap();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|e| (e.seq, e.pubkey.as_str(), e.event_id.as_deref(), e.added.clone(), e.removed.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "alice", Some("e1"), follows(&["bob", "carol"]), vec![]),
+                (2, "bob", Some("e2"), vec![], vec![]),
+                (3, "alice", Some("e4"), follows(&["dave"]), follows(&["bob"])),
+            ]
+        );
+        assert_eq!(db.outbox_since(1, 1).unwrap()[0].seq, 2);
+        assert!(db.outbox_since(3, 10).unwrap().is_empty());
+
+        // Sequences aren't reused once everything is pruned
+        assert_eq!(db.outbox_oldest_seq().unwrap(), Some(1));
+        assert_eq!(db.prune_outbox(i64::MAX).unwrap(), 3);
+        assert_eq!(db.outbox_oldest_seq().unwrap(), Some(4));
+        db.update_follows("carol", &follows(&["alice"]), Some("e5"), Some(400)).unwrap();
+        assert_eq!(db.outbox_since(0, 10).unwrap()[0].seq, 4);
+    }
+
+    #[test]
+    fn test_node_timestamps() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+
+        assert!(db.node_timestamps("alice").unwrap().is_none());
+
+        db.update_follows("alice", &["bob".to_string()], None, None).unwrap();
+        let (first_seen, updated_at) = db.node_timestamps("bob").unwrap().unwrap();
+        assert_eq!(first_seen, Some(updated_at));
+
+        // Only authors of a contact list
+        db.update_follows("carol", &["bob".to_string()], Some("e1"), Some(100)).unwrap();
+        let authors = db.author_first_seen().unwrap();
+        assert_eq!(authors.len(), 1);
+        assert_eq!(authors[0].0, "carol");
+
+        // Three nodes first seen today, none tomorrow; only carol's list has a created_at
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(db.daily_new_nodes(now - 86_400, now + 86_400).unwrap().iter().map(|d| d.1).sum::<u64>(), 3);
+        assert!(db.daily_new_nodes(now + 1, now + 86_400).unwrap().is_empty());
+        assert_eq!(db.latest_list_at().unwrap(), Some(100));
+    }
+
+    #[test]
+    fn test_first_seen_migration() {
+        let temp_file = NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(temp_file.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE nodes (id INTEGER PRIMARY KEY, pubkey TEXT NOT NULL UNIQUE,
+                                     kind3_event_id TEXT, kind3_created_at INTEGER, updated_at INTEGER NOT NULL);
+                 INSERT INTO nodes (pubkey, updated_at) VALUES ('alice', 100);",
+            )
+            .unwrap();
+        }
+
+        let db = Database::open(temp_file.path()).unwrap();
+        assert_eq!(db.node_timestamps("alice").unwrap(), Some((None, 100)));
+    }
+
+    #[test]
+    fn test_follower_history() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+
+        db.record_follower_counts(100, &[(pk("alice"), 5), (pk("bob"), 1)]).unwrap();
+        db.record_follower_counts(200, &[(pk("alice"), 7)]).unwrap();
+
+        assert_eq!(db.follower_history(&hex("alice")).unwrap(), vec![(100, 5), (200, 7)]);
+        assert_eq!(db.prune_follower_history(150).unwrap(), 2);
+        assert_eq!(db.follower_history(&hex("alice")).unwrap(), vec![(200, 7)]);
+        assert!(db.follower_history(&hex("bob")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_blocked_pubkeys() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        db.update_follows(&hex("alice"), &[hex("bob"), hex("mallory")], None, None).unwrap();
+        db.update_follows(&hex("mallory"), &[hex("bob")], None, None).unwrap();
+
+        let blocked = vec![hex("mallory")];
+        assert_eq!(db.block_pubkeys(&blocked, Some("abuse")).unwrap(), 1);
+        assert_eq!(db.block_pubkeys(&blocked, None).unwrap(), 0);
+        assert_eq!(db.get_stats().unwrap(), (2, 1)); // alice->bob remains
+        assert_eq!(db.blocked_pubkeys().unwrap()[0].reason.as_deref(), Some("abuse"));
+
+        // Edges persisted afterwards are stripped on load
+        db.update_follows(&hex("carol"), &[hex("mallory")], None, None).unwrap();
+        let graph = WotGraph::new();
+        graph.block(&blocked);
+        db.load_graph(&graph).unwrap();
+        assert!(graph.node_id(&pk("mallory")).is_none());
+        assert!(graph.get_follows(&pk("carol")).unwrap().is_empty());
+
+        assert_eq!(db.unblock_pubkeys(&blocked).unwrap(), 1);
+        assert!(db.blocked_pubkeys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lists_changed_since() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        db.update_follows("alice", &["bob".to_string()], Some("e1"), Some(100)).unwrap();
+        db.update_follows("carol", &[], Some("e2"), Some(100)).unwrap();
+        db.update_follows("dave", &["alice".to_string(), "bob".to_string()], Some("e3"), Some(100)).unwrap();
+        let before = chrono::Utc::now().timestamp() + 1;
+
+        // bob only appears as a follow
+        let first = db.lists_changed_since((0, 0), before, 2).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].1.pubkey, "alice");
+        assert_eq!(first[0].1.follows, vec!["bob".to_string()]);
+        assert_eq!(first[1].1.event_id.as_deref(), Some("e2"));
+
+        let rest = db.lists_changed_since(first[1].0, before, 2).unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].1.follows.len(), 2);
+        assert!(db.lists_changed_since(rest[0].0, before, 2).unwrap().is_empty());
+        assert!(db.lists_changed_since((0, 0), 0, 10).unwrap().is_empty());
+
+        db.block_pubkeys(&["mallory".to_string()], None).unwrap();
+        assert_eq!(db.blocked_since(0, before).unwrap(), vec!["mallory".to_string()]);
+        assert!(db.blocked_since(before, before + 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_to() {
+        let db = Database::open(":memory:").unwrap();
+        db.update_follows("alice", &["bob".to_string()], Some("e1"), Some(100)).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.db").to_string_lossy().into_owned();
+        db.snapshot_to(&path).unwrap();
+        assert!(db.snapshot_to(&path).is_err());
+
+        let copy = Database::open(&path).unwrap();
+        assert_eq!(copy.get_stats().unwrap(), (2, 1));
+    }
+
+    #[test]
+    fn test_record_author_list() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+
+        let tags = vec![vec!["r".to_string(), "wss://relay.example".to_string()]];
+        assert!(db.record_author_list("alice", 10002, "e1", 100, &tags).unwrap());
+        assert!(db.record_author_list("alice", 10000, "e2", 100, &[]).unwrap());
+        // Older or same-age lists don't replace the stored one
+        assert!(!db.record_author_list("alice", 10002, "e0", 90, &[]).unwrap());
+        assert!(!db.record_author_list("alice", 10002, "e1", 100, &tags).unwrap());
+        assert!(db.record_author_list("alice", 10002, "e3", 110, &[]).unwrap());
+        assert_eq!(db.author_list("alice", 10002).unwrap(), Some(Vec::new()));
+        assert_eq!(db.author_list("alice", 3).unwrap(), None);
+    }
+
+    #[test]
+    fn test_reports() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+
+        let spam = |pk: &str| (pk.to_string(), Some("spam".to_string()));
+        assert_eq!(db.record_report("alice", "e1", 100, &[spam("mallory"), spam("eve")]).unwrap(), 2);
+        assert_eq!(db.record_report("bob", "e2", 100, &[("mallory".to_string(), None)]).unwrap(), 1);
+        // A reporter counts once per pubkey, and older reports don't replace newer ones
+        assert_eq!(db.record_report("alice", "e0", 90, &[spam("mallory")]).unwrap(), 0);
+        assert_eq!(db.record_report("alice", "e3", 110, &[spam("mallory")]).unwrap(), 1);
+        assert_eq!(db.report_count("mallory").unwrap(), 2);
+        assert_eq!(db.report_count("eve").unwrap(), 1);
+        assert_eq!(db.report_count("alice").unwrap(), 0);
+
+        let mut counts = db.report_counts().unwrap();
+        counts.sort();
+        assert_eq!(counts, vec![("eve".to_string(), 1), ("mallory".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_user_settings() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+
+        assert_eq!(db.user_settings("alice").unwrap(), None);
+        let mut settings = UserSettings {
+            root_pubkey: Some("bob".to_string()),
+            max_hops: Some(2),
+            exclude_muted: true,
+            scoring: Some("followerCount".to_string()),
+        };
+        db.set_user_settings("alice", &settings).unwrap();
+        assert_eq!(db.user_settings("alice").unwrap(), Some(settings.clone()));
+        assert_eq!(db.users_excluding_muted().unwrap(), vec!["alice".to_string()]);
+
+        settings = UserSettings::default();
+        db.set_user_settings("alice", &settings).unwrap();
+        assert_eq!(db.user_settings("alice").unwrap(), Some(settings));
+        assert!(db.users_excluding_muted().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_follow_anomalies() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+
+        let mut anomaly = FollowAnomaly {
+            id: 0,
+            pubkey: "alice".to_string(),
+            event_id: "e1".to_string(),
+            event_created_at: 100,
+            detected_at: 200,
+            previous_count: 500,
+            new_count: 0,
+            added: 0,
+            removed: 500,
+            action: "held".to_string(),
+        };
+        let first = db.record_anomaly(&anomaly).unwrap();
+        anomaly.event_id = "e2".to_string();
+        anomaly.detected_at = 300;
+        db.record_anomaly(&anomaly).unwrap();
+        db.set_anomaly_action(first, "superseded").unwrap();
+
+        let recent = db.recent_anomalies(10).unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].event_id, "e2");
+        assert_eq!(recent[1].action, "superseded");
+        assert_eq!(db.recent_anomalies(1).unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,358 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Instant;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::api::dvm::DvmStatsSnapshot;
+use crate::api::http::AppState;
+use crate::cache::{CacheStats, QueryCache};
+use crate::graph::{LockMetricsSnapshot, NodeId, WotGraph};
+
+/// HTTP requests remembered for the slowest-requests list
+const RECENT_REQUESTS: usize = 1024;
+const SLOWEST_REQUESTS: usize = 20;
+const TOP_NODES: usize = 10;
+
+/// State gathered for the diagnostic dump (`GET /admin/debug`, `SIGUSR1`) that nothing else
+/// keeps: recent HTTP request timings and ingestion relay connectivity
+pub struct Diagnostics {
+    started: Instant,
+    requests: Mutex<VecDeque<RequestTiming>>,
+    relays: RwLock<BTreeMap<String, RelayState>>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RequestTiming {
+    pub method: String,
+    /// Path and query string
+    pub uri: String,
+    pub status: u16,
+    pub duration_ms: f64,
+    pub at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RelayState {
+    pub url: String,
+    pub connected: bool,
+    /// When the current disconnection started; absent while connected
+    pub down_since: Option<i64>,
+    pub checked_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NodeDegree {
+    pub pubkey: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GraphDiagnostics {
+    pub name: String,
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub nodes_with_follows: usize,
+    pub quarantined_nodes: usize,
+    pub rejected_lists: u64,
+    pub oversized_lists: u64,
+    pub truncated_lists: u64,
+    pub duplicate_follows: u64,
+    pub blocked_pubkeys: usize,
+    pub epoch: u64,
+    pub cache: CacheStats,
+    pub locks: LockMetricsSnapshot,
+    /// Most followed pubkeys, by follower count
+    pub most_followed: Vec<NodeDegree>,
+    /// Pubkeys following the most others, by follow count
+    pub most_following: Vec<NodeDegree>,
+}
+
+/// Tokio runtime figures. The blocking pool fields need a build with `--cfg tokio_unstable`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RuntimeStats {
+    pub workers: usize,
+    pub alive_tasks: usize,
+    pub global_queue_depth: usize,
+    /// Share of worker time spent busy since startup (0-1)
+    pub worker_busy_ratio: f64,
+    pub blocking_threads: Option<usize>,
+    pub idle_blocking_threads: Option<usize>,
+    pub blocking_queue_depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DiagnosticDump {
+    pub generated_at: i64,
+    pub uptime_secs: u64,
+    /// The primary graph first, then any named graphs
+    pub graphs: Vec<GraphDiagnostics>,
+    pub runtime: RuntimeStats,
+    /// Ingestion relays as of their last health check (every 30 seconds)
+    pub relays: Vec<RelayState>,
+    /// DVM relay status and request counters; absent unless `DVM_ENABLED`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dvm: Option<DvmStatsSnapshot>,
+    /// Slowest of the last 1024 HTTP requests, slowest first
+    pub slowest_requests: Vec<RequestTiming>,
+}
+
+impl Diagnostics {
+    pub fn record_request(&self, timing: RequestTiming) {
+        let mut requests = self.requests.lock();
+        if requests.len() == RECENT_REQUESTS {
+            requests.pop_front();
+        }
+        requests.push_back(timing);
+    }
+
+    /// The `n` slowest recent requests, slowest first
+    pub fn slowest_requests(&self, n: usize) -> Vec<RequestTiming> {
+        let mut requests: Vec<RequestTiming> = self.requests.lock().iter().cloned().collect();
+        requests.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+        requests.truncate(n);
+        requests
+    }
+
+    pub fn set_relay(&self, url: &str, connected: bool, now: i64) {
+        let mut relays = self.relays.write();
+        let down_since = match relays.get(url) {
+            _ if connected => None,
+            Some(RelayState { down_since: Some(since), .. }) => Some(*since),
+            _ => Some(now),
+        };
+        relays.insert(
+            url.to_string(),
+            RelayState { url: url.to_string(), connected, down_since, checked_at: now },
+        );
+    }
+
+    pub fn relays(&self) -> Vec<RelayState> {
+        self.relays.read().values().cloned().collect()
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: Mutex::new(VecDeque::with_capacity(RECENT_REQUESTS)),
+            relays: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+/// Time every HTTP request for the slowest-requests list
+pub async fn track_requests(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let uri = req.uri().to_string();
+    let start = Instant::now();
+
+    let response = next.run(req).await;
+
+    state.diagnostics.record_request(RequestTiming {
+        method,
+        uri,
+        status: response.status().as_u16(),
+        duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+        at: chrono::Utc::now().timestamp(),
+    });
+    response
+}
+
+/// Pubkeys with the longest lists, longest first
+pub(crate) fn top_by_len(graph: &WotGraph, followers: bool, n: usize) -> Vec<NodeDegree> {
+    let top: Vec<(NodeId, usize)> = graph.with_adjacency(|follows, follower_lists| {
+        let lists = if followers { follower_lists } else { follows };
+        let mut counts: Vec<(NodeId, usize)> = lists
+            .iter()
+            .enumerate()
+            .filter(|(_, list)| !list.is_empty())
+            .map(|(id, list)| (id as NodeId, list.len()))
+            .collect();
+        if counts.len() > n {
+            counts.select_nth_unstable_by(n, |a, b| b.1.cmp(&a.1));
+            counts.truncate(n);
+        }
+        counts.sort_unstable_by_key(|&(_, count)| std::cmp::Reverse(count));
+        counts
+    });
+
+    let ids: Vec<NodeId> = top.iter().map(|&(id, _)| id).collect();
+    graph
+        .resolve_pubkeys(&ids)
+        .into_iter()
+        .zip(top)
+        .map(|(pubkey, (_, count))| NodeDegree { pubkey: pubkey.to_hex(), count })
+        .collect()
+}
+
+fn runtime_stats(uptime_secs: f64) -> RuntimeStats {
+    let metrics = tokio::runtime::Handle::current().metrics();
+    let workers = metrics.num_workers();
+    let busy_secs: f64 = (0..workers)
+        .map(|worker| metrics.worker_total_busy_duration(worker).as_secs_f64())
+        .sum();
+    let capacity = uptime_secs * workers as f64;
+
+    #[cfg(tokio_unstable)]
+    let (blocking_threads, idle_blocking_threads, blocking_queue_depth) = (
+        Some(metrics.num_blocking_threads()),
+        Some(metrics.num_idle_blocking_threads()),
+        Some(metrics.blocking_queue_depth()),
+    );
+    #[cfg(not(tokio_unstable))]
+    let (blocking_threads, idle_blocking_threads, blocking_queue_depth) = (None, None, None);
+
+    RuntimeStats {
+        workers,
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+        worker_busy_ratio: if capacity > 0.0 { (busy_secs / capacity).min(1.0) } else { 0.0 },
+        blocking_threads,
+        idle_blocking_threads,
+        blocking_queue_depth,
+    }
+}
+
+fn graph_diagnostics(name: &str, graph: &WotGraph, cache: &QueryCache) -> GraphDiagnostics {
+    let stats = graph.stats();
+    GraphDiagnostics {
+        name: name.to_string(),
+        node_count: stats.node_count,
+        edge_count: stats.edge_count,
+        nodes_with_follows: stats.nodes_with_follows,
+        quarantined_nodes: stats.quarantined_nodes,
+        rejected_lists: stats.rejected_lists,
+        oversized_lists: stats.oversized_lists,
+        truncated_lists: stats.truncated_lists,
+        duplicate_follows: stats.duplicate_follows,
+        blocked_pubkeys: graph.blocked_count(),
+        epoch: graph.epoch(),
+        cache: cache.stats(),
+        locks: graph.lock_metrics(),
+        most_followed: top_by_len(graph, true, TOP_NODES),
+        most_following: top_by_len(graph, false, TOP_NODES),
+    }
+}
+
+/// Collect the diagnostic dump. Walks every adjacency list, so it costs about as much as a
+/// `/stats` call per graph.
+pub fn dump(state: &AppState) -> DiagnosticDump {
+    let uptime = state.diagnostics.started.elapsed();
+    let primary = state.namespaces.names()[0];
+    let graphs = std::iter::once(graph_diagnostics(primary, &state.graph, &state.cache))
+        .chain(
+            state
+                .namespaces
+                .extra()
+                .map(|ns| graph_diagnostics(&ns.name, &ns.graph, &ns.cache)),
+        )
+        .collect();
+
+    DiagnosticDump {
+        generated_at: chrono::Utc::now().timestamp(),
+        uptime_secs: uptime.as_secs(),
+        graphs,
+        runtime: runtime_stats(uptime.as_secs_f64()),
+        relays: state.diagnostics.relays(),
+        dvm: state.config.dvm_enabled.then(|| state.dvm_stats.snapshot()),
+        slowest_requests: state.diagnostics.slowest_requests(SLOWEST_REQUESTS),
+    }
+}
+
+/// Log the diagnostic dump as JSON whenever the process receives `SIGUSR1`
+#[cfg(unix)]
+pub fn spawn_signal_dump(state: AppState) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = match signal(SignalKind::user_defined1()) {
+        Ok(signals) => signals,
+        Err(e) => {
+            error!("Failed to install SIGUSR1 handler: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            let state = state.clone();
+            match tokio::task::spawn_blocking(move || serde_json::to_string(&dump(&state))).await {
+                Ok(Ok(json)) => info!("Diagnostic dump: {}", json),
+                Ok(Err(e)) => error!("Failed to serialize diagnostic dump: {}", e),
+                Err(e) => error!("Diagnostic dump task failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn spawn_signal_dump(_state: AppState) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    fn timing(uri: &str, duration_ms: f64) -> RequestTiming {
+        RequestTiming {
+            method: "GET".to_string(),
+            uri: uri.to_string(),
+            status: 200,
+            duration_ms,
+            at: 0,
+        }
+    }
+
+    #[test]
+    fn test_slowest_requests_and_relays() {
+        let diagnostics = Diagnostics::default();
+        for i in 0..RECENT_REQUESTS + 1 {
+            diagnostics.record_request(timing(&format!("/distance?i={}", i), i as f64));
+        }
+        // The oldest (fastest) request was evicted
+        let slowest = diagnostics.slowest_requests(2);
+        assert_eq!(slowest[0].duration_ms, RECENT_REQUESTS as f64);
+        assert_eq!(slowest[1].duration_ms, RECENT_REQUESTS as f64 - 1.0);
+        assert_eq!(diagnostics.requests.lock().len(), RECENT_REQUESTS);
+
+        diagnostics.set_relay("wss://a", false, 100);
+        diagnostics.set_relay("wss://a", false, 130);
+        diagnostics.set_relay("wss://b", true, 130);
+        let relays = diagnostics.relays();
+        assert_eq!(relays[0].down_since, Some(100));
+        assert_eq!(relays[0].checked_at, 130);
+        assert!(relays[1].connected && relays[1].down_since.is_none());
+    }
+
+    #[test]
+    fn test_top_by_len() {
+        let graph = WotGraph::new();
+        graph.update_follows(&hex("alice"), &[hex("carol")], None, Some(1));
+        graph.update_follows(&hex("bob"), &[hex("carol"), hex("alice")], None, Some(1));
+        graph.update_follows(&hex("dave"), &[hex("carol")], None, Some(1));
+
+        let followed = top_by_len(&graph, true, 1);
+        assert_eq!(followed[0].pubkey, hex("carol"));
+        assert_eq!(followed[0].count, 3);
+        assert_eq!(top_by_len(&graph, false, 10)[0].pubkey, hex("bob"));
+        assert_eq!(top_by_len(&graph, false, 10).len(), 3);
+    }
+}
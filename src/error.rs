@@ -0,0 +1,129 @@
+//! Failures the oracle reports to clients. Each has a stable machine-readable code, sent as the
+//! `code` of HTTP error bodies and the fourth element of DVM `status` tags, and an HTTP status,
+//! so clients branch on the code rather than the message.
+
+use axum::http::StatusCode;
+
+use crate::api::identity::ResolveError;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum OracleError {
+    /// A malformed or out-of-range parameter; `code` names it (`INVALID_LIMIT`, `TOO_MANY_TARGETS`, …)
+    #[error("{message}")]
+    InvalidParam { code: &'static str, message: String },
+    #[error("Invalid pubkey format")]
+    InvalidPubkey,
+    /// An npub, nprofile or NIP-05 input that didn't resolve to a pubkey
+    #[error("{0}")]
+    UnresolvedIdentifier(String),
+    #[error("Pubkey not in graph")]
+    NodeNotFound,
+    #[error("Unknown graph")]
+    UnknownGraph,
+    #[error("Access denied")]
+    IpDenied,
+    #[error("Pubkey not available")]
+    PubkeyDenied,
+    #[error("Source pubkey not registered")]
+    SourceDenied,
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("Rate limit exceeded")]
+    RateLimited,
+    /// A follow list changed since the data answering the request was computed
+    #[error("{0}")]
+    FollowsChanged(String),
+    /// A feature the request needs is switched off; `code` names it (`NO_ANCHORS`, `NO_ORACLE_KEY`, …)
+    #[error("{message}")]
+    Disabled { code: &'static str, message: String },
+    /// A background computation the request needs hasn't finished since startup
+    #[error("{0}")]
+    GraphWarming(String),
+    /// The job costs more than the requester's bid
+    #[error("{0}")]
+    BudgetExceeded(String),
+    #[error("Payment not received before invoice expiry")]
+    PaymentExpired,
+    /// Too much work queued to take this request; retrying later may succeed
+    #[error("{0}")]
+    Overloaded(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl OracleError {
+    pub fn invalid(code: &'static str, message: impl Into<String>) -> Self {
+        Self::InvalidParam { code, message: message.into() }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::Internal(message.into())
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidParam { code, .. } | Self::Disabled { code, .. } => code,
+            Self::InvalidPubkey => "INVALID_PUBKEY",
+            Self::UnresolvedIdentifier(_) => "UNRESOLVED_IDENTIFIER",
+            Self::NodeNotFound => "NODE_NOT_FOUND",
+            Self::UnknownGraph => "UNKNOWN_GRAPH",
+            Self::IpDenied => "IP_DENIED",
+            Self::PubkeyDenied => "PUBKEY_DENIED",
+            Self::SourceDenied => "SOURCE_DENIED",
+            Self::Unauthorized => "UNAUTHORIZED",
+            Self::RateLimited => "RATE_LIMITED",
+            Self::FollowsChanged(_) => "FOLLOWS_CHANGED",
+            Self::GraphWarming(_) => "GRAPH_WARMING",
+            Self::BudgetExceeded(_) => "BUDGET_EXCEEDED",
+            Self::PaymentExpired => "PAYMENT_EXPIRED",
+            Self::Overloaded(_) => "OVERLOADED",
+            Self::Internal(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::InvalidParam { .. } | Self::InvalidPubkey | Self::UnresolvedIdentifier(_) => StatusCode::BAD_REQUEST,
+            Self::NodeNotFound | Self::UnknownGraph => StatusCode::NOT_FOUND,
+            Self::IpDenied | Self::PubkeyDenied | Self::SourceDenied => StatusCode::FORBIDDEN,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::FollowsChanged(_) => StatusCode::CONFLICT,
+            Self::BudgetExceeded(_) | Self::PaymentExpired => StatusCode::PAYMENT_REQUIRED,
+            Self::Disabled { .. } | Self::GraphWarming(_) | Self::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<ResolveError> for OracleError {
+    fn from(e: ResolveError) -> Self {
+        match e {
+            ResolveError::Invalid => Self::InvalidPubkey,
+            ResolveError::TooManyLookups => Self::invalid("TOO_MANY_NIP05", e.to_string()),
+            ResolveError::Unresolved | ResolveError::Nip05Disabled => Self::UnresolvedIdentifier(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_and_statuses() {
+        let not_found = OracleError::NodeNotFound;
+        assert_eq!((not_found.code(), not_found.status()), ("NODE_NOT_FOUND", StatusCode::NOT_FOUND));
+
+        let invalid = OracleError::invalid("INVALID_LIMIT", "limit must be between 1 and 100");
+        assert_eq!((invalid.code(), invalid.status()), ("INVALID_LIMIT", StatusCode::BAD_REQUEST));
+        assert_eq!(invalid.to_string(), "limit must be between 1 and 100");
+
+        let busy = OracleError::Overloaded("DVM is busy, try again later".to_string());
+        assert_eq!((busy.code(), busy.status()), ("OVERLOADED", StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(OracleError::internal("boom").status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        assert_eq!(OracleError::from(ResolveError::Invalid), OracleError::InvalidPubkey);
+        assert_eq!(OracleError::from(ResolveError::Unresolved).code(), "UNRESOLVED_IDENTIFIER");
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{bail, Result};
+
+use crate::db::{Database, IntegrityReport};
+use crate::graph::WotGraph;
+
+const USAGE: &str = "Usage: wot-oracle fsck [--db PATH] [--repair]";
+
+/// Options for `wot-oracle fsck`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsckOptions {
+    /// Database to check; defaults to `DB_PATH`
+    pub db: String,
+    /// Delete orphan edges and fill missing `updated_at` before reporting
+    pub repair: bool,
+}
+
+impl Default for FsckOptions {
+    fn default() -> Self {
+        Self {
+            db: std::env::var("DB_PATH").unwrap_or_else(|_| "wot.db".into()),
+            repair: false,
+        }
+    }
+}
+
+impl FsckOptions {
+    /// Parse the arguments following `fsck`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--repair" => options.repair = true,
+                "--db" => match args.next() {
+                    Some(path) => options.db = path,
+                    None => bail!("--db needs a value\n{}", USAGE),
+                },
+                "--help" | "-h" => bail!(USAGE),
+                _ => bail!("Unknown option {}\n{}", flag, USAGE),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// What `fsck` found, and repaired when asked to
+#[derive(Debug, Clone)]
+pub struct FsckReport {
+    pub db: String,
+    /// Rows changed by `--repair`
+    pub repaired: Option<usize>,
+    pub integrity: IntegrityReport,
+    /// Nodes and edges in the graph built from the database
+    pub graph_nodes: usize,
+    pub graph_edges: usize,
+}
+
+impl FsckReport {
+    /// Edges the loader should have built: every stored edge except the orphans it skips
+    pub fn expected_edges(&self) -> usize {
+        self.integrity.edges - self.integrity.orphan_edges
+    }
+
+    /// Invariant violations plus a graph that disagrees with the stored counts
+    pub fn problems(&self) -> usize {
+        let mismatch = self.graph_nodes != self.integrity.nodes || self.graph_edges != self.expected_edges();
+        self.integrity.problems() + usize::from(mismatch)
+    }
+}
+
+/// Check (and optionally repair) the database, then load it and compare the graph's counts
+pub fn run(options: &FsckOptions) -> Result<FsckReport> {
+    // Database::open would create an empty database at a mistyped path
+    if !std::path::Path::new(&options.db).exists() {
+        bail!("Database {} not found", options.db);
+    }
+    let db = Database::open(&options.db)?;
+    let repaired = match options.repair {
+        true => Some(db.repair_integrity()?),
+        false => None,
+    };
+    let integrity = db.check_integrity()?;
+
+    let graph = WotGraph::new();
+    db.load_graph(&graph)?;
+    let stats = graph.stats();
+
+    Ok(FsckReport {
+        db: options.db.clone(),
+        repaired,
+        integrity,
+        graph_nodes: stats.node_count,
+        graph_edges: stats.edge_count,
+    })
+}
+
+impl std::fmt::Display for FsckReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let integrity = &self.integrity;
+        writeln!(f, "Database: {}", self.db)?;
+        if let Some(rows) = self.repaired {
+            writeln!(f, "Repaired: {} rows", rows)?;
+        }
+        writeln!(f, "SQLite quick_check:  {}", integrity.quick_check)?;
+        writeln!(f, "Orphan edges:        {}", integrity.orphan_edges)?;
+        writeln!(f, "Duplicate pubkeys:   {}", integrity.duplicate_pubkeys)?;
+        writeln!(f, "NULL updated_at:     {}", integrity.null_updated_at)?;
+        writeln!(f, "Stored:              {} nodes, {} edges", integrity.nodes, integrity.edges)?;
+        writeln!(f, "Loaded graph:        {} nodes, {} edges", self.graph_nodes, self.graph_edges)?;
+        match self.problems() {
+            0 => write!(f, "No problems found"),
+            n => write!(f, "{} problems found", n),
+        }
+    }
+}
+
+/// Entry point for `wot-oracle fsck <options>`; fails when problems remain
+pub fn main(args: impl IntoIterator<Item = String>) -> Result<()> {
+    let options = FsckOptions::parse(args)?;
+    let report = run(&options)?;
+    println!("{}", report);
+    if report.problems() > 0 {
+        match options.repair {
+            true => bail!("Problems remain after repair; duplicate pubkeys must be merged by hand"),
+            false => bail!("Run with --repair to delete orphan edges and fill missing updated_at"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_parse_options() {
+        let options = FsckOptions::parse(["--db", "x.db", "--repair"].map(String::from)).unwrap();
+        assert_eq!(options, FsckOptions { db: "x.db".into(), repair: true });
+        assert!(FsckOptions::parse(["--db".to_string()]).is_err());
+        assert!(FsckOptions::parse(["--bogus".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_fsck_repairs_orphan_edges() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        db.update_follows(&"a".repeat(64), &["b".repeat(64), "c".repeat(64)], None, Some(1)).unwrap();
+        drop(db);
+        // Orphans predate enforced foreign keys, so write them the way an old build could
+        rusqlite::Connection::open(temp_file.path())
+            .unwrap()
+            .execute_batch(
+                "PRAGMA foreign_keys = OFF;
+                 INSERT INTO edges (follower_id, followed_id) VALUES (1, 999), (998, 1);",
+            )
+            .unwrap();
+
+        let mut options = FsckOptions { db: temp_file.path().to_string_lossy().into(), repair: false };
+        let report = run(&options).unwrap();
+        assert_eq!((report.integrity.nodes, report.integrity.edges), (3, 4));
+        assert_eq!(report.integrity.orphan_edges, 2);
+        assert_eq!((report.graph_nodes, report.graph_edges), (3, 2));
+        assert_eq!(report.problems(), 2);
+        assert!(report.to_string().contains("2 problems found"));
+
+        options.repair = true;
+        let report = run(&options).unwrap();
+        assert_eq!(report.repaired, Some(2));
+        assert_eq!((report.integrity.edges, report.problems()), (2, 0));
+
+        options.db = format!("{}-missing", options.db);
+        assert!(run(&options).is_err());
+    }
+}
@@ -1,7 +0,0 @@
-pub mod store;
-pub mod bfs;
-pub mod metrics;
-pub mod interner;
-
-pub use store::WotGraph;
-pub use metrics::LockMetricsSnapshot;
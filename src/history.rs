@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::config::Config;
+use crate::db::Database;
+use crate::graph::{NodeId, WotGraph};
+use crate::types::Pubkey;
+
+/// Bucket width for follower history queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    Hour,
+    Day,
+    Week,
+}
+
+impl Granularity {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "hour" => Some(Self::Hour),
+            "day" => Some(Self::Day),
+            "week" => Some(Self::Week),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Week => "week",
+        }
+    }
+
+    fn secs(self) -> i64 {
+        match self {
+            Self::Hour => 3600,
+            Self::Day => 86_400,
+            Self::Week => 7 * 86_400,
+        }
+    }
+
+    /// Start of the bucket containing `t`, in UTC; weeks start on Monday
+    fn bucket_start(self, t: i64) -> i64 {
+        // The Unix epoch fell on a Thursday; the first Monday was four days later
+        let offset = if self == Self::Week { 4 * 86_400 } else { 0 };
+        let width = self.secs();
+        (t - offset).div_euclid(width) * width + offset
+    }
+}
+
+/// Collapse `(taken_at, count)` snapshots (oldest first) to the last one in each bucket,
+/// keyed by bucket start
+pub fn bucket(snapshots: &[(i64, u64)], granularity: Granularity) -> Vec<(i64, u64)> {
+    let mut points: Vec<(i64, u64)> = Vec::new();
+    for &(taken_at, count) in snapshots {
+        let start = granularity.bucket_start(taken_at);
+        match points.last_mut() {
+            Some(last) if last.0 == start => last.1 = count,
+            _ => points.push((start, count)),
+        }
+    }
+    points
+}
+
+/// Periodically records follower counts of well-followed and watched pubkeys, so growth
+/// can be inspected over time (`GET /history/followers/:pubkey`)
+pub struct FollowerHistory {
+    graph: Arc<WotGraph>,
+    db: Arc<Database>,
+    config: Arc<Config>,
+}
+
+impl FollowerHistory {
+    pub fn new(graph: Arc<WotGraph>, db: Arc<Database>, config: Arc<Config>) -> Self {
+        Self { graph, db, config }
+    }
+
+    pub async fn start(&self, interval_secs: u64) -> Result<()> {
+        info!(
+            "Starting follower history (every {}s, >= {} followers, {} watched pubkeys)",
+            interval_secs,
+            self.config.follower_history_min_followers,
+            self.config.follower_history_pubkeys.len()
+        );
+
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            match self.record().await {
+                Ok(count) => info!("Recorded follower counts for {} pubkeys", count),
+                Err(e) => error!("Failed to record follower history: {}", e),
+            }
+        }
+    }
+
+    async fn record(&self) -> Result<usize> {
+        let (graph, db, config) = (self.graph.clone(), self.db.clone(), self.config.clone());
+        tokio::task::spawn_blocking(move || {
+            let now = chrono::Utc::now().timestamp();
+            let counts = snapshot(
+                &graph,
+                config.follower_history_min_followers,
+                &config.follower_history_pubkeys,
+            );
+            let recorded = db.record_follower_counts(now, &counts)?;
+
+            let retention_secs = config.follower_history_retention_days as i64 * 86_400;
+            db.prune_follower_history(now - retention_secs)?;
+            Ok(recorded)
+        })
+        .await
+        .context("Follower history task failed")?
+    }
+}
+
+/// Follower counts of every pubkey with at least `min_followers` followers, plus each
+/// watched pubkey present in the graph
+pub fn snapshot(graph: &WotGraph, min_followers: usize, watched: &[String]) -> Vec<(Pubkey, usize)> {
+    let watched: HashSet<NodeId> = watched.iter().filter_map(|pk| graph.node_id(&pk.parse().ok()?)).collect();
+
+    let ids: Vec<(NodeId, usize)> = graph.with_adjacency(|_, followers| {
+        followers
+            .iter()
+            .enumerate()
+            .filter(|(id, list)| list.len() >= min_followers || watched.contains(&(*id as NodeId)))
+            .map(|(id, list)| (id as NodeId, list.len()))
+            .collect()
+    });
+
+    ids.into_iter()
+        .filter_map(|(id, count)| graph.pubkey(id).map(|pk| (pk, count)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    #[test]
+    fn test_snapshot_threshold_and_watchlist() {
+        let graph = WotGraph::new();
+        graph.update_follows(&hex("alice"), &[hex("carol")], None, None);
+        graph.update_follows(&hex("bob"), &[hex("carol"), hex("dave")], None, None);
+
+        let mut counts = snapshot(&graph, 2, &[hex("dave"), hex("nobody")]);
+        counts.sort();
+        assert_eq!(counts, vec![(pk("carol"), 2), (pk("dave"), 1)]);
+    }
+
+    #[test]
+    fn test_bucket_keeps_last_snapshot() {
+        let snapshots = [(3_600, 10), (7_200, 12), (86_400 + 60, 20), (86_400 * 2 + 5, 25)];
+        assert_eq!(
+            bucket(&snapshots, Granularity::Day),
+            vec![(0, 12), (86_400, 20), (86_400 * 2, 25)]
+        );
+        assert_eq!(bucket(&snapshots, Granularity::Hour).len(), 4);
+        // 1970-01-05 was a Monday
+        assert_eq!(bucket(&snapshots, Granularity::Week), vec![(-3 * 86_400, 25)]);
+        assert_eq!(Granularity::parse("week"), Some(Granularity::Week));
+        assert_eq!(Granularity::parse("month"), None);
+    }
+}
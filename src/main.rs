@@ -1,21 +1,41 @@
+mod alert;
 mod api;
-mod cache;
+mod bench;
+mod botscore;
 mod config;
 mod db;
-mod graph;
+mod diagnostics;
+mod error;
+mod fsck;
+mod history;
+#[cfg(test)]
+mod mock_relay;
+mod namespace;
+mod popularity;
+mod reach;
+mod report;
+mod sample;
+mod scheduler;
 mod sync;
 
 use anyhow::Result;
+use nostr_sdk::prelude::Keys;
 use std::sync::Arc;
 use tracing::{info, error};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+// Graph engine and query cache live in the wot-oracle-core library
+use wot_oracle_core::{cache, graph, types};
 
-use api::{http::AppState, DvmService};
-use cache::QueryCache;
+use api::{
+    access::AccessLists, commitment::Commitments, dvm::DvmStats, http::AppState, identity::Resolver, profiles::Profiles,
+    ratelimit::RateLimits,
+    CommitmentPublisher, DvmService, RankingPublisher,
+};
 use config::Config;
-use db::Database;
-use graph::WotGraph;
-use sync::Ingestion;
+use diagnostics::Diagnostics;
+use history::FollowerHistory;
+use namespace::{GraphNamespace, Namespaces};
+use sync::{IngestionPause, Replicator, Watchlist};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -25,6 +45,15 @@ async fn main() -> Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        // CPU-bound threads of its own; the runtime stays idle
+        Some("bench") => return bench::main(args),
+        Some("fsck") => return fsck::main(args),
+        Some("report") => return report::main(args),
+        _ => {}
+    }
+
     info!("WoT Oracle v{} starting...", env!("CARGO_PKG_VERSION"));
     info!(
         "Tokio runtime: {} worker threads",
@@ -35,49 +64,136 @@ async fn main() -> Result<()> {
     let config = Config::from_env();
     info!("Configuration loaded: {} relays, HTTP port {}", config.relays.len(), config.http_port);
 
-    // Initialize database
-    let db = Arc::new(Database::open(&config.db_path)?);
-    info!("Database opened at: {}", config.db_path);
-
-    // Create graph and load from database
-    let graph = Arc::new(WotGraph::new());
-    db.load_graph(&graph)?;
-
-    let initial_stats = graph.stats();
-    info!(
-        "Graph loaded: {} nodes, {} edges",
-        initial_stats.node_count, initial_stats.edge_count
-    );
-
-    // Create shared config
+    // Open the primary graph's database and load it
     let config = Arc::new(config);
+    let primary = GraphNamespace::open(&config.graph_name, config.clone())?;
+    let (graph, db, cache) = (primary.graph.clone(), primary.db.clone(), primary.cache.clone());
+    let (ranks, components, anchors) = (primary.ranks.clone(), primary.components.clone(), primary.anchors.clone());
+    let (popular_anchors, popularity) = (primary.popular_anchors.clone(), primary.popularity.clone());
+    let (sightings, botscores) = (primary.sightings.clone(), primary.botscores.clone());
+    let (persistence, jobs) = (primary.persistence.clone(), primary.jobs.clone());
+    match config.cache_max_mb {
+        0 => info!("Query cache initialized: {} entries, {} second TTL", config.cache_size, config.cache_ttl_secs),
+        mb => info!("Query cache initialized: {} MiB, {} second TTL", mb, config.cache_ttl_secs),
+    }
 
-    // Create query cache
-    let cache = Arc::new(QueryCache::new(config.cache_size, config.cache_ttl_secs));
-    info!(
-        "Query cache initialized: {} entries, {} second TTL",
-        config.cache_size, config.cache_ttl_secs
-    );
+    // PageRank, components, anchor distances, hot and popular cache entries, bot scores and
+    // retention pruning are recomputed in the background on the graph's scheduler
+    primary.spawn_jobs();
+
+    // Additional named graphs, each with its own relays, database, cache and rankings
+    let mut extra_graphs = Vec::new();
+    for graph_config in &config.graphs {
+        let graph_settings = Arc::new(Config {
+            relays: graph_config.relays.clone(),
+            db_path: graph_config.db_path.clone(),
+            ..(*config).clone()
+        });
+        let namespace = Arc::new(GraphNamespace::open(&graph_config.name, graph_settings)?);
+        namespace.spawn_jobs();
+        extra_graphs.push(namespace);
+    }
+    let namespaces = Arc::new(Namespaces::new(&config.graph_name, extra_graphs));
+    info!("Serving graphs: {}", namespaces.names().join(", "));
 
     // Create app state for HTTP server
+    let rate_limits = Arc::new(
+        RateLimits::new(config.rate_limit_per_minute, config.admin_rate_limit_per_minute)
+            .with_explore_limit(config.explore_rate_limit_per_minute),
+    );
+    let access = Arc::new(AccessLists::from_config(&config));
+    let oracle_keys = config.dvm_private_key.as_deref().and_then(|key| match Keys::parse(key) {
+        Ok(keys) => Some(keys),
+        Err(e) => {
+            error!("Invalid DVM_PRIVATE_KEY: {}", e);
+            None
+        }
+    });
+    if config.sign_responses && oracle_keys.is_none() {
+        error!("SIGN_RESPONSES set but no valid DVM_PRIVATE_KEY; responses will be unsigned");
+    }
+    let resolver = Arc::new(Resolver::new(config.nip05_enabled));
+    let dvm_stats = Arc::new(DvmStats::default());
+    let watchlist = Arc::new(Watchlist::from_config(&config));
+    // Users who exclude their mute list need it ingested (`/me/settings`)
+    match db.users_excluding_muted() {
+        Ok(users) => {
+            let _ = watchlist.add(&users);
+        }
+        Err(e) => error!("Failed to load users excluding muted pubkeys: {}", e),
+    }
+    let diagnostics = Arc::new(Diagnostics::default());
+    let commitments = Arc::new(Commitments::default());
+    let ingestion_pause = Arc::new(IngestionPause::default());
     let app_state = AppState {
         graph: graph.clone(),
         config: config.clone(),
         cache: cache.clone(),
+        rate_limits,
+        access: access.clone(),
+        ranks: ranks.clone(),
+        components,
+        anchors,
+        popular_anchors,
+        popularity,
+        sightings,
+        botscores,
+        persistence,
+        jobs,
+        commitments: commitments.clone(),
+        db: db.clone(),
+        oracle_keys: oracle_keys.clone(),
+        resolver: resolver.clone(),
+        profiles: Arc::new(Profiles::new(config.relays.clone())),
+        dvm_stats: dvm_stats.clone(),
+        watchlist: watchlist.clone(),
+        ingestion_pause: ingestion_pause.clone(),
+        namespaces: namespaces.clone(),
+        diagnostics: diagnostics.clone(),
     };
+    diagnostics::spawn_signal_dump(app_state.clone());
 
-    // Start ingestion daemon
-    let ingestion = Ingestion::new(graph.clone(), db.clone(), config.relays.clone());
-    let ingestion_handle = tokio::spawn(async move {
-        if let Err(e) = ingestion.start().await {
-            error!("Ingestion error: {}", e);
+    // Operator alerts (webhook and/or Nostr DM)
+    let alerts = alert::start(config.clone(), oracle_keys.clone());
+
+    // Start ingestion daemons; the watchlist only applies to the primary graph. A replica
+    // pulls the primary graph from REPLICATE_FROM instead of ingesting it from relays.
+    let mut seen_caches = Vec::new();
+    let ingestion_handle = match &config.replicate_from {
+        Some(primary_url) => {
+            let replicator = Replicator::new(graph.clone(), db.clone(), &config, primary_url);
+            tokio::spawn(async move {
+                if let Err(e) = replicator.start().await {
+                    error!("Replication error: {}", e);
+                }
+            })
         }
-    });
+        None => {
+            let (seen, handle) = primary.start_sync(watchlist, ingestion_pause.clone(), alerts.clone(), diagnostics.clone()).await;
+            seen_caches.push((seen, db.clone()));
+            handle
+        }
+    };
+    for namespace in namespaces.extra() {
+        let (seen, _) = namespace.start_sync(Arc::default(), ingestion_pause.clone(), alerts.clone(), diagnostics.clone()).await;
+        seen_caches.push((seen, namespace.db.clone()));
+    }
 
     // Start DVM service if enabled
     let _dvm_handle = if config.dvm_enabled {
         if let Some(ref private_key) = config.dvm_private_key {
-            match DvmService::new(graph.clone(), cache.clone(), config.clone(), private_key) {
+            match DvmService::new(
+                graph.clone(),
+                cache.clone(),
+                ranks.clone(),
+                config.clone(),
+                access.clone(),
+                resolver.clone(),
+                dvm_stats.clone(),
+                namespaces.clone(),
+                db.clone(),
+                private_key,
+            ) {
                 Ok(dvm) => {
                     let handle = tokio::spawn(async move {
                         if let Err(e) = dvm.start().await {
@@ -100,11 +216,60 @@ async fn main() -> Result<()> {
         None
     };
 
+    // Start follow-list commitments if configured; roots are published when the oracle has keys
+    if let Some(interval_secs) = config.commitment_interval_secs {
+        if oracle_keys.is_none() {
+            error!("COMMITMENT_INTERVAL_SECS set but no valid DVM_PRIVATE_KEY; commitments will not be published");
+        }
+        let publisher = CommitmentPublisher::new(graph.clone(), commitments, config.relays.clone(), oracle_keys.clone());
+        tokio::spawn(async move {
+            if let Err(e) = publisher.start(interval_secs).await {
+                error!("Commitment publisher error: {}", e);
+            }
+        });
+    }
+
+    // Start ranking publisher if configured
+    if let Some(interval_secs) = config.publish_interval_secs {
+        match oracle_keys {
+            Some(keys) => {
+                let publisher = RankingPublisher::new(graph.clone(), ranks, config.clone(), access.clone(), keys);
+                tokio::spawn(async move {
+                    if let Err(e) = publisher.start(interval_secs).await {
+                        error!("Ranking publisher error: {}", e);
+                    }
+                });
+            }
+            None => error!("PUBLISH_INTERVAL_SECS set but no valid DVM_PRIVATE_KEY; rankings will not be published"),
+        }
+    }
+
+    // Start follower history snapshots unless disabled
+    if let Some(interval_secs) = config.follower_history_interval_secs {
+        let history = FollowerHistory::new(graph.clone(), db.clone(), config.clone());
+        tokio::spawn(async move {
+            if let Err(e) = history.start(interval_secs).await {
+                error!("Follower history error: {}", e);
+            }
+        });
+    } else {
+        info!("Follower history disabled");
+    }
+
+    // Start gRPC server if configured
+    if let Some(grpc_port) = config.grpc_port {
+        let grpc_state = app_state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = api::grpc::start_server(grpc_state, grpc_port).await {
+                error!("gRPC server error: {}", e);
+            }
+        });
+    }
+
     // Start HTTP server
     let http_port = config.http_port;
-    let rate_limit = config.rate_limit_per_minute;
     let http_handle = tokio::spawn(async move {
-        if let Err(e) = api::http::start_server(app_state, http_port, rate_limit).await {
+        if let Err(e) = api::http::start_server(app_state, http_port).await {
             error!("HTTP server error: {}", e);
         }
     });
@@ -123,5 +288,11 @@ async fn main() -> Result<()> {
     }
 
     info!("Shutting down...");
+    for (seen, db) in seen_caches {
+        match seen.persist(db).await {
+            Ok(count) => info!("Saved seen cache ({} pubkeys)", count),
+            Err(e) => error!("Failed to save seen cache: {}", e),
+        }
+    }
     Ok(())
 }
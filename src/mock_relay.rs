@@ -0,0 +1,301 @@
+//! In-process NIP-01 relay for end-to-end tests of `Ingestion` and `DvmService`.
+//!
+//! Stores every event it is given (by the test via [`MockRelay::publish`], or by clients with
+//! `EVENT`), answers `REQ` with the stored matches and `EOSE`, then streams newly stored
+//! matches to open subscriptions. Signatures are not checked; that is the client's job.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+    routing::get,
+    Router,
+};
+use nostr_sdk::prelude::*;
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+const LIVE_QUEUE_SIZE: usize = 1024;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+struct Shared {
+    events: RwLock<Vec<Value>>,
+    live: broadcast::Sender<Value>,
+    /// Open subscriptions across all connections
+    subscriptions: AtomicUsize,
+}
+
+impl Shared {
+    fn store(&self, event: Value) {
+        self.events.write().push(event.clone());
+        let _ = self.live.send(event);
+    }
+}
+
+pub struct MockRelay {
+    url: String,
+    shared: Arc<Shared>,
+    server: JoinHandle<()>,
+}
+
+impl MockRelay {
+    /// Listen on a free localhost port
+    pub async fn start() -> Self {
+        let shared = Arc::new(Shared {
+            events: RwLock::new(Vec::new()),
+            live: broadcast::channel(LIVE_QUEUE_SIZE).0,
+            subscriptions: AtomicUsize::new(0),
+        });
+        let app = Router::new().route("/", get(upgrade)).with_state(shared.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Self { url, shared, server }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Store an event as if some other client had published it
+    pub fn publish(&self, event: Value) {
+        self.shared.store(event);
+    }
+
+    /// Store a signed event as if some other client had published it
+    pub fn publish_event(&self, event: &Event) {
+        self.publish(serde_json::from_str(&event.as_json()).expect("event JSON"));
+    }
+
+    /// Every stored event, oldest first
+    pub fn events(&self) -> Vec<Value> {
+        self.shared.events.read().clone()
+    }
+
+    /// Wait until some client holds an open subscription
+    pub async fn wait_for_subscription(&self, timeout: Duration) -> bool {
+        poll(timeout, || (self.shared.subscriptions.load(Ordering::Relaxed) > 0).then_some(())).await.is_some()
+    }
+
+    /// Wait for a stored event matching a NIP-01 filter
+    pub async fn wait_for_event(&self, filter: &Value, timeout: Duration) -> Option<Value> {
+        poll(timeout, || self.shared.events.read().iter().find(|event| matches(filter, event)).cloned()).await
+    }
+}
+
+/// Wait until `check` passes, for state a background task updates
+pub async fn eventually(timeout: Duration, mut check: impl FnMut() -> bool) -> bool {
+    poll(timeout, || check().then_some(())).await.is_some()
+}
+
+impl Drop for MockRelay {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn poll<T>(timeout: Duration, mut check: impl FnMut() -> Option<T>) -> Option<T> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if let Some(found) = check() {
+            return Some(found);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return None;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Whether `event` satisfies a NIP-01 filter (`ids`, `authors`, `kinds`, `#<tag>`, `since`,
+/// `until`; `limit` is applied by the caller)
+fn matches(filter: &Value, event: &Value) -> bool {
+    let Some(filter) = filter.as_object() else {
+        return false;
+    };
+    filter.iter().all(|(key, wanted)| {
+        let any_of = |value: Option<&Value>| {
+            wanted
+                .as_array()
+                .is_some_and(|wanted| value.is_some_and(|value| wanted.contains(value)))
+        };
+        match key.as_str() {
+            "ids" => any_of(event.get("id")),
+            "authors" => any_of(event.get("pubkey")),
+            "kinds" => any_of(event.get("kind")),
+            "since" => event["created_at"].as_u64() >= wanted.as_u64(),
+            "until" => event["created_at"].as_u64() <= wanted.as_u64(),
+            "limit" => true,
+            key => match key.strip_prefix('#') {
+                Some(name) => event["tags"].as_array().is_some_and(|tags| {
+                    tags.iter()
+                        .filter(|tag| tag[0].as_str() == Some(name))
+                        .any(|tag| any_of(tag.get(1)))
+                }),
+                None => true,
+            },
+        }
+    })
+}
+
+async fn upgrade(State(shared): State<Arc<Shared>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| serve(socket, shared))
+}
+
+async fn serve(mut socket: WebSocket, shared: Arc<Shared>) {
+    let mut live = shared.live.subscribe();
+    let mut subscriptions: HashMap<String, Vec<Value>> = HashMap::new();
+
+    loop {
+        let replies = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => handle(&shared, &mut subscriptions, &text),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            event = live.recv() => match event {
+                Ok(event) => subscriptions
+                    .iter()
+                    .filter(|(_, filters)| filters.iter().any(|filter| matches(filter, &event)))
+                    .map(|(id, _)| json!(["EVENT", id, event]).to_string())
+                    .collect(),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        for reply in replies {
+            if socket.send(Message::Text(reply)).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    shared.subscriptions.fetch_sub(subscriptions.len(), Ordering::Relaxed);
+}
+
+fn handle(shared: &Shared, subscriptions: &mut HashMap<String, Vec<Value>>, text: &str) -> Vec<String> {
+    let Ok(Value::Array(message)) = serde_json::from_str::<Value>(text) else {
+        return vec![json!(["NOTICE", "invalid: message is not a JSON array"]).to_string()];
+    };
+
+    match (message.first().and_then(Value::as_str), message.get(1)) {
+        (Some("REQ"), Some(Value::String(id))) => {
+            let filters = message[2..].to_vec();
+            let mut replies: Vec<String> = Vec::new();
+            for filter in &filters {
+                let limit = filter["limit"].as_u64().map_or(usize::MAX, |limit| limit as usize);
+                let events = shared.events.read();
+                let found = events.iter().rev().filter(|event| matches(filter, event)).take(limit);
+                replies.extend(found.map(|event| json!(["EVENT", id, event]).to_string()));
+            }
+            replies.push(json!(["EOSE", id]).to_string());
+            if subscriptions.insert(id.clone(), filters).is_none() {
+                shared.subscriptions.fetch_add(1, Ordering::Relaxed);
+            }
+            replies
+        }
+        (Some("CLOSE"), Some(Value::String(id))) => {
+            if subscriptions.remove(id).is_some() {
+                shared.subscriptions.fetch_sub(1, Ordering::Relaxed);
+            }
+            Vec::new()
+        }
+        (Some("EVENT"), Some(event)) => {
+            let id = event["id"].clone();
+            shared.store(event.clone());
+            vec![json!(["OK", id, true, ""]).to_string()]
+        }
+        _ => vec![json!(["NOTICE", "invalid: unsupported message"]).to_string()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite;
+
+    fn event(id: &str, kind: u64, created_at: u64, p: &str) -> Value {
+        json!({
+            "id": id,
+            "pubkey": "a".repeat(64),
+            "created_at": created_at,
+            "kind": kind,
+            "tags": [["p", p]],
+            "content": "",
+            "sig": "",
+        })
+    }
+
+    #[test]
+    fn test_matches() {
+        let e = event("e1", 3, 100, "bob");
+        assert!(matches(&json!({"kinds": [3], "#p": ["bob"], "since": 100}), &e));
+        assert!(matches(&json!({"authors": ["a".repeat(64)], "limit": 1}), &e));
+        assert!(!matches(&json!({"kinds": [1]}), &e));
+        assert!(!matches(&json!({"#p": ["carol"]}), &e));
+        assert!(!matches(&json!({"until": 99}), &e));
+        assert!(!matches(&json!({"#e": ["e0"]}), &e));
+    }
+
+    type Socket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+    async fn recv(ws: &mut Socket) -> Value {
+        loop {
+            match ws.next().await {
+                Some(Ok(tungstenite::Message::Text(text))) => return serde_json::from_str(&text).unwrap(),
+                Some(Ok(_)) => continue,
+                other => panic!("relay closed: {:?}", other),
+            }
+        }
+    }
+
+    async fn send(ws: &mut Socket, message: Value) {
+        ws.send(tungstenite::Message::Text(message.to_string())).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_req_event_and_live_stream() {
+        let relay = MockRelay::start().await;
+        relay.publish(event("stored", 3, 100, "bob"));
+        relay.publish(event("other", 1, 100, "bob"));
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(relay.url()).await.unwrap();
+        send(&mut ws, json!(["REQ", "sub", {"kinds": [3]}])).await;
+        assert_eq!(recv(&mut ws).await[2]["id"], "stored");
+        assert_eq!(recv(&mut ws).await, json!(["EOSE", "sub"]));
+        assert!(relay.wait_for_subscription(Duration::from_secs(1)).await);
+
+        // Published by another client after EOSE: streamed live
+        relay.publish(event("live", 3, 200, "carol"));
+        assert_eq!(recv(&mut ws).await[2]["id"], "live");
+
+        // Published by this client: acknowledged, stored and echoed to its own subscription
+        send(&mut ws, json!(["EVENT", event("mine", 3, 300, "dave")])).await;
+        let replies = [recv(&mut ws).await, recv(&mut ws).await];
+        assert!(replies.contains(&json!(["OK", "mine", true, ""])));
+        let found = relay.wait_for_event(&json!({"#p": ["dave"]}), Duration::from_secs(1)).await;
+        assert_eq!(found.unwrap()["id"], "mine");
+        assert_eq!(relay.events().len(), 4);
+
+        send(&mut ws, json!(["CLOSE", "sub"])).await;
+        send(&mut ws, json!(["REQ", "newest", {"kinds": [3], "limit": 1}])).await;
+        assert_eq!(recv(&mut ws).await[2]["id"], "mine");
+        assert_eq!(recv(&mut ws).await, json!(["EOSE", "newest"]));
+
+        // Only the open subscription gets new events
+        relay.publish(event("after-close", 3, 400, "erin"));
+        assert_eq!(recv(&mut ws).await[1], "newest");
+        assert!(tokio::time::timeout(Duration::from_millis(100), ws.next()).await.is_err());
+    }
+}
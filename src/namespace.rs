@@ -0,0 +1,503 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::alert::Alerts;
+use crate::botscore::BotScores;
+use crate::cache::QueryCache;
+use crate::config::{Config, GRAPH_NAME_DEFAULT};
+use crate::db::snapshot::{self, SnapshotReader};
+use crate::db::Database;
+use crate::diagnostics::Diagnostics;
+use crate::graph::{Anchors, Components, GraphLimits, PageRank, WotGraph};
+use crate::popularity::Popularity;
+use crate::scheduler::{Schedule, Scheduler};
+use crate::sync::{Ingestion, IngestionPause, PersistenceStats, RelaySightings, SeenCache, Watchlist};
+use crate::types::Pubkey;
+
+/// How often the most popular pairs are checked against the cache (`CACHE_WARM_COUNT`)
+const CACHE_WARM_INTERVAL: Duration = Duration::from_secs(60);
+/// How often edge changes and outbox entries past their retention are deleted
+const EDGE_HISTORY_PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Lists stored this long before a snapshot was taken are replayed too, in case the clock
+/// stepped back
+const SNAPSHOT_REPLAY_MARGIN_SECS: i64 = 60;
+/// Lists read from the database per catch-up query after a snapshot load
+const SNAPSHOT_REPLAY_BATCH: usize = 10_000;
+
+/// A named follow graph with its own database, query cache and rankings
+pub struct GraphNamespace {
+    pub name: String,
+    /// This graph's settings: the shared config with its own `relays` and `db_path`
+    pub config: Arc<Config>,
+    pub graph: Arc<WotGraph>,
+    pub db: Arc<Database>,
+    pub cache: Arc<QueryCache>,
+    pub ranks: Arc<PageRank>,
+    pub components: Arc<Components>,
+    pub anchors: Arc<Anchors>,
+    /// Anchors picked from the most popular query sources (`ANCHOR_AUTO_COUNT`)
+    pub popular_anchors: Arc<Anchors>,
+    pub popularity: Arc<Popularity>,
+    /// Relays that delivered each pubkey's current contact list, for `confidence`
+    pub sightings: Arc<RelaySightings>,
+    pub botscores: Arc<BotScores>,
+    /// Health of ingestion's database writes
+    pub persistence: Arc<PersistenceStats>,
+    /// Background recomputation jobs and their run statistics
+    pub jobs: Arc<Scheduler>,
+}
+
+impl GraphNamespace {
+    /// Open the database at `config.db_path` and load its graph, applying the spam limits and
+    /// the blocklist (seeded from `BLOCKED_PUBKEYS`) first
+    pub fn open(name: &str, config: Arc<Config>) -> Result<Self> {
+        let db = Arc::new(Database::open(&config.db_path)?);
+        info!("Database for graph {} opened at: {}", name, config.db_path);
+
+        let limited_graph = || {
+            let graph = WotGraph::new();
+            graph.set_limits(GraphLimits {
+                max_follows: config.max_follows_per_list,
+                quarantine_min_follows: config.quarantine_min_follows,
+                expansion_limit: config.bfs_expansion_limit,
+                max_tags: config.max_tags_per_list,
+                truncate_tags: config.truncate_oversized_lists,
+            });
+            graph
+        };
+
+        // Blocked before loading, so blocked pubkeys never become nodes
+        let configured: Vec<String> = config
+            .blocked_pubkeys
+            .iter()
+            .filter_map(|pk| Pubkey::parse(pk).ok())
+            .map(String::from)
+            .collect();
+        db.block_pubkeys(&configured, Some("BLOCKED_PUBKEYS"))?;
+
+        // From the snapshot when there is a usable one, else every stored list
+        let mut graph = limited_graph();
+        let snapshot_path = config.graph_snapshot_path().filter(|path| std::path::Path::new(path).exists());
+        let blocked = match snapshot_path.map(|path| load_snapshot(&graph, &db, &path)) {
+            Some(Ok(Some(blocked))) => blocked,
+            Some(Err(e)) => {
+                warn!("Graph {} snapshot unusable, loading from the database: {:#}", name, e);
+                graph = limited_graph();
+                load(&graph, &db)?
+            }
+            Some(Ok(None)) | None => load(&graph, &db)?,
+        };
+        let graph = Arc::new(graph);
+
+        let stats = graph.stats();
+        info!(
+            "Graph {} loaded: {} nodes, {} edges, {} blocked pubkeys",
+            name, stats.node_count, stats.edge_count, blocked
+        );
+
+        Ok(Self {
+            name: name.to_string(),
+            cache: Arc::new(query_cache(&config).with_hot_pairs(config.hot_pair_min_hits)),
+            anchors: Arc::new(Anchors::new(config.anchor_pubkeys.clone())),
+            popular_anchors: Arc::default(),
+            popularity: Arc::new(Popularity::new(config.popularity_half_life_secs)),
+            sightings: Arc::default(),
+            botscores: Arc::default(),
+            persistence: Arc::default(),
+            jobs: Arc::default(),
+            config,
+            graph,
+            db,
+            ranks: Arc::new(PageRank::new()),
+            components: Arc::new(Components::new()),
+        })
+    }
+
+    /// Start this graph's background jobs on its scheduler. Each runs on its interval setting
+    /// unless `SCHEDULE_<JOB>` replaces it, and is listed as off when its feature is disabled.
+    pub fn spawn_jobs(&self) {
+        let config = &self.config;
+        let every = |secs: u64| Schedule::Every(Duration::from_secs(secs));
+
+        // PageRank, for rankings
+        let (graph, ranks) = (self.graph.clone(), self.ranks.clone());
+        self.jobs.spawn("pagerank", config.job_schedule("pagerank", every(config.pagerank_interval_secs)), move || {
+            ranks.refresh(&graph);
+            Ok(())
+        });
+
+        // Between full computations, PageRank follows changed follow lists incrementally
+        let schedule = match config.pagerank_update_interval_secs {
+            Some(secs) => config.job_schedule("pagerank_updates", every(secs)),
+            None => Schedule::Off,
+        };
+        let (graph, ranks) = (self.graph.clone(), self.ranks.clone());
+        self.jobs.spawn("pagerank_updates", schedule, move || {
+            ranks.update(&graph);
+            Ok(())
+        });
+
+        // The largest component, for reachability checks
+        let (graph, components) = (self.graph.clone(), self.components.clone());
+        let schedule = config.job_schedule("components", every(config.pagerank_interval_secs));
+        self.jobs.spawn("components", schedule, move || {
+            components.refresh(&graph);
+            Ok(())
+        });
+
+        // Anchor distance tables, when anchors are configured or picked from query
+        // popularity (`ANCHOR_AUTO_COUNT`)
+        let auto_count = config.anchor_auto_count;
+        let schedule = match self.anchors.pubkeys().is_empty() && auto_count == 0 {
+            true => Schedule::Off,
+            false => config.job_schedule("anchors", every(config.anchor_interval_secs)),
+        };
+        let (graph, anchors, popular_anchors) = (self.graph.clone(), self.anchors.clone(), self.popular_anchors.clone());
+        let popularity = self.popularity.clone();
+        self.jobs.spawn("anchors", schedule, move || {
+            if auto_count > 0 {
+                let configured = anchors.pubkeys();
+                let picked: Vec<String> = popularity
+                    .top_sources(auto_count + configured.len())
+                    .into_iter()
+                    .filter_map(|(id, _)| graph.pubkey(id).map(String::from))
+                    .filter(|pk| !configured.contains(pk))
+                    .take(auto_count)
+                    .collect();
+                popular_anchors.set_pubkeys(picked);
+            }
+            anchors.refresh(&graph);
+            popular_anchors.refresh(&graph);
+            Ok(())
+        });
+
+        // Frequently queried cache entries, recomputed once the graph has changed, when
+        // `HOT_PAIR_MIN_HITS` is set
+        let schedule = match config.hot_pair_min_hits {
+            0 => Schedule::Off,
+            _ => config.job_schedule("hot_pairs", every(config.hot_pair_refresh_secs)),
+        };
+        let (graph, cache) = (self.graph.clone(), self.cache.clone());
+        self.jobs.spawn("hot_pairs", schedule, move || {
+            match cache.refresh_hot(&graph) {
+                0 => {}
+                count => debug!("Refreshed {} hot cache entries", count),
+            }
+            Ok(())
+        });
+
+        // The `CACHE_WARM_COUNT` most popular pairs, computed whenever they are missing from
+        // the cache so they are answered from it even after expiring or a cache clear
+        let count = config.cache_warm_count;
+        let schedule = match count {
+            0 => Schedule::Off,
+            _ => config.job_schedule("cache_warming", Schedule::Every(CACHE_WARM_INTERVAL)),
+        };
+        let (graph, cache, popularity) = (self.graph.clone(), self.cache.clone(), self.popularity.clone());
+        self.jobs.spawn("cache_warming", schedule, move || {
+            let keys: Vec<_> = popularity.top_pairs(count).into_iter().map(|(key, _)| key).collect();
+            match cache.warm(&keys, &graph) {
+                0 => {}
+                warmed => debug!("Warmed {} popular cache entries", warmed),
+            }
+            Ok(())
+        });
+
+        // Bot-likelihood scores, unless `BOTSCORE_INTERVAL_SECS` is 0
+        let schedule = match config.botscore_interval_secs {
+            Some(secs) => config.job_schedule("botscores", every(secs)),
+            None => Schedule::Off,
+        };
+        let (graph, db, botscores) = (self.graph.clone(), self.db.clone(), self.botscores.clone());
+        let weights = config.botscore_weights;
+        self.jobs.spawn("botscores", schedule, move || {
+            botscores.refresh(&graph, &db, weights, chrono::Utc::now().timestamp())?;
+            Ok(())
+        });
+
+        // The edge change log past `EDGE_HISTORY_RETENTION_DAYS` and the outbox past
+        // `OUTBOX_RETENTION_DAYS`
+        let db = self.db.clone();
+        let retention_secs = config.edge_history_retention_days as i64 * 86_400;
+        let outbox_retention_secs = config.outbox_retention_days as i64 * 86_400;
+        let schedule = config.job_schedule("pruning", Schedule::Every(EDGE_HISTORY_PRUNE_INTERVAL));
+        self.jobs.spawn("pruning", schedule, move || {
+            let now = chrono::Utc::now().timestamp();
+            let changes = db.prune_edge_changes(now - retention_secs)?;
+            let entries = db.prune_outbox(now - outbox_retention_secs)?;
+            if changes > 0 || entries > 0 {
+                debug!("Pruned {} edge changes, {} outbox entries", changes, entries);
+            }
+            Ok(())
+        });
+
+        // A binary snapshot of the graph for the next startup, when `GRAPH_SNAPSHOT_INTERVAL_SECS`
+        // is set
+        let schedule = match config.graph_snapshot_interval_secs {
+            Some(secs) => config.job_schedule("graph_snapshot", every(secs)),
+            None => Schedule::Off,
+        };
+        let (graph, db, path) = (self.graph.clone(), self.db.clone(), config.graph_snapshot_path());
+        self.jobs.spawn("graph_snapshot", schedule, move || {
+            let Some(path) = &path else {
+                return Ok(());
+            };
+            let blocked: Vec<String> = db.blocked_pubkeys()?.into_iter().map(|b| b.pubkey).collect();
+            let lists = snapshot::write(&graph, &blocked, chrono::Utc::now().timestamp(), path)?;
+            debug!("Wrote graph snapshot with {} lists to {}", lists, path);
+            Ok(())
+        });
+    }
+
+    /// Load this graph's dedup cache, keep saving it, and start ingesting from its relays.
+    /// The cache is returned so it can be saved once more on shutdown.
+    pub async fn start_sync(
+        &self,
+        watchlist: Arc<Watchlist>,
+        pause: Arc<IngestionPause>,
+        alerts: Alerts,
+        diagnostics: Arc<Diagnostics>,
+    ) -> (Arc<SeenCache>, JoinHandle<()>) {
+        // Dedup cache saved by the previous run, so restarts skip already-known events
+        let seen = Arc::new(SeenCache::new(self.config.seen_cache_capacity));
+        match seen.load(self.db.clone()).await {
+            Ok(count) => info!("Loaded seen cache for graph {} ({} pubkeys)", self.name, count),
+            Err(e) => error!("Failed to load seen cache for graph {}: {}", self.name, e),
+        }
+        let (seen_saver, seen_db) = (seen.clone(), self.db.clone());
+        let seen_persist_secs = self.config.seen_cache_persist_secs;
+        tokio::spawn(async move {
+            if let Err(e) = seen_saver.start(seen_db, seen_persist_secs).await {
+                error!("Seen cache saver error: {}", e);
+            }
+        });
+
+        let ingestion = Ingestion::new(
+            self.graph.clone(),
+            self.db.clone(),
+            self.config.clone(),
+            seen.clone(),
+            self.sightings.clone(),
+            watchlist,
+            pause,
+            alerts,
+            diagnostics,
+            self.persistence.clone(),
+        );
+        let name = self.name.clone();
+        let handle = tokio::spawn(async move {
+            if let Err(e) = ingestion.start().await {
+                error!("Ingestion error in graph {}: {}", name, e);
+            }
+        });
+        (seen, handle)
+    }
+}
+
+/// A graph's query cache, bounded by `CACHE_MAX_MB` when set and by `CACHE_SIZE` entries otherwise
+fn query_cache(config: &Config) -> QueryCache {
+    match config.cache_max_mb {
+        0 => QueryCache::new(config.cache_size, config.cache_ttl_secs),
+        mb => QueryCache::with_max_bytes(mb * 1024 * 1024, config.cache_ttl_secs),
+    }
+}
+
+/// Apply the persisted blocklist to `graph`, then load the stored follow lists into it.
+/// Returns the number of blocked pubkeys.
+pub(crate) fn load(graph: &WotGraph, db: &Database) -> Result<usize> {
+    let blocked: Vec<String> = db.blocked_pubkeys()?.into_iter().map(|b| b.pubkey).collect();
+    graph.block(&blocked);
+    db.load_graph(graph)?;
+    Ok(blocked.len())
+}
+
+/// Load `graph` from the snapshot at `path`, then replay the lists the database stored since
+/// it was taken and apply the persisted blocklist. Returns the number of blocked pubkeys, or
+/// None (with `graph` still empty) when the snapshot can't stand in for a full load: it was taken
+/// under another `max_follows`, or a pubkey it left out as blocked has been unblocked since.
+pub(crate) fn load_snapshot(graph: &WotGraph, db: &Database, path: &str) -> Result<Option<usize>> {
+    let blocked: Vec<String> = db.blocked_pubkeys()?.into_iter().map(|b| b.pubkey).collect();
+    let reader = SnapshotReader::open(path)?;
+    let header = reader.header();
+    if header.max_follows != graph.limits().max_follows {
+        info!("Graph snapshot {} was taken under another MAX_FOLLOWS_PER_LIST, not using it", path);
+        return Ok(None);
+    }
+    if header.blocked.iter().any(|pk| !blocked.contains(pk)) {
+        info!("Pubkeys were unblocked since graph snapshot {} was taken, not using it", path);
+        return Ok(None);
+    }
+    let taken_at = header.taken_at;
+
+    graph.block(&blocked);
+    let lists = reader.load_into(graph)?;
+    info!("Loaded {} follow lists from graph snapshot {}", lists, path);
+
+    // Lists stored since the snapshot, in write order; `update_follows` skips the ones it
+    // already has, so replaying a few too many is harmless
+    let mut after = (taken_at - SNAPSHOT_REPLAY_MARGIN_SECS, i64::MIN);
+    let mut replayed = 0;
+    loop {
+        let page = db.lists_changed_since(after, i64::MAX, SNAPSHOT_REPLAY_BATCH)?;
+        for (_, list) in &page {
+            graph.update_follows(&list.pubkey, &list.follows, list.event_id.clone(), list.created_at);
+        }
+        replayed += page.len();
+        match page.last() {
+            Some((position, _)) if page.len() == SNAPSHOT_REPLAY_BATCH => after = *position,
+            _ => break,
+        }
+    }
+    info!("Replayed {} follow lists stored since the snapshot", replayed);
+    Ok(Some(blocked.len()))
+}
+
+/// Rebuild a live graph from its database without a window where it is empty: the stored
+/// lists are loaded into a fresh graph, which then replaces the live adjacency. Updates still
+/// waiting to be persisted are dropped until their authors publish again.
+pub fn reload(graph: &WotGraph, db: &Database) -> Result<()> {
+    let fresh = WotGraph::new();
+    fresh.set_limits(graph.limits());
+    load(&fresh, db)?;
+    graph.replace_with(&fresh)?;
+    Ok(())
+}
+
+/// Graphs besides the primary one, selected by name with `graph=`. The primary graph is the one
+/// in `AppState`'s own fields; selecting it (or nothing) leaves the state as is.
+pub struct Namespaces {
+    primary: String,
+    extra: BTreeMap<String, Arc<GraphNamespace>>,
+}
+
+impl Namespaces {
+    pub fn new(primary: &str, extra: Vec<Arc<GraphNamespace>>) -> Self {
+        Self {
+            primary: primary.to_string(),
+            extra: extra.into_iter().map(|ns| (ns.name.clone(), ns)).collect(),
+        }
+    }
+
+    /// All graph names, primary first
+    pub fn names(&self) -> Vec<&str> {
+        std::iter::once(self.primary.as_str())
+            .chain(self.extra.keys().map(String::as_str))
+            .collect()
+    }
+
+    pub fn extra(&self) -> impl Iterator<Item = &Arc<GraphNamespace>> {
+        self.extra.values()
+    }
+
+    /// `Ok(None)` selects the primary graph; `Err` means no graph has that name
+    pub fn select(&self, name: Option<&str>) -> Result<Option<&Arc<GraphNamespace>>, UnknownGraph> {
+        match name {
+            None => Ok(None),
+            Some(name) if name == self.primary => Ok(None),
+            Some(name) => self.extra.get(name).map(Some).ok_or(UnknownGraph),
+        }
+    }
+}
+
+impl Default for Namespaces {
+    fn default() -> Self {
+        Self::new(GRAPH_NAME_DEFAULT, Vec::new())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownGraph;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let mut config = Config::from_env();
+        config.db_path = ":memory:".to_string();
+        let test = Arc::new(GraphNamespace::open("test", Arc::new(config)).unwrap());
+        let namespaces = Namespaces::new("mainnet", vec![test]);
+
+        assert_eq!(namespaces.names(), vec!["mainnet", "test"]);
+        assert!(namespaces.select(None).unwrap().is_none());
+        assert!(namespaces.select(Some("mainnet")).unwrap().is_none());
+        assert_eq!(namespaces.select(Some("test")).unwrap().unwrap().name, "test");
+        assert!(namespaces.select(Some("staging")).is_err());
+    }
+
+    #[test]
+    fn test_load_snapshot_catches_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wot.db.snapshot").to_string_lossy().into_owned();
+        let db = Database::open(dir.path().join("wot.db")).unwrap();
+        let (a, b, c, d) = ("a".repeat(64), "b".repeat(64), "c".repeat(64), "d".repeat(64));
+        db.update_follows(&a, &[b.clone(), c.clone()], Some("e1"), Some(100)).unwrap();
+        db.update_follows(&b, std::slice::from_ref(&a), Some("e2"), Some(100)).unwrap();
+
+        let live = WotGraph::new();
+        load(&live, &db).unwrap();
+        let taken_at = chrono::Utc::now().timestamp();
+        snapshot::write(&live, &[], taken_at, &path).unwrap();
+
+        // Changed, added and blocked after the snapshot
+        db.update_follows(&a, &[c.clone(), d.clone()], Some("e3"), Some(200)).unwrap();
+        db.update_follows(&d, &[a.clone(), b.clone()], Some("e4"), Some(150)).unwrap();
+        db.block_pubkeys(std::slice::from_ref(&b), None).unwrap();
+
+        let restored = WotGraph::new();
+        assert_eq!(load_snapshot(&restored, &db, &path).unwrap(), Some(1));
+        let full = WotGraph::new();
+        load(&full, &db).unwrap();
+        for pk in [&a, &b, &c, &d] {
+            let key = pk.parse().unwrap();
+            let sorted = |graph: &WotGraph| {
+                let mut follows = graph.get_follows(&key).unwrap_or_default();
+                follows.sort();
+                follows
+            };
+            assert_eq!(sorted(&restored), sorted(&full), "follows of {}", &pk[..1]);
+            let created_at = |graph: &WotGraph| graph.get_node_info(&key).and_then(|info| info.kind3_created_at);
+            assert_eq!(created_at(&restored), created_at(&full));
+        }
+        assert_eq!(restored.get_follows(&a.parse().unwrap()).unwrap().len(), 2);
+        assert!(restored.is_blocked(&b));
+
+        // Stamped after the changes, nothing is replayed: the lists come from the snapshot
+        snapshot::write(&live, &[], taken_at + 3600, &path).unwrap();
+        let stale = WotGraph::new();
+        load_snapshot(&stale, &db, &path).unwrap();
+        assert_eq!(stale.get_node_info(&a.parse().unwrap()).unwrap().kind3_created_at, Some(100));
+        assert!(stale.node_id(&d.parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_load_snapshot_refuses_unblocked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wot.db.snapshot").to_string_lossy().into_owned();
+        let db = Database::open(dir.path().join("wot.db")).unwrap();
+        let (a, b) = ("a".repeat(64), "b".repeat(64));
+        db.update_follows(&a, std::slice::from_ref(&b), Some("e1"), Some(100)).unwrap();
+        db.block_pubkeys(std::slice::from_ref(&b), None).unwrap();
+
+        let live = WotGraph::new();
+        load(&live, &db).unwrap();
+        snapshot::write(&live, std::slice::from_ref(&b), chrono::Utc::now().timestamp(), &path).unwrap();
+        db.unblock_pubkeys(std::slice::from_ref(&b)).unwrap();
+
+        // b's edges were never written, so only a full load restores them
+        let graph = WotGraph::new();
+        assert_eq!(load_snapshot(&graph, &db, &path).unwrap(), None);
+        assert_eq!(graph.node_count(), 0);
+
+        // A snapshot under another max_follows is refused too
+        let limited = WotGraph::new();
+        limited.set_limits(GraphLimits { max_follows: 10, ..Default::default() });
+        snapshot::write(&live, &[], chrono::Utc::now().timestamp(), &path).unwrap();
+        assert_eq!(load_snapshot(&limited, &db, &path).unwrap(), None);
+    }
+}
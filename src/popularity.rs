@@ -0,0 +1,162 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::cache::CacheKey;
+use crate::graph::NodeId;
+
+/// Most pairs and subjects tracked each; past that the least popular half is dropped
+const MAX_TRACKED: usize = 10_000;
+
+/// Counts that halve every `half_life`, for a bounded number of keys
+struct DecayingCounts<K> {
+    counts: HashMap<K, (f64, Instant)>,
+}
+
+impl<K: Copy + Eq + Hash> DecayingCounts<K> {
+    fn new() -> Self {
+        Self { counts: HashMap::new() }
+    }
+
+    fn decayed(count: f64, since: Instant, now: Instant, half_life: Duration) -> f64 {
+        count * 0.5f64.powf(now.saturating_duration_since(since).as_secs_f64() / half_life.as_secs_f64())
+    }
+
+    fn bump(&mut self, key: K, now: Instant, half_life: Duration) {
+        let (count, at) = self.counts.entry(key).or_insert((0.0, now));
+        *count = Self::decayed(*count, *at, now, half_life) + 1.0;
+        *at = now;
+        if self.counts.len() > MAX_TRACKED {
+            let kept = self.top(MAX_TRACKED / 2, now, half_life);
+            self.counts = kept.into_iter().map(|(key, count)| (key, (count, now))).collect();
+        }
+    }
+
+    /// Keys by current count, highest first
+    fn top(&self, n: usize, now: Instant, half_life: Duration) -> Vec<(K, f64)> {
+        let mut top: Vec<(K, f64)> = self
+            .counts
+            .iter()
+            .map(|(&key, &(count, at))| (key, Self::decayed(count, at, now, half_life)))
+            .collect();
+        top.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        top.truncate(n);
+        top
+    }
+}
+
+/// What a graph is asked: decaying query counts per (from, to) pair and per pubkey queried
+/// as either end. Drives cache warming (`CACHE_WARM_COUNT`), anchor selection
+/// (`ANCHOR_AUTO_COUNT`) and `GET /stats/popular`.
+pub struct Popularity {
+    half_life: Duration,
+    pairs: Mutex<DecayingCounts<CacheKey>>,
+    subjects: Mutex<DecayingCounts<NodeId>>,
+}
+
+impl Popularity {
+    pub fn new(half_life_secs: u64) -> Self {
+        Self {
+            half_life: Duration::from_secs(half_life_secs.max(1)),
+            pairs: Mutex::new(DecayingCounts::new()),
+            subjects: Mutex::new(DecayingCounts::new()),
+        }
+    }
+
+    pub fn half_life_secs(&self) -> u64 {
+        self.half_life.as_secs()
+    }
+
+    /// Count a distance query
+    pub fn record(&self, key: CacheKey) {
+        let now = Instant::now();
+        self.pairs.lock().bump(key, now, self.half_life);
+        let mut subjects = self.subjects.lock();
+        subjects.bump(key.from_id, now, self.half_life);
+        if key.to_id != key.from_id {
+            subjects.bump(key.to_id, now, self.half_life);
+        }
+    }
+
+    /// The most queried pairs, with their query parameters
+    pub fn top_pairs(&self, n: usize) -> Vec<(CacheKey, f64)> {
+        self.pairs.lock().top(n, Instant::now(), self.half_life)
+    }
+
+    /// The most queried pubkeys, as either end of a query
+    pub fn top_subjects(&self, n: usize) -> Vec<(NodeId, f64)> {
+        self.subjects.lock().top(n, Instant::now(), self.half_life)
+    }
+
+    /// The most common query sources, summed over their tracked pairs
+    pub fn top_sources(&self, n: usize) -> Vec<(NodeId, f64)> {
+        let mut sources: HashMap<NodeId, f64> = HashMap::new();
+        for (key, count) in self.top_pairs(MAX_TRACKED) {
+            *sources.entry(key.from_id).or_default() += count;
+        }
+        let mut top: Vec<(NodeId, f64)> = sources.into_iter().collect();
+        top.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        top.truncate(n);
+        top
+    }
+}
+
+impl Default for Popularity {
+    fn default() -> Self {
+        Self::new(crate::config::POPULARITY_HALF_LIFE_DEFAULT_SECS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_decay() {
+        let half_life = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut counts = DecayingCounts::new();
+        for _ in 0..3 {
+            counts.bump(1u32, start, half_life);
+        }
+        assert_eq!(counts.top(1, start, half_life), vec![(1, 3.0)]);
+
+        // 3 halves to 1.5 by the time 2 is counted twice
+        counts.bump(2u32, start + half_life, half_life);
+        counts.bump(2u32, start + half_life, half_life);
+        let top = counts.top(5, start + half_life, half_life);
+        assert_eq!(top.iter().map(|&(k, _)| k).collect::<Vec<_>>(), vec![2, 1]);
+        assert!((top[1].1 - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_counts_bounded() {
+        let (half_life, now) = (Duration::from_secs(60), Instant::now());
+        let mut counts = DecayingCounts::new();
+        counts.bump(u32::MAX, now, half_life);
+        counts.bump(u32::MAX, now, half_life);
+        for key in 0..MAX_TRACKED as u32 {
+            counts.bump(key, now, half_life);
+        }
+        assert_eq!(counts.counts.len(), MAX_TRACKED / 2);
+        assert_eq!(counts.top(1, now, half_life)[0].0, u32::MAX);
+    }
+
+    #[test]
+    fn test_record() {
+        let popularity = Popularity::default();
+        popularity.record(CacheKey::new(1, 2, 3, false));
+        popularity.record(CacheKey::new(1, 3, 3, false));
+        popularity.record(CacheKey::new(4, 4, 3, false));
+
+        assert_eq!(popularity.top_pairs(5).len(), 3);
+        assert_eq!(popularity.top_subjects(1)[0].0, 1);
+        let (source, count) = popularity.top_sources(1)[0];
+        assert_eq!(source, 1);
+        assert!((count - 2.0).abs() < 1e-3);
+        // A self-query counts its pubkey once
+        let (_, count) = *popularity.top_subjects(5).iter().find(|&&(id, _)| id == 4).unwrap();
+        assert!((count - 1.0).abs() < 1e-3);
+    }
+}
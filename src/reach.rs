@@ -0,0 +1,163 @@
+use crate::graph::synth::Rng;
+use crate::graph::{NodeId, WotGraph};
+
+/// Follower edges a cascade simulation may try across all its runs; runs stop early past it
+pub const EDGE_BUDGET: u64 = 20_000_000;
+
+/// How a post is assumed to spread from its author through followers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// Independent cascade: each newly reached pubkey gets one chance, with the transmission
+    /// probability, to pass the post to each of its followers. Averaged over random runs.
+    Cascade,
+    /// Everyone `h` hops away along follower edges is reached with probability `p^h`
+    Decay,
+}
+
+impl Model {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "cascade" => Some(Self::Cascade),
+            "decay" => Some(Self::Decay),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cascade => "cascade",
+            Self::Decay => "decay",
+        }
+    }
+}
+
+/// Expected pubkeys first reached at each hop from the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reach {
+    /// `per_hop[i]` is the expected count at `i + 1` hops
+    pub per_hop: Vec<f64>,
+    /// Cascade runs averaged; fewer than asked when `EDGE_BUDGET` ran out, 0 for `Decay`
+    pub runs: usize,
+}
+
+/// Simulate a post by `source` spreading up to `max_hops` along follower edges, passed on with
+/// `probability` per edge (`Cascade`) or per hop (`Decay`). The same seed on the same graph
+/// gives the same result.
+pub fn simulate(
+    graph: &WotGraph,
+    source: NodeId,
+    model: Model,
+    probability: f64,
+    max_hops: u8,
+    runs: usize,
+    seed: u64,
+) -> Reach {
+    let max_hops = max_hops as usize;
+    graph.with_adjacency(|_, followers| {
+        if source as usize >= followers.len() {
+            return Reach { per_hop: vec![0.0; max_hops], runs: 0 };
+        }
+        // Run stamp per node, so each run starts from a clean set without clearing it
+        let mut reached = vec![0u32; followers.len()];
+        let (mut current, mut next) = (Vec::new(), Vec::new());
+        match model {
+            Model::Decay => {
+                reached[source as usize] = 1;
+                current.push(source);
+                let mut per_hop = Vec::with_capacity(max_hops);
+                for hops in 1..=max_hops {
+                    for &node in &current {
+                        for &follower in &followers[node as usize] {
+                            if std::mem::replace(&mut reached[follower as usize], 1) == 0 {
+                                next.push(follower);
+                            }
+                        }
+                    }
+                    per_hop.push(next.len() as f64 * probability.powi(hops as i32));
+                    current.clear();
+                    std::mem::swap(&mut current, &mut next);
+                }
+                Reach { per_hop, runs: 0 }
+            }
+            Model::Cascade => {
+                let mut rng = Rng::new(seed);
+                let mut counts = vec![0u64; max_hops];
+                let (mut edges, mut completed) = (0u64, 0);
+                while completed < runs && (completed == 0 || edges < EDGE_BUDGET) {
+                    completed += 1;
+                    let stamp = completed as u32;
+                    reached[source as usize] = stamp;
+                    current.clear();
+                    current.push(source);
+                    for count in counts.iter_mut() {
+                        for &node in &current {
+                            let list = &followers[node as usize];
+                            edges += list.len() as u64;
+                            for &follower in list {
+                                if reached[follower as usize] != stamp && rng.unit() < probability {
+                                    reached[follower as usize] = stamp;
+                                    next.push(follower);
+                                }
+                            }
+                        }
+                        *count += next.len() as u64;
+                        current.clear();
+                        std::mem::swap(&mut current, &mut next);
+                        if current.is_empty() {
+                            break;
+                        }
+                    }
+                }
+                let per_hop = counts.iter().map(|&count| count as f64 / completed as f64).collect();
+                Reach { per_hop, runs: completed }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// source <- a, b; a <- c; b <- c, d (arrows point at the followed pubkey)
+    fn tree() -> WotGraph {
+        let graph = WotGraph::new();
+        let follows = |names: &[&str]| names.iter().map(|name| pk(name).to_hex()).collect::<Vec<_>>();
+        graph.update_follows(&pk("a").to_hex(), &follows(&["source"]), None, Some(1));
+        graph.update_follows(&pk("b").to_hex(), &follows(&["source"]), None, Some(1));
+        graph.update_follows(&pk("c").to_hex(), &follows(&["a", "b"]), None, Some(1));
+        graph.update_follows(&pk("d").to_hex(), &follows(&["b"]), None, Some(1));
+        graph
+    }
+
+    #[test]
+    fn test_decay() {
+        let graph = tree();
+        let source = graph.node_id(&pk("source")).unwrap();
+        let reach = simulate(&graph, source, Model::Decay, 0.5, 3, 1, 0);
+        assert_eq!(reach, Reach { per_hop: vec![1.0, 0.5, 0.0], runs: 0 });
+    }
+
+    #[test]
+    fn test_cascade() {
+        let graph = tree();
+        let source = graph.node_id(&pk("source")).unwrap();
+        // Certain transmission reaches everyone at their distance
+        let reach = simulate(&graph, source, Model::Cascade, 1.0, 3, 10, 7);
+        assert_eq!(reach, Reach { per_hop: vec![2.0, 2.0, 0.0], runs: 10 });
+
+        // a and b are reached half the time; then c with 1 - 0.75^2 and d with 0.25
+        let reach = simulate(&graph, source, Model::Cascade, 0.5, 2, 1000, 7);
+        assert!((reach.per_hop[0] - 1.0).abs() < 0.1, "{:?}", reach);
+        assert_eq!(simulate(&graph, source, Model::Cascade, 0.5, 2, 1000, 7), reach);
+        assert!((reach.per_hop[1] - 0.6875).abs() < 0.1, "{:?}", reach);
+    }
+}
@@ -0,0 +1,563 @@
+//! Graph summary for weekly operator review: size, growth, degree distribution, top pubkeys,
+//! component structure and ingestion health. `wot-oracle report` builds it from a database,
+//! `GET /admin/report` from the running graphs.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::fmt::Write;
+use utoipa::ToSchema;
+
+use crate::config::GRAPH_NAME_DEFAULT;
+use crate::db::{Database, SyncState};
+use crate::diagnostics::{top_by_len, NodeDegree, RelayState};
+use crate::graph::components::component_sizes;
+use crate::graph::{NodeId, PageRank, WotGraph};
+use crate::namespace;
+use crate::sync::pause::IngestionStatus;
+use crate::sync::persistence::PersistenceStatsSnapshot;
+
+pub const DAYS_DEFAULT: u32 = 28;
+pub const DAYS_MAX: u32 = 365;
+const TOP_NODES: usize = 10;
+/// Component sizes listed beyond the largest
+const TOP_COMPONENTS: usize = 10;
+
+const USAGE: &str = "Usage: wot-oracle report [--db PATH] [--graph NAME] [--days N] [--format json|html]";
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GraphReport {
+    pub graph: String,
+    pub generated_at: i64,
+    pub size: GraphSize,
+    pub growth: Growth,
+    pub degrees: DegreeDistribution,
+    pub top: TopNodes,
+    pub components: ComponentStructure,
+    pub ingestion: IngestionHealth,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GraphSize {
+    pub nodes: usize,
+    pub edges: usize,
+    /// Pubkeys with a non-empty contact list
+    pub authors: usize,
+    pub quarantined: usize,
+    pub blocked: usize,
+    /// Contact lists rejected since the graph was loaded
+    pub rejected_lists: u64,
+    /// Follows per author
+    pub mean_follows: f64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Growth {
+    /// Days covered, ending now
+    pub days: u32,
+    pub new_pubkeys: u64,
+    pub follows_added: u64,
+    pub follows_removed: u64,
+    /// Days with any change, oldest first
+    pub daily: Vec<GrowthDay>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct GrowthDay {
+    /// Unix time the UTC day starts
+    pub day: i64,
+    /// Pubkeys first seen that day
+    pub new_pubkeys: u64,
+    pub follows_added: u64,
+    pub follows_removed: u64,
+}
+
+/// Degree histograms in power-of-two buckets, from 0 up to the largest degree
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DegreeDistribution {
+    pub followers: Vec<DegreeBucket>,
+    pub follows: Vec<DegreeBucket>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct DegreeBucket {
+    pub min: usize,
+    pub max: usize,
+    pub nodes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TopNodes {
+    pub most_followed: Vec<NodeDegree>,
+    pub most_following: Vec<NodeDegree>,
+    /// Empty until PageRank has been computed
+    pub pagerank: Vec<RankedNode>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RankedNode {
+    pub pubkey: String,
+    pub score: f64,
+}
+
+/// Weakly connected components, follow direction ignored
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ComponentStructure {
+    pub count: usize,
+    pub largest: usize,
+    /// Share of nodes in the largest component (0-1)
+    pub largest_share: f64,
+    /// Nodes with no follows in either direction
+    pub isolated: usize,
+    /// The largest components' sizes, largest first
+    pub sizes: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct IngestionHealth {
+    /// `created_at` of the newest stored contact list
+    pub latest_list_at: Option<i64>,
+    /// Where ingestion from each relay left off
+    pub sync_state: Vec<SyncState>,
+    /// State of the running service; absent from `wot-oracle report`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub live: Option<LiveIngestion>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LiveIngestion {
+    pub status: IngestionStatus,
+    pub persistence: PersistenceStatsSnapshot,
+    /// Ingestion relays as of their last health check
+    pub relays: Vec<RelayState>,
+}
+
+/// Summarize `graph` and its database over the last `days`. Ingestion health is left without
+/// the live part, which only the running service has. CPU-bound: call from a blocking thread.
+pub fn build(name: &str, graph: &WotGraph, db: &Database, ranks: &PageRank, days: u32, now: i64) -> Result<GraphReport> {
+    let stats = graph.stats();
+    let size = GraphSize {
+        nodes: stats.node_count,
+        edges: stats.edge_count,
+        authors: stats.nodes_with_follows,
+        quarantined: stats.quarantined_nodes,
+        blocked: graph.blocked_count(),
+        rejected_lists: stats.rejected_lists,
+        mean_follows: match stats.nodes_with_follows {
+            0 => 0.0,
+            authors => stats.edge_count as f64 / authors as f64,
+        },
+    };
+
+    let from_ts = now - days as i64 * 86_400;
+    let daily = merge_days(&db.daily_new_nodes(from_ts, now)?, &db.daily_edge_changes(from_ts, now)?);
+    let growth = Growth {
+        days,
+        new_pubkeys: daily.iter().map(|d| d.new_pubkeys).sum(),
+        follows_added: daily.iter().map(|d| d.follows_added).sum(),
+        follows_removed: daily.iter().map(|d| d.follows_removed).sum(),
+        daily,
+    };
+
+    let (degrees, sizes, isolated) = graph.with_adjacency(|follows, followers| {
+        let degrees = DegreeDistribution {
+            followers: degree_buckets(followers.iter().map(|list| list.len())),
+            follows: degree_buckets(follows.iter().map(|list| list.len())),
+        };
+        let isolated = follows.iter().zip(followers.iter()).filter(|(out, inc)| out.is_empty() && inc.is_empty()).count();
+        (degrees, component_sizes(follows), isolated)
+    });
+    let largest = sizes.first().map_or(0, |&size| size as usize);
+    let components = ComponentStructure {
+        count: sizes.len(),
+        largest,
+        largest_share: match stats.node_count {
+            0 => 0.0,
+            nodes => largest as f64 / nodes as f64,
+        },
+        isolated,
+        sizes: sizes.into_iter().take(TOP_COMPONENTS + 1).collect(),
+    };
+
+    let ranked = ranks.top(TOP_NODES);
+    let ids: Vec<NodeId> = ranked.iter().map(|&(id, _)| id).collect();
+    let pagerank = graph
+        .resolve_pubkeys(&ids)
+        .into_iter()
+        .zip(ranked)
+        .map(|(pubkey, (_, score))| RankedNode { pubkey: pubkey.to_hex(), score })
+        .collect();
+
+    Ok(GraphReport {
+        graph: name.to_string(),
+        generated_at: now,
+        size,
+        growth,
+        degrees,
+        top: TopNodes {
+            most_followed: top_by_len(graph, true, TOP_NODES),
+            most_following: top_by_len(graph, false, TOP_NODES),
+            pagerank,
+        },
+        components,
+        ingestion: IngestionHealth {
+            latest_list_at: db.latest_list_at()?,
+            sync_state: db.sync_states()?,
+            live: None,
+        },
+    })
+}
+
+/// Join per-day new pubkeys and edge changes, both sorted by day, into one timeline
+fn merge_days(new_nodes: &[(i64, u64)], edge_changes: &[(i64, u64, u64)]) -> Vec<GrowthDay> {
+    let mut days: std::collections::BTreeMap<i64, GrowthDay> = std::collections::BTreeMap::new();
+    let empty = |day| GrowthDay { day, new_pubkeys: 0, follows_added: 0, follows_removed: 0 };
+    for &(day, count) in new_nodes {
+        days.entry(day).or_insert_with(|| empty(day)).new_pubkeys = count;
+    }
+    for &(day, added, removed) in edge_changes {
+        let entry = days.entry(day).or_insert_with(|| empty(day));
+        entry.follows_added = added;
+        entry.follows_removed = removed;
+    }
+    days.into_values().collect()
+}
+
+/// Bucket 0 holds degree 0, bucket `i` degrees `2^(i-1)` to `2^i - 1`
+fn degree_buckets(degrees: impl Iterator<Item = usize>) -> Vec<DegreeBucket> {
+    let mut counts: Vec<usize> = Vec::new();
+    for degree in degrees {
+        let bucket = (usize::BITS - degree.leading_zeros()) as usize;
+        if counts.len() <= bucket {
+            counts.resize(bucket + 1, 0);
+        }
+        counts[bucket] += 1;
+    }
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, nodes)| match i {
+            0 => DegreeBucket { min: 0, max: 0, nodes },
+            i => DegreeBucket { min: 1 << (i - 1), max: (1 << i) - 1, nodes },
+        })
+        .collect()
+}
+
+/// Standalone HTML page with one section per report
+pub fn render_html(reports: &[GraphReport]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>WoT Oracle report</title>\n\
+         <style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin:0.5em 0 1.5em}\
+         td,th{border:1px solid #ccc;padding:2px 8px;text-align:right}td.l,th.l{text-align:left}\
+         code{font-size:0.85em}</style></head><body>\n",
+    );
+    for report in reports {
+        // Writing to a String cannot fail
+        let _ = write_section(&mut html, report);
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+fn write_section(html: &mut String, report: &GraphReport) -> std::fmt::Result {
+    writeln!(html, "<h1>Graph {}</h1>", escape(&report.graph))?;
+    writeln!(html, "<p>Generated {}</p>", date_time(Some(report.generated_at)))?;
+
+    let size = &report.size;
+    writeln!(html, "<h2>Size</h2><table>")?;
+    for (label, value) in [
+        ("Nodes", size.nodes.to_string()),
+        ("Edges", size.edges.to_string()),
+        ("Authors", size.authors.to_string()),
+        ("Follows per author", format!("{:.1}", size.mean_follows)),
+        ("Quarantined", size.quarantined.to_string()),
+        ("Blocked", size.blocked.to_string()),
+        ("Rejected lists", size.rejected_lists.to_string()),
+    ] {
+        writeln!(html, "<tr><th class=\"l\">{}</th><td>{}</td></tr>", label, value)?;
+    }
+    writeln!(html, "</table>")?;
+
+    let growth = &report.growth;
+    writeln!(
+        html,
+        "<h2>Growth, last {} days</h2><p>{} new pubkeys, {} follows added, {} removed</p>",
+        growth.days, growth.new_pubkeys, growth.follows_added, growth.follows_removed
+    )?;
+    writeln!(html, "<table><tr><th class=\"l\">Day</th><th>New pubkeys</th><th>Added</th><th>Removed</th></tr>")?;
+    for day in &growth.daily {
+        writeln!(
+            html,
+            "<tr><td class=\"l\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            date(day.day),
+            day.new_pubkeys,
+            day.follows_added,
+            day.follows_removed
+        )?;
+    }
+    writeln!(html, "</table>")?;
+
+    writeln!(html, "<h2>Degree distribution</h2>")?;
+    writeln!(html, "<table><tr><th class=\"l\">Degree</th><th>Followers</th><th>Follows</th></tr>")?;
+    let (followers, follows) = (&report.degrees.followers, &report.degrees.follows);
+    for i in 0..followers.len().max(follows.len()) {
+        let bucket = followers.get(i).or(follows.get(i)).expect("index below the longer histogram");
+        let nodes = |buckets: &[DegreeBucket]| buckets.get(i).map_or(0, |b| b.nodes);
+        let range = match bucket.min == bucket.max {
+            true => bucket.min.to_string(),
+            false => format!("{}-{}", bucket.min, bucket.max),
+        };
+        writeln!(
+            html,
+            "<tr><td class=\"l\">{}</td><td>{}</td><td>{}</td></tr>",
+            range,
+            nodes(followers),
+            nodes(follows)
+        )?;
+    }
+    writeln!(html, "</table>")?;
+
+    writeln!(html, "<h2>Top pubkeys</h2>")?;
+    for (title, nodes) in [("Most followed", &report.top.most_followed), ("Following the most", &report.top.most_following)] {
+        writeln!(html, "<table><tr><th class=\"l\">{}</th><th>Count</th></tr>", title)?;
+        for node in nodes {
+            writeln!(html, "<tr><td class=\"l\"><code>{}</code></td><td>{}</td></tr>", escape(&node.pubkey), node.count)?;
+        }
+        writeln!(html, "</table>")?;
+    }
+    writeln!(html, "<table><tr><th class=\"l\">PageRank</th><th>Score</th></tr>")?;
+    for node in &report.top.pagerank {
+        writeln!(html, "<tr><td class=\"l\"><code>{}</code></td><td>{:.6}</td></tr>", escape(&node.pubkey), node.score)?;
+    }
+    writeln!(html, "</table>")?;
+
+    let components = &report.components;
+    writeln!(
+        html,
+        "<h2>Components</h2><p>{} weakly connected components; the largest holds {} nodes ({:.1}%), {} nodes are isolated</p>",
+        components.count,
+        components.largest,
+        components.largest_share * 100.0,
+        components.isolated
+    )?;
+    let sizes: Vec<String> = components.sizes.iter().map(u32::to_string).collect();
+    writeln!(html, "<p>Largest sizes: {}</p>", sizes.join(", "))?;
+
+    let ingestion = &report.ingestion;
+    writeln!(html, "<h2>Ingestion</h2><p>Newest contact list: {}</p>", date_time(ingestion.latest_list_at))?;
+    if let Some(live) = &ingestion.live {
+        let status = match (&live.status.paused, &live.status.reason) {
+            (false, _) => "running".to_string(),
+            (true, Some(reason)) => format!("paused ({})", escape(reason)),
+            (true, None) => "paused".to_string(),
+        };
+        let persistence = &live.persistence;
+        writeln!(
+            html,
+            "<p>Ingestion {}; persistence queue {}, {} updates dropped, {} failed, last flush {}</p>",
+            status,
+            persistence.queue_depth,
+            persistence.dropped_updates,
+            persistence.updates_failed,
+            date_time(persistence.last_flush_at)
+        )?;
+        writeln!(html, "<table><tr><th class=\"l\">Relay</th><th class=\"l\">Connected</th><th>Down since</th></tr>")?;
+        for relay in &live.relays {
+            writeln!(
+                html,
+                "<tr><td class=\"l\">{}</td><td class=\"l\">{}</td><td>{}</td></tr>",
+                escape(&relay.url),
+                if relay.connected { "yes" } else { "no" },
+                date_time(relay.down_since)
+            )?;
+        }
+        writeln!(html, "</table>")?;
+    }
+    writeln!(html, "<table><tr><th class=\"l\">Relay</th><th>Last event</th><th>Last sync</th></tr>")?;
+    for state in &ingestion.sync_state {
+        writeln!(
+            html,
+            "<tr><td class=\"l\">{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&state.relay_url),
+            date_time(state.last_event_time),
+            date_time(state.last_sync_at)
+        )?;
+    }
+    writeln!(html, "</table>")
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn date(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0).map_or_else(|| ts.to_string(), |t| t.format("%Y-%m-%d").to_string())
+}
+
+fn date_time(ts: Option<i64>) -> String {
+    match ts.and_then(|ts| chrono::DateTime::from_timestamp(ts, 0)) {
+        Some(t) => t.format("%Y-%m-%d %H:%M UTC").to_string(),
+        None => "-".to_string(),
+    }
+}
+
+/// Options for `wot-oracle report`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportOptions {
+    /// Database to summarize; defaults to `DB_PATH`
+    pub db: String,
+    /// Name shown in the report; defaults to `GRAPH_NAME`
+    pub graph: String,
+    pub days: u32,
+    pub html: bool,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            db: std::env::var("DB_PATH").unwrap_or_else(|_| "wot.db".into()),
+            graph: std::env::var("GRAPH_NAME").unwrap_or_else(|_| GRAPH_NAME_DEFAULT.into()),
+            days: DAYS_DEFAULT,
+            html: false,
+        }
+    }
+}
+
+impl ReportOptions {
+    /// Parse the arguments following `report`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let Some(value) = (match flag.as_str() {
+                "--help" | "-h" => bail!(USAGE),
+                "--db" | "--graph" | "--days" | "--format" => args.next(),
+                _ => bail!("Unknown option {}\n{}", flag, USAGE),
+            }) else {
+                bail!("{} needs a value\n{}", flag, USAGE);
+            };
+            match flag.as_str() {
+                "--db" => options.db = value,
+                "--graph" => options.graph = value,
+                "--days" => match value.parse() {
+                    Ok(days) if (1..=DAYS_MAX).contains(&days) => options.days = days,
+                    _ => bail!("--days must be between 1 and {}", DAYS_MAX),
+                },
+                _ => match value.as_str() {
+                    "json" => options.html = false,
+                    "html" => options.html = true,
+                    _ => bail!("--format must be json or html"),
+                },
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Load the database into a graph, compute PageRank and summarize it
+pub fn run(options: &ReportOptions) -> Result<GraphReport> {
+    // Database::open would create an empty database at a mistyped path
+    if !std::path::Path::new(&options.db).exists() {
+        bail!("Database {} not found", options.db);
+    }
+    let db = Database::open(&options.db)?;
+    let graph = WotGraph::new();
+    namespace::load(&graph, &db)?;
+    let ranks = PageRank::new();
+    ranks.refresh(&graph);
+    build(&options.graph, &graph, &db, &ranks, options.days, chrono::Utc::now().timestamp())
+}
+
+/// Entry point for `wot-oracle report <options>`; prints the report to stdout
+pub fn main(args: impl IntoIterator<Item = String>) -> Result<()> {
+    let options = ReportOptions::parse(args)?;
+    let report = run(&options)?;
+    match options.html {
+        true => print!("{}", render_html(std::slice::from_ref(&report))),
+        false => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_parse_options() {
+        let options = ReportOptions::parse(["--db", "x.db", "--days", "7", "--format", "html"].map(String::from)).unwrap();
+        assert_eq!((options.db.as_str(), options.days, options.html), ("x.db", 7, true));
+        assert!(ReportOptions::parse(["--days".to_string()]).is_err());
+        assert!(ReportOptions::parse(["--days", "0"].map(String::from)).is_err());
+        assert!(ReportOptions::parse(["--format", "xml"].map(String::from)).is_err());
+        assert!(ReportOptions::parse(["--bogus".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_degree_buckets() {
+        assert_eq!(
+            degree_buckets([0, 1, 2, 3, 4, 0].into_iter()),
+            vec![
+                DegreeBucket { min: 0, max: 0, nodes: 2 },
+                DegreeBucket { min: 1, max: 1, nodes: 1 },
+                DegreeBucket { min: 2, max: 3, nodes: 2 },
+                DegreeBucket { min: 4, max: 7, nodes: 1 },
+            ]
+        );
+        assert!(degree_buckets(std::iter::empty()).is_empty());
+    }
+
+    #[test]
+    fn test_merge_days() {
+        assert_eq!(
+            merge_days(&[(0, 3)], &[(0, 5, 1), (86_400, 2, 0)]),
+            vec![
+                GrowthDay { day: 0, new_pubkeys: 3, follows_added: 5, follows_removed: 1 },
+                GrowthDay { day: 86_400, new_pubkeys: 0, follows_added: 2, follows_removed: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        let pk = |c: &str| c.repeat(64);
+        db.update_follows(&pk("a"), &[pk("b"), pk("c")], Some("e1"), Some(100)).unwrap();
+        db.update_follows(&pk("a"), &[pk("b"), pk("c"), pk("d")], Some("e2"), Some(200)).unwrap();
+        db.update_follows(&pk("e"), &[pk("f")], Some("e3"), Some(300)).unwrap();
+        db.set_sync_state("wss://<relay>.test", Some(300)).unwrap();
+        drop(db);
+
+        let options = ReportOptions {
+            db: temp_file.path().to_string_lossy().into(),
+            graph: "default".into(),
+            days: 7,
+            html: false,
+        };
+        let report = run(&options).unwrap();
+        assert_eq!((report.size.nodes, report.size.edges, report.size.authors), (6, 4, 2));
+        assert_eq!(report.growth.new_pubkeys, 6);
+        assert_eq!(report.components.sizes, vec![4, 2]);
+        assert_eq!((report.components.count, report.components.isolated), (2, 0));
+        assert_eq!(report.top.most_following[0].count, 3);
+        assert_eq!(report.top.pagerank.len(), 6);
+        assert_eq!(report.ingestion.latest_list_at, Some(300));
+        assert_eq!(report.ingestion.sync_state.len(), 1);
+
+        let html = render_html(&[report]);
+        assert!(html.contains("<h1>Graph default</h1>"));
+        assert!(html.contains("&lt;relay&gt;") && !html.contains("<relay>"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::cmp::Ordering;
+
+use crate::graph::synth::Rng;
+use crate::graph::{NodeId, WotGraph};
+use crate::types::Pubkey;
+
+/// How `GET /sample` weighs each eligible node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weighting {
+    /// Every node equally likely
+    Uniform,
+    /// Proportional to follows plus followers (nodes without edges are never drawn)
+    Degree,
+}
+
+impl Weighting {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "uniform" => Some(Self::Uniform),
+            "degree" => Some(Self::Degree),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Uniform => "uniform",
+            Self::Degree => "degree",
+        }
+    }
+}
+
+/// Sort key, node ID, follow count, follower count
+type Keyed = (f64, NodeId, usize, usize);
+
+/// A sampled node with its degrees
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sampled {
+    pub pubkey: Pubkey,
+    pub follow_count: usize,
+    pub follower_count: usize,
+}
+
+/// Up to `n` distinct nodes with at least `min_followers` followers, drawn without replacement.
+/// `population` is how many nodes could have been drawn. Each node gets the key `u^(1/w)` for
+/// a uniform `u` and its weight `w` (Efraimidis-Spirakis) and the highest keys win, so the same
+/// seed on the same graph gives the same sample. Pubkeys failing `allowed` are passed over.
+pub fn sample(
+    graph: &WotGraph,
+    n: usize,
+    min_followers: usize,
+    weighting: Weighting,
+    seed: u64,
+    allowed: impl Fn(&Pubkey) -> bool,
+) -> (usize, Vec<Sampled>) {
+    let mut rng = Rng::new(seed);
+    let mut keyed: Vec<Keyed> = graph.with_adjacency(|follows, followers| {
+        followers
+            .iter()
+            .zip(follows.iter())
+            .enumerate()
+            .filter(|(_, (theirs, _))| theirs.len() >= min_followers)
+            .filter_map(|(id, (theirs, ours))| {
+                let weight = match weighting {
+                    Weighting::Uniform => 1.0,
+                    Weighting::Degree => (ours.len() + theirs.len()) as f64,
+                };
+                // ln(u)/w orders like u^(1/w) without underflowing for heavy nodes
+                (weight > 0.0).then(|| (rng.unit().ln() / weight, id as NodeId, ours.len(), theirs.len()))
+            })
+            .collect()
+    });
+    let population = keyed.len();
+
+    // Only the head is ordered up front; the rest is sorted if access lists pass over too many
+    let by_key = |a: &Keyed, b: &Keyed| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal);
+    let head = (2 * n).min(population);
+    if head < population {
+        keyed.select_nth_unstable_by(head, by_key);
+    }
+    keyed[..head].sort_unstable_by(by_key);
+
+    let mut sampled = pick(graph, &keyed[..head], n, &allowed);
+    if sampled.len() < n && head < population {
+        keyed[head..].sort_unstable_by(by_key);
+        let more = pick(graph, &keyed[head..], n - sampled.len(), &allowed);
+        sampled.extend(more);
+    }
+    (population, sampled)
+}
+
+fn pick(graph: &WotGraph, candidates: &[Keyed], n: usize, allowed: impl Fn(&Pubkey) -> bool) -> Vec<Sampled> {
+    candidates
+        .iter()
+        .filter_map(|&(_, id, follow_count, follower_count)| {
+            let pubkey = graph.pubkey(id).filter(|pk| allowed(pk))?;
+            Some(Sampled { pubkey, follow_count, follower_count })
+        })
+        .take(n)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    fn star() -> WotGraph {
+        // hub is followed by everyone, so it has by far the highest degree
+        let graph = WotGraph::new();
+        for i in 0..50 {
+            graph.update_follows(&pk(&format!("leaf{}", i)).to_hex(), &[pk("hub").to_hex()], None, Some(1));
+        }
+        graph
+    }
+
+    #[test]
+    fn test_sample() {
+        let graph = star();
+        let (population, sampled) = sample(&graph, 10, 0, Weighting::Uniform, 7, |_| true);
+        assert_eq!(population, 51);
+        assert_eq!(sampled.len(), 10);
+        let mut pubkeys: Vec<_> = sampled.iter().map(|s| s.pubkey).collect();
+        pubkeys.sort();
+        pubkeys.dedup();
+        assert_eq!(pubkeys.len(), 10);
+
+        // Same seed, same sample
+        assert_eq!(sample(&graph, 10, 0, Weighting::Uniform, 7, |_| true).1, sampled);
+
+        // min_followers leaves only the hub
+        let (population, sampled) = sample(&graph, 10, 1, Weighting::Uniform, 7, |_| true);
+        assert_eq!(population, 1);
+        assert_eq!(sampled[0].follower_count, 50);
+
+        // Passed-over pubkeys are replaced from the rest, even past the ordered head
+        let (_, sampled) = sample(&graph, 10, 0, Weighting::Uniform, 7, |key| *key != pk("hub") && *key < pk("leaf2"));
+        assert_eq!(sampled.len(), 10);
+        assert!(sampled.iter().all(|s| s.pubkey < pk("leaf2")));
+    }
+
+    #[test]
+    fn test_degree_weighting() {
+        let graph = star();
+        // The hub carries half the total degree, so it is drawn first far more often than 1 in 51
+        let first_hub = (0..200)
+            .filter(|&seed| sample(&graph, 1, 0, Weighting::Degree, seed, |_| true).1[0].pubkey == pk("hub"))
+            .count();
+        assert!(first_hub > 60, "hub drawn first {} times", first_hub);
+        let uniform_hub = (0..200)
+            .filter(|&seed| sample(&graph, 1, 0, Weighting::Uniform, seed, |_| true).1[0].pubkey == pk("hub"))
+            .count();
+        assert!(uniform_hub < 20, "hub drawn first {} times", uniform_hub);
+    }
+}
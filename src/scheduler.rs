@@ -0,0 +1,358 @@
+//! Background recomputation jobs (PageRank, components, anchors, ...) on one scheduler instead
+//! of a loop per feature. Each job runs on a fixed interval or a cron expression, never overlaps
+//! itself, and keeps run statistics for `/stats`.
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Timelike, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error};
+use utoipa::ToSchema;
+
+/// When a job runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Right away, then every interval after each run started
+    Every(Duration),
+    /// At the minutes a five-field cron expression matches, in UTC
+    Cron(Cron),
+    Off,
+}
+
+impl Schedule {
+    /// Seconds (`300`), a five-field cron expression (`*/10 * * * *`), or `off`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("off") {
+            return Ok(Self::Off);
+        }
+        if let Ok(secs) = s.parse::<u64>() {
+            return match secs {
+                0 => Err("interval must be at least 1 second".to_string()),
+                secs => Ok(Self::Every(Duration::from_secs(secs))),
+            };
+        }
+        Cron::parse(s).map(Self::Cron)
+    }
+
+    /// Unix time of the first run after the one scheduled at `last`, or of the first run at or
+    /// after `now` when there was none; None when the job is off or the cron never matches
+    pub fn next_run(&self, last: Option<i64>, now: i64) -> Option<i64> {
+        match (self, last) {
+            (Self::Off, _) => None,
+            (Self::Every(_), None) => Some(now),
+            (Self::Every(interval), Some(last)) => Some(last + interval.as_secs() as i64),
+            (Self::Cron(cron), last) => cron.next_after(last.unwrap_or(now - 1)),
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Every(interval) => write!(f, "every {}s", interval.as_secs()),
+            Self::Cron(cron) => write!(f, "{}", cron.source),
+            Self::Off => write!(f, "off"),
+        }
+    }
+}
+
+/// Five-field cron expression: minute, hour, day of month, month, day of week (0 or 7 is
+/// Sunday). Fields take `*`, numbers, ranges `a-b`, steps `*/n` or `a-b/n`, and lists of those.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Standard cron: with both day fields restricted, either one matching is enough
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("expected seconds or 5 cron fields, got {:?}", s));
+        };
+        let mut weekdays_mask = cron_field(weekdays, 0, 7)?;
+        // 7 is Sunday too
+        if weekdays_mask & (1 << 7) != 0 {
+            weekdays_mask = (weekdays_mask | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            source: fields.join(" "),
+            minutes: cron_field(minutes, 0, 59)?,
+            hours: cron_field(hours, 0, 23)?,
+            days: cron_field(days, 1, 31)?,
+            months: cron_field(months, 1, 12)?,
+            weekdays: weekdays_mask,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// First matching minute strictly after `ts`; None if nothing matches within five years
+    /// (e.g. February 30th)
+    pub fn next_after(&self, ts: i64) -> Option<i64> {
+        let mut t = DateTime::<Utc>::from_timestamp(ts, 0)?.with_second(0)? + ChronoDuration::minutes(1);
+        let limit = t + ChronoDuration::days(5 * 366);
+        while t < limit {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 { (t.year() + 1, 1) } else { (t.year(), t.month() + 1) };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if !self.day_matches(t.date_naive()) {
+                t = (t.date_naive() + ChronoDuration::days(1)).and_hms_opt(0, 0, 0)?.and_utc();
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += ChronoDuration::minutes(1);
+            } else {
+                return Some(t.timestamp());
+            }
+        }
+        None
+    }
+}
+
+/// Bitmask of the values a cron field matches
+fn cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|&s| s > 0)),
+            None => (part, Some(1)),
+        };
+        let step = step.ok_or_else(|| format!("invalid step in {:?}", part))?;
+        let number = |s: &str| {
+            s.parse::<u32>().ok().filter(|n| (min..=max).contains(n)).ok_or_else(|| {
+                format!("{:?} is not a number between {} and {}", s, min, max)
+            })
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((from, to)) => (number(from)?, number(to)?),
+                // `a/n` runs from a to the end of the range
+                None if part.contains('/') => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if from > to {
+            return Err(format!("empty range {:?}", part));
+        }
+        for value in (from..=to).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+/// Run statistics of one job, for `/stats`
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct JobStats {
+    pub name: String,
+    /// `every Ns`, the cron expression, or `off`
+    pub schedule: String,
+    pub running: bool,
+    /// Completed runs, failed ones included
+    pub runs: u64,
+    pub failures: u64,
+    /// Scheduled runs skipped because the previous run was still going
+    pub skipped_runs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_started_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_duration_ms: Option<f64>,
+    /// Error of the last run, if it failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_run_at: Option<i64>,
+}
+
+/// A graph's background jobs and their statistics
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: RwLock<Vec<Arc<Mutex<JobStats>>>>,
+}
+
+impl Scheduler {
+    /// Run `job` on a blocking thread at each time `schedule` names. A run that is still going
+    /// when the next is due delays it rather than overlapping, and the slots it missed are
+    /// skipped. The job's statistics are listed even when it is off.
+    pub fn spawn<F>(&self, name: &str, schedule: Schedule, job: F)
+    where
+        F: Fn() -> Result<()> + Send + Sync + 'static,
+    {
+        let stats = Arc::new(Mutex::new(JobStats {
+            name: name.to_string(),
+            schedule: schedule.to_string(),
+            ..Default::default()
+        }));
+        self.jobs.write().push(stats.clone());
+        if schedule == Schedule::Off {
+            return;
+        }
+
+        let (name, job) = (name.to_string(), Arc::new(job));
+        tokio::spawn(async move {
+            let mut last = None;
+            loop {
+                let now = Utc::now().timestamp();
+                // Slots that passed while the last run was going are skipped
+                let mut next = schedule.next_run(last, now);
+                let mut skipped = 0;
+                while let Some(slot) = next.filter(|&slot| slot < now) {
+                    next = schedule.next_run(Some(slot), now);
+                    skipped += 1;
+                }
+                let Some(next) = next else {
+                    error!("Job {} has no upcoming run, stopping", name);
+                    return;
+                };
+                {
+                    let mut stats = stats.lock();
+                    stats.skipped_runs += skipped;
+                    stats.next_run_at = Some(next);
+                }
+                if next > now {
+                    tokio::time::sleep(Duration::from_secs((next - now) as u64)).await;
+                }
+
+                last = Some(next);
+                {
+                    let mut stats = stats.lock();
+                    stats.running = true;
+                    stats.last_started_at = Some(Utc::now().timestamp());
+                    stats.next_run_at = None;
+                }
+                let start = Instant::now();
+                let result = match tokio::task::spawn_blocking({
+                    let job = job.clone();
+                    move || job()
+                })
+                .await
+                {
+                    Ok(result) => result,
+                    Err(e) => Err(anyhow::anyhow!("task failed: {}", e)),
+                };
+                let elapsed = start.elapsed();
+
+                let mut stats = stats.lock();
+                stats.running = false;
+                stats.runs += 1;
+                stats.last_duration_ms = Some(elapsed.as_secs_f64() * 1000.0);
+                match result {
+                    Ok(()) => {
+                        debug!("Job {} finished in {:?}", name, elapsed);
+                        stats.last_error = None;
+                    }
+                    Err(e) => {
+                        error!("Job {} failed: {}", name, e);
+                        stats.failures += 1;
+                        stats.last_error = Some(e.to_string());
+                    }
+                }
+            }
+        });
+    }
+
+    /// Statistics of every job, in the order they were added
+    pub fn stats(&self) -> Vec<JobStats> {
+        self.jobs.read().iter().map(|stats| stats.lock().clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> i64 {
+        DateTime::parse_from_rfc3339(s).unwrap().timestamp()
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        assert_eq!(Schedule::parse("300"), Ok(Schedule::Every(Duration::from_secs(300))));
+        assert_eq!(Schedule::parse("off"), Ok(Schedule::Off));
+        assert!(Schedule::parse("0").is_err());
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+        assert_eq!(Schedule::parse(" */10  * * * * ").unwrap().to_string(), "*/10 * * * *");
+    }
+
+    #[test]
+    fn test_cron_next() {
+        let every_ten = Cron::parse("*/10 * * * *").unwrap();
+        assert_eq!(every_ten.next_after(ts("2024-01-01T00:00:00Z")), Some(ts("2024-01-01T00:10:00Z")));
+        assert_eq!(every_ten.next_after(ts("2024-01-01T00:09:59Z")), Some(ts("2024-01-01T00:10:00Z")));
+
+        // 03:30 on Sundays (7 is Sunday too); 2024-01-07 is a Sunday
+        let weekly = Cron::parse("30 3 * * 7").unwrap();
+        assert_eq!(weekly.next_after(ts("2024-01-01T12:00:00Z")), Some(ts("2024-01-07T03:30:00Z")));
+
+        // Across a year end, with a list and a range
+        let nightly = Cron::parse("0 1,22-23 * 12 *").unwrap();
+        assert_eq!(nightly.next_after(ts("2023-12-31T23:00:00Z")), Some(ts("2024-12-01T01:00:00Z")));
+
+        // Both day fields restricted: either matches (the 15th, or a Monday)
+        let either = Cron::parse("0 0 15 * 1").unwrap();
+        assert_eq!(either.next_after(ts("2024-01-09T00:00:00Z")), Some(ts("2024-01-15T00:00:00Z")));
+        assert_eq!(either.next_after(ts("2024-01-15T00:00:00Z")), Some(ts("2024-01-22T00:00:00Z")));
+
+        assert_eq!(Cron::parse("0 0 30 2 *").unwrap().next_after(0), None);
+    }
+
+    #[test]
+    fn test_next_run() {
+        let every = Schedule::Every(Duration::from_secs(60));
+        assert_eq!(every.next_run(None, 1000), Some(1000));
+        assert_eq!(every.next_run(Some(1000), 1010), Some(1060));
+        let cron = Schedule::parse("* * * * *").unwrap();
+        assert_eq!(cron.next_run(None, 120), Some(120));
+        assert_eq!(cron.next_run(Some(120), 125), Some(180));
+        assert_eq!(Schedule::Off.next_run(None, 0), None);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_stats() {
+        let scheduler = Scheduler::default();
+        let (tx, rx) = std::sync::mpsc::channel();
+        scheduler.spawn("fails", Schedule::Every(Duration::from_secs(3600)), move || {
+            let _ = tx.send(());
+            anyhow::bail!("boom")
+        });
+        scheduler.spawn("disabled", Schedule::Off, || Ok(()));
+
+        tokio::task::spawn_blocking(move || rx.recv_timeout(Duration::from_secs(5)).unwrap()).await.unwrap();
+        // Wait for the run to be recorded
+        for _ in 0..100 {
+            if scheduler.stats()[0].runs == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let stats = scheduler.stats();
+        assert_eq!((stats[0].runs, stats[0].failures), (1, 1));
+        assert_eq!(stats[0].last_error.as_deref(), Some("boom"));
+        assert_eq!(stats[0].schedule, "every 3600s");
+        assert_eq!((stats[1].name.as_str(), stats[1].schedule.as_str(), stats[1].runs), ("disabled", "off", 0));
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use super::ingestion::FollowUpdate;
+use crate::config::Config;
+use crate::graph::{NodeId, WotGraph};
+use crate::types::Pubkey;
+
+/// How an incoming contact list differs from the one currently applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowDiff {
+    pub previous: usize,
+    pub new: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl FollowDiff {
+    /// Diff `update` against the graph. None if the pubkey has no applied contact list yet
+    /// (a first list is never an anomaly) or `update` would not replace it.
+    pub fn against(graph: &WotGraph, update: &FollowUpdate) -> Option<Self> {
+        let pubkey = Pubkey::parse(&update.pubkey).ok()?;
+        let info = graph.get_node_info(&pubkey)?;
+        if info.kind3_created_at.is_some_and(|current| update.created_at <= current) {
+            return None;
+        }
+        let node_id = graph.node_id(&pubkey)?;
+
+        // Entries that aren't hex pubkeys are dropped when the list is applied
+        let new: HashSet<Pubkey> = update.follows.iter().filter_map(|pk| pk.parse().ok()).collect();
+        let known: HashSet<NodeId> = new.iter().filter_map(|pk| graph.node_id(pk)).collect();
+
+        let (previous, kept) = graph.with_adjacency(|follows, _| {
+            let old = follows.get(node_id as usize).unwrap_or_default();
+            (old.len(), old.iter().filter(|id| known.contains(id)).count())
+        });
+
+        Some(Self {
+            previous,
+            new: new.len(),
+            added: new.len() - kept,
+            removed: previous - kept,
+        })
+    }
+
+    pub fn changed(&self) -> usize {
+        self.added + self.removed
+    }
+}
+
+/// An anomalous update waiting out the hold window
+#[derive(Debug)]
+pub struct HeldUpdate {
+    pub update: FollowUpdate,
+    /// Row in `follow_anomalies`, if recording succeeded
+    pub anomaly_id: Option<i64>,
+    since: Instant,
+}
+
+/// Flags contact lists that replace a large share of the previous list at once, as a
+/// compromised key mass-following or wiping follows would. With a hold window, flagged
+/// updates are kept out of the graph until the window passes without a newer list
+/// from the same author; a newer list (anomalous or not) supersedes the held one.
+pub struct AnomalyDetector {
+    ratio: f64,
+    min_changes: usize,
+    max_changes: usize,
+    hold: Option<Duration>,
+    held: HashMap<String, HeldUpdate>,
+}
+
+impl AnomalyDetector {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            ratio: config.anomaly_change_ratio,
+            min_changes: config.anomaly_min_changes,
+            max_changes: config.anomaly_max_changes,
+            hold: (config.anomaly_hold_secs > 0).then(|| Duration::from_secs(config.anomaly_hold_secs)),
+            held: HashMap::new(),
+        }
+    }
+
+    pub fn is_anomalous(&self, diff: &FollowDiff) -> bool {
+        let changed = diff.changed();
+        if changed >= self.max_changes {
+            return true;
+        }
+        changed >= self.min_changes && changed as f64 > self.ratio * diff.previous.max(diff.new) as f64
+    }
+
+    /// Whether anomalous updates are held back rather than applied
+    pub fn holds(&self) -> bool {
+        self.hold.is_some()
+    }
+
+    pub fn has_held(&self) -> bool {
+        !self.held.is_empty()
+    }
+
+    /// Hold an anomalous update; returns the earlier held update it supersedes
+    pub fn hold(&mut self, update: FollowUpdate, anomaly_id: Option<i64>) -> Option<HeldUpdate> {
+        let held = HeldUpdate {
+            update,
+            anomaly_id,
+            since: Instant::now(),
+        };
+        self.held.insert(held.update.pubkey.clone(), held)
+    }
+
+    /// Drop the held update for a pubkey whose newer list is being applied
+    pub fn supersede(&mut self, pubkey: &str) -> Option<HeldUpdate> {
+        self.held.remove(pubkey)
+    }
+
+    /// Held updates whose window has passed, to be applied
+    pub fn release_expired(&mut self, now: Instant) -> Vec<HeldUpdate> {
+        let Some(hold) = self.hold else {
+            return Vec::new();
+        };
+        let expired: Vec<String> = self
+            .held
+            .iter()
+            .filter(|(_, held)| now.duration_since(held.since) >= hold)
+            .map(|(pubkey, _)| pubkey.clone())
+            .collect();
+        expired.into_iter().filter_map(|pubkey| self.held.remove(&pubkey)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The hex of a pubkey spelling out `name`, so tests read as names
+    fn hex(name: &str) -> String {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes).to_hex()
+    }
+
+    fn update(name: &str, follows: &[&str], created_at: i64) -> FollowUpdate {
+        FollowUpdate {
+            pubkey: hex(name),
+            follows: follows.iter().map(|f| hex(f)).collect(),
+            event_id: format!("{}-{}", name, created_at),
+            created_at,
+        }
+    }
+
+    fn detector(hold_secs: u64) -> AnomalyDetector {
+        let mut config = Config::from_env();
+        config.anomaly_change_ratio = 0.5;
+        config.anomaly_min_changes = 2;
+        config.anomaly_max_changes = 10;
+        config.anomaly_hold_secs = hold_secs;
+        AnomalyDetector::from_config(&config)
+    }
+
+    #[test]
+    fn test_diff_against_graph() {
+        let graph = WotGraph::new();
+        // First contact list: nothing to compare with
+        assert!(FollowDiff::against(&graph, &update("alice", &["bob", "carol"], 1)).is_none());
+        graph.update_follows(&hex("alice"), &[hex("bob"), hex("carol")], None, Some(1));
+
+        let diff = FollowDiff::against(&graph, &update("alice", &["carol", "dave", "erin"], 2)).unwrap();
+        assert_eq!(diff, FollowDiff { previous: 2, new: 3, added: 2, removed: 1 });
+
+        // Stale lists would not be applied anyway
+        assert!(FollowDiff::against(&graph, &update("alice", &[], 1)).is_none());
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let detector = detector(0);
+        let diff = |previous, new, added, removed| FollowDiff { previous, new, added, removed };
+
+        // Small lists never trip the ratio below min_changes
+        assert!(!detector.is_anomalous(&diff(1, 0, 0, 1)));
+        assert!(detector.is_anomalous(&diff(4, 0, 0, 4)));
+        assert!(!detector.is_anomalous(&diff(100, 102, 3, 1)));
+        // Absolute limit regardless of list size
+        assert!(detector.is_anomalous(&diff(1000, 1010, 10, 0)));
+    }
+
+    #[test]
+    fn test_hold_supersede_and_release() {
+        let mut detector = detector(60);
+        assert!(detector.holds());
+
+        assert!(detector.hold(update("alice", &[], 2), Some(1)).is_none());
+        let superseded = detector.hold(update("alice", &[], 3), Some(2)).unwrap();
+        assert_eq!(superseded.anomaly_id, Some(1));
+
+        assert!(detector.release_expired(Instant::now()).is_empty());
+        let released = detector.release_expired(Instant::now() + Duration::from_secs(61));
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].update.created_at, 3);
+        assert!(!detector.has_held());
+
+        detector.hold(update("bob", &[], 2), None);
+        assert!(detector.supersede(&hex("bob")).is_some());
+        assert!(!detector.has_held());
+    }
+}
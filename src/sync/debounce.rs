@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::ingestion::FollowUpdate;
+
+/// Coalesces contact lists from the same author arriving within a short window, so
+/// high-churn pubkeys (bots republishing every minute) cost one diff and write-lock cycle
+/// per window instead of one per event. The window runs from the first pending update,
+/// so constant republishing still lands once per window. Each update keeps the index of the
+/// relay pool that delivered it, so applied lists are still counted per pool.
+pub struct Debouncer {
+    window: Duration,
+    pending: HashMap<String, (FollowUpdate, usize, Instant)>,
+    coalesced: u64,
+}
+
+impl Debouncer {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: HashMap::new(),
+            coalesced: 0,
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Updates dropped in favour of a newer one from the same author
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced
+    }
+
+    /// Queue an update from `pool`, keeping only the newest per author
+    pub fn push(&mut self, update: FollowUpdate, pool: usize, now: Instant) {
+        match self.pending.get_mut(&update.pubkey) {
+            Some((pending, pending_pool, _)) => {
+                self.coalesced += 1;
+                if update.created_at > pending.created_at {
+                    *pending = update;
+                    *pending_pool = pool;
+                }
+            }
+            None => {
+                self.pending.insert(update.pubkey.clone(), (update, pool, now));
+            }
+        }
+    }
+
+    /// Updates whose window has passed, oldest first, each with the pool that delivered it
+    pub fn due(&mut self, now: Instant) -> Vec<(FollowUpdate, usize)> {
+        let mut due: Vec<(Instant, String)> = self
+            .pending
+            .iter()
+            .filter(|(_, (_, _, since))| now.duration_since(*since) >= self.window)
+            .map(|(pubkey, (_, _, since))| (*since, pubkey.clone()))
+            .collect();
+        due.sort();
+        due.into_iter()
+            .filter_map(|(_, pubkey)| self.pending.remove(&pubkey).map(|(update, pool, _)| (update, pool)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(pubkey: &str, created_at: i64) -> FollowUpdate {
+        FollowUpdate {
+            pubkey: pubkey.to_string(),
+            follows: Vec::new(),
+            event_id: format!("{}-{}", pubkey, created_at),
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_coalesces_per_author() {
+        let mut debouncer = Debouncer::new(Duration::from_secs(2));
+        let start = Instant::now();
+
+        debouncer.push(update("bot", 1), 0, start);
+        debouncer.push(update("alice", 5), 0, start + Duration::from_millis(500));
+        debouncer.push(update("bot", 3), 1, start + Duration::from_secs(1));
+        // Out-of-order older list doesn't replace the pending one, nor its pool
+        debouncer.push(update("bot", 2), 0, start + Duration::from_millis(1500));
+        assert_eq!(debouncer.coalesced(), 2);
+
+        assert!(debouncer.due(start + Duration::from_secs(1)).is_empty());
+        let due = debouncer.due(start + Duration::from_secs(2));
+        assert_eq!(due.len(), 1);
+        assert_eq!((due[0].0.created_at, due[0].1), (3, 1));
+
+        let due = debouncer.due(start + Duration::from_secs(3));
+        assert_eq!((due[0].0.pubkey.as_str(), due[0].1), ("alice", 0));
+        assert!(!debouncer.has_pending());
+    }
+}
@@ -0,0 +1,247 @@
+use nostr_sdk::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use super::watchlist::WATCHED_KINDS;
+use crate::db::Database;
+use crate::types::Pubkey;
+
+const GOSSIP_SUBSCRIPTION_ID: &str = "gossip";
+const AUTHORS_PER_FILTER: usize = 500;
+const RELAY_LIST_KIND: u16 = 10002;
+
+/// Relay -> authors subscribed on it
+type Plan = BTreeMap<String, BTreeSet<String>>;
+
+/// Outbox-model subscriptions (NIP-65) for watched and anchor pubkeys (`GOSSIP_ENABLED`): each
+/// author is followed on a few of their own write relays, with its own client, so their lists
+/// arrive without waiting on the configured relays to carry them. The plan is recomputed when
+/// the authors or their relay lists change, and only the relays whose author sets changed are
+/// resubscribed.
+pub struct Gossip {
+    client: Client,
+    per_author: usize,
+    max_relays: usize,
+    /// Relays for authors with no usable relay list yet (the configured relays)
+    fallback: Vec<String>,
+    authors: HashSet<String>,
+    current: Plan,
+}
+
+impl Gossip {
+    pub fn new(per_author: usize, max_relays: usize, fallback: Vec<String>) -> Self {
+        Self {
+            client: Client::default(),
+            per_author: per_author.max(1),
+            max_relays: max_relays.max(1),
+            fallback,
+            authors: HashSet::new(),
+            current: Plan::new(),
+        }
+    }
+
+    pub fn notifications(&self) -> broadcast::Receiver<RelayPoolNotification> {
+        self.client.notifications()
+    }
+
+    /// Whether a relay list from this author should trigger a refresh
+    pub fn tracks(&self, pubkey: &str) -> bool {
+        self.authors.contains(pubkey)
+    }
+
+    pub fn author_count(&self) -> usize {
+        self.authors.len()
+    }
+
+    pub fn relay_count(&self) -> usize {
+        self.current.len()
+    }
+
+    /// Re-plan for `authors` from their stored relay lists and apply the difference.
+    /// `watched` authors are already subscribed on the configured relays, so they get no
+    /// fallback subscription while their relay list is unknown.
+    pub async fn refresh(&mut self, db: &Database, authors: &[String], watched: &HashSet<String>) {
+        let mut relay_lists = BTreeMap::new();
+        let mut unlisted = Vec::new();
+        for author in authors.iter().filter(|pk| Pubkey::is_valid(pk)) {
+            let relays = match db.author_list(author, RELAY_LIST_KIND) {
+                Ok(tags) => tags.map(|tags| write_relays(&tags)).unwrap_or_default(),
+                Err(e) => {
+                    warn!("Failed to load relay list of {}: {}", author, e);
+                    Vec::new()
+                }
+            };
+            if !relays.is_empty() {
+                relay_lists.insert(author.clone(), relays);
+            } else if !watched.contains(author) {
+                unlisted.push(author.clone());
+            }
+        }
+        self.authors = authors.iter().cloned().collect();
+
+        let plan = plan(&relay_lists, &unlisted, &self.fallback, self.per_author, self.max_relays);
+        self.apply(plan).await;
+    }
+
+    /// Drop every subscription and relay, e.g. while ingestion is paused
+    pub async fn clear(&mut self) {
+        self.apply(Plan::new()).await;
+    }
+
+    async fn apply(&mut self, plan: Plan) {
+        let mut resubscribed = 0;
+        for relay in self.current.keys().filter(|relay| !plan.contains_key(*relay)) {
+            if let Err(e) = self.client.remove_relay(relay.as_str()).await {
+                debug!("Failed to remove gossip relay {}: {}", relay, e);
+            }
+        }
+        for (relay, authors) in &plan {
+            if self.current.get(relay) == Some(authors) {
+                continue;
+            }
+            if !self.current.contains_key(relay) {
+                if let Err(e) = self.client.add_relay(relay.as_str()).await {
+                    debug!("Failed to add gossip relay {}: {}", relay, e);
+                    continue;
+                }
+                if let Err(e) = self.client.connect_relay(relay.as_str()).await {
+                    debug!("Failed to connect gossip relay {}: {}", relay, e);
+                }
+            }
+            let keys: Vec<PublicKey> = authors.iter().filter_map(|pk| PublicKey::from_hex(pk).ok()).collect();
+            let filters: Vec<Filter> = keys
+                .chunks(AUTHORS_PER_FILTER)
+                .map(|chunk| Filter::new().kinds(WATCHED_KINDS.map(Kind::from)).authors(chunk.to_vec()))
+                .collect();
+            let id = SubscriptionId::new(GOSSIP_SUBSCRIPTION_ID);
+            match self.client.subscribe_with_id_to([relay.as_str()], id, filters, None).await {
+                Ok(_) => resubscribed += 1,
+                Err(e) => debug!("Failed to subscribe on gossip relay {}: {}", relay, e),
+            }
+        }
+        if resubscribed > 0 || plan.len() != self.current.len() {
+            info!(
+                "Gossip subscriptions: {} authors on {} relays ({} resubscribed)",
+                self.authors.len(),
+                plan.len(),
+                resubscribed
+            );
+        }
+        self.current = plan;
+    }
+}
+
+/// Write relays of a NIP-65 relay list: `r` tags without a marker or marked `write`,
+/// normalized, and limited to public ws/wss URLs
+pub fn write_relays(tags: &[Vec<String>]) -> Vec<String> {
+    let mut relays = Vec::new();
+    for tag in tags {
+        if tag.first().map(String::as_str) != Some("r") || tag.get(2).is_some_and(|marker| marker != "write") {
+            continue;
+        }
+        let Some(url) = tag.get(1).and_then(|url| Url::parse(url.trim()).ok()) else {
+            continue;
+        };
+        let public = url.host_str().is_some_and(|host| host != "localhost" && !host.starts_with("127."));
+        if matches!(url.scheme(), "ws" | "wss") && public {
+            let url = url.as_str().trim_end_matches('/').to_string();
+            if !relays.contains(&url) {
+                relays.push(url);
+            }
+        }
+    }
+    relays
+}
+
+/// Assign each author up to `per_author` of their relays, using at most `max_relays` relays in
+/// all and preferring those shared by more authors, so fewer connections cover everyone.
+/// Authors left without a relay, and `unlisted` ones, go on every `fallback` relay.
+fn plan(
+    relay_lists: &BTreeMap<String, Vec<String>>,
+    unlisted: &[String],
+    fallback: &[String],
+    per_author: usize,
+    max_relays: usize,
+) -> Plan {
+    let mut popularity: HashMap<&str, usize> = HashMap::new();
+    for relays in relay_lists.values() {
+        for relay in relays {
+            *popularity.entry(relay.as_str()).or_default() += 1;
+        }
+    }
+    let mut ranked: Vec<(&str, usize)> = popularity.into_iter().collect();
+    ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let rank: HashMap<&str, usize> = ranked.iter().take(max_relays).enumerate().map(|(i, &(relay, _))| (relay, i)).collect();
+
+    let mut plan = Plan::new();
+    let mut homeless: Vec<&String> = unlisted.iter().collect();
+    for (author, relays) in relay_lists {
+        let mut usable: Vec<&String> = relays.iter().filter(|relay| rank.contains_key(relay.as_str())).collect();
+        if usable.is_empty() {
+            homeless.push(author);
+            continue;
+        }
+        usable.sort_by_key(|relay| rank[relay.as_str()]);
+        for relay in usable.into_iter().take(per_author) {
+            plan.entry(relay.clone()).or_default().insert(author.clone());
+        }
+    }
+    if !homeless.is_empty() {
+        for relay in fallback {
+            let relay = relay.trim_end_matches('/').to_string();
+            plan.entry(relay).or_default().extend(homeless.iter().map(|author| (*author).clone()));
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(entries: &[&[&str]]) -> Vec<Vec<String>> {
+        entries.iter().map(|tag| tag.iter().map(|s| s.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn test_write_relays() {
+        let list = tags(&[
+            &["r", "wss://both.example/"],
+            &["r", "wss://write.example", "write"],
+            &["r", "wss://read.example", "read"],
+            &["r", "https://not-a-relay.example"],
+            &["r", "ws://localhost:7777"],
+            &["r", "wss://both.example"],
+            &["p", "wss://wrong-tag.example"],
+        ]);
+        assert_eq!(write_relays(&list), vec!["wss://both.example", "wss://write.example"]);
+    }
+
+    #[test]
+    fn test_plan() {
+        let lists: BTreeMap<String, Vec<String>> = [
+            ("a", vec!["wss://shared", "wss://a1", "wss://a2"]),
+            ("b", vec!["wss://b1", "wss://shared"]),
+            ("c", vec!["wss://c1"]),
+        ]
+        .into_iter()
+        .map(|(author, relays)| (author.to_string(), relays.into_iter().map(String::from).collect()))
+        .collect();
+        let fallback = vec!["wss://fallback/".to_string()];
+
+        // Shared relays first, two per author
+        let plan = plan(&lists, &["d".to_string()], &fallback, 2, 10);
+        let authors = |relay: &str| plan.get(relay).map(|a| a.iter().map(String::as_str).collect::<Vec<_>>());
+        assert_eq!(authors("wss://shared"), Some(vec!["a", "b"]));
+        assert_eq!(authors("wss://a1"), Some(vec!["a"]));
+        assert_eq!(authors("wss://a2"), None);
+        assert_eq!(authors("wss://c1"), Some(vec!["c"]));
+        assert_eq!(authors("wss://fallback"), Some(vec!["d"]));
+
+        // With one relay allowed, authors not on it fall back
+        let capped = super::plan(&lists, &[], &fallback, 2, 1);
+        assert_eq!(capped.len(), 2);
+        assert_eq!(capped["wss://fallback"].iter().collect::<Vec<_>>(), vec!["c"]);
+    }
+}
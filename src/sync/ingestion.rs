@@ -1,174 +1,803 @@
 use anyhow::Result;
-use lru::LruCache;
 use nostr_sdk::prelude::*;
-use std::num::NonZeroUsize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{info, warn, error, debug};
 
-use crate::db::{Database, FollowUpdateBatch};
+use super::anomaly::{AnomalyDetector, FollowDiff, HeldUpdate};
+use super::debounce::Debouncer;
+use super::gossip::Gossip;
+use super::pause::IngestionPause;
+use super::persistence::PersistenceStats;
+use super::seen::SeenCache;
+use super::sightings::RelaySightings;
+use super::watchlist::{Watchlist, WATCHED_KINDS};
+use crate::alert::{AlertKind, Alerts};
+use crate::config::{Config, RelayMode, RelaySettings};
+use crate::db::{Database, FollowAnomaly, FollowUpdateBatch};
+use crate::diagnostics::Diagnostics;
 use crate::graph::WotGraph;
+use crate::types::Pubkey;
 
-const SEEN_CACHE_CAPACITY: usize = 100_000;
-
-/// Tracks the latest seen event for a pubkey (for deduplication)
-#[derive(Debug, Clone)]
-struct SeenEvent {
-    created_at: u64,
-    #[allow(dead_code)]
-    event_id: EventId,
-}
+const FIREHOSE_QUEUE_SIZE: usize = 10_000;
+const WATCHLIST_SUBSCRIPTION_ID: &str = "watchlist";
+const WATCHLIST_AUTHORS_PER_FILTER: usize = 500;
+/// How far before a pause catch-up subscriptions start, for events relays received late
+const CATCH_UP_MARGIN_SECS: i64 = 600;
 
 pub struct Ingestion {
     graph: Arc<WotGraph>,
     db: Arc<Database>,
-    relays: Vec<String>,
+    config: Arc<Config>,
+    seen: Arc<SeenCache>,
+    sightings: Arc<RelaySightings>,
+    watchlist: Arc<Watchlist>,
+    pause: Arc<IngestionPause>,
+    alerts: Alerts,
+    diagnostics: Arc<Diagnostics>,
+    persistence: Arc<PersistenceStats>,
 }
 
 #[derive(Debug)]
-struct FollowUpdate {
-    pubkey: String,
-    follows: Vec<String>,
-    event_id: String,
-    created_at: i64,
+pub struct FollowUpdate {
+    pub pubkey: String,
+    pub follows: Vec<String>,
+    pub event_id: String,
+    pub created_at: i64,
 }
 
 impl Ingestion {
-    pub fn new(graph: Arc<WotGraph>, db: Arc<Database>, relays: Vec<String>) -> Self {
-        Self { graph, db, relays }
+    #[allow(clippy::too_many_arguments)] // Shared handles owned by the graph namespace and main
+    pub fn new(
+        graph: Arc<WotGraph>,
+        db: Arc<Database>,
+        config: Arc<Config>,
+        seen: Arc<SeenCache>,
+        sightings: Arc<RelaySightings>,
+        watchlist: Arc<Watchlist>,
+        pause: Arc<IngestionPause>,
+        alerts: Alerts,
+        diagnostics: Arc<Diagnostics>,
+        persistence: Arc<PersistenceStats>,
+    ) -> Self {
+        Self { graph, db, config, seen, sightings, watchlist, pause, alerts, diagnostics, persistence }
     }
 
     pub async fn start(&self) -> Result<()> {
-        info!("Starting ingestion from {} relays", self.relays.len());
+        info!(
+            "Starting ingestion from {} relays ({} watched authors)",
+            self.config.relays.len(),
+            self.watchlist.len()
+        );
 
         // Channel for database persistence
-        let (persist_tx, persist_rx) = mpsc::channel::<FollowUpdate>(10000);
+        let (persist_tx, persist_rx) = mpsc::channel::<FollowUpdate>(self.config.persist_queue_size);
 
         // Start persistence worker
         let db = self.db.clone();
+        let alerts = self.alerts.clone();
+        let stats = self.persistence.clone();
+        let batch_size = self.config.persist_batch_size;
+        let flush_interval = Duration::from_millis(self.config.persist_flush_interval_ms);
         tokio::spawn(async move {
-            persistence_worker(db, persist_rx).await;
+            persistence_worker(db, persist_rx, alerts, stats, batch_size, flush_interval).await;
         });
 
-        // Create nostr client
-        let client = Client::default();
+        // Firehose clients, each on its own relay group, merged into one event stream;
+        // discovery relays only serve the watched authors' subscriptions
+        let firehose_relays: Vec<String> = self
+            .config
+            .relays
+            .iter()
+            .filter(|relay| self.config.relay_settings(relay).mode == RelayMode::Read)
+            .cloned()
+            .collect();
+        let pools = partition_relays(&firehose_relays, self.config.ingestion_pools);
+        let (firehose_tx, mut firehose_rx) = mpsc::channel::<(usize, Box<Event>)>(FIREHOSE_QUEUE_SIZE);
+        let mut clients = Vec::with_capacity(pools.len());
+        let pool_stats: Arc<Vec<PoolStats>> = Arc::new(pools.iter().map(|_| PoolStats::default()).collect());
+        let mut pause_changes = self.pause.subscribe();
+        let mut paused_at = {
+            let status = pause_changes.borrow_and_update();
+            status.paused.then(|| status.paused_at.unwrap_or_else(|| chrono::Utc::now().timestamp()))
+        };
 
-        // Add relays
-        for relay_url in &self.relays {
-            match client.add_relay(relay_url).await {
-                Ok(_) => info!("Added relay: {}", relay_url),
-                Err(e) => warn!("Failed to add relay {}: {}", relay_url, e),
+        for (pool, relays) in pools.iter().enumerate() {
+            let client = Client::default();
+            for relay_url in relays {
+                match client.add_relay(relay_url).await {
+                    Ok(_) => info!("Added relay to pool {}: {}", pool, relay_url),
+                    Err(e) => warn!("Failed to add relay {}: {}", relay_url, e),
+                }
             }
-        }
+            client.connect().await;
 
-        // Connect to relays
-        client.connect().await;
+            let notifications = client.notifications();
+            let gates = relays
+                .iter()
+                .map(|relay| (relay.trim_end_matches('/').to_string(), RelayGate::new(&self.config.relay_settings(relay))))
+                .filter(|(_, gate)| !gate.is_open())
+                .collect();
+            tokio::spawn(forward_notifications(pool, notifications, gates, firehose_tx.clone(), pool_stats.clone(), self.sightings.clone()));
 
-        // Subscribe to kind:3 (contact list) events
-        let filter = Filter::new().kind(Kind::ContactList);
+            // Subscribe to each relay's kinds (contact lists by default), unless started paused
+            if paused_at.is_none() {
+                subscribe_firehose(&client, relays, &self.config, None).await;
+            }
+            clients.push(client);
+        }
+        drop(firehose_tx);
 
-        info!("Subscribing to kind:3 events...");
+        info!("Subscribed to kind:3 events on {} pools", clients.len());
 
         let graph = self.graph.clone();
+        let db = self.db.clone();
         let persist_tx = persist_tx.clone();
+        let alerts = self.alerts.clone();
+        let persistence = self.persistence.clone();
+        let mut anomalies = AnomalyDetector::from_config(&self.config);
+        let mut release_interval = tokio::time::interval(Duration::from_secs(10));
+        let mut health = IngestionHealth::new(&self.config);
+        let mut health_interval = tokio::time::interval(Duration::from_secs(30));
+        let debounce_window = Duration::from_millis(self.config.ingestion_debounce_ms);
+        let mut debouncer = (!debounce_window.is_zero()).then(|| Debouncer::new(debounce_window));
+        let mut debounce_interval =
+            tokio::time::interval((debounce_window / 4).max(Duration::from_millis(50)));
 
-        // LRU cache for deduplication: pubkey bytes → latest seen event
-        // Evicts oldest entries when full, never clears entirely
-        let seen_events: Arc<tokio::sync::RwLock<LruCache<[u8; 32], SeenEvent>>> =
-            Arc::new(tokio::sync::RwLock::new(LruCache::new(
-                NonZeroUsize::new(SEEN_CACHE_CAPACITY).unwrap()
-            )));
+        let seen_events = self.seen.clone();
 
-        // Handle events
-        client
-            .subscribe(vec![filter], None)
-            .await?;
+        // Watched authors get their own client, so their events are neither queued behind
+        // nor dropped with a lagging firehose stream
+        let priority_client = Client::default();
+        for relay_url in &self.config.relays {
+            if let Err(e) = priority_client.add_relay(relay_url).await {
+                warn!("Failed to add watchlist relay {}: {}", relay_url, e);
+            }
+        }
+        priority_client.connect().await;
+        let mut watchlist_changes = self.watchlist.subscribe();
+        watchlist_changes.borrow_and_update();
+        if paused_at.is_none() {
+            subscribe_watchlist(&priority_client, &self.watchlist, None).await;
+        }
+
+        // With GOSSIP_ENABLED, watched and anchor pubkeys are also followed on their own write
+        // relays; the plan is recomputed when either set or one of their relay lists changes
+        let mut gossip = Gossip::new(
+            self.config.gossip_relays_per_author,
+            self.config.gossip_max_relays,
+            self.config.relays.clone(),
+        );
+        let mut gossip_notifications = gossip.notifications();
+        let mut gossip_dirty = self.config.gossip_enabled;
+        let mut gossip_interval = tokio::time::interval(Duration::from_secs(30));
 
         // Process events
-        let mut notifications = client.notifications();
+        let mut priority_notifications = priority_client.notifications();
         let mut event_count: u64 = 0;
+        let mut priority_count: u64 = 0;
         let mut dedup_skip_count: u64 = 0;
+        let mut pool_counts = vec![PoolCounts::default(); clients.len()];
         let mut last_log_time = std::time::Instant::now();
 
         loop {
-            tokio::select! {
-                Ok(notification) = notifications.recv() => {
-                    if let RelayPoolNotification::Event { event, .. } = notification {
-                        let pubkey_bytes = event.pubkey.to_bytes();
-                        let event_created_at = event.created_at.as_u64();
-
-                        // Early dedup check BEFORE parsing tags
-                        // Skip if we've already seen a newer or equal event for this pubkey
-                        let dominated = {
-                            let seen = seen_events.read().await;
-                            if let Some(existing) = seen.peek(&pubkey_bytes) {
-                                event_created_at <= existing.created_at
-                            } else {
-                                false
+            // Housekeeping first (rarely ready), then watched authors ahead of the firehose
+            // Firehose events carry their pool; None marks the watchlist and gossip subscriptions
+            let (event, pool) = tokio::select! {
+                biased;
+                Ok(()) = watchlist_changes.changed() => {
+                    // A paused subscription picks up the new set on resume
+                    if paused_at.is_none() {
+                        subscribe_watchlist(&priority_client, &self.watchlist, None).await;
+                    }
+                    gossip_dirty = self.config.gossip_enabled;
+                    continue;
+                }
+                Ok(()) = pause_changes.changed() => {
+                    let status = pause_changes.borrow_and_update().clone();
+                    match (status.paused, paused_at) {
+                        (true, None) => {
+                            for client in clients.iter().chain([&priority_client]) {
+                                client.unsubscribe_all().await;
                             }
-                        };
-                        if dominated {
-                            dedup_skip_count += 1;
-                            continue;
+                            gossip.clear().await;
+                            paused_at = Some(status.paused_at.unwrap_or_else(|| chrono::Utc::now().timestamp()));
+                            info!("Ingestion paused ({})", status.reason.as_deref().unwrap_or("no reason given"));
                         }
-
-                        // Process the event (parse tags, extract follows)
-                        if let Some(update) = process_event(&event) {
-                            // Update in-memory graph (has its own timestamp check)
-                            let updated = graph.update_follows(
-                                &update.pubkey,
-                                &update.follows,
-                                Some(update.event_id.clone()),
-                                Some(update.created_at),
-                            );
-
-                            if updated {
+                        (false, Some(at)) => {
+                            // Relays replay what was published while paused; the seen cache drops the overlap
+                            let since = Timestamp::from((at - CATCH_UP_MARGIN_SECS).max(0) as u64);
+                            for (client, relays) in clients.iter().zip(&pools) {
+                                subscribe_firehose(client, relays, &self.config, Some(since)).await;
+                            }
+                            subscribe_watchlist(&priority_client, &self.watchlist, Some(since)).await;
+                            gossip_dirty = self.config.gossip_enabled;
+                            health.resumed(chrono::Utc::now().timestamp());
+                            paused_at = None;
+                            info!("Ingestion resumed, catching up from {}", since.as_u64());
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                _ = release_interval.tick(), if anomalies.has_held() && paused_at.is_none() => {
+                    for held in anomalies.release_expired(std::time::Instant::now()) {
+                        info!("Hold window passed, applying contact list from {}", held.update.pubkey);
+                        resolve_anomaly(&db, &held, "released");
+                        if apply_update(&graph, &persist_tx, &persistence, &alerts, held.update) {
+                            event_count += 1;
+                        }
+                    }
+                    continue;
+                }
+                _ = debounce_interval.tick(), if paused_at.is_none() && debouncer.as_ref().is_some_and(Debouncer::has_pending) => {
+                    let due = debouncer.as_mut().map(|d| d.due(std::time::Instant::now())).unwrap_or_default();
+                    for (update, pool) in due {
+                        if let Some(update) = screen_update(&graph, &db, &alerts, &mut anomalies, update) {
+                            if apply_update(&graph, &persist_tx, &persistence, &alerts, update) {
                                 event_count += 1;
-
-                                // Update seen cache AFTER successful graph update
-                                {
-                                    let mut seen = seen_events.write().await;
-                                    seen.put(pubkey_bytes, SeenEvent {
-                                        created_at: event_created_at,
-                                        event_id: event.id,
-                                    });
-                                }
-
-                                // Send to persistence worker
-                                if let Err(e) = persist_tx.try_send(update) {
-                                    warn!("Persistence queue full: {}", e);
-                                }
+                                pool_counts[pool].applied += 1;
                             }
                         }
-
-                        // Log progress periodically
-                        if last_log_time.elapsed() > Duration::from_secs(10) {
-                            let stats = graph.stats();
-                            let seen_size = seen_events.read().await.len();
-                            info!(
-                                "Sync progress: {} events, {} dedup skips, {} nodes, {} edges, seen_cache={}",
-                                event_count, dedup_skip_count, stats.node_count, stats.edge_count, seen_size
-                            );
-                            last_log_time = std::time::Instant::now();
+                    }
+                    continue;
+                }
+                _ = gossip_interval.tick(), if gossip_dirty && paused_at.is_none() => {
+                    let watched: HashSet<String> = self.watchlist.pubkeys().into_iter().collect();
+                    gossip.refresh(&db, &gossip_authors(&self.config, &watched), &watched).await;
+                    gossip_dirty = false;
+                    continue;
+                }
+                _ = health_interval.tick() => {
+                    let now = chrono::Utc::now().timestamp();
+                    for client in &clients {
+                        for (url, relay) in client.relays().await {
+                            let connected = relay.status().await == RelayStatus::Connected;
+                            self.diagnostics.set_relay(url.as_str(), connected, now);
+                            if let Some(down_secs) = health.relay_status(url.as_str(), connected, now) {
+                                alerts.raise(
+                                    AlertKind::RelayDown,
+                                    url.to_string(),
+                                    format!("Ingestion relay {} disconnected for {} minutes", url, down_secs / 60),
+                                );
+                            }
                         }
                     }
+                    if let Some(lag_secs) = health.lag(now).filter(|_| paused_at.is_none()) {
+                        alerts.raise(
+                            AlertKind::IngestionLag,
+                            "ingestion",
+                            format!("No new contact lists for {} seconds", lag_secs),
+                        );
+                    }
+                    continue;
                 }
+                Ok(notification) = priority_notifications.recv() => match notification {
+                    RelayPoolNotification::Event { event, .. } => (event, None),
+                    notification => {
+                        note_sighting(&self.sightings, &notification);
+                        continue;
+                    }
+                },
+                Ok(notification) = gossip_notifications.recv() => match notification {
+                    RelayPoolNotification::Event { event, .. } => (event, None),
+                    notification => {
+                        note_sighting(&self.sightings, &notification);
+                        continue;
+                    }
+                },
+                Some((pool, event)) = firehose_rx.recv() => (event, Some(pool)),
                 _ = tokio::time::sleep(Duration::from_secs(60)) => {
                     // Periodic status log
                     let stats = graph.stats();
-                    let seen_size = seen_events.read().await.len();
+                    let seen_size = seen_events.len().await;
                     info!(
-                        "Sync status: {} events, {} dedup skips, {} nodes, {} edges, seen_cache={}",
-                        event_count, dedup_skip_count, stats.node_count, stats.edge_count, seen_size
+                        "Sync status: {} events ({} watched), {} dedup skips, {} coalesced, {} nodes, {} edges, seen_cache={}",
+                        event_count,
+                        priority_count,
+                        dedup_skip_count,
+                        debouncer.as_ref().map_or(0, Debouncer::coalesced),
+                        stats.node_count,
+                        stats.edge_count,
+                        seen_size
                     );
+                    if self.config.gossip_enabled {
+                        info!("Gossip: {} authors on {} relays", gossip.author_count(), gossip.relay_count());
+                    }
+                    if clients.len() > 1 || !self.config.relay_settings.is_empty() {
+                        for (pool, counts) in pool_counts.iter().enumerate() {
+                            info!(
+                                "Pool {}: {} received, {} applied, {} dedup skips, {} lagged, {} filtered, {} rate limited",
+                                pool,
+                                pool_stats[pool].received.load(Ordering::Relaxed),
+                                counts.applied,
+                                counts.dedup_skipped,
+                                pool_stats[pool].lagged.load(Ordering::Relaxed),
+                                pool_stats[pool].filtered.load(Ordering::Relaxed),
+                                pool_stats[pool].rate_limited.load(Ordering::Relaxed)
+                            );
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            // Events already queued when ingestion paused are replayed by the catch-up
+            if paused_at.is_some() {
+                continue;
+            }
+
+            let pubkey_bytes = event.pubkey.to_bytes();
+            let event_created_at = event.created_at.as_u64();
+
+            if pool.is_none() {
+                priority_count += 1;
+                self.watchlist.saw(&event.pubkey.to_hex(), event.kind.as_u16(), event_created_at as i64);
+            }
+            // Mute lists, relay lists and reports, from watched authors or relays subscribed to them
+            if event.kind != Kind::ContactList {
+                let author = event.pubkey.to_hex();
+                if !graph.is_blocked(&author) && record_other_kind(&db, &event) && event.kind == Kind::RelayList {
+                    gossip_dirty |= gossip.tracks(&author);
+                }
+                continue;
+            }
+            health.saw_event(event_created_at as i64);
+
+            // Early dedup check BEFORE parsing tags
+            // Skip if we've already seen a newer or equal event for this pubkey
+            if seen_events.is_dominated(&pubkey_bytes, event_created_at).await {
+                dedup_skip_count += 1;
+                if let Some(pool) = pool {
+                    pool_counts[pool].dedup_skipped += 1;
+                }
+                continue;
+            }
+
+            // Absurdly long lists are dropped, or cut down, before their tags are parsed
+            let Some(tag_count) = graph.admit_tag_count(event.tags.len()) else {
+                debug!("Rejected contact list from {} with {} tags", event.pubkey, event.tags.len());
+                seen_events.mark(pubkey_bytes, event_created_at).await;
+                continue;
+            };
+
+            // Process the event (parse tags, extract follows)
+            if let Some(mut update) = process_event(&event, tag_count) {
+                graph.count_duplicate_follows(dedup_follows(&mut update.follows));
+                // Blocked authors are dropped; blocked follows are stripped before persisting
+                if graph.is_blocked(&update.pubkey) {
+                    seen_events.mark(pubkey_bytes, event_created_at).await;
+                    continue;
+                }
+                graph.strip_blocked(&mut update.follows);
+
+                // Oversized lists are dropped before they cost a diff or a debounce slot
+                if !graph.admit_follow_count(update.follows.len()) {
+                    debug!("Rejected contact list from {} with {} follows", &update.pubkey[..8], update.follows.len());
+                    seen_events.mark(pubkey_bytes, event_created_at).await;
+                    continue;
                 }
+
+                // Firehose lists wait out the debounce window; watched authors skip it.
+                // Queued lists count as seen, so relay duplicates are dropped early.
+                if let (Some(debouncer), Some(pool)) = (debouncer.as_mut(), pool) {
+                    debouncer.push(update, pool, std::time::Instant::now());
+                    seen_events.mark(pubkey_bytes, event_created_at).await;
+                    continue;
+                }
+
+                // Held anomalies count as seen, so relay duplicates don't re-flag them
+                let mark_seen = match screen_update(&graph, &db, &alerts, &mut anomalies, update) {
+                    Some(update) => {
+                        let updated = apply_update(&graph, &persist_tx, &persistence, &alerts, update);
+                        if updated {
+                            event_count += 1;
+                            if let Some(pool) = pool {
+                                pool_counts[pool].applied += 1;
+                            }
+                        }
+                        updated
+                    }
+                    None => true,
+                };
+
+                // Update seen cache AFTER successful graph update
+                if mark_seen {
+                    seen_events.mark(pubkey_bytes, event_created_at).await;
+                }
+            }
+
+            // Log progress periodically
+            if last_log_time.elapsed() > Duration::from_secs(10) {
+                let stats = graph.stats();
+                let seen_size = seen_events.len().await;
+                info!(
+                    "Sync progress: {} events ({} watched), {} dedup skips, {} nodes, {} edges, seen_cache={}",
+                    event_count, priority_count, dedup_skip_count, stats.node_count, stats.edge_count, seen_size
+                );
+                last_log_time = std::time::Instant::now();
+            }
+        }
+    }
+}
+
+/// Split relays round-robin into at most `pools` non-empty groups
+fn partition_relays(relays: &[String], pools: usize) -> Vec<Vec<String>> {
+    let pools = pools.clamp(1, relays.len().max(1));
+    let mut groups = vec![Vec::new(); pools];
+    for (i, relay) in relays.iter().enumerate() {
+        groups[i % pools].push(relay.clone());
+    }
+    groups
+}
+
+/// Counters updated by a pool's forwarding task
+#[derive(Default)]
+struct PoolStats {
+    received: AtomicU64,
+    lagged: AtomicU64,
+    /// Dropped for an author outside the relay's `authors` prefixes
+    filtered: AtomicU64,
+    /// Dropped over the relay's `rate` cap
+    rate_limited: AtomicU64,
+}
+
+/// A firehose relay's author prefixes and rate cap (`RELAY_SETTINGS`), applied as its events
+/// arrive, before they take a place in the merged queue
+struct RelayGate {
+    author_prefixes: Vec<String>,
+    max_per_sec: u32,
+    window_start: std::time::Instant,
+    window_count: u32,
+}
+
+impl RelayGate {
+    fn new(settings: &RelaySettings) -> Self {
+        Self {
+            author_prefixes: settings.author_prefixes.clone(),
+            max_per_sec: settings.max_events_per_sec,
+            window_start: std::time::Instant::now(),
+            window_count: 0,
+        }
+    }
+
+    /// Whether the gate lets everything through
+    fn is_open(&self) -> bool {
+        self.author_prefixes.is_empty() && self.max_per_sec == 0
+    }
+
+    fn admits_author(&self, pubkey_hex: &str) -> bool {
+        self.author_prefixes.is_empty() || self.author_prefixes.iter().any(|p| pubkey_hex.starts_with(p.as_str()))
+    }
+
+    /// Count an event against the cap; false once this second's allowance is used up
+    fn take(&mut self, now: std::time::Instant) -> bool {
+        if self.max_per_sec == 0 {
+            return true;
+        }
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.window_count = 0;
+        }
+        self.window_count += 1;
+        self.window_count <= self.max_per_sec
+    }
+}
+
+/// Counters updated by the event loop
+#[derive(Default, Clone, Copy)]
+struct PoolCounts {
+    applied: u64,
+    dedup_skipped: u64,
+}
+
+/// Move a pool's events into the merged firehose stream
+async fn forward_notifications(
+    pool: usize,
+    mut notifications: broadcast::Receiver<RelayPoolNotification>,
+    mut gates: HashMap<String, RelayGate>,
+    tx: mpsc::Sender<(usize, Box<Event>)>,
+    stats: Arc<Vec<PoolStats>>,
+    sightings: Arc<RelaySightings>,
+) {
+    loop {
+        match notifications.recv().await {
+            Ok(RelayPoolNotification::Event { relay_url, event, .. }) => {
+                stats[pool].received.fetch_add(1, Ordering::Relaxed);
+                if let Some(gate) = gates.get_mut(relay_url.as_str().trim_end_matches('/')) {
+                    if !gate.admits_author(&event.pubkey.to_hex()) {
+                        stats[pool].filtered.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    if !gate.take(std::time::Instant::now()) {
+                        stats[pool].rate_limited.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                }
+                if tx.send((pool, event)).await.is_err() {
+                    return;
+                }
+            }
+            Ok(notification) => note_sighting(&sightings, &notification),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                stats[pool].lagged.fetch_add(skipped, Ordering::Relaxed);
+                warn!("Ingestion pool {} fell behind, {} notifications dropped", pool, skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Count a relay's copy of a contact list. The client only raises `Event` for the first copy
+/// of each event, so every relay's copy is taken from the raw `Message` notifications instead.
+fn note_sighting(sightings: &RelaySightings, notification: &RelayPoolNotification) {
+    if let RelayPoolNotification::Message { relay_url, message: RelayMessage::Event { event, .. } } = notification {
+        if event.kind == Kind::ContactList {
+            sightings.saw(event.pubkey.to_bytes(), event.id.to_bytes(), event.created_at.as_u64(), relay_url.as_str());
+        }
+    }
+}
+
+/// Open the firehose subscription on each of a pool's relays, for that relay's kinds
+async fn subscribe_firehose(client: &Client, relays: &[String], config: &Config, since: Option<Timestamp>) {
+    for relay in relays {
+        let kinds = config.relay_settings(relay).kinds.into_iter().map(Kind::from);
+        let filter = Filter::new().kinds(kinds);
+        let filter = match since {
+            Some(since) => filter.since(since),
+            None => filter,
+        };
+        if let Err(e) = client.subscribe_to([relay.as_str()], vec![filter], None).await {
+            warn!("Failed to subscribe to {}: {}", relay, e);
+        }
+    }
+}
+
+/// Replace the priority subscription with one covering the current watchlist
+async fn subscribe_watchlist(client: &Client, watchlist: &Watchlist, since: Option<Timestamp>) {
+    let id = SubscriptionId::new(WATCHLIST_SUBSCRIPTION_ID);
+    let authors: Vec<PublicKey> = watchlist
+        .pubkeys()
+        .iter()
+        .filter_map(|pk| PublicKey::from_hex(pk).ok())
+        .collect();
+    if authors.is_empty() {
+        client.unsubscribe(id).await;
+        return;
+    }
+
+    // Relays cap filter sizes, so split large watchlists across filters
+    let filters: Vec<Filter> = authors
+        .chunks(WATCHLIST_AUTHORS_PER_FILTER)
+        .map(|chunk| {
+            let filter = Filter::new().kinds(WATCHED_KINDS.map(Kind::from)).authors(chunk.to_vec());
+            match since {
+                Some(since) => filter.since(since),
+                None => filter,
             }
+        })
+        .collect();
+    match client.subscribe_with_id(id, filters, None).await {
+        Ok(_) => info!("Subscribed to {} watched authors", authors.len()),
+        Err(e) => warn!("Failed to subscribe to watched authors: {}", e),
+    }
+}
+
+/// Store a mute list, relay list or report. Returns whether a list was replaced.
+fn record_other_kind(db: &Database, event: &Event) -> bool {
+    match event.kind {
+        Kind::Reporting => {
+            record_report(db, event);
+            false
+        }
+        _ => record_author_list(db, event),
+    }
+}
+
+/// Watched pubkeys plus the configured anchors, the authors followed on their own relays
+fn gossip_authors(config: &Config, watched: &HashSet<String>) -> Vec<String> {
+    let mut authors: Vec<String> = watched.iter().cloned().collect();
+    for anchor in &config.anchor_pubkeys {
+        let anchor = anchor.to_ascii_lowercase();
+        if !watched.contains(&anchor) && !authors.contains(&anchor) {
+            authors.push(anchor);
+        }
+    }
+    authors
+}
+
+/// Store who a report names (its `p` tags), with the NIP-56 report type when given
+fn record_report(db: &Database, event: &Event) {
+    let reported: Vec<(String, Option<String>)> = event
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .filter(|tag| tag.len() >= 2 && tag[0] == "p")
+        .filter_map(|tag| Some((Pubkey::parse(&tag[1]).ok()?.to_hex(), tag.get(2).cloned())))
+        .collect();
+    if reported.is_empty() {
+        return;
+    }
+    if let Err(e) = db.record_report(
+        &event.pubkey.to_hex(),
+        &event.id.to_hex(),
+        event.created_at.as_u64() as i64,
+        &reported,
+    ) {
+        error!("Failed to store report from {}: {}", event.pubkey, e);
+    }
+}
+
+/// Store a mute or relay list. Returns whether it replaced an older one.
+fn record_author_list(db: &Database, event: &Event) -> bool {
+    let tags: Vec<Vec<String>> = event.tags.iter().map(|tag| tag.as_slice().to_vec()).collect();
+    match db.record_author_list(
+        &event.pubkey.to_hex(),
+        event.kind.as_u16(),
+        &event.id.to_hex(),
+        event.created_at.as_u64() as i64,
+        &tags,
+    ) {
+        Ok(stored) => stored,
+        Err(e) => {
+            error!("Failed to store kind {} list from {}: {}", event.kind.as_u16(), event.pubkey, e);
+            false
+        }
+    }
+}
+
+/// Relay connectivity and event freshness, checked against the alert thresholds
+struct IngestionHealth {
+    relay_down_secs: i64,
+    lag_secs: i64,
+    started_at: i64,
+    down_since: HashMap<String, i64>,
+    newest_event_at: Option<i64>,
+}
+
+impl IngestionHealth {
+    fn new(config: &Config) -> Self {
+        Self {
+            relay_down_secs: config.alert_relay_down_mins as i64 * 60,
+            lag_secs: config.alert_ingestion_lag_secs as i64,
+            started_at: chrono::Utc::now().timestamp(),
+            down_since: HashMap::new(),
+            newest_event_at: None,
+        }
+    }
+
+    fn saw_event(&mut self, created_at: i64) {
+        // Clamp future timestamps from skewed clocks so they can't mask a stall
+        let created_at = created_at.min(chrono::Utc::now().timestamp());
+        self.newest_event_at = Some(self.newest_event_at.map_or(created_at, |t| t.max(created_at)));
+    }
+
+    /// Seconds the relay has been down, once past the alert threshold
+    fn relay_status(&mut self, url: &str, connected: bool, now: i64) -> Option<i64> {
+        if connected {
+            self.down_since.remove(url);
+            return None;
+        }
+        let since = *self.down_since.entry(url.to_string()).or_insert(now);
+        let down = now - since;
+        (down >= self.relay_down_secs).then_some(down)
+    }
+
+    /// Restart the lag clock after a pause, so the catch-up isn't alerted as a stall
+    fn resumed(&mut self, now: i64) {
+        self.started_at = now;
+        self.newest_event_at = None;
+    }
+
+    /// Age of the newest contact list seen (or time since startup), once past the alert threshold
+    fn lag(&self, now: i64) -> Option<i64> {
+        let lag = now - self.newest_event_at.unwrap_or(self.started_at);
+        (lag >= self.lag_secs).then_some(lag)
+    }
+}
+
+/// Apply an update to the in-memory graph (which has its own timestamp check) and queue it
+/// for persistence. Returns whether the graph changed.
+fn apply_update(
+    graph: &WotGraph,
+    persist_tx: &mpsc::Sender<FollowUpdate>,
+    persistence: &PersistenceStats,
+    alerts: &Alerts,
+    update: FollowUpdate,
+) -> bool {
+    let updated = graph.update_follows(
+        &update.pubkey,
+        &update.follows,
+        Some(update.event_id.clone()),
+        Some(update.created_at),
+    );
+
+    if updated {
+        // Send to persistence worker
+        if let Err(e) = persist_tx.try_send(update) {
+            persistence.record_dropped();
+            warn!("Persistence queue full: {}", e);
+            alerts.raise(AlertKind::PersistenceFailure, "queue", format!("Persistence queue full: {}", e));
+        } else {
+            persistence.record_queued();
+        }
+    }
+    updated
+}
+
+/// Check an update for a mass follow/unfollow and record it if so.
+/// Returns the update to apply now, or None if it is held back.
+fn screen_update(
+    graph: &WotGraph,
+    db: &Database,
+    alerts: &Alerts,
+    anomalies: &mut AnomalyDetector,
+    update: FollowUpdate,
+) -> Option<FollowUpdate> {
+    let Some(diff) = FollowDiff::against(graph, &update).filter(|diff| anomalies.is_anomalous(diff)) else {
+        // A newer, unremarkable list replaces whatever was held for this author
+        if let Some(held) = anomalies.supersede(&update.pubkey) {
+            resolve_anomaly(db, &held, "superseded");
+        }
+        return Some(update);
+    };
+
+    let action = if anomalies.holds() { "held" } else { "applied" };
+    warn!(
+        "Anomalous contact list from {}: {} -> {} follows ({} added, {} removed), {}",
+        update.pubkey, diff.previous, diff.new, diff.added, diff.removed, action
+    );
+    alerts.raise(
+        AlertKind::Anomaly,
+        update.pubkey.clone(),
+        format!(
+            "Contact list from {} changed {} -> {} follows ({} added, {} removed), {}",
+            update.pubkey, diff.previous, diff.new, diff.added, diff.removed, action
+        ),
+    );
+
+    let anomaly = FollowAnomaly {
+        id: 0,
+        pubkey: update.pubkey.clone(),
+        event_id: update.event_id.clone(),
+        event_created_at: update.created_at,
+        detected_at: chrono::Utc::now().timestamp(),
+        previous_count: diff.previous,
+        new_count: diff.new,
+        added: diff.added,
+        removed: diff.removed,
+        action: action.to_string(),
+    };
+    let anomaly_id = match db.record_anomaly(&anomaly) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            error!("Failed to record follow anomaly: {}", e);
+            None
+        }
+    };
+
+    if !anomalies.holds() {
+        return Some(update);
+    }
+    if let Some(superseded) = anomalies.hold(update, anomaly_id) {
+        resolve_anomaly(db, &superseded, "superseded");
+    }
+    None
+}
+
+fn resolve_anomaly(db: &Database, held: &HeldUpdate, action: &str) {
+    if let Some(id) = held.anomaly_id {
+        if let Err(e) = db.set_anomaly_action(id, action) {
+            error!("Failed to update follow anomaly {}: {}", id, e);
         }
     }
 }
 
-fn process_event(event: &Event) -> Option<FollowUpdate> {
+/// The follow list of a contact list event, from its first `tag_count` tags
+fn process_event(event: &Event, tag_count: usize) -> Option<FollowUpdate> {
     if event.kind != Kind::ContactList {
         return None;
     }
@@ -181,16 +810,12 @@ fn process_event(event: &Event) -> Option<FollowUpdate> {
     let follows: Vec<String> = event
         .tags
         .iter()
+        .take(tag_count)
         .filter_map(|tag| {
             let tag_vec = tag.as_slice();
             if tag_vec.len() >= 2 && tag_vec[0] == "p" {
-                // Validate pubkey format (64 hex chars)
-                let pk = &tag_vec[1];
-                if pk.len() == 64 && pk.chars().all(|c| c.is_ascii_hexdigit()) {
-                    Some(pk.to_string())
-                } else {
-                    None
-                }
+                // Malformed pubkeys are skipped; uppercase hex is lowercased
+                Pubkey::parse(&tag_vec[1]).ok().map(String::from)
             } else {
                 None
             }
@@ -211,10 +836,34 @@ fn process_event(event: &Event) -> Option<FollowUpdate> {
     })
 }
 
-async fn persistence_worker(db: Arc<Database>, mut rx: mpsc::Receiver<FollowUpdate>) {
-    info!("Persistence worker started");
+/// Drop repeats of a follow, keeping the first; returns how many were dropped. Lists padded
+/// with duplicates would otherwise cost hashing, diffing and persistence per repeat.
+fn dedup_follows(follows: &mut Vec<String>) -> usize {
+    let before = follows.len();
+    let keep: Vec<bool> = {
+        let mut seen = HashSet::with_capacity(before);
+        follows.iter().map(|pk| seen.insert(pk.as_str())).collect()
+    };
+    let mut keep = keep.into_iter();
+    follows.retain(|_| keep.next().unwrap_or(true));
+    before - follows.len()
+}
+
+async fn persistence_worker(
+    db: Arc<Database>,
+    mut rx: mpsc::Receiver<FollowUpdate>,
+    alerts: Alerts,
+    stats: Arc<PersistenceStats>,
+    batch_size: usize,
+    flush_interval: Duration,
+) {
+    info!(
+        "Persistence worker started: batches of {} lists, flushed at least every {} ms",
+        batch_size,
+        flush_interval.as_millis()
+    );
 
-    let mut batch: Vec<FollowUpdate> = Vec::with_capacity(100);
+    let mut batch: Vec<FollowUpdate> = Vec::with_capacity(batch_size);
     let mut last_flush = std::time::Instant::now();
 
     loop {
@@ -223,14 +872,14 @@ async fn persistence_worker(db: Arc<Database>, mut rx: mpsc::Receiver<FollowUpda
                 batch.push(update);
 
                 // Flush batch when full or after timeout
-                if batch.len() >= 100 || last_flush.elapsed() > Duration::from_secs(5) {
-                    flush_batch(&db, &mut batch).await;
+                if batch.len() >= batch_size || last_flush.elapsed() > flush_interval {
+                    flush_batch(&db, &mut batch, &alerts, &stats).await;
                     last_flush = std::time::Instant::now();
                 }
             }
-            _ = tokio::time::sleep(Duration::from_secs(5)) => {
+            _ = tokio::time::sleep(flush_interval) => {
                 if !batch.is_empty() {
-                    flush_batch(&db, &mut batch).await;
+                    flush_batch(&db, &mut batch, &alerts, &stats).await;
                     last_flush = std::time::Instant::now();
                 }
             }
@@ -238,7 +887,7 @@ async fn persistence_worker(db: Arc<Database>, mut rx: mpsc::Receiver<FollowUpda
     }
 }
 
-async fn flush_batch(db: &Database, batch: &mut Vec<FollowUpdate>) {
+async fn flush_batch(db: &Database, batch: &mut Vec<FollowUpdate>, alerts: &Alerts, stats: &PersistenceStats) {
     if batch.is_empty() {
         return;
     }
@@ -256,10 +905,283 @@ async fn flush_batch(db: &Database, batch: &mut Vec<FollowUpdate>) {
         })
         .collect();
 
-    match db.update_follows_batch(&updates) {
+    // One node row and the edge rows per list
+    let rows = batch.iter().map(|u| 1 + u.follows.len() as u64).sum();
+    let started = std::time::Instant::now();
+    let result = db.update_follows_batch(&updates);
+    stats.record_flush(batch.len(), result.is_ok().then_some(rows), started.elapsed());
+    match result {
         Ok(count) => debug!("Persisted {} updates in single transaction", count),
-        Err(e) => error!("Failed to persist follow batch: {}", e),
+        Err(e) => {
+            error!("Failed to persist follow batch: {}", e);
+            alerts.raise(
+                AlertKind::PersistenceFailure,
+                "database",
+                format!("Failed to persist {} follow updates: {}", batch.len(), e),
+            );
+        }
     }
 
     batch.clear();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_follows() {
+        let mut follows: Vec<String> = ["a", "b", "a", "c", "b", "a"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(dedup_follows(&mut follows), 3);
+        assert_eq!(follows, vec!["a", "b", "c"]);
+        assert_eq!(dedup_follows(&mut follows), 0);
+    }
+
+    #[test]
+    fn test_partition_relays() {
+        let relays: Vec<String> = ["a", "b", "c", "d", "e"].iter().map(|r| r.to_string()).collect();
+        assert_eq!(partition_relays(&relays, 1), vec![relays.clone()]);
+        assert_eq!(
+            partition_relays(&relays, 2),
+            vec![vec!["a", "c", "e"], vec!["b", "d"]]
+        );
+        // Never more pools than relays
+        assert_eq!(partition_relays(&relays[..2], 4).len(), 2);
+        assert_eq!(partition_relays(&[], 3), vec![Vec::<String>::new()]);
+    }
+
+    #[test]
+    fn test_ingestion_health_thresholds() {
+        let mut config = Config::from_env();
+        config.alert_relay_down_mins = 10;
+        config.alert_ingestion_lag_secs = 600;
+        let mut health = IngestionHealth::new(&config);
+        let now = health.started_at;
+
+        assert_eq!(health.relay_status("wss://a", false, now), None);
+        assert_eq!(health.relay_status("wss://a", false, now + 599), None);
+        assert_eq!(health.relay_status("wss://a", false, now + 600), Some(600));
+        // Reconnecting resets the clock
+        assert_eq!(health.relay_status("wss://a", true, now + 601), None);
+        assert_eq!(health.relay_status("wss://a", false, now + 1200), None);
+
+        assert_eq!(health.lag(now + 599), None);
+        assert_eq!(health.lag(now + 600), Some(600));
+        health.saw_event(now - 100);
+        health.saw_event(now - 300);
+        assert_eq!(health.lag(now + 499), None);
+        assert_eq!(health.lag(now + 500), Some(600));
+    }
+
+    #[test]
+    fn test_relay_gate() {
+        let settings = RelaySettings { author_prefixes: vec!["ab".into()], max_events_per_sec: 2, ..Default::default() };
+        let mut gate = RelayGate::new(&settings);
+        assert!(!gate.is_open());
+        assert!(gate.admits_author("ab12"));
+        assert!(!gate.admits_author("ba12"));
+
+        let start = std::time::Instant::now();
+        assert!(gate.take(start) && gate.take(start));
+        assert!(!gate.take(start + Duration::from_millis(500)));
+        // A new second brings a new allowance
+        assert!(gate.take(start + Duration::from_secs(1)));
+        assert!(RelayGate::new(&RelaySettings::default()).is_open());
+    }
+
+    #[tokio::test]
+    async fn test_persistence_worker_thresholds() {
+        use crate::mock_relay::eventually;
+
+        let db = Arc::new(Database::open(":memory:").unwrap());
+        let stats = Arc::new(PersistenceStats::default());
+        let (tx, rx) = mpsc::channel(10);
+        let worker = tokio::spawn(persistence_worker(
+            db.clone(),
+            rx,
+            Alerts::disabled(),
+            stats.clone(),
+            2,
+            Duration::from_millis(300),
+        ));
+        let update = |pubkey: &str| FollowUpdate {
+            pubkey: pubkey.to_string(),
+            follows: vec!["bob".to_string()],
+            event_id: format!("{}-event", pubkey),
+            created_at: 1,
+        };
+        let written = || stats.snapshot().updates_written;
+
+        // A full batch is written at once
+        tx.send(update("alice")).await.unwrap();
+        tx.send(update("carol")).await.unwrap();
+        assert!(eventually(Duration::from_millis(200), || written() == 2).await);
+
+        // A partial one waits for the flush interval
+        tx.send(update("dave")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(written(), 2);
+        assert!(eventually(Duration::from_secs(2), || written() == 3).await);
+        assert_eq!(db.get_stats().unwrap(), (4, 3));
+        worker.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_ingests_contact_lists_from_relay() {
+        use crate::mock_relay::{eventually, MockRelay};
+
+        let relay = MockRelay::start().await;
+        let (alice, bob, carol) = (Keys::generate(), Keys::generate(), Keys::generate());
+        let follows = |author: &Keys, followed: &Keys| {
+            EventBuilder::new(Kind::ContactList, "", [Tag::public_key(followed.public_key())])
+                .to_event(author)
+                .unwrap()
+        };
+        // Stored before ingestion subscribes
+        relay.publish_event(&follows(&alice, &bob));
+
+        let mut config = Config::from_env();
+        config.relays = vec![relay.url().to_string()];
+        config.ingestion_debounce_ms = 0;
+        let graph = Arc::new(WotGraph::new());
+        let ingestion = Ingestion::new(
+            graph.clone(),
+            Arc::new(Database::open(":memory:").unwrap()),
+            Arc::new(config),
+            Arc::new(SeenCache::new(100)),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Alerts::disabled(),
+            Arc::default(),
+            Arc::default(),
+        );
+        let task = tokio::spawn(async move { ingestion.start().await });
+
+        let pubkey = |keys: &Keys| -> crate::types::Pubkey { keys.public_key().to_hex().parse().unwrap() };
+        let timeout = Duration::from_secs(10);
+        assert!(eventually(timeout, || graph.get_follows(&pubkey(&alice)) == Some(vec![pubkey(&bob)])).await);
+
+        // Published while subscribed
+        relay.publish_event(&follows(&bob, &carol));
+        assert!(eventually(timeout, || graph.get_follows(&pubkey(&bob)) == Some(vec![pubkey(&carol)])).await);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_counts_relays_per_list() {
+        use crate::mock_relay::{eventually, MockRelay};
+
+        let (first, second) = (MockRelay::start().await, MockRelay::start().await);
+        let (alice, bob) = (Keys::generate(), Keys::generate());
+        let event = EventBuilder::new(Kind::ContactList, "", [Tag::public_key(bob.public_key())])
+            .to_event(&alice)
+            .unwrap();
+        first.publish_event(&event);
+        second.publish_event(&event);
+
+        let mut config = Config::from_env();
+        config.relays = vec![first.url().to_string(), second.url().to_string()];
+        config.ingestion_debounce_ms = 0;
+        let sightings = Arc::new(RelaySightings::default());
+        let ingestion = Ingestion::new(
+            Arc::new(WotGraph::new()),
+            Arc::new(Database::open(":memory:").unwrap()),
+            Arc::new(config),
+            Arc::new(SeenCache::new(100)),
+            sightings.clone(),
+            Arc::default(),
+            Arc::default(),
+            Alerts::disabled(),
+            Arc::default(),
+            Arc::default(),
+        );
+        let task = tokio::spawn(async move { ingestion.start().await });
+
+        let (pubkey, id) = (alice.public_key().to_hex(), event.id.to_hex());
+        assert!(eventually(Duration::from_secs(10), || sightings.confidence(&pubkey, &id) == Some(2)).await);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_relay_kinds() {
+        use crate::mock_relay::{eventually, MockRelay};
+
+        let relay = MockRelay::start().await;
+        let (alice, mallory) = (Keys::generate(), Keys::generate());
+        let report = EventBuilder::new(
+            Kind::Reporting,
+            "",
+            [Tag::parse(&["p", &mallory.public_key().to_hex(), "spam"]).unwrap()],
+        )
+        .to_event(&alice)
+        .unwrap();
+        relay.publish_event(&report);
+
+        let mut config = Config::from_env();
+        config.relays = vec![relay.url().to_string()];
+        config.relay_settings =
+            [(relay.url().to_string(), RelaySettings { kinds: vec![3, 1984], ..Default::default() })].into();
+        let db = Arc::new(Database::open(":memory:").unwrap());
+        let ingestion = Ingestion::new(
+            Arc::new(WotGraph::new()),
+            db.clone(),
+            Arc::new(config),
+            Arc::new(SeenCache::new(100)),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Alerts::disabled(),
+            Arc::default(),
+            Arc::default(),
+        );
+        let task = tokio::spawn(async move { ingestion.start().await });
+
+        let mallory_hex = mallory.public_key().to_hex();
+        assert!(eventually(Duration::from_secs(10), || db.report_count(&mallory_hex).unwrap() == 1).await);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_pause_catches_up_on_resume() {
+        use crate::mock_relay::{eventually, MockRelay};
+
+        let relay = MockRelay::start().await;
+        let (alice, bob) = (Keys::generate(), Keys::generate());
+        let mut config = Config::from_env();
+        config.relays = vec![relay.url().to_string()];
+        config.ingestion_debounce_ms = 0;
+        let graph = Arc::new(WotGraph::new());
+        let pause = Arc::new(IngestionPause::default());
+        let ingestion = Ingestion::new(
+            graph.clone(),
+            Arc::new(Database::open(":memory:").unwrap()),
+            Arc::new(config),
+            Arc::new(SeenCache::new(100)),
+            Arc::default(),
+            Arc::default(),
+            pause.clone(),
+            Alerts::disabled(),
+            Arc::default(),
+            Arc::default(),
+        );
+        let task = tokio::spawn(async move { ingestion.start().await });
+        assert!(relay.wait_for_subscription(Duration::from_secs(10)).await);
+
+        assert!(pause.pause(Some("test".to_string()), chrono::Utc::now().timestamp()));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let event = EventBuilder::new(Kind::ContactList, "", [Tag::public_key(bob.public_key())])
+            .to_event(&alice)
+            .unwrap();
+        relay.publish_event(&event);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let alice_key: crate::types::Pubkey = alice.public_key().to_hex().parse().unwrap();
+        assert_eq!(graph.get_follows(&alice_key), None);
+
+        // Resuming replays what was published while paused
+        assert!(pause.resume());
+        let expected = Some(vec![bob.public_key().to_hex().parse().unwrap()]);
+        assert!(eventually(Duration::from_secs(10), || graph.get_follows(&alice_key) == expected).await);
+        task.abort();
+    }
+}
@@ -1,3 +1,18 @@
+pub mod anomaly;
+pub mod debounce;
+pub mod gossip;
 pub mod ingestion;
+pub mod pause;
+pub mod persistence;
+pub mod replication;
+pub mod seen;
+pub mod sightings;
+pub mod watchlist;
 
 pub use ingestion::Ingestion;
+pub use pause::IngestionPause;
+pub use persistence::PersistenceStats;
+pub use replication::Replicator;
+pub use seen::SeenCache;
+pub use sightings::RelaySightings;
+pub use watchlist::Watchlist;
@@ -0,0 +1,87 @@
+use serde::Serialize;
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+/// Ingestion state, as reported by `/health`, `/stats` and the admin pause routes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct IngestionStatus {
+    pub paused: bool,
+    /// Unix time ingestion was paused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused_at: Option<i64>,
+    /// Operator note given with the pause (e.g. "compaction")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Operator switch for relay ingestion (`/admin/ingestion/pause`), shared by every graph.
+/// While paused, ingestion drops its relay subscriptions and applies nothing; on resume it
+/// resubscribes with `since` shortly before the pause, so relays replay what was missed.
+pub struct IngestionPause {
+    status: watch::Sender<IngestionStatus>,
+}
+
+impl IngestionPause {
+    pub fn status(&self) -> IngestionStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Notified whenever ingestion is paused or resumed
+    pub fn subscribe(&self) -> watch::Receiver<IngestionStatus> {
+        self.status.subscribe()
+    }
+
+    /// Pause ingestion; false if it already was (the original pause time is kept)
+    pub fn pause(&self, reason: Option<String>, now: i64) -> bool {
+        self.status.send_if_modified(|status| {
+            if status.paused {
+                return false;
+            }
+            *status = IngestionStatus { paused: true, paused_at: Some(now), reason };
+            true
+        })
+    }
+
+    /// Resume ingestion; false if it wasn't paused
+    pub fn resume(&self) -> bool {
+        self.status.send_if_modified(|status| {
+            if !status.paused {
+                return false;
+            }
+            *status = IngestionStatus::default();
+            true
+        })
+    }
+}
+
+impl Default for IngestionPause {
+    fn default() -> Self {
+        Self { status: watch::Sender::new(IngestionStatus::default()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_resume() {
+        let pause = IngestionPause::default();
+        let mut changes = pause.subscribe();
+        assert!(!pause.status().paused);
+        assert!(!pause.resume());
+
+        assert!(pause.pause(Some("compaction".to_string()), 100));
+        assert!(!pause.pause(None, 200));
+        assert_eq!(
+            pause.status(),
+            IngestionStatus { paused: true, paused_at: Some(100), reason: Some("compaction".to_string()) }
+        );
+        assert!(changes.has_changed().unwrap());
+        changes.borrow_and_update();
+
+        assert!(pause.resume());
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(pause.status(), IngestionStatus::default());
+    }
+}
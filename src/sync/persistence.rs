@@ -0,0 +1,168 @@
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// Span `rows_per_sec` is averaged over
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Rows written since `started`, and the rate over the previous window
+struct RateWindow {
+    started: Instant,
+    rows_at_start: u64,
+    rows_per_sec: f64,
+}
+
+impl Default for RateWindow {
+    fn default() -> Self {
+        Self { started: Instant::now(), rows_at_start: 0, rows_per_sec: 0.0 }
+    }
+}
+
+impl RateWindow {
+    /// Close the window once it spans `RATE_WINDOW`, so the rate also falls to zero when writes stop
+    fn roll(&mut self, rows: u64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.started);
+        if elapsed >= RATE_WINDOW {
+            self.rows_per_sec = (rows - self.rows_at_start) as f64 / elapsed.as_secs_f64();
+            self.started = now;
+            self.rows_at_start = rows;
+        }
+    }
+}
+
+/// Health of the worker that writes applied contact lists to the database, read by `/stats`
+/// and `/metrics`. The in-memory graph is updated first, so updates dropped on a full queue
+/// or lost in a failed batch are missing from the database until their authors publish again.
+#[derive(Default)]
+pub struct PersistenceStats {
+    queued: AtomicU64,
+    dropped: AtomicU64,
+    written: AtomicU64,
+    failed: AtomicU64,
+    rows: AtomicU64,
+    flushes: AtomicU64,
+    flush_us_total: AtomicU64,
+    last_flush_us: AtomicU64,
+    max_flush_us: AtomicU64,
+    /// Unix time of the last successful flush; 0 before the first
+    last_flush_at: AtomicI64,
+    rate: Mutex<RateWindow>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct PersistenceStatsSnapshot {
+    /// Applied updates waiting in the queue or the batch being built
+    pub queue_depth: u64,
+    /// Updates the graph applied but the full queue turned away, never persisted
+    pub dropped_updates: u64,
+    /// Contact lists written to the database
+    pub updates_written: u64,
+    /// Contact lists in batches the database rejected
+    pub updates_failed: u64,
+    /// Node and edge rows written
+    pub rows_written: u64,
+    /// Rows written per second over the last minute
+    pub rows_per_sec: f64,
+    /// Batches committed or attempted
+    pub flushes: u64,
+    pub last_flush_ms: f64,
+    pub max_flush_ms: f64,
+    /// Total time spent flushing, for an average with `flushes`
+    pub flush_ms_total: f64,
+    /// Unix time of the last successful flush
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_flush_at: Option<i64>,
+}
+
+impl PersistenceStats {
+    pub fn record_queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a batch of `updates` lists taking `elapsed`; `rows` is None if it failed
+    pub fn record_flush(&self, updates: usize, rows: Option<u64>, elapsed: Duration) {
+        let micros = elapsed.as_micros().min(u64::MAX as u128) as u64;
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+        self.flush_us_total.fetch_add(micros, Ordering::Relaxed);
+        self.last_flush_us.store(micros, Ordering::Relaxed);
+        self.max_flush_us.fetch_max(micros, Ordering::Relaxed);
+        match rows {
+            Some(rows) => {
+                self.written.fetch_add(updates as u64, Ordering::Relaxed);
+                let total = self.rows.fetch_add(rows, Ordering::Relaxed) + rows;
+                self.last_flush_at.store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+                self.rate.lock().roll(total, Instant::now());
+            }
+            None => {
+                self.failed.fetch_add(updates as u64, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> PersistenceStatsSnapshot {
+        let rows = self.rows.load(Ordering::Relaxed);
+        let rows_per_sec = {
+            let mut rate = self.rate.lock();
+            rate.roll(rows, Instant::now());
+            rate.rows_per_sec
+        };
+        let (written, failed) = (self.written.load(Ordering::Relaxed), self.failed.load(Ordering::Relaxed));
+        let ms = |micros: &AtomicU64| micros.load(Ordering::Relaxed) as f64 / 1000.0;
+        PersistenceStatsSnapshot {
+            queue_depth: self.queued.load(Ordering::Relaxed).saturating_sub(written + failed),
+            dropped_updates: self.dropped.load(Ordering::Relaxed),
+            updates_written: written,
+            updates_failed: failed,
+            rows_written: rows,
+            rows_per_sec,
+            flushes: self.flushes.load(Ordering::Relaxed),
+            last_flush_ms: ms(&self.last_flush_us),
+            max_flush_ms: ms(&self.max_flush_us),
+            flush_ms_total: ms(&self.flush_us_total),
+            last_flush_at: Some(self.last_flush_at.load(Ordering::Relaxed)).filter(|&at| at > 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persistence_stats() {
+        let stats = PersistenceStats::default();
+        for _ in 0..5 {
+            stats.record_queued();
+        }
+        stats.record_dropped();
+        stats.record_flush(2, Some(40), Duration::from_millis(3));
+        stats.record_flush(1, None, Duration::from_millis(7));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.queue_depth, 2);
+        assert_eq!((snapshot.updates_written, snapshot.updates_failed), (2, 1));
+        assert_eq!((snapshot.rows_written, snapshot.dropped_updates, snapshot.flushes), (40, 1, 2));
+        assert_eq!((snapshot.last_flush_ms, snapshot.max_flush_ms, snapshot.flush_ms_total), (7.0, 7.0, 10.0));
+        assert!(snapshot.last_flush_at.is_some());
+        assert_eq!(PersistenceStats::default().snapshot().last_flush_at, None);
+    }
+
+    #[test]
+    fn test_rate_window() {
+        let start = Instant::now();
+        let mut window = RateWindow { started: start, rows_at_start: 0, rows_per_sec: 0.0 };
+        window.roll(500, start + Duration::from_secs(30));
+        assert_eq!(window.rows_per_sec, 0.0);
+        window.roll(1200, start + RATE_WINDOW);
+        assert_eq!(window.rows_per_sec, 20.0);
+        // No writes over the next window
+        window.roll(1200, start + RATE_WINDOW * 2);
+        assert_eq!(window.rows_per_sec, 0.0);
+    }
+}
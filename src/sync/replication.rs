@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::db::{Database, FollowUpdateBatch, ReplicatedList};
+use crate::graph::WotGraph;
+
+pub const PAGE_LIMIT_DEFAULT: usize = 1000;
+pub const PAGE_LIMIT_MAX: usize = 5000;
+
+/// Lists written in the last few seconds are held back, so a batch never ends partway
+/// through a second that is still receiving writes
+const SETTLE_SECS: i64 = 5;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A position in a primary's write order: `(updated_at, node id)` of the last list served.
+/// Encoded as `updated_at:id`; the default (start of time) requests a full snapshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    pub updated_at: i64,
+    pub id: i64,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.updated_at, self.id)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (updated_at, id) = s.split_once(':').ok_or(())?;
+        Ok(Self {
+            updated_at: updated_at.parse().map_err(|_| ())?,
+            id: id.parse().map_err(|_| ())?,
+        })
+    }
+}
+
+/// Contact lists and blocks written on a primary since a cursor
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReplicationBatch {
+    /// Current contact lists of authors that published since the cursor, in write order
+    pub lists: Vec<ReplicatedList>,
+    /// Pubkeys blocked since the cursor (unblocking is not replicated)
+    pub blocked: Vec<String>,
+    /// Pass back as `cursor` to continue after this batch
+    pub cursor: String,
+    /// More lists are ready; fetch the next batch without waiting
+    pub more: bool,
+}
+
+/// Build the batch following `cursor` from a primary's database
+pub fn changes(db: &Database, cursor: Cursor, limit: usize, now: i64) -> Result<ReplicationBatch> {
+    let before = now - SETTLE_SECS;
+    let blocked = db.blocked_since(cursor.updated_at, before)?;
+    let lists = db.lists_changed_since((cursor.updated_at, cursor.id), before, limit)?;
+
+    let more = lists.len() == limit;
+    let mut next = lists
+        .last()
+        .map(|&((updated_at, id), _)| Cursor { updated_at, id })
+        .unwrap_or(cursor);
+    if !more {
+        // Everything before `before` has been served; skip ahead so idle polls stay empty
+        next = next.max(Cursor { updated_at: before, id: 0 });
+    }
+
+    Ok(ReplicationBatch {
+        lists: lists.into_iter().map(|(_, list)| list).collect(),
+        blocked,
+        cursor: next.to_string(),
+        more,
+    })
+}
+
+/// Apply a batch to a replica's graph and persist the lists that changed it.
+/// Returns how many lists were applied.
+pub fn apply(graph: &WotGraph, db: &Database, batch: &ReplicationBatch) -> Result<usize> {
+    // Blocks first, so the lists below are stripped of them
+    if !batch.blocked.is_empty() {
+        db.block_pubkeys(&batch.blocked, Some("replication"))?;
+        graph.block(&batch.blocked);
+    }
+
+    let updates: Vec<FollowUpdateBatch<'_>> = batch
+        .lists
+        .iter()
+        .filter(|list| graph.update_follows(&list.pubkey, &list.follows, list.event_id.clone(), list.created_at))
+        .map(|list| FollowUpdateBatch {
+            pubkey: &list.pubkey,
+            follows: &list.follows,
+            event_id: list.event_id.as_deref(),
+            created_at: list.created_at,
+        })
+        .collect();
+    db.update_follows_batch(&updates)
+}
+
+/// Read-only mode: keeps the graph in sync by pulling batches from `REPLICATE_FROM`
+/// instead of ingesting from relays
+pub struct Replicator {
+    graph: Arc<WotGraph>,
+    db: Arc<Database>,
+    primary: String,
+    token: Option<String>,
+    interval: Duration,
+    http: reqwest::Client,
+}
+
+impl Replicator {
+    pub fn new(graph: Arc<WotGraph>, db: Arc<Database>, config: &Config, primary: &str) -> Self {
+        Self {
+            graph,
+            db,
+            primary: primary.to_string(),
+            token: config.replication_token.clone(),
+            interval: Duration::from_secs(config.replication_interval_secs),
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        // Resume from the start of the last applied second; re-applied lists are no-ops
+        let state_key = format!("replica:{}", self.primary);
+        let (db, key) = (self.db.clone(), state_key.clone());
+        let saved = tokio::task::spawn_blocking(move || db.get_sync_state(&key)).await??;
+        let mut cursor = saved
+            .and_then(|state| state.last_event_time)
+            .map(|updated_at| Cursor { updated_at, id: 0 })
+            .unwrap_or_default();
+        info!("Replicating from {} (from cursor {})", self.primary, cursor);
+
+        loop {
+            match self.pull(cursor, &state_key).await {
+                Ok((next, more)) => {
+                    cursor = next;
+                    if more {
+                        continue;
+                    }
+                }
+                Err(e) => warn!("Replication from {} failed: {:#}", self.primary, e),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    /// Fetch and apply the batch after `cursor`, then save the new position
+    async fn pull(&self, cursor: Cursor, state_key: &str) -> Result<(Cursor, bool)> {
+        let mut request = self
+            .http
+            .get(format!("{}/replication/changes", self.primary))
+            .query(&[("cursor", cursor.to_string()), ("limit", PAGE_LIMIT_MAX.to_string())]);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let batch: ReplicationBatch = request
+            .send()
+            .await?
+            .error_for_status()
+            .context("Primary rejected replication request")?
+            .json()
+            .await?;
+        let next: Cursor = batch
+            .cursor
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid cursor from primary: {}", batch.cursor))?;
+        let more = batch.more;
+
+        let (graph, db, key) = (self.graph.clone(), self.db.clone(), state_key.to_string());
+        let applied = tokio::task::spawn_blocking(move || {
+            let applied = apply(&graph, &db, &batch)?;
+            db.set_sync_state(&key, Some(next.updated_at))?;
+            anyhow::Ok(applied)
+        })
+        .await??;
+
+        debug!("Applied {} replicated lists (cursor {})", applied, next);
+        Ok((next, more))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Pubkey;
+
+    /// A pubkey spelling out `name`, so tests read as names
+    fn pk(name: &str) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::from_bytes(bytes)
+    }
+
+    /// Its hex, as follow lists carry it
+    fn hex(name: &str) -> String {
+        pk(name).to_hex()
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = Cursor { updated_at: 1700000000, id: 42 };
+        assert_eq!(cursor.to_string().parse::<Cursor>(), Ok(cursor));
+        assert!("1700000000".parse::<Cursor>().is_err());
+        assert!("a:b".parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn test_changes_applied_to_replica() {
+        let primary = Database::open(":memory:").unwrap();
+        primary.update_follows(&hex("alice"), &[hex("bob"), hex("mallory")], Some("e1"), Some(100)).unwrap();
+        primary.update_follows(&hex("bob"), &[hex("alice")], Some("e2"), Some(100)).unwrap();
+        primary.block_pubkeys(&[hex("mallory")], None).unwrap();
+        let now = chrono::Utc::now().timestamp() + SETTLE_SECS + 1;
+
+        let first = changes(&primary, Cursor::default(), 1, now).unwrap();
+        assert_eq!(first.lists.len(), 1);
+        assert_eq!(first.blocked, vec![hex("mallory")]);
+        assert!(first.more);
+
+        let replica_graph = WotGraph::new();
+        let replica_db = Database::open(":memory:").unwrap();
+        assert_eq!(apply(&replica_graph, &replica_db, &first).unwrap(), 1);
+
+        let second = changes(&primary, first.cursor.parse().unwrap(), 1, now).unwrap();
+        assert_eq!(second.lists[0].pubkey, hex("bob"));
+        assert_eq!(apply(&replica_graph, &replica_db, &second).unwrap(), 1);
+        // Applying the same batch again changes nothing
+        assert_eq!(apply(&replica_graph, &replica_db, &second).unwrap(), 0);
+
+        let idle = changes(&primary, second.cursor.parse().unwrap(), 1, now).unwrap();
+        assert!(idle.lists.is_empty() && !idle.more);
+        assert!(changes(&primary, idle.cursor.parse().unwrap(), 1, now).unwrap().blocked.is_empty());
+
+        assert!(replica_graph.is_blocked(&hex("mallory")));
+        assert_eq!(replica_graph.get_follows(&pk("alice")).unwrap(), vec![pk("bob")]);
+        assert_eq!(replica_db.get_stats().unwrap(), (2, 2));
+    }
+}
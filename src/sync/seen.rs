@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+use crate::db::Database;
+
+/// Newest contact list `created_at` seen per pubkey, for dropping duplicate and older events
+/// before their tags are parsed. Evicts least recently used pubkeys when full, never clears
+/// entirely. Saved to SQLite periodically and on shutdown, so restarts don't reprocess a
+/// flood of already-known events.
+pub struct SeenCache {
+    entries: RwLock<LruCache<[u8; 32], u64>>,
+}
+
+impl SeenCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: RwLock::new(LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap())),
+        }
+    }
+
+    /// Whether an event at `created_at` is no newer than one already seen for this pubkey
+    pub async fn is_dominated(&self, pubkey: &[u8; 32], created_at: u64) -> bool {
+        self.entries
+            .read()
+            .await
+            .peek(pubkey)
+            .is_some_and(|&seen| created_at <= seen)
+    }
+
+    pub async fn mark(&self, pubkey: [u8; 32], created_at: u64) {
+        self.entries.write().await.put(pubkey, created_at);
+    }
+
+    pub async fn len(&self) -> usize {
+        self.entries.read().await.len()
+    }
+
+    /// Preload the cache saved by a previous run
+    pub async fn load(&self, db: Arc<Database>) -> Result<usize> {
+        let capacity = self.entries.read().await.cap().get();
+        let saved = tokio::task::spawn_blocking(move || db.load_seen_events(capacity))
+            .await
+            .context("Seen cache load task failed")??;
+
+        // Oldest first, so the newest end up most recently used
+        let mut entries = self.entries.write().await;
+        for (pubkey, created_at) in &saved {
+            entries.put(*pubkey, *created_at);
+        }
+        Ok(saved.len())
+    }
+
+    /// Replace the saved cache with the current contents
+    pub async fn persist(&self, db: Arc<Database>) -> Result<usize> {
+        let snapshot: Vec<([u8; 32], u64)> = self
+            .entries
+            .read()
+            .await
+            .iter()
+            .map(|(pubkey, created_at)| (*pubkey, *created_at))
+            .collect();
+        tokio::task::spawn_blocking(move || db.save_seen_events(&snapshot))
+            .await
+            .context("Seen cache persist task failed")?
+    }
+
+    /// Persist every `interval_secs` until the process exits
+    pub async fn start(&self, db: Arc<Database>, interval_secs: u64) -> Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match self.persist(db.clone()).await {
+                Ok(count) => info!("Saved seen cache ({} pubkeys)", count),
+                Err(e) => error!("Failed to save seen cache: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_persist_and_load() {
+        let db = Arc::new(Database::open(":memory:").unwrap());
+        let seen = SeenCache::new(2);
+        seen.mark([1; 32], 100).await;
+        seen.mark([2; 32], 200).await;
+        seen.mark([3; 32], 300).await;
+        assert_eq!(seen.len().await, 2);
+        assert!(seen.is_dominated(&[2; 32], 200).await);
+        assert!(!seen.is_dominated(&[2; 32], 201).await);
+        assert!(!seen.is_dominated(&[1; 32], 50).await);
+
+        assert_eq!(seen.persist(db.clone()).await.unwrap(), 2);
+
+        let restored = SeenCache::new(10);
+        assert_eq!(restored.load(db.clone()).await.unwrap(), 2);
+        assert!(restored.is_dominated(&[3; 32], 300).await);
+        assert!(!restored.is_dominated(&[1; 32], 50).await);
+
+        // A smaller cache keeps the newest entries
+        let small = SeenCache::new(1);
+        assert_eq!(small.load(db).await.unwrap(), 1);
+        assert!(small.is_dominated(&[3; 32], 300).await);
+    }
+}
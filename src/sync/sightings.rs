@@ -0,0 +1,93 @@
+use nostr_sdk::util::hex;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// Relays that delivered a pubkey's newest contact list
+struct Sighting {
+    created_at: u64,
+    event_id: [u8; 32],
+    /// Interned relay indices
+    relays: Vec<u16>,
+}
+
+#[derive(Default)]
+struct Inner {
+    relay_ids: HashMap<String, u16>,
+    sightings: HashMap<[u8; 32], Sighting>,
+}
+
+/// Which relays delivered each pubkey's newest contact list since startup, so `/node` can
+/// report how widely a follow list is corroborated (`confidence`). Every relay's copy
+/// is counted here, including the relay duplicates ingestion otherwise drops.
+#[derive(Default)]
+pub struct RelaySightings {
+    inner: RwLock<Inner>,
+}
+
+impl RelaySightings {
+    /// Record that `relay` delivered a contact list; a newer list starts a new count
+    pub fn saw(&self, pubkey: [u8; 32], event_id: [u8; 32], created_at: u64, relay: &str) {
+        let mut inner = self.inner.write();
+        let relay = relay.trim_end_matches('/');
+        let relay_id = match inner.relay_ids.get(relay) {
+            Some(&id) => id,
+            None => {
+                let Ok(id) = u16::try_from(inner.relay_ids.len()) else {
+                    return;
+                };
+                inner.relay_ids.insert(relay.to_string(), id);
+                id
+            }
+        };
+
+        let sighting = inner.sightings.entry(pubkey).or_insert_with(|| Sighting {
+            created_at,
+            event_id,
+            relays: Vec::new(),
+        });
+        if (created_at, event_id) > (sighting.created_at, sighting.event_id) {
+            *sighting = Sighting { created_at, event_id, relays: Vec::new() };
+        } else if event_id != sighting.event_id {
+            return;
+        }
+        if !sighting.relays.contains(&relay_id) {
+            sighting.relays.push(relay_id);
+        }
+    }
+
+    /// Distinct relays that delivered this contact list, or None if it hasn't been seen since
+    /// startup (e.g. loaded from the database, or superseded by a newer one in flight)
+    pub fn confidence(&self, pubkey: &str, event_id: &str) -> Option<usize> {
+        let (mut pubkey_bytes, mut event_id_bytes) = ([0u8; 32], [0u8; 32]);
+        hex::decode_to_slice(pubkey, &mut pubkey_bytes).ok()?;
+        hex::decode_to_slice(event_id, &mut event_id_bytes).ok()?;
+        let inner = self.inner.read();
+        let sighting = inner.sightings.get(&pubkey_bytes)?;
+        (sighting.event_id == event_id_bytes).then_some(sighting.relays.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sightings() {
+        let sightings = RelaySightings::default();
+        let (pk, pubkey) = ("aa".repeat(32), [0xaa; 32]);
+        let (old, new) = ([1u8; 32], [2u8; 32]);
+        let hex = |id: [u8; 32]| hex::encode(id);
+
+        sightings.saw(pubkey, old, 100, "wss://a");
+        sightings.saw(pubkey, old, 100, "wss://b/");
+        sightings.saw(pubkey, old, 100, "wss://b");
+        assert_eq!(sightings.confidence(&pk, &hex(old)), Some(2));
+
+        // A newer list starts over; stragglers of the old one are ignored
+        sightings.saw(pubkey, new, 200, "wss://c");
+        sightings.saw(pubkey, old, 100, "wss://d");
+        assert_eq!(sightings.confidence(&pk, &hex(new)), Some(1));
+        assert_eq!(sightings.confidence(&pk, &hex(old)), None);
+        assert_eq!(sightings.confidence(&"b".repeat(64), &hex(new)), None);
+    }
+}
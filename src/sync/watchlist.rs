@@ -0,0 +1,156 @@
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::types::Pubkey;
+
+/// Kinds fetched for watched authors: contact list, mute list (NIP-51) and relay list (NIP-65)
+pub const WATCHED_KINDS: [u16; 3] = [3, 10000, 10002];
+
+/// Newest event seen per watched kind, Unix times
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct WatchedPubkey {
+    pub pubkey: String,
+    pub contact_list_at: Option<i64>,
+    pub mute_list_at: Option<i64>,
+    pub relay_list_at: Option<i64>,
+}
+
+/// Priority pubkeys (an operator's user base) that ingestion keeps dedicated subscriptions
+/// for, editable at runtime. Ingestion resubscribes when the set changes.
+pub struct Watchlist {
+    entries: RwLock<BTreeMap<String, WatchedPubkey>>,
+    changed: watch::Sender<u64>,
+}
+
+impl Watchlist {
+    pub fn from_config(config: &Config) -> Self {
+        let watchlist = Self::default();
+        let valid: Vec<String> = config
+            .watchlist_pubkeys
+            .iter()
+            .filter_map(|pk| Pubkey::parse(pk).ok())
+            .map(String::from)
+            .collect();
+        watchlist.insert(&valid);
+        watchlist
+    }
+
+    pub fn pubkeys(&self) -> Vec<String> {
+        self.entries.read().keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().len()
+    }
+
+    pub fn snapshot(&self) -> Vec<WatchedPubkey> {
+        self.entries.read().values().cloned().collect()
+    }
+
+    /// Notified with a new version whenever pubkeys are added or removed
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.changed.subscribe()
+    }
+
+    pub fn add(&self, pubkeys: &[String]) -> Result<usize, String> {
+        if let Some(bad) = pubkeys.iter().find(|pk| !Pubkey::is_valid(pk)) {
+            return Err(format!("Invalid pubkey: {}", bad));
+        }
+        Ok(self.insert(pubkeys))
+    }
+
+    pub fn remove(&self, pubkeys: &[String]) -> usize {
+        let removed = {
+            let mut entries = self.entries.write();
+            pubkeys
+                .iter()
+                .filter(|pk| entries.remove(&pk.to_ascii_lowercase()).is_some())
+                .count()
+        };
+        if removed > 0 {
+            self.changed.send_modify(|version| *version += 1);
+        }
+        removed
+    }
+
+    /// Record an event from a watched author; ignored for anyone else or older events
+    pub fn saw(&self, pubkey: &str, kind: u16, created_at: i64) {
+        let mut entries = self.entries.write();
+        let Some(entry) = entries.get_mut(pubkey) else {
+            return;
+        };
+        let slot = match kind {
+            3 => &mut entry.contact_list_at,
+            10000 => &mut entry.mute_list_at,
+            10002 => &mut entry.relay_list_at,
+            _ => return,
+        };
+        if slot.is_none_or(|at| created_at > at) {
+            *slot = Some(created_at);
+        }
+    }
+
+    fn insert(&self, pubkeys: &[String]) -> usize {
+        let added = {
+            let mut entries = self.entries.write();
+            pubkeys
+                .iter()
+                .map(|pk| pk.to_ascii_lowercase())
+                .filter(|pk| match entries.entry(pk.clone()) {
+                    Entry::Vacant(slot) => {
+                        slot.insert(WatchedPubkey { pubkey: pk.clone(), ..Default::default() });
+                        true
+                    }
+                    Entry::Occupied(_) => false,
+                })
+                .count()
+        };
+        if added > 0 {
+            self.changed.send_modify(|version| *version += 1);
+        }
+        added
+    }
+}
+
+impl Default for Watchlist {
+    fn default() -> Self {
+        Self {
+            entries: RwLock::new(BTreeMap::new()),
+            changed: watch::Sender::new(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_remove_and_freshness() {
+        let mut config = Config::from_env();
+        config.watchlist_pubkeys = vec!["A".repeat(64), "not-a-pubkey".to_string()];
+        let watchlist = Watchlist::from_config(&config);
+        let changes = watchlist.subscribe();
+        assert_eq!(watchlist.pubkeys(), vec!["a".repeat(64)]);
+
+        assert!(watchlist.add(&["xyz".to_string()]).is_err());
+        assert_eq!(watchlist.add(&["b".repeat(64), "a".repeat(64)]), Ok(1));
+        assert!(changes.has_changed().unwrap());
+
+        watchlist.saw(&"b".repeat(64), 10002, 100);
+        watchlist.saw(&"b".repeat(64), 10002, 50);
+        watchlist.saw(&"c".repeat(64), 3, 100);
+        let b = &watchlist.snapshot()[1];
+        assert_eq!(b.relay_list_at, Some(100));
+        assert_eq!(b.contact_list_at, None);
+        assert_eq!(watchlist.len(), 2);
+
+        assert_eq!(watchlist.remove(&["A".repeat(64), "c".repeat(64)]), 1);
+        assert_eq!(watchlist.len(), 1);
+    }
+}